@@ -0,0 +1,26 @@
+extern crate minerva;
+#[macro_use]
+extern crate criterion;
+
+use minerva::Minerva;
+use criterion::Criterion;
+
+fn factorial(c: &mut Criterion) {
+    let mut m = Minerva::new();
+    m.eval_str("(define (fact n) (if (< n 2) 1 (* n (fact (- n 1)))))").unwrap();
+    c.bench_function("factorial 100", move |b| b.iter(|| m.eval_str("(fact 100)").unwrap()));
+}
+
+fn fibonacci(c: &mut Criterion) {
+    let mut m = Minerva::new();
+    m.eval_str("(define (fib-iter a b n) (if (= n 0) a (fib-iter b (+ a b) (- n 1))))").unwrap();
+    c.bench_function("fib-big 500", move |b| b.iter(|| m.eval_str("(fib-iter 0 1 500)").unwrap()));
+}
+
+criterion_group! {
+    name = benches;
+    // Every instruction is followed by a collection, so each iteration is slow
+    config = Criterion::default().sample_size(10);
+    targets = factorial, fibonacci
+}
+criterion_main!(benches);
@@ -3,8 +3,9 @@ extern crate rustyline;
 extern crate string_interner;
 extern crate vm;
 
-use minerva::{ParseError, Token};
-use vm::{assemble, init_env, Environment, Operation, Register, Value, VM};
+use minerva::{init_env, Expander, Minerva, Next, ParseError, Reader, Token};
+use vm::{assemble_pooled, catch_signal, write_definition, write_header, CodeReader, ConstantPool, Environment, Features,
+         Register, Restart, Signal, Value, VM};
 
 use rustyline::{Context, Editor, Helper};
 use rustyline::completion::{Completer, FilenameCompleter, Pair};
@@ -13,62 +14,294 @@ use rustyline::error::ReadlineError;
 use rustyline::highlight::{Highlighter, MatchingBracketHighlighter};
 use rustyline::hint::{Hinter, HistoryHinter};
 use rustyline::validate::{Validator, ValidationResult, ValidationContext};
-use string_interner::{get_symbol, get_value};
+use string_interner::{get_symbol, get_value, Symbol};
 
-use std::fs;
+use std::{env, fs, io, process};
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::time::Instant;
+
+// The name errors in what is typed at the prompt are reported with
+const INPUT: &str = "<repl>";
+
+/// The state of an interactive session.
+struct Session {
+    vm: VM,
+    env: Environment,
+    /// The macros defined so far.
+    expander: Expander,
+    /// Print the IR and assembly produced for each expression.
+    trace: bool,
+    /// Print timing and allocation statistics after each expression.
+    verbose: bool,
+    /// Print the calls compiled as tail calls in each expression.
+    tail_calls: bool,
+    /// Compile expressions for coverage.
+    coverage: bool,
+    /// What the environment binds before anything is evaluated, which isn't saved with the session.
+    builtins: HashMap<Symbol, Value>,
+}
+
+impl Session {
+    fn new() -> Self {
+        let mut vm = VM::new();
+        //vm.set_debug();
+        let env = init_env();
+        vm.assign_environment(env.clone());
+        let builtins = env.get_definitions().into_iter()
+            .filter_map(|s| env.lookup_variable_value(s).map(|v| (s, v)))
+            .collect();
+        Session {
+            vm: vm,
+            env: env,
+            expander: Expander::new(),
+            trace: true,
+            verbose: false,
+            tail_calls: false,
+            coverage: false,
+            builtins: builtins,
+        }
+    }
+
+    /// Save the definitions made in this session to `path` as a compiled file, each as a `define`
+    /// of its value, which `,restore-session` evaluates to make them again. Macros aren't saved.
+    /// Returns the names of the values which can't be saved: those which belong to this machine,
+    /// such as ports, and procedures closed over local variables, which are closed over the top
+    /// level when restored.
+    fn save_session(&self, path: &str) -> io::Result<Vec<String>> {
+        let mut names: Vec<_> = self.env.get_definitions().into_iter()
+            .map(|s| (get_value(s).unwrap(), s))
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let mut forms = Vec::new();
+        let mut features = Features::NONE;
+        let mut unsaved = Vec::new();
+        for (name, s) in names {
+            let value = self.env.lookup_variable_value(s).unwrap();
+            if self.builtins.get(&s) == Some(&value) {
+                continue;
+            }
+            if value.is_lambda() && !self.is_top_level(value) {
+                unsaved.push(name);
+                continue;
+            }
+            match write_definition(&mut forms, s, value) {
+                Ok(()) => features = features | Features::of(&[], &[value]),
+                Err(_) => unsaved.push(name),
+            }
+        }
+        let mut out = Vec::new();
+        write_header(&mut out, features);
+        out.extend(forms);
+        fs::write(path, out)?;
+        Ok(unsaved)
+    }
+
+    /// Whether `procedure` was made at the top level, in a frame of its own with nothing bound in it.
+    fn is_top_level(&self, procedure: Value) -> bool {
+        let lambda = procedure.to_lambda();
+        let top_level = lambda.env.local_bindings().is_empty()
+            && lambda.env.parent().map_or(false, |parent| parent.ptr_eq(&self.env));
+        Box::into_raw(lambda);
+        top_level
+    }
+
+    /// Evaluate a session saved by `,save-session`, defining what it holds in this one.
+    fn restore_session(&mut self, path: &str) -> io::Result<()> {
+        let invalid = |e: vm::LoadError| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+        let bytes = fs::read(path)?;
+        // Each form is read just before it runs, so that its constants are rooted by the machine
+        for code in CodeReader::new(&bytes).map_err(invalid)? {
+            let (code, consts) = code.map_err(invalid)?;
+            self.vm.load_code(code, consts);
+            self.vm.run();
+            for warning in self.vm.take_warnings() {
+                println!("WARNING: {}", warning);
+            }
+            if self.vm.condition().is_some() {
+                print_restarts(&self.vm, Some(path.to_string()));
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The file REPL history is kept in between sessions.
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".minerva_history"))
+}
+
+// The number of inputs kept in the history
+const HISTORY_SIZE: usize = 1000;
+
+/// The key bindings to edit input with: Emacs style, as readline has by default, unless
+/// `$MINERVA_EDIT_MODE` is `vi`.
+fn edit_mode() -> EditMode {
+    match env::var("MINERVA_EDIT_MODE") {
+        Ok(mode) if mode.eq_ignore_ascii_case("vi") => EditMode::Vi,
+        _ => EditMode::Emacs,
+    }
+}
+
+/// Evaluate the forms read from standard input when it isn't a terminal, as in `minerva <
+/// script.ss`. Forms may span lines; input which ends inside one is an error. Returns the exit
+/// status: 1 if any form failed, and 0 otherwise.
+fn run_piped(session: &mut Session) -> i32 {
+    let stdin = io::stdin();
+    let mut reader = Reader::new("<stdin>", stdin.lock());
+    let mut status = 0;
+    loop {
+        match reader.read() {
+            Ok(Next::Form(form)) => if !run(session, false, "<stdin>", form.text) {
+                status = 1;
+            },
+            Ok(Next::Incomplete(location)) => {
+                println!("ERROR: <stdin>:{}: {}", location, ParseError::EOF);
+                return 1;
+            }
+            Ok(Next::Eof) => return status,
+            Err(e) => {
+                println!("ERROR: {}", e);
+                status = 1;
+            }
+        }
+    }
+}
+
+/// Run the script `args[0]`, and then its `main` procedure with `args` if it defines one, and then
+/// the timers it scheduled. Returns the exit status: the value of `main` if it is a fixnum, 1 if
+/// it is `#f` or the script failed, and 0 otherwise.
+fn run_script(args: Vec<String>) -> i32 {
+    let input = match fs::read_to_string(&args[0]) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("ERROR: Could not load {}: {}", args[0], e);
+            return 1;
+        }
+    };
+    let mut m = Minerva::new();
+    // The script may read its arguments before `main` is called
+    m.vm().set_command_line(args.clone());
+    exit_status(m.eval_source(&args[0], &input).and_then(|_| m.run_main(args)).and_then(|v| m.run_timers().map(|_| v)))
+}
+
+/// Run the compiled file `args[0]` as `run_script` runs a script.
+fn run_compiled(args: Vec<String>) -> i32 {
+    let bytes = match fs::read(&args[0]) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("ERROR: Could not load {}: {}", args[0], e);
+            return 1;
+        }
+    };
+    let mut m = Minerva::new();
+    m.vm().set_command_line(args.clone());
+    exit_status(m.eval_compiled(&bytes).and_then(|_| m.run_main(args)).and_then(|v| m.run_timers().map(|_| v)))
+}
+
+fn exit_status(result: Result<Option<Value>, minerva::Error>) -> i32 {
+    match result {
+        Ok(Some(v)) if v.is_integer() => v.to_integer(),
+        Ok(Some(v)) if v.is_false() => 1,
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            1
+        }
+    }
+}
+
+/// Compile the script named by `args`, `FILE [-o OUTPUT]`, to a file which `minerva run` runs. The
+/// output is written next to the script with an `.mvc` extension by default.
+fn compile_script(args: &[String]) -> i32 {
+    let (path, output) = match args {
+        [path] => (path, PathBuf::from(path).with_extension("mvc")),
+        [path, o, output] | [o, output, path] if o == "-o" => (path, PathBuf::from(output)),
+        _ => {
+            eprintln!("usage: minerva compile FILE [-o OUTPUT]");
+            return 1;
+        }
+    };
+    let input = match fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("ERROR: Could not load {}: {}", path, e);
+            return 1;
+        }
+    };
+    let bytes = match Minerva::new().compile_source(path, &input) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return 1;
+        }
+    };
+    if let Err(e) = fs::write(&output, bytes) {
+        eprintln!("ERROR: Could not write {}: {}", output.display(), e);
+        return 1;
+    }
+    0
+}
 
 fn main() {
-    let mut vm = VM::new();
-    //vm.set_debug();
-    let env = init_env();
-    vm.assign_environment(env.clone());
+    // `minerva script.ss arg ...` runs a script instead of starting the REPL, `minerva compile
+    // script.ss` compiles one, and `minerva run script.mvc arg ...` runs the result. Input piped
+    // to `minerva` is evaluated without prompting
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Ctrl-C interrupts the running code with a condition instead of killing the process
+    catch_signal(Signal::Interrupt);
+    match args.first().map(String::as_str) {
+        Some("compile") => process::exit(compile_script(&args[1..])),
+        Some("run") if args.len() > 1 => process::exit(run_compiled(args[1..].to_vec())),
+        Some(_) => process::exit(run_script(args)),
+        None => (),
+    }
+
+    let mut session = Session::new();
+    if !io::stdin().is_terminal() {
+        session.trace = false;
+        process::exit(run_piped(&mut session));
+    }
     let repl = Repl {
-        env: env.clone(),
-        keywords: vec!["define".into(), "if".into(), "lambda".into(), "begin".into()],
+        env: session.env.clone(),
+        keywords: vec!["define".into(), "if".into(), "lambda".into(), "begin".into(), "case".into()],
         path: FilenameCompleter::new(),
         m: MatchingBracketHighlighter::new(),
     };
 
-    if let Ok(input) = fs::read_to_string("~/.config/minerva/init.ss") {
-        run(&mut vm, None, input);
+    let init = "~/.config/minerva/init.ss";
+    if let Ok(input) = fs::read_to_string(init) {
+        run(&mut session, false, init, input);
     }
 
     let config = config::Builder::new()
         .completion_type(CompletionType::List)
-        .edit_mode(EditMode::Vi)
+        .edit_mode(edit_mode())
         .auto_add_history(true)
+        .max_history_size(HISTORY_SIZE)
+        .history_ignore_dups(true)
+        .history_ignore_space(true)
         .build();
     let mut rl: Editor<Repl> = Editor::with_config(config);
     rl.set_helper(Some(repl));
+    if let Some(ref path) = history_path() {
+        let _ = rl.load_history(path);
+    }
 
     let mut ctrlc = false;
     loop {
-        let s = get_symbol("$PROMPT".into());
-        let prompt = if let Some(v) = env.lookup_variable_value(s) {
-            vm.assign_register(Register(0), v);
-            vm.load_code(vec![Operation::Call(Register(0))], vec![]);
-            vm.run();
-            let p = vm.load_register(Register(0));
-            if p.is_string() {
-                let v = p.to_string();
-                let s = v.str.clone();
-                Box::into_raw(v);
-                s
-            } else {
-                println!("ERROR: Expected $PROMPT to produce a string!");
-                ">> ".to_string()
-            }
-        } else {
-            ">> ".to_string()
-        };
-
+        let prompt = prompt(&mut session);
         let input = match rl.readline(&prompt) {
             Ok(i) => i,
             Err(e) => match e {
-                ReadlineError::Eof => return,
+                ReadlineError::Eof => break,
                 ReadlineError::Interrupted => if ctrlc {
-                    return;
+                    break;
                 } else {
                     ctrlc = true;
                     continue;
@@ -78,67 +311,315 @@ fn main() {
         };
         ctrlc = false;
 
-        if "exit\n" == input {
+        if "exit" == input.trim() {
             break;
         }
 
-        run(&mut vm, Some(&env), input);
+        if session.vm.condition_depth() > 0 {
+            if let Ok(i) = input.trim().parse::<usize>() {
+                choose_restart(&mut session, &mut rl, i);
+                continue;
+            }
+        }
+
+        if input.trim_start().starts_with(',') {
+            command(&mut session, &mut rl, input.trim());
+            continue;
+        }
+
+        run(&mut session, true, INPUT, input);
+    }
+
+    if let Some(ref path) = history_path() {
+        if let Err(e) = rl.save_history(path) {
+            println!("ERROR: Could not save history: {}", e);
+        }
     }
 }
 
-fn run(vm: &mut VM, env: Option<&Environment>, input: String) {
-    let tokens = match minerva::Tokenizer::tokenize(&input) {
-        Ok(t) => t,
+/// The prompt to read the next input with. `$PROMPT` customizes it: a string is used as it is,
+/// and a procedure is called with no arguments to produce one, so it may e.g. include the
+/// current directory or be colored with `colorize`. The prompt is prefixed with the number of
+/// suspended computations while there are any.
+fn prompt(session: &mut Session) -> String {
+    let prompt = match session.env.lookup_variable_value(get_symbol("$PROMPT".into())) {
+        Some(v) if v.is_procedure() => session.vm.apply(v, &[]),
+        Some(v) => Ok(v),
+        None => Ok(Value::String(">> ".to_string())),
+    };
+    let prompt = match prompt {
+        Ok(p) if p.is_string() => {
+            let v = p.to_string();
+            let s = v.str.clone();
+            Box::into_raw(v);
+            s
+        }
+        Ok(p) => {
+            println!("ERROR: Expected $PROMPT to produce a string, got {}", p);
+            ">> ".to_string()
+        }
         Err(e) => {
-            println!("ERROR: {}", e);
+            println!("ERROR: Exception in {}", e);
+            ">> ".to_string()
+        }
+    };
+
+    match session.vm.condition_depth() {
+        0 => prompt,
+        depth if session.vm.terminal_colors() => format!("\x1b[31m[{}]\x1b[0m {}", depth, prompt),
+        depth => format!("[{}] {}", depth, prompt),
+    }
+}
+
+/// Dispatch a REPL meta-command such as `,env` or `,load file.ss`.
+fn command(session: &mut Session, rl: &mut Editor<Repl>, line: &str) {
+    let line = &line[1..];
+    let (name, arg) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    };
+
+    match name {
+        "env" => {
+            let mut defs: Vec<_> = session.env.get_definitions().into_iter()
+                .map(|s| (get_value(s).unwrap(), s))
+                .filter(|(name, _)| name.starts_with(arg))
+                .collect();
+            defs.sort();
+            defs.dedup();
+            for (name, s) in defs {
+                let v = session.env.lookup_variable_value(s).unwrap();
+                println!("{} = {}", name, v);
+            }
+        }
+        "time" => {
+            session.vm.set_gc_timing(true);
+            let start = Instant::now();
+            let stats = session.vm.stats();
+            run(session, true, INPUT, arg.to_string());
+            println!("; {:?} elapsed, {}", start.elapsed(), session.vm.stats() - stats);
+            session.vm.set_gc_timing(session.verbose);
+        }
+        "verbose" => {
+            session.verbose = !session.verbose;
+            session.vm.set_gc_timing(session.verbose);
+            println!("; verbose mode {}", if session.verbose { "on" } else { "off" });
+        }
+        "trace" => {
+            session.trace = !session.trace;
+            println!("; compiler trace {}", if session.trace { "on" } else { "off" });
+        }
+        "tail-calls" => {
+            session.tail_calls = !session.tail_calls;
+            println!("; tail call report {}", if session.tail_calls { "on" } else { "off" });
+        }
+        "coverage" => {
+            session.coverage = !session.coverage;
+            println!("; coverage {}", if session.coverage { "on, see (coverage-report)" } else { "off" });
+        }
+        "load" => {
+            let path = arg.trim_matches('"');
+            match fs::read_to_string(path) {
+                Ok(input) => {
+                    run(session, false, path, input);
+                }
+                Err(e) => println!("ERROR: Could not load {}: {}", path, e),
+            }
+        }
+        "save-session" => {
+            let path = arg.trim_matches('"');
+            match session.save_session(path) {
+                Ok(unsaved) => for name in unsaved {
+                    println!("WARNING: {} was not saved, its value can't be written", name);
+                },
+                Err(e) => println!("ERROR: Could not save session to {}: {}", path, e),
+            }
+        }
+        "restore-session" => {
+            let path = arg.trim_matches('"');
+            if let Err(e) = session.restore_session(path) {
+                println!("ERROR: Could not restore session from {}: {}", path, e);
+            }
+        }
+        "reset" => {
+            let trace = session.trace;
+            *session = Session::new();
+            session.trace = trace;
+            if let Some(helper) = rl.helper_mut() {
+                helper.env = session.env.clone();
+            }
+            println!("; environment reset");
+        }
+        "help" | "" => {
+            println!(",env [prefix]   List the current bindings");
+            println!(",time <expr>    Evaluate an expression and report how long it took");
+            println!(",trace          Toggle printing the IR and assembly of each expression");
+            println!(",verbose        Toggle printing statistics after each expression");
+            println!(",tail-calls     Toggle reporting the calls compiled as tail calls");
+            println!(",coverage       Toggle compiling expressions for coverage");
+            println!(",load <file>    Evaluate the contents of a file");
+            println!(",reset          Discard all definitions and start over");
+            println!(",save-session <file>     Save the definitions made so far");
+            println!(",restore-session <file>  Define what a saved session holds");
+            println!(",help           Show this message");
+        }
+        _ => println!("ERROR: Unknown command ,{}. Try ,help", name),
+    }
+}
+
+fn print_restarts(vm: &VM, at: Option<String>) {
+    if let Some(condition) = vm.condition() {
+        match at {
+            Some(at) => println!("ERROR: {}: {}", at, condition),
+            None => println!("ERROR: {}", condition),
+        }
+        println!("Restarts:");
+        for (i, restart) in condition.restarts().iter().enumerate() {
+            println!("  {}: {}", i, restart);
+        }
+    }
+}
+
+fn choose_restart(session: &mut Session, rl: &mut Editor<Repl>, i: usize) {
+    let restart = match session.vm.condition().and_then(|c| c.restarts().get(i).copied()) {
+        Some(r) => r,
+        None => {
+            println!("ERROR: No restart numbered {}", i);
+            return;
+        }
+    };
+
+    let restart = if let Restart::UseValue(_) = restart {
+        let input = match rl.readline("Value to use: ") {
+            Ok(i) => i,
+            Err(_) => return,
+        };
+        if !run(session, false, INPUT, input) {
             return;
         }
+        Restart::UseValue(session.vm.load_register(Register(0)))
+    } else {
+        restart
     };
 
-    let ast: Vec<minerva::Ast> = match minerva::Parser::parse(tokens) {
-        Ok(o) => o,
+    session.vm.invoke_restart(restart);
+    if restart == Restart::Abort {
+        return;
+    }
+    session.vm.run();
+    finish(session, true, None);
+}
+
+/// Evaluate every expression in `input`, read from `name`. If `record` is set results are saved to
+/// `$1`-`$9`. Returns `false` if the input could not be parsed or signalled a condition.
+fn run(session: &mut Session, record: bool, name: &str, input: String) -> bool {
+    // Included files are read in place of their includes
+    let forms = match minerva::read_forms(name, &input) {
+        Ok(forms) => forms,
         Err(e) => {
             println!("ERROR: {}", e);
-            return;
+            return false;
         }
     };
 
-    for mut ast in ast {
+    let mut ast = Vec::new();
+    for (name, tokens, location) in forms {
+        let form = session.expander.expand(tokens).and_then(minerva::Parser::parse);
+        match form {
+            Ok(form) => ast.extend(form.into_iter().map(|form| (form, format!("{}:{}", name, location)))),
+            Err(e) => {
+                println!("ERROR: {}:{}: {}", name, location, e);
+                return false;
+            }
+        }
+    }
+
+    // A `#!no-tail-call` directive lasts until the end of the input
+    let mut options = minerva::Options::default();
+    let mut forms = Vec::new();
+    // Every form is assembled before any runs, so they can share constants
+    let mut pool = ConstantPool::new();
+    for (mut ast, at) in ast {
+        if let minerva::Ast::Directive(_) = ast {
+            options.tail_calls = false;
+            continue;
+        }
         threading(&mut ast);
+        let ast = if session.coverage { minerva::instrument(ast, &mut session.vm) } else { ast };
         let ir = minerva::compile(ast);
-        let ir = minerva::optimize(ir);
-        println!("IR:");
-        for i in &ir {
-            println!("{}", i);
+        let ir = minerva::optimize_with(ir, options);
+        if session.tail_calls {
+            for call in minerva::tail_calls(&ir) {
+                println!("; {}", call);
+            }
+        }
+        if session.trace {
+            println!("IR:");
+            for i in &ir {
+                println!("{}", i);
+            }
+            println!();
         }
-        println!();
 
-        println!("ASM:");
         let asm = minerva::output_asm(ir);
-        for i in &asm {
-            println!("{}", i);
-        }
-        println!();
-
-        println!("RESULT:");
-        let (code, consts) = assemble(asm);
-        vm.load_code(code, consts);
-        vm.run();
-        let result = vm.load_register(Register(0));
-        if !result.is_void() {
-            println!("{}", result);
-            if let Some(env) = env {
-                swap_cash_vars(env, result);
+        if session.trace {
+            println!("ASM:");
+            for i in &asm {
+                println!("{}", i);
             }
+            println!();
+        }
+        forms.push((assemble_pooled(asm, &mut pool), at));
+    }
+
+    // The values read from `input` would be collected while the forms before them run
+    session.vm.retain(forms.iter().flat_map(|((_, consts), _)| consts.iter().copied()).collect());
+    for ((code, consts), at) in forms {
+        if session.trace {
+            println!("RESULT:");
+        }
+        let start = Instant::now();
+        let stats = session.vm.stats();
+        session.vm.load_code(code, consts);
+        session.vm.run();
+        if session.verbose {
+            println!("; {:?} elapsed, {}", start.elapsed(), session.vm.stats() - stats);
+        }
+        if !finish(session, record, Some(at)) {
+            session.vm.retain(vec![]);
+            return false;
+        }
+    }
+    session.vm.retain(vec![]);
+    true
+}
+
+/// Report the outcome of running code, from the form `at` if it is known. Returns `false` if a
+/// condition suspended the computation.
+fn finish(session: &mut Session, record: bool, at: Option<String>) -> bool {
+    for warning in session.vm.take_warnings() {
+        println!("WARNING: {}", warning);
+    }
+    if session.vm.condition().is_some() {
+        print_restarts(&session.vm, at);
+        return false;
+    }
+
+    let result = session.vm.load_register(Register(0));
+    if !result.is_void() {
+        println!("{}", result);
+        if record {
+            swap_cash_vars(&session.env, result);
         }
     }
+    true
 }
 
 fn threading(ast: &mut minerva::Ast) {
     use minerva::Ast::*;
 
     match ast {
-        Define { value, .. } => threading(&mut *value),
+        Define { value, .. } | Set { value, .. } => threading(&mut *value),
         Lambda { body, .. } => for a in body {
             threading(a);
         },
@@ -147,6 +628,18 @@ fn threading(ast: &mut minerva::Ast) {
             threading(&mut *consequent);
             threading(&mut *alternative);
         }
+        Case { key, clauses, default } => {
+            threading(&mut *key);
+            for a in clauses.iter_mut().flat_map(|(_, body)| body).chain(default) {
+                threading(a);
+            }
+        }
+        Destructure { value, body, .. } => {
+            threading(&mut *value);
+            for a in body {
+                threading(a);
+            }
+        }
         Begin(v) => for a in v {
             threading(a);
         },
@@ -297,8 +790,8 @@ impl Completer for Repl {
                 Ok((p, candidates))
             },
             Err(ParseError::InString) => self.path.complete_path(line, pos),
-            // TODO
-            _ => unreachable!(),
+            // Nothing can be completed in the middle of a malformed token
+            Err(_) => Ok((pos, Vec::new())),
         }
 
     }
@@ -306,18 +799,19 @@ impl Completer for Repl {
 
 impl Validator for Repl {
     fn validate(&self, ctx: &mut ValidationContext<'_>) -> Result<ValidationResult, ReadlineError> {
-        match minerva::Tokenizer::tokenize(ctx.input()) {
-            Ok(tokens) => if tokens.is_empty() ||
-                tokens.iter().filter(|&t| t.is_left_paren()).count()
-                > tokens.iter().filter(|&t| t.is_right_paren()).count()
-            {
-                return Ok(ValidationResult::Incomplete);
-            },
-            Err(ParseError::InString) => return Ok(ValidationResult::Incomplete),
-            _ => (),
+        if ctx.input().trim().is_empty() {
+            return Ok(ValidationResult::Incomplete);
+        }
+        // Errors are reported when the input is evaluated
+        let mut reader = Reader::new(INPUT, io::empty());
+        reader.push_line(ctx.input());
+        loop {
+            match reader.read() {
+                Ok(Next::Form(_)) => (),
+                Ok(Next::Incomplete(_)) => return Ok(ValidationResult::Incomplete),
+                Ok(Next::Eof) | Err(_) => return Ok(ValidationResult::Valid(None)),
+            }
         }
-
-        Ok(ValidationResult::Valid(None))
     }
 
     fn validate_while_typing(&self) -> bool {
@@ -1,9 +1,11 @@
-use {Ast, IR};
+use {Ast, IR, Pattern};
 
 use vm::Value;
 
 use string_interner::{get_symbol, Symbol};
 
+use std::collections::{HashMap, HashSet};
+
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 fn make_label() -> Symbol {
@@ -12,7 +14,7 @@ fn make_label() -> Symbol {
     get_symbol(l)
 }
 
-fn gen_var() -> Symbol {
+pub(crate) fn gen_var() -> Symbol {
     static VAR: AtomicUsize = AtomicUsize::new(0);
     let l = format!("x{}", VAR.fetch_add(1, Ordering::SeqCst));
     get_symbol(l)
@@ -28,16 +30,106 @@ pub fn compile(exp: Ast) -> Vec<IR> {
 
 struct Compiler;
 
+/// Collect the names assigned by `set!` in `exp`, leaving out those of nested procedures' own
+/// formals.
+fn assigned(exp: &Ast, names: &mut HashSet<Symbol>) {
+    match exp {
+        Ast::Set { name, value } => {
+            names.insert(*name);
+            assigned(value, names);
+        }
+        Ast::Define { value, .. } => assigned(value, names),
+        Ast::Lambda { args, body } => {
+            let mut inner = HashSet::new();
+            for exp in body {
+                assigned(exp, &mut inner);
+            }
+            names.extend(inner.into_iter().filter(|name| !args.contains(name)));
+        }
+        Ast::If { predicate, consequent, alternative } => {
+            assigned(predicate, names);
+            assigned(consequent, names);
+            assigned(alternative, names);
+        }
+        Ast::Case { key, clauses, default } => {
+            assigned(key, names);
+            for exp in clauses.iter().flat_map(|(_, body)| body).chain(default) {
+                assigned(exp, names);
+            }
+        }
+        Ast::Destructure { value, body, .. } => {
+            assigned(value, names);
+            for exp in body {
+                assigned(exp, names);
+            }
+        }
+        Ast::Begin(exps) | Ast::Apply(exps) => for exp in exps {
+            assigned(exp, names);
+        },
+        Ast::Ident(_) | Ast::Primitive(_) | Ast::Directive(_) => (),
+    }
+}
+
+/// Collect the names referred to from inside procedures made in `exp`, leaving out those of the
+/// procedures' own formals. `inside` is whether `exp` is in such a procedure already.
+fn captured(exp: &Ast, inside: bool, names: &mut HashSet<Symbol>) {
+    match exp {
+        Ast::Ident(name) => if inside {
+            names.insert(*name);
+        },
+        Ast::Set { name, value } => {
+            if inside {
+                names.insert(*name);
+            }
+            captured(value, inside, names);
+        }
+        Ast::Define { value, .. } => captured(value, inside, names),
+        Ast::Lambda { args, body } => {
+            let mut inner = HashSet::new();
+            for exp in body {
+                captured(exp, true, &mut inner);
+            }
+            names.extend(inner.into_iter().filter(|name| !args.contains(name)));
+        }
+        Ast::If { predicate, consequent, alternative } => {
+            captured(predicate, inside, names);
+            captured(consequent, inside, names);
+            captured(alternative, inside, names);
+        }
+        Ast::Case { key, clauses, default } => {
+            captured(key, inside, names);
+            for exp in clauses.iter().flat_map(|(_, body)| body).chain(default) {
+                captured(exp, inside, names);
+            }
+        }
+        Ast::Destructure { value, body, .. } => {
+            captured(value, inside, names);
+            for exp in body {
+                captured(exp, inside, names);
+            }
+        }
+        Ast::Begin(exps) | Ast::Apply(exps) => for exp in exps {
+            captured(exp, inside, names);
+        },
+        Ast::Primitive(_) | Ast::Directive(_) => (),
+    }
+}
+
 impl Compiler {
     fn _compile(&mut self, exp: Ast, target: Symbol) -> Vec<IR> {
         match exp {
             Ast::Primitive(p) => self.compile_self_evaluating(p, target),
             Ast::Ident(i) => self.compile_variable(i, target),
             Ast::Define { .. } => self.compile_define(exp, target),
+            Ast::Set { .. } => self.compile_set(exp, target),
             Ast::If { .. } => self.compile_if(exp, target),
+            Ast::Case { .. } => self.compile_case(exp, target),
+            Ast::Destructure { .. } => self.compile_destructure(exp, target),
             Ast::Begin(v) => self.compile_sequence(v, target),
             Ast::Lambda { .. } => self.compile_lambda(exp, target),
             Ast::Apply(v) => self.compile_application(v, target),
+            // Directives are followed by whoever compiles the input
+            Ast::Directive(_) => self.compile_self_evaluating(Value::Void, target),
         }
     }
 
@@ -56,6 +148,15 @@ impl Compiler {
         n
     }
 
+    fn compile_set(&mut self, exp: Ast, target: Symbol) -> Vec<IR> {
+        let (name, value) = exp.unwrap_set();
+        let v = gen_var();
+        let mut n = self._compile(value, v);
+        n.push(IR::Set(name, v));
+        n.push(IR::Primitive(target, Value::Void));
+        n
+    }
+
     fn compile_if(&mut self, exp: Ast, target: Symbol) -> Vec<IR> {
         let alt_label = make_label();
         let after_if = make_label();
@@ -102,6 +203,62 @@ impl Compiler {
         */
     }
 
+    fn compile_case(&mut self, exp: Ast, target: Symbol) -> Vec<IR> {
+        let (key, clauses, mut default) = exp.unwrap_case();
+        if default.is_empty() {
+            default.push(Ast::Primitive(Value::Void));
+        }
+
+        let key_var = gen_var();
+        let mut ir = self._compile(key, key_var);
+        if clauses.is_empty() {
+            ir.append(&mut self.compile_sequence(default, target));
+            return ir;
+        }
+
+        // Only the first clause containing a datum is taken
+        let mut table: Vec<(Value, Symbol)> = Vec::new();
+        let mut branches = Vec::new();
+        for (data, body) in clauses {
+            let label = make_label();
+            for d in data {
+                if !table.iter().any(|(k, _)| *k == d) {
+                    table.push((d, label));
+                }
+            }
+            branches.push((label, body));
+        }
+        let default_label = make_label();
+        let after_case = make_label();
+        ir.push(IR::Switch(key_var, table, default_label));
+
+        // Each clause is the consequent of a PHI whose alternative holds the remaining clauses
+        let mut unions = vec![target];
+        for _ in 1..branches.len() {
+            unions.push(gen_var());
+        }
+
+        let default_var = gen_var();
+        let mut alt = self.compile_sequence(default, default_var);
+        alt.insert(0, IR::Label(default_label));
+        alt.push(IR::Move(*unions.last().unwrap(), default_var));
+        let mut alt_var = default_var;
+
+        for ((label, body), union) in branches.into_iter().zip(unions).rev() {
+            let cons_var = gen_var();
+            let mut cons = self.compile_sequence(body, cons_var);
+            cons.push(IR::Move(union, cons_var));
+            cons.push(IR::Goto(after_case));
+
+            alt = vec![IR::Label(label), IR::Phi(union, cons_var, cons, alt_var, alt)];
+            alt_var = union;
+        }
+
+        ir.append(&mut alt);
+        ir.push(IR::Label(after_case));
+        ir
+    }
+
     fn compile_sequence(&mut self, v: Vec<Ast>, target: Symbol) -> Vec<IR> {
         let mut ir = Vec::new();
         let size = v.len();
@@ -116,12 +273,86 @@ impl Compiler {
         ir
     }
 
+    /// Take apart the value with `CAR` and `CDR` directly, and refer to the parts wherever the body
+    /// looks up the names of the pattern. Like formals, the names aren't visible to nested
+    /// procedures.
+    fn compile_destructure(&mut self, exp: Ast, target: Symbol) -> Vec<IR> {
+        fn bind(pattern: Pattern, value: Symbol, ir: &mut Vec<IR>, names: &mut HashMap<Symbol, Symbol>) {
+            match pattern {
+                Pattern::Bind(name) => {
+                    names.insert(name, value);
+                }
+                Pattern::Ignore => (),
+                Pattern::List(items, rest) => {
+                    let mut l = value;
+                    let last = items.len() - 1;
+                    for (i, item) in items.into_iter().enumerate() {
+                        if !matches!(item, Pattern::Ignore) {
+                            let car = gen_var();
+                            ir.push(IR::Car(car, l));
+                            bind(item, car, ir, names);
+                        }
+                        if i < last || rest.is_some() {
+                            let cdr = gen_var();
+                            ir.push(IR::Cdr(cdr, l));
+                            l = cdr;
+                        }
+                    }
+                    if let Some(rest) = rest {
+                        bind(*rest, l, ir, names);
+                    }
+                }
+            }
+        }
+
+        fn rename(ir: &mut [IR], names: &HashMap<Symbol, Symbol>) {
+            for i in ir.iter_mut() {
+                match i {
+                    IR::Lookup(t, ident) => if let Some(&v) = names.get(ident) {
+                        *i = IR::Copy(*t, v);
+                    },
+                    IR::Phi(_, _, cons, _, alt) => {
+                        rename(cons, names);
+                        rename(alt, names);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let (pattern, value, body) = exp.unwrap_destructure();
+        let v = gen_var();
+        let mut ir = self._compile(value, v);
+        let mut names = HashMap::new();
+        bind(pattern, v, &mut ir, &mut names);
+        let mut body = self.compile_sequence(body, target);
+        rename(&mut body, &names);
+        ir.append(&mut body);
+        ir
+    }
+
+    /// Formals are kept in registers, except those assigned by `set!` or referred to by the
+    /// procedures made in the body, which are defined in the frame of the call on entry. Those
+    /// procedures are made over the frame, so they find the formals there by name, and the body
+    /// and the procedures share one binding.
     fn compile_lambda(&mut self, exp: Ast, target: Symbol) -> Vec<IR> {
-        let (args, body) = exp.unwrap_lambda();
+        let (mut args, body) = exp.unwrap_lambda();
+        let mut in_frame = HashSet::new();
+        for exp in &body {
+            assigned(exp, &mut in_frame);
+            captured(exp, false, &mut in_frame);
+        }
+
+        let mut ir = Vec::new();
+        for arg in args.iter_mut().filter(|arg| in_frame.contains(*arg)) {
+            let register = gen_var();
+            ir.push(IR::Define(*arg, register));
+            *arg = register;
+        }
         let ret = gen_var();
-        let mut body = self.compile_sequence(body, ret);
-        body.push(IR::Return(ret));
-        vec![IR::Fn(target, args, body)]
+        ir.append(&mut self.compile_sequence(body, ret));
+        ir.push(IR::Return(ret));
+        vec![IR::Fn(target, args, ir)]
     }
 
     fn compile_application(&mut self, mut v: Vec<Ast>, target: Symbol) -> Vec<IR> {
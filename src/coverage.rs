@@ -0,0 +1,101 @@
+//! Instrumenting code for coverage.
+//!
+//! The body of every procedure and each branch of every `if` and `case` begins with a call to the
+//! machine's coverage probe, so `coverage-report` shows which of them ran. The reader does not
+//! keep source positions, so probes are described by the procedure they are in and the text of
+//! the expression rather than by line.
+
+use vm::{coverage_probe, Value, VM};
+
+use Ast;
+
+use string_interner::{get_value, Symbol};
+
+// The longest expression text included in the description of a probe
+const TEXT_WIDTH: usize = 40;
+
+/// Add probes to `ast` and their descriptions to `vm`.
+pub fn instrument(ast: Ast, vm: &mut VM) -> Ast {
+    Instrument { vm, probe: coverage_probe() }.ast(ast, "top level")
+}
+
+struct Instrument<'a> {
+    vm: &'a mut VM,
+    probe: Value,
+}
+
+impl<'a> Instrument<'a> {
+    /// Instrument `ast`, which is inside the procedure named `proc`.
+    fn ast(&mut self, ast: Ast, proc: &str) -> Ast {
+        match ast {
+            Ast::Define { name, value } => {
+                let value = match *value {
+                    Ast::Lambda { args, body } => self.lambda(args, body, &get_value(name).unwrap()),
+                    value => self.ast(value, proc),
+                };
+                Ast::Define { name, value: Box::new(value) }
+            }
+            Ast::Set { name, value } => Ast::Set { name, value: Box::new(self.ast(*value, proc)) },
+            Ast::Lambda { args, body } => self.lambda(args, body, "lambda"),
+            Ast::If { predicate, consequent, alternative } => {
+                let text = format!("{}: (if {} ...)", proc, abbreviate(&predicate));
+                let predicate = Box::new(self.ast(*predicate, proc));
+                let consequent = Box::new(self.branch(*consequent, format!("{} then", text), proc));
+                let alternative = Box::new(self.branch(*alternative, format!("{} else", text), proc));
+                Ast::If { predicate, consequent, alternative }
+            }
+            Ast::Case { key, clauses, default } => {
+                let text = format!("{}: (case {} ...)", proc, abbreviate(&key));
+                let key = Box::new(self.ast(*key, proc));
+                let clauses = clauses.into_iter().map(|(values, body)| {
+                    let values_text: Vec<_> = values.iter().map(|v| format!("{}", v)).collect();
+                    let description = format!("{} ({})", text, values_text.join(" "));
+                    (values, self.body(body, description, proc))
+                }).collect();
+                let default = self.body(default, format!("{} else", text), proc);
+                Ast::Case { key, clauses, default }
+            }
+            Ast::Destructure { pattern, value, body } => Ast::Destructure {
+                pattern,
+                value: Box::new(self.ast(*value, proc)),
+                body: self.all(body, proc),
+            },
+            Ast::Begin(exps) => Ast::Begin(self.all(exps, proc)),
+            Ast::Apply(exps) => Ast::Apply(self.all(exps, proc)),
+            ast => ast,
+        }
+    }
+
+    fn all(&mut self, exps: Vec<Ast>, proc: &str) -> Vec<Ast> {
+        exps.into_iter().map(|e| self.ast(e, proc)).collect()
+    }
+
+    fn lambda(&mut self, args: Vec<Symbol>, body: Vec<Ast>, proc: &str) -> Ast {
+        let body = self.body(body, proc.to_string(), proc);
+        Ast::Lambda { args, body }
+    }
+
+    /// `body` preceded by a new probe. The last expression stays last, so it is still in tail
+    /// position.
+    fn body(&mut self, body: Vec<Ast>, description: String, proc: &str) -> Vec<Ast> {
+        let probe = self.vm.add_probe(description);
+        let hit = Ast::Apply(vec![Ast::Primitive(self.probe), Ast::Primitive(Value::Integer(probe as i32))]);
+        let mut exps = vec![hit];
+        exps.append(&mut self.all(body, proc));
+        exps
+    }
+
+    fn branch(&mut self, branch: Ast, description: String, proc: &str) -> Ast {
+        Ast::Begin(self.body(vec![branch], description, proc))
+    }
+}
+
+fn abbreviate(ast: &Ast) -> String {
+    let text = format!("{}", ast);
+    if text.chars().count() > TEXT_WIDTH {
+        let text: String = text.chars().take(TEXT_WIDTH - 3).collect();
+        format!("{}...", text)
+    } else {
+        text
+    }
+}
@@ -0,0 +1,513 @@
+//! A simple interface for hosts embedding the interpreter.
+//!
+//! Evaluation is wrapped in a `catch_unwind` boundary so that a bug in the interpreter is returned
+//! to the host as `Error::InternalPanic` instead of unwinding through it. The interpreter is then
+//! poisoned: its machine may be in any state, so every later evaluation returns `Error::Poisoned`
+//! until it is reset. Objects on the shared heap may also have been left inconsistent, which a
+//! reset can't repair, so a host which sees repeated internal errors should restart the process.
+
+use vm::{assemble, assemble_pooled, deep_copy, define_closure, write_code, write_header, Arity, CodeReader,
+         ConstantPool, Environment, Features, NativeError, Operation, Prelude, Register, Restart, Stats, Value, VM};
+
+use {compile, init_env, instrument, optimize_with, output_asm, read_forms, Ast, AstPass, Error, Expander, Location,
+     Options, Parser};
+
+use string_interner::get_symbol;
+
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+thread_local! {
+    // Whether this thread is inside `eval_str`, and the backtrace of a panic there
+    static EVALUATING: Cell<bool> = const { Cell::new(false) };
+    static BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+// A compiled top level form, with the name of its input and its location
+type Form = ((Vec<Operation>, Vec<Value>), String, Location);
+
+// An evaluation whose fuel ran out, kept for `Minerva::resume`
+struct Interrupted {
+    // Where the form which was running is
+    name: String,
+    location: Location,
+    // The forms after it
+    forms: Vec<Form>,
+    // The counters of the machine when the evaluation started
+    start: Stats,
+}
+
+/// Install a panic hook which records a backtrace for panics inside `eval_str` instead of printing
+/// them, and leaves other panics to the previous hook.
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if EVALUATING.with(|e| e.get()) {
+                let backtrace = Backtrace::force_capture().to_string();
+                BACKTRACE.with(|b| *b.borrow_mut() = Some(backtrace));
+            } else {
+                previous(info);
+            }
+        }));
+    });
+}
+
+/// The resources used by one call to `Minerva::eval_str`, for hosts which bill, throttle, or log
+/// per script.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct EvalReport {
+    /// Instructions executed.
+    pub instructions: usize,
+    /// Heap objects allocated.
+    pub allocations: usize,
+    /// Garbage collections performed.
+    pub gc_pauses: usize,
+    /// The largest the stack grew to.
+    pub peak_stack: usize,
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instructions, {} allocations, {} collections, peak stack {}",
+               self.instructions, self.allocations, self.gc_pauses, self.peak_stack)
+    }
+}
+
+/// An interpreter with its own global environment.
+pub struct Minerva {
+    vm: VM,
+    env: Environment,
+    // The macros defined so far
+    expander: Expander,
+    report: EvalReport,
+    poisoned: bool,
+    // Whether code is compiled for coverage
+    coverage: bool,
+    // The bindings restored by `reset`, or the initial environment if `None`
+    prelude: Option<Prelude>,
+    // The passes run on each form before it is compiled, in order
+    passes: Vec<Box<dyn AstPass>>,
+    interrupted: Option<Interrupted>,
+}
+
+impl Default for Minerva {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Minerva {
+    /// Create an interpreter with the initial environment.
+    pub fn new() -> Self {
+        Self::with_environment(init_env())
+    }
+
+    /// Create an interpreter which evaluates in `env`. Resetting it starts over with the initial
+    /// environment.
+    pub fn with_environment(env: Environment) -> Self {
+        let mut vm = VM::new();
+        vm.assign_environment(env.clone());
+        Minerva {
+            vm,
+            env,
+            expander: Expander::new(),
+            report: EvalReport::default(),
+            poisoned: false,
+            coverage: false,
+            prelude: None,
+            passes: Vec::new(),
+            interrupted: None,
+        }
+    }
+
+    /// Create an interpreter which evaluates in a new instance of `prelude`. Resetting it starts
+    /// over with another instance.
+    pub fn with_prelude(prelude: &Prelude) -> Self {
+        let mut minerva = Self::with_environment(prelude.instance());
+        minerva.prelude = Some(prelude.clone());
+        minerva
+    }
+
+    /// Discard every definition and the state of the machine, including after an internal error,
+    /// and start over from the prelude. A log sink installed in the machine and the passes added
+    /// are kept.
+    pub fn reset(&mut self) {
+        self.env = match self.prelude {
+            Some(ref prelude) => prelude.instance(),
+            None => init_env(),
+        };
+        let log_sink = self.vm.take_log_sink();
+        self.vm = VM::new();
+        self.vm.assign_environment(self.env.clone());
+        if let Some(sink) = log_sink {
+            self.vm.set_log_sink(sink);
+        }
+        self.expander = Expander::new();
+        self.report = EvalReport::default();
+        self.poisoned = false;
+        self.interrupted = None;
+    }
+
+    /// The global environment.
+    pub fn environment(&self) -> &Environment {
+        &self.env
+    }
+
+    /// The machine, for hosts which need more control.
+    pub fn vm(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    /// Define `name` in the global environment as a procedure which calls `f` with its arguments,
+    /// so that a host can add procedures of its own. A message `f` returns is signalled after the
+    /// name, as those of the other procedures are. `vm::string_arg`, `integer_arg` and `list_arg`
+    /// check and convert arguments, and `make_list` makes a list to return:
+    ///
+    /// ```
+    /// # extern crate minerva;
+    /// # extern crate vm;
+    /// use minerva::Minerva;
+    /// use vm::{integer_arg, list_arg, make_list, string_arg, Arity, Value};
+    ///
+    /// let mut m = Minerva::new();
+    /// m.register_fn("shout", Arity::Exactly(1), |args| {
+    ///     Ok(Value::String(string_arg(args[0])?.to_uppercase()))
+    /// }).unwrap();
+    /// m.register_fn("scale", Arity::Exactly(2), |args| {
+    ///     let factor = integer_arg(args[1])?;
+    ///     let scaled = list_arg(args[0])?.into_iter()
+    ///         .map(|v| integer_arg(v).map(|n| Value::Integer(n * factor)))
+    ///         .collect::<Result<Vec<_>, _>>()?;
+    ///     Ok(make_list(scaled))
+    /// }).unwrap();
+    /// assert_eq!("(2 4 6)", format!("{}", m.eval_str("(scale '(1 2 3) 2)").unwrap().0));
+    /// ```
+    ///
+    /// The procedure is lost when the interpreter is reset, and each call registers a new one, so
+    /// it should be defined once rather than before each evaluation.
+    pub fn register_fn<F>(&mut self, name: &str, arity: Arity, f: F) -> Result<(), NativeError>
+        where F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static
+    {
+        let prefix = name.to_string();
+        define_closure(&self.env, name, arity, move |_, args| f(args).map_err(|e| format!("{}: {}", prefix, e)))?;
+        Ok(())
+    }
+
+    /// Evaluate every expression in `input`, returning the value of the last and the resources
+    /// used. A `#!no-tail-call` directive turns off tail calls for the rest of `input`. If a
+    /// condition is signalled the computation is aborted. The value is only safe to
+    /// use until the next evaluation, unless it is bound in the environment.
+    pub fn eval_str(&mut self, input: &str) -> Result<(Value, EvalReport), Error> {
+        self.guarded(|m| m.eval("", input)).map_err(Error::without_location)
+    }
+
+    /// Evaluate `input`, the contents of the file `name`, as `eval_str` does. Errors in reading or
+    /// evaluating it are returned as `Error::At`, with the location of the top level form they
+    /// happened in, or of the token where it couldn't be read.
+    pub fn eval_source(&mut self, name: &str, input: &str) -> Result<(Value, EvalReport), Error> {
+        self.guarded(|m| m.eval(name, input))
+    }
+
+    /// Evaluate `input` as `eval_str` does, but running at most `fuel` instructions, so that a host
+    /// can run code it doesn't trust without it running forever. If the fuel runs out,
+    /// `Error::OutOfFuel` is returned and `resume` continues the evaluation with more. Evaluating
+    /// anything else first abandons it.
+    ///
+    /// ```
+    /// # extern crate minerva;
+    /// use minerva::{Error, Minerva};
+    ///
+    /// let mut m = Minerva::new();
+    /// m.eval_str("(define (count n) (if (= n 0) 'done (count (- n 1))))").unwrap();
+    /// assert_eq!(Err(Error::OutOfFuel), m.eval_with_fuel("(count 1000)", 100).map(|(v, _)| v));
+    /// let mut result = m.resume(100);
+    /// while result == Err(Error::OutOfFuel) {
+    ///     result = m.resume(100);
+    /// }
+    /// assert_eq!("done", format!("{}", result.unwrap().0));
+    /// ```
+    ///
+    /// Fuel running out in a procedure applied by a native procedure, such as one given to
+    /// `for-each`, fails like any other condition, and can't be resumed.
+    pub fn eval_with_fuel(&mut self, input: &str, fuel: usize) -> Result<(Value, EvalReport), Error> {
+        self.vm.set_fuel(Some(fuel));
+        let result = self.eval_str(input);
+        self.vm.set_fuel(None);
+        result
+    }
+
+    /// Continue the evaluation whose fuel ran out with `fuel` more instructions, as
+    /// `eval_with_fuel` would. The report covers the whole evaluation.
+    pub fn resume(&mut self, fuel: usize) -> Result<(Value, EvalReport), Error> {
+        self.vm.set_fuel(Some(fuel));
+        let result = self.guarded(|m| {
+            let Interrupted { name, location, forms, start } = m.interrupted.take().ok_or(Error::NotInterrupted)?;
+            m.vm.invoke_restart(Restart::Retry);
+            m.run_forms(start, Some((name, location)), forms)
+        });
+        self.vm.set_fuel(None);
+        result.map_err(Error::without_location)
+    }
+
+    /// Compile every expression in `input` without evaluating it, returning a compiled file which
+    /// `eval_compiled` evaluates. Macros defined in `input` are defined here too, as by `eval_str`.
+    pub fn compile_str(&mut self, input: &str) -> Result<Vec<u8>, Error> {
+        self.guarded(|m| m.write_compiled("", input)).map_err(Error::without_location)
+    }
+
+    /// Compile `input`, the contents of the file `name`, as `compile_str` does. Errors are
+    /// returned with their location as by `eval_source`.
+    pub fn compile_source(&mut self, name: &str, input: &str) -> Result<Vec<u8>, Error> {
+        self.guarded(|m| m.write_compiled(name, input))
+    }
+
+    /// Evaluate a compiled file made by `compile_str`, as `eval_str` would the source it was
+    /// compiled from. The file is checked as it is read, forms before an invalid one have already
+    /// been evaluated when the error is returned.
+    pub fn eval_compiled(&mut self, bytes: &[u8]) -> Result<(Value, EvalReport), Error> {
+        self.guarded(|m| m.run_compiled(bytes))
+    }
+
+    // Run `f`, poisoning the interpreter if it panics
+    fn guarded<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        if self.poisoned {
+            return Err(Error::Poisoned);
+        }
+        install_panic_hook();
+        EVALUATING.with(|e| e.set(true));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        EVALUATING.with(|e| e.set(false));
+        result.unwrap_or_else(|payload| {
+            self.poisoned = true;
+            let message = match payload.downcast_ref::<&str>() {
+                Some(s) => s.to_string(),
+                None => match payload.downcast_ref::<String>() {
+                    Some(s) => s.clone(),
+                    None => "unknown panic".to_string(),
+                },
+            };
+            let backtrace = BACKTRACE.with(|b| b.borrow_mut().take()).unwrap_or_default();
+            Err(Error::InternalPanic(message, backtrace))
+        })
+    }
+
+    /// Call the `main` procedure defined by the code evaluated so far with `args`, the path of the
+    /// program followed by its arguments, as a script run from the command line is. `args` is also
+    /// returned by `(command-line)`. Returns `None` if there is no `main`.
+    pub fn run_main(&mut self, args: Vec<String>) -> Result<Option<Value>, Error> {
+        if self.poisoned {
+            return Err(Error::Poisoned);
+        }
+        self.vm.set_command_line(args);
+        if self.env.lookup_variable_value(get_symbol("main".to_string())).is_none() {
+            return Ok(None);
+        }
+        self.eval_str("(main (command-line))").map(|(v, _)| Some(v))
+    }
+
+    /// Wait for the procedures scheduled with `after` and run them as they come due, as is done
+    /// once a script run from the command line has finished.
+    pub fn run_timers(&mut self) -> Result<(), Error> {
+        self.guarded(|m| m.vm.run_timers().map_err(|e| Error::Condition(format!("Exception in {}", e))))
+    }
+
+    /// Compile the code evaluated from now on for coverage, or stop doing so. `(coverage-report)`
+    /// lists which parts of it ran.
+    pub fn set_coverage(&mut self, coverage: bool) {
+        self.coverage = coverage;
+    }
+
+    /// Whether an internal error has made the interpreter unusable.
+    /// Run `pass` on each top level form evaluated or compiled from now on, after macros are
+    /// expanded and after the passes added before it.
+    pub fn add_pass(&mut self, pass: Box<dyn AstPass>) {
+        self.passes.push(pass);
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    // Read the top level forms of `input`, the contents of `name`, and of the files it includes,
+    // each with the name of its file and its location, and run the passes on them
+    fn read(&mut self, name: &str, input: &str) -> Result<Vec<(Ast, String, Location)>, Error> {
+        let mut ast = Vec::new();
+        for (name, tokens, location) in read_forms(name, input)? {
+            let at = |e| Error::At(name.clone(), location.clone(), Box::new(Error::Parse(e)));
+            let tokens = self.expander.expand(tokens).map_err(at)?;
+            let form = Parser::parse(tokens).map_err(at)?;
+            for mut form in form {
+                // Directives are for the compiler alone
+                if !matches!(form, Ast::Directive(_)) {
+                    for pass in &mut self.passes {
+                        form = pass.run(form);
+                    }
+                }
+                ast.push((form, name.clone(), location.clone()));
+            }
+        }
+        Ok(ast)
+    }
+
+    fn eval(&mut self, name: &str, input: &str) -> Result<(Value, EvalReport), Error> {
+        self.abandon();
+        let ast = self.read(name, input)?;
+
+        let mut forms = Vec::new();
+        let mut options = Options::default();
+        // Every form is assembled before any runs, so they can share constants
+        let mut pool = ConstantPool::new();
+        for (ast, name, location) in ast {
+            if let Ast::Directive(_) = ast {
+                options.tail_calls = false;
+                continue;
+            }
+            let ast = if self.coverage { instrument(ast, &mut self.vm) } else { ast };
+            let asm = output_asm(optimize_with(compile(ast), options));
+            forms.push((assemble_pooled(asm, &mut pool), name, location));
+        }
+
+        // The values read from `input` would be collected while the forms before them run
+        self.vm.retain(forms.iter().flat_map(|((_, consts), _, _)| consts.iter().copied()).collect());
+        let start = self.start_report();
+        self.run_forms(start, None, forms)
+    }
+
+    // Run each of `forms` in turn, after finishing the form at `resumed` if there is one loaded. If
+    // the fuel runs out the evaluation is kept in `interrupted`.
+    fn run_forms(&mut self, start: Stats, resumed: Option<(String, Location)>, forms: Vec<Form>)
+        -> Result<(Value, EvalReport), Error>
+    {
+        let mut forms = forms.into_iter();
+        let mut current = resumed;
+        let mut result = Ok(Value::Void);
+        loop {
+            let (name, location) = match current.take() {
+                Some(at) => at,
+                None => match forms.next() {
+                    Some(((code, consts), name, location)) => {
+                        self.vm.load_code(code, consts);
+                        (name, location)
+                    }
+                    None => break,
+                },
+            };
+            self.vm.run();
+            if self.vm.condition().map_or(false, |c| c.is_out_of_fuel()) {
+                // The constants of the forms left are still retained
+                self.interrupted = Some(Interrupted { name, location, forms: forms.collect(), start });
+                return self.finish_report(start, Err(Error::OutOfFuel));
+            }
+            result = self.outcome().map_err(|e| Error::At(name, location, Box::new(e)));
+            if result.is_err() {
+                break;
+            }
+        }
+        self.vm.retain(vec![]);
+        self.finish_report(start, result)
+    }
+
+    // Give up on an evaluation whose fuel ran out
+    fn abandon(&mut self) {
+        if self.interrupted.take().is_some() {
+            self.vm.invoke_restart(Restart::Abort);
+            self.vm.retain(vec![]);
+        }
+    }
+
+    fn write_compiled(&mut self, name: &str, input: &str) -> Result<Vec<u8>, Error> {
+        let ast = self.read(name, input)?;
+
+        let mut forms = Vec::new();
+        let mut options = Options::default();
+        for (ast, name, location) in ast {
+            if let Ast::Directive(_) = ast {
+                options.tail_calls = false;
+                continue;
+            }
+            forms.push((assemble(output_asm(optimize_with(compile(ast), options))), name, location));
+        }
+
+        // The header declares the features of every form, so they are all compiled first
+        let features = forms.iter().fold(Features::NONE, |f, ((code, consts), _, _)| f | Features::of(code, consts));
+        let mut out = Vec::new();
+        write_header(&mut out, features);
+        for ((code, consts), name, location) in forms {
+            write_code(&mut out, &code, &consts).map_err(|v| {
+                Error::At(name, location, Box::new(Error::Serialize(format!("{}", v))))
+            })?;
+        }
+        Ok(out)
+    }
+
+    fn run_compiled(&mut self, bytes: &[u8]) -> Result<(Value, EvalReport), Error> {
+        self.abandon();
+        let reader = CodeReader::new(bytes).map_err(Error::Load)?;
+
+        let start = self.start_report();
+        let mut result = Ok(Value::Void);
+        // Each form is read just before it runs, so that its constants are rooted by the machine
+        for code in reader {
+            result = code.map_err(Error::Load).and_then(|(code, consts)| self.run(code, consts));
+            if result.is_err() {
+                break;
+            }
+        }
+        self.finish_report(start, result)
+    }
+
+    // Run the code of one top level form, aborting the computation if a condition is signalled
+    fn run(&mut self, code: Vec<Operation>, consts: Vec<Value>) -> Result<Value, Error> {
+        self.vm.load_code(code, consts);
+        self.vm.run();
+        self.outcome()
+    }
+
+    // The value of the computation which ran, aborting it if it signalled a condition
+    fn outcome(&mut self) -> Result<Value, Error> {
+        if let Some(condition) = self.vm.condition() {
+            let message = format!("{}", condition);
+            self.vm.invoke_restart(Restart::Abort);
+            return Err(Error::Condition(message));
+        }
+        Ok(self.vm.load_register(Register(0)))
+    }
+
+    fn start_report(&mut self) -> Stats {
+        let start = self.vm.stats();
+        self.vm.reset_peak_stack();
+        start
+    }
+
+    fn finish_report(&mut self, start: Stats, result: Result<Value, Error>) -> Result<(Value, EvalReport), Error> {
+        let stats = self.vm.stats() - start;
+        self.report = EvalReport {
+            instructions: stats.steps,
+            allocations: stats.allocations,
+            gc_pauses: stats.collections,
+            peak_stack: self.vm.peak_stack(),
+        };
+        result.map(|v| (v, self.report))
+    }
+
+    /// Copy `value` from this interpreter for use by `other`, with no structure shared between
+    /// them. Like the result of `eval_str`, the copy should be bound in the environment of `other`
+    /// before it evaluates anything.
+    pub fn transfer(&self, value: Value, other: &mut Minerva) -> Result<Value, Error> {
+        if self.poisoned || other.poisoned {
+            return Err(Error::Poisoned);
+        }
+        deep_copy(value).map_err(|v| Error::Transfer(format!("{}", v)))
+    }
+
+    /// The resources used by the last call to `eval_str`, including one which failed.
+    pub fn last_report(&self) -> EvalReport {
+        self.report
+    }
+}
@@ -1,3 +1,7 @@
+use {Location, ParseError};
+
+use vm::LoadError;
+
 use std::fmt::{self, Display, Formatter};
 
 #[derive(Clone, Debug, PartialEq)]
@@ -8,6 +12,40 @@ pub enum Error {
     WrongArgs,
     ElseNotLast,
     UserDefined(String),
+    /// The input could not be read.
+    Parse(ParseError),
+    /// Evaluation signalled a condition, with the condition's message.
+    Condition(String),
+    /// The interpreter panicked, with the panic message and a backtrace.
+    InternalPanic(String, String),
+    /// The interpreter panicked earlier and can't be used again.
+    Poisoned,
+    /// A value which can't be copied to another interpreter.
+    Transfer(String),
+    /// A constant which can't be written to a compiled file.
+    Serialize(String),
+    /// A compiled file could not be read.
+    Load(LoadError),
+    /// Reading the input failed, with the error's message.
+    Io(String),
+    /// A file named by `include` could not be read, with why.
+    Include(String),
+    /// The fuel given to `Minerva::eval_with_fuel` ran out, `Minerva::resume` continues.
+    OutOfFuel,
+    /// There is no evaluation to resume.
+    NotInterrupted,
+    /// An error in the input named by the string, in the top level form at the location.
+    At(String, Location, Box<Error>),
+}
+
+impl Error {
+    /// The error without where it happened.
+    pub fn without_location(self) -> Error {
+        match self {
+            Error::At(_, _, e) => *e,
+            e => e,
+        }
+    }
 }
 
 impl Display for Error {
@@ -19,6 +57,18 @@ impl Display for Error {
             Error::WrongArgs => write!(f, "Incorrect number of arguments passed to procedure"),
             Error::ElseNotLast => write!(f, "Else expression not last"),
             Error::UserDefined(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Condition(e) => write!(f, "{}", e),
+            Error::InternalPanic(e, _) => write!(f, "Internal error: {}", e),
+            Error::Poisoned => write!(f, "The interpreter can't be used after an internal error"),
+            Error::Transfer(v) => write!(f, "{} can't be transferred to another interpreter", v),
+            Error::Serialize(v) => write!(f, "{} can't be written to a compiled file", v),
+            Error::Load(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Include(e) => write!(f, "{}", e),
+            Error::OutOfFuel => write!(f, "The evaluation ran out of fuel"),
+            Error::NotInterrupted => write!(f, "There is no evaluation to resume"),
+            Error::At(name, location, e) => write!(f, "{}:{}: {}", name, location, e),
         }
     }
 }
@@ -0,0 +1,927 @@
+//! Macros, defined by `define-syntax`, `let-syntax`, and `letrec-syntax` with `syntax-rules`.
+//!
+//! Macros are expanded in the tokens of the input before it is parsed, so a macro can expand into
+//! any form the parser understands, including another macro definition. Expansion is hygienic in
+//! the usual way for `syntax-rules`:
+//!
+//! - A variable bound by a template, like the `t` of `((lambda (t) (if t t e)) x)`, is renamed, so
+//!   it can't capture a variable of the same name in the code given to the macro.
+//! - A macro keyword in a template means the macro visible where the macro was defined, not one
+//!   visible where it is used.
+//! - A free variable in a template means the binding visible where the macro was defined. Where a
+//!   local variable of the same name is in scope at the use, the local variable is renamed, so it
+//!   doesn't hide the top level one.
+//!
+//! A free variable which was local where the macro was defined is still looked up by name, as is
+//! the variable assigned by `set!` in a template. Top level definitions made by a template keep
+//! their names, so a macro can define a name chosen by it.
+
+use {ParseError, Token};
+
+use string_interner::{get_symbol, get_value, Symbol};
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::iter::Peekable;
+use std::mem;
+use std::rc::Rc;
+use std::vec::IntoIter;
+
+// The most expansions nested in one another, so that a macro which always expands into a use of
+// itself is an error rather than a stack overflow
+const MAX_DEPTH: usize = 256;
+
+/// Where a datum is: in code, or quoted. Unquotes inside a quasiquote go back to code.
+#[derive(Copy, Clone, PartialEq)]
+enum Context {
+    Code,
+    Quoted,
+    /// Inside this many quasiquotes, none of them quoted
+    Quasiquoted(usize),
+}
+
+impl Context {
+    /// The context of the datum of `(prefix datum)` in this context.
+    fn enter(self, prefix: &str) -> Context {
+        match (self, prefix) {
+            (Context::Code, "quote") => Context::Quoted,
+            (Context::Code, "quasiquote") => Context::Quasiquoted(1),
+            (Context::Quasiquoted(n), "quasiquote") => Context::Quasiquoted(n + 1),
+            (Context::Quasiquoted(1), "unquote") | (Context::Quasiquoted(1), "unquote-splicing") => Context::Code,
+            (Context::Quasiquoted(n), "unquote") | (Context::Quasiquoted(n), "unquote-splicing") =>
+                Context::Quasiquoted(n - 1),
+            (context, _) => context,
+        }
+    }
+
+    /// The context of the elements of `items`, a list in this context.
+    fn enter_list(self, items: &[Syntax]) -> Context {
+        match items.first().and_then(Syntax::symbol).and_then(get_value) {
+            Some(head) => self.enter(&head),
+            None => self,
+        }
+    }
+}
+
+/// A datum of the input, with the symbols a template inserted marked by their expansion.
+#[derive(Clone, Debug)]
+enum Syntax {
+    Token(Token),
+    Inserted(Symbol, usize),
+    /// A datum after `'`, `` ` ``, `,`, or `,@`.
+    Prefixed(Token, Box<Syntax>),
+    /// The tokens of a `#` literal such as `#t` or `#u8(1 2)`, which is never expanded.
+    Literal(Vec<Token>),
+    /// A list, with the `.` of a dotted list as an element.
+    List(Vec<Syntax>),
+}
+
+impl Syntax {
+    /// The name of a symbol, whether it was in the input or inserted by a template.
+    fn symbol(&self) -> Option<Symbol> {
+        match self {
+            Syntax::Token(Token::Symbol(s)) | Syntax::Inserted(s, _) => Some(*s),
+            _ => None,
+        }
+    }
+
+    fn is(&self, name: &str) -> bool {
+        self.symbol().and_then(get_value).as_deref() == Some(name)
+    }
+
+    fn is_dot(&self) -> bool {
+        matches!(self, Syntax::Token(Token::Dot))
+    }
+}
+
+#[derive(Clone)]
+enum Binding {
+    Macro(Rc<Macro>),
+    // A local variable, which shadows any macro of the same name
+    Variable,
+}
+
+type Scope = Rc<RefCell<HashMap<Symbol, Binding>>>;
+
+struct Macro {
+    ellipsis: Symbol,
+    literals: Vec<Symbol>,
+    rules: Vec<(Vec<Syntax>, Syntax)>,
+    // The local scopes where it was defined, in which its templates are expanded
+    scopes: Vec<Scope>,
+}
+
+/// What a pattern variable matched.
+#[derive(Clone, Debug)]
+enum Match {
+    One(Syntax),
+    // The matches of a pattern followed by an ellipsis
+    Many(Vec<Match>),
+}
+
+/// Expands the macros in input, keeping the macros defined at top level for later input.
+#[derive(Default)]
+pub struct Expander {
+    macros: HashMap<Symbol, Rc<Macro>>,
+    // The scopes of the macro which made each expansion in the form being expanded
+    expansions: HashMap<usize, Vec<Scope>>,
+    // The inserted symbols which the expansions in the form being expanded bind as variables
+    bound: HashSet<(Symbol, usize)>,
+    // The local variables which hide a top level variable an expansion refers to, by the scope
+    // binding each, which are renamed once their scope is expanded
+    hiding: Vec<(Scope, Symbol)>,
+    next_expansion: usize,
+}
+
+impl Expander {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expand every macro use in `tokens`, and define the macros they define. Input which can
+    /// neither define nor use a macro is returned as it is.
+    pub fn expand(&mut self, tokens: Vec<Token>) -> Result<Vec<Token>, ParseError> {
+        if self.macros.is_empty() && !tokens.iter().any(defines_syntax) {
+            return Ok(tokens);
+        }
+
+        let mut tokens = tokens.into_iter().peekable();
+        let mut expanded = Vec::new();
+        while let Some(form) = read(&mut tokens)? {
+            self.expansions.clear();
+            self.bound.clear();
+            self.hiding.clear();
+            if let Some(form) = self.expand_form(form, &[], 0)? {
+                let mut binders = HashSet::new();
+                binders_of(&form, true, &mut binders);
+                write(&form, &binders, Context::Code, &mut expanded);
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Expand `form` in the local `scopes`. A macro definition expands into nothing.
+    fn expand_form(&mut self, form: Syntax, scopes: &[Scope], depth: usize) -> Result<Option<Syntax>, ParseError> {
+        let mut items = match form {
+            Syntax::List(items) => items,
+            Syntax::Prefixed(Token::Quasiquote, _) =>
+                return self.expand_quoted(form, Context::Code, scopes, depth).map(Some),
+            Syntax::Inserted(s, expansion) => {
+                self.resolve(s, expansion, scopes);
+                return Ok(Some(form));
+            }
+            form => return Ok(Some(form)),
+        };
+        let head = match items.first() {
+            Some(head) if head.symbol().is_some() => head.clone(),
+            _ => return self.expand_all(items, scopes, depth).map(|items| Some(Syntax::List(items))),
+        };
+        match self.lookup(&head, scopes) {
+            Some(Binding::Macro(m)) => {
+                if depth == MAX_DEPTH {
+                    return Err(ParseError::MacroDepth);
+                }
+                let expansion = self.transcribe(&m, &items)?;
+                binders_of(&expansion, false, &mut self.bound);
+                return self.expand_form(expansion, scopes, depth + 1);
+            }
+            Some(Binding::Variable) => {
+                return self.expand_all(items, scopes, depth).map(|items| Some(Syntax::List(items)));
+            }
+            None => (),
+        }
+
+        let name = get_value(head.symbol().unwrap()).unwrap();
+        match name.as_str() {
+            // The parts of library forms are quoted, and a library has macros of its own
+            "quote" | "define-library" | "import" => (),
+            "quasiquote" => {
+                return self.expand_quoted(Syntax::List(items), Context::Code, scopes, depth).map(Some);
+            }
+            "define-syntax" => {
+                self.define_syntax(&items, scopes)?;
+                return Ok(None);
+            }
+            "let-syntax" | "letrec-syntax" => {
+                return self.let_syntax(items, scopes, depth, name == "letrec-syntax").map(Some);
+            }
+            // The keyword, and the variable assigned by `set!`, are not expressions
+            "if" | "begin" | "set!" => {
+                let keep = if name == "set!" { 2 } else { 1 };
+                let rest = items.split_off(keep.min(items.len()));
+                items.extend(self.expand_all(rest, scopes, depth)?);
+            }
+            "lambda" if items.len() > 2 => {
+                let body = items.split_off(2);
+                let body = self.expand_body(body, vec![&mut items[1]], scopes, depth)?;
+                items.extend(body);
+            }
+            "define" | "define-memoized" => {
+                // Memoized definitions may give a cache size before the signature
+                let signature = items.iter().skip(1).position(|i| !matches!(i, Syntax::Token(Token::Integer(_))));
+                let signature = match signature {
+                    Some(i) => i + 1,
+                    None => return Err(ParseError::Expected("a name or signature to define")),
+                };
+                let body = items.split_off(signature + 1);
+                let (name, binders) = match items[signature] {
+                    Syntax::List(ref mut signature) if !signature.is_empty() => {
+                        let (name, formals) = signature.split_at_mut(1);
+                        (name[0].symbol(), formals.iter_mut().collect())
+                    }
+                    ref name => (name.symbol(), vec![]),
+                };
+                // A definition replaces a macro of the same name
+                if let (Some(name), true) = (name, scopes.is_empty()) {
+                    self.macros.remove(&name);
+                }
+                let body = self.expand_body(body, binders, scopes, depth)?;
+                items.extend(body);
+            }
+            "destructuring-bind" if items.len() > 3 => {
+                let body = items.split_off(3);
+                let value = mem::replace(&mut items[2], Syntax::List(vec![]));
+                items[2] = self.expand_one(value, scopes, depth)?;
+                let body = self.expand_body(body, vec![&mut items[1]], scopes, depth)?;
+                items.extend(body);
+            }
+            "for/list" | "for/hash" | "for/fold" => {
+                let clauses = if name == "for/fold" { 2 } else { 1 };
+                if items.len() <= clauses + 1 {
+                    return Err(ParseError::Expected("an expression in the body"));
+                }
+                let body = items.split_off(clauses + 1);
+                let mut binders = Vec::new();
+                // Each clause is `([binding init])`
+                for clause in &mut items[1..] {
+                    if let Syntax::List(ref mut clause) = clause {
+                        if let Some(Syntax::List(ref mut clause)) = clause.first_mut() {
+                            if clause.len() == 2 {
+                                let init = clause.pop().unwrap();
+                                clause.push(self.expand_one(init, scopes, depth)?);
+                                binders.push(&mut clause[0]);
+                            }
+                        }
+                    }
+                }
+                let body = self.expand_body(body, binders, scopes, depth)?;
+                items.extend(body);
+            }
+            "case" if items.len() > 1 => {
+                let key = mem::replace(&mut items[1], Syntax::List(vec![]));
+                items[1] = self.expand_one(key, scopes, depth)?;
+                // The data of the clauses are not expressions
+                for clause in &mut items[2..] {
+                    if let Syntax::List(ref mut clause) = clause {
+                        if !clause.is_empty() {
+                            let body = clause.split_off(1);
+                            clause.extend(self.expand_all(body, scopes, depth)?);
+                        }
+                    }
+                }
+            }
+            _ => items = self.expand_all(items, scopes, depth)?,
+        }
+        Ok(Some(Syntax::List(items)))
+    }
+
+    fn expand_all(&mut self, forms: Vec<Syntax>, scopes: &[Scope], depth: usize) -> Result<Vec<Syntax>, ParseError> {
+        let mut expanded = Vec::with_capacity(forms.len());
+        for form in forms {
+            expanded.extend(self.expand_form(form, scopes, depth)?);
+        }
+        Ok(expanded)
+    }
+
+    /// Expand the unquoted expressions in `form`, a datum in `context`.
+    fn expand_quoted(&mut self, form: Syntax, context: Context, scopes: &[Scope], depth: usize)
+        -> Result<Syntax, ParseError>
+    {
+        match form {
+            Syntax::Prefixed(token, datum) => {
+                let datum = match context.enter(token.prefix_name().unwrap()) {
+                    Context::Code => self.expand_one(*datum, scopes, depth)?,
+                    inner => self.expand_quoted(*datum, inner, scopes, depth)?,
+                };
+                Ok(Syntax::Prefixed(token, Box::new(datum)))
+            }
+            Syntax::List(mut items) => match context.enter_list(&items) {
+                // `(unquote expression)`
+                Context::Code if context != Context::Code => {
+                    let expressions = items.split_off(1);
+                    items.extend(self.expand_all(expressions, scopes, depth)?);
+                    Ok(Syntax::List(items))
+                }
+                inner => items.into_iter()
+                    .map(|item| self.expand_quoted(item, inner, scopes, depth))
+                    .collect::<Result<_, _>>()
+                    .map(Syntax::List),
+            },
+            form => Ok(form),
+        }
+    }
+
+    // Expand a form which must be an expression
+    fn expand_one(&mut self, form: Syntax, scopes: &[Scope], depth: usize) -> Result<Syntax, ParseError> {
+        self.expand_form(form, scopes, depth)?.ok_or(ParseError::IllegalUse)
+    }
+
+    // Expand `forms` with the variables of `binders` bound, renaming those which hide a top level
+    // variable that a template refers to
+    fn expand_body(&mut self, forms: Vec<Syntax>, mut binders: Vec<&mut Syntax>, scopes: &[Scope], depth: usize)
+        -> Result<Vec<Syntax>, ParseError>
+    {
+        let names = binders.iter().flat_map(|binder| symbols(binder));
+        let scope: Scope = Rc::new(RefCell::new(names.map(|name| (name, Binding::Variable)).collect()));
+        let mut scopes = scopes.to_vec();
+        scopes.push(scope.clone());
+        let mut forms = self.expand_all(forms, &scopes, depth)?;
+
+        let (hiding, others) = mem::take(&mut self.hiding).into_iter().partition(|(s, _)| Rc::ptr_eq(s, &scope));
+        self.hiding = others;
+        for (_, name) in hiding {
+            let renamed = get_symbol(format!(" {}.{}", get_value(name).unwrap(), self.next_expansion));
+            self.next_expansion += 1;
+            // A variable bound by a template is renamed when it is written anyway
+            let mut found = false;
+            for binder in &mut binders {
+                found |= rename_binder(binder, name, renamed);
+            }
+            if found {
+                for form in &mut forms {
+                    self.rename(form, name, renamed, &scope, Context::Code);
+                }
+            }
+        }
+        Ok(forms)
+    }
+
+    /// The binding of the symbol `name`, looked up in the scopes of the macro which inserted it if
+    /// one did, and otherwise in `scopes`.
+    fn lookup(&self, name: &Syntax, scopes: &[Scope]) -> Option<Binding> {
+        let (symbol, scopes) = match name {
+            Syntax::Inserted(s, expansion) => (*s, self.expansions.get(expansion).map_or(&[][..], Vec::as_slice)),
+            _ => (name.symbol()?, scopes),
+        };
+        scopes.iter().rev()
+            .find_map(|scope| scope.borrow().get(&symbol).cloned())
+            .or_else(|| self.macros.get(&symbol).cloned().map(Binding::Macro))
+    }
+
+    /// Make the variable `s`, inserted by `expansion`, refer to the binding visible where the macro
+    /// was defined. Only a top level binding can be hidden here, by the local variables of the same
+    /// name in `scopes`, which are renamed.
+    fn resolve(&mut self, s: Symbol, expansion: usize, scopes: &[Scope]) {
+        if self.bound.contains(&(s, expansion)) || self.lookup(&Syntax::Inserted(s, expansion), scopes).is_some() {
+            return;
+        }
+        for scope in scopes {
+            let hides = matches!(scope.borrow().get(&s), Some(Binding::Variable));
+            if hides && !self.hiding.iter().any(|(h, name)| *name == s && Rc::ptr_eq(h, scope)) {
+                self.hiding.push((scope.clone(), s));
+            }
+        }
+    }
+
+    /// Rename the variable `from`, bound by `scope`, to `to` in `form`, a datum in `context`. The
+    /// symbols inserted by a macro defined where `scope` is visible may refer to it as well.
+    fn rename(&self, form: &mut Syntax, from: Symbol, to: Symbol, scope: &Scope, context: Context) {
+        match form {
+            Syntax::Token(Token::Symbol(s)) if *s == from && context == Context::Code => *s = to,
+            Syntax::Inserted(s, expansion) if *s == from && context == Context::Code => {
+                let refers = !self.bound.contains(&(*s, *expansion)) && self.expansions.get(expansion)
+                    .and_then(|scopes| scopes.iter().rev().find(|s| s.borrow().contains_key(&from)))
+                    .is_some_and(|s| Rc::ptr_eq(s, scope));
+                if refers {
+                    *form = Syntax::Token(Token::Symbol(to));
+                }
+            }
+            Syntax::Prefixed(token, datum) =>
+                self.rename(datum, from, to, scope, context.enter(token.prefix_name().unwrap())),
+            // The data of the clauses of `case` are not expressions
+            Syntax::List(items) if context == Context::Code && items.first().is_some_and(|i| i.is("case")) => {
+                for (i, item) in items.iter_mut().enumerate().skip(1) {
+                    match item {
+                        Syntax::List(clause) if i > 1 => for expression in clause.iter_mut().skip(1) {
+                            self.rename(expression, from, to, scope, context);
+                        },
+                        _ => self.rename(item, from, to, scope, context),
+                    }
+                }
+            }
+            Syntax::List(items) => {
+                let context = context.enter_list(items);
+                for item in items {
+                    self.rename(item, from, to, scope, context);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// `(define-syntax keyword (syntax-rules ...))` defines a macro in the innermost scope.
+    fn define_syntax(&mut self, items: &[Syntax], scopes: &[Scope]) -> Result<(), ParseError> {
+        let (name, rules) = match items {
+            [_, name, rules] => (name.symbol().ok_or(ParseError::BadMacro)?, rules),
+            _ => return Err(ParseError::BadMacro),
+        };
+        // The scope it is defined in is included, so it can use itself
+        let m = Rc::new(syntax_rules(rules, scopes.to_vec())?);
+        match scopes.last() {
+            Some(scope) => {
+                scope.borrow_mut().insert(name, Binding::Macro(m));
+            }
+            None => {
+                self.macros.insert(name, m);
+            }
+        }
+        Ok(())
+    }
+
+    /// `(let-syntax ((keyword (syntax-rules ...)) ...) body ...)` expands `body` with the macros
+    /// defined. With `letrec-syntax` the macros can also use one another.
+    fn let_syntax(&mut self, mut items: Vec<Syntax>, scopes: &[Scope], depth: usize, recursive: bool)
+        -> Result<Syntax, ParseError>
+    {
+        if items.len() < 3 {
+            return Err(ParseError::BadMacro);
+        }
+        let body = items.split_off(2);
+        let bindings = match items.pop() {
+            Some(Syntax::List(bindings)) => bindings,
+            _ => return Err(ParseError::BadMacro),
+        };
+
+        let scope = Scope::default();
+        let mut inner = scopes.to_vec();
+        inner.push(scope.clone());
+        for binding in bindings {
+            match binding {
+                Syntax::List(ref binding) if binding.len() == 2 => {
+                    let name = binding[0].symbol().ok_or(ParseError::BadMacro)?;
+                    let defined_in = if recursive { &inner } else { scopes };
+                    let m = syntax_rules(&binding[1], defined_in.to_vec())?;
+                    scope.borrow_mut().insert(name, Binding::Macro(Rc::new(m)));
+                }
+                _ => return Err(ParseError::BadMacro),
+            }
+        }
+
+        let mut body = self.expand_all(body, &inner, depth)?;
+        match body.len() {
+            0 => Err(ParseError::BadMacro),
+            1 => Ok(body.pop().unwrap()),
+            _ => {
+                body.insert(0, Syntax::Token(Token::Symbol(get_symbol("begin".to_string()))));
+                Ok(Syntax::List(body))
+            }
+        }
+    }
+
+    /// The expansion of the use of `m` made of `items`, by the first rule which matches it.
+    fn transcribe(&mut self, m: &Macro, items: &[Syntax]) -> Result<Syntax, ParseError> {
+        for (pattern, template) in &m.rules {
+            let mut bindings = HashMap::new();
+            // The keyword is not matched
+            if match_list(m, &pattern[1..], &items[1..], &mut bindings) {
+                let expansion = self.next_expansion;
+                self.next_expansion += 1;
+                self.expansions.insert(expansion, m.scopes.clone());
+                return instantiate(template, &bindings, Some(m.ellipsis), expansion);
+            }
+        }
+        Err(ParseError::NoMatchingRule)
+    }
+}
+
+fn defines_syntax(token: &Token) -> bool {
+    match token {
+        Token::Symbol(s) => matches!(get_value(*s).as_deref(), Some("define-syntax" | "let-syntax" | "letrec-syntax")),
+        _ => false,
+    }
+}
+
+/// Read the next datum, or `None` at the end of the input.
+fn read(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Option<Syntax>, ParseError> {
+    let token = match tokens.next() {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+    let syntax = match token {
+        Token::Comment(_) | Token::BlockComment(_) | Token::DatumComment(_) => return read(tokens),
+        Token::LeftParen => {
+            let mut items = Vec::new();
+            loop {
+                match tokens.peek() {
+                    Some(Token::RightParen) => {
+                        tokens.next();
+                        break;
+                    }
+                    Some(Token::Comment(_) | Token::BlockComment(_) | Token::DatumComment(_)) => {
+                        tokens.next();
+                    }
+                    _ => items.push(read(tokens)?.ok_or(ParseError::EOF)?),
+                }
+            }
+            Syntax::List(items)
+        }
+        Token::RightParen => return Err(ParseError::UnexpectedCloseParen),
+        Token::Quote | Token::Quasiquote | Token::Unquote | Token::UnquoteSplice => match read(tokens)? {
+            Some(datum) => Syntax::Prefixed(token, Box::new(datum)),
+            None => return Err(ParseError::BadQuote),
+        },
+        Token::Pound => {
+            let next = tokens.next().ok_or(ParseError::EOF)?;
+            let bytes = matches!(next, Token::Symbol(s) if get_value(s).as_deref() == Some("u8"));
+            let list = next.is_left_paren() || (bytes && tokens.peek().is_some_and(Token::is_left_paren));
+            let mut literal = vec![token, next];
+            if list {
+                if bytes {
+                    literal.push(tokens.next().unwrap());
+                }
+                let mut depth = 1;
+                while depth > 0 {
+                    let token = tokens.next().ok_or(ParseError::EOF)?;
+                    match token {
+                        Token::LeftParen => depth += 1,
+                        Token::RightParen => depth -= 1,
+                        _ => (),
+                    }
+                    literal.push(token);
+                }
+            }
+            Syntax::Literal(literal)
+        }
+        token => Syntax::Token(token),
+    };
+    Ok(Some(syntax))
+}
+
+/// The symbols bound by a list of formals or a pattern.
+fn symbols(syntax: &Syntax) -> Vec<Symbol> {
+    match syntax {
+        Syntax::List(items) => items.iter().flat_map(symbols).collect(),
+        _ => syntax.symbol().into_iter().collect(),
+    }
+}
+
+/// Rename the symbol `from` in `binder`, a list of formals or a pattern, where it was in the input.
+fn rename_binder(binder: &mut Syntax, from: Symbol, to: Symbol) -> bool {
+    match binder {
+        Syntax::Token(Token::Symbol(s)) if *s == from => {
+            *s = to;
+            true
+        }
+        Syntax::List(items) => items.iter_mut().fold(false, |found, item| rename_binder(item, from, to) | found),
+        _ => false,
+    }
+}
+
+/// `(syntax-rules [ellipsis] (literal ...) (pattern template) ...)`
+fn syntax_rules(spec: &Syntax, scopes: Vec<Scope>) -> Result<Macro, ParseError> {
+    let items = match spec {
+        Syntax::List(items) if items.first().is_some_and(|head| head.is("syntax-rules")) => &items[1..],
+        _ => return Err(ParseError::BadMacro),
+    };
+    let (ellipsis, items) = match items.first().and_then(Syntax::symbol) {
+        Some(ellipsis) => (ellipsis, &items[1..]),
+        None => (get_symbol("...".to_string()), items),
+    };
+    let literals = match items.first() {
+        Some(Syntax::List(literals)) =>
+            literals.iter().map(|l| l.symbol().ok_or(ParseError::BadMacro)).collect::<Result<_, _>>()?,
+        _ => return Err(ParseError::BadMacro),
+    };
+
+    let mut rules = Vec::new();
+    for rule in &items[1..] {
+        // A macro defined by a template is expanded like one written out
+        match unmark(rule) {
+            Syntax::List(mut rule) if rule.len() == 2 => {
+                let template = rule.pop().unwrap();
+                match rule.pop().unwrap() {
+                    Syntax::List(pattern) if !pattern.is_empty() => rules.push((pattern, template)),
+                    _ => return Err(ParseError::BadMacro),
+                }
+            }
+            _ => return Err(ParseError::BadMacro),
+        }
+    }
+    Ok(Macro { ellipsis, literals, rules, scopes })
+}
+
+fn unmark(syntax: &Syntax) -> Syntax {
+    match syntax {
+        Syntax::Inserted(s, _) => Syntax::Token(Token::Symbol(*s)),
+        Syntax::Prefixed(t, datum) => Syntax::Prefixed(t.clone(), Box::new(unmark(datum))),
+        Syntax::List(items) => Syntax::List(items.iter().map(unmark).collect()),
+        _ => syntax.clone(),
+    }
+}
+
+fn is_underscore(s: Symbol) -> bool {
+    get_value(s).as_deref() == Some("_")
+}
+
+fn match_pattern(m: &Macro, pattern: &Syntax, form: &Syntax, bindings: &mut HashMap<Symbol, Match>) -> bool {
+    match pattern {
+        Syntax::Token(Token::Symbol(s)) if m.literals.contains(s) => form.symbol() == Some(*s),
+        Syntax::Token(Token::Symbol(s)) => {
+            if !is_underscore(*s) {
+                bindings.insert(*s, Match::One(form.clone()));
+            }
+            true
+        }
+        Syntax::List(patterns) => match form {
+            Syntax::List(forms) => match_list(m, patterns, forms, bindings),
+            _ => false,
+        },
+        Syntax::Prefixed(t, pattern) => match form {
+            Syntax::Prefixed(u, form) => t == u && match_pattern(m, pattern, form, bindings),
+            _ => false,
+        },
+        Syntax::Token(t) => matches!(form, Syntax::Token(u) if t == u),
+        Syntax::Literal(t) => matches!(form, Syntax::Literal(u) if t == u),
+        Syntax::Inserted(..) => false,
+    }
+}
+
+// The elements of a list, and its tail if it is dotted
+fn split_tail(items: &[Syntax]) -> (&[Syntax], Option<&Syntax>) {
+    match items.len() {
+        n if n >= 2 && items[n - 2].is_dot() => (&items[..n - 2], Some(&items[n - 1])),
+        _ => (items, None),
+    }
+}
+
+/// Match the elements of a list pattern, which may have one element followed by an ellipsis and
+/// may be dotted, against those of a form.
+fn match_list(m: &Macro, patterns: &[Syntax], forms: &[Syntax], bindings: &mut HashMap<Symbol, Match>) -> bool {
+    let (patterns, tail) = split_tail(patterns);
+    let (forms, form_tail) = split_tail(forms);
+    let (before, repeated, after) = match patterns.iter().position(|p| p.symbol() == Some(m.ellipsis)) {
+        Some(0) => return false,
+        Some(i) => (&patterns[..i - 1], Some(&patterns[i - 1]), &patterns[i + 1..]),
+        None => (patterns, None, &[][..]),
+    };
+    let fixed = before.len() + after.len();
+    if forms.len() < fixed || (repeated.is_none() && tail.is_none() && forms.len() != fixed) {
+        return false;
+    }
+
+    for (pattern, form) in before.iter().zip(forms) {
+        if !match_pattern(m, pattern, form, bindings) {
+            return false;
+        }
+    }
+    let rest = &forms[before.len()..];
+    let repeated = match repeated {
+        Some(repeated) => repeated,
+        None => return match (tail, form_tail) {
+            // The tail matches the rest of the list
+            (Some(tail), Some(form_tail)) if rest.is_empty() => match_pattern(m, tail, form_tail, bindings),
+            (Some(tail), form_tail) => {
+                let mut rest = rest.to_vec();
+                if let Some(form_tail) = form_tail {
+                    rest.push(Syntax::Token(Token::Dot));
+                    rest.push(form_tail.clone());
+                }
+                match_pattern(m, tail, &Syntax::List(rest), bindings)
+            }
+            (None, form_tail) => form_tail.is_none(),
+        },
+    };
+
+    let n = rest.len() - after.len();
+    let mut matches = Vec::with_capacity(n);
+    for form in &rest[..n] {
+        let mut b = HashMap::new();
+        if !match_pattern(m, repeated, form, &mut b) {
+            return false;
+        }
+        matches.push(b);
+    }
+    let mut vars = Vec::new();
+    pattern_vars(m, repeated, &mut vars);
+    for var in vars {
+        bindings.insert(var, Match::Many(matches.iter().map(|b| b[&var].clone()).collect()));
+    }
+    for (pattern, form) in after.iter().zip(&rest[n..]) {
+        if !match_pattern(m, pattern, form, bindings) {
+            return false;
+        }
+    }
+    match (tail, form_tail) {
+        (Some(tail), Some(form_tail)) => match_pattern(m, tail, form_tail, bindings),
+        (Some(tail), None) => match_pattern(m, tail, &Syntax::List(vec![]), bindings),
+        (None, form_tail) => form_tail.is_none(),
+    }
+}
+
+fn pattern_vars(m: &Macro, pattern: &Syntax, vars: &mut Vec<Symbol>) {
+    match pattern {
+        Syntax::Token(Token::Symbol(s)) if !m.literals.contains(s) && !is_underscore(*s) && *s != m.ellipsis =>
+            vars.push(*s),
+        Syntax::List(items) => for item in items {
+            pattern_vars(m, item, vars);
+        },
+        Syntax::Prefixed(_, pattern) => pattern_vars(m, pattern, vars),
+        _ => (),
+    }
+}
+
+/// Fill in `template` with the forms matched by the pattern variables in `bindings`. Other symbols
+/// are marked as inserted by `expansion`.
+fn instantiate(template: &Syntax, bindings: &HashMap<Symbol, Match>, ellipsis: Option<Symbol>, expansion: usize)
+    -> Result<Syntax, ParseError>
+{
+    match template {
+        Syntax::Token(Token::Symbol(s)) => match bindings.get(s) {
+            Some(Match::One(form)) => Ok(form.clone()),
+            // A variable matched under an ellipsis must be followed by one
+            Some(Match::Many(_)) => Err(ParseError::BadMacro),
+            None => Ok(Syntax::Inserted(*s, expansion)),
+        },
+        Syntax::List(items) => {
+            // `(... template)` is `template` with ellipses taken literally
+            if items.len() == 2 && ellipsis.is_some() && items[0].symbol() == ellipsis {
+                return instantiate(&items[1], bindings, None, expansion);
+            }
+            let mut list = Vec::with_capacity(items.len());
+            let mut i = 0;
+            while i < items.len() {
+                let mut depth = 0;
+                while ellipsis.is_some() && items.get(i + depth + 1).and_then(Syntax::symbol) == ellipsis {
+                    depth += 1;
+                }
+                if depth == 0 {
+                    list.push(instantiate(&items[i], bindings, ellipsis, expansion)?);
+                } else {
+                    repeat(&items[i], depth, bindings, ellipsis, expansion, &mut list)?;
+                }
+                i += depth + 1;
+            }
+            // A list substituted after a `.` continues the list
+            let n = list.len();
+            if n >= 2 && list[n - 2].is_dot() {
+                if let Syntax::List(_) = list[n - 1] {
+                    if let Some(Syntax::List(tail)) = list.pop() {
+                        list.pop();
+                        list.extend(tail);
+                    }
+                }
+            }
+            Ok(Syntax::List(list))
+        }
+        Syntax::Prefixed(t, datum) =>
+            Ok(Syntax::Prefixed(t.clone(), Box::new(instantiate(datum, bindings, ellipsis, expansion)?))),
+        _ => Ok(template.clone()),
+    }
+}
+
+/// Instantiate `template`, followed by `depth` ellipses, once for each form matched by the
+/// variables in it which were matched under an ellipsis.
+fn repeat(template: &Syntax, depth: usize, bindings: &HashMap<Symbol, Match>, ellipsis: Option<Symbol>,
+          expansion: usize, list: &mut Vec<Syntax>) -> Result<(), ParseError>
+{
+    let mut vars = Vec::new();
+    template_vars(template, &mut vars);
+    let many: Vec<_> = vars.into_iter()
+        .filter_map(|var| match bindings.get(&var) {
+            Some(Match::Many(matches)) => Some((var, matches)),
+            _ => None,
+        })
+        .collect();
+    let n = match many.first() {
+        Some((_, matches)) => matches.len(),
+        None => return Err(ParseError::BadMacro),
+    };
+    if many.iter().any(|(_, matches)| matches.len() != n) {
+        return Err(ParseError::BadMacro);
+    }
+
+    for i in 0..n {
+        let mut bindings = bindings.clone();
+        for (var, matches) in &many {
+            bindings.insert(*var, matches[i].clone());
+        }
+        if depth > 1 {
+            repeat(template, depth - 1, &bindings, ellipsis, expansion, list)?;
+        } else {
+            list.push(instantiate(template, &bindings, ellipsis, expansion)?);
+        }
+    }
+    Ok(())
+}
+
+fn template_vars(template: &Syntax, vars: &mut Vec<Symbol>) {
+    match template {
+        Syntax::Token(Token::Symbol(s)) => vars.push(*s),
+        Syntax::List(items) => for item in items {
+            template_vars(item, vars);
+        },
+        Syntax::Prefixed(_, datum) => template_vars(datum, vars),
+        _ => (),
+    }
+}
+
+/// Collect the inserted symbols which are bound as variables in `form`, which are renamed. Those
+/// defined at top level are not.
+fn binders_of(form: &Syntax, toplevel: bool, binders: &mut HashSet<(Symbol, usize)>) {
+    let items = match form {
+        Syntax::List(items) => items,
+        Syntax::Prefixed(Token::Quasiquote, _) => return unquoted(form, Context::Code, binders),
+        _ => return,
+    };
+    let head = items.first().and_then(Syntax::symbol).and_then(get_value);
+    match head.as_deref() {
+        Some("quote") => return,
+        Some("quasiquote") => return unquoted(form, Context::Code, binders),
+        Some("lambda") | Some("destructuring-bind") => if let Some(formals) = items.get(1) {
+            inserted(formals, binders);
+        },
+        Some("define") | Some("define-memoized") => match items.iter().skip(1)
+            .find(|i| !matches!(i, Syntax::Token(Token::Integer(_)))) {
+            Some(Syntax::List(signature)) => for (i, s) in signature.iter().enumerate() {
+                if i > 0 || !toplevel {
+                    inserted(s, binders);
+                }
+            },
+            Some(name) if !toplevel => inserted(name, binders),
+            _ => (),
+        },
+        Some("for/list") | Some("for/hash") | Some("for/fold") => for clause in &items[1..] {
+            if let Syntax::List(clause) = clause {
+                if let Some(Syntax::List(clause)) = clause.first() {
+                    if let Some(binding) = clause.first() {
+                        inserted(binding, binders);
+                    }
+                }
+            }
+        },
+        _ => (),
+    }
+
+    let toplevel = toplevel && head.as_deref() == Some("begin");
+    for item in items {
+        binders_of(item, toplevel, binders);
+    }
+}
+
+/// Collect the binders of the unquoted expressions in `form`, a datum in `context`.
+fn unquoted(form: &Syntax, context: Context, binders: &mut HashSet<(Symbol, usize)>) {
+    match form {
+        Syntax::Prefixed(token, datum) => match context.enter(token.prefix_name().unwrap()) {
+            Context::Code => binders_of(datum, false, binders),
+            inner => unquoted(datum, inner, binders),
+        },
+        Syntax::List(items) => match context.enter_list(items) {
+            Context::Code if context != Context::Code => for item in &items[1..] {
+                binders_of(item, false, binders);
+            },
+            inner => for item in items {
+                unquoted(item, inner, binders);
+            },
+        },
+        _ => (),
+    }
+}
+
+fn inserted(syntax: &Syntax, binders: &mut HashSet<(Symbol, usize)>) {
+    match syntax {
+        Syntax::Inserted(s, expansion) => {
+            binders.insert((*s, *expansion));
+        }
+        Syntax::List(items) => for item in items {
+            inserted(item, binders);
+        },
+        _ => (),
+    }
+}
+
+/// Write `form`, a datum in `context`, out as tokens, renaming the inserted symbols in `binders`
+/// in code.
+fn write(form: &Syntax, binders: &HashSet<(Symbol, usize)>, context: Context, tokens: &mut Vec<Token>) {
+    match form {
+        Syntax::Token(token) => tokens.push(token.clone()),
+        Syntax::Inserted(s, expansion) if context == Context::Code && binders.contains(&(*s, *expansion)) => {
+            // Names starting with a space can't be written, so they can't clash with another
+            let name = format!(" {}.{}", get_value(*s).unwrap(), expansion);
+            tokens.push(Token::Symbol(get_symbol(name)));
+        }
+        Syntax::Inserted(s, _) => tokens.push(Token::Symbol(*s)),
+        Syntax::Prefixed(token, datum) => {
+            tokens.push(token.clone());
+            write(datum, binders, context.enter(token.prefix_name().unwrap()), tokens);
+        }
+        Syntax::Literal(literal) => tokens.extend(literal.iter().cloned()),
+        Syntax::List(items) => {
+            let context = context.enter_list(items);
+            tokens.push(Token::LeftParen);
+            for item in items {
+                write(item, binders, context, tokens);
+            }
+            tokens.push(Token::RightParen);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! `(include "file" ...)`, which reads the forms of files in place of the form including them.
+//!
+//! Includes are replaced before macros are expanded, so the forms of an included file are
+//! expanded and evaluated as if they were written where the include is, and may use and define
+//! macros like them. Only top level includes are replaced. A relative path is resolved against
+//! the directory of the file the include is in, or the working directory if the input isn't from
+//! a file. A file which includes itself, directly or through other files, is an error.
+
+use {Error, Location, ParseError, Token, Tokenizer};
+
+use string_interner::get_value;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A top level form: the name of the input it was read from, its tokens and its location.
+pub type Source = (String, Vec<Token>, Location);
+
+/// The path which `path` names when it appears in the input `name`.
+pub(crate) fn resolve(name: &str, path: &str) -> PathBuf {
+    let path = Path::new(path);
+    // Input such as `<repl>` or a string isn't from a file
+    let from_file = !name.is_empty() && !name.starts_with('<');
+    match Path::new(name).parent() {
+        Some(dir) if from_file && path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The top level forms of `input`, the contents of `name`, with those of the files it includes in
+/// place of its includes.
+pub fn read_forms(name: &str, input: &str) -> Result<Vec<Source>, Error> {
+    let mut forms = Vec::new();
+    read_into(name, input, &mut Vec::new(), &mut forms)?;
+    Ok(forms)
+}
+
+// Add the forms of `input` to `forms`, when the files in `including` are being included
+fn read_into(name: &str, input: &str, including: &mut Vec<PathBuf>, forms: &mut Vec<Source>) -> Result<(), Error> {
+    let at = |e, location| Error::At(name.to_string(), location, Box::new(e));
+    let read = Tokenizer::tokenize_forms(input).map_err(|(e, location)| at(Error::Parse(e), location))?;
+    for (tokens, location) in read {
+        let paths = match included(&tokens) {
+            Some(Ok(paths)) => paths,
+            Some(Err(e)) => return Err(at(Error::Parse(e), location)),
+            None => {
+                forms.push((name.to_string(), tokens, location));
+                continue;
+            }
+        };
+        for path in paths {
+            let path = resolve(name, &path);
+            let fail = |why: String| at(Error::Include(format!("Could not include {}: {}", path.display(), why)),
+                                        location.clone());
+            let canonical = fs::canonicalize(&path).map_err(|e| fail(e.to_string()))?;
+            if including.contains(&canonical) {
+                return Err(fail("it includes itself".to_string()));
+            }
+            let input = fs::read_to_string(&path).map_err(|e| fail(e.to_string()))?;
+            including.push(canonical);
+            read_into(&path.to_string_lossy(), &input, including, forms)?;
+            including.pop();
+        }
+    }
+    Ok(())
+}
+
+/// The paths of `(include "file" ...)`, if `tokens` is one.
+fn included(tokens: &[Token]) -> Option<Result<Vec<String>, ParseError>> {
+    match tokens {
+        [Token::LeftParen, Token::Symbol(s), paths @ .., Token::RightParen]
+            if get_value(*s).as_deref() == Some("include") =>
+        {
+            let paths = paths.iter()
+                .map(|t| match t {
+                    Token::String(path) => Ok(path.clone()),
+                    _ => Err(ParseError::Expected("the names of files to include")),
+                })
+                .collect();
+            Some(paths)
+        }
+        _ => None,
+    }
+}
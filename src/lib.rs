@@ -1,17 +1,33 @@
 #![feature(lazy_cell)]
 
+extern crate num_bigint;
 extern crate regex;
 extern crate string_interner;
 extern crate vm;
 
 mod compiler;
+mod coverage;
+mod embed;
 mod error;
+mod expander;
+mod include;
+mod library;
+mod load;
+mod module;
 mod optimize;
 mod parser;
+mod pass;
+mod reader;
 mod tokenizer;
 
 pub use compiler::compile;
+pub use coverage::instrument;
+pub use embed::{EvalReport, Minerva};
 pub use error::Error;
-pub use optimize::{IR, optimize, output_asm};
-pub use parser::{Ast, Parser, ParseError};
-pub use tokenizer::{Token, Tokenizer};
+pub use expander::Expander;
+pub use include::{read_forms, Source};
+pub use optimize::{IR, optimize, optimize_with, output_asm, tail_calls, Options, TailCall};
+pub use parser::{Ast, Parser, ParseError, Pattern};
+pub use pass::AstPass;
+pub use reader::{init_env, Form, Next, Reader};
+pub use tokenizer::{Location, Token, Tokenizer};
@@ -0,0 +1,279 @@
+//! Libraries, as in R7RS: `define-library` and `import`.
+//!
+//! ```scheme
+//! (define-library (geometry shapes)
+//!   (export area (rename make-square square))
+//!   (import (scheme base))
+//!   (begin
+//!     (define (make-square side) (list 'square side))
+//!     (define (area shape) (* (cadr shape) (cadr shape)))))
+//!
+//! (import (prefix (geometry shapes) shapes:))
+//! (shapes:area (shapes:square 2))
+//! ```
+//!
+//! A library has an environment of its own, which holds only what it imports and defines, and
+//! importing it copies the bindings it exports into the importing environment. A library may
+//! export names it imports, and the import sets `only`, `except`, `prefix` and `rename` choose and
+//! rename what is imported. The libraries `(scheme ...)` and `(minerva)` are the whole initial
+//! environment. The declarations of a library are `export`, `import`, `begin`, and `include`,
+//! whose files are found like those of `load`. Macros defined by a library are only visible in it.
+//!
+//! Importing a library which hasn't been defined looks for its file, `geometry/shapes.sld` or
+//! `geometry/shapes.ss` for `(geometry shapes)`, and loads it. The file is looked for in the
+//! directory of the file being loaded, if there is one, then the directories added with
+//! `add-library-path!`, latest first, then those in `MINERVA_LIBRARY_PATH`, then the working
+//! directory.
+//!
+//! `define-library` and `import` are read as calls to the procedures of the same names, with their
+//! parts quoted. Libraries are shared by the machines on a thread.
+
+use vm::{Environment, Value, VM};
+
+use {init_env, read_forms, Source};
+use load::{eval_forms, loading, loading_file, path_arg, read_file};
+
+use string_interner::{get_symbol, get_value, Symbol};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+// The extensions of library files, in the order they are looked for
+const EXTENSIONS: [&str; 2] = ["sld", "ss"];
+
+thread_local! {
+    // The libraries which have been defined, by their names as written
+    static LIBRARIES: RefCell<HashMap<String, Library>> = RefCell::new(HashMap::new());
+    // The directories libraries are looked for in, after that of the file being loaded
+    static SEARCH_PATH: RefCell<Vec<PathBuf>> = RefCell::new(search_path());
+    // The bindings of the standard libraries
+    static STANDARD: Environment = {
+        let env = init_env();
+        env.add_root();
+        env
+    };
+}
+
+struct Library {
+    env: Environment,
+    // The names it exports, each with the name it has in the library
+    exports: Vec<(Symbol, Symbol)>,
+}
+
+fn search_path() -> Vec<PathBuf> {
+    let mut path: Vec<PathBuf> = env::var_os("MINERVA_LIBRARY_PATH")
+        .map(|dirs| env::split_paths(&dirs).collect())
+        .unwrap_or_default();
+    path.push(PathBuf::from("."));
+    path
+}
+
+/// The elements of the list `v`, or `None` if it isn't a proper list.
+fn elements(v: Value) -> Option<Vec<Value>> {
+    let mut elements = Vec::new();
+    let mut l = v;
+    while l.is_pair() {
+        elements.push(l.car());
+        l = l.cdr();
+    }
+    if l.is_nil() { Some(elements) } else { None }
+}
+
+fn is_named(v: Value, name: &str) -> bool {
+    v.is_symbol() && get_value(v.to_symbol()).as_deref() == Some(name)
+}
+
+fn symbol_arg(who: &str, v: Value) -> Result<Symbol, String> {
+    if v.is_symbol() {
+        Ok(v.to_symbol())
+    } else {
+        Err(format!("{}: {} is not a symbol", who, v))
+    }
+}
+
+/// The components of the library name `v`, which are symbols or exact non-negative integers.
+fn components(who: &str, v: Value) -> Result<Vec<String>, String> {
+    let components = elements(v).unwrap_or_default();
+    let valid = |c: &Value| c.is_symbol() || c.is_integer() && c.to_integer() >= 0;
+    if components.is_empty() || !components.iter().all(valid) {
+        return Err(format!("{}: {} is not a library name", who, v));
+    }
+    Ok(components.iter().map(|c| format!("{}", c)).collect())
+}
+
+/// Bind each name in `env` to its value, unless it is already.
+fn bind(env: &Environment, bindings: Vec<(Symbol, Value)>) {
+    for (name, value) in bindings {
+        if env.lookup_variable_value(name) != Some(value) {
+            env.define_variable(name, value);
+        }
+    }
+}
+
+/// Load the file of the library `name`, from the first directory of the search path which has it.
+fn find(vm: &mut VM, who: &str, name: &str, components: &[String]) -> Result<(), String> {
+    let mut dirs: Vec<PathBuf> = loading_file().and_then(|file| file.parent().map(PathBuf::from)).into_iter()
+        .collect();
+    SEARCH_PATH.with(|path| dirs.extend(path.borrow().iter().cloned()));
+    let file = dirs.iter()
+        .flat_map(|dir| EXTENSIONS.iter().map(move |ext| {
+            let mut file: PathBuf = dir.join(components.join("/"));
+            file.set_extension(ext);
+            file
+        }))
+        .find(|file| file.is_file());
+    let file = match file {
+        Some(file) => file,
+        None => return Err(format!("{}: there is no library {}", who, name)),
+    };
+
+    let (path, forms) = read_file(who, &file)?;
+    loading(path, || eval_forms(vm, who, None, forms))?;
+    if LIBRARIES.with(|l| l.borrow().contains_key(name)) {
+        Ok(())
+    } else {
+        Err(format!("{}: {} does not define {}", who, file.display(), name))
+    }
+}
+
+/// The bindings exported by the library named `v`, defining it first if it hasn't been.
+fn exports(vm: &mut VM, who: &str, v: Value) -> Result<Vec<(Symbol, Value)>, String> {
+    let components = components(who, v)?;
+    if components[0] == "scheme" || components == ["minerva"] {
+        return Ok(STANDARD.with(Environment::local_bindings));
+    }
+    let name = format!("({})", components.join(" "));
+    if !LIBRARIES.with(|l| l.borrow().contains_key(&name)) {
+        find(vm, who, &name, &components)?;
+    }
+    LIBRARIES.with(|l| {
+        let l = l.borrow();
+        let library = &l[&name];
+        // Every export was checked to be bound when the library was defined
+        Ok(library.exports.iter()
+            .map(|&(external, internal)| (external, library.env.lookup_variable_value(internal).unwrap()))
+            .collect())
+    })
+}
+
+/// The bindings imported by the import set `set`.
+fn import_set(vm: &mut VM, who: &str, set: Value) -> Result<Vec<(Symbol, Value)>, String> {
+    let items = elements(set).unwrap_or_default();
+    let head = items.first().filter(|v| v.is_symbol()).and_then(|v| get_value(v.to_symbol()));
+    let modifier = match head.as_deref() {
+        Some(m @ "only") | Some(m @ "except") | Some(m @ "prefix") | Some(m @ "rename") if items.len() >= 2 => m,
+        _ => return exports(vm, who, set),
+    };
+    let mut bindings = import_set(vm, who, items[1])?;
+    let has = |bindings: &[(Symbol, Value)], name: Symbol| {
+        if bindings.iter().any(|&(s, _)| s == name) {
+            Ok(())
+        } else {
+            Err(format!("{}: {} is not in {}", who, Value::Symbol(name), items[1]))
+        }
+    };
+    match modifier {
+        "only" | "except" => {
+            let names = items[2..].iter().map(|&v| symbol_arg(who, v)).collect::<Result<Vec<_>, _>>()?;
+            for &name in &names {
+                has(&bindings, name)?;
+            }
+            bindings.retain(|(s, _)| names.contains(s) == (modifier == "only"));
+        }
+        "prefix" => {
+            if items.len() != 3 {
+                return Err(format!("{}: {} is not an import set", who, set));
+            }
+            let prefix = get_value(symbol_arg(who, items[2])?).unwrap();
+            for (s, _) in &mut bindings {
+                *s = get_symbol(format!("{}{}", prefix, get_value(*s).unwrap()));
+            }
+        }
+        _ => {
+            for &rename in &items[2..] {
+                let (from, to) = match elements(rename).as_deref() {
+                    Some(&[from, to]) => (symbol_arg(who, from)?, symbol_arg(who, to)?),
+                    _ => return Err(format!("{}: {} is not a renaming", who, rename)),
+                };
+                has(&bindings, from)?;
+                for (s, _) in &mut bindings {
+                    if *s == from {
+                        *s = to;
+                    }
+                }
+            }
+        }
+    }
+    Ok(bindings)
+}
+
+/// `(import set ...)` Bind the names which each import set imports in the global environment, or
+/// that of the library being defined.
+pub fn import(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let env = vm.global_environment();
+    for &set in args {
+        let bindings = import_set(vm, "import", set)?;
+        bind(&env, bindings);
+    }
+    Ok(Value::Void)
+}
+
+/// `(define-library name declaration ...)` Define the library `name`.
+pub fn define_library(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let who = "define-library";
+    let name = format!("({})", components(who, args[0])?.join(" "));
+    let env = Environment::new();
+    env.add_root();
+    let mut exports = Vec::new();
+    let mut body: Vec<Source> = Vec::new();
+    for &declaration in &args[1..] {
+        let items = elements(declaration).unwrap_or_default();
+        let head = items.first().filter(|v| v.is_symbol()).and_then(|v| get_value(v.to_symbol()));
+        match head.as_deref() {
+            Some("export") => {
+                for &spec in &items[1..] {
+                    exports.push(match elements(spec).as_deref() {
+                        Some(&[rename, internal, external]) if is_named(rename, "rename") =>
+                            (symbol_arg(who, external)?, symbol_arg(who, internal)?),
+                        _ => (symbol_arg(who, spec)?, symbol_arg(who, spec)?),
+                    });
+                }
+            }
+            Some("import") => {
+                for &set in &items[1..] {
+                    let bindings = import_set(vm, who, set)?;
+                    bind(&env, bindings);
+                }
+            }
+            Some("begin") => {
+                let text: Vec<String> = items[1..].iter().map(|form| format!("{}", form)).collect();
+                let forms = read_forms(&format!("<library {}>", name), &text.join("\n"))
+                    .map_err(|e| format!("{}: {}", who, e))?;
+                body.extend(forms);
+            }
+            Some("include") => {
+                for &file in &items[1..] {
+                    body.extend(read_file(who, &path_arg(who, file)?)?.1);
+                }
+            }
+            _ => return Err(format!("{}: {} is not a library declaration", who, declaration)),
+        }
+    }
+
+    eval_forms(vm, who, Some(&env), body)?;
+    if let Some(&(_, internal)) = exports.iter().find(|&&(_, internal)| env.lookup_variable_value(internal).is_none()) {
+        return Err(format!("{}: {} does not define {}", who, name, Value::Symbol(internal)));
+    }
+    LIBRARIES.with(|l| l.borrow_mut().insert(name, Library { env, exports }));
+    Ok(Value::Void)
+}
+
+/// `(add-library-path! directory)` Look for libraries in `directory` before the directories
+/// already in the search path.
+pub fn add_library_path(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let dir = path_arg("add-library-path!", args[0])?;
+    SEARCH_PATH.with(|path| path.borrow_mut().insert(0, dir));
+    Ok(Value::Void)
+}
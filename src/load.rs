@@ -0,0 +1,92 @@
+//! `(load "file")`, which evaluates the forms of a file in the global environment when it is
+//! called.
+//!
+//! Unlike `include`, which reads a file once when the form including it is read, `load` reads the
+//! file every time it runs, and the file has its own macros: those defined by the code loading it
+//! aren't visible in it, and those it defines aren't visible afterwards. A relative path is
+//! resolved against the directory of the file being loaded, if `load` is called while one is, and
+//! otherwise against the working directory. Loading a file while it is being loaded is an error.
+
+use vm::{assemble, Environment, Value, VM};
+
+use {compile, optimize_with, output_asm, read_forms, Ast, Expander, Options, Parser};
+use include::{resolve, Source};
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+thread_local! {
+    // The files being loaded, innermost last
+    static LOADING: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// `(load path)` Evaluate the forms of the file `path`, returning the value of the last.
+pub fn load(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let (path, forms) = read_file("load", &path_arg("load", args[0])?)?;
+    loading(path, || eval_forms(vm, "load", None, forms))
+}
+
+/// The path named by the string `v`, an argument to the procedure `name`.
+pub(crate) fn path_arg(name: &str, v: Value) -> Result<PathBuf, String> {
+    if !v.is_string() {
+        return Err(format!("{}: {} is not a string", name, v));
+    }
+    let s = v.to_string();
+    let from = loading_file().map(|file| file.to_string_lossy().into_owned());
+    let path = resolve(from.as_deref().unwrap_or(""), &s.str);
+    Box::into_raw(s);
+    Ok(path)
+}
+
+/// The canonical path of the file `path` and its forms, for the procedure `name`.
+pub(crate) fn read_file(name: &str, path: &Path) -> Result<(PathBuf, Vec<Source>), String> {
+    let canonical = fs::canonicalize(path)
+        .map_err(|e| format!("{}: could not load {}: {}", name, path.display(), e))?;
+    if LOADING.with(|l| l.borrow().contains(&canonical)) {
+        return Err(format!("{}: {} is already being loaded", name, path.display()));
+    }
+    let input = fs::read_to_string(path).map_err(|e| format!("{}: could not load {}: {}", name, path.display(), e))?;
+    let forms = read_forms(&canonical.to_string_lossy(), &input).map_err(|e| format!("{}: {}", name, e))?;
+    Ok((canonical, forms))
+}
+
+/// The innermost file being loaded.
+pub(crate) fn loading_file() -> Option<PathBuf> {
+    LOADING.with(|l| l.borrow().last().cloned())
+}
+
+/// Run `f` while the file `path` is being loaded.
+pub(crate) fn loading<T, F: FnOnce() -> T>(path: PathBuf, f: F) -> T {
+    LOADING.with(|l| l.borrow_mut().push(path));
+    let result = f();
+    LOADING.with(|l| l.borrow_mut().pop());
+    result
+}
+
+/// Compile and run each form in turn for the procedure `name`, in `env` or the global environment,
+/// so that the constants of a form only exist while it runs.
+pub(crate) fn eval_forms(vm: &mut VM, name: &str, env: Option<&Environment>, forms: Vec<Source>)
+    -> Result<Value, String>
+{
+    let mut expander = Expander::new();
+    let mut options = Options::default();
+    let mut result = Value::Void;
+    for (file, tokens, location) in forms {
+        let at = |e| format!("{}: {}:{}: {}", name, file, location, e);
+        let tokens = expander.expand(tokens).map_err(at)?;
+        for ast in Parser::parse(tokens).map_err(at)? {
+            // A `#!no-tail-call` directive lasts until the end of the file
+            if let Ast::Directive(_) = ast {
+                options.tail_calls = false;
+                continue;
+            }
+            let (code, consts) = assemble(output_asm(optimize_with(compile(ast), options)));
+            result = match env {
+                Some(env) => vm.run_nested_in(env, code, consts)?,
+                None => vm.run_nested(code, consts)?,
+            };
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,76 @@
+//! Modules: files loaded under a name, which can be reloaded while the program runs.
+//!
+//! `(load-module 'name "file")` evaluates the forms of a file like `load`, but in a frame of its
+//! own, and only once they have all run are the definitions it made copied into the global
+//! environment. `(reload-module 'name)` does the same again with the file as it is now, so a
+//! long-running program or the REPL picks up edits to it without restarting:
+//!
+//! ```scheme
+//! (load-module 'handlers "handlers.ss")
+//! ; ... edit handlers.ss ...
+//! (reload-module 'handlers)
+//! ```
+//!
+//! If reading or running the file fails, the global environment is left as it was. Otherwise every
+//! definition replaces the global binding at once, so call sites which cached the old procedure
+//! see the new one on their next call. Procedures of the module refer to each other through its
+//! own frame, so a variable the module assigns should be read through one of its procedures, since
+//! the global binding is only a copy made when it was loaded. Names the file no longer defines keep
+//! their old values.
+
+use vm::{Value, VM};
+
+use load::{eval_forms, loading, path_arg, read_file};
+
+use string_interner::{get_value, Symbol};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+thread_local! {
+    // The files modules were loaded from, by name
+    static MODULES: RefCell<HashMap<Symbol, PathBuf>> = RefCell::new(HashMap::new());
+}
+
+fn module_arg(name: &str, v: Value) -> Result<Symbol, String> {
+    if v.is_symbol() {
+        Ok(v.to_symbol())
+    } else {
+        Err(format!("{}: {} is not a symbol", name, v))
+    }
+}
+
+/// Load `module` from the file `path` for the procedure `name`, returning the names it defines in
+/// order.
+fn load_from(vm: &mut VM, name: &str, module: Symbol, path: &Path) -> Result<Value, String> {
+    let (path, forms) = read_file(name, path)?;
+    let global = vm.global_environment();
+    let env = global.extend();
+    loading(path.clone(), || eval_forms(vm, name, Some(&env), forms))?;
+
+    let mut bindings = env.local_bindings();
+    bindings.sort_by_key(|&(s, _)| get_value(s));
+    for &(s, v) in &bindings {
+        global.define_variable(s, v);
+    }
+    MODULES.with(|m| m.borrow_mut().insert(module, path));
+    Ok(bindings.iter().rev().fold(Value::Nil, |l, &(s, _)| Value::Pair(Value::Symbol(s), l)))
+}
+
+/// `(load-module name path)` Load the file `path` as the module `name`, returning the names it
+/// defines.
+pub fn load_module(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let module = module_arg("load-module", args[0])?;
+    let path = path_arg("load-module", args[1])?;
+    load_from(vm, "load-module", module, &path)
+}
+
+/// `(reload-module name)` Load the module `name` again from the file it was loaded from, returning
+/// the names it defines.
+pub fn reload_module(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let module = module_arg("reload-module", args[0])?;
+    let path = MODULES.with(|m| m.borrow().get(&module).cloned())
+        .ok_or_else(|| format!("reload-module: {} is not a module", args[0]))?;
+    load_from(vm, "reload-module", module, &path)
+}
@@ -1,5 +1,5 @@
 use string_interner::{get_value, Symbol};
-use vm::Value;
+use vm::{Instruction, Value};
 
 use std::fmt;
 
@@ -10,17 +10,31 @@ pub enum IR {
     Goto(Symbol),
     GotoIf(Symbol, Symbol),
     GotoIfNot(Symbol, Symbol),
+    /// Switch(key, table, default) Goto the label paired with the value of `key`.
+    Switch(Symbol, Vec<(Value, Symbol)>, Symbol),
     // TODO: new PHI
     Phi(Symbol, Symbol, Vec<IR>, Symbol, Vec<IR>),
     Move(Symbol, Symbol),
     //Phi(Symbol, Symbol, Symbol),
     Define(Symbol, Symbol),
+    /// Set(name, value) Assign `value` to the nearest binding of the variable `name`.
+    Set(Symbol, Symbol),
     Primitive(Symbol, Value),
     Lookup(Symbol, Symbol),
     Copy(Symbol, Symbol),
+    /// Car(target, pair)
+    Car(Symbol, Symbol),
+    /// Cdr(target, pair)
+    Cdr(Symbol, Symbol),
     //Param(Symbol),
     //Call(Symbol, Symbol, usize),
     Call(Symbol, Symbol, Vec<Symbol>),
+    /// CallPrimitive(target, proc, args, instruction) Call `proc`, which was looked up by the name
+    /// of the primitive made of `instruction`, running the instruction in place of the call if it
+    /// still is that primitive.
+    CallPrimitive(Symbol, Symbol, Vec<Symbol>, Instruction),
+    /// TailCall(proc, args) Call `proc` in place of the current procedure, returning its result.
+    TailCall(Symbol, Vec<Symbol>),
     Fn(Symbol, Vec<Symbol>, Vec<IR>),
 }
 
@@ -29,8 +43,11 @@ impl fmt::Display for IR {
         match self {
             IR::Primitive(s, v) => write!(f, "PRIMITIVE {}, {}", get_value(*s).unwrap(), v),
             IR::Define(s1, s2) => write!(f, "DEFINE {}, {}", get_value(*s1).unwrap(), get_value(*s2).unwrap()),
+            IR::Set(s1, s2) => write!(f, "SET {}, {}", get_value(*s1).unwrap(), get_value(*s2).unwrap()),
             IR::Lookup(t, s) => write!(f, "{}, LOOKUP {}", get_value(*t).unwrap(), get_value(*s).unwrap()),
             IR::Copy(t, s) => write!(f, "COPY {}, {}", get_value(*t).unwrap(), get_value(*s).unwrap()),
+            IR::Car(t, s) => write!(f, "{}, CAR {}", get_value(*t).unwrap(), get_value(*s).unwrap()),
+            IR::Cdr(t, s) => write!(f, "{}, CDR {}", get_value(*t).unwrap(), get_value(*s).unwrap()),
             //IR::Param(s) => write!(f, "PARAM {}", get_value(*s).unwrap()),
             IR::Call(s, proc, args) => {
                 //write!(f, "{} CALL {}, {}", get_value(*s).unwrap(), get_value(*proc).unwrap(), args)
@@ -40,6 +57,20 @@ impl fmt::Display for IR {
                 }
                 write!(f, ")")
             }
+            IR::CallPrimitive(s, proc, args, i) => {
+                write!(f, "{} CALLPRIMITIVE {:?} {}(", get_value(*s).unwrap(), i, get_value(*proc).unwrap())?;
+                for arg in args {
+                    write!(f, "{}, ", get_value(*arg).unwrap())?;
+                }
+                write!(f, ")")
+            }
+            IR::TailCall(proc, args) => {
+                write!(f, "TAILCALL {}(", get_value(*proc).unwrap())?;
+                for arg in args {
+                    write!(f, "{}, ", get_value(*arg).unwrap())?;
+                }
+                write!(f, ")")
+            }
             IR::Fn(s, args, ir) => {
                 write!(f, "{}(", get_value(*s).unwrap())?;
                 for arg in args {
@@ -67,6 +98,13 @@ impl fmt::Display for IR {
             IR::Goto(s) => write!(f, "GOTO {}", get_value(*s).unwrap()),
             IR::GotoIf(s1, s2) => write!(f, "GOTOIF {}, {}", get_value(*s1).unwrap(), get_value(*s2).unwrap()),
             IR::GotoIfNot(s1, s2) => write!(f, "GOTOIFNOT {}, {}", get_value(*s1).unwrap(), get_value(*s2).unwrap()),
+            IR::Switch(s, table, default) => {
+                write!(f, "SWITCH {}, [", get_value(*s).unwrap())?;
+                for (v, l) in table {
+                    write!(f, "{} => {}, ", v, get_value(*l).unwrap())?;
+                }
+                write!(f, "], {}", get_value(*default).unwrap())
+            }
             IR::Return(s) => write!(f, "RETURN {}", get_value(*s).unwrap()),
             IR::Move(s1, s2) => write!(f, "MOVE {}, {}", get_value(*s1).unwrap(), get_value(*s2).unwrap()),
         }
@@ -1,19 +1,49 @@
 mod ir;
+mod tail_call;
 
 pub use self::ir::IR;
+pub use self::tail_call::{tail_calls, TailCall};
 
-use vm::{ASM, GotoValue, Register, Value};
+use self::tail_call::optimize_tail_calls;
 
-use string_interner::Symbol;
+use compiler::gen_var;
+
+use vm::{ASM, GotoValue, Instruction, Register, Value};
+
+use string_interner::{get_symbol, get_value, Symbol};
 
 use std::collections::{HashMap, HashSet};
+use std::mem;
+
+/// The optimizations which can be turned off.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Options {
+    /// Compile calls in tail position as tail calls, which don't return to their caller.
+    pub tail_calls: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            tail_calls: true,
+        }
+    }
+}
+
+pub fn optimize(ir: Vec<IR>) -> Vec<IR> {
+    optimize_with(ir, Options::default())
+}
 
-pub fn optimize(mut ir: Vec<IR>) -> Vec<IR> {
+pub fn optimize_with(mut ir: Vec<IR>, options: Options) -> Vec<IR> {
     optimize_lambda_formals(&mut ir);
+    optimize_linear_updates(&mut ir);
     optimize_lookups(&mut ir);
     optimize_copies(&mut ir);
+    optimize_primitive_calls(&mut ir);
     optimize_dead_code(&mut ir);
-    //optimize_tail_call(&mut ir);
+    if options.tail_calls {
+        optimize_tail_calls(&mut ir);
+    }
     //optimize_recursion(&mut ir);
     ir
 }
@@ -31,17 +61,6 @@ fn optimize_recursion(ir: &mut Vec<IR>) {
     }
 }
 
-// TODO
-fn optimize_tail_call(ir: &mut Vec<IR>) {
-    fn inner(f: &mut IR) {
-    }
-
-    for i in ir.iter_mut() {
-        if let IR::Fn(_, _, _) = *i {
-            inner(i);
-        }
-    }
-}
 */
 
 fn optimize_lambda_formals(ir: &mut Vec<IR>) {
@@ -86,6 +105,106 @@ fn optimize_lambda_formals(ir: &mut Vec<IR>) {
     }
 }
 
+/// Call the linear update versions of list procedures on lists known to be unshared, the result of
+/// `list` or `reverse` which is used nowhere else. Either name may have been redefined or shadowed
+/// by the time the call is made, so the procedure to call is chosen at run time by
+/// ` linear-update`, which only picks the linear update version if both are still the builtins.
+fn optimize_linear_updates(ir: &mut Vec<IR>) {
+    #[derive(Default)]
+    struct Scan {
+        procs: HashMap<Symbol, Symbol>,
+        // Lists made by calls which may make a new list, and the procedure called
+        fresh: HashMap<Symbol, Symbol>,
+        uses: HashMap<Symbol, usize>,
+        calls: Vec<(Symbol, Vec<Symbol>)>,
+    }
+
+    impl Scan {
+        fn used(&mut self, s: Symbol) {
+            *self.uses.entry(s).or_insert(0) += 1;
+        }
+
+        /// The procedure which made `s`, if it may be a new list which is used nowhere else.
+        fn unshared(&self, s: Symbol) -> Option<Symbol> {
+            self.fresh.get(&s).copied().filter(|_| self.uses.get(&s) == Some(&1))
+        }
+
+        fn scan(&mut self, ir: &[IR]) {
+            for i in ir {
+                match i {
+                    IR::Lookup(t, ident) => { self.procs.insert(*t, *ident); }
+                    IR::Call(t, proc, args) => {
+                        self.used(*proc);
+                        for arg in args {
+                            self.used(*arg);
+                        }
+                        if let Some(name) = self.procs.get(proc) {
+                            if matches!(get_value(*name).unwrap().as_str(), "list" | "reverse") {
+                                self.fresh.insert(*t, *proc);
+                            }
+                        }
+                        self.calls.push((*proc, args.clone()));
+                    }
+                    IR::Move(_, s) | IR::Copy(_, s) | IR::Car(_, s) | IR::Cdr(_, s) | IR::Define(_, s) | IR::Return(s) |
+                    IR::Set(_, s) | IR::GotoIf(_, s) | IR::GotoIfNot(_, s) | IR::Switch(s, _, _) => self.used(*s),
+                    IR::Phi(_, conss, cons, alts, alt) => {
+                        self.used(*conss);
+                        self.used(*alts);
+                        self.scan(cons);
+                        self.scan(alt);
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    // Call the procedure ` linear-update` chooses from each procedure in `producers` and the
+    // procedure which made the list passed to it
+    fn rewrite(ir: &mut Vec<IR>, producers: &HashMap<Symbol, Symbol>) {
+        let mut idx = 0;
+        while idx < ir.len() {
+            match &mut ir[idx] {
+                IR::Call(_, proc, _) if producers.contains_key(&*proc) => {
+                    let producer = producers[&*proc];
+                    let (check, chosen) = (gen_var(), gen_var());
+                    let original = mem::replace(proc, chosen);
+                    ir.insert(idx, IR::Call(chosen, check, vec![original, producer]));
+                    ir.insert(idx, IR::Lookup(check, get_symbol(" linear-update".to_string())));
+                    idx += 2;
+                }
+                IR::Phi(_, _, cons, _, alt) => {
+                    rewrite(cons, producers);
+                    rewrite(alt, producers);
+                }
+                IR::Fn(_, _, body) => optimize_linear_updates(body),
+                _ => (),
+            }
+            idx += 1;
+        }
+    }
+
+    let mut scan = Scan::default();
+    scan.scan(ir);
+
+    let mut producers = HashMap::new();
+    for (proc, args) in &scan.calls {
+        let name = match scan.procs.get(proc) {
+            Some(name) => get_value(*name).unwrap(),
+            None => continue,
+        };
+        // `append!` only modifies the lists before the last, so only the first of two needs to
+        // be unshared
+        if !matches!((name.as_str(), args.len()), ("reverse", 1) | ("append", 2)) {
+            continue;
+        }
+        if let Some(producer) = scan.unshared(args[0]) {
+            producers.insert(*proc, producer);
+        }
+    }
+    rewrite(ir, &producers);
+}
+
 fn optimize_lookups(ir: &mut Vec<IR>) {
     fn inner(ir: &mut Vec<IR>, lookups: &mut HashMap<Symbol, Symbol>) {
         for i in ir.iter_mut() {
@@ -99,10 +218,16 @@ fn optimize_lookups(ir: &mut Vec<IR>) {
                         lookups.insert(ident, *target);
                     }
                 }
+                // The variable, or any variable for a call, may be assigned a new value
+                IR::Set(ident, _) => {
+                    lookups.remove(ident);
+                }
+                IR::Call(..) => lookups.clear(),
                 IR::Fn(_, _, ir) => optimize_lookups(ir),
+                // A lookup made in one branch isn't made in the other, or after the PHI
                 IR::Phi(_, _, cons, _, alt) => {
-                    inner(cons, lookups);
-                    inner(alt, lookups);
+                    inner(cons, &mut lookups.clone());
+                    inner(alt, &mut lookups.clone());
                 },
                 _ => (),
             }
@@ -113,16 +238,56 @@ fn optimize_lookups(ir: &mut Vec<IR>) {
 
 }
 
+/// Compile calls to `car`, `cdr`, `cons` and `vector-ref` to their instructions, which the machine
+/// runs without making a call as long as the names are bound to the primitives. The procedure is
+/// still looked up, so a redefinition is called instead.
+fn optimize_primitive_calls(ir: &mut [IR]) {
+    fn inner(ir: &mut [IR], procs: &mut HashMap<Symbol, Symbol>) {
+        for i in ir.iter_mut() {
+            match i {
+                IR::Lookup(t, ident) => { procs.insert(*t, *ident); }
+                IR::Call(t, proc, args) => {
+                    if let Some(p) = procs.get(proc).and_then(|name| primitive(*name, args.len())) {
+                        *i = IR::CallPrimitive(*t, *proc, mem::take(args), p);
+                    }
+                }
+                IR::Phi(_, _, cons, _, alt) => {
+                    inner(cons, procs);
+                    inner(alt, procs);
+                }
+                IR::Fn(_, _, body) => optimize_primitive_calls(body),
+                _ => (),
+            }
+        }
+    }
+
+    // The instruction of the primitive named `name`, if it takes `argc` arguments.
+    fn primitive(name: Symbol, argc: usize) -> Option<Instruction> {
+        match (get_value(name).unwrap().as_str(), argc) {
+            ("car", 1) => Some(Instruction::Car),
+            ("cdr", 1) => Some(Instruction::Cdr),
+            ("cons", 2) => Some(Instruction::Cons),
+            ("vector-ref", 2) => Some(Instruction::VectorRef),
+            _ => None,
+        }
+    }
+
+    inner(ir, &mut HashMap::new());
+}
+
 fn optimize_dead_code(ir: &mut Vec<IR>) {
     fn intern(ir: &Vec<IR>, used: &mut HashSet<Symbol>) {
         for i in ir.iter().rev() {
             match i {
                 IR::GotoIf(_, s) => { used.insert(*s); }
                 IR::GotoIfNot(_, s) => { used.insert(*s); }
+                IR::Switch(s, _, _) => { used.insert(*s); }
                 //IR::Param(s) => { used.insert(*s); }
                 IR::Return(s) => { used.insert(*s); }
-                IR::Define(_, s) => { used.insert(*s); }
-                IR::Call(_, s, args) => {
+                IR::Define(_, s) | IR::Set(_, s) => { used.insert(*s); }
+                // Kept even when unused, as they fail on values which don't match
+                IR::Car(_, s) | IR::Cdr(_, s) => { used.insert(*s); }
+                IR::Call(_, s, args) | IR::CallPrimitive(_, s, args, _) => {
                     used.insert(*s);
                     for arg in args {
                         used.insert(*arg);
@@ -139,30 +304,36 @@ fn optimize_dead_code(ir: &mut Vec<IR>) {
             }
         }
     }
-    let mut used = HashSet::new();
-    intern(ir, &mut used);
-
-    let mut idx = 0;
-    while idx < ir.len() {
-        match &mut ir[idx] {
-            IR::Fn(s, _, ir) => if !used.contains(s) {
-                ir.remove(idx);
-                continue;
-            } else {
-                optimize_dead_code(ir);
-            },
-            IR::Primitive(s, _) => if !used.contains(s) {
-                ir.remove(idx);
-                continue;
-            },
-            IR::Lookup(s, _) => if !used.contains(s) {
-                ir.remove(idx);
-                continue;
-            },
-            _ => (),
+    fn remove(ir: &mut Vec<IR>, used: &HashSet<Symbol>) {
+        let mut idx = 0;
+        while idx < ir.len() {
+            match &mut ir[idx] {
+                IR::Fn(s, _, ir) => if !used.contains(s) {
+                    ir.remove(idx);
+                    continue;
+                } else {
+                    optimize_dead_code(ir);
+                },
+                IR::Primitive(s, _) => if !used.contains(s) {
+                    ir.remove(idx);
+                    continue;
+                },
+                IR::Lookup(s, _) => if !used.contains(s) {
+                    ir.remove(idx);
+                    continue;
+                },
+                IR::Phi(_, _, cons, _, alt) => {
+                    remove(cons, used);
+                    remove(alt, used);
+                }
+                _ => (),
+            }
+            idx += 1;
         }
-        idx += 1;
     }
+    let mut used = HashSet::new();
+    intern(ir, &mut used);
+    remove(ir, &used);
 }
 
 fn optimize_copies(ir: &mut Vec<IR>) {
@@ -191,6 +362,9 @@ fn optimize_copies(ir: &mut Vec<IR>) {
                 IR::GotoIfNot(a, s) => if let Some(t) = copies.get(s) {
                     ir[idx] = IR::GotoIfNot(*a, *t);
                 },
+                IR::Switch(s, _, _) => if let Some(t) = copies.get(s) {
+                    *s = *t;
+                },
                 // TODO: PHI
                 IR::Phi(_, s1, ir1, s2, ir2) => {
                     intern(ir1, copies);
@@ -203,8 +377,11 @@ fn optimize_copies(ir: &mut Vec<IR>) {
                         *s2 = *t;
                     }
                 }
-                IR::Define(b, s) => if let Some(t) = copies.get(s) {
-                    ir[idx] = IR::Define(*b, *t);
+                IR::Define(_, s) | IR::Set(_, s) => if let Some(&t) = copies.get(s) {
+                    *s = t;
+                },
+                IR::Car(_, s) | IR::Cdr(_, s) => if let Some(&t) = copies.get(s) {
+                    *s = t;
                 },
                 //IR::Param(s) => if let Some(t) = copies.get(s) {
                 //    ir[idx] = IR::Param(*t);
@@ -241,10 +418,64 @@ pub fn output_asm(ir: Vec<IR>) -> Vec<ASM> {
         live: HashMap::new(),
         //stack: Vec::new(),
         stack: 0,
+        procs: HashSet::new(),
     };
     output._output_asm(ir, Register(0))
 }
 
+/// Symbol `case`s with at most this many keys compare the key against each symbol in turn.
+const SYMBOL_COMPARISONS: usize = 4;
+/// Symbol `case`s with at least this many keys dispatch through a perfect hash table.
+const SYMBOL_HASH: usize = 8;
+
+/// Choose how to dispatch on the key of a `case`. Dense integer keys index a jump table, a few
+/// symbols are compared directly and many symbols are hashed, anything else is looked up by binary
+/// search. Comparisons are made in `t`, which must hold no live variable.
+fn switch(r: Register, t: Register, table: Vec<(Value, Symbol)>, default: Symbol, asm: &mut Vec<ASM>) {
+    if !table.is_empty() && table.iter().all(|(v, _)| v.is_symbol()) {
+        if table.len() <= SYMBOL_COMPARISONS {
+            // Interned symbols are equal exactly when their ids are
+            for (v, l) in table {
+                asm.push(ASM::LoadConst(t, v));
+                asm.push(ASM::Eq(t, r, t));
+                asm.push(ASM::GotoIf(GotoValue::Label(l), t));
+            }
+            asm.push(ASM::Goto(GotoValue::Label(default)));
+            return;
+        } else if table.len() >= SYMBOL_HASH {
+            let table = table.into_iter().map(|(v, l)| (v.to_symbol(), l)).collect();
+            asm.push(ASM::PerfectHash(r, table, default));
+            return;
+        }
+    }
+
+    if !table.is_empty() && table.iter().all(|(v, _)| v.is_integer()) {
+        let min = table.iter().map(|(v, _)| v.to_integer()).min().unwrap();
+        let max = table.iter().map(|(v, _)| v.to_integer()).max().unwrap();
+        let span = (max as i64 - min as i64 + 1) as usize;
+        // At least half of the table must be filled
+        if span <= 2 * table.len() {
+            let mut labels = vec![default; span];
+            for (v, l) in table {
+                labels[(v.to_integer() - min) as usize] = l;
+            }
+            asm.push(ASM::JumpTable(r, min, labels, default));
+            return;
+        }
+    }
+    asm.push(ASM::BinarySearch(r, table, default));
+}
+
+/// The number of positions `i` takes up in the order used for liveness. The instructions in the
+/// branches of a PHI follow it, so that variables used inside them are live for exactly as long as
+/// they are used.
+fn width(i: &IR) -> usize {
+    match i {
+        IR::Phi(_, _, cons, _, alt) => 1 + cons.iter().chain(alt).map(width).sum::<usize>(),
+        _ => 1,
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum M {
     R(Register),
@@ -261,6 +492,8 @@ struct Output {
     live: HashMap<Symbol, usize>,
     //stack: Vec<Symbol>,
     stack: usize,
+    // The variables holding procedures which are called
+    procs: HashSet<Symbol>,
 }
 
 impl Output {
@@ -268,8 +501,11 @@ impl Output {
         self.register_allocation(&ir, target);
 
         let mut asm = Vec::new();
-        for (idx, i) in ir.into_iter().enumerate() {
+        let mut idx = 0;
+        for i in ir {
+            let w = width(&i);
             self._output_asm_inner(idx, i, target, &mut asm);
+            idx += w;
         }
         asm
     }
@@ -286,34 +522,38 @@ impl Output {
                     let r2 = self.find_symbol(s2, asm);
                     asm.push(ASM::Define(r, r2));
                 }
+                IR::Set(n, s2) => {
+                    let r2 = self.find_symbol(s2, asm);
+                    // The value may have been left in the register the name is usually loaded into
+                    let r = if r2 == Register(17) { Register(18) } else { Register(17) };
+                    asm.push(ASM::LoadConst(r, Value::Symbol(n)));
+                    asm.push(ASM::Set(r, r2));
+                }
                 IR::Lookup(s, ident) => {
                     let r = self.get_register(s, asm, idx);
                     asm.push(ASM::LoadConst(r, Value::Symbol(ident)));
-                    asm.push(ASM::Lookup(r, r));
-                }
-                IR::Call(s, proc, args) => {
-                    for (r, s) in &self.used {
-                        if idx < *self.live.get(s).unwrap() {
-                            self.var_stack.push(*s);
-                            //self.var_location.insert(*s, M::S(self.stack));
-                            self.stack += 1;
-                            asm.push(ASM::Save(*r));
-                        }
-                    }
-                    for (i, arg) in args.iter().enumerate() {
-                        self.load_symbol(*arg, Register(i as u8 + 1), asm);
+                    if self.procs.contains(&s) {
+                        asm.push(ASM::LookupCached(r, r));
+                    } else {
+                        asm.push(ASM::Lookup(r, r));
                     }
-
+                }
+                IR::Car(s, pair) => {
+                    let p = self.find_symbol(pair, asm);
+                    let r = self.get_register(s, asm, idx);
+                    asm.push(ASM::Car(r, p));
+                }
+                IR::Cdr(s, pair) => {
+                    let p = self.find_symbol(pair, asm);
+                    let r = self.get_register(s, asm, idx);
+                    asm.push(ASM::Cdr(r, p));
+                }
+                IR::Call(s, proc, args) => self.call(idx, s, proc, &args, None, asm),
+                IR::CallPrimitive(s, proc, args, p) => self.call(idx, s, proc, &args, Some(p), asm),
+                IR::TailCall(proc, args) => {
+                    self.load_arguments(proc, &args, asm);
                     let r = self.find_symbol(proc, asm);
-                    asm.push(ASM::Call(r));
-                    if Register(0) != self.lookup_register(s) {
-                        asm.push(ASM::Move(self.lookup_register(s), Register(0)));
-                    }
-                    //self.var_location.insert(s, M::R(self.lookup_register(s)));
-                    self.var_reg = [None; 32];
-                    self.var_reg[self.lookup_register(s).0 as usize] = Some(s);
-                    self.used.clear();
-                    self.used.insert(self.lookup_register(s), s);
+                    asm.push(ASM::TailCall(r, args.len()));
                 }
                 IR::Fn(s, args, ir) => {
                     let mut output = Output {
@@ -325,6 +565,7 @@ impl Output {
                         live: HashMap::new(),
                         //stack: Vec::new(),
                         stack: 0,
+                        procs: HashSet::new(),
                     };
                     for (i, arg) in args.iter().enumerate() {
                         output.var_mapping.insert(*arg, Register(i as u8 + 1));
@@ -340,6 +581,11 @@ impl Output {
                 IR::Goto(l) => asm.push(ASM::Goto(GotoValue::Label(l))),
                 IR::GotoIf(l, s) => asm.push(ASM::GotoIf(GotoValue::Label(l), self.lookup_register(s))),
                 IR::GotoIfNot(l, s) => asm.push(ASM::GotoIfNot(GotoValue::Label(l), self.lookup_register(s))),
+                IR::Switch(s, table, default) => {
+                    let r = self.find_symbol(s, asm);
+                    let t = self.free_register(r);
+                    switch(r, t, table, default, asm);
+                }
                 IR::Return(s) => {
                     self.load_symbol(s, target, asm);
                     /*
@@ -354,10 +600,11 @@ impl Output {
                 }
                 // Not needed after register allocation
                 IR::Phi(union, conss, cons, alts, alt) => {
-                    // TODO: save variables that outlive this PHI, convert cons to asm followed by
-                    // alt
+                    // TODO: convert cons to asm followed by alt
+                    // Save variables that outlive this PHI
+                    let end = idx + cons.iter().chain(&alt).map(width).sum::<usize>();
                     for (r, s) in &self.used {
-                        if idx < *self.live.get(s).unwrap() {
+                        if self.live.get(s).is_some_and(|&l| end < l) {
                             //self.var_location.insert(*s, M::S(self.stack));
                             self.var_stack.push(*s);
                             self.stack += 1;
@@ -366,21 +613,34 @@ impl Output {
                     }
 
                     let mut c = self.clone();
+                    let mut i_idx = idx + 1;
                     for i in cons {
-                        c._output_asm_inner(idx, i, target, asm);
+                        let w = width(&i);
+                        c._output_asm_inner(i_idx, i, target, asm);
+                        i_idx += w;
                     }
-                    let cons_pos = c.var_reg.iter().position(|x| *x == Some(conss)).unwrap();
+                    // A branch which ends in a return or tail call never reaches the union
+                    let cons_pos = c.var_reg.iter().position(|x| *x == Some(conss));
                     //self.var_location.insert(conss, *c.var_location.get(&conss).unwrap());
                     let mut a = self.clone();
                     for i in alt {
-                        a._output_asm_inner(idx, i, target, asm);
+                        let w = width(&i);
+                        a._output_asm_inner(i_idx, i, target, asm);
+                        i_idx += w;
                     }
-                    let alt_pos = a.var_reg.iter().position(|x| *x == Some(alts)).unwrap();
+                    let alt_pos = a.var_reg.iter().position(|x| *x == Some(alts));
                     //self.var_location.insert(alts, *a.var_location.get(&alts).unwrap());
                     //assert_eq!(self.var_location.get(&conss).unwrap(), self.var_location.get(&alts).unwrap());
-                    assert_eq!(cons_pos, alt_pos);
+                    let pos = match (cons_pos, alt_pos) {
+                        (Some(c), Some(a)) => {
+                            assert_eq!(c, a);
+                            c
+                        }
+                        (Some(p), None) | (None, Some(p)) => p,
+                        (None, None) => self.lookup_register(union).0 as usize,
+                    };
                     //self.var_location.insert(union, *self.var_location.get(&conss).unwrap());
-                    self.var_reg[alt_pos] = Some(union);
+                    self.var_reg[pos] = Some(union);
                 }
                 //IR::Param(_) => (),
                 // Only used for optimization
@@ -388,23 +648,68 @@ impl Output {
             }
     }
 
+    /// Output a call to `proc`, through the instruction of `primitive` if it is one.
+    fn call(&mut self, idx: usize, s: Symbol, proc: Symbol, args: &[Symbol], primitive: Option<Instruction>,
+            asm: &mut Vec<ASM>) {
+        // Unused formals have no liveness, and never need saving
+        for (r, s) in &self.used {
+            if self.live.get(s).is_some_and(|&l| idx < l) {
+                self.var_stack.push(*s);
+                //self.var_location.insert(*s, M::S(self.stack));
+                self.stack += 1;
+                asm.push(ASM::Save(*r));
+            }
+        }
+        self.load_arguments(proc, args, asm);
+        let r = self.find_symbol(proc, asm);
+        match primitive {
+            Some(p) => asm.push(ASM::CallPrimitive(r, p)),
+            None => asm.push(ASM::Call(r, args.len())),
+        }
+        if Register(0) != self.lookup_register(s) {
+            asm.push(ASM::Move(self.lookup_register(s), Register(0)));
+        }
+        //self.var_location.insert(s, M::R(self.lookup_register(s)));
+        self.var_reg = [None; 32];
+        self.var_reg[self.lookup_register(s).0 as usize] = Some(s);
+        self.used.clear();
+        self.used.insert(self.lookup_register(s), s);
+    }
+
     fn register_allocation(&mut self, ir: &[IR], target: Register) {
         // Iterate in reverse
-        for (idx, i) in ir.iter().enumerate().rev() {
+        let mut idx = ir.iter().map(width).sum();
+        for i in ir.iter().rev() {
+            idx -= width(i);
             self.reg_alloc_inner(i, idx, target);
         }
     }
     fn reg_alloc_inner(&mut self, i: &IR, idx: usize, target: Register) {
         match i {
-            IR::Call(s, proc, ref args) => {
+            IR::Call(s, proc, ref args) | IR::CallPrimitive(s, proc, ref args, _) => {
                 self.live.entry(*s).or_insert(idx);
                 self.live.entry(*proc).or_insert(idx);
+                self.procs.insert(*proc);
                 self.var_mapping.insert(*proc, Register(0));
                 for (i, arg) in args.iter().enumerate() {
                     self.var_mapping.insert(*arg, Register(i as u8 + 1));
                     self.live.entry(*arg).or_insert(idx);
                 }
             }
+            IR::TailCall(proc, ref args) => {
+                self.live.entry(*proc).or_insert(idx);
+                self.procs.insert(*proc);
+                self.var_mapping.insert(*proc, Register(0));
+                for (i, arg) in args.iter().enumerate() {
+                    self.var_mapping.insert(*arg, Register(i as u8 + 1));
+                    self.live.entry(*arg).or_insert(idx);
+                }
+            }
+            IR::Set(_, s) => {
+                let r = self.lookup_register(*s);
+                self.var_mapping.insert(*s, r);
+                self.live.entry(*s).or_insert(idx);
+            }
             IR::Define(_, s) => if !self.live.contains_key(&s) {
                 self.live.insert(*s, idx);
             }
@@ -417,15 +722,15 @@ impl Output {
                     self.live.insert(*conss, i);
                     self.live.insert(*alts, i);
                 } else {
-                    self.live.insert(*s1, idx);
-                    self.live.insert(*conss, idx);
-                    self.live.insert(*alts, idx);
+                    let end = idx + width(i) - 1;
+                    self.live.insert(*s1, end);
+                    self.live.insert(*conss, end);
+                    self.live.insert(*alts, end);
                 }
-                for i in alt.iter().rev() {
-                    self.reg_alloc_inner(i, idx, target);
-                }
-                for i in cons.iter().rev() {
-                    self.reg_alloc_inner(i, idx, target);
+                let mut i_idx = idx + width(i);
+                for i in alt.iter().rev().chain(cons.iter().rev()) {
+                    i_idx -= width(i);
+                    self.reg_alloc_inner(i, i_idx, target);
                 }
                 //self.reg_alloc_inner(&alt, target);
                 //self.reg_alloc_inner(&cons, target);
@@ -436,8 +741,11 @@ impl Output {
                 self.var_mapping.insert(*s, target);
                 self.live.entry(*s).or_insert(idx);
             }
-            IR::GotoIf(_, s) => { self.live.entry(*s).or_insert(idx); }
-            IR::GotoIfNot(_, s) => { self.live.entry(*s).or_insert(idx); }
+            IR::GotoIf(_, s) | IR::GotoIfNot(_, s) | IR::Switch(s, _, _) => {
+                let r = self.lookup_register(*s);
+                self.var_mapping.insert(*s, r);
+                self.live.entry(*s).or_insert(idx);
+            }
             IR::Goto(_) | IR::Label(_) => (),
             IR::Fn(s, _, _) => if !self.var_mapping.contains_key(&s) {
                 panic!("Dead code?");
@@ -448,15 +756,44 @@ impl Output {
             IR::Lookup(s, _) => if !self.live.contains_key(&s) {
                 panic!("Dead code?");
             },
+            IR::Car(s, pair) | IR::Cdr(s, pair) => {
+                self.live.entry(*s).or_insert(idx);
+                let r = self.lookup_register(*pair);
+                self.var_mapping.insert(*pair, r);
+                self.live.entry(*pair).or_insert(idx);
+            }
             // Only used for optimization
             IR::Copy(_, _) => unreachable!(),
         }
     }
 
+    /// Load `args` into the argument registers of a call to `proc`.
+    fn load_arguments(&mut self, proc: Symbol, args: &[Symbol], asm: &mut Vec<ASM>) {
+        for (i, arg) in args.iter().enumerate() {
+            // Move a value which is still needed out of the way before loading over
+            // it, as when the arguments are a permutation of the formals
+            if let Some(s) = self.var_reg[i + 1] {
+                let needed = s != *arg && (s == proc || args[i + 1..].contains(&s));
+                if needed && self.var_reg.iter().filter(|x| **x == Some(s)).count() == 1 {
+                    let free = (args.len() + 1..Register::FP.0 as usize).rev().find(|&r| self.var_reg[r].is_none()).unwrap();
+                    asm.push(ASM::Move(Register(free as u8), Register(i as u8 + 1)));
+                    self.var_reg[free] = Some(s);
+                }
+            }
+            self.load_symbol(*arg, Register(i as u8 + 1), asm);
+        }
+    }
+
+    /// A register other than `r` which holds no variable.
+    fn free_register(&self, r: Register) -> Register {
+        let free = (1..Register::FP.0 as usize).rev().find(|&f| f != r.0 as usize && self.var_reg[f].is_none()).unwrap();
+        Register(free as u8)
+    }
+
     fn get_register(&mut self, s: Symbol, asm: &mut Vec<ASM>, idx: usize) -> Register {
         let r = self.lookup_register(s);
         if let Some(s) = self.used.get(&r) {
-            if idx <= *self.live.get(s).unwrap() {
+            if self.live.get(s).is_some_and(|&l| idx <= l) {
                 //self.var_location.insert(*s, M::S(self.stack));
                 self.var_stack.push(*s);
                 self.stack += 1;
@@ -0,0 +1,155 @@
+//! Tail calls, calls whose result is returned at once by the procedure making them. They replace
+//! the frame of their caller instead of returning to it, so loops written as recursion run in
+//! constant space, but the caller is missing from stack traces.
+
+use super::IR;
+
+use string_interner::{get_value, Symbol};
+
+use std::fmt;
+
+/// Turn calls in tail position inside procedures into tail calls, including calls at the end of
+/// either branch of an `if` or `case` whose value is returned.
+pub fn optimize_tail_calls(ir: &mut [IR]) {
+    for i in ir.iter_mut() {
+        if let IR::Fn(_, _, body) = i {
+            optimize_tail_calls(body);
+            tail(body);
+        } else if let IR::Phi(_, _, cons, _, alt) = i {
+            optimize_tail_calls(cons);
+            optimize_tail_calls(alt);
+        }
+    }
+}
+
+/// Make the call returned by `body` a tail call. Returns whether any were made.
+fn tail(body: &mut Vec<IR>) -> bool {
+    let len = body.len();
+    let r = match body.last() {
+        Some(IR::Return(r)) => *r,
+        _ => return false,
+    };
+    if len >= 2 && matches!(&body[len - 2], IR::Call(s, _, _) if *s == r) {
+        body.pop();
+        if let Some(IR::Call(_, proc, args)) = body.pop() {
+            body.push(IR::TailCall(proc, args));
+        }
+        return true;
+    }
+
+    // The result of a PHI is returned after the label its branches jump to
+    let phi = match body[..len - 1].iter().rposition(|i| !matches!(i, IR::Label(_))) {
+        Some(p) => p,
+        None => return false,
+    };
+    if let IR::Phi(u, conss, cons, alts, alt) = &mut body[phi] {
+        if *u == r {
+            let c = tail_branch(cons, *conss);
+            let a = tail_branch(alt, *alts);
+            return c || a;
+        }
+    }
+    false
+}
+
+/// Return `var`, the value of a branch of a PHI, from the end of the branch rather than moving it
+/// to the union, if that makes a tail call.
+fn tail_branch(branch: &mut Vec<IR>, var: Symbol) -> bool {
+    let original = branch.clone();
+    match branch.as_slice() {
+        [.., IR::Move(_, x), IR::Goto(_)] if *x == var => {
+            branch.truncate(branch.len() - 2);
+            branch.push(IR::Return(var));
+        }
+        [.., IR::Move(_, x)] if *x == var => {
+            branch.pop();
+            branch.push(IR::Return(var));
+        }
+        // The remaining clauses of a `case`
+        [.., IR::Phi(u, _, _, _, _)] if *u == var => branch.push(IR::Return(var)),
+        _ => return false,
+    }
+    if tail(branch) {
+        true
+    } else {
+        *branch = original;
+        false
+    }
+}
+
+/// A call compiled as a tail call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TailCall {
+    /// The name the calling procedure was defined with.
+    pub caller: Option<Symbol>,
+    /// The variable the called procedure was looked up in.
+    pub callee: Option<Symbol>,
+}
+
+impl fmt::Display for TailCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.caller {
+            Some(s) => write!(f, "{}: ", get_value(s).unwrap())?,
+            None => write!(f, "lambda: ")?,
+        }
+        match self.callee {
+            Some(s) => write!(f, "tail call to {}", get_value(s).unwrap()),
+            None => write!(f, "tail call to a procedure"),
+        }
+    }
+}
+
+/// The calls in optimized `ir` which were compiled as tail calls, in order.
+pub fn tail_calls(ir: &[IR]) -> Vec<TailCall> {
+    let mut calls = Vec::new();
+    find_tail_calls(ir, &mut calls);
+    calls
+}
+
+fn find_tail_calls(ir: &[IR], calls: &mut Vec<TailCall>) {
+    for i in ir {
+        match i {
+            IR::Fn(s, formals, body) => {
+                let caller = ir.iter().find_map(|i| match i {
+                    IR::Define(name, v) if v == s => Some(*name),
+                    _ => None,
+                });
+                let mut procs = Vec::new();
+                tail_call_procs(body, &mut procs);
+                for proc in procs {
+                    let callee = if formals.contains(&proc) { Some(proc) } else { lookup(body, proc) };
+                    calls.push(TailCall { caller, callee });
+                }
+                find_tail_calls(body, calls);
+            }
+            IR::Phi(_, _, cons, _, alt) => {
+                find_tail_calls(cons, calls);
+                find_tail_calls(alt, calls);
+            }
+            _ => (),
+        }
+    }
+}
+
+// The procedures tail called in `body`, not including nested procedures.
+fn tail_call_procs(body: &[IR], procs: &mut Vec<Symbol>) {
+    for i in body {
+        match i {
+            IR::TailCall(proc, _) => procs.push(*proc),
+            IR::Phi(_, _, cons, _, alt) => {
+                tail_call_procs(cons, procs);
+                tail_call_procs(alt, procs);
+            }
+            _ => (),
+        }
+    }
+}
+
+// The variable `s` was looked up from in `body`.
+fn lookup(body: &[IR], s: Symbol) -> Option<Symbol> {
+    body.iter().find_map(|i| match i {
+        IR::Lookup(t, ident) if *t == s => Some(*ident),
+        IR::Phi(_, _, cons, _, alt) => lookup(cons, s).or_else(|| lookup(alt, s)),
+        _ => None,
+    })
+}
@@ -1,6 +1,8 @@
 use vm::Value;
 
-use string_interner::Symbol;
+use string_interner::{get_value, Symbol};
+
+use std::fmt;
 
 #[derive(Clone, Debug)]
 pub enum Ast {
@@ -8,6 +10,11 @@ pub enum Ast {
         name: Symbol,
         value: Box<Ast>,
     },
+    /// `(set! name value)` Assign to the nearest binding of `name`.
+    Set {
+        name: Symbol,
+        value: Box<Ast>,
+    },
     Lambda {
         args: Vec<Symbol>,
         body: Vec<Ast>,
@@ -17,13 +24,44 @@ pub enum Ast {
         consequent: Box<Ast>,
         alternative: Box<Ast>,
     },
+    Case {
+        key: Box<Ast>,
+        clauses: Vec<(Vec<Value>, Vec<Ast>)>,
+        default: Vec<Ast>,
+    },
+    Destructure {
+        pattern: Pattern,
+        value: Box<Ast>,
+        body: Vec<Ast>,
+    },
     Begin(Vec<Ast>),
     Apply(Vec<Ast>),
     Ident(Symbol),
     Primitive(Value),
+    /// `#!name` A directive to the compiler, which has no value.
+    Directive(Symbol),
+}
+
+/// The pattern of a `destructuring-bind`.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    /// Bind the whole value to a name.
+    Bind(Symbol),
+    /// `_` matches anything without binding it.
+    Ignore,
+    /// `(p ...)` or `(p ... . rest)`, where the elements after those matched by `p ...` are ignored
+    /// if there is no `rest`.
+    List(Vec<Pattern>, Option<Box<Pattern>>),
 }
 
 impl Ast {
+    pub fn is_ident(&self, name: &str) -> bool {
+        match self {
+            Ast::Ident(s) => get_value(*s).as_deref() == Some(name),
+            _ => false,
+        }
+    }
+
     pub fn unwrap_define(self) -> (Symbol, Self) {
         match self {
             Ast::Define { name, value } => (name, *value),
@@ -31,6 +69,13 @@ impl Ast {
         }
     }
 
+    pub fn unwrap_set(self) -> (Symbol, Self) {
+        match self {
+            Ast::Set { name, value } => (name, *value),
+            _ => unreachable!(),
+        }
+    }
+
     pub fn unwrap_if(self) -> (Self, Self, Self) {
         match self {
             Ast::If { predicate, consequent, alternative } =>
@@ -39,6 +84,13 @@ impl Ast {
         }
     }
 
+    pub fn unwrap_case(self) -> (Self, Vec<(Vec<Value>, Vec<Self>)>, Vec<Self>) {
+        match self {
+            Ast::Case { key, clauses, default } => (*key, clauses, default),
+            _ => unreachable!(),
+        }
+    }
+
     pub fn unwrap_lambda(self) -> (Vec<Symbol>, Vec<Self>) {
         match self {
             Ast::Lambda { args, body } => (args, body),
@@ -46,6 +98,13 @@ impl Ast {
         }
     }
 
+    pub fn unwrap_destructure(self) -> (Pattern, Self, Vec<Self>) {
+        match self {
+            Ast::Destructure { pattern, value, body } => (pattern, *value, body),
+            _ => unreachable!(),
+        }
+    }
+
     pub fn unwrap_begin(self) -> Vec<Self> {
         match self {
             Ast::Begin(b) => b,
@@ -53,3 +112,80 @@ impl Ast {
         }
     }
 }
+
+fn name(s: Symbol) -> String {
+    get_value(s).unwrap()
+}
+
+fn write_all(f: &mut fmt::Formatter, exps: &[Ast]) -> fmt::Result {
+    for e in exps {
+        write!(f, " {}", e)?;
+    }
+    Ok(())
+}
+
+/// Write the expression as source code. Quoted data are written in full, but derived forms such as
+/// `for/list` are written as what they expand into.
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Define { name: n, value } => write!(f, "(define {} {})", name(*n), value),
+            Ast::Set { name: n, value } => write!(f, "(set! {} {})", name(*n), value),
+            Ast::Lambda { args, body } => {
+                let args: Vec<_> = args.iter().map(|&a| name(a)).collect();
+                write!(f, "(lambda ({})", args.join(" "))?;
+                write_all(f, body)?;
+                write!(f, ")")
+            }
+            Ast::If { predicate, consequent, alternative } =>
+                write!(f, "(if {} {} {})", predicate, consequent, alternative),
+            Ast::Case { key, clauses, default } => {
+                write!(f, "(case {}", key)?;
+                for (values, body) in clauses {
+                    let values: Vec<_> = values.iter().map(|v| format!("{}", v)).collect();
+                    write!(f, " (({})", values.join(" "))?;
+                    write_all(f, body)?;
+                    write!(f, ")")?;
+                }
+                write!(f, " (else")?;
+                write_all(f, default)?;
+                write!(f, "))")
+            }
+            Ast::Destructure { pattern, value, body } => {
+                write!(f, "(destructuring-bind {} {}", pattern, value)?;
+                write_all(f, body)?;
+                write!(f, ")")
+            }
+            Ast::Begin(exps) => {
+                write!(f, "(begin")?;
+                write_all(f, exps)?;
+                write!(f, ")")
+            }
+            Ast::Apply(exps) => {
+                let exps: Vec<_> = exps.iter().map(|e| format!("{}", e)).collect();
+                write!(f, "({})", exps.join(" "))
+            }
+            Ast::Ident(s) => write!(f, "{}", name(*s)),
+            Ast::Primitive(v) if v.is_symbol() || v.is_pair() || v.is_nil() => write!(f, "'{}", v),
+            Ast::Primitive(v) => write!(f, "{}", v),
+            Ast::Directive(s) => write!(f, "#!{}", name(*s)),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Bind(s) => write!(f, "{}", name(*s)),
+            Pattern::Ignore => write!(f, "_"),
+            Pattern::List(patterns, rest) => {
+                let patterns: Vec<_> = patterns.iter().map(|p| format!("{}", p)).collect();
+                write!(f, "({}", patterns.join(" "))?;
+                if let Some(rest) = rest {
+                    write!(f, " . {}", rest)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
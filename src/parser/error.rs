@@ -4,12 +4,18 @@ use std::fmt::{self, Display, Formatter};
 pub enum ParseError {
     EOF,
     InString,
-    Input,
-    Token,
+    /// A `\` in a string followed by a character which doesn't start an escape, or an escape
+    /// which doesn't denote a character, with the line and column of the `\`.
+    BadEscape(char, usize, usize),
+    /// Input which isn't what the grammar calls for there, with a description of what it does.
+    Expected(&'static str),
     UnbalancedParen,
     BadQuote,
     UnexpectedCloseParen,
     IllegalUse,
+    BadMacro,
+    NoMatchingRule,
+    MacroDepth,
 }
 
 impl Display for ParseError {
@@ -17,12 +23,17 @@ impl Display for ParseError {
         match self {
             ParseError::EOF => write!(f, "Unexpected end of input"),
             ParseError::InString => write!(f, "Unexpected end of input in string"),
-            ParseError::Input => write!(f, "Unexpected input"),
-            ParseError::Token => write!(f, "Unexpected token"),
+            ParseError::BadEscape(c, line, column) => {
+                write!(f, "Invalid escape `\\{}` in string at {}:{}", c.escape_debug(), line, column)
+            }
+            ParseError::Expected(what) => write!(f, "Expected {}", what),
             ParseError::UnbalancedParen => write!(f, "Expected a `)` to close `(`"),
             ParseError::BadQuote => write!(f, "Expected an element for quoting, found EOF"),
             ParseError::UnexpectedCloseParen => write!(f, "Unexpected `)`"),
             ParseError::IllegalUse => write!(f, "Illegal use of `.`"),
+            ParseError::BadMacro => write!(f, "Malformed macro"),
+            ParseError::NoMatchingRule => write!(f, "No rule of the macro matches its use"),
+            ParseError::MacroDepth => write!(f, "Too many macro expansions nested in one another"),
         }
     }
 }
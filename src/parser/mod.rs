@@ -1,13 +1,14 @@
 mod ast;
 mod error;
 
-pub use self::ast::Ast;
+pub use self::ast::{Ast, Pattern};
 pub use self::error::ParseError;
 
-use Token;
+use {Token, Tokenizer};
 use vm::Value;
 
-use string_interner::get_value;
+use num_bigint::BigInt;
+use string_interner::{get_symbol, get_value, Symbol};
 
 use std::iter::Peekable;
 use std::slice::Iter;
@@ -22,6 +23,75 @@ macro_rules! t {
     };
 }
 
+/// A number with exactness and radix prefixes, given the text after the first `#`, e.g. `x1f` or
+/// `e#b101`. Without rationals, only integral numbers can be made exact, and only integers can be
+/// written in a radix other than 10.
+fn prefixed_number(s: &str) -> Result<Value, ParseError> {
+    const EXPECTED: ParseError = ParseError::Expected("a number after `#e`, `#i`, `#x`, `#o`, `#b` or `#d`");
+    let (mut exact, mut radix) = (None, None);
+    let mut rest = s;
+    loop {
+        let mut chars = rest.chars();
+        match chars.next().map(|c| c.to_ascii_lowercase()) {
+            Some(c @ ('e' | 'i')) if exact.is_none() => exact = Some(c == 'e'),
+            Some('x') if radix.is_none() => radix = Some(16),
+            Some('o') if radix.is_none() => radix = Some(8),
+            Some('b') if radix.is_none() => radix = Some(2),
+            Some('d') if radix.is_none() => radix = Some(10),
+            _ => return Err(EXPECTED),
+        }
+        rest = chars.as_str();
+        match rest.strip_prefix('#') {
+            Some(r) => rest = r,
+            None => break,
+        }
+    }
+
+    let n = match radix {
+        Some(radix) if radix != 10 => Value::integer(radix_integer(rest, radix).ok_or(EXPECTED)?),
+        _ => match Tokenizer::tokenize(rest)?.as_slice() {
+            [t @ Token::Integer(_)] | [t @ Token::BigInt(_)] | [t @ Token::Float(_)] => t.to_primitive(),
+            _ => return Err(EXPECTED),
+        },
+    };
+    match exact {
+        Some(true) if n.is_float() => {
+            let f = n.to_float();
+            if f.fract() != 0.0 || !f.is_finite() {
+                return Err(ParseError::Expected("an integral number after `#e`, as there are no rationals"));
+            }
+            Ok(Value::integer(format!("{:.0}", f).parse().unwrap()))
+        }
+        Some(false) if n.is_integer() => Ok(Value::Float(n.to_integer() as f64)),
+        Some(false) if n.is_bigint() => Ok(Value::Float(format!("{}", n).parse().unwrap())),
+        _ => Ok(n),
+    }
+}
+
+/// The integer written in `radix` by `s`, with an optional sign.
+fn radix_integer(s: &str, radix: u32) -> Option<BigInt> {
+    let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    BigInt::parse_bytes(s.as_bytes(), radix)
+}
+
+/// The expression `(cons car cdr)`, or the pair itself if both are constant.
+fn cons(car: Ast, cdr: Ast) -> Ast {
+    match (car, cdr) {
+        (Ast::Primitive(car), Ast::Primitive(cdr)) => Ast::Primitive(Value::Pair(car, cdr)),
+        (car, cdr) => Ast::Apply(vec![Ast::Ident(get_symbol("cons".to_string())), car, cdr]),
+    }
+}
+
+#[derive(Copy, Clone)]
+enum For {
+    List,
+    Fold,
+    Hash,
+}
+
 pub struct Parser<'a> {
     ast: Vec<Ast>,
     tokens: Peekable<Iter<'a, Token>>,
@@ -43,9 +113,22 @@ impl<'a> Parser<'a> {
         Ok(parser.ast)
     }
 
+    /// Parse the tokens of a single datum, as from `Tokenizer::tokenize_datum`, into its value.
+    pub fn parse_datum(tokens: Vec<Token>) -> Result<Value, ParseError> {
+        let mut parser = Parser {
+            ast: Vec::new(),
+            tokens: tokens.iter().peekable(),
+        };
+        let datum = parser._parse_quote()?;
+        if parser.tokens.next().is_some() {
+            return Err(ParseError::Expected("a single datum"));
+        }
+        Ok(datum)
+    }
+
     fn _parse(&mut self) -> Result<Ast, ParseError> {
         match t!(self.tokens.next()) {
-            Token::Comment(_) | Token::BlockComment(_) => self._parse(),
+            Token::Comment(_) | Token::BlockComment(_) | Token::DatumComment(_) => self._parse(),
             Token::LeftParen => self.parse_expr(),
             Token::Quote => self.parse_quote(false),
             Token::Symbol(s) => Ok(Ast::Ident(*s)),
@@ -53,10 +136,11 @@ impl<'a> Parser<'a> {
             Token::RightParen => Err(ParseError::UnexpectedCloseParen),
             Token::Pound => self.parse_pound(),
             Token::Dot => Err(ParseError::IllegalUse),
-            Token::Quasiquote => unimplemented!(),
-            Token::Unquote => unimplemented!(),
-            Token::UnquoteSplice => unimplemented!(),
-            Token::String(_) | Token::Float(_) | Token::Integer(_) => unreachable!(),
+            Token::Quasiquote => self.parse_quasiquote(false),
+            // Only allowed in a quasiquote
+            Token::Unquote | Token::UnquoteSplice => Err(ParseError::IllegalUse),
+            Token::String(_) | Token::Float(_) | Token::Integer(_) | Token::BigInt(_) | Token::Char(_) =>
+                unreachable!(),
         }
     }
 
@@ -65,29 +149,69 @@ impl<'a> Parser<'a> {
             Token::Symbol(s) => match get_value(*s).unwrap().as_str() {
                 "t" => Ok(Ast::Primitive(Value::Bool(true))),
                 "f" => Ok(Ast::Primitive(Value::Bool(false))),
-                _ => todo!(),
+                "!no-tail-call" => Ok(Ast::Directive(get_symbol("no-tail-call".to_string()))),
+                "u8" => self.parse_bytevector(),
+                s if s.starts_with(['e', 'i', 'x', 'o', 'b', 'd', 'E', 'I', 'X', 'O', 'B', 'D']) =>
+                    prefixed_number(s).map(Ast::Primitive),
+                _ => Err(ParseError::Expected("a boolean, vector, bytevector, number or directive after `#`")),
             }
-            //Token::LeftParen => {
-            //}
-            _ => todo!(),
+            Token::LeftParen => self.parse_vector(),
+            _ => Err(ParseError::Expected("a boolean, vector, bytevector, number or directive after `#`")),
         }
     }
 
-    fn parse_expr(&mut self) -> Result<Ast, ParseError> {
-        match t!(self.tokens.next()) {
-            Token::Symbol(s) => match get_value(*s).unwrap().as_str() {
-                "define" => self.parse_define(),
-                "lambda" => self.parse_lambda(),
-                "if" => self.parse_if(),
-                "begin" => self.parse_begin(),
-                "quote" => self.parse_quote(true),
-                _ => self.parse_application(Ast::Ident(*s)),
+    /// `#(datum ...)`, after the `(`. Vectors are self-evaluating, their elements are quoted.
+    fn parse_vector(&mut self) -> Result<Ast, ParseError> {
+        let mut elements = Vec::new();
+        while !t!(self.tokens.peek()).is_right_paren() {
+            elements.push(self._parse_quote()?);
+        }
+        self.tokens.next();
+        Ok(Ast::Primitive(Value::Vec(elements)))
+    }
+
+    /// `#u8(byte ...)`, after the `u8`. Bytevectors are self-evaluating.
+    fn parse_bytevector(&mut self) -> Result<Ast, ParseError> {
+        if !t!(self.tokens.next()).is_left_paren() {
+            return Err(ParseError::Expected("`(` after `#u8`"));
+        }
+        let mut bytes = Vec::new();
+        loop {
+            match t!(self.tokens.next()) {
+                Token::RightParen => return Ok(Ast::Primitive(Value::Bytevector(bytes))),
+                Token::Integer(n) if 0 <= *n && *n <= u8::MAX as i32 => bytes.push(*n as u8),
+                _ => return Err(ParseError::Expected("a byte from 0 to 255 in a bytevector")),
             }
-            Token::LeftParen => {
-                let op = self.parse_expr()?;
-                self.parse_application(op)
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Ast, ParseError> {
+        let s = match t!(self.tokens.peek()) {
+            Token::Symbol(s) => *s,
+            Token::RightParen => return Err(ParseError::Expected("an expression to apply in `()`")),
+            // Anything else is applied, and fails when run if it isn't a procedure
+            _ => {
+                let op = self._parse()?;
+                return self.parse_application(op);
             }
-            _ => unimplemented!(),
+        };
+        self.tokens.next();
+        match get_value(s).unwrap().as_str() {
+            "define" => self.parse_define(),
+            "define-memoized" => self.parse_define_memoized(),
+            "set!" => self.parse_set(),
+            "lambda" => self.parse_lambda(),
+            "if" => self.parse_if(),
+            "case" => self.parse_case(),
+            "begin" => self.parse_begin(),
+            "quote" => self.parse_quote(true),
+            "quasiquote" => self.parse_quasiquote(true),
+            "destructuring-bind" => self.parse_destructuring_bind(),
+            "for/list" => self.parse_for(For::List),
+            "for/fold" => self.parse_for(For::Fold),
+            "for/hash" => self.parse_for(For::Hash),
+            "define-library" | "import" => self.parse_library_form(s),
+            _ => self.parse_application(Ast::Ident(s)),
         }
     }
 
@@ -99,9 +223,9 @@ impl<'a> Parser<'a> {
                 proc = true;
                 *s
             } else {
-                return Err(ParseError::Input);
+                return Err(ParseError::Expected("a name to define"));
             },
-            _ => return Err(ParseError::Input),
+            _ => return Err(ParseError::Expected("a name or signature to define")),
         };
 
         let value = if proc {
@@ -110,7 +234,7 @@ impl<'a> Parser<'a> {
                 match t!(self.tokens.next()) {
                     Token::Symbol(s) => args.push(*s),
                     Token::RightParen => break,
-                    _ => return Err(ParseError::Input),
+                    _ => return Err(ParseError::Expected("a symbol in the formals")),
                 }
             }
             Ast::Lambda{
@@ -129,18 +253,81 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// `(define-memoized [limit] (name formal ...) body ...)` defines `name` as
+    /// `(memoize (lambda (formal ...) body ...) [limit])`.
+    fn parse_define_memoized(&mut self) -> Result<Ast, ParseError> {
+        let mut memoize = vec![Ast::Ident(get_symbol("memoize".to_string()))];
+        let limit = match t!(self.tokens.next()) {
+            Token::LeftParen => None,
+            Token::Integer(n) => {
+                if !t!(self.tokens.next()).is_left_paren() {
+                    return Err(ParseError::Expected("a signature after the cache size"));
+                }
+                Some(Ast::Primitive(Value::Integer(*n)))
+            }
+            _ => return Err(ParseError::Expected("a cache size or signature to define")),
+        };
+
+        let name = match t!(self.tokens.next()) {
+            Token::Symbol(s) => *s,
+            _ => return Err(ParseError::Expected("a name to define")),
+        };
+        let mut args = Vec::new();
+        loop {
+            match t!(self.tokens.next()) {
+                Token::Symbol(s) => args.push(*s),
+                Token::RightParen => break,
+                _ => return Err(ParseError::Expected("a symbol in the formals")),
+            }
+        }
+        memoize.push(Ast::Lambda {
+            args: args,
+            body: self.lambda_body()?,
+        });
+        memoize.extend(limit);
+
+        Ok(Ast::Define {
+            name: name,
+            value: Box::new(Ast::Apply(memoize)),
+        })
+    }
+
+    /// `(define-library name declaration ...)` and `(import set ...)` call the procedures of the
+    /// same names with their parts quoted.
+    fn parse_library_form(&mut self, name: Symbol) -> Result<Ast, ParseError> {
+        let mut apply = vec![Ast::Ident(name)];
+        while !t!(self.tokens.peek()).is_right_paren() {
+            apply.push(Ast::Primitive(self._parse_quote()?));
+        }
+        self.tokens.next();
+        Ok(Ast::Apply(apply))
+    }
+
+    fn parse_set(&mut self) -> Result<Ast, ParseError> {
+        let name = match t!(self.tokens.next()) {
+            Token::Symbol(s) => *s,
+            _ => return Err(ParseError::Expected("a name to set")),
+        };
+        let value = self._parse()?;
+        self.read_closer()?;
+        Ok(Ast::Set {
+            name,
+            value: Box::new(value),
+        })
+    }
+
     fn parse_lambda(&mut self) -> Result<Ast, ParseError> {
         let mut args = vec![];
 
         if !t!(self.tokens.next()).is_left_paren() {
-            return Err(ParseError::Input);
+            return Err(ParseError::Expected("a list of formals"));
         }
 
         loop {
             match t!(self.tokens.next()) {
                 Token::Symbol(s) => args.push(*s),
                 Token::RightParen => break,
-                _ => return Err(ParseError::Input),
+                _ => return Err(ParseError::Expected("a symbol in the formals")),
             }
         }
 
@@ -161,6 +348,147 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// `(destructuring-bind pattern expr body ...)` evaluates `body` with the names in `pattern`
+    /// bound to the matching parts of the value of `expr`.
+    fn parse_destructuring_bind(&mut self) -> Result<Ast, ParseError> {
+        let pattern = self.parse_pattern()?;
+        let value = Box::new(self._parse()?);
+        let mut body = Vec::new();
+        while !t!(self.tokens.peek()).is_right_paren() {
+            body.push(self._parse()?);
+        }
+        self.tokens.next();
+        if body.is_empty() {
+            return Err(ParseError::Expected("an expression in the body"));
+        }
+
+        Ok(Ast::Destructure {
+            pattern,
+            value,
+            body,
+        })
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        match t!(self.tokens.next()) {
+            Token::Symbol(s) if get_value(*s).unwrap() == "_" => Ok(Pattern::Ignore),
+            Token::Symbol(s) => Ok(Pattern::Bind(*s)),
+            Token::LeftParen => {
+                let mut items = Vec::new();
+                loop {
+                    match t!(self.tokens.peek()) {
+                        Token::RightParen => {
+                            self.tokens.next();
+                            return Ok(Pattern::List(items, None));
+                        }
+                        Token::Dot if !items.is_empty() => {
+                            self.tokens.next();
+                            let rest = self.parse_pattern()?;
+                            self.read_closer()?;
+                            return Ok(Pattern::List(items, Some(Box::new(rest))));
+                        }
+                        _ => items.push(self.parse_pattern()?),
+                    }
+                }
+            }
+            _ => Err(ParseError::Expected("a symbol or list in the pattern")),
+        }
+    }
+
+    /// Comprehensions over a single sequence, which may be anything `fold` accepts. The body
+    /// becomes one procedure which `fold` calls on each element, with the accumulator as its last
+    /// argument:
+    ///
+    /// - `(for/list ([x seq]) body ...)` is
+    ///   `(reverse! (fold (lambda (x acc) (cons (begin body ...) acc)) '() seq))`.
+    /// - `(for/fold ([acc init]) ([x seq]) body ...)` is `(fold (lambda (x acc) body ...) init seq)`.
+    /// - `(for/hash ([x seq]) body ... (values key value))` is
+    ///   `(fold (lambda (x acc) body ... (hash-table-set! acc key value) acc) (make-hash-table) seq)`.
+    ///
+    /// The binding of a clause may also be a list of names, such as `(k v)` for a hash table.
+    fn parse_for(&mut self, kind: For) -> Result<Ast, ParseError> {
+        let ident = |s: &str| Ast::Ident(get_symbol(s.to_string()));
+        let (acc, init) = match kind {
+            For::Fold => {
+                self.expect_left_paren()?;
+                self.expect_left_paren()?;
+                let acc = match t!(self.tokens.next()) {
+                    Token::Symbol(s) => *s,
+                    _ => return Err(ParseError::Expected("the name of the accumulator")),
+                };
+                let init = self._parse()?;
+                self.read_closer()?;
+                self.read_closer()?;
+                (acc, init)
+            }
+            For::List => (get_symbol(" for/list".to_string()), Ast::Primitive(Value::Nil)),
+            For::Hash => (get_symbol(" for/hash".to_string()), Ast::Apply(vec![ident("make-hash-table")])),
+        };
+
+        self.expect_left_paren()?;
+        self.expect_left_paren()?;
+        let mut args = match t!(self.tokens.next()) {
+            Token::Symbol(s) => vec![*s],
+            Token::LeftParen => {
+                let mut args = Vec::new();
+                loop {
+                    match t!(self.tokens.next()) {
+                        Token::Symbol(s) => args.push(*s),
+                        Token::RightParen => break,
+                        _ => return Err(ParseError::Expected("a symbol in the names to bind")),
+                    }
+                }
+                args
+            }
+            _ => return Err(ParseError::Expected("a name or list of names to bind")),
+        };
+        args.push(acc);
+        let seq = self._parse()?;
+        self.read_closer()?;
+        self.read_closer()?;
+
+        let mut body = Vec::new();
+        while !t!(self.tokens.peek()).is_right_paren() {
+            body.push(self._parse()?);
+        }
+        self.tokens.next();
+        if body.is_empty() {
+            return Err(ParseError::Expected("an expression in the body"));
+        }
+
+        let body = match kind {
+            For::Fold => body,
+            For::List => {
+                let last = if body.len() == 1 { body.pop().unwrap() } else { Ast::Begin(body) };
+                vec![Ast::Apply(vec![ident("cons"), last, Ast::Ident(acc)])]
+            }
+            For::Hash => match body.pop() {
+                Some(Ast::Apply(mut values)) if values.len() == 3 && values[0].is_ident("values") => {
+                    let value = values.pop().unwrap();
+                    let key = values.pop().unwrap();
+                    body.push(Ast::Apply(vec![ident("hash-table-set!"), Ast::Ident(acc), key, value]));
+                    body.push(Ast::Ident(acc));
+                    body
+                }
+                _ => return Err(ParseError::Expected("`(values key value)` at the end of the body")),
+            },
+        };
+
+        let fold = Ast::Apply(vec![ident("fold"), Ast::Lambda { args, body }, init, seq]);
+        Ok(match kind {
+            For::List => Ast::Apply(vec![ident("reverse!"), fold]),
+            For::Fold | For::Hash => fold,
+        })
+    }
+
+    fn expect_left_paren(&mut self) -> Result<(), ParseError> {
+        if t!(self.tokens.next()).is_left_paren() {
+            Ok(())
+        } else {
+            Err(ParseError::Expected("`(`"))
+        }
+    }
+
     fn parse_if(&mut self) -> Result<Ast, ParseError> {
         let predicate = Box::new(self._parse()?);
         let consequent = Box::new(self._parse()?);
@@ -179,17 +507,64 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_begin(&mut self) -> Result<Ast, ParseError> {
-        let mut sequence = vec![];
+    fn parse_case(&mut self) -> Result<Ast, ParseError> {
+        let key = Box::new(self._parse()?);
+        let mut clauses = Vec::new();
+        let mut default = Vec::new();
         loop {
             match t!(self.tokens.next()) {
-                Token::RightParen => return Ok(Ast::Begin(sequence)),
-                Token::LeftParen => sequence.push(self.parse_expr()?),
-                Token::Symbol(s) => sequence.push(Ast::Ident(*s)),
-                t if t.is_primitive() => sequence.push(Ast::Primitive(t.to_primitive())),
-                _ => return Err(ParseError::Input),
+                Token::RightParen => break,
+                Token::LeftParen => (),
+                _ => return Err(ParseError::Expected("a clause")),
+            }
+
+            // The `else` clause must come last
+            if !default.is_empty() {
+                return Err(ParseError::Expected("the `else` clause to be last"));
+            }
+
+            let data = match t!(self.tokens.next()) {
+                Token::Symbol(s) if get_value(*s).unwrap() == "else" => None,
+                Token::LeftParen => {
+                    let mut data = Vec::new();
+                    while !t!(self.tokens.peek()).is_right_paren() {
+                        data.push(self._parse_quote()?);
+                    }
+                    self.tokens.next();
+                    Some(data)
+                }
+                _ => return Err(ParseError::Expected("a list of data or `else`")),
+            };
+
+            let mut body = Vec::new();
+            while !t!(self.tokens.peek()).is_right_paren() {
+                body.push(self._parse()?);
+            }
+            self.tokens.next();
+            if body.is_empty() {
+                return Err(ParseError::Expected("an expression in the body"));
+            }
+
+            match data {
+                Some(data) => clauses.push((data, body)),
+                None => default = body,
             }
         }
+
+        Ok(Ast::Case {
+            key,
+            clauses,
+            default,
+        })
+    }
+
+    fn parse_begin(&mut self) -> Result<Ast, ParseError> {
+        let mut sequence = vec![];
+        while !t!(self.tokens.peek()).is_right_paren() {
+            sequence.push(self._parse()?);
+        }
+        self.tokens.next();
+        Ok(Ast::Begin(sequence))
     }
 
     fn parse_application(&mut self, op: Ast) -> Result<Ast, ParseError> {
@@ -215,36 +590,160 @@ impl<'a> Parser<'a> {
     fn _parse_quote(&mut self) -> Result<Value, ParseError> {
         match t!(self.tokens.next()) {
             Token::LeftParen => self.quote_list(),
+            t @ Token::Quote | t @ Token::Quasiquote | t @ Token::Unquote | t @ Token::UnquoteSplice => {
+                let prefix = Value::Symbol(get_symbol(t.prefix_name().unwrap().to_string()));
+                Ok(Value::Pair(prefix, Value::Pair(self._parse_quote()?, Value::Nil)))
+            }
+            Token::Pound => match self.parse_pound()? {
+                Ast::Primitive(v) => Ok(v),
+                _ => Err(ParseError::Expected("a datum after `#`")),
+            },
             Token::Symbol(s) => Ok(Value::Symbol(*s)),
             t if t.is_primitive() => Ok(t.to_primitive()),
-            _ => Err(ParseError::Input),
+            Token::Dot => Err(ParseError::IllegalUse),
+            _ => Err(ParseError::Expected("a datum")),
         }
     }
 
+    /// A quoted list, after the `(`. It is improper if its last element follows a `.`, as in
+    /// `(a b . c)`.
     fn quote_list(&mut self) -> Result<Value, ParseError> {
-        let mut parens = 1;
-        let mut list_rev = Vec::new();
-        while parens != 0 {
-            if t!(self.tokens.peek()).is_right_paren() {
-                self.tokens.next();
-                parens -= 1;
-            } else {
-                list_rev.push(self._parse_quote()?);
+        let mut elements = Vec::new();
+        let mut tail = Value::Nil;
+        loop {
+            match t!(self.tokens.peek()) {
+                Token::RightParen => {
+                    self.tokens.next();
+                    break;
+                }
+                Token::Dot if !elements.is_empty() => {
+                    self.tokens.next();
+                    tail = self._parse_quote()?;
+                    self.read_closer()?;
+                    break;
+                }
+                _ => elements.push(self._parse_quote()?),
             }
         }
+        Ok(elements.into_iter().rev().fold(tail, |l, v| Value::Pair(v, l)))
+    }
+
+    /// `` `template `` or `(quasiquote template)`, after the `` ` `` or `quasiquote`. The template
+    /// is quoted, except for the expressions after `,` and `,@`, whose values are put in it.
+    /// `,@` splices the elements of a list into the list around it. Quasiquotes can be nested, and
+    /// only unquotes belonging to the outermost one are evaluated.
+    fn parse_quasiquote(&mut self, read_closer: bool) -> Result<Ast, ParseError> {
+        let template = self.quasiquote(1)?;
+        if read_closer {
+            self.read_closer()?;
+        }
+        Ok(template)
+    }
+
+    /// The expression which builds the template starting at the next token, inside `level`
+    /// quasiquotes.
+    fn quasiquote(&mut self, level: usize) -> Result<Ast, ParseError> {
+        match t!(self.tokens.next()) {
+            Token::LeftParen => self.quasiquote_list(level),
+            t @ Token::Quote | t @ Token::Quasiquote | t @ Token::Unquote | t @ Token::UnquoteSplice =>
+                self.quasiquote_prefixed(t.prefix_name().unwrap(), level),
+            Token::Pound => match self.parse_pound()? {
+                p @ Ast::Primitive(_) => Ok(p),
+                _ => Err(ParseError::Expected("a datum after `#`")),
+            },
+            Token::Symbol(s) => Ok(Ast::Primitive(Value::Symbol(*s))),
+            t if t.is_primitive() => Ok(Ast::Primitive(t.to_primitive())),
+            Token::Dot => Err(ParseError::IllegalUse),
+            _ => Err(ParseError::Expected("a datum")),
+        }
+    }
+
+    /// `(prefix datum)` in a template, after the `prefix`.
+    fn quasiquote_prefixed(&mut self, prefix: &str, level: usize) -> Result<Ast, ParseError> {
+        let datum = match (prefix, level) {
+            ("unquote", 1) => return self._parse(),
+            // Splicing needs a list around it
+            ("unquote-splicing", 1) => return Err(ParseError::IllegalUse),
+            ("unquote", _) | ("unquote-splicing", _) => self.quasiquote(level - 1)?,
+            ("quasiquote", _) => self.quasiquote(level + 1)?,
+            _ => self.quasiquote(level)?,
+        };
+        let prefix = Ast::Primitive(Value::Symbol(get_symbol(prefix.to_string())));
+        Ok(cons(prefix, cons(datum, Ast::Primitive(Value::Nil))))
+    }
 
-        let mut list = Value::Nil;
-        while !list_rev.is_empty() {
-            list = Value::Pair(list_rev.pop().unwrap(), list);
+    /// A list in a template, after the `(`, which may be improper.
+    fn quasiquote_list(&mut self, level: usize) -> Result<Ast, ParseError> {
+        if let Some(prefix) = self.peek_prefix_form() {
+            self.tokens.next();
+            let datum = self.quasiquote_prefixed(&prefix, level)?;
+            self.read_closer()?;
+            return Ok(datum);
         }
 
-        Ok(list)
+        // Each element, and whether it is spliced
+        let mut elements = Vec::new();
+        let mut tail = Ast::Primitive(Value::Nil);
+        loop {
+            match t!(self.tokens.peek()) {
+                Token::RightParen => {
+                    self.tokens.next();
+                    break;
+                }
+                Token::Dot if !elements.is_empty() => {
+                    self.tokens.next();
+                    tail = self.quasiquote(level)?;
+                    self.read_closer()?;
+                    break;
+                }
+                Token::UnquoteSplice if level == 1 => {
+                    self.tokens.next();
+                    elements.push((self._parse()?, true));
+                }
+                Token::LeftParen if level == 1 => {
+                    self.tokens.next();
+                    if self.peek_prefix_form().as_deref() == Some("unquote-splicing") {
+                        self.tokens.next();
+                        elements.push((self._parse()?, true));
+                        self.read_closer()?;
+                    } else {
+                        elements.push((self.quasiquote_list(level)?, false));
+                    }
+                }
+                _ => elements.push((self.quasiquote(level)?, false)),
+            }
+        }
+
+        let ident = |s: &str| Ast::Ident(get_symbol(s.to_string()));
+        Ok(elements.into_iter().rev().fold(tail, |tail, (element, spliced)| {
+            if spliced {
+                Ast::Apply(vec![ident("append"), element, tail])
+            } else {
+                cons(element, tail)
+            }
+        }))
+    }
+
+    /// The name of the prefix, if the next tokens are `quote`, `quasiquote`, `unquote`, or
+    /// `unquote-splicing` written out, followed by a single datum.
+    fn peek_prefix_form(&self) -> Option<String> {
+        let mut tokens = self.tokens.clone();
+        let name = match tokens.next() {
+            Some(Token::Symbol(s)) => get_value(*s).unwrap(),
+            _ => return None,
+        };
+        let prefix = matches!(name.as_str(), "quote" | "quasiquote" | "unquote" | "unquote-splicing");
+        if prefix && tokens.next().is_some_and(|t| !t.is_right_paren()) {
+            Some(name)
+        } else {
+            None
+        }
     }
 
     fn read_closer(&mut self) -> Result<(), ParseError> {
         if let Some(token) = self.tokens.next() {
             if token != &Token::RightParen {
-                return Err(ParseError::Input);
+                return Err(ParseError::Expected("`)`"));
             }
         } else {
             return Err(ParseError::UnbalancedParen);
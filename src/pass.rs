@@ -0,0 +1,19 @@
+//! Transformations of the syntax tree supplied by the host.
+//!
+//! A pass added with `Minerva::add_pass` sees each top level form after its macros are expanded and
+//! before it is compiled, so an embedder can rewrite or instrument code, e.g. to fold calls to its
+//! own procedures or to count them, without changing the compiler. Passes run in the order they
+//! were added, each on the result of the one before.
+
+use Ast;
+
+/// A transformation of a top level form.
+pub trait AstPass {
+    fn run(&mut self, ast: Ast) -> Ast;
+}
+
+impl<F: FnMut(Ast) -> Ast> AstPass for F {
+    fn run(&mut self, ast: Ast) -> Ast {
+        self(ast)
+    }
+}
@@ -0,0 +1,179 @@
+//! Reading source text a form at a time, and procedures which read and write data as text using
+//! the same reader as source code.
+//!
+//! A `Reader` reads the forms of its input one at a time, taking only as many lines as the next
+//! form needs, so a large file needn't be read whole. It says when the text so far ends inside a
+//! form, apart from errors, so that a REPL given lines from elsewhere knows to ask for another.
+//!
+//! The procedures need the tokenizer and parser, so unlike the rest of the initial environment
+//! they are defined here rather than by the machine.
+
+use {Error, Location, ParseError, Parser, Token, Tokenizer};
+
+use vm::{define_native, Arity, Environment, Value, VM};
+
+use std::io::BufRead;
+
+/// A top level form read by a `Reader`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Form {
+    /// The text of the form, without the whitespace and comments before it.
+    pub text: String,
+    /// Its tokens without comments, for `Parser::parse`.
+    pub tokens: Vec<Token>,
+    /// Where it is in the input.
+    pub location: Location,
+}
+
+/// What a `Reader` read next.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Next {
+    Form(Form),
+    /// The input ended inside the form starting at the location. More lines given with
+    /// `push_line` may finish it.
+    Incomplete(Location),
+    /// The input ended, with nothing but whitespace and comments after the last form.
+    Eof,
+}
+
+/// Reads the forms of `input` one at a time.
+pub struct Reader<R> {
+    name: String,
+    input: R,
+    // Text taken from the input which hasn't been read as a form yet
+    text: String,
+    // Where `text` starts in the input
+    start: Location,
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Read from `input`, which is called `name` in errors.
+    pub fn new(name: &str, input: R) -> Self {
+        Reader {
+            name: name.to_string(),
+            input,
+            text: String::new(),
+            start: Location::default(),
+        }
+    }
+
+    /// Add `line` to the text to read, as though it were the next line of the input. A REPL can
+    /// read from `io::empty()` and give each line it reads here.
+    pub fn push_line(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+    }
+
+    /// Read the next form, taking lines from the input until it is complete. An error is returned
+    /// as `Error::At` with where it was found, and the rest of the text taken so far is discarded.
+    pub fn read(&mut self) -> Result<Next, Error> {
+        loop {
+            match Tokenizer::tokenize_datum_located(&self.text) {
+                Ok(Some((tokens, location))) => return Ok(Next::Form(self.take(tokens, location))),
+                Ok(None) => {
+                    let len = self.text.chars().count();
+                    self.advance(len);
+                }
+                // A form, string or comment which may go on in the next line
+                Err((ParseError::EOF, _)) | Err((ParseError::InString, _)) => (),
+                Err((e, location)) => {
+                    let location = self.in_input(location);
+                    let len = self.text.chars().count();
+                    self.advance(len);
+                    return Err(Error::At(self.name.clone(), location, Box::new(Error::Parse(e))));
+                }
+            }
+            let n = self.input.read_line(&mut self.text).map_err(|e| Error::Io(e.to_string()))?;
+            if n == 0 {
+                return Ok(match Tokenizer::tokenize_datum_located(&self.text) {
+                    Err((_, location)) => Next::Incomplete(self.in_input(location)),
+                    _ => Next::Eof,
+                });
+            }
+        }
+    }
+
+    // The form with `tokens` at `location` in `text`, which is then discarded with the text before
+    // it.
+    fn take(&mut self, tokens: Vec<Token>, location: Location) -> Form {
+        let text = self.text.chars().skip(location.span.start).take(location.span.len()).collect();
+        let location = self.in_input(location);
+        self.advance(location.span.end - self.start.span.start);
+        Form { text, tokens, location }
+    }
+
+    // `location` in `text` as a location in the input.
+    fn in_input(&self, location: Location) -> Location {
+        let offset = self.start.span.start;
+        Location {
+            line: self.start.line + location.line - 1,
+            column: if location.line == 1 { self.start.column + location.column - 1 } else { location.column },
+            span: offset + location.span.start..offset + location.span.end,
+        }
+    }
+
+    // Discard the first `n` characters of `text`.
+    fn advance(&mut self, n: usize) {
+        let end = self.text.char_indices().nth(n).map_or(self.text.len(), |(i, _)| i);
+        for c in self.text.drain(..end) {
+            self.start.span.start += 1;
+            if c == '\n' {
+                self.start.line += 1;
+                self.start.column = 1;
+            } else {
+                self.start.column += 1;
+            }
+        }
+        self.start.span.end = self.start.span.start;
+    }
+}
+
+/// The initial environment of the machine, together with the procedures which need the reader or
+/// the compiler.
+pub fn init_env() -> Environment {
+    let env = ::vm::init_env();
+    define_native(&env, "read-from-string", Arity::Range(1, 2), read_from_string).unwrap();
+    define_native(&env, "write-to-string", Arity::Exactly(1), write_to_string).unwrap();
+    define_native(&env, "load", Arity::Exactly(1), ::load::load).unwrap();
+    define_native(&env, "load-module", Arity::Exactly(2), ::module::load_module).unwrap();
+    define_native(&env, "reload-module", Arity::Exactly(1), ::module::reload_module).unwrap();
+    define_native(&env, "define-library", Arity::AtLeast(1), ::library::define_library).unwrap();
+    define_native(&env, "import", Arity::AtLeast(0), ::library::import).unwrap();
+    define_native(&env, "add-library-path!", Arity::Exactly(1), ::library::add_library_path).unwrap();
+    env
+}
+
+/// `(read-from-string string [start])` Read the first datum of `string` from the character index
+/// `start`, returning a pair of the datum and the index after it. Returns the eof object if only
+/// whitespace and comments remain.
+pub fn read_from_string(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !args[0].is_string() {
+        return Err(format!("read-from-string: {} is not a string", args[0]));
+    }
+    let s = args[0].to_string();
+    let text = s.str.clone();
+    Box::into_raw(s);
+
+    let len = text.chars().count();
+    let start = match args.get(1) {
+        None => 0,
+        Some(v) if v.is_integer() && v.to_integer() >= 0 && v.to_integer() as usize <= len =>
+            v.to_integer() as usize,
+        Some(v) => return Err(format!("read-from-string: {} is not a valid index", v)),
+    };
+    let offset = text.char_indices().nth(start).map_or(text.len(), |(i, _)| i);
+
+    match Tokenizer::tokenize_datum(&text[offset..]) {
+        Ok(Some((tokens, read))) => {
+            let datum = Parser::parse_datum(tokens).map_err(|e| format!("read-from-string: {}", e))?;
+            Ok(Value::Pair(datum, Value::Integer((start + read) as i32)))
+        }
+        Ok(None) => Ok(Value::Eof),
+        Err(e) => Err(format!("read-from-string: {}", e)),
+    }
+}
+
+/// `(write-to-string obj)` The text `write` would write for `obj`.
+pub fn write_to_string(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::String(format!("{}", args[0])))
+}
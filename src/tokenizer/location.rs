@@ -0,0 +1,38 @@
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+/// Where a token or form is in the input: the line and column it starts at, counting from 1, and
+/// the characters it covers, counting from 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    pub span: Range<usize>,
+}
+
+impl Location {
+    /// The location from the start of `self` to the end of `other`.
+    pub fn to(&self, other: &Location) -> Location {
+        Location {
+            line: self.line,
+            column: self.column,
+            span: self.span.start..other.span.end,
+        }
+    }
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Location {
+            line: 1,
+            column: 1,
+            span: 0..0,
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
@@ -1,118 +1,292 @@
+mod location;
 mod token;
 
+pub use self::location::Location;
 pub use self::token::Token;
 
 use ParseError;
 
+use num_bigint::BigInt;
 use regex::Regex;
-use string_interner::get_symbol;
+use string_interner::{get_symbol, get_value};
 
+use std::convert::TryFrom;
 use std::iter::Peekable;
 use std::str::Chars;
 
 type ParseResult = Result<(), ParseError>;
+// An error with where in the input it was found
+type LocatedResult<T> = Result<T, (ParseError, Location)>;
 
 pub struct Tokenizer<'a> {
     position: usize,
+    line: usize,
+    column: usize,
     input: Peekable<Chars<'a>>,
     tokens: Vec<Token>,
+    // Where each of `tokens` is
+    locations: Vec<Location>,
+    // Where the token being read starts, and the last character read
+    start: Location,
+    last: Option<(char, Location)>,
 }
 
 impl<'a> Tokenizer<'a> {
-    pub fn tokenize(input: &'a str) -> Result<Vec<Token>, ParseError> {
-        let input = input.chars().peekable();
-        let mut tokenizer = Tokenizer {
+    fn new(input: &'a str) -> Self {
+        Tokenizer {
             position: 0,
-            input: input,
+            line: 1,
+            column: 1,
+            input: input.chars().peekable(),
             tokens: Vec::new(),
-        };
-        tokenizer._tokenize()?;
+            locations: Vec::new(),
+            start: Location::default(),
+            last: None,
+        }
+    }
+
+    pub fn tokenize(input: &'a str) -> Result<Vec<Token>, ParseError> {
+        let mut tokenizer = Tokenizer::new(input);
+        tokenizer._tokenize().map_err(|(e, _)| e)?;
 
         Ok(tokenizer.tokens)
     }
 
+    /// Tokenize `input`, with the location of each token. An error is returned with the location
+    /// of the token it was found in.
+    pub fn tokenize_located(input: &'a str) -> LocatedResult<Vec<(Token, Location)>> {
+        let mut tokenizer = Tokenizer::new(input);
+        tokenizer._tokenize()?;
+
+        Ok(tokenizer.tokens.into_iter().zip(tokenizer.locations).collect())
+    }
+
+    /// Tokenize `input` and split it into its top level forms, each with its tokens, without
+    /// comments, and its location. An error is returned with the location of the token it was
+    /// found in, or of the start of the form which the input ends in.
+    pub fn tokenize_forms(input: &'a str) -> LocatedResult<Vec<(Vec<Token>, Location)>> {
+        let mut tokenizer = Tokenizer::new(input);
+        tokenizer._tokenize()?;
+        let Tokenizer { tokens, locations, .. } = tokenizer;
+
+        let mut forms = Vec::new();
+        let mut datum = Datum::default();
+        let mut start = None;
+        for i in 0..tokens.len() {
+            if is_comment(&tokens[i]) {
+                continue;
+            }
+            let first = *start.get_or_insert(i);
+            if datum.read(&tokens, i).map_err(|e| (e, locations[i].clone()))? {
+                let form = tokens[first..=i].iter().filter(|t| !is_comment(t)).cloned().collect();
+                forms.push((form, locations[first].to(&locations[i])));
+                start = None;
+            }
+        }
+        match start {
+            Some(first) => Err((ParseError::EOF, locations[first].clone())),
+            None => Ok(forms),
+        }
+    }
+
+    /// Tokenize the first datum of `input`, skipping any whitespace and comments before it. Returns
+    /// the tokens of the datum and the number of characters read, or `None` if the input ends
+    /// before a datum starts.
+    pub fn tokenize_datum(input: &'a str) -> Result<Option<(Vec<Token>, usize)>, ParseError> {
+        let mut tokenizer = Tokenizer::new(input);
+        let mut datum = Datum::default();
+        while let Some(c) = tokenizer.next() {
+            let start = tokenizer.tokens.len();
+            tokenizer.token(c)?;
+            // Numbers and symbols may be read together with the delimiter which ends them
+            for i in start..tokenizer.tokens.len() {
+                if !is_comment(&tokenizer.tokens[i]) && datum.read(&tokenizer.tokens, i)? {
+                    let mut tokens = tokenizer.tokens;
+                    tokens.truncate(i + 1);
+                    tokens.retain(|t| !is_comment(t));
+                    return Ok(Some((tokens, tokenizer.position)));
+                }
+            }
+        }
+        if datum.depth > 0 || datum.prefixed {
+            Err(ParseError::EOF)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Tokenize the first datum of `input` as `tokenize_datum` does, returning its tokens and its
+    /// location rather than the number of characters read. The datum ends at its last token, not
+    /// at the delimiter after it. If the input ends inside the datum, the error is
+    /// `ParseError::EOF` or `ParseError::InString` with the location of the start of the datum or
+    /// the token which was cut off.
+    pub fn tokenize_datum_located(input: &'a str) -> LocatedResult<Option<(Vec<Token>, Location)>> {
+        let mut tokenizer = Tokenizer::new(input);
+        let mut datum = Datum::default();
+        let mut first = None;
+        loop {
+            let read = tokenizer.tokens.len();
+            if !tokenizer.located_token()? {
+                break;
+            }
+            for i in read..tokenizer.tokens.len() {
+                if is_comment(&tokenizer.tokens[i]) {
+                    continue;
+                }
+                let first = *first.get_or_insert(i);
+                if datum.read(&tokenizer.tokens, i).map_err(|e| (e, tokenizer.locations[i].clone()))? {
+                    let location = tokenizer.locations[first].to(&tokenizer.locations[i]);
+                    let mut tokens = tokenizer.tokens;
+                    tokens.truncate(i + 1);
+                    tokens.drain(..first);
+                    tokens.retain(|t| !is_comment(t));
+                    return Ok(Some((tokens, location)));
+                }
+            }
+        }
+        match first {
+            Some(first) => Err((ParseError::EOF, tokenizer.locations[first].clone())),
+            None => Ok(None),
+        }
+    }
+
     fn next(&mut self) -> Option<char> {
         if let Some(c) = self.input.next() {
+            let here = Location {
+                line: self.line,
+                column: self.column,
+                span: self.position..self.position + 1,
+            };
+            self.last = Some((c, here));
             self.position += 1;
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
             Some(c)
         } else {
             None
         }
     }
 
+    // Add a token which ends with the last character read, or just before it if that is the
+    // delimiter which ended a number or symbol
+    fn push(&mut self, token: Token) {
+        let delimited = match self.last {
+            Some((c, _)) => is_delimiter(c) && matches!(token, Token::Symbol(_) | Token::Integer(_) |
+                                                                Token::BigInt(_) | Token::Float(_)),
+            None => false,
+        };
+        let end = if delimited { self.position - 1 } else { self.position };
+        self.locations.push(Location { span: self.start.span.start..end, ..self.start.clone() });
+        self.tokens.push(token);
+        // A token read with the delimiter starts at it
+        if let (true, Some((_, here))) = (delimited, &self.last) {
+            self.start = here.clone();
+        }
+    }
+
     fn peek(&mut self) -> Option<char> {
         self.input.peek().copied()
     }
 
-    fn _tokenize(&mut self) -> ParseResult {
-        while let Some(c) = self.next() {
-            match c {
-                c if is_pair_start(c) => self.tokens.push(Token::LeftParen),
-                c if is_pair_end(c) => self.tokens.push(Token::RightParen),
-                '\'' => self.tokens.push(Token::Quote),
-                '`' => self.tokens.push(Token::Quasiquote),
-                ',' => match self.peek() {
-                    Some('@') => {
+    fn _tokenize(&mut self) -> LocatedResult<()> {
+        while self.located_token()? {}
+        Ok(())
+    }
+
+    // Tokenize the next token, if any, recording where it starts. Returns whether there was one.
+    fn located_token(&mut self) -> LocatedResult<bool> {
+        self.start = Location {
+            line: self.line,
+            column: self.column,
+            span: self.position..self.position,
+        };
+        match self.next() {
+            Some(c) => match self.token(c) {
+                Ok(()) => Ok(true),
+                // From the start of the token to where the error was found
+                Err(e) => {
+                    let span = self.start.span.start..self.position;
+                    Err((e, Location { span, ..self.start.clone() }))
+                }
+            },
+            None => Ok(false),
+        }
+    }
+
+    /// Tokenize the input starting with `c`.
+    fn token(&mut self, c: char) -> ParseResult {
+        match c {
+            c if is_pair_start(c) => self.push(Token::LeftParen),
+            c if is_pair_end(c) => self.push(Token::RightParen),
+            '\'' => self.push(Token::Quote),
+            '`' => self.push(Token::Quasiquote),
+            ',' => match self.peek() {
+                Some('@') => {
+                    self.next();
+                    self.push(Token::UnquoteSplice);
+                }
+                _ => self.push(Token::Unquote),
+            },
+            '"' => self.tokenize_string()?,
+            '|' => self.tokenize_identifier(String::new(), true)?,
+            ';' => self.tokenize_comment(c)?,
+            '#' => {
+                match self.peek() {
+                    Some('|') => {
                         self.next();
-                        self.tokens.push(Token::UnquoteSplice);
+                        self.tokenize_block_comment()?;
                     }
-                    _ => self.tokens.push(Token::Unquote),
-                },
-                '"' => self.tokenize_string()?,
-                '|' => self.tokenize_identifier(String::new(), true)?,
-                ';' => self.tokenize_comment(c)?,
-                '#' => {
-                    match self.peek() {
-                        Some('|') => {
-                            self.next();
-                            self.tokenize_block_comment()?;
-                        }
-                        _ => self.tokens.push(Token::Pound),
+                    Some(';') => {
+                        self.next();
+                        self.tokenize_datum_comment()?;
+                    }
+                    Some('\\') => {
+                        self.next();
+                        self.tokenize_char()?;
                     }
+                    _ => self.push(Token::Pound),
                 }
-                c if c.is_whitespace() => {}
-                '.' => match self.peek() {
-                    Some(c) => match c {
-                        c if is_delimiter(c) => self.tokens.push(Token::Dot),
-                        _ => self.tokenize_ambiguous('.')?,
-                    },
-                    None => self.tokens.push(Token::Dot),
+            }
+            c if c.is_whitespace() => {}
+            '.' => match self.peek() {
+                Some(c) => match c {
+                    c if is_delimiter(c) => self.push(Token::Dot),
+                    _ => self.tokenize_ambiguous('.')?,
                 },
-                '0' ..= '9' | '+' | '-' => self.tokenize_ambiguous(c)?,
-                _ => {
-                    let mut buf = String::new();
-                    match c {
-                        '\\' => match self.next() {
-                            Some(c) => buf.push(c),
-                            None => return Err(ParseError::EOF),
-                        },
-                        _ => buf.push(c),
-                    }
-                    self.tokenize_identifier(buf, false)?;
+                None => self.push(Token::Dot),
+            },
+            '0' ..= '9' | '+' | '-' => self.tokenize_ambiguous(c)?,
+            _ => {
+                let mut buf = String::new();
+                match c {
+                    '\\' => match self.next() {
+                        Some(c) => buf.push(c),
+                        None => return Err(ParseError::EOF),
+                    },
+                    _ => buf.push(c),
                 }
+                self.tokenize_identifier(buf, false)?;
             }
         }
         Ok(())
     }
 
+    /// Tokenize a number, or a symbol which starts like one such as `-` or `1+`, starting with `c`.
     fn tokenize_ambiguous(&mut self, c: char) -> ParseResult {
         let mut buf = String::new();
         buf.push(c);
 
         while let Some(c) = self.next() {
             match c {
-                '0' ..= '9' | '+' | '-' | '/' | '.' | 'e' | 'i' => buf.push(c),
-                c if is_pair_start(c) => {
+                c if is_delimiter(c) => {
                     self.distinguish_ambiguous(buf)?;
-                    self.tokens.push(Token::LeftParen);
-                    return Ok(());
-                }
-                c if is_pair_end(c) => {
-                    self.distinguish_ambiguous(buf)?;
-                    self.tokens.push(Token::RightParen);
-                    return Ok(());
+                    return self.delimiter(c);
                 }
-                c if c.is_whitespace() => break,
+                // Only symbols have escapes
                 '\\' => match self.next() {
                     Some(c) => {
                         buf.push(c);
@@ -120,10 +294,8 @@ impl<'a> Tokenizer<'a> {
                     }
                     None => return Err(ParseError::EOF),
                 },
-                _ => {
-                    buf.push(c);
-                    return self.tokenize_identifier(buf, c == '|');
-                }
+                '|' => return self.tokenize_identifier(buf, true),
+                _ => buf.push(c),
             }
         }
         self.distinguish_ambiguous(buf)
@@ -133,9 +305,10 @@ impl<'a> Tokenizer<'a> {
         use std::sync::LazyLock;
 
         //const _RAT: &str = r"\d+(?:/\d+)?";
-        const _REAL: &str = r"\d*\.?\d+(?:[eE][-+]?\d+)?";
+        const _REAL: &str = r"(?:\d+\.?\d*|\.\d+)(?:[eE][-+]?\d+)?";
         static INTEGER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([+-]?\d+)$").unwrap());
         static FLOAT: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!(r"^([+-]?{})$", _REAL)).unwrap());
+        static RATIO: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([+-]?\d+)/(\d+)$").unwrap());
         //static COMPLEX_RAT: SyncLazy<Regex> = SyncLazy::new(|| Regex::new(&format!("^([+-]?{})?(?:([+-](?:{0})?)i)?$", _RAT)).unwrap());
         //static COMPLEX_REAL: SyncLazy<Regex> = SyncLazy::new(|| Regex::new(&format!("^([+-]?(?:{}|{}))?(?:([+-](?:{0}|{1})?)i)?$", _REAL, _RAT)).unwrap());
 
@@ -145,23 +318,43 @@ impl<'a> Tokenizer<'a> {
             let captures = COMPLEX_RAT.captures(&buf).unwrap();
             let real = captures.get(1).map(|s| s.as_str().to_owned());
             let imaginary = captures.get(2).map(|s| s.as_str().to_owned());
-            self.tokens.push(Token::ComplexExact(real, imaginary));
+            self.push(Token::ComplexExact(real, imaginary));
         } else if COMPLEX_REAL.is_match(&buf) {
             let captures = COMPLEX_REAL.captures(&buf).unwrap();
             let real = captures.get(1).map(|s| s.as_str().to_owned());
             let imaginary = captures.get(2).map(|s| s.as_str().to_owned());
-            self.tokens.push(Token::ComplexFloating(real, imaginary));
+            self.push(Token::ComplexFloating(real, imaginary));
             */
         if INTEGER.is_match(&buf) {
             let captures = INTEGER.captures(&buf).unwrap();
             let n = captures.get(1).map(|s| s.as_str().to_owned()).unwrap();
-            self.tokens.push(Token::Integer(n.parse().unwrap()));
+            // Literals which don't fit in a fixnum become bignums
+            match n.parse() {
+                Ok(i) => self.push(Token::Integer(i)),
+                Err(_) => self.push(Token::BigInt(n.parse().unwrap())),
+            }
+        } else if let Some(captures) = RATIO.captures(&buf) {
+            // There are no rationals, so only a ratio which is an integer can be read
+            let n: BigInt = captures[1].parse().unwrap();
+            let d: BigInt = captures[2].parse().unwrap();
+            if d == BigInt::from(0) {
+                return Err(ParseError::Expected("a ratio with a nonzero denominator"));
+            } else if &n % &d != BigInt::from(0) {
+                return Err(ParseError::Expected("a ratio which is an integer, as there are no rationals"));
+            }
+            let n = n / d;
+            match i32::try_from(&n) {
+                Ok(i) => self.push(Token::Integer(i)),
+                Err(_) => self.push(Token::BigInt(n)),
+            }
         } else if FLOAT.is_match(&buf) {
             let captures = FLOAT.captures(&buf).unwrap();
             let n = captures.get(1).map(|s| s.as_str().to_owned()).unwrap();
-            self.tokens.push(Token::Float(n.parse().unwrap()));
+            self.push(Token::Float(n.parse().unwrap()));
+        } else if let Some(f) = infinity_or_nan(&buf) {
+            self.push(Token::Float(f));
         } else {
-            self.tokens.push(Token::Symbol(get_symbol(buf)));
+            self.push(Token::Symbol(get_symbol(buf)));
         }
         Ok(())
     }
@@ -177,20 +370,25 @@ impl<'a> Tokenizer<'a> {
                 c if is_delimiter(c) => if in_bar {
                     buf.push(c);
                 } else {
-                    self.tokens.push(Token::Symbol(get_symbol(buf)));
-                    return match c {
-                        c if c.is_whitespace() => Ok(()),
-                        c if is_pair_start(c) => Ok(self.tokens.push(Token::LeftParen)),
-                        c if is_pair_end(c) => Ok(self.tokens.push(Token::RightParen)),
-                        '"' => self.tokenize_string(),
-                        ';' => self.tokenize_comment(c),
-                        _ => panic!("Parser error"),
-                    };
+                    self.push(Token::Symbol(get_symbol(buf)));
+                    return self.delimiter(c);
                 },
                 _ => buf.push(c),
             }
         }
-        self.tokens.push(Token::Symbol(get_symbol(buf)));
+        self.push(Token::Symbol(get_symbol(buf)));
+        Ok(())
+    }
+
+    /// Tokenize the delimiter `c` which ended a number or symbol.
+    fn delimiter(&mut self, c: char) -> ParseResult {
+        match c {
+            c if is_pair_start(c) => self.push(Token::LeftParen),
+            c if is_pair_end(c) => self.push(Token::RightParen),
+            '"' => self.tokenize_string()?,
+            ';' => self.tokenize_comment(c)?,
+            _ => {}
+        }
         Ok(())
     }
 
@@ -198,18 +396,26 @@ impl<'a> Tokenizer<'a> {
         let mut buf = String::new();
         while let Some(c) = self.next() {
             match c {
-                '\\' => if let Some(c) = self.next() {
-                    match c {
-                        'n' => buf.push('\n'),
-                        't' => buf.push('\t'),
-                        // TODO: handle other escapes
-                        _ => buf.push(c),
+                '\\' => {
+                    // Where the `\` just read is
+                    let at = (self.line, self.column - 1);
+                    match self.next() {
+                        Some('n') => buf.push('\n'),
+                        Some('t') => buf.push('\t'),
+                        Some('r') => buf.push('\r'),
+                        Some('a') => buf.push('\x07'),
+                        Some('b') => buf.push('\x08'),
+                        Some('0') => buf.push('\0'),
+                        Some(c @ '\\') | Some(c @ '"') | Some(c @ '|') => buf.push(c),
+                        Some('x') => buf.push(self.hex_escape(at)?),
+                        Some('u') => buf.push(self.unicode_escape(at)?),
+                        Some(c) if c == '\n' || c == '\r' || is_intraline_whitespace(c) => self.line_continuation(c, at)?,
+                        Some(c) => return Err(ParseError::BadEscape(c, at.0, at.1)),
+                        None => return Err(ParseError::InString),
                     }
-                } else {
-                    return Err(ParseError::InString);
-                },
+                }
                 '"' => {
-                    self.tokens.push(Token::String(buf));
+                    self.push(Token::String(buf));
                     return Ok(());
                 }
                 _ => buf.push(c),
@@ -218,6 +424,75 @@ impl<'a> Tokenizer<'a> {
         Err(ParseError::InString)
     }
 
+    /// Tokenize a character, after the `#\`. It is either a single character, which may be a
+    /// delimiter, or the name of one.
+    fn tokenize_char(&mut self) -> ParseResult {
+        let mut name = String::new();
+        match self.next() {
+            Some(c) => name.push(c),
+            None => return Err(ParseError::EOF),
+        }
+        while let Some(c) = self.peek() {
+            if is_delimiter(c) {
+                break;
+            }
+            name.push(c);
+            self.next();
+        }
+        let mut chars = name.chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => char_named(&name).ok_or(ParseError::Expected("a character or character name after `#\\`"))?,
+        };
+        self.push(Token::Char(c));
+        Ok(())
+    }
+
+    // The character of a `\x<hex>;` escape, after the `x`. The escape starts at line and column `at`.
+    fn hex_escape(&mut self, at: (usize, usize)) -> Result<char, ParseError> {
+        let mut hex = String::new();
+        loop {
+            match self.next() {
+                Some(';') => break,
+                Some(c) if c.is_ascii_hexdigit() => hex.push(c),
+                Some(_) => return Err(ParseError::BadEscape('x', at.0, at.1)),
+                None => return Err(ParseError::InString),
+            }
+        }
+        u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).ok_or(ParseError::BadEscape('x', at.0, at.1))
+    }
+
+    // The character of a `\u<4 hex>` escape, after the `u`. The escape starts at line and column `at`.
+    fn unicode_escape(&mut self, at: (usize, usize)) -> Result<char, ParseError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.next() {
+                Some(c) => code = code * 16 + c.to_digit(16).ok_or(ParseError::BadEscape('u', at.0, at.1))?,
+                None => return Err(ParseError::InString),
+            }
+        }
+        char::from_u32(code).ok_or(ParseError::BadEscape('u', at.0, at.1))
+    }
+
+    // Skip a `\<whitespace><newline><whitespace>` line continuation, after the first character
+    // following the `\`. The `\` is at line and column `at`.
+    fn line_continuation(&mut self, mut c: char, at: (usize, usize)) -> ParseResult {
+        while is_intraline_whitespace(c) {
+            c = self.next().ok_or(ParseError::InString)?;
+        }
+        if c == '\r' && self.peek() == Some('\n') {
+            c = '\n';
+            self.next();
+        }
+        if c != '\n' {
+            return Err(ParseError::BadEscape(' ', at.0, at.1));
+        }
+        while self.peek().map_or(false, is_intraline_whitespace) {
+            self.next();
+        }
+        Ok(())
+    }
+
     fn tokenize_block_comment(&mut self) -> ParseResult {
         let mut buf = String::from("#|");
         let mut nesting = 1;
@@ -229,7 +504,7 @@ impl<'a> Tokenizer<'a> {
                         buf.push('|');
                         buf.push('#');
                         if nesting == 0 {
-                            self.tokens.push(Token::BlockComment(buf));
+                            self.push(Token::BlockComment(buf));
                             return Ok(());
                         }
                     }
@@ -251,26 +526,110 @@ impl<'a> Tokenizer<'a> {
         Err(ParseError::EOF)
     }
 
-    // TODO
     fn tokenize_comment(&mut self, _c: char) -> ParseResult {
         let mut buf = String::from(";");
 
         while let Some(c) = self.next() {
             match c {
-                '\\' => match self.next() {
-                    Some(c) => {
-                        buf.push('\\');
-                        buf.push(c);
-                    }
-                    None => break,
-                },
                 '\n' => break,
                 _ => buf.push(c),
             }
         }
-        self.tokens.push(Token::Comment(buf));
+        self.push(Token::Comment(buf));
         Ok(())
     }
+
+    /// Tokenize the datum after a `#;`, which together with it is a single comment.
+    fn tokenize_datum_comment(&mut self) -> ParseResult {
+        let start = self.start.clone();
+        let first = self.tokens.len();
+        let mut datum = Datum::default();
+        while let Some(c) = self.next() {
+            let read = self.tokens.len();
+            self.token(c)?;
+            for i in read..self.tokens.len() {
+                if is_comment(&self.tokens[i]) || !datum.read(&self.tokens, i)? {
+                    continue;
+                }
+                // The delimiter which ended the datum is read with it, but isn't commented out
+                let after = self.tokens.split_off(i + 1);
+                let after_locations = self.locations.split_off(i + 1);
+                let end = self.locations[i].span.end;
+                let commented = self.tokens.split_off(first);
+                self.locations.truncate(first);
+                self.tokens.push(Token::DatumComment(commented));
+                self.locations.push(Location { span: start.span.start..end, ..start });
+                self.tokens.extend(after);
+                self.locations.extend(after_locations);
+                return Ok(());
+            }
+        }
+        Err(ParseError::EOF)
+    }
+}
+
+/// How much of a datum has been read, to find the token it ends with.
+#[derive(Default)]
+struct Datum {
+    // The lists it is inside
+    depth: usize,
+    // Whether the last token read is a prefix, such as a quote, which must be followed by a datum
+    prefixed: bool,
+}
+
+impl Datum {
+    /// Read `tokens[i]`, which isn't a comment. Returns whether it ends the datum.
+    fn read(&mut self, tokens: &[Token], i: usize) -> Result<bool, ParseError> {
+        match tokens[i] {
+            Token::LeftParen => self.depth += 1,
+            Token::RightParen if self.depth == 0 => return Err(ParseError::UnexpectedCloseParen),
+            Token::RightParen => self.depth -= 1,
+            _ => (),
+        }
+        self.prefixed = match tokens[i] {
+            Token::Quote | Token::Quasiquote | Token::Unquote | Token::UnquoteSplice | Token::Pound => true,
+            // The `u8` of a `#u8(...)` bytevector is followed by its elements
+            Token::Symbol(s) => i > 0 && tokens[i - 1] == Token::Pound && get_value(s).map_or(false, |s| s == "u8"),
+            _ => false,
+        };
+        Ok(self.depth == 0 && !self.prefixed)
+    }
+}
+
+fn is_comment(token: &Token) -> bool {
+    matches!(token, Token::Comment(_) | Token::BlockComment(_) | Token::DatumComment(_))
+}
+
+/// The character written `#\name`, e.g. `#\space` or `#\x3bb`.
+fn char_named(name: &str) -> Option<char> {
+    match name {
+        "space" => Some(' '),
+        "newline" => Some('\n'),
+        "tab" => Some('\t'),
+        "return" => Some('\r'),
+        "null" | "nul" => Some('\0'),
+        "alarm" => Some('\x07'),
+        "backspace" => Some('\x08'),
+        "delete" => Some('\x7f'),
+        "escape" => Some('\x1b'),
+        _ if name.starts_with('x') =>
+            u32::from_str_radix(&name[1..], 16).ok().and_then(char::from_u32),
+        _ => None,
+    }
+}
+
+/// The float written `+inf.0`, `-inf.0`, `+nan.0` or `-nan.0`.
+fn infinity_or_nan(s: &str) -> Option<f64> {
+    match s {
+        "+inf.0" => Some(f64::INFINITY),
+        "-inf.0" => Some(f64::NEG_INFINITY),
+        "+nan.0" | "-nan.0" => Some(f64::NAN),
+        _ => None,
+    }
+}
+
+fn is_intraline_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t'
 }
 
 fn is_delimiter(c: char) -> bool {
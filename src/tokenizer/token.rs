@@ -1,11 +1,14 @@
 use vm::Value;
 
+use num_bigint::BigInt;
 use string_interner::Symbol;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Token {
     Comment(String),
     BlockComment(String),
+    // `#;` and the tokens of the datum it comments out
+    DatumComment(Vec<Token>),
     LeftParen,
     RightParen,
     Dot,
@@ -16,7 +19,10 @@ pub enum Token {
     Pound,
     String(String),
     Integer(i32),
+    // An integer literal outside the fixnum range
+    BigInt(BigInt),
     Float(f64),
+    Char(char),
     //ComplexExact(Option<String>, Option<String>),
     //ComplexFloating(Option<String>, Option<String>),
     Symbol(Symbol),
@@ -24,18 +30,32 @@ pub enum Token {
 
 impl Token {
     pub fn is_primitive(&self) -> bool {
-        matches!(self, Token::String(_) | Token::Integer(_) | Token::Float(_))
+        matches!(self, Token::String(_) | Token::Integer(_) | Token::BigInt(_) | Token::Float(_) |
+                       Token::Char(_))
     }
 
     pub fn to_primitive(&self) -> Value {
         match self {
             Token::String(s) => Value::String(s.to_string()),
             Token::Integer(i) => Value::Integer(*i),
+            Token::BigInt(n) => Value::BigInt(n.clone()),
             Token::Float(i) => Value::Float(*i),
+            Token::Char(c) => Value::Char(*c),
             _ => unreachable!(),
         }
     }
 
+    /// The name of the form abbreviated by a prefix such as `'`.
+    pub fn prefix_name(&self) -> Option<&'static str> {
+        match self {
+            Token::Quote => Some("quote"),
+            Token::Quasiquote => Some("quasiquote"),
+            Token::Unquote => Some("unquote"),
+            Token::UnquoteSplice => Some("unquote-splicing"),
+            _ => None,
+        }
+    }
+
     pub fn is_left_paren(&self) -> bool {
         matches!(self, Token::LeftParen)
     }
@@ -0,0 +1,19 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::Minerva;
+
+#[test]
+fn symbol_comparisons_keep_live_registers() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    // The formals fill the registers up to and past the ones a comparison could use
+    m.eval_str("(define (pick k a1 a2 a3 a4 a5 a6 a7 a8 a9 a10 a11 a12 a13 a14 a15 a16 a17 a18 a19 a20)
+                  (case k ((x y) a18) ((z) a19) (else a20)))").unwrap();
+    for &(k, expected) in &[("x", "18"), ("z", "19"), ("w", "20")] {
+        let input = format!("(pick '{} 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20)", k);
+        assert_eq!(expected, format!("{}", m.eval_str(&input).unwrap().0));
+    }
+}
@@ -0,0 +1,81 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn membership() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define vowels (string->char-set \"aeiouaa\"))").unwrap();
+    assert_eq!("#t", eval(&mut m, "(char-set? vowels)"));
+    assert_eq!("#f", eval(&mut m, "(char-set? \"aeiou\")"));
+    assert_eq!("#t", eval(&mut m, "(char-set-contains? vowels #\\e)"));
+    assert_eq!("#f", eval(&mut m, "(char-set-contains? vowels #\\b)"));
+    assert_eq!("5", eval(&mut m, "(char-set-size vowels)"));
+    assert_eq!("(#\\a #\\b #\\c)", eval(&mut m, "(char-set->list (char-set #\\c #\\a #\\b #\\a))"));
+    assert_eq!("(#\\x #\\y)", eval(&mut m, "(char-set->list (list->char-set (list #\\y #\\x)))"));
+    assert_eq!("(#\\0 #\\1 #\\2)", eval(&mut m, "(char-set->list (ucs-range->char-set 48 51))"));
+
+    // The standard sets
+    assert_eq!("#t", eval(&mut m, "(char-set-contains? char-set:letter #\\λ)"));
+    assert_eq!("#f", eval(&mut m, "(char-set-contains? char-set:letter #\\1)"));
+    assert_eq!("#t", eval(&mut m, "(char-set-contains? char-set:digit #\\7)"));
+    assert_eq!("#t", eval(&mut m, "(char-set-contains? char-set:whitespace #\\newline)"));
+    assert_eq!("#t", eval(&mut m, "(char-set-contains? char-set:upper-case #\\Q)"));
+    assert_eq!("22", eval(&mut m, "(char-set-size char-set:hex-digit)"));
+    assert_eq!("128", eval(&mut m, "(char-set-size char-set:ascii)"));
+    assert_eq!("0", eval(&mut m, "(char-set-size char-set:empty)"));
+    // Every code point but the surrogates
+    assert_eq!("1112064", eval(&mut m, "(char-set-size char-set:full)"));
+
+    assert_eq!("Exception in char-set-contains?: \"a\" is not a char-set",
+               exception(&mut m, "(char-set-contains? \"a\" #\\a)"));
+    assert_eq!("Exception in char-set: 1 is not a character", exception(&mut m, "(char-set 1)"));
+    assert_eq!("Exception in ucs-range->char-set: -1 is not a code point",
+               exception(&mut m, "(ucs-range->char-set -1 10)"));
+}
+
+#[test]
+fn algebra() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define abc (string->char-set \"abc\"))").unwrap();
+    m.eval_str("(define cde (string->char-set \"cde\"))").unwrap();
+    assert_eq!("(#\\a #\\b #\\c #\\d #\\e)", eval(&mut m, "(char-set->list (char-set-union abc cde))"));
+    assert_eq!("()", eval(&mut m, "(char-set->list (char-set-union))"));
+    assert_eq!("(#\\c)", eval(&mut m, "(char-set->list (char-set-intersection abc cde))"));
+    assert_eq!("(#\\a #\\b)", eval(&mut m, "(char-set->list (char-set-difference abc cde))"));
+    assert_eq!("(#\\a)", eval(&mut m, "(char-set->list (char-set-difference abc cde (char-set #\\b)))"));
+
+    m.eval_str("(define not-abc (char-set-complement abc))").unwrap();
+    assert_eq!("#f", eval(&mut m, "(char-set-contains? not-abc #\\b)"));
+    assert_eq!("#t", eval(&mut m, "(char-set-contains? not-abc #\\z)"));
+    assert_eq!("1112061", eval(&mut m, "(char-set-size not-abc)"));
+    assert_eq!("3", eval(&mut m, "(char-set-size (char-set-complement not-abc))"));
+    assert_eq!("10", eval(&mut m, "(char-set-size (char-set-intersection char-set:digit char-set:ascii))"));
+}
+
+#[test]
+fn iteration() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define seen '())").unwrap();
+    m.eval_str("(for-each (lambda (c) (set! seen (cons c seen))) (string->char-set \"cab\"))").unwrap();
+    assert_eq!("(#\\c #\\b #\\a)", eval(&mut m, "seen"));
+    assert_eq!("294", eval(&mut m, "(fold (lambda (c n) (+ n (char->integer c))) 0 (string->char-set \"abc\"))"));
+}
@@ -0,0 +1,38 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::Minerva;
+
+use std::sync::Arc;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn closures() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (make-adder n) (lambda (x) (+ x n)))").unwrap();
+    m.eval_str("(define add1 (make-adder 1))").unwrap();
+    m.eval_str("(define add2 (make-adder 2))").unwrap();
+
+    // Each closure has its own environment
+    assert_eq!("(11 12)", eval(&mut m, "(list (add1 10) (add2 10))"));
+    let (add1, add2) = (m.eval_str("add1").unwrap().0, m.eval_str("add2").unwrap().0);
+    assert!(add1 != add2);
+
+    // But shares its code and constants with the others made by the same lambda expression
+    let (add1, add2) = (add1.to_lambda(), add2.to_lambda());
+    assert!(Arc::ptr_eq(&add1.code, &add2.code));
+    assert!(Arc::ptr_eq(&add1.consts, &add2.consts));
+    Box::into_raw(add1);
+    Box::into_raw(add2);
+
+    m.eval_str("(define (counter) ((lambda (n) (lambda () (set! n (+ n 1)) n)) 0))").unwrap();
+    m.eval_str("(define a (counter))").unwrap();
+    m.eval_str("(define b (counter))").unwrap();
+    assert_eq!("(1 2 1)", eval(&mut m, "((lambda (x) ((lambda (y) (list x y (b))) (a))) (a))"));
+}
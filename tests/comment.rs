@@ -0,0 +1,39 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Location, Minerva, ParseError, Tokenizer};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn comments() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("3", eval(&mut m, "; adds\n(+ 1 ; one\n 2) ; two"));
+    // A line comment ends at the newline, even after a backslash
+    assert_eq!("(1 2)", eval(&mut m, "(list 1 ; c:\\\n 2)"));
+    assert_eq!("(1 2)", eval(&mut m, "(list 1 #| a #| nested |# comment |# 2)"));
+    assert_eq!("(1 3)", eval(&mut m, "(list 1 #;2 3)"));
+    assert_eq!("(1 4)", eval(&mut m, "(list 1 #; (+ 2 ; inner\n 3) 4)"));
+    assert_eq!("(1)", eval(&mut m, "(list 1 #;abc)"));
+    assert_eq!("(1 4)", eval(&mut m, "(list 1 #;#;2 3 4)"));
+    assert_eq!("(a d)", eval(&mut m, "'(a #;'(b c) d)"));
+    assert_eq!("2", eval(&mut m, "#;(undefined-procedure) 2"));
+    assert_eq!("5", eval(&mut m, "(define (f x) #;(display x) x)\n(f 5)"));
+
+    assert_eq!(Err(Error::Parse(ParseError::EOF)), m.eval_str("(list 1 #;").map(|(v, _)| v));
+    assert_eq!(Err(Error::Parse(ParseError::UnexpectedCloseParen)), m.eval_str("(list 1 #;)").map(|(v, _)| v));
+}
+
+#[test]
+fn forms() {
+    let forms = Tokenizer::tokenize_forms("#;(a 1) b #| c |#\n#;d e").unwrap();
+    let locations: Vec<_> = forms.into_iter().map(|(_, l)| l).collect();
+    assert_eq!(vec![Location { line: 1, column: 9, span: 8..9 }, Location { line: 2, column: 5, span: 22..23 }],
+               locations);
+    assert_eq!(Ok(None), Tokenizer::tokenize_datum(" #;(a) ; b"));
+}
@@ -0,0 +1,8 @@
+//! Helpers shared by the tests.
+
+use std::sync::Mutex;
+
+/// The heap is shared by every machine, so tests which allocate must not run concurrently.
+/// A test which panics while holding it poisons it, so take it with
+/// `HEAP.lock().unwrap_or_else(|e| e.into_inner())` to keep one failure from failing the rest.
+pub static HEAP: Mutex<()> = Mutex::new(());
@@ -0,0 +1,54 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+use vm::LoadError;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn round_trip() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let bytes = Minerva::new().compile_str("
+        (define-syntax swap (syntax-rules () ((_ a b) (list b a))))
+        (define (kind x) (case x ((a e i o u) 'vowel) ((y) 'sometimes) (else 'consonant)))
+        (define data '(1 (2.5 #\\a) \"text\" 123456789012345678901234567890))
+        (define (count n) (if (< n 1) 0 (+ 1 (count (- n 1)))))
+        (swap 1 2)").unwrap();
+
+    // Nothing was evaluated by compiling
+    let mut m = Minerva::new();
+    assert_eq!("(2 1)", format!("{}", m.eval_compiled(&bytes).unwrap().0));
+    assert_eq!("vowel", eval(&mut m, "(kind 'o)"));
+    assert_eq!("consonant", eval(&mut m, "(kind 'z)"));
+    assert_eq!("(1 (2.5 #\\a) \"text\" 123456789012345678901234567890)", eval(&mut m, "data"));
+    assert_eq!("100", eval(&mut m, "(count 100)"));
+
+    // The main procedure of a compiled script is called as for a script
+    let bytes = Minerva::new().compile_str("(define (main args) (length args))").unwrap();
+    let mut m = Minerva::new();
+    m.eval_compiled(&bytes).unwrap();
+    assert_eq!("2", format!("{}", m.run_main(vec!["a.mvc".to_string(), "b".to_string()]).unwrap().unwrap()));
+}
+
+#[test]
+fn invalid() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!(Err(Error::Load(LoadError::NotCompiled)), m.eval_compiled(b"(+ 1 2)").map(|(v, _)| v));
+
+    // Forms before the truncated one have been evaluated
+    let bytes = Minerva::new().compile_str("(define x 1) (define y 2)").unwrap();
+    assert_eq!(Err(Error::Load(LoadError::Truncated)),
+               m.eval_compiled(&bytes[..bytes.len() - 1]).map(|(v, _)| v));
+    assert_eq!("1", eval(&mut m, "x"));
+
+    let bytes = Minerva::new().compile_str("(car 1)").unwrap();
+    assert_eq!(Err(Error::Condition("Exception in car: 1 is not a pair".to_string())),
+               m.eval_compiled(&bytes).map(|(v, _)| v));
+}
@@ -0,0 +1,60 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva, ParseError};
+use vm::Value;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn for_list() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("(1 4 9)", eval(&mut m, "(for/list ([x (list 1 2 3)]) (* x x))"));
+    assert_eq!("()", eval(&mut m, "(for/list ([x '()]) x)"));
+    assert_eq!("(#\\b #\\c)", eval(&mut m, "(for/list ([c \"bc\"]) c)"));
+    // The body may be a sequence, and see global bindings
+    m.eval_str("(define n 10)").unwrap();
+    assert_eq!("(11 12)", eval(&mut m, "(for/list [(x (list 1 2))] (list x) (+ x n))"));
+}
+
+#[test]
+fn for_fold() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!(Value::Integer(10), m.eval_str("(for/fold ([sum 0]) ([x (list 1 2 3 4)]) (+ sum x))").unwrap().0);
+    assert_eq!("(3 2 1)", eval(&mut m, "(for/fold ([acc '()]) ([x (list 1 2 3)]) (cons x acc))"));
+    m.eval_str("(define t (for/hash ([x (list 1 2 3)]) (values x (* x 10))))").unwrap();
+    assert_eq!(Value::Integer(60), m.eval_str("(for/fold ([sum 0]) ([(k v) t]) (+ sum v))").unwrap().0);
+}
+
+#[test]
+fn for_hash() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define t (for/hash ([s (list 'a 'b)]) (values s (list s))))").unwrap();
+    assert_eq!(Value::Integer(2), m.eval_str("(hash-table-count t)").unwrap().0);
+    assert_eq!("(b)", eval(&mut m, "(hash-table-ref t 'b)"));
+    assert_eq!(Value::Integer(0), m.eval_str("(hash-table-count (for/hash ([x '()]) (values x x)))").unwrap().0);
+}
+
+#[test]
+fn malformed() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!(Err(Error::Parse(ParseError::Expected("`(`"))), m.eval_str("(for/list (x (list 1)) x)"));
+    assert_eq!(Err(Error::Parse(ParseError::Expected("an expression in the body"))),
+               m.eval_str("(for/list ([x (list 1)]))"));
+    // Only one clause is supported
+    assert_eq!(Err(Error::Parse(ParseError::Expected("`)`"))), m.eval_str("(for/list ([x '()] [y '()]) x)"));
+    // The body of for/hash must end with the key and value
+    assert_eq!(Err(Error::Parse(ParseError::Expected("`(values key value)` at the end of the body"))),
+               m.eval_str("(for/hash ([x '()]) x)"));
+    assert_eq!(Err(Error::Condition("Exception in fold: 5 is not a collection".to_string())),
+               m.eval_str("(for/list ([x 5]) x)"));
+}
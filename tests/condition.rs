@@ -0,0 +1,37 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn restart_handlers() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    // Returning from the handler abandons the thunk
+    assert_eq!("unbound-variable", eval(&mut m, "(with-restart-handler (lambda (kind message) kind) \
+                                                   (lambda () (+ 1 missing)))"));
+    assert_eq!("3", eval(&mut m, "(with-restart-handler (lambda (kind message) 'handled) (lambda () 3))"));
+
+    // Or the handler goes on from where the condition was signalled
+    assert_eq!("42", eval(&mut m, "(with-restart-handler (lambda (kind message) (invoke-restart 'use-value 41)) \
+                                     (lambda () (+ 1 missing)))"));
+    assert_eq!("3", eval(&mut m, "(with-restart-handler (lambda (kind message) (set! later 2) (invoke-restart 'retry)) \
+                                    (lambda () (+ 1 later)))"));
+
+    // The innermost handler is called, with the ones outside it handling its own conditions
+    assert_eq!("outer", eval(&mut m, "(with-restart-handler (lambda (kind message) 'outer) \
+                                        (lambda () (with-restart-handler (lambda (kind message) (+ 1 missing)) \
+                                                     (lambda () (+ 1 missing)))))"));
+
+    // A restart which doesn't apply fails the handler, leaving the condition unhandled
+    assert!(m.eval_str("(with-restart-handler (lambda (kind message) (invoke-restart 'retry)) \
+                          (lambda () (vector-ref (vector) 1)))").is_err());
+    assert_eq!(Err(Error::Condition("Exception in invoke-restart: no condition is being handled".to_string())),
+               m.eval_str("(invoke-restart 'retry)").map(|(v, _)| v));
+}
@@ -0,0 +1,104 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn escape() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("3", eval(&mut m, "(+ 1 (call/cc (lambda (k) 2)))"));
+    assert_eq!("11", eval(&mut m, "(+ 1 (call/cc (lambda (k) (+ 100 (k 10)))))"));
+    assert_eq!("11", eval(&mut m, "(+ 1 (call-with-current-continuation (lambda (k) (+ 100 (k 10)))))"));
+
+    // Escaping from deep inside a recursion
+    m.eval_str("(define (product l k) \
+                  (if (= 0 (length l)) 1 (if (= 0 (car l)) (k 0) (* (car l) (product (cdr l) k)))))")
+        .unwrap();
+    assert_eq!("24", eval(&mut m, "(call/cc (lambda (k) (product '(1 2 3 4) k)))"));
+    assert_eq!("0", eval(&mut m, "(call/cc (lambda (k) (product '(1 2 0 4) k)))"));
+
+    // A tail call passes the caller's continuation
+    m.eval_str("(define (f) (call/cc (lambda (k) (k 5))))").unwrap();
+    assert_eq!("6", eval(&mut m, "(+ 1 (f))"));
+
+    assert_eq!("#t", eval(&mut m, "(call/cc (lambda (k) (continuation? k)))"));
+    assert_eq!("#f", eval(&mut m, "(continuation? car)"));
+}
+
+#[test]
+fn escape_from_native() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define exits (vector))").unwrap();
+    m.eval_str("(define (check x) (if (< 2 x) ((vector-pop! exits) x) 0))").unwrap();
+    assert_eq!("3", eval(&mut m, "(call/cc (lambda (k) \
+                                    (begin (vector-push! exits k) (for-each check '(1 2 3 4)) 0)))"));
+    // Out of two natives at once
+    m.eval_str("(define (step x acc) (begin (for-each check (list x)) (+ x acc)))").unwrap();
+    assert_eq!("15", eval(&mut m, "(+ 10 (call/cc (lambda (k) \
+                                     (begin (vector-push! exits k) (fold step 0 '(1 2 5 1))))))"));
+    assert_eq!("4", eval(&mut m, "(+ 1 (call/cc (lambda (k) (begin (vector-push! exits k) (fold step 0 '(1 2))))))"));
+    // The machine is left ready for more
+    assert_eq!("2", eval(&mut m, "(+ 1 1)"));
+}
+
+#[test]
+fn reentry() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define saved (vector))").unwrap();
+    m.eval_str("(define (count) (+ 1 (call/cc (lambda (k) (begin (vector-push! saved k) 0)))))").unwrap();
+    assert_eq!("1", eval(&mut m, "(count)"));
+    // The call to count has returned, but its continuation can still be resumed
+    assert_eq!("11", eval(&mut m, "((vector-ref saved 0) 10)"));
+    assert_eq!("21", eval(&mut m, "((vector-ref saved 0) 20)"));
+}
+
+#[test]
+fn invalid_resumption() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define saved (vector))").unwrap();
+    m.eval_str("(for-each (lambda (x) (call/cc (lambda (k) (vector-push! saved k)))) '(1))").unwrap();
+    assert_eq!("Exception in continuation: #<continuation 0> was captured in a procedure applied by a native \
+                procedure which has returned",
+               exception(&mut m, "((vector-ref saved 0) 1)"));
+    assert_eq!("Exception in continuation: expected 0 to 1 arguments, got 2",
+               exception(&mut m, "(call/cc (lambda (k) (k 1 2)))"));
+    assert_eq!("Exception in call-with-current-continuation: expected 1 arguments, got 0",
+               exception(&mut m, "(call/cc)"));
+}
+
+#[test]
+fn collected() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    // Each continuation is freed once nothing refers to it, so escaping from every iteration of a
+    // loop doesn't grow the continuation table
+    m.eval_str("(define (spin n) (if (= n 0) 0 (begin (call/cc (lambda (k) (k n))) (spin (- n 1)))))").unwrap();
+    assert_eq!("0", eval(&mut m, "(spin 200)"));
+    let k = eval(&mut m, "(call/cc (lambda (k) k))");
+    let i: usize = k.trim_start_matches("#<continuation ").trim_end_matches('>').parse().unwrap();
+    assert!(i < 100, "{} was given a new slot", k);
+
+    // One which can still be reached is kept
+    m.eval_str("(define saved (vector))").unwrap();
+    assert_eq!("1", eval(&mut m, "(+ 1 (call/cc (lambda (k) (begin (vector-push! saved k) 0))))"));
+    assert_eq!("0", eval(&mut m, "(spin 200)"));
+    assert_eq!("6", eval(&mut m, "((vector-ref saved 0) 5)"));
+}
@@ -0,0 +1,54 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::Minerva;
+
+fn report(m: &mut Minerva) -> String {
+    let v = m.eval_str("(coverage-report)").unwrap().0;
+    let s = v.to_string();
+    let text = s.str.clone();
+    Box::into_raw(s);
+    text
+}
+
+#[test]
+fn coverage_report() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("; no code was compiled for coverage\n", report(&mut m));
+
+    // Only code evaluated while coverage is on is instrumented
+    m.eval_str("(define (untested x) x)").unwrap();
+    m.set_coverage(true);
+    m.eval_str("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))").unwrap();
+    m.eval_str("(define (sign n) (case n ((0) 'zero) ((1 2 3) 'small) (else 'big)))").unwrap();
+    m.set_coverage(false);
+    assert_eq!("6", format!("{}", m.eval_str("(fact 3)").unwrap().0));
+    assert_eq!("small", format!("{}", m.eval_str("(sign 2)").unwrap().0));
+    assert_eq!("; 5 of 7 probes reached (71.4%)
+        4: fact
+        1: fact: (if (= n 0) ...) then
+        3: fact: (if (= n 0) ...) else
+        1: sign
+    #####: sign: (case n ...) (0)
+        1: sign: (case n ...) (1 2 3)
+    #####: sign: (case n ...) else
+", report(&mut m));
+}
+
+#[test]
+fn tail_calls_are_kept() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.set_coverage(true);
+    m.eval_str("(define (count n acc) (if (= n 0) acc (count (- n 1) (+ acc 1))))").unwrap();
+    let (v, short) = m.eval_str("(count 10 0)").unwrap();
+    assert_eq!("10", format!("{}", v));
+    let (v, long) = m.eval_str("(count 1000 0)").unwrap();
+    assert_eq!("1000", format!("{}", v));
+    assert_eq!(short.peak_stack, long.peak_stack);
+    assert_eq!(1012, m.vm().probes()[0].hits);
+}
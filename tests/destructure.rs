@@ -0,0 +1,47 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva, ParseError};
+use vm::Value;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn destructuring_bind() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("((4 5) 3 2 1)", eval(&mut m, "(destructuring-bind (a (b c) . d) (list 1 (list 2 3) 4 5) (list d c b a))"));
+    assert_eq!("2", eval(&mut m, "(destructuring-bind (_ x) (list 1 2 3) x)"));
+    assert_eq!("6", eval(&mut m, "(destructuring-bind x 5 (+ x 1))"));
+    assert_eq!("5", eval(&mut m, "(destructuring-bind ((a b) (c d)) (list (list 1 2) (list 3 4)) (if (= a 1) (+ b c) d))"));
+
+    // The parts are bound inside procedures as well, alongside the formals
+    m.eval_str("(define (f p q) (destructuring-bind (a . b) p (cons (+ a q) b)))").unwrap();
+    assert_eq!("(11 2)", eval(&mut m, "(f (list 1 2) 10)"));
+    // Inner bindings shadow outer ones
+    assert_eq!("(3 2)", eval(&mut m, "(destructuring-bind (a b) (list 1 2) (destructuring-bind (a) (list 3) (list a b)))"));
+}
+
+#[test]
+fn mismatch() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!(Err(Error::Condition("Exception in car: () is not a pair".to_string())),
+               m.eval_str("(destructuring-bind (a b) (list 1) a)"));
+    assert_eq!(Err(Error::Condition("Exception in cdr: 5 is not a pair".to_string())),
+               m.eval_str("(destructuring-bind (_ . b) 5 b)"));
+    assert_eq!(Value::Integer(1), m.eval_str("(car (list 1))").unwrap().0);
+    assert_eq!(Err(Error::Condition("Exception in car: 1 is not a pair".to_string())), m.eval_str("(car 1)"));
+
+    assert_eq!(Err(Error::Parse(ParseError::Expected("a symbol or list in the pattern"))),
+               m.eval_str("(destructuring-bind (a 1) (list 1 1) a)"));
+    assert_eq!(Err(Error::Parse(ParseError::Expected("a symbol or list in the pattern"))),
+               m.eval_str("(destructuring-bind (. a) (list 1) a)"));
+    assert_eq!(Err(Error::Parse(ParseError::Expected("an expression in the body"))),
+               m.eval_str("(destructuring-bind (a) (list 1))"));
+}
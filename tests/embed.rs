@@ -0,0 +1,291 @@
+extern crate minerva;
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, EvalReport, Minerva, ParseError};
+use string_interner::get_symbol;
+use vm::{NativeError, Value};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[test]
+fn eval_str() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let (v, report) = m.eval_str("(define x 2) (+ x 3)").unwrap();
+    assert_eq!(Value::Integer(5), v);
+    assert!(report.instructions > 0);
+    assert_eq!(report, m.last_report());
+
+    assert_eq!(Err(Error::Parse(ParseError::EOF)), m.eval_str("(+ 1"));
+    // Malformed input is an error rather than a panic
+    assert_eq!(Err(Error::Parse(ParseError::Expected("an expression to apply in `()`"))), m.eval_str("()"));
+    assert_eq!(Err(Error::Condition("Exception: attempt to apply non-procedure 1".to_string())), m.eval_str("(1 2)"));
+    assert_eq!(Err(Error::Condition("Exception: variable y is not bound".to_string())), m.eval_str("(+ y 1)"));
+    // The failed evaluation is still reported, and doesn't leave the machine suspended
+    assert!(m.last_report().instructions > 0);
+    assert_eq!(0, m.vm().condition_depth());
+    assert_eq!(Value::Integer(3), m.eval_str("(+ x 1) (- x -1)").unwrap().0);
+    // The constants of later forms survive the collections made while earlier forms run
+    let s = m.eval_str("(define y 1) (define (f) \"text\") (f)").unwrap().0.to_string();
+    assert_eq!("text", s.str);
+    Box::into_raw(s);
+}
+
+#[test]
+fn report() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let (_, small) = m.eval_str("(+ 1 2)").unwrap();
+    let (_, large) = m.eval_str("(list 1 2 3 4 5 6 7 8) (list 1 2 3 4 5 6 7 8)").unwrap();
+    assert!(large.instructions > small.instructions);
+    assert!(large.allocations >= 16);
+    assert!(large.gc_pauses > 0);
+    assert_ne!(EvalReport::default(), large);
+
+    // Each recursive call saves registers on the stack
+    m.eval_str("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))").unwrap();
+    let (v, shallow) = m.eval_str("(fact 2)").unwrap();
+    assert_eq!(Value::Integer(2), v);
+    let (v, deep) = m.eval_str("(fact 10)").unwrap();
+    assert_eq!(Value::Integer(3628800), v);
+    assert!(deep.peak_stack > shallow.peak_stack);
+}
+
+#[test]
+fn register_fn() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = Arc::clone(&calls);
+    m.register_fn("embed-test-greet", vm::Arity::Exactly(1), move |args| {
+        let name = vm::string_arg(args[0])?;
+        counted.fetch_add(1, Ordering::SeqCst);
+        Ok(Value::String(format!("Hello, {}", name)))
+    }).unwrap();
+    m.register_fn("embed-test-sum", vm::Arity::Exactly(1), |args| {
+        let sum = vm::list_arg(args[0])?.into_iter().map(vm::integer_arg).sum::<Result<i32, _>>()?;
+        Ok(vm::make_list(vec![Value::Integer(sum), args[0]]))
+    }).unwrap();
+
+    assert_eq!("\"Hello, there\"", format!("{}", m.eval_str("(embed-test-greet \"there\")").unwrap().0));
+    assert_eq!(1, calls.load(Ordering::SeqCst));
+    assert_eq!("(6 (1 2 3))", format!("{}", m.eval_str("(embed-test-sum '(1 2 3))").unwrap().0));
+    // Closures are procedures like any other
+    assert_eq!(Value::Integer(9), m.eval_str("((lambda (f) (car (f '(4 5)))) embed-test-sum)").unwrap().0);
+    assert_eq!(Err(Error::Condition("Exception in embed-test-greet: 1 is not a string".to_string())),
+               m.eval_str("(embed-test-greet 1)"));
+    assert_eq!(Err(Error::Condition("Exception in embed-test-sum: (1 . 2) is not a list".to_string())),
+               m.eval_str("(embed-test-sum '(1 . 2))"));
+    assert_eq!(Err(Error::Condition("Exception in embed-test-sum: a is not an integer".to_string())),
+               m.eval_str("(embed-test-sum '(1 a))"));
+    assert_eq!(Err(NativeError::AlreadyBound("car".to_string())),
+               m.register_fn("car", vm::Arity::Exactly(1), |args| Ok(args[0])));
+
+    // Each interpreter may define its own procedure under the same name
+    let mut other = Minerva::new();
+    other.register_fn("embed-test-greet", vm::Arity::Exactly(1), |_| Ok(Value::Integer(0))).unwrap();
+    assert_eq!(Value::Integer(0), other.eval_str("(embed-test-greet 1)").unwrap().0);
+    assert!(m.eval_str("(embed-test-greet \"again\")").is_ok());
+    assert_eq!(2, calls.load(Ordering::SeqCst));
+}
+
+fn explode(_: &mut vm::VM, _: &[Value]) -> Result<Value, String> {
+    panic!("boom")
+}
+
+#[test]
+fn internal_panic() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    vm::define_native(m.environment(), "embed-test-explode", vm::Arity::Exactly(0), explode).unwrap();
+    match m.eval_str("(embed-test-explode)") {
+        Err(Error::InternalPanic(message, backtrace)) => {
+            assert_eq!("boom", message);
+            assert!(!backtrace.is_empty());
+        }
+        r => panic!("expected an internal panic, got {:?}", r),
+    }
+    assert!(m.is_poisoned());
+    assert_eq!(Err(Error::Poisoned), m.eval_str("(+ 1 2)"));
+
+    // Other interpreters are unaffected
+    assert_eq!(Value::Integer(3), Minerva::new().eval_str("(+ 1 2)").unwrap().0);
+}
+
+#[test]
+fn reset() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let env = vm::init_env();
+    env.define_variable(get_symbol("base".to_string()), Value::Integer(10));
+    let prelude = vm::Prelude::new(env);
+    let mut m = Minerva::with_prelude(&prelude);
+    m.eval_str("(define x 1)").unwrap();
+    vm::define_native(m.environment(), "embed-test-explode", vm::Arity::Exactly(0), explode).unwrap();
+    assert!(m.eval_str("(embed-test-explode)").is_err());
+    assert!(m.is_poisoned());
+
+    m.reset();
+    assert!(!m.is_poisoned());
+    assert_eq!(EvalReport::default(), m.last_report());
+    assert_eq!(Value::Integer(11), m.eval_str("(+ base 1)").unwrap().0);
+    assert_eq!(Err(Error::Condition("Exception: variable x is not bound".to_string())), m.eval_str("x"));
+
+    // Without a prelude the initial environment is restored
+    let mut m = Minerva::new();
+    m.eval_str("(define x 1)").unwrap();
+    m.reset();
+    assert_eq!(Err(Error::Condition("Exception: variable x is not bound".to_string())), m.eval_str("x"));
+    assert_eq!(Value::Integer(3), m.eval_str("(+ 1 2)").unwrap().0);
+}
+
+#[test]
+fn transfer() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut a = Minerva::new();
+    let mut b = Minerva::new();
+    let (l, _) = a.eval_str("(define l (list 1 \"two\" 'three 99999999999)) l").unwrap();
+    let copy = a.transfer(l, &mut b).unwrap();
+    b.environment().define_variable(get_symbol("l".to_string()), copy);
+    assert_ne!(l, copy);
+    assert_eq!("(1 \"two\" three 99999999999)", format!("{}", copy));
+
+    // Mutating the copy doesn't affect the original
+    b.eval_str("(set-car! l 100)").unwrap();
+    assert_eq!("(100 \"two\" three 99999999999)", format!("{}", b.eval_str("l").unwrap().0));
+    assert_eq!("(1 \"two\" three 99999999999)", format!("{}", a.eval_str("l").unwrap().0));
+
+    // Sharing and cycles are preserved
+    let (c, _) = a.eval_str("(define c (list 1 2)) (set-cdr! (cdr c) c) c").unwrap();
+    let s = Value::String("shared".to_string());
+    let v = Value::Vec(vec![s, s, c]);
+    a.environment().define_variable(get_symbol("v".to_string()), v);
+    let copy = a.transfer(v, &mut b).unwrap();
+    b.environment().define_variable(get_symbol("v".to_string()), copy);
+    let items = copy.to_vec();
+    let (s1, s2, c1) = (items.vec[0], items.vec[1], items.vec[2]);
+    Box::into_raw(items);
+    assert_eq!(s1, s2);
+    assert_ne!(s, s1);
+    assert_ne!(c, c1);
+    assert_eq!(c1, c1.cdr().cdr());
+    assert_eq!(Value::Integer(2), c1.cdr().car());
+
+    let (f, _) = a.eval_str("(define (f) 1) f").unwrap();
+    assert!(matches!(a.transfer(f, &mut b), Err(Error::Transfer(_))));
+    let (car, _) = a.eval_str("(list car length)").unwrap();
+    assert!(matches!(a.transfer(car, &mut b), Err(Error::Transfer(_))));
+    let (length, _) = a.eval_str("length").unwrap();
+    assert_eq!(Ok(length), a.transfer(length, &mut b));
+}
+
+#[test]
+fn frame_reuse() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))").unwrap();
+    m.eval_str("(define (make) (lambda () 5))").unwrap();
+    m.eval_str("(define g (make))").unwrap();
+
+    let before = m.vm().stats();
+    assert_eq!(Value::Integer(3628800), m.eval_str("(fact 10)").unwrap().0);
+    assert_eq!(Value::Integer(3628800), m.eval_str("(fact 10)").unwrap().0);
+    assert!((m.vm().stats() - before).frames_reused >= 10);
+
+    // The frame captured by `g` is never reused, so its environment is still intact
+    assert_eq!(Value::Integer(5), m.eval_str("(g)").unwrap().0);
+}
+
+#[test]
+fn inline_caches() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (f x) (+ x 1))").unwrap();
+    m.eval_str("(define (twice x) (f (f x)))").unwrap();
+    assert_eq!(Value::Integer(3), m.eval_str("(twice 1)").unwrap().0);
+    let before = m.vm().stats();
+    assert_eq!(Value::Integer(3), m.eval_str("(twice 1)").unwrap().0);
+    assert!((m.vm().stats() - before).cache_hits >= 3);
+
+    // Redefining a procedure is seen by call sites which have already called it
+    m.eval_str("(define (f x) (* x 10))").unwrap();
+    assert_eq!(Value::Integer(100), m.eval_str("(twice 1)").unwrap().0);
+
+    // A call to a procedure bound in the frame of the call is never cached
+    m.eval_str("(define (shadow f) (f 1))").unwrap();
+    assert_eq!("inner", format!("{}", m.eval_str("(shadow (lambda (x) 'inner))").unwrap().0));
+    assert_eq!(Value::Integer(2), m.eval_str("(shadow (lambda (x) (+ x 1)))").unwrap().0);
+    assert_eq!(Value::Integer(100), m.eval_str("(twice 1)").unwrap().0);
+}
+
+#[test]
+fn read_from_string() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let eval = |m: &mut Minerva, s: &str| format!("{}", m.eval_str(s).unwrap().0);
+    assert_eq!("((a 1 \"b\") . 9)", eval(&mut m, "(read-from-string \"(a 1 \\\"b\\\") (c)\")"));
+    assert_eq!("((c) . 13)", eval(&mut m, "(read-from-string \"(a 1 \\\"b\\\") (c)\" 9)"));
+    // The delimiter after a number is read with it
+    assert_eq!("(42 . 5)", eval(&mut m, "(read-from-string \"  42 x\")"));
+    assert_eq!("(x . 6)", eval(&mut m, "(read-from-string \"  42 x\" 5)"));
+    assert_eq!("((quote (#t)) . 9)", eval(&mut m, "(read-from-string \"; c\n'(#t)\")"));
+    assert_eq!("(#u8(1 2) . 8)", eval(&mut m, "(read-from-string \"#u8(1 2) 3\")"));
+    assert_eq!(Value::Eof, m.eval_str("(read-from-string \"  ; nothing\")").unwrap().0);
+
+    assert_eq!(Err(Error::Condition("Exception in read-from-string: Unexpected end of input".to_string())),
+               m.eval_str("(read-from-string \"(a (b)\")"));
+    assert_eq!(Err(Error::Condition("Exception in read-from-string: 9 is not a valid index".to_string())),
+               m.eval_str("(read-from-string \"abc\" 9)"));
+
+    assert_eq!(r#""(1 \"two\" three)""#, eval(&mut m, "(write-to-string (list 1 \"two\" 'three))"));
+}
+
+#[test]
+fn write_round_trip() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let eval = |m: &mut Minerva, s: &str| format!("{}", m.eval_str(s).unwrap().0);
+    assert_eq!(r#""say \"hi\"""#, eval(&mut m, r#""say \"hi\"""#));
+    assert_eq!(r#""a\\b\nc\td\re\x1b;""#, eval(&mut m, r#""a\\b\nc\td\re\x1b;""#));
+    assert_eq!(r#""λ""#, eval(&mut m, r#""\x3bb;""#));
+
+    // Writing a string and reading it back gives the same string
+    m.eval_str(r#"(define s "q\"\\\a\b\x0;")"#).unwrap();
+    assert_eq!(r#""q\"\\\a\b\x0;""#, eval(&mut m, "s"));
+    assert_eq!(eval(&mut m, "s"), eval(&mut m, "(car (read-from-string (write-to-string s)))"));
+    assert_eq!(eval(&mut m, "(list s)"), eval(&mut m, "(car (read-from-string (write-to-string (list s))))"));
+
+    assert_eq!(Err(Error::Parse(ParseError::BadEscape('x', 1, 2))), m.eval_str(r#""\xzz;""#));
+
+    // Floats read back as floats
+    assert_eq!("(2.0 1e21 0.1)", eval(&mut m, "(car (read-from-string (write-to-string (list 2.0 1e21 0.1))))"));
+}
+
+#[test]
+fn current_stack() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let eval = |m: &mut Minerva, s: &str| format!("{}", m.eval_str(s).unwrap().0);
+    m.eval_str("(define (names s) (reverse (fold (lambda (frame acc) (cons (car frame) acc)) '() s)))").unwrap();
+    m.eval_str("(define (inner) (current-stack))").unwrap();
+    m.eval_str("(define (outer) (cons 'outer (inner)))").unwrap();
+    assert_eq!("(inner outer)", eval(&mut m, "(names (cdr (outer)))"));
+    assert_eq!(eval(&mut m, "inner"), eval(&mut m, "(car (cdr (car (inner))))"));
+    assert_eq!("()", eval(&mut m, "(current-stack)"));
+
+    // A tail call replaces the frame of its caller
+    m.eval_str("(define (tail) (inner))").unwrap();
+    assert_eq!("(inner)", eval(&mut m, "(names (tail))"));
+
+    // Anonymous procedures have no name
+    assert_eq!("(#f)", eval(&mut m, "(names ((lambda () (current-stack))))"));
+
+    m.eval_str("(define (deep n) (if (= n 0) (current-stack 3) (car (cons (deep (- n 1)) n))))").unwrap();
+    assert_eq!("(deep deep deep)", eval(&mut m, "(names (deep 10))"));
+    assert_eq!(Err(Error::Condition("Exception in current-stack: -1 is not a valid limit".to_string())),
+               m.eval_str("(current-stack -1)"));
+}
@@ -0,0 +1,89 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+// A directory tree for a test, with `paths` as empty files. Directories end in `/`.
+fn tree(test: &str, paths: &[&str]) -> PathBuf {
+    let root = env::temp_dir().join(format!("minerva-files-{}-{}", test, process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for path in paths {
+        if let Some(dir) = path.strip_suffix('/') {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        } else {
+            fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+            fs::write(root.join(path), "").unwrap();
+        }
+    }
+    root
+}
+
+// The paths `input` evaluates to, relative to `root`.
+fn relative(m: &mut Minerva, root: &PathBuf, input: &str) -> String {
+    eval(m, input).replace(&format!("{}/", root.display()), "")
+}
+
+#[cfg(unix)]
+#[test]
+fn glob() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let root = tree("glob", &["a.ss", "b.ss", "c.txt", ".d.ss", "src/e.ss", "src/lib/f.ss", "src/.git/g.ss", "empty/"]);
+    let glob = |m: &mut Minerva, pattern: &str, flags: &str| {
+        relative(m, &root, &format!("(glob \"{}/{}\" '({}))", root.display(), pattern, flags))
+    };
+    assert_eq!("(\"a.ss\" \"b.ss\")", glob(&mut m, "*.ss", ""));
+    assert_eq!("(\".d.ss\")", glob(&mut m, ".*", ""));
+    assert_eq!("(\".d.ss\" \"a.ss\" \"b.ss\")", glob(&mut m, "*.ss", "hidden"));
+    assert_eq!("(\"c.txt\")", glob(&mut m, "[a-c].[!s]*", ""));
+    assert_eq!("(\"a.ss\" \"b.ss\" \"src/e.ss\" \"src/lib/f.ss\")", glob(&mut m, "**/*.ss", ""));
+    assert_eq!("(\"src/e.ss\" \"src/lib/f.ss\")", glob(&mut m, "src/**/?.ss", ""));
+    assert_eq!("(\"src/.git/g.ss\" \"src/e.ss\" \"src/lib/f.ss\")", glob(&mut m, "src/**/?.ss", "hidden"));
+    assert_eq!("(\"src/lib\")", glob(&mut m, "*/l*", ""));
+    assert_eq!("()", glob(&mut m, "missing/*", ""));
+
+    assert_eq!("Exception in glob: all is not a flag", exception(&mut m, "(glob \"*\" '(all))"));
+    assert_eq!("Exception in glob: 1 is not a string", exception(&mut m, "(glob 1)"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn walk() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let root = tree("walk", &["b.ss", "a/x.ss", "a/.y", ".z/w"]);
+    std::os::unix::fs::symlink(&root, root.join("a/loop")).unwrap();
+    let walk = |m: &mut Minerva, flags: &str| {
+        m.eval_str("(define seen '())").unwrap();
+        m.eval_str(&format!("(walk-directory \"{}\" (lambda (p) (set! seen (cons p seen))) '({}))",
+                            root.display(), flags)).unwrap();
+        relative(m, &root, "(reverse seen)")
+    };
+    assert_eq!("(\"a\" \"a/loop\" \"a/x.ss\" \"b.ss\")", walk(&mut m, ""));
+    assert_eq!("(\".z\" \".z/w\" \"a\" \"a/.y\" \"a/loop\" \"a/x.ss\" \"b.ss\")", walk(&mut m, "hidden"));
+    // The link back to the root is not followed
+    assert_eq!("(\"a\" \"a/loop\" \"a/x.ss\" \"b.ss\")", walk(&mut m, "follow-links"));
+
+    let file = format!("(walk-directory \"{}/b.ss\" display)", root.display());
+    assert!(exception(&mut m, &file).starts_with("Exception in walk-directory: could not read "));
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,57 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn fueled(m: &mut Minerva, input: &str, fuel: usize) -> Result<String, Error> {
+    m.eval_with_fuel(input, fuel).map(|(v, _)| format!("{}", v))
+}
+
+fn resume(m: &mut Minerva, fuel: usize) -> Result<String, Error> {
+    m.resume(fuel).map(|(v, _)| format!("{}", v))
+}
+
+#[test]
+fn fuel() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (loop) (loop))").unwrap();
+    m.eval_str("(define (count n) (if (= n 0) 'done (count (- n 1))))").unwrap();
+
+    assert_eq!(Ok("3".to_string()), fueled(&mut m, "(+ 1 2)", 1000));
+    assert_eq!(Err(Error::OutOfFuel), fueled(&mut m, "(loop)", 1000));
+    assert!(m.last_report().instructions >= 1000);
+    // Evaluating without fuel abandons the interrupted evaluation, and isn't limited
+    assert_eq!("3", eval(&mut m, "(+ 1 2)"));
+    assert_eq!(Err(Error::NotInterrupted), resume(&mut m, 1000));
+    assert_eq!("done", eval(&mut m, "(count 200)"));
+
+    // Resuming continues where the fuel ran out, and then runs the forms after it
+    assert_eq!(Err(Error::OutOfFuel), fueled(&mut m, "(define x (count 200)) (define y 2) (list x y)", 100));
+    let mut result = resume(&mut m, 100);
+    let mut resumes = 1;
+    while result == Err(Error::OutOfFuel) {
+        result = resume(&mut m, 100);
+        resumes += 1;
+    }
+    assert_eq!(Ok("(done 2)".to_string()), result);
+    assert!(resumes > 10);
+    assert!(m.last_report().instructions > 1000);
+    assert_eq!(Err(Error::NotInterrupted), resume(&mut m, 100));
+
+    // Other errors end the evaluation as usual
+    assert_eq!(Err(Error::OutOfFuel), fueled(&mut m, "(count 100) (car 1) 'unreached", 100));
+    assert_eq!(Err(Error::Condition("Exception in car: 1 is not a pair".to_string())), resume(&mut m, 10000));
+    assert_eq!(Err(Error::NotInterrupted), resume(&mut m, 100));
+
+    // Fuel running out where a native procedure applies a procedure can't be resumed
+    assert_eq!(Err(Error::Condition("Exception in fuel: ran out".to_string())),
+               fueled(&mut m, "(for-each (lambda (x) (loop)) '(1))", 1000));
+    assert_eq!(Err(Error::NotInterrupted), resume(&mut m, 100));
+}
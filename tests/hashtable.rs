@@ -0,0 +1,71 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn capacity() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("0", eval(&mut m, "(hash-table-capacity (make-hash-table))"));
+    assert_eq!("0.0", eval(&mut m, "(hash-table-load-factor (make-hash-table))"));
+
+    m.eval_str("(define t (make-hash-table 100))").unwrap();
+    assert_eq!("#t", eval(&mut m, "(< 99 (hash-table-capacity t))"));
+    assert_eq!("0.0", eval(&mut m, "(hash-table-load-factor t)"));
+    m.eval_str("(hash-table-set! t 'a 1)").unwrap();
+    assert_eq!("#t", eval(&mut m, "(< 0 (hash-table-load-factor t))"));
+
+    m.eval_str("(hash-table-reserve! t 1000)").unwrap();
+    assert_eq!("#t", eval(&mut m, "(< 1000 (hash-table-capacity t))"));
+    assert_eq!("1", eval(&mut m, "(hash-table-ref t 'a)"));
+
+    assert_eq!("Exception in make-hash-table: -1 is not a valid capacity",
+               exception(&mut m, "(make-hash-table -1)"));
+    assert_eq!("Exception in hash-table-reserve!: a is not a valid capacity",
+               exception(&mut m, "(hash-table-reserve! t 'a)"));
+}
+
+#[test]
+fn bulk() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define t (alist->hash-table (list (cons 'a 1) (cons 'b 2) (cons 'a 3))))").unwrap();
+    assert_eq!("2", eval(&mut m, "(hash-table-count t)"));
+    assert_eq!("1", eval(&mut m, "(hash-table-ref t 'a)"));
+    assert_eq!("2", eval(&mut m, "(hash-table-ref (alist->hash-table (vector (cons 'a 1) (cons 'b 2))) 'b)"));
+    assert_eq!("0", eval(&mut m, "(hash-table-count (alist->hash-table '()))"));
+
+    // A copy doesn't share its entries
+    m.eval_str("(define c (hash-table-copy t))").unwrap();
+    m.eval_str("(hash-table-set! c 'c 3)").unwrap();
+    assert_eq!("3", eval(&mut m, "(hash-table-count c)"));
+    assert_eq!("2", eval(&mut m, "(hash-table-count t)"));
+
+    m.eval_str("(define u (alist->hash-table (list (cons 'b 20) (cons 'd 40))))").unwrap();
+    m.eval_str("(hash-table-merge! c u)").unwrap();
+    assert_eq!("4", eval(&mut m, "(hash-table-count c)"));
+    assert_eq!("20", eval(&mut m, "(hash-table-ref c 'b)"));
+    assert_eq!("40", eval(&mut m, "(hash-table-ref c 'd)"));
+    assert_eq!("22", eval(&mut m, "(hash-table-ref (hash-table-merge! t u (lambda (k a b) (+ a b))) 'b)"));
+    assert_eq!("1", eval(&mut m, "(hash-table-ref t 'a)"));
+
+    assert_eq!("Exception in alist->hash-table: 1 is not a pair", exception(&mut m, "(alist->hash-table '(1))"));
+    assert_eq!("Exception in alist->hash-table: a is not a list or vector",
+               exception(&mut m, "(alist->hash-table 'a)"));
+    assert_eq!("Exception in hash-table-merge!: 1 is not a hash table", exception(&mut m, "(hash-table-merge! t 1)"));
+}
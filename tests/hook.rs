@@ -0,0 +1,69 @@
+#![cfg(feature = "hooks")]
+
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::Minerva;
+use vm::{Operation, Value, VmHook, VM};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct Events {
+    instructions: usize,
+    calls: Vec<usize>,
+    returned: Vec<Value>,
+}
+
+struct Recorder(Rc<RefCell<Events>>);
+
+impl VmHook for Recorder {
+    fn instruction(&mut self, _vm: &VM, _pc: usize, _op: Operation) {
+        self.0.borrow_mut().instructions += 1;
+    }
+
+    fn call(&mut self, _vm: &VM, _procedure: Value, argc: usize) {
+        self.0.borrow_mut().calls.push(argc);
+    }
+
+    fn ret(&mut self, _vm: &VM, _procedure: Value, result: Value) {
+        self.0.borrow_mut().returned.push(result);
+    }
+}
+
+#[test]
+fn events() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (id x) x)").unwrap();
+
+    let events = Rc::new(RefCell::new(Events::default()));
+    m.vm().set_hook(Recorder(events.clone()));
+    let steps = m.vm().steps();
+    assert_eq!("(1 2)", format!("{}", m.eval_str("(list (id 1) (id 2))").unwrap().0));
+
+    let mut events = events.borrow_mut();
+    // Every instruction run is seen once
+    assert_eq!(m.vm().steps() - steps, events.instructions);
+    // `id` twice and `list`, which doesn't return as compound procedures do
+    events.calls.sort();
+    assert_eq!(vec![1, 1, 2], events.calls);
+    assert_eq!(2, events.returned.len());
+    assert!(events.returned.contains(&Value::Integer(1)));
+    assert!(events.returned.contains(&Value::Integer(2)));
+}
+
+#[test]
+fn take_hook() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let events = Rc::new(RefCell::new(Events::default()));
+    m.vm().set_hook(Recorder(events.clone()));
+    assert!(m.vm().take_hook().is_some());
+    m.eval_str("(+ 1 2)").unwrap();
+    assert_eq!(0, events.borrow().instructions);
+}
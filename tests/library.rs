@@ -0,0 +1,99 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+use std::env;
+use std::fs;
+use std::process;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn define_and_import() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("
+        (define-library (geometry shapes)
+          (export area (rename make-square square))
+          (import (scheme base))
+          (begin
+            (define (make-square side) (list 'square side))
+            (define (area shape) (* (car (cdr shape)) (car (cdr shape))))))").unwrap();
+    m.eval_str("(import (prefix (geometry shapes) shapes:))").unwrap();
+    assert_eq!("16", eval(&mut m, "(shapes:area (shapes:square 4))"));
+    assert!(m.eval_str("make-square").is_err());
+
+    m.eval_str("(import (rename (only (geometry shapes) area) (area size)))").unwrap();
+    assert_eq!("9", eval(&mut m, "(size (shapes:square 3))"));
+    assert!(m.eval_str("area").is_err());
+    m.eval_str("(import (except (geometry shapes) area))").unwrap();
+    assert_eq!("(square 1)", eval(&mut m, "(square 1)"));
+
+    // A library sees only what it imports
+    m.eval_str("(define secret 1)").unwrap();
+    m.eval_str("(define-library (peek) (export peek) (begin (define (peek) secret)))").unwrap();
+    m.eval_str("(import (peek))").unwrap();
+    assert!(m.eval_str("(peek)").is_err());
+
+    // Exports may be imported, and macros stay in their library
+    m.eval_str("
+        (define-library (numbers)
+          (export twice area)
+          (import (scheme base) (only (geometry shapes) area))
+          (begin
+            (define-syntax double (syntax-rules () ((_ x) (* 2 x))))
+            (define (twice x) (double x))))").unwrap();
+    m.eval_str("(import (numbers))").unwrap();
+    assert_eq!("6", eval(&mut m, "(twice 3)"));
+    assert_eq!("4", eval(&mut m, "(area (square 2))"));
+    assert!(m.eval_str("(double 2)").is_err());
+
+    assert_eq!("Exception in import: volume is not in (geometry shapes)",
+               exception(&mut m, "(import (only (geometry shapes) volume))"));
+    assert_eq!("Exception in import: there is no library (no such library)",
+               exception(&mut m, "(import (no such library))"));
+    assert_eq!("Exception in define-library: (bad) does not define x",
+               exception(&mut m, "(define-library (bad) (export x) (begin (define y 1)))"));
+    assert_eq!("Exception in define-library: (1.5) is not a library name",
+               exception(&mut m, "(define-library (1.5))"));
+    assert_eq!("Exception in define-library: (provide x) is not a library declaration",
+               exception(&mut m, "(define-library (bad) (provide x))"));
+}
+
+#[test]
+fn search_path() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let root = env::temp_dir().join(format!("minerva-library-{}", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("geometry")).unwrap();
+    fs::write(root.join("geometry/units.sld"), "
+        (define-library (geometry units)
+          (export metres)
+          (import (scheme base) (geometry scale))
+          (include \"units-body.ss\"))").unwrap();
+    // Included files are found next to the library's file
+    fs::write(root.join("geometry/units-body.ss"), "(define (metres n) (* n scale))").unwrap();
+    fs::write(root.join("geometry/scale.ss"),
+              "(define-library (geometry scale) (export scale) (begin (define scale 100)))").unwrap();
+    fs::write(root.join("empty.sld"), "(define x 1)").unwrap();
+
+    m.eval_str(&format!("(add-library-path! \"{}\")", root.display())).unwrap();
+    m.eval_str("(import (geometry units))").unwrap();
+    assert_eq!("300", eval(&mut m, "(metres 3)"));
+    let message = exception(&mut m, "(import (empty))");
+    assert!(message.ends_with("empty.sld does not define (empty)"), "{}", message);
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,51 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::Minerva;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn unshared_lists() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("(3 2 1)", eval(&mut m, "(reverse (list 1 2 3))"));
+    assert_eq!("(1 2 3)", eval(&mut m, "(reverse (reverse (list 1 2 3)))"));
+    assert_eq!("(1 2 3)", eval(&mut m, "(append (list 1 2) '(3))"));
+    m.eval_str("(define (backwards a b) (reverse (list a b)))").unwrap();
+    assert_eq!("(2 1)", eval(&mut m, "(backwards 1 2)"));
+
+    // The pairs of the list are reused rather than copied
+    let (_, made) = m.eval_str("(list 1 2 3 4 5 6 7 8)").unwrap();
+    let (_, reversed) = m.eval_str("(reverse (reverse (list 1 2 3 4 5 6 7 8)))").unwrap();
+    assert!(reversed.allocations < made.allocations + 8);
+}
+
+#[test]
+fn redefined() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (reverse l) 'mine)").unwrap();
+    assert_eq!("mine", eval(&mut m, "(reverse (list 1 2 3))"));
+
+    // A list made by a redefined `list` may be shared, so it is left alone
+    let mut m = Minerva::new();
+    m.eval_str("(define shared '(1 2 3))").unwrap();
+    m.eval_str("(define (list a b c) shared)").unwrap();
+    assert_eq!("(3 2 1)", eval(&mut m, "(reverse (list 1 2 3))"));
+    assert_eq!("(1 2 3)", eval(&mut m, "shared"));
+
+    // Redefined after the call was compiled
+    let mut m = Minerva::new();
+    m.eval_str("(define (joined a) (append (list a) '(2)))").unwrap();
+    assert_eq!("(1 2)", eval(&mut m, "(joined 1)"));
+    m.eval_str("(define (append a b) b)").unwrap();
+    assert_eq!("(2)", eval(&mut m, "(joined 1)"));
+
+    // Shadowed by a formal
+    assert_eq!("3", eval(&mut m, "((lambda (reverse) (reverse (list 1 2 3))) length)"));
+}
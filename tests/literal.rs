@@ -0,0 +1,235 @@
+extern crate minerva;
+extern crate num_bigint;
+extern crate string_interner;
+extern crate vm;
+
+use minerva::{compile, optimize, output_asm, ParseError, Parser, Token, Tokenizer};
+use num_bigint::BigInt;
+use string_interner::get_symbol;
+use vm::{assemble, init_env, Register, Value, VM};
+
+fn token(input: &str) -> Token {
+    Tokenizer::tokenize(input).unwrap().remove(0)
+}
+
+fn eval(vm: &mut VM, input: &str) -> Value {
+    let ast = Parser::parse(Tokenizer::tokenize(input).unwrap()).unwrap().remove(0);
+    let (code, consts) = assemble(output_asm(optimize(compile(ast))));
+    vm.load_code(code, consts);
+    vm.run();
+    vm.load_register(Register(0))
+}
+
+#[test]
+fn fixnum_boundary() {
+    assert_eq!(Token::Integer(i32::MAX), token("2147483647"));
+    assert_eq!(Token::Integer(i32::MIN), token("-2147483648"));
+    assert_eq!(Token::BigInt(BigInt::from(i32::MAX as i64 + 1)), token("2147483648"));
+    assert_eq!(Token::BigInt(BigInt::from(i32::MIN as i64 - 1)), token("-2147483649"));
+    assert_eq!(Token::BigInt("123456789012345678901234567890".parse().unwrap()),
+               token("+123456789012345678901234567890"));
+}
+
+#[test]
+fn signed_literal() {
+    let sym = |s: &str| Token::Symbol(get_symbol(s.to_string()));
+    assert_eq!(Token::Integer(-5), token("-5"));
+    assert_eq!(Token::Integer(5), token("+5"));
+    assert_eq!(Token::Float(-0.5), token("-.5"));
+    assert_eq!(Token::Float(1.5e2), token("+1.5e2"));
+    for s in &["-", "+", "...", "-a", "+a", "1+", "--5", "+-5", "1/+2", "1/"] {
+        assert_eq!(sym(s), token(s));
+    }
+    // A number ends at any delimiter
+    assert_eq!(vec![Token::LeftParen, Token::Integer(-5), Token::String("a".to_string()), Token::Integer(-1),
+                    Token::Comment(";b".to_string()), Token::Symbol(get_symbol("-".to_string())), Token::RightParen],
+               Tokenizer::tokenize("(-5\"a\"-1;b\n-)").unwrap());
+    assert_eq!(vec![sym("-1 2")], Tokenizer::tokenize("-|1 2|").unwrap());
+
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    assert_eq!(Value::Integer(1), eval(&mut vm, "(+ -1 2)"));
+    assert_eq!(Value::Integer(-7), eval(&mut vm, "(- -5 2)"));
+    assert_eq!("(-5 + 3)", format!("{}", eval(&mut vm, "'(-5 + +3)")));
+}
+
+#[test]
+fn float_literal() {
+    assert_eq!(Token::Float(2.75), token("2.75"));
+    assert_eq!(Token::Float(1e10), token("1e10"));
+    assert_eq!(Token::Float(0.5), token(".5"));
+    assert_eq!(Token::Float(5.0), token("5."));
+    assert_eq!(Token::Float(-2500.0), token("-2.5E3"));
+    assert_eq!(Token::Float(1000.0), token("1.e3"));
+    assert_eq!(Token::Float(f64::INFINITY), token("+inf.0"));
+    assert_eq!(Token::Float(f64::NEG_INFINITY), token("-inf.0"));
+    assert!(matches!(token("+nan.0"), Token::Float(f) if f.is_nan()));
+    for s in &["1e", "e1", ".e1", "inf.0", "+inf", "1.2.3"] {
+        assert!(matches!(token(s), Token::Symbol(_)), "{} is not a symbol", s);
+    }
+
+    // Floats are written so that they read back as the same float
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    for s in &["0.1", "-2.5", "1e21", "1e-7", "123456.789", "+inf.0", "-inf.0", "+nan.0"] {
+        let v = eval(&mut vm, s);
+        assert!(v.is_float());
+        assert_eq!(*s, format!("{}", v));
+    }
+}
+
+#[test]
+fn ratio_literal() {
+    assert_eq!(Token::Integer(3), token("6/2"));
+    assert_eq!(Token::Integer(-2), token("-4/2"));
+    assert_eq!(Token::Integer(0), token("+0/7"));
+    assert_eq!(Token::BigInt(BigInt::from(1) << 40), token("2199023255552/2"));
+    // Without rationals, other ratios can't be read
+    assert_eq!(Err(ParseError::Expected("a ratio which is an integer, as there are no rationals")),
+               Tokenizer::tokenize("1/3"));
+    assert_eq!(Err(ParseError::Expected("a ratio with a nonzero denominator")), Tokenizer::tokenize("(list 1/0)"));
+
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    assert_eq!(Value::Integer(5), eval(&mut vm, "(+ 9/3 2)"));
+    assert_eq!(Value::Float(4.0), eval(&mut vm, "#i8/2"));
+}
+
+#[test]
+fn bignum_literal() {
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+
+    assert_eq!(Value::Integer(i32::MAX), eval(&mut vm, "2147483647"));
+    let v = eval(&mut vm, "2147483648");
+    assert!(v.is_bigint());
+    assert_eq!("2147483648", format!("{}", v));
+    assert_eq!("-123456789012345678901234567890", format!("{}", eval(&mut vm, "'-123456789012345678901234567890")));
+    assert_eq!(Value::True, eval(&mut vm, "(= 99999999999 99999999999)"));
+    assert_eq!(Value::False, eval(&mut vm, "(= 99999999999 99999999998)"));
+}
+
+#[test]
+fn exactness_prefix() {
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+
+    assert_eq!(Value::Integer(2), eval(&mut vm, "#e2.0"));
+    assert_eq!(Value::Float(5.0), eval(&mut vm, "#i5"));
+    assert_eq!(Value::Float(-1.5), eval(&mut vm, "#i-1.5"));
+    assert_eq!(Value::Integer(-3), eval(&mut vm, "#e-3"));
+    assert_eq!("1000000000000000000000", format!("{}", eval(&mut vm, "#e1e21")));
+    assert_eq!("(3 4.0)", format!("{}", eval(&mut vm, "'(#e3 #i4)")));
+    // There are no rationals to make non-integral numbers exact
+    assert!(Parser::parse(Tokenizer::tokenize("#e1.5").unwrap()).is_err());
+    assert!(Parser::parse(Tokenizer::tokenize("#ifoo").unwrap()).is_err());
+}
+
+#[test]
+fn radix_prefix() {
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+
+    assert_eq!(Value::Integer(255), eval(&mut vm, "#xff"));
+    assert_eq!(Value::Integer(-31), eval(&mut vm, "#X-1F"));
+    assert_eq!(Value::Integer(8), eval(&mut vm, "#o10"));
+    assert_eq!(Value::Integer(5), eval(&mut vm, "#b+101"));
+    assert_eq!(Value::Integer(10), eval(&mut vm, "#d10"));
+    assert_eq!(Value::Float(1.5), eval(&mut vm, "#d1.5"));
+    assert_eq!("340282366920938463463374607431768211455",
+               format!("{}", eval(&mut vm, "#xffffffffffffffffffffffffffffffff")));
+    // Exactness and radix prefixes go in either order
+    assert_eq!(Value::Float(16.0), eval(&mut vm, "#i#x10"));
+    assert_eq!(Value::Integer(2), eval(&mut vm, "#b#e10"));
+    assert_eq!("(15 7 (1 3))", format!("{}", eval(&mut vm, "'(#xf #o7 (#b1 #x3))")));
+
+    for s in &["#x", "#xfg", "#b102", "#o8", "#x1.5", "#x#x1", "#e#i1", "#x1_0"] {
+        assert!(Parser::parse(Tokenizer::tokenize(s).unwrap()).is_err(), "{} was read", s);
+    }
+}
+
+#[test]
+fn char_literal() {
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+
+    assert_eq!(Token::Char('a'), token("#\\a"));
+    assert_eq!(Token::Char('('), token("#\\("));
+    assert_eq!(Token::Char(' '), token("#\\space"));
+    assert_eq!(Token::Char('λ'), token("#\\x3bb"));
+    assert_eq!(Token::Char('x'), token("#\\x"));
+    assert_eq!(vec![Token::LeftParen, Token::Char('a'), Token::Char(')'), Token::RightParen],
+               Tokenizer::tokenize("(#\\a #\\))").unwrap());
+    assert!(Tokenizer::tokenize("#\\bogus").is_err());
+    assert!(Tokenizer::tokenize("#\\").is_err());
+
+    assert_eq!(Value::Char('a'), eval(&mut vm, "#\\a"));
+    // Written the way they are read
+    assert_eq!("(#\\a #\\space #\\newline #\\null #\\λ)",
+               format!("{}", eval(&mut vm, "'(#\\a #\\space #\\newline #\\x0 #\\x3bb)")));
+    assert_eq!(Value::Integer(955), eval(&mut vm, "(char->integer #\\λ)"));
+    assert_eq!(Value::Char('A'), eval(&mut vm, "(char-upcase #\\a)"));
+}
+
+#[test]
+fn string_escapes() {
+    let string = |s: &str| Token::String(s.to_string());
+    assert_eq!(string("a\nb\tc\rd\x07\x08"), token(r#""a\nb\tc\rd\a\b""#));
+    assert_eq!(string("\\ \" | \0"), token(r#""\\ \" \| \0""#));
+    assert_eq!(string("λ😀"), token(r#""\x3bb;\x1F600;""#));
+    assert_eq!(string("é€"), token(r#""\u00e9\u20AC""#));
+    // A `\` at the end of a line joins it to the next, without the whitespace between the `\` and
+    // the first character of the next line. Whitespace before the `\` is kept.
+    assert_eq!(string("one two"), token("\"one \\  \n   two\""));
+    assert_eq!(string("onetwo"), token("\"one\\\r\ntwo\""));
+
+    assert_eq!(Err(ParseError::BadEscape('q', 1, 2)), Tokenizer::tokenize(r#""\q""#));
+    assert_eq!(Err(ParseError::BadEscape('x', 1, 4)), Tokenizer::tokenize(r#""ab\x;""#));
+    assert_eq!(Err(ParseError::BadEscape('x', 2, 3)), Tokenizer::tokenize("\"a\n b\\xd800;\""));
+    assert_eq!(Err(ParseError::BadEscape('u', 1, 2)), Tokenizer::tokenize(r#""\u12g4""#));
+    assert_eq!(Err(ParseError::BadEscape(' ', 1, 2)), Tokenizer::tokenize(r#""\ a""#));
+    assert_eq!("Invalid escape `\\q` in string at 1:2", format!("{}", ParseError::BadEscape('q', 1, 2)));
+    assert_eq!(Err(ParseError::InString), Tokenizer::tokenize(r#""\u12"#));
+}
+
+#[test]
+fn vector_literal() {
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+
+    assert_eq!("#(1 \"two\" #\\3)", format!("{}", eval(&mut vm, "#(1 \"two\" #\\3)")));
+    assert_eq!("#()", format!("{}", eval(&mut vm, "'#()")));
+    // The elements are quoted
+    assert_eq!("#(a (b . c) #(d) #u8(1))", format!("{}", eval(&mut vm, "#(a (b . c) #(d) #u8(1))")));
+    assert_eq!("(1 #(2 3))", format!("{}", eval(&mut vm, "'(1 #(2 3))")));
+    assert_eq!(Value::Integer(3), eval(&mut vm, "(vector-ref #(1 2 3) 2)"));
+    assert_eq!(Value::Bool(true), eval(&mut vm, "(vector? #(1))"));
+    // What is written reads back
+    assert_eq!("#(1 2 3)", format!("{}", eval(&mut vm, "(vector 1 2 3)")));
+    assert!(matches!(Parser::parse(Tokenizer::tokenize("#(1 2").unwrap()), Err(ParseError::EOF)));
+}
+
+#[test]
+fn bytevector_literal() {
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+
+    assert_eq!("#u8(0 1 255)", format!("{}", eval(&mut vm, "#u8(0 1 255)")));
+    assert_eq!("#u8()", format!("{}", eval(&mut vm, "'#u8()")));
+    assert_eq!("(1 #u8(2 3))", format!("{}", eval(&mut vm, "'(1 #u8(2 3))")));
+    assert!(Parser::parse(Tokenizer::tokenize("#u8(256)").unwrap()).is_err());
+    assert!(Parser::parse(Tokenizer::tokenize("#u8(a)").unwrap()).is_err());
+    assert!(Parser::parse(Tokenizer::tokenize("#u8(1").unwrap()).is_err());
+
+    assert_eq!(Value::Integer(3), eval(&mut vm, "(bytevector-length #u8(1 2 3))"));
+    assert_eq!(Value::Integer(2), eval(&mut vm, "(bytevector-u8-ref #u8(1 2 3) 1)"));
+    assert_eq!("#u8(7 7)", format!("{}", eval(&mut vm, "(make-bytevector 2 7)")));
+    assert_eq!("#u8(0 0 0)", format!("{}", eval(&mut vm, "(make-bytevector 3)")));
+    assert_eq!("#u8(2 3)", format!("{}", eval(&mut vm, "(bytevector-copy #u8(1 2 3) 1)")));
+    assert_eq!("#u8(2)", format!("{}", eval(&mut vm, "(bytevector-copy #u8(1 2 3) 1 2)")));
+    assert_eq!("#u8(1 9)",
+               format!("{}", eval(&mut vm, "((lambda (b) (bytevector-u8-set! b 1 9) b) (make-bytevector 2 1))")));
+    // A copy does not share bytes with the original
+    assert_eq!("#u8(1 2)",
+               format!("{}", eval(&mut vm, "((lambda (b) (bytevector-u8-set! (bytevector-copy b) 0 9) b) #u8(1 2))")));
+}
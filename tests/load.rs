@@ -0,0 +1,128 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+// A directory for a test with `files`, each a name and its contents.
+fn files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = env::temp_dir().join(format!("minerva-load-{}-{}", test, process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("lib")).unwrap();
+    for (name, contents) in files {
+        fs::write(root.join(name), contents).unwrap();
+    }
+    root
+}
+
+#[test]
+fn include() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let root = files("include", &[
+        ("main.ss", "(include \"lib/square.ss\" \"lib/swap.ss\")\n(define x (square 3))"),
+        // Relative to the file including it
+        ("lib/square.ss", "(include \"double.ss\")\n(define (square x) (* x x))"),
+        ("lib/double.ss", "(define (double x) (+ x x))"),
+        ("lib/swap.ss", "(define-syntax swap! (syntax-rules () ((_ a b) (define a b))))"),
+        ("a.ss", "(include \"b.ss\")"),
+        ("b.ss", "(define b 1)\n(include \"a.ss\")"),
+    ]);
+    let main = root.join("main.ss");
+    m.eval_source(&main.to_string_lossy(), &fs::read_to_string(&main).unwrap()).unwrap();
+    assert_eq!("9", eval(&mut m, "x"));
+    assert_eq!("8", eval(&mut m, "(double 4)"));
+    // Macros defined by included files are defined for the code including them
+    m.eval_str("(swap! y 2)").unwrap();
+    assert_eq!("2", eval(&mut m, "y"));
+
+    let a = root.join("a.ss");
+    match m.eval_str(&format!("(include \"{}\")", a.display())) {
+        Err(Error::Include(message)) => assert!(message.ends_with("a.ss: it includes itself"), "{}", message),
+        r => panic!("expected an include error, found {:?}", r),
+    }
+    match m.eval_str("(include \"no/such/file.ss\")") {
+        Err(Error::Include(message)) => assert!(message.starts_with("Could not include no/such/file.ss: ")),
+        r => panic!("expected an include error, found {:?}", r),
+    }
+    assert_eq!(Err(Error::Parse(minerva::ParseError::Expected("the names of files to include"))),
+               m.eval_str("(include 1)").map(|_| ()));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn load() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let root = files("load", &[
+        ("square.ss", "(define (square x) (* x x))\n(square 5)"),
+        ("outer.ss", "(load \"lib/inner.ss\")\n(define outer (+ inner 1))"),
+        ("lib/inner.ss", "(define inner 41)"),
+        ("self.ss", "(load \"self.ss\")"),
+        ("fails.ss", "(define before 1)\n(car 1)\n(define after-load-unbound 2)"),
+    ]);
+    let load = |m: &mut Minerva, file: &str| eval(m, &format!("(load \"{}\")", root.join(file).display()));
+    assert_eq!("25", load(&mut m, "square.ss"));
+    assert_eq!("16", eval(&mut m, "(square 4)"));
+    load(&mut m, "outer.ss");
+    assert_eq!("42", eval(&mut m, "outer"));
+
+    let message = exception(&mut m, &format!("(load \"{}\")", root.join("self.ss").display()));
+    assert!(message.ends_with("self.ss is already being loaded"), "{}", message);
+    let message = exception(&mut m, &format!("(load \"{}\")", root.join("fails.ss").display()));
+    assert!(message.contains("car"), "{}", message);
+    assert_eq!("1", eval(&mut m, "before"));
+    assert!(m.eval_str("after-load-unbound").is_err());
+    let message = exception(&mut m, "(load \"no/such/file.ss\")");
+    assert!(message.starts_with("Exception in load: could not load no/such/file.ss: "), "{}", message);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn reload_module() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let root = files("module", &[
+        ("handlers.ss", "(define (greet) (helper))\n(define (helper) 1)\n(define count 0)"),
+    ]);
+    let handlers = root.join("handlers.ss");
+    let load = format!("(load-module 'handlers \"{}\")", handlers.display());
+    assert_eq!("(count greet helper)", eval(&mut m, &load));
+    m.eval_str("(define (call-greet) (greet))").unwrap();
+    assert_eq!("1", eval(&mut m, "(call-greet)"));
+
+    // Call sites see the new definitions
+    fs::write(&handlers, "(define (greet) (helper))\n(define (helper) 2)").unwrap();
+    assert_eq!("(greet helper)", eval(&mut m, "(reload-module 'handlers)"));
+    assert_eq!("2", eval(&mut m, "(call-greet)"));
+    assert_eq!("0", eval(&mut m, "count"));
+
+    // A module which fails to load changes nothing
+    fs::write(&handlers, "(define (greet) 3)\n(car 1)").unwrap();
+    assert!(exception(&mut m, "(reload-module 'handlers)").contains("car"));
+    assert_eq!("2", eval(&mut m, "(call-greet)"));
+    fs::write(&handlers, "(define (greet) 3)\n(define (helper)").unwrap();
+    assert!(exception(&mut m, "(reload-module 'handlers)").starts_with("Exception in reload-module: "));
+    assert_eq!("2", eval(&mut m, "(call-greet)"));
+
+    assert_eq!("Exception in reload-module: nothing is not a module", exception(&mut m, "(reload-module 'nothing)"));
+    assert_eq!("Exception in load-module: 1 is not a symbol", exception(&mut m, "(load-module 1 \"handlers.ss\")"));
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,60 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn comparison() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("#t", eval(&mut m, "(string-locale<? \"apple\" \"banana\")"));
+    assert_eq!("#f", eval(&mut m, "(string-locale>? \"apple\" \"banana\")"));
+    assert_eq!("#t", eval(&mut m, "(string-locale=? \"apple\" \"apple\" \"en\")"));
+    assert_eq!("\"STRASSE\"", eval(&mut m, "(string-locale-upcase \"straße\")"));
+    assert_eq!("\"grüße\"", eval(&mut m, "(string-locale-downcase \"GRÜßE\")"));
+
+    assert_eq!("Exception in string-locale<?: 1 is not a string", exception(&mut m, "(string-locale<? 1 \"a\")"));
+    assert_eq!("Exception in string-locale-upcase: en is not a string",
+               exception(&mut m, "(string-locale-upcase \"a\" 'en)"));
+}
+
+#[cfg(not(feature = "icu"))]
+#[test]
+fn code_points() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    // Upper case letters come before all lower case ones, and the locale is ignored
+    assert_eq!("#t", eval(&mut m, "(string-locale<? \"Zebra\" \"apple\")"));
+    assert_eq!("#t", eval(&mut m, "(string-locale<? \"z\" \"ö\" \"de\")"));
+    assert_eq!("\"I\"", eval(&mut m, "(string-locale-upcase \"i\" \"tr\")"));
+}
+
+#[cfg(feature = "icu")]
+#[test]
+fn locales() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("#t", eval(&mut m, "(string-locale<? \"apple\" \"Zebra\")"));
+    assert_eq!("#t", eval(&mut m, "(string-locale<? \"ö\" \"z\" \"de\")"));
+    assert_eq!("#t", eval(&mut m, "(string-locale<? \"z\" \"ö\" \"sv\")"));
+    // Composed and decomposed forms collate the same
+    assert_eq!("#t", eval(&mut m, "(string-locale=? \"\\xe9;\" \"e\\x301;\")"));
+    assert_eq!("\"İ\"", eval(&mut m, "(string-locale-upcase \"i\" \"tr\")"));
+    assert_eq!("\"ı\"", eval(&mut m, "(string-locale-downcase \"I\" \"tr\")"));
+
+    assert_eq!("Exception in string-locale<?: \"not a tag!\" is not a locale",
+               exception(&mut m, "(string-locale<? \"a\" \"b\" \"not a tag!\")"));
+}
@@ -0,0 +1,55 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Location, Minerva, ParseError, Tokenizer};
+
+fn at(line: usize, column: usize, start: usize, end: usize) -> Location {
+    Location { line, column, span: start..end }
+}
+
+fn error(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_source("test.ss", input).unwrap_err())
+}
+
+#[test]
+fn tokens() {
+    let locations: Vec<_> = Tokenizer::tokenize_located("(car x)\n  'abc \"s\"(1)")
+        .unwrap()
+        .into_iter()
+        .map(|(_, l)| l)
+        .collect();
+    assert_eq!(vec![at(1, 1, 0, 1), at(1, 2, 1, 4), at(1, 6, 5, 6), at(1, 7, 6, 7),
+                    at(2, 3, 10, 11), at(2, 4, 11, 14), at(2, 8, 15, 18),
+                    at(2, 11, 18, 19), at(2, 12, 19, 20), at(2, 13, 20, 21)],
+               locations);
+    assert_eq!(Err((ParseError::InString, at(2, 3, 6, 10))), Tokenizer::tokenize_located("(a)\n  \"abc"));
+}
+
+#[test]
+fn forms() {
+    let forms = Tokenizer::tokenize_forms("(a 1) ; one\n 'b\n#u8(1 2)").unwrap();
+    let locations: Vec<_> = forms.into_iter().map(|(_, l)| l).collect();
+    assert_eq!(vec![at(1, 1, 0, 5), at(2, 2, 13, 15), at(3, 1, 16, 24)], locations);
+    // An unfinished form is reported where it starts
+    assert_eq!(Err((ParseError::EOF, at(2, 1, 6, 7))), Tokenizer::tokenize_forms("(a 1)\n(b (c)"));
+    assert_eq!(Err((ParseError::UnexpectedCloseParen, at(1, 6, 5, 6))), Tokenizer::tokenize_forms("(a 1))"));
+}
+
+#[test]
+fn errors() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_source("test.ss", "(define (first x)\n  (car x))").unwrap();
+
+    assert_eq!("test.ss:3:3: Exception in car: 1 is not a pair", error(&mut m, "(first '(1))\n\n  (first 1)"));
+    assert_eq!("test.ss:2:1: Expected a symbol in the formals", error(&mut m, "(first '(1))\n(lambda (x . ) x)"));
+    assert_eq!("test.ss:1:10: Unexpected `)`", error(&mut m, "(first 1))"));
+    assert_eq!("test.ss:2:3: Invalid escape `\\x` in string at 2:4", error(&mut m, "1\n  \"\\xzz;\""));
+    assert_eq!("test.ss:1:1: Invalid escape `\\q` in string at 1:3", error(&mut m, "\"a\\qb\""));
+
+    // `eval_str` doesn't report locations
+    assert_eq!(Err(Error::Condition("Exception in car: 1 is not a pair".to_string())),
+               m.eval_str("1\n(first 1)").map(|(v, _)| v));
+}
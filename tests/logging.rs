@@ -0,0 +1,72 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+use vm::Level;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type Log = Rc<RefCell<Vec<(Level, String)>>>;
+
+fn logged(m: &mut Minerva) -> Log {
+    let log = Log::default();
+    let sink = log.clone();
+    m.vm().set_log_sink(move |level, message| sink.borrow_mut().push((level, message.to_string())));
+    log
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn levels() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let log = logged(&mut m);
+
+    m.eval_str("(log-debug \"hidden\")").unwrap();
+    m.eval_str("(log-info \"loaded ~a rules from ~s~%\" 3 \"rules.ss\")").unwrap();
+    m.eval_str("(log-warn \"~a~~\" 'odd)").unwrap();
+    m.eval_str("(log-error \"failed\")").unwrap();
+    assert_eq!(vec![(Level::Info, "loaded 3 rules from \"rules.ss\"\n".to_string()),
+                    (Level::Warn, "odd~".to_string()),
+                    (Level::Error, "failed".to_string())],
+               *log.borrow());
+
+    log.borrow_mut().clear();
+    assert_eq!("info", format!("{}", m.eval_str("(log-level)").unwrap().0));
+    m.eval_str("(set-log-level! 'debug)").unwrap();
+    assert_eq!(Level::Debug, m.vm().log_level());
+    m.eval_str("(log-debug \"shown\")").unwrap();
+    m.vm().set_log_level(Level::Error);
+    m.eval_str("(log-warn \"hidden\")").unwrap();
+    assert_eq!(vec![(Level::Debug, "shown".to_string())], *log.borrow());
+
+    // The sink outlives a reset
+    m.reset();
+    m.eval_str("(log-info \"after reset\")").unwrap();
+    assert_eq!((Level::Info, "after reset".to_string()), log.borrow()[1]);
+}
+
+#[test]
+fn errors() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    logged(&mut m);
+
+    assert_eq!("Exception in log-info: too few arguments for \"~a and ~a\"",
+               exception(&mut m, "(log-info \"~a and ~a\" 1)"));
+    assert_eq!("Exception in log-info: too many arguments for \"~a\"", exception(&mut m, "(log-info \"~a\" 1 2)"));
+    assert_eq!("Exception in log-warn: ~d is not a format directive", exception(&mut m, "(log-warn \"~d\" 1)"));
+    assert_eq!("Exception in log-error: 1 is not a string", exception(&mut m, "(log-error 1)"));
+    assert_eq!("Exception in set-log-level!: loud is not a log level, expected debug, info, warn or error",
+               exception(&mut m, "(set-log-level! 'loud)"));
+}
@@ -0,0 +1,111 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva, ParseError};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn parse_error(m: &mut Minerva, input: &str) -> ParseError {
+    match m.eval_str(input) {
+        Err(Error::Parse(e)) => e,
+        r => panic!("{} did not fail to parse: {:?}", input, r),
+    }
+}
+
+#[test]
+fn syntax_rules() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define-syntax swap-pair (syntax-rules () ((_ (a b)) (list b a))))").unwrap();
+    assert_eq!("(2 1)", eval(&mut m, "(swap-pair (1 2))"));
+
+    // Several rules, recursion, and ellipses
+    m.eval_str("(define-syntax list* (syntax-rules () ((_ e) e) ((_ e r ...) (cons e (list* r ...)))))").unwrap();
+    assert_eq!("(1 2 3)", eval(&mut m, "(list* 1 2 '(3))"));
+    assert_eq!("3", eval(&mut m, "(list* 3)"));
+
+    m.eval_str("(define-syntax pairs (syntax-rules () ((_ (a b ...) ...) (list (list a (list b ...)) ...))))")
+        .unwrap();
+    assert_eq!("((1 (2 3)) (4 ()))", eval(&mut m, "(pairs (1 2 3) (4))"));
+
+    // Literals, dotted patterns, and quoted pattern variables
+    m.eval_str("(define-syntax arrow (syntax-rules (=>) ((_ a => b) (cons a b)) ((_ a b) 'no-arrow)))").unwrap();
+    assert_eq!("(1 . 2)", eval(&mut m, "(arrow 1 => 2)"));
+    assert_eq!("no-arrow", eval(&mut m, "(arrow 1 2)"));
+    m.eval_str("(define-syntax rest (syntax-rules () ((_ a . r) 'r)))").unwrap();
+    assert_eq!("(2 3)", eval(&mut m, "(rest 1 2 3)"));
+
+    // A custom ellipsis, and an escaped one
+    m.eval_str("(define-syntax my-list (syntax-rules ::: () ((_ x :::) (list x :::))))").unwrap();
+    assert_eq!("(1 2)", eval(&mut m, "(my-list 1 2)"));
+    m.eval_str("(define-syntax dots (syntax-rules () ((_) '(... ...))))").unwrap();
+    assert_eq!("...", eval(&mut m, "(dots)"));
+
+    assert_eq!(ParseError::NoMatchingRule, parse_error(&mut m, "(swap-pair 1)"));
+    assert_eq!(ParseError::BadMacro, parse_error(&mut m, "(define-syntax bad (lambda (x) x))"));
+    m.eval_str("(define-syntax forever (syntax-rules () ((_) (forever))))").unwrap();
+    assert_eq!(ParseError::MacroDepth, parse_error(&mut m, "(forever)"));
+
+    // A definition replaces a macro
+    m.eval_str("(define (swap-pair p) 'procedure)").unwrap();
+    assert_eq!("procedure", eval(&mut m, "(swap-pair 1)"));
+}
+
+#[test]
+fn hygiene() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define-syntax pair-with (syntax-rules () ((_ a b) ((lambda (t) (cons t b)) a))))").unwrap();
+    m.eval_str("(define t 5)").unwrap();
+    // The `t` bound by the template doesn't capture the one passed to it
+    assert_eq!("(1 . 5)", eval(&mut m, "(pair-with 1 t)"));
+    // Quoted, it is just a symbol
+    m.eval_str("(define-syntax name-of-t (syntax-rules () ((_ a) ((lambda (t) 't) a))))").unwrap();
+    assert_eq!("t", eval(&mut m, "(name-of-t 1)"));
+
+    // A local variable shadows a macro of the same name
+    assert_eq!("(1 2)", eval(&mut m, "((lambda (pair-with) (pair-with 1 2)) list)"));
+
+    // A free variable in a template means the top level one, even where a local variable hides it
+    m.eval_str("(define-syntax pair-of (syntax-rules () ((_ a b) (list a b))))").unwrap();
+    assert_eq!("(1 2)", eval(&mut m, "((lambda (list) (pair-of 1 2)) vector)"));
+    m.eval_str("(define-syntax with-list (syntax-rules () ((_ f) (f list))))").unwrap();
+    assert_eq!("(1 2)", eval(&mut m, "((lambda (list) (with-list (lambda (g) (g 1 2)))) vector)"));
+    // The local variable is renamed where it is used, but not where it is quoted
+    assert_eq!("(#(3) 1 2)", eval(&mut m, "((lambda (list) (cons (list 3) (pair-of 1 2))) vector)"));
+    assert_eq!("(list 1 2)", eval(&mut m, "((lambda (list) (cons 'list (pair-of 1 2))) vector)"));
+    assert_eq!("(5 1 2)",
+               eval(&mut m, "((lambda (list) (let-syntax ((own (syntax-rules () ((_) list)))) (cons (own) (pair-of 1 2)))) 5)"));
+    // Keywords are not variables
+    m.eval_str("(define-syntax choose (syntax-rules () ((_ c a b) (if c a b))))").unwrap();
+    assert_eq!("1", eval(&mut m, "((lambda (if) (choose #t 1 2)) list)"));
+    // A variable bound by the template is still its own
+    m.eval_str("(define-syntax local-list (syntax-rules () ((_ a) ((lambda (list) (list a)) vector))))").unwrap();
+    assert_eq!("#(1)", eval(&mut m, "((lambda (list) (local-list 1)) 5)"));
+}
+
+#[test]
+fn local_macros() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("(2 1)", eval(&mut m, "(let-syntax ((flip (syntax-rules () ((_ a b) (list b a))))) (flip 1 2))"));
+    // The macro is only defined in the body
+    assert_eq!(Err(Error::Condition("Exception: variable flip is not bound".to_string())),
+               m.eval_str("(flip 1 2)").map(|(v, _)| v));
+
+    m.eval_str("(define-syntax outer (syntax-rules () ((_) 'outer)))").unwrap();
+    // A `let-syntax` macro uses the macros visible outside it, and a `letrec-syntax` one its own
+    assert_eq!("outer", eval(&mut m, "(let-syntax ((outer (syntax-rules () ((_) 'inner))) \
+                                                   (use (syntax-rules () ((_) (outer))))) \
+                                        (use))"));
+    assert_eq!("inner", eval(&mut m, "(letrec-syntax ((outer (syntax-rules () ((_) 'inner))) \
+                                                      (use (syntax-rules () ((_) (outer))))) \
+                                        (use))"));
+    assert_eq!("3", eval(&mut m, "(letrec-syntax ((count (syntax-rules () ((_) 0) ((_ x y ...) (+ 1 (count y ...)))))) \
+                                    (count a b c))"));
+}
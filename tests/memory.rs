@@ -0,0 +1,66 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+use vm::heap_bytes;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn heap_limit() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (grow l) (grow (cons 1 l)))").unwrap();
+    let limit = heap_bytes() + 20_000;
+    m.vm().set_process_heap_limit(Some(limit));
+
+    let message = exception(&mut m, "(grow '())");
+    assert!(message.starts_with("Exception in out-of-memory: the heap uses "), "{}", message);
+    assert!(message.ends_with(&format!("over its limit of {}", limit)), "{}", message);
+    // Aborting the computation freed what it allocated
+    assert_eq!("(1 2)", eval(&mut m, "(list 1 2)"));
+    assert!(heap_bytes() <= limit);
+
+    assert_eq!("2", eval(&mut m, "(bytevector-length (make-bytevector 2 0))"));
+    assert_eq!(format!("Exception in make-bytevector: allocating 1000000 bytes would put the heap over its limit of \
+                        {}", limit),
+               exception(&mut m, "(make-bytevector 1000000 0)"));
+
+    // Other procedures which are given a size check it too
+    for call in ["(random-bytes 1000000)", "(make-hash-table 1000000)", "(integer->bytevector 1 1000000)",
+                 "(number->string 1 1000000)"] {
+        let message = exception(&mut m, call);
+        assert!(message.contains("would put the heap over its limit"), "{}", message);
+    }
+
+    m.vm().set_process_heap_limit(None);
+    assert_eq!("1000000", eval(&mut m, "(bytevector-length (make-bytevector 1000000 0))"));
+}
+
+#[test]
+fn out_of_memory_handled() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (grow l) (grow (cons 1 l)))").unwrap();
+    let limit = heap_bytes() + 20_000;
+    m.vm().set_process_heap_limit(Some(limit));
+
+    // The handler has room to run, and returning from it abandons what filled the heap
+    assert_eq!("out-of-memory", eval(&mut m, "(with-restart-handler (lambda (kind message) kind) \
+                                                (lambda () (grow '())))"));
+    assert_eq!("(1 2)", eval(&mut m, "(list 1 2)"));
+
+    m.vm().set_process_heap_limit(None);
+}
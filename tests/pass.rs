@@ -0,0 +1,65 @@
+extern crate minerva;
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Ast, Minerva};
+use string_interner::get_value;
+use vm::Value;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn is_ident(ast: &Ast, name: &str) -> bool {
+    matches!(ast, Ast::Ident(s) if get_value(*s).as_deref() == Some(name))
+}
+
+/// Replace `(double n)` of a literal fixnum by its value, anywhere in `ast`.
+fn fold_double(ast: Ast) -> Ast {
+    match ast {
+        Ast::Apply(exprs) => {
+            let exprs: Vec<_> = exprs.into_iter().map(fold_double).collect();
+            match exprs.as_slice() {
+                [f, Ast::Primitive(n)] if is_ident(f, "double") && n.is_integer() =>
+                    Ast::Primitive(Value::Integer(n.to_integer() * 2)),
+                _ => Ast::Apply(exprs),
+            }
+        }
+        Ast::Define { name, value } => Ast::Define { name, value: Box::new(fold_double(*value)) },
+        Ast::Lambda { args, body } => Ast::Lambda { args, body: body.into_iter().map(fold_double).collect() },
+        ast => ast,
+    }
+}
+
+#[test]
+fn passes() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let forms = log.clone();
+    m.add_pass(Box::new(move |ast: Ast| {
+        forms.borrow_mut().push(format!("{:?}", ast).starts_with("Define"));
+        ast
+    }));
+    m.add_pass(Box::new(fold_double));
+
+    // `double` is never defined, so only folded calls can run
+    assert_eq!("10", eval(&mut m, "(double 5)"));
+    assert_eq!("(8 6)", eval(&mut m, "#!no-tail-call\n(define (f) (list (double 4) (double 3)))\n(f)"));
+    // Directives aren't passed on
+    assert_eq!(vec![false, true, false], *log.borrow());
+
+    // Passes run on compiled code too, and outlive a reset
+    let bytes = m.compile_str("(+ (double 1) 1)").unwrap();
+    m.reset();
+    assert_eq!("3", format!("{}", m.eval_compiled(&bytes).unwrap().0));
+    assert_eq!("14", eval(&mut m, "(double 7)"));
+    assert!(m.eval_str("(double 'a)").is_err());
+    assert_eq!(6, log.borrow().len());
+}
@@ -0,0 +1,71 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+use std::env;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn components() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("#\\/", eval(&mut m, "(path-separator)"));
+    assert_eq!("\"src/bin/repl.rs\"", eval(&mut m, "(path-join \"src\" \"bin\" \"repl.rs\")"));
+    // An absolute path starts over
+    assert_eq!("\"/etc/hosts\"", eval(&mut m, "(path-join \"src\" \"/etc\" \"hosts\")"));
+
+    assert_eq!("\"gz\"", eval(&mut m, "(path-extension \"logs/app.log.gz\")"));
+    assert_eq!("#f", eval(&mut m, "(path-extension \"Makefile\")"));
+    assert_eq!("#f", eval(&mut m, "(path-extension \".profile\")"));
+    assert_eq!("\"app.log.gz\"", eval(&mut m, "(path-file-name \"logs/app.log.gz\")"));
+    assert_eq!("#f", eval(&mut m, "(path-file-name \"/\")"));
+    assert_eq!("\"logs\"", eval(&mut m, "(path-parent \"logs/app.log.gz\")"));
+    assert_eq!("#f", eval(&mut m, "(path-parent \"/\")"));
+    assert_eq!("#t", eval(&mut m, "(path-absolute? \"/usr/bin\")"));
+    assert_eq!("#f", eval(&mut m, "(path-absolute? \"usr/bin\")"));
+
+    assert_eq!("Exception in path-join: 1 is not a string", exception(&mut m, "(path-join \"a\" 1)"));
+}
+
+#[cfg(unix)]
+#[test]
+fn normalize() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("\"a/c\"", eval(&mut m, "(path-normalize \"./a//b/../c/.\")"));
+    assert_eq!("\"../b\"", eval(&mut m, "(path-normalize \"a/../../b\")"));
+    assert_eq!("\"/b\"", eval(&mut m, "(path-normalize \"/../b\")"));
+    assert_eq!("\".\"", eval(&mut m, "(path-normalize \"a/..\")"));
+}
+
+#[test]
+fn expand() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    env::set_var("HOME", "/home/ada");
+    env::set_var("USERPROFILE", "/home/ada");
+    env::set_var("MINERVA_PATH_TEST", "lib");
+    env::remove_var("MINERVA_PATH_UNSET");
+    assert_eq!("\"/home/ada\"", eval(&mut m, "(expand-user \"~\")"));
+    assert_eq!("\"/home/ada/notes\"", eval(&mut m, "(expand-user \"~/notes\")"));
+    assert_eq!("\"~ada/notes\"", eval(&mut m, "(expand-user \"~ada/notes\")"));
+    assert_eq!("\"notes/~\"", eval(&mut m, "(expand-user \"notes/~\")"));
+
+    assert_eq!("\"lib/x lib.ss\"", eval(&mut m, "(expand-variables \"$MINERVA_PATH_TEST/x ${MINERVA_PATH_TEST}.ss\")"));
+    assert_eq!("\"$MINERVA_PATH_UNSET/${MINERVA_PATH_UNSET} $ ${\"",
+               eval(&mut m, "(expand-variables \"$MINERVA_PATH_UNSET/${MINERVA_PATH_UNSET} $ ${\")"));
+}
@@ -0,0 +1,68 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{compile, optimize, output_asm, Error, Minerva, Parser, Tokenizer};
+
+fn asm(input: &str) -> String {
+    let tokens = Tokenizer::tokenize(input).unwrap();
+    Parser::parse(tokens).unwrap().into_iter()
+        .flat_map(|ast| output_asm(optimize(compile(ast))))
+        .map(|i| format!("{}\n", i))
+        .collect()
+}
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn compiled_to_instructions() {
+    let code = asm("(define (second l) (car (cdr l)))");
+    assert!(code.contains("CALLPRIMITIVE X0, CDR"));
+    assert!(code.contains("CALLPRIMITIVE X0, CAR"));
+    assert!(!code.contains("CALL X"));
+    assert!(asm("(lambda (v) (cons (vector-ref v 0) '()))").contains("CALLPRIMITIVE X0, VECTORREF"));
+
+    // Only with the number of arguments the primitive takes
+    assert!(asm("(car 1 2)").contains("CALL X0, 2"));
+    // A formal is not the primitive
+    assert!(!asm("(lambda (car x) (car x))").contains("CALLPRIMITIVE"));
+}
+
+#[test]
+fn primitive_calls() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (second l) (car (cdr l)))").unwrap();
+    assert_eq!("2", eval(&mut m, "(second '(1 2 3))"));
+    assert_eq!("(1 . 2)", eval(&mut m, "(cons 1 2)"));
+    assert_eq!("b", eval(&mut m, "(vector-ref (vector 'a 'b 'c) 1)"));
+    assert_eq!(Err(Error::Condition("Exception in cdr: 1 is not a pair".to_string())), m.eval_str("(second 1)"));
+    assert_eq!(Err(Error::Condition("Exception in vector-ref: 3 is not a valid index".to_string())),
+               m.eval_str("(vector-ref (vector 1 2 3) 3)"));
+
+    // Running the instruction takes fewer steps than calling the primitive
+    m.eval_str("(define first car)").unwrap();
+    m.eval_str("(define l '(1 2))").unwrap();
+    let (_, inline) = m.eval_str("(car l)").unwrap();
+    let (_, called) = m.eval_str("(first l)").unwrap();
+    assert!(inline.instructions < called.instructions);
+}
+
+#[test]
+fn redefined_primitives() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (second l) (car (cdr l)))").unwrap();
+    m.eval_str("(define (car x) 'mine)").unwrap();
+    assert_eq!("mine", eval(&mut m, "(second '(1 2 3))"));
+    assert_eq!("mine", eval(&mut m, "(car 1)"));
+
+    // A procedure which isn't one at all is still an error
+    m.eval_str("(define cons 1)").unwrap();
+    assert_eq!(Err(Error::Condition("Exception: attempt to apply non-procedure 1".to_string())),
+               m.eval_str("(cons 1 2)"));
+}
@@ -0,0 +1,71 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+fn command_line(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn main_procedure() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!(None, m.run_main(command_line(&["script.ss"])).unwrap());
+
+    m.eval_str("(define (main args) (length args))").unwrap();
+    assert_eq!("3", format!("{}", m.run_main(command_line(&["script.ss", "a", "b"])).unwrap().unwrap()));
+    assert_eq!("(\"script.ss\" \"a\" \"b\")", eval(&mut m, "(command-line)"));
+
+    m.eval_str("(define (main args) (car '()))").unwrap();
+    assert!(matches!(m.run_main(command_line(&["script.ss"])), Err(Error::Condition(_))));
+}
+
+#[test]
+fn parse_args() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.vm().set_command_line(command_line(&["script.ss", "--verbose", "in.txt", "-o", "out.txt"]));
+    m.eval_str("(define spec (list 'verbose 'quiet (list 'o \"a.out\") (list 'level 1)))").unwrap();
+    m.eval_str("(define (option name args) (hash-table-ref (car args) name))").unwrap();
+
+    // By default the arguments of the program are read
+    m.eval_str("(define args (parse-args spec))").unwrap();
+    assert_eq!("#t", eval(&mut m, "(option 'verbose args)"));
+    assert_eq!("#f", eval(&mut m, "(option 'quiet args)"));
+    assert_eq!("\"out.txt\"", eval(&mut m, "(option 'o args)"));
+    assert_eq!("1", eval(&mut m, "(option 'level args)"));
+    assert_eq!("(\"in.txt\")", eval(&mut m, "(car (cdr args))"));
+
+    m.eval_str("(define args (parse-args spec (list \"--level=2\" \"-\" \"--\" \"--quiet\")))").unwrap();
+    assert_eq!("\"2\"", eval(&mut m, "(option 'level args)"));
+    assert_eq!("#f", eval(&mut m, "(option 'quiet args)"));
+    assert_eq!("(\"-\" \"--quiet\")", eval(&mut m, "(car (cdr args))"));
+
+    assert_eq!("Exception in parse-args: unknown option --loud",
+               exception(&mut m, "(parse-args spec (list \"--loud\"))"));
+    // A one character name is written with a single `-`
+    assert_eq!("Exception in parse-args: unknown option --o",
+               exception(&mut m, "(parse-args spec (list \"--o\" \"x\"))"));
+    assert_eq!("Exception in parse-args: option --quiet doesn't take a value",
+               exception(&mut m, "(parse-args spec (list \"--quiet=yes\"))"));
+    assert_eq!("Exception in parse-args: option --level needs a value",
+               exception(&mut m, "(parse-args spec (list \"--level\"))"));
+    assert_eq!("Exception in parse-args: 1 is not an option", exception(&mut m, "(parse-args (list 1))"));
+    assert_eq!("Exception in parse-args: (1) is not a list of strings",
+               exception(&mut m, "(parse-args spec (list 1))"));
+}
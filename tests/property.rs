@@ -0,0 +1,51 @@
+extern crate minerva;
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+use string_interner::get_symbol;
+use vm::Value;
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn properties() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("#f", eval(&mut m, "(get 'swap 'doc)"));
+    assert_eq!("none", eval(&mut m, "(get 'swap 'doc 'none)"));
+
+    // The value outlives the collections run while evaluating the rest
+    m.eval_str("(put! 'swap 'args (list 'a 'b))").unwrap();
+    m.eval_str("(put! 'swap 'doc \"Swap\")").unwrap();
+    m.eval_str("(put! 'swap 'doc \"Exchange two values\")").unwrap();
+    assert_eq!("(a b)", eval(&mut m, "(get 'swap 'args)"));
+    assert_eq!("(args (a b) doc \"Exchange two values\")", eval(&mut m, "(symbol-plist 'swap)"));
+    assert_eq!("()", eval(&mut m, "(symbol-plist 'other)"));
+
+    assert_eq!("#t", eval(&mut m, "(remprop! 'swap 'doc)"));
+    assert_eq!("#f", eval(&mut m, "(remprop! 'swap 'doc)"));
+    assert_eq!("(args (a b))", eval(&mut m, "(symbol-plist 'swap)"));
+
+    // Hosts can read and write them too
+    let swap = get_symbol("swap".to_string());
+    let kind = Value::Symbol(get_symbol("kind".to_string()));
+    m.vm().put_property(swap, kind, Value::Symbol(get_symbol("procedure".to_string())));
+    assert_eq!("procedure", eval(&mut m, "(get 'swap 'kind)"));
+    assert_eq!(None, m.vm().property(swap, Value::Symbol(get_symbol("doc".to_string()))));
+
+    assert_eq!("Exception in put!: \"swap\" is not a symbol", exception(&mut m, "(put! \"swap\" 'doc 1)"));
+    assert_eq!("Exception in get: 1 is not a symbol", exception(&mut m, "(get 1 'doc)"));
+}
@@ -0,0 +1,91 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva, ParseError};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn unquote() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define x 5)").unwrap();
+    assert_eq!("(1 2 3)", eval(&mut m, "`(1 2 3)"));
+    assert_eq!("(1 5 (x 6))", eval(&mut m, "`(1 ,x (x ,(+ x 1)))"));
+    assert_eq!("5", eval(&mut m, "`,x"));
+    assert_eq!("(1 5)", eval(&mut m, "(quasiquote (1 (unquote x)))"));
+    // Quoted, the prefixes are read as the forms they abbreviate
+    assert_eq!("(quasiquote (a (unquote x) (unquote-splicing y)))", eval(&mut m, "'`(a ,x ,@y)"));
+
+    assert_eq!(Err(Error::Parse(ParseError::IllegalUse)), m.eval_str(",x").map(|(v, _)| v));
+    assert_eq!(Err(Error::Parse(ParseError::IllegalUse)), m.eval_str("`,@x").map(|(v, _)| v));
+}
+
+#[test]
+fn unquote_splicing() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define l (list 2 3))").unwrap();
+    assert_eq!("(1 2 3 4)", eval(&mut m, "`(1 ,@l 4)"));
+    assert_eq!("(2 3 2 3)", eval(&mut m, "`(,@l ,@l)"));
+    assert_eq!("(1)", eval(&mut m, "`(1 ,@'())"));
+    assert_eq!("(1 2 3)", eval(&mut m, "(quasiquote (1 (unquote-splicing l)))"));
+    // The spliced list is copied
+    assert_eq!("(2 3)", eval(&mut m, "(begin (set-car! (cdr `(1 ,@l)) 9) l)"));
+
+    assert_eq!("Exception in append: 2 is not a proper list",
+               match m.eval_str("`(1 ,@2)") {
+                   Err(Error::Condition(message)) => message,
+                   r => panic!("did not raise a condition: {:?}", r),
+               });
+}
+
+#[test]
+fn dotted() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define x 5)").unwrap();
+    // Improper lists read back as they are written
+    for s in &["(1 . 2)", "(a b . c)", "((a . 1) (b . 2))", "(1 (2 . 3) . #t)"] {
+        assert_eq!(*s, eval(&mut m, &format!("'{}", s)));
+    }
+    assert_eq!("(1 2 3)", eval(&mut m, "'(1 . (2 3))"));
+    assert_eq!("1", eval(&mut m, "(car '[1 . 2])"));
+
+    assert_eq!("(1 . 5)", eval(&mut m, "`(1 . ,x)"));
+    assert_eq!("(1 2 3 . 5)", eval(&mut m, "`(1 ,@(list 2 3) . ,x)"));
+    assert_eq!("(1 . 5)", eval(&mut m, "`(1 . (unquote x))"));
+
+    assert_eq!(Err(Error::Parse(ParseError::IllegalUse)), m.eval_str("'(. 1)").map(|(v, _)| v));
+    assert_eq!(Err(Error::Parse(ParseError::Expected("`)`"))), m.eval_str("'(1 . 2 3)").map(|(v, _)| v));
+    assert_eq!(Err(Error::Parse(ParseError::IllegalUse)), m.eval_str("`(1 . ,@x)").map(|(v, _)| v));
+}
+
+#[test]
+fn nested() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define x 5)").unwrap();
+    // Only the unquotes of the outermost quasiquote are evaluated
+    assert_eq!("(a (quasiquote (b (unquote x))))", eval(&mut m, "`(a `(b ,x))"));
+    assert_eq!("(a (quasiquote (b (unquote (c 5)))))", eval(&mut m, "`(a `(b ,(c ,x)))"));
+    assert_eq!("(a (quasiquote (b (unquote (quote 5)))))", eval(&mut m, "`(a `(b ,',x))"));
+}
+
+#[test]
+fn in_macros() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define-syntax wrap (syntax-rules () ((_ a) ((lambda (t) `(t ,t ,a)) 1))))").unwrap();
+    m.eval_str("(define t 5)").unwrap();
+    // The quoted `t` is a symbol, while the unquoted ones are variables
+    assert_eq!("(t 1 5)", eval(&mut m, "(wrap t)"));
+    m.eval_str("(define-syntax twice (syntax-rules () ((_ a) (list a a))))").unwrap();
+    assert_eq!("(1 (5 5))", eval(&mut m, "`(1 ,(twice t))"));
+    assert_eq!("(1 (twice t))", eval(&mut m, "`(1 (twice t))"));
+}
@@ -0,0 +1,82 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Location, Minerva, Next, ParseError, Parser, Reader};
+
+use std::io::{self, Cursor};
+
+fn at(line: usize, column: usize, start: usize, end: usize) -> Location {
+    Location { line, column, span: start..end }
+}
+
+fn form<R: io::BufRead>(reader: &mut Reader<R>) -> (String, Location) {
+    match reader.read() {
+        Ok(Next::Form(f)) => (f.text, f.location),
+        r => panic!("expected a form, found {:?}", r),
+    }
+}
+
+#[test]
+fn forms() {
+    let input = "(define x 1) ; one\n  #| two |# (list x\n 'y)\n\"a\nb\" z";
+    let mut reader = Reader::new("test.ss", Cursor::new(input));
+    assert_eq!(("(define x 1)".to_string(), at(1, 1, 0, 12)), form(&mut reader));
+    assert_eq!(("(list x\n 'y)".to_string(), at(2, 13, 31, 43)), form(&mut reader));
+    assert_eq!(("\"a\nb\"".to_string(), at(4, 1, 44, 49)), form(&mut reader));
+    assert_eq!(("z".to_string(), at(5, 4, 50, 51)), form(&mut reader));
+    assert_eq!(Ok(Next::Eof), reader.read());
+    assert_eq!(Ok(Next::Eof), reader.read());
+
+    // The tokens are those of the form, without comments
+    let mut reader = Reader::new("test.ss", Cursor::new("(f #;(g) 1) 2"));
+    match reader.read() {
+        Ok(Next::Form(f)) => assert_eq!("(f 1)", format!("{}", Parser::parse(f.tokens).unwrap()[0])),
+        r => panic!("expected a form, found {:?}", r),
+    }
+}
+
+#[test]
+fn continuation_lines() {
+    let mut reader = Reader::new("<repl>", io::empty());
+    assert_eq!(Ok(Next::Eof), reader.read());
+    reader.push_line("(define (f x)");
+    assert_eq!(Ok(Next::Incomplete(at(1, 1, 0, 1))), reader.read());
+    reader.push_line("  (* x x)) \"un");
+    assert_eq!(("(define (f x)\n  (* x x))".to_string(), at(1, 1, 0, 24)), form(&mut reader));
+    assert_eq!(Ok(Next::Incomplete(at(2, 12, 25, 29))), reader.read());
+    reader.push_line("finished\"");
+    assert_eq!(("\"un\nfinished\"".to_string(), at(2, 12, 25, 38)), form(&mut reader));
+    assert_eq!(Ok(Next::Eof), reader.read());
+
+    // A file may end inside a form
+    let mut reader = Reader::new("test.ss", Cursor::new("1\n(a\n b"));
+    assert_eq!(("1".to_string(), at(1, 1, 0, 1)), form(&mut reader));
+    assert_eq!(Ok(Next::Incomplete(at(2, 1, 2, 3))), reader.read());
+}
+
+#[test]
+fn errors() {
+    // The rest of the line with the error is discarded
+    let mut reader = Reader::new("test.ss", Cursor::new("1 ) 2\n3"));
+    assert_eq!(("1".to_string(), at(1, 1, 0, 1)), form(&mut reader));
+    assert_eq!(Err(Error::At("test.ss".to_string(), at(1, 3, 2, 3), Box::new(Error::Parse(ParseError::UnexpectedCloseParen)))),
+               reader.read());
+    assert_eq!(("3".to_string(), at(2, 1, 6, 7)), form(&mut reader));
+
+    let mut reader = Reader::new("test.ss", Cursor::new(vec![b'1', b' ', 0xff, b'\n']));
+    assert!(matches!(reader.read(), Err(Error::Io(_))));
+}
+
+#[test]
+fn evaluate() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let mut reader = Reader::new("test.ss", Cursor::new("(define (square x)\n  (* x x))\n(square 7)\n"));
+    let mut last = None;
+    while let Next::Form(f) = reader.read().unwrap() {
+        last = Some(m.eval_source("test.ss", &f.text).unwrap().0);
+    }
+    assert_eq!("49", format!("{}", last.unwrap()));
+}
@@ -0,0 +1,61 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva, ParseError};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+#[test]
+fn globals() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define x 1)").unwrap();
+    m.eval_str("(set! x 5)").unwrap();
+    assert_eq!("5", eval(&mut m, "x"));
+    assert_eq!("100", eval(&mut m, "(begin (set! x 100) x)"));
+
+    // A procedure assigns the global binding
+    m.eval_str("(define (inc!) (set! x (+ x 1)))").unwrap();
+    m.eval_str("(inc!)").unwrap();
+    assert_eq!("101", eval(&mut m, "x"));
+    m.eval_str("(define (record! i) (if (< i 3) (begin (set! x i) 'set) 'skipped))").unwrap();
+    assert_eq!("set", eval(&mut m, "(record! 2)"));
+    assert_eq!("2", eval(&mut m, "x"));
+
+    // Permissive environments define unbound variables, strict ones don't
+    m.eval_str("(set! y 1)").unwrap();
+    assert_eq!("1", eval(&mut m, "y"));
+    m.eval_str("(set-environment-strictness! (interaction-environment) 'strict)").unwrap();
+    assert_eq!(Err(Error::Condition("Exception in set!: variable z is not bound".to_string())),
+               m.eval_str("(set! z 1)").map(|(v, _)| v));
+
+    assert_eq!(Err(Error::Parse(ParseError::Expected("a name to set"))), m.eval_str("(set! 1 2)").map(|(v, _)| v));
+}
+
+#[test]
+fn locals() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (f a b) (begin (set! a (+ a b)) (list a b)))").unwrap();
+    assert_eq!("(3 2)", eval(&mut m, "(f 1 2)"));
+    m.eval_str("(define (collect i acc) (if (< i 3) (begin (set! acc (cons i acc)) (collect (+ i 1) acc)) acc))")
+        .unwrap();
+    assert_eq!("(2 1 0)", eval(&mut m, "(collect 0 (list))"));
+
+    // Closures share the variables they assign
+    m.eval_str("(define (make-counter n) (lambda () (begin (set! n (+ n 1)) n)))").unwrap();
+    m.eval_str("(define c (make-counter 10))").unwrap();
+    assert_eq!("11", eval(&mut m, "(c)"));
+    assert_eq!("12", eval(&mut m, "(c)"));
+    m.eval_str("(define (g a) (begin ((lambda () (set! a 7))) a))").unwrap();
+    assert_eq!("7", eval(&mut m, "(g 1)"));
+
+    // An inner variable of the same name is a different binding
+    m.eval_str("(define (h a) (begin ((lambda (a) (set! a 9)) 1) a))").unwrap();
+    assert_eq!("3", eval(&mut m, "(h 3)"));
+}
@@ -0,0 +1,86 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+use vm::{define_native, deliver_signal, Arity, Signal, Value, VM};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+fn interrupt(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    deliver_signal(Signal::Interrupt);
+    Ok(Value::Void)
+}
+
+#[cfg(unix)]
+fn terminate(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    extern "C" {
+        fn raise(signum: i32) -> i32;
+    }
+    unsafe {
+        raise(Signal::Terminate as i32);
+    }
+    Ok(Value::Void)
+}
+
+#[test]
+fn interrupts() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    define_native(m.environment(), "signal-test-interrupt", Arity::Exactly(0), interrupt).unwrap();
+
+    assert_eq!("Exception in interrupt: received SIGINT", exception(&mut m, "(begin (signal-test-interrupt) 1)"));
+    // The computation was abandoned, not the interpreter
+    assert_eq!("2", eval(&mut m, "(+ 1 1)"));
+}
+
+#[test]
+fn handlers() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    define_native(m.environment(), "signal-test-interrupt", Arity::Exactly(0), interrupt).unwrap();
+    m.eval_str("(define (interrupted) (begin (signal-test-interrupt) 'finished))").unwrap();
+    m.eval_str("(define (caught signal) (list 'caught signal))").unwrap();
+
+    assert_eq!("finished", eval(&mut m, "(with-signal-handler 'SIGINT (lambda () 'finished) caught)"));
+    assert_eq!("(caught SIGINT)", eval(&mut m, "(with-signal-handler 'SIGINT interrupted caught)"));
+    // From inside a handler for another signal, or a procedure applied by a native
+    assert_eq!("(caught SIGINT)",
+               eval(&mut m, "(with-signal-handler 'SIGINT \
+                               (lambda () (with-signal-handler 'SIGTERM interrupted caught)) caught)"));
+    assert_eq!("(caught SIGINT)",
+               eval(&mut m, "(with-signal-handler 'SIGINT (lambda () (for-each (lambda (x) (interrupted)) '(1))) \
+                               caught)"));
+
+    assert_eq!("Exception in interrupt: received SIGINT",
+               exception(&mut m, "(with-signal-handler 'SIGTERM interrupted caught)"));
+    // Other conditions aren't handled
+    assert_eq!("Exception in car: 1 is not a pair",
+               exception(&mut m, "(with-signal-handler 'SIGINT (lambda () (car 1)) caught)"));
+    assert_eq!("Exception in with-signal-handler: SIGHUP is not a signal, expected SIGINT or SIGTERM",
+               exception(&mut m, "(with-signal-handler 'SIGHUP interrupted caught)"));
+    assert_eq!("Exception in with-signal-handler: 1 is not a procedure",
+               exception(&mut m, "(with-signal-handler 'SIGINT 1 caught)"));
+}
+
+#[cfg(unix)]
+#[test]
+fn process_signals() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    define_native(m.environment(), "signal-test-terminate", Arity::Exactly(0), terminate).unwrap();
+    assert_eq!("(cleanup SIGTERM)",
+               eval(&mut m, "(with-signal-handler 'SIGTERM (lambda () (begin (signal-test-terminate) 'finished)) \
+                               (lambda (s) (list 'cleanup s)))"));
+}
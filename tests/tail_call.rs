@@ -0,0 +1,87 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{compile, optimize_with, tail_calls, Minerva, Options, Parser, Tokenizer};
+
+const LOOP: &str = "(define (count n acc) (if (= n 0) acc (count (- n 1) (+ acc 1))))";
+
+fn report(input: &str, options: Options) -> Vec<String> {
+    let tokens = Tokenizer::tokenize(input).unwrap();
+    Parser::parse(tokens).unwrap().into_iter()
+        .flat_map(|ast| tail_calls(&optimize_with(compile(ast), options)))
+        .map(|call| format!("{}", call))
+        .collect()
+}
+
+#[test]
+fn loops_run_in_constant_space() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str(LOOP).unwrap();
+    let (v, short) = m.eval_str("(count 10 0)").unwrap();
+    assert_eq!("10", format!("{}", v));
+    let (v, long) = m.eval_str("(count 100 0)").unwrap();
+    assert_eq!("100", format!("{}", v));
+    assert_eq!(short.peak_stack, long.peak_stack);
+
+    // Calls in the branches of a `case` are in tail position too
+    m.eval_str("(define (down n) (case n ((0) 'done) (else (down (- n 1)))))").unwrap();
+    assert_eq!("done", format!("{}", m.eval_str("(down 100)").unwrap().0));
+}
+
+#[test]
+fn mutual_recursion_runs_in_constant_space() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define (loop n) (if (= n 0) 'done (loop (- n 1))))").unwrap();
+    m.eval_str("(define (even n) (if (= n 0) #t (odd (- n 1))))").unwrap();
+    m.eval_str("(define (odd n) (if (= n 0) #f (even (- n 1))))").unwrap();
+    let (v, short) = m.eval_str("(loop 10)").unwrap();
+    assert_eq!("done", format!("{}", v));
+    let (v, long) = m.eval_str("(loop 100)").unwrap();
+    assert_eq!("done", format!("{}", v));
+    assert_eq!(short.peak_stack, long.peak_stack);
+
+    // A tail call to another procedure reuses the frame too
+    let (v, short) = m.eval_str("(even 10)").unwrap();
+    assert_eq!("#t", format!("{}", v));
+    let (v, long) = m.eval_str("(even 101)").unwrap();
+    assert_eq!("#f", format!("{}", v));
+    assert_eq!(short.peak_stack, long.peak_stack);
+}
+
+#[test]
+fn no_tail_call() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let (v, short) = m.eval_str(&format!("#!no-tail-call {} (count 10 0)", LOOP)).unwrap();
+    assert_eq!("10", format!("{}", v));
+    let (v, long) = m.eval_str(&format!("#!no-tail-call {} (count 20 0)", LOOP)).unwrap();
+    assert_eq!("20", format!("{}", v));
+    assert!(long.peak_stack > short.peak_stack);
+
+    // The directive only lasts until the end of its input
+    m.eval_str(LOOP).unwrap();
+    let (_, report) = m.eval_str("(count 20 0)").unwrap();
+    assert!(report.peak_stack < short.peak_stack);
+}
+
+#[test]
+fn reported_tail_calls() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    assert_eq!(vec!["count: tail call to count".to_string()], report(LOOP, Options::default()));
+    assert!(report(LOOP, Options { tail_calls: false }).is_empty());
+
+    // Only calls whose value is returned at once
+    let fib = "(define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))";
+    assert_eq!(vec!["fib: tail call to +".to_string()], report(fib, Options::default()));
+    assert_eq!(vec!["lambda: tail call to f".to_string()], report("(lambda (f x) (f x))", Options::default()));
+    assert!(report("(f 1)", Options::default()).is_empty());
+
+    let tokens = Tokenizer::tokenize("#!no-tail-call").unwrap();
+    assert_eq!(1, Parser::parse(tokens).unwrap().len());
+    assert!(Parser::parse(Tokenizer::tokenize("#!tail-call").unwrap()).is_err());
+}
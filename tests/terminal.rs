@@ -0,0 +1,44 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn colors() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    // Test output isn't a terminal
+    assert_eq!("#f", eval(&mut m, "(terminal-colors?)"));
+    assert_eq!("\"plain\"", eval(&mut m, "(colorize \"plain\" 'red)"));
+    assert_eq!("2", eval(&mut m, "(with-color 'red (lambda () 2))"));
+
+    m.eval_str("(set-terminal-colors! #t)").unwrap();
+    assert_eq!("\"\\x1b;[31mred\\x1b;[0m\"", eval(&mut m, "(colorize \"red\" 'red)"));
+    assert_eq!("\"\\x1b;[94mblue\\x1b;[0m\"", eval(&mut m, "(colorize \"blue\" 'bright-blue)"));
+    assert_eq!("\"\\x1b;[4mlink\\x1b;[0m\"", eval(&mut m, "(colorize \"link\" 'underline)"));
+    // Nested colors restore those around them
+    assert_eq!("\"\\x1b;[31mx\\x1b;[0m\\x1b;[1;32m\"",
+               eval(&mut m, "(with-color 'bold (lambda () (with-color 'green (lambda () (colorize \"x\" 'red)))))"));
+
+    assert_eq!("Exception in colorize: purple is not a color", exception(&mut m, "(colorize \"x\" 'purple)"));
+    assert_eq!("Exception in colorize: bright-bold is not a color",
+               exception(&mut m, "(colorize \"x\" 'bright-bold)"));
+    assert_eq!("Exception in colorize: 1 is not a string", exception(&mut m, "(colorize 1 'red)"));
+    assert_eq!("Exception in with-color: 1 is not a procedure", exception(&mut m, "(with-color 'red 1)"));
+    assert_eq!("Exception in car: 1 is not a pair", exception(&mut m, "(with-color 'red (lambda () (car 1)))"));
+    m.vm().set_terminal_colors(false);
+    assert_eq!("\"plain\"", eval(&mut m, "(colorize \"plain\" 'red)"));
+}
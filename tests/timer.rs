@@ -0,0 +1,79 @@
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+use std::time::{Duration, Instant};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn sleeping() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let start = Instant::now();
+    assert_eq!("#t", eval(&mut m, "(< (monotonic-time) (begin (sleep 0.02) (monotonic-time)))"));
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    m.eval_str("(sleep 0)").unwrap();
+
+    assert_eq!("Exception in sleep: -1 is not a number of seconds", exception(&mut m, "(sleep -1)"));
+    assert_eq!("Exception in sleep: a is not a number of seconds", exception(&mut m, "(sleep 'a)"));
+}
+
+#[test]
+fn timers() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    // The virtual clock stands still between the timers being scheduled
+    m.vm().set_virtual_clock(true);
+    m.eval_str("(define log '())").unwrap();
+    for name in &["first", "also-first", "second", "later"] {
+        m.eval_str(&format!("(define ({}) (set! log (cons '{} log)))", name, name)).unwrap();
+    }
+
+    // Timers run while sleeping, in the order they are due
+    m.eval_str("(after 0.02 second)").unwrap();
+    m.eval_str("(after 0.01 first)").unwrap();
+    m.eval_str("(after 0.01 also-first)").unwrap();
+    m.eval_str("(after 10 later)").unwrap();
+    assert_eq!("()", eval(&mut m, "log"));
+    assert_eq!("(second also-first first)", eval(&mut m, "(begin (sleep 0.05) log)"));
+    assert_eq!(1, m.vm().pending_timers());
+
+    // Or once the program has finished
+    let mut m = Minerva::new();
+    m.vm().set_virtual_clock(true);
+    m.eval_str("(define log '())").unwrap();
+    m.eval_str("(after 0.01 (lambda () (set! log (cons 'ran log))))").unwrap();
+    m.run_timers().unwrap();
+    assert_eq!("(ran)", eval(&mut m, "log"));
+
+    m.eval_str("(after 0 (lambda () (car 1)))").unwrap();
+    assert_eq!(Err(Error::Condition("Exception in car: 1 is not a pair".to_string())), m.run_timers());
+    assert_eq!("Exception in after: 1 is not a procedure", exception(&mut m, "(after 1 1)"));
+}
+
+#[test]
+fn virtual_clock() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.vm().set_virtual_clock(true);
+    m.eval_str("(define start (monotonic-time))").unwrap();
+    // Sleeping jumps the clock ahead without waiting
+    let real = Instant::now();
+    m.eval_str("(sleep 60)").unwrap();
+    assert!(real.elapsed() < Duration::from_secs(1));
+    assert_eq!("#t", eval(&mut m, "(< 59.9 (- (monotonic-time) start))"));
+    assert_eq!("#t", eval(&mut m, "(< (- (monotonic-time) start) 60.1)"));
+}
@@ -0,0 +1,113 @@
+extern crate minerva;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+fn eval(m: &mut Minerva, input: &str) -> String {
+    format!("{}", m.eval_str(input).unwrap().0)
+}
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn searching() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define v (vector 1 3 5 7 9 11))").unwrap();
+    m.eval_str("(define (compare a b) (- a b))").unwrap();
+    assert_eq!("3", eval(&mut m, "(vector-binary-search v 7 compare)"));
+    assert_eq!("0", eval(&mut m, "(vector-binary-search v 1 compare)"));
+    assert_eq!("5", eval(&mut m, "(vector-binary-search v 11 compare)"));
+    assert_eq!("#f", eval(&mut m, "(vector-binary-search v 4 compare)"));
+    assert_eq!("#f", eval(&mut m, "(vector-binary-search (vector) 4 compare)"));
+    assert_eq!("Exception in vector-binary-search: a is not a number",
+               exception(&mut m, "(vector-binary-search v 4 (lambda (a b) 'a))"));
+
+    assert_eq!("2", eval(&mut m, "(vector-index (lambda (x) (< 4 x)) v)"));
+    assert_eq!("#f", eval(&mut m, "(vector-index (lambda (x) (< 100 x)) v)"));
+}
+
+#[test]
+fn slices() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define v (vector 1 3 5 7 9 11))").unwrap();
+    m.eval_str("(define s (vector-slice v 2 5))").unwrap();
+    assert_eq!("#t", eval(&mut m, "(vector-slice? s)"));
+    assert_eq!("#f", eval(&mut m, "(vector? s)"));
+    assert_eq!("3", eval(&mut m, "(vector-length s)"));
+    assert_eq!("#(5 7 9)", eval(&mut m, "(vector-slice->vector s)"));
+
+    // Indices count from the start of the slice
+    assert_eq!("5", eval(&mut m, "(vector-ref s 0)"));
+    assert_eq!("Exception in vector-ref: 3 is not a valid index", exception(&mut m, "(vector-ref s 3)"));
+    assert_eq!("2", eval(&mut m, "(vector-binary-search s 9 (lambda (a b) (- a b)))"));
+    assert_eq!("#f", eval(&mut m, "(vector-binary-search s 11 (lambda (a b) (- a b)))"));
+    assert_eq!("1", eval(&mut m, "(vector-index (lambda (x) (= x 7)) s)"));
+    assert_eq!("21", eval(&mut m, "(fold + 0 s)"));
+
+    // A slice of a slice views the same vector
+    assert_eq!("#(7 9)", eval(&mut m, "(vector-slice->vector (vector-slice s 1))"));
+    assert_eq!("#()", eval(&mut m, "(vector-slice->vector (vector-slice s 3 3))"));
+    assert_eq!("Exception in vector-slice: 4 is not a valid index", exception(&mut m, "(vector-slice s 0 4)"));
+    assert_eq!("Exception in vector-slice: 1 is before the start 2", exception(&mut m, "(vector-slice v 2 1)"));
+}
+
+#[test]
+fn slices_collected() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    // Passing a smaller slice to every call of a recursion frees each once the call returns
+    m.eval_str("(define (sum s) (if (= 0 (vector-length s)) 0 (+ (vector-ref s 0) (sum (vector-slice s 1)))))")
+        .unwrap();
+    m.eval_str("(define v (vector))").unwrap();
+    m.eval_str("(define (fill n) (if (= n 0) 0 (begin (vector-push! v 2) (fill (- n 1)))))").unwrap();
+    m.eval_str("(fill 20)").unwrap();
+    assert_eq!("40", eval(&mut m, "(sum v)"));
+    assert_eq!("40", eval(&mut m, "(sum v)"));
+    let s = eval(&mut m, "(vector-slice v 0)");
+    let i: usize = s.trim_start_matches("#<vector-slice ").trim_end_matches('>').parse().unwrap();
+    assert!(i <= 20, "{} was given a new slot", s);
+
+    // The vector a slice views is kept alive by the slice
+    m.eval_str("(define s (vector-slice (vector 1 2 3) 1))").unwrap();
+    assert_eq!("40", eval(&mut m, "(sum v)"));
+    assert_eq!("#(2 3)", eval(&mut m, "(vector-slice->vector s)"));
+}
+
+#[test]
+fn growing() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    m.eval_str("(define v (vector 1))").unwrap();
+    m.eval_str("(vector-push! v 2)").unwrap();
+    assert_eq!("#(1 2)", eval(&mut m, "v"));
+    m.eval_str("(vector-extend! v (vector 3 4))").unwrap();
+    m.eval_str("(vector-extend! v (list 5 6))").unwrap();
+    assert_eq!("#(1 2 3 4 5 6)", eval(&mut m, "v"));
+    m.eval_str("(vector-extend! v (vector-slice v 0 2))").unwrap();
+    assert_eq!("8", eval(&mut m, "(vector-length v)"));
+
+    m.eval_str("(define s (vector-slice v 4))").unwrap();
+    assert_eq!("2", eval(&mut m, "(vector-pop! v)"));
+    assert_eq!("1", eval(&mut m, "(vector-pop! v)"));
+    assert_eq!("#(1 2 3 4 5 6)", eval(&mut m, "v"));
+    // The slice ends where the vector now does
+    assert_eq!("#(5 6)", eval(&mut m, "(vector-slice->vector s)"));
+
+    assert_eq!("Exception in vector-pop!: #() is empty", exception(&mut m, "(vector-pop! (vector))"));
+    // Which slot of the slice table the slice has depends on the slices freed before it
+    let message = exception(&mut m, "(vector-push! s 1)");
+    assert!(message.starts_with("Exception in vector-push!: #<vector-slice "), "{}", message);
+    assert!(message.ends_with("> is not a vector"), "{}", message);
+    assert_eq!("Exception in vector-extend!: (1 . 2) is not a proper list",
+               exception(&mut m, "(vector-extend! v (cons 1 2))"));
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "watch")]
+
+extern crate minerva;
+
+mod common;
+
+use common::HEAP;
+use minerva::{Error, Minerva};
+
+use std::env;
+use std::fs;
+use std::process;
+
+fn exception(m: &mut Minerva, input: &str) -> String {
+    match m.eval_str(input) {
+        Err(Error::Condition(message)) => message,
+        r => panic!("{} did not raise a condition: {:?}", input, r),
+    }
+}
+
+#[test]
+fn changes() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    let root = env::temp_dir().join(format!("minerva-watch-{}", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("old.ss"), "").unwrap();
+
+    m.eval_str("(define log '())").unwrap();
+    m.eval_str("(define (record event file) (set! log (cons (cons event file) log)))").unwrap();
+    m.eval_str(&format!("(define w (watch-path \"{}\" record 0.01))", root.display())).unwrap();
+    // The changes seen while sleeping, relative to `root`
+    let log = |m: &mut Minerva| {
+        m.eval_str("(sleep 0.05)").unwrap();
+        let log = format!("{}", m.eval_str("(reverse log)").unwrap().0);
+        m.eval_str("(set! log '())").unwrap();
+        log.replace(&format!("{}/", root.display()), "")
+    };
+    assert_eq!("()", log(&mut m));
+
+    fs::write(root.join("sub/new.ss"), "(display 1)").unwrap();
+    fs::write(root.join("old.ss"), "(display 2)").unwrap();
+    assert_eq!("((modified . \"old.ss\") (created . \"sub/new.ss\"))", log(&mut m));
+    fs::remove_file(root.join("old.ss")).unwrap();
+    assert_eq!("((removed . \"old.ss\"))", log(&mut m));
+
+    // Once unwatched, nothing is left to wait for
+    m.eval_str("(unwatch-path w)").unwrap();
+    fs::write(root.join("sub/new.ss"), "").unwrap();
+    assert_eq!(0, m.vm().pending_timers());
+    assert_eq!("()", log(&mut m));
+    assert_eq!("Exception in unwatch-path: 0 is not a watch", exception(&mut m, "(unwatch-path w)"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn errors() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut m = Minerva::new();
+    assert_eq!("Exception in watch-path: 1 is not a string", exception(&mut m, "(watch-path 1 display)"));
+    assert_eq!("Exception in watch-path: 1 is not a procedure", exception(&mut m, "(watch-path \".\" 1)"));
+    assert!(exception(&mut m, "(watch-path \"/no/such/path\" display)")
+            .starts_with("Exception in watch-path: could not watch \"/no/such/path\": "));
+}
@@ -1,9 +1,10 @@
 use {Instruction, Environment, Operation, Value};
+use value_hash::encode;
 
 use string_interner::{get_value, Symbol};
 
 use std::collections::HashMap;
-use std::fmt;
+use std::{fmt, mem};
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Hash)]
 pub struct Register(pub u8);
@@ -137,10 +138,33 @@ pub enum ASM {
     SetCar(Register, Register),
     /// SetCdr(reg, arg) Set the cdr of the pair in `reg` to `arg`.
     SetCdr(Register, Register),
+    /// VectorRef(reg, vector, k) Retrieve element `k` of `vector` and place the result in `reg`.
+    VectorRef(Register, Register, Register),
+    /// Set(name, arg) Assign `arg` to the variable named by the symbol in `name`.
+    Set(Register, Register),
     Define(Register, Register),
     Lookup(Register, Register),
-    Call(Register),
-    TailCall(Register),
+    /// LookupCached(reg, name) Like `Lookup`, caching the value found at this call site.
+    LookupCached(Register, Register),
+    /// Call(reg, argc) Call the procedure in `reg` with `argc` arguments in X1..
+    Call(Register, usize),
+    TailCall(Register, usize),
+    /// CallPrimitive(reg, instruction) Call the procedure in `reg` like `Call`, running
+    /// `instruction` on X1.. instead if the procedure is the primitive made of it alone.
+    CallPrimitive(Register, Instruction),
+    /// Trace(reg) Log every call to and return from the procedure in `reg`.
+    Trace(Register),
+    /// Untrace(reg) Stop tracing the procedure in `reg`.
+    Untrace(Register),
+    /// JumpTable(reg, min, labels, default) Goto `labels[reg - min]`, or `default` if `reg` is
+    /// not an integer in range.
+    JumpTable(Register, i32, Vec<Symbol>, Symbol),
+    /// BinarySearch(reg, table, default) Goto the label paired with the value in `reg`, or
+    /// `default` if there is none. The table is sorted when assembled.
+    BinarySearch(Register, Vec<(Value, Symbol)>, Symbol),
+    /// PerfectHash(reg, table, default) Goto the label paired with the symbol in `reg`, or
+    /// `default` if there is none. A collision free hash of the symbols is found when assembled.
+    PerfectHash(Register, Vec<(Symbol, Symbol)>, Symbol),
     Return,
     Label(Symbol),
 }
@@ -178,21 +202,121 @@ impl fmt::Display for ASM {
             Cdr(r1, r2) => write!(f, "CDR {}, {}", r1, r2),
             SetCar(r1, r2) => write!(f, "SETCAR {}, {}", r1, r2),
             SetCdr(r1, r2) => write!(f, "SETCDR {}, {}", r1, r2),
+            VectorRef(r1, r2, r3) => write!(f, "VECTORREF {}, {}, {}", r1, r2, r3),
+            Set(r1, r2) => write!(f, "SET {}, {}", r1, r2),
             Define(r1, r2) => write!(f, "DEFINE {}, {}", r1, r2),
             Lookup(r1, r2) => write!(f, "LOOKUP {}, {}", r1, r2),
-            Call(r) => write!(f, "CALL {}", r),
-            TailCall(r) => write!(f, "TAILCALL {}", r),
+            LookupCached(r1, r2) => write!(f, "LOOKUPCACHED {}, {}", r1, r2),
+            Call(r, n) => write!(f, "CALL {}, {}", r, n),
+            TailCall(r, n) => write!(f, "TAILCALL {}, {}", r, n),
+            CallPrimitive(r, i) => write!(f, "CALLPRIMITIVE {}, {}", r, format!("{:?}", i).to_uppercase()),
+            Trace(r) => write!(f, "TRACE {}", r),
+            Untrace(r) => write!(f, "UNTRACE {}", r),
+            JumpTable(r, min, labels, default) => {
+                write!(f, "JUMPTABLE {}, {}, [", r, min)?;
+                for l in labels {
+                    write!(f, "`{}`, ", get_value(*l).unwrap())?;
+                }
+                write!(f, "], `{}`", get_value(*default).unwrap())
+            }
+            BinarySearch(r, table, default) => {
+                write!(f, "BSEARCH {}, [", r)?;
+                for (v, l) in table {
+                    write!(f, "{} => `{}`, ", v, get_value(*l).unwrap())?;
+                }
+                write!(f, "], `{}`", get_value(*default).unwrap())
+            }
+            PerfectHash(r, table, default) => {
+                write!(f, "PHASH {}, [", r)?;
+                for (s, l) in table {
+                    write!(f, "{} => `{}`, ", get_value(*s).unwrap(), get_value(*l).unwrap())?;
+                }
+                write!(f, "], `{}`", get_value(*default).unwrap())
+            }
             Return => write!(f, "RETURN"),
             Label(s) => write!(f, "{}:", get_value(*s).unwrap()),
         }
     }
 }
 
+/// Hash an interned symbol into a table of `1 << bits` slots.
+pub(crate) fn symbol_hash(symbol: usize, multiplier: u32, bits: u32) -> usize {
+    ((symbol as u32).wrapping_mul(multiplier) >> (32 - bits)) as usize
+}
+
+/// Find a multiplier and table size for which `symbol_hash` has no collisions among the keys of
+/// `entries`. Tables are kept at most half full.
+pub(crate) fn perfect_hash<T>(entries: &[(Symbol, T)]) -> (u32, u32) {
+    let mut bits = 1;
+    while 1 << bits < 2 * entries.len() {
+        bits += 1;
+    }
+
+    let mut multiplier: u32 = 0x9E37_79B9;
+    loop {
+        for _ in 0..256 {
+            let mut used = vec![false; 1 << bits];
+            let found = entries.iter().all(|(s, _)| {
+                let i = symbol_hash(**s, multiplier, bits);
+                !mem::replace(&mut used[i], true)
+            });
+            if found {
+                return (multiplier, bits);
+            }
+            multiplier = multiplier.wrapping_mul(0x2C1B_3C6D).wrapping_add(0x297A_2D39) | 1;
+        }
+        bits += 1;
+    }
+}
+
+/// The most inline caches a piece of code can have, as many as the slot of a `LookupCached`
+/// instruction can index.
+const INLINE_CACHES: usize = 256;
+
+/// Constants with the same contents, which the code assembled with the pool shares.
+///
+/// Macros and quoted data repeat the same strings and lists, and each copy would otherwise be
+/// kept by every procedure which loads it. Strings, pairs, vectors, bytevectors, and bignums
+/// whose encodings for `value_hash` are the same are replaced by the first of them, so equal
+/// literals may be `eq?`, as the standard allows. The pool doesn't keep its values alive: it must
+/// not be used for more code once any code assembled with it has run, since a collection may have
+/// freed them.
+#[derive(Default)]
+pub struct ConstantPool {
+    values: HashMap<Vec<u8>, Value>,
+}
+
+impl ConstantPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The constant in the pool with the contents of `v`, adding `v` if there is none.
+    fn intern(&mut self, v: Value) -> Value {
+        let shareable = v.is_string() || v.is_pair() || v.is_vec() || v.is_bytevector() || v.is_bigint();
+        match encode(v) {
+            Ok(encoding) if shareable => *self.values.entry(encoding).or_insert(v),
+            _ => v,
+        }
+    }
+}
+
+/// Assemble `asm`, sharing equal constants among it and the procedures in it.
 pub fn assemble(asm: Vec<ASM>) -> (Vec<Operation>, Vec<Value>) {
+    assemble_pooled(asm, &mut ConstantPool::new())
+}
+
+/// Assemble `asm`, sharing equal constants with the other code assembled with `pool`.
+pub fn assemble_pooled(asm: Vec<ASM>, pool: &mut ConstantPool) -> (Vec<Operation>, Vec<Value>) {
     let mut ops = Vec::new();
     let mut consts = Vec::new();
+    // The index of each constant loaded, so that one loaded twice is only stored once
+    let mut loaded = HashMap::new();
     let mut labels = HashMap::new();
     let mut jumps = Vec::new();
+    // Tables of jump targets are stored as constants, with the labels filled in at the end.
+    let mut tables = Vec::new();
+    let mut slots = 0;
 
     for inst in asm {
         match inst {
@@ -214,12 +338,16 @@ pub fn assemble(asm: Vec<ASM>) -> (Vec<Operation>, Vec<Value>) {
             ASM::Restore(r) => ops.push(Operation::Restore(r)),
             ASM::ReadStack(r, p) => ops.push(Operation::ReadStack(r, p)),
             ASM::LoadConst(r, v) => {
-                ops.push(Operation::LoadConst(r, consts.len()));
-                consts.push(v);
+                let v = pool.intern(v);
+                let c = *loaded.entry(v).or_insert_with(|| {
+                    consts.push(v);
+                    consts.len() - 1
+                });
+                ops.push(Operation::LoadConst(r, c));
             }
             ASM::MakeClosure(r, code) => {
                 // Compile lambda
-                let (lambda_code, lambda_consts) = assemble(*code);
+                let (lambda_code, lambda_consts) = assemble_pooled(*code, pool);
                 let lambda = Value::Lambda(Environment::new(), lambda_code, lambda_consts);
                 ops.push(Operation::MakeClosure(r, consts.len()));
                 consts.push(lambda);
@@ -281,14 +409,71 @@ pub fn assemble(asm: Vec<ASM>) -> (Vec<Operation>, Vec<Value>) {
             ASM::SetCdr(r, a) => {
                 ops.push(Operation::SetCdr(r, a));
             }
+            ASM::VectorRef(r, v, k) => ops.push(Operation::VectorRef(r, v, k)),
+            ASM::Set(a1, a2) => ops.push(Operation::Set(a1, a2)),
             ASM::Define(a1, a2) => {
                 ops.push(Operation::Define(a1, a2));
             }
             ASM::Lookup(r, a) => {
                 ops.push(Operation::Lookup(r, a));
             }
-            ASM::Call(r) => ops.push(Operation::Call(r)),
-            ASM::TailCall(r) => ops.push(Operation::TailCall(r)),
+            // Each call site of the code gets its own slot, the rest are looked up every time
+            ASM::LookupCached(r, a) => if slots < INLINE_CACHES {
+                ops.push(Operation::LookupCached(r, a, slots));
+                slots += 1;
+            } else {
+                ops.push(Operation::Lookup(r, a));
+            },
+            ASM::Call(r, n) => ops.push(Operation::Call(r, n)),
+            ASM::TailCall(r, n) => ops.push(Operation::TailCall(r, n)),
+            ASM::CallPrimitive(r, i) => ops.push(Operation::CallPrimitive(r, i)),
+            ASM::Trace(r) => ops.push(Operation::Trace(r)),
+            ASM::Untrace(r) => ops.push(Operation::Untrace(r)),
+            ASM::JumpTable(r, min, labels, default) => {
+                // The table is `[min, default, targets...]`
+                let mut table = vec![Value::Integer(min), Value::Void];
+                let mut targets = vec![(1, default)];
+                for l in labels {
+                    targets.push((table.len(), l));
+                    table.push(Value::Void);
+                }
+                ops.push(Operation::JumpTable(r, consts.len()));
+                tables.push((consts.len(), table, targets));
+                consts.push(Value::Void);
+            }
+            ASM::BinarySearch(r, mut entries, default) => {
+                // The table is `[default, key, target, key, target...]` sorted by key
+                entries.sort_by_key(|(v, _)| **v);
+                let mut table = vec![Value::Void];
+                let mut targets = vec![(0, default)];
+                for (v, l) in entries {
+                    table.push(v);
+                    targets.push((table.len(), l));
+                    table.push(Value::Void);
+                }
+                ops.push(Operation::BinarySearch(r, consts.len()));
+                tables.push((consts.len(), table, targets));
+                consts.push(Value::Void);
+            }
+            ASM::PerfectHash(r, entries, default) => {
+                // The table is `[default, multiplier, key, target, key, target...]` with a slot
+                // for every possible hash, empty slots hold `Void`
+                let (multiplier, bits) = perfect_hash(&entries);
+                let mut table = vec![Value::Void; 2 + (2 << bits)];
+                table[1] = Value::Integer(multiplier as i32);
+                let mut targets = vec![(0, default)];
+                for i in 0..1 << bits {
+                    targets.push((3 + 2 * i, default));
+                }
+                for (s, l) in entries {
+                    let i = symbol_hash(*s, multiplier, bits);
+                    table[2 + 2 * i] = Value::Symbol(s);
+                    targets[i + 1] = (3 + 2 * i, l);
+                }
+                ops.push(Operation::PerfectHash(r, consts.len()));
+                tables.push((consts.len(), table, targets));
+                consts.push(Value::Void);
+            }
             ASM::Return => ops.push(Operation::Return),
         };
     }
@@ -310,5 +495,17 @@ pub fn assemble(asm: Vec<ASM>) -> (Vec<Operation>, Vec<Value>) {
         }
     }
 
+    for (c, mut table, targets) in tables {
+        for (i, label) in targets {
+            let p = if let Some(p) = labels.get(&label) {
+                *p
+            } else {
+                panic!("Unknown label `{}`", get_value(label).unwrap());
+            };
+            table[i] = Value::Integer(p as i32);
+        }
+        consts[c] = Value::Vec(table);
+    }
+
     (ops, consts)
 }
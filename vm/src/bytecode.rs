@@ -16,9 +16,12 @@ impl fmt::Display for Operation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.instruction() {
             LoadContinue | SaveContinue | RestoreContinue => self.print_continue(f),
-            Save | Restore | ReadStack | LoadConst | MakeClosure | Call | TailCall => self.print_register(f),
-            Move | Car | Cdr | StringToSymbol | Set | SetCar | SetCdr | Define | Lookup => self.print_register2(f),
-            Add | Sub | Mul | Eq | LT | Cons => self.print_register_opvalue2(f),
+            Save | Restore | ReadStack | LoadConst | MakeClosure | Call | TailCall | Trace | Untrace |
+            JumpTable | BinarySearch | PerfectHash | CallPrimitive => self.print_register(f),
+            Move | Car | Cdr | StringToSymbol | Set | SetCar | SetCdr | Define | Lookup | LookupCached => {
+                self.print_register2(f)
+            }
+            Add | Sub | Mul | Eq | LT | Cons | VectorRef => self.print_register_opvalue2(f),
             Goto | GotoIf | GotoIfNot => self.print_goto(f),
             Return => write!(f, "RETURN"),
         }
@@ -101,7 +104,8 @@ macro_rules! register_gotovalue {
     ($instruction:ident, $register:ident, $goto:ident, $set:ident) => {
         pub fn $instruction(register: Register, value: Option<usize>) -> Self {
             let register = register.0 as u32;
-            let value = if let Some(p) = value { (p as u32) << 16 } else { 1 << 12 };
+            // Registers take bits 8 to 12, going to the continue register is flagged above them
+            let value = if let Some(p) = value { (p as u32) << 16 } else { 1 << 13 };
             Operation(value | (register << 8) | ($instruction as u32))
         }
 
@@ -111,7 +115,7 @@ macro_rules! register_gotovalue {
         }
 
         pub fn $goto(self) -> Option<usize> {
-            let cond = (self.0 >> 12) & 255;
+            let cond = (self.0 >> 13) & 7;
             if cond == 1 {
                 None
             } else {
@@ -147,8 +151,17 @@ impl Operation {
             ReadStack => write!(f, "READSTACK {}, -{}", self.readstack_register(), self.readstack_offset()),
             LoadConst => write!(f, "LOADCONST {}", self.loadconst_register()),
             MakeClosure => write!(f, "MAKECLOSURE {}", self.makeclosure_register()),
-            Call => write!(f, "CALL {}", self.call_register()),
-            TailCall => write!(f, "TAILCALL {}", self.call_register()),
+            Call => write!(f, "CALL {}, {}", self.call_register(), self.call_argc()),
+            TailCall => write!(f, "TAILCALL {}, {}", self.tail_call_register(), self.tail_call_argc()),
+            Trace => write!(f, "TRACE {}", self.trace_register()),
+            Untrace => write!(f, "UNTRACE {}", self.untrace_register()),
+            JumpTable => write!(f, "JUMPTABLE {}, #{}", self.jumptable_register(), self.jumptable_table()),
+            BinarySearch => write!(f, "BSEARCH {}, #{}", self.bsearch_register(), self.bsearch_table()),
+            PerfectHash => write!(f, "PHASH {}, #{}", self.phash_register(), self.phash_table()),
+            CallPrimitive => {
+                let primitive = format!("{:?}", self.callprimitive_instruction()).to_uppercase();
+                write!(f, "CALLPRIMITIVE {}, {}", self.callprimitive_register(), primitive)
+            }
             _ => unreachable!(),
         }
     }
@@ -164,6 +177,8 @@ impl Operation {
             StringToSymbol => write!(f, "STRINGTOSYMBOL {}, {}", self.stringtosymbol_register(), self.stringtosymbol_value()),
             Define => write!(f, "DEFINE {}, {}", self.define_name(), self.define_value()),
             Lookup => write!(f, "LOOKUP {}, {}", self.lookup_register(), self.lookup_name()),
+            LookupCached => write!(f, "LOOKUPCACHED {}, {}, #{}", self.lookupcached_register(),
+                                   self.lookupcached_name(), self.lookupcached_slot()),
             _ => unreachable!(),
         }
     }
@@ -176,6 +191,7 @@ impl Operation {
             Eq => write!(f, "EQ {}, {}, {}", self.eq_register(), self.eq_left(), self.eq_right()),
             LT => write!(f, "LT {}, {}, {}", self.lt_register(), self.lt_left(), self.lt_right()),
             Cons => write!(f, "CONS {}, {}, {}", self.cons_register(), self.cons_car(), self.cons_cdr()),
+            VectorRef => write!(f, "VECTORREF {}, {}, {}", self.vectorref_register(), self.vectorref_vector(), self.vectorref_index()),
             _ => unreachable!(),
         }
     }
@@ -295,6 +311,12 @@ impl Operation {
     // Retrieve the `from` register from a Cdr instruction.
     register2!(Cdr, cdr_to, cdr_from);
 
+    // Creates a VectorRef instruction. Takes the form `index-vector-register-VectorRef`.
+    // Retrieve the register from a VectorRef instruction.
+    // Retrieve the `vector` from a VectorRef instruction.
+    // Retrieve the `index` from a VectorRef instruction.
+    register_opvalue2!(VectorRef, vectorref_register, vectorref_vector, vectorref_index);
+
 
     register2!(Set, set_name, set_value);
 
@@ -318,10 +340,63 @@ impl Operation {
     // Retrive the `name` from a Lookup instruction.
     register2!(Lookup, lookup_register, lookup_name);
 
-    // Creates a Call instruction. The register to call from uses 1 byte.
+    // Creates a LookupCached instruction. Takes the form `slot-name-register-LookupCached`, each
+    // taking up one byte.
+    pub fn LookupCached(register: Register, name: Register, slot: usize) -> Self {
+        let register = register.0 as u32;
+        let name = name.0 as u32;
+        let slot = slot as u32;
+        Operation((slot << 24) | (name << 16) | (register << 8) | (LookupCached as u32))
+    }
+
+    pub fn lookupcached_register(self) -> Register {
+        Register::from((self.0 >> 8) & 255)
+    }
+
+    pub fn lookupcached_name(self) -> Register {
+        Register::from((self.0 >> 16) & 255)
+    }
+
+    pub fn lookupcached_slot(self) -> usize {
+        (self.0 >> 24) as usize
+    }
+
+    // Creates a Call instruction. The register to call from uses 1 byte, the number of arguments
+    // passed takes up the remaining bytes.
     // Retrieve the register from a Call instruction.
-    register!(Call, call_register);
-    register!(TailCall, tail_call_register);
+    // Retrieve the number of arguments from a Call instruction.
+    register_constant!(Call, call_register, call_argc);
+    register_constant!(TailCall, tail_call_register, tail_call_argc);
+
+    // Creates a Trace instruction. The register holding the procedure uses 1 byte.
+    register!(Trace, trace_register);
+    // Creates an Untrace instruction. The register holding the procedure uses 1 byte.
+    register!(Untrace, untrace_register);
+
+    // Creates a JumpTable instruction. The register holding the key uses 1 byte, the constant
+    // holding the table takes up the remaining bytes.
+    register_constant!(JumpTable, jumptable_register, jumptable_table);
+    // Creates a BinarySearch instruction. The register holding the key uses 1 byte, the constant
+    // holding the sorted table takes up the remaining bytes.
+    register_constant!(BinarySearch, bsearch_register, bsearch_table);
+    // Creates a PerfectHash instruction. The register holding the key uses 1 byte, the constant
+    // holding the hash table takes up the remaining bytes.
+    register_constant!(PerfectHash, phash_register, phash_table);
+
+    // Creates a CallPrimitive instruction. The register holding the procedure uses 1 byte, the
+    // instruction of the primitive takes up the remaining bytes.
+    pub fn CallPrimitive(register: Register, primitive: Instruction) -> Self {
+        let register = register.0 as u32;
+        Operation(((primitive as u32) << 16) | (register << 8) | (CallPrimitive as u32))
+    }
+
+    pub fn callprimitive_register(self) -> Register {
+        Register::from((self.0 >> 8) & 255)
+    }
+
+    pub fn callprimitive_instruction(self) -> Instruction {
+        Instruction::from(self.0 >> 16)
+    }
 
     // Creates a Return instruction.
     pub const Return: Self = Operation(Return as u32);
@@ -392,6 +467,25 @@ pub enum Instruction {
     ReadStack = 26,
     Set = 27,
     TailCall = 28,
+    /// Trace(reg) Log every call to and return from the procedure in `reg`.
+    Trace = 29,
+    /// Untrace(reg) Stop tracing the procedure in `reg`.
+    Untrace = 30,
+    /// JumpTable(reg, table) Goto the entry of `table` indexed by the integer in `reg`.
+    JumpTable = 31,
+    /// BinarySearch(reg, table) Goto the entry of the sorted `table` whose key matches `reg`.
+    BinarySearch = 32,
+    /// PerfectHash(reg, table) Goto the entry of the hash `table` whose symbol matches `reg`.
+    PerfectHash = 33,
+    /// VectorRef(reg, vector, k) Retrieve element `k` of `vector` and place the result in `reg`.
+    VectorRef = 34,
+    /// CallPrimitive(reg, instruction) Call the procedure in `reg` with the arguments of
+    /// `instruction` in X1.., by running `instruction` directly if the procedure is the primitive
+    /// made of it alone.
+    CallPrimitive = 35,
+    /// LookupCached(reg, name, slot) Like `Lookup`, remembering the value found in the inline
+    /// cache `slot` of the running code.
+    LookupCached = 36,
 }
 
 impl From<u32> for Instruction {
@@ -427,6 +521,14 @@ impl From<u32> for Instruction {
             26 => ReadStack,
             27 => Set,
             28 => TailCall,
+            29 => Trace,
+            30 => Untrace,
+            31 => JumpTable,
+            32 => BinarySearch,
+            33 => PerfectHash,
+            34 => VectorRef,
+            35 => CallPrimitive,
+            36 => LookupCached,
             _ => panic!("Invalid Instruction value {}", r),
         }
     }
@@ -530,6 +632,14 @@ mod test {
         assert_eq!(Some(1), op.gotoifnot_value());
         let op = op.gotoifnot_set_label(2);
         assert_eq!(Some(2), op.gotoifnot_value());
+
+        // Registers from X16 up don't look like the continue register
+        let op = Operation::GotoIfNot(Register(17), Some(3));
+        assert_eq!(Register(17), op.gotoifnot_register());
+        assert_eq!(Some(3), op.gotoifnot_value());
+        let op = Operation::GotoIfNot(Register(17), None);
+        assert_eq!(Register(17), op.gotoifnot_register());
+        assert_eq!(None, op.gotoifnot_value());
     }
 
     #[test]
@@ -624,11 +734,67 @@ mod test {
         assert_eq!(Register(0), op.lookup_name());
     }
 
+    #[test]
+    fn lookup_cached() {
+        let op = Operation::LookupCached(Register(3), Register(4), 255);
+        assert_eq!(LookupCached, op.instruction());
+        assert_eq!(Register(3), op.lookupcached_register());
+        assert_eq!(Register(4), op.lookupcached_name());
+        assert_eq!(255, op.lookupcached_slot());
+    }
+
     #[test]
     fn call() {
-        let op = Operation::Call(Register(0));
+        let op = Operation::Call(Register(0), 2);
         assert_eq!(Call, op.instruction());
         assert_eq!(Register(0), op.call_register());
+        assert_eq!(2, op.call_argc());
+    }
+
+    #[test]
+    fn trace() {
+        let op = Operation::Trace(Register(1));
+        assert_eq!(Trace, op.instruction());
+        assert_eq!(Register(1), op.trace_register());
+
+        let op = Operation::Untrace(Register(1));
+        assert_eq!(Untrace, op.instruction());
+        assert_eq!(Register(1), op.untrace_register());
+    }
+
+    #[test]
+    fn jump_table() {
+        let op = Operation::JumpTable(Register(3), 7);
+        assert_eq!(JumpTable, op.instruction());
+        assert_eq!(Register(3), op.jumptable_register());
+        assert_eq!(7, op.jumptable_table());
+
+        let op = Operation::BinarySearch(Register(3), 7);
+        assert_eq!(BinarySearch, op.instruction());
+        assert_eq!(Register(3), op.bsearch_register());
+        assert_eq!(7, op.bsearch_table());
+
+        let op = Operation::PerfectHash(Register(3), 7);
+        assert_eq!(PerfectHash, op.instruction());
+        assert_eq!(Register(3), op.phash_register());
+        assert_eq!(7, op.phash_table());
+    }
+
+    #[test]
+    fn vector_ref() {
+        let op = Operation::VectorRef(Register(0), Register(1), Register(2));
+        assert_eq!(VectorRef, op.instruction());
+        assert_eq!(Register(0), op.vectorref_register());
+        assert_eq!(Register(1), op.vectorref_vector());
+        assert_eq!(Register(2), op.vectorref_index());
+    }
+
+    #[test]
+    fn call_primitive() {
+        let op = Operation::CallPrimitive(Register(4), Cdr);
+        assert_eq!(CallPrimitive, op.instruction());
+        assert_eq!(Register(4), op.callprimitive_register());
+        assert_eq!(Cdr, op.callprimitive_instruction());
     }
 
     #[test]
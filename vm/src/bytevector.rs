@@ -0,0 +1,361 @@
+//! Bytevectors and the fixed-width numeric encodings shared with binary ports.
+//!
+//! Integers are exact when they fit in a fixnum. Unsigned 32 bit values above `i32::MAX` are
+//! returned as floats, which represent them exactly. Integers of any size can be converted with
+//! `integer->bytevector` and `bytevector->integer`.
+
+use {Value, VM};
+use number::exact_integer;
+
+use num_bigint::{BigInt, Sign};
+use string_interner::get_value;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Endianness {
+    Big,
+    Little,
+}
+
+impl Endianness {
+    const NATIVE: Endianness = if cfg!(target_endian = "big") {
+        Endianness::Big
+    } else {
+        Endianness::Little
+    };
+
+    fn name(self) -> &'static str {
+        match self {
+            Endianness::Big => "big",
+            Endianness::Little => "little",
+        }
+    }
+}
+
+/// Parse an endianness argument, `big` or `little`. Defaults to big endian, the order used by
+/// most file formats and network protocols.
+pub(crate) fn endianness(name: &str, v: Option<&Value>) -> Result<Endianness, String> {
+    let v = match v {
+        Some(&v) => v,
+        None => return Ok(Endianness::Big),
+    };
+    if v.is_symbol() {
+        match get_value(v.to_symbol()).as_ref().map(String::as_str) {
+            Some("big") => return Ok(Endianness::Big),
+            Some("little") => return Ok(Endianness::Little),
+            _ => (),
+        }
+    }
+    Err(format!("{}: {} is not an endianness, expected big or little", name, v))
+}
+
+/// A fixed-width number encoding.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Numeric {
+    U8,
+    S8,
+    U16,
+    S16,
+    U32,
+    S32,
+    F32,
+    F64,
+}
+
+impl Numeric {
+    pub(crate) fn size(self) -> usize {
+        match self {
+            Numeric::U8 | Numeric::S8 => 1,
+            Numeric::U16 | Numeric::S16 => 2,
+            Numeric::U32 | Numeric::S32 | Numeric::F32 => 4,
+            Numeric::F64 => 8,
+        }
+    }
+
+    /// Decode `bytes`, which must be exactly `self.size()` long.
+    pub(crate) fn decode(self, bytes: &[u8], e: Endianness) -> Value {
+        let mut raw = [0; 8];
+        raw[..bytes.len()].copy_from_slice(bytes);
+        if e != Endianness::NATIVE {
+            raw[..bytes.len()].reverse();
+        }
+        let b2 = [raw[0], raw[1]];
+        let b4 = [raw[0], raw[1], raw[2], raw[3]];
+        match self {
+            Numeric::U8 => Value::Integer(raw[0] as i32),
+            Numeric::S8 => Value::Integer(raw[0] as i8 as i32),
+            Numeric::U16 => Value::Integer(u16::from_ne_bytes(b2) as i32),
+            Numeric::S16 => Value::Integer(i16::from_ne_bytes(b2) as i32),
+            Numeric::U32 => {
+                let n = u32::from_ne_bytes(b4);
+                if n > i32::MAX as u32 {
+                    Value::Float(n as f64)
+                } else {
+                    Value::Integer(n as i32)
+                }
+            }
+            Numeric::S32 => Value::Integer(i32::from_ne_bytes(b4)),
+            Numeric::F32 => Value::Float(f32::from_ne_bytes(b4) as f64),
+            Numeric::F64 => Value::Float(f64::from_ne_bytes(raw)),
+        }
+    }
+
+    /// Encode `v`, which must be a number in range for this encoding.
+    pub(crate) fn encode(self, name: &str, v: Value, e: Endianness) -> Result<Vec<u8>, String> {
+        let out_of_range = || format!("{}: {} is out of range", name, v);
+        let integer = |min: i64, max: i64| -> Result<i64, String> {
+            let n = if v.is_integer() {
+                v.to_integer() as i64
+            } else if v.is_float() && v.to_float().fract() == 0.0 {
+                // Large unsigned values are floats
+                v.to_float() as i64
+            } else {
+                return Err(format!("{}: {} is not an exact integer", name, v));
+            };
+            if n < min || n > max {
+                Err(out_of_range())
+            } else {
+                Ok(n)
+            }
+        };
+        let float = || -> Result<f64, String> {
+            if v.is_float() {
+                Ok(v.to_float())
+            } else if v.is_integer() {
+                Ok(v.to_integer() as f64)
+            } else {
+                Err(format!("{}: {} is not a number", name, v))
+            }
+        };
+
+        let mut bytes = match self {
+            Numeric::U8 => vec![integer(0, u8::MAX as i64)? as u8],
+            Numeric::S8 => vec![integer(i8::MIN as i64, i8::MAX as i64)? as u8],
+            Numeric::U16 => (integer(0, u16::MAX as i64)? as u16).to_ne_bytes().to_vec(),
+            Numeric::S16 => (integer(i16::MIN as i64, i16::MAX as i64)? as i16).to_ne_bytes().to_vec(),
+            Numeric::U32 => (integer(0, u32::MAX as i64)? as u32).to_ne_bytes().to_vec(),
+            Numeric::S32 => (integer(i32::MIN as i64, i32::MAX as i64)? as i32).to_ne_bytes().to_vec(),
+            Numeric::F32 => (float()? as f32).to_ne_bytes().to_vec(),
+            Numeric::F64 => float()?.to_ne_bytes().to_vec(),
+        };
+        if e != Endianness::NATIVE {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+}
+
+/// The bytes of a bytevector, or of a string encoded as UTF-8.
+#[cfg(any(feature = "compress", feature = "crypto"))]
+pub(crate) fn bytes_arg(name: &str, v: Value) -> Result<Vec<u8>, String> {
+    if v.is_bytevector() {
+        let b = v.to_bytevector();
+        let r = b.bytes.clone();
+        Box::into_raw(b);
+        Ok(r)
+    } else if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.as_bytes().to_vec();
+        Box::into_raw(s);
+        Ok(r)
+    } else {
+        Err(format!("{}: {} is not a bytevector or string", name, v))
+    }
+}
+
+/// Check that `v` is a bytevector and `k` an index at which `size` bytes can be accessed.
+fn check_index(name: &str, v: Value, k: Value, size: usize) -> Result<usize, String> {
+    if !v.is_bytevector() {
+        return Err(format!("{}: {} is not a bytevector", name, v));
+    }
+    let b = v.to_bytevector();
+    let len = b.bytes.len();
+    Box::into_raw(b);
+    if k.is_integer() && k.to_integer() >= 0 && k.to_integer() as usize + size <= len {
+        Ok(k.to_integer() as usize)
+    } else {
+        Err(format!("{}: {} is not a valid index", name, k))
+    }
+}
+
+/// `(bytevector-*-ref bytevector k [endianness])`
+fn numeric_ref(name: &str, n: Numeric, args: &[Value]) -> Result<Value, String> {
+    let k = check_index(name, args[0], args[1], n.size())?;
+    let e = endianness(name, args.get(2))?;
+    let b = args[0].to_bytevector();
+    let v = n.decode(&b.bytes[k..k + n.size()], e);
+    Box::into_raw(b);
+    Ok(v)
+}
+
+/// `(bytevector-*-set! bytevector k n [endianness])`
+fn numeric_set(name: &str, n: Numeric, args: &[Value]) -> Result<Value, String> {
+    let k = check_index(name, args[0], args[1], n.size())?;
+    let bytes = n.encode(name, args[2], endianness(name, args.get(3))?)?;
+    let mut b = args[0].to_bytevector();
+    b.bytes[k..k + n.size()].copy_from_slice(&bytes);
+    Box::into_raw(b);
+    Ok(Value::Void)
+}
+
+macro_rules! accessors {
+    ($($numeric:ident: $get:ident $get_name:expr, $set:ident $set_name:expr;)*) => {
+        $(
+            #[doc = concat!("`(", $get_name, " bytevector k [endianness])`")]
+            pub fn $get(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+                numeric_ref($get_name, Numeric::$numeric, args)
+            }
+
+            #[doc = concat!("`(", $set_name, " bytevector k n [endianness])`")]
+            pub fn $set(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+                numeric_set($set_name, Numeric::$numeric, args)
+            }
+        )*
+    };
+}
+
+accessors! {
+    U16: u16_ref "bytevector-u16-ref", u16_set "bytevector-u16-set!";
+    S16: s16_ref "bytevector-s16-ref", s16_set "bytevector-s16-set!";
+    U32: u32_ref "bytevector-u32-ref", u32_set "bytevector-u32-set!";
+    S32: s32_ref "bytevector-s32-ref", s32_set "bytevector-s32-set!";
+    F32: ieee_single_ref "bytevector-ieee-single-ref", ieee_single_set "bytevector-ieee-single-set!";
+    F64: ieee_double_ref "bytevector-ieee-double-ref", ieee_double_set "bytevector-ieee-double-set!";
+}
+
+/// `(bytevector byte ...)` Return a newly allocated bytevector of the arguments.
+pub fn bytevector(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let mut bytes = Vec::with_capacity(args.len());
+    for &v in args {
+        bytes.extend(Numeric::U8.encode("bytevector", v, Endianness::Big)?);
+    }
+    Ok(Value::Bytevector(bytes))
+}
+
+/// `(make-bytevector k [byte])` A newly allocated bytevector of `k` copies of `byte`, or of zeros.
+pub fn make_bytevector(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "make-bytevector";
+    let k = args[0];
+    if !k.is_integer() || k.to_integer() < 0 {
+        return Err(format!("{}: {} is not a valid length", name, k));
+    }
+    vm.check_allocation(name, k.to_integer() as usize)?;
+    let fill = match args.get(1) {
+        Some(&v) => Numeric::U8.encode(name, v, Endianness::Big)?[0],
+        None => 0,
+    };
+    Ok(Value::Bytevector(vec![fill; k.to_integer() as usize]))
+}
+
+/// `(bytevector-u8-ref bytevector k)`
+pub fn u8_ref(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    numeric_ref("bytevector-u8-ref", Numeric::U8, args)
+}
+
+/// `(bytevector-u8-set! bytevector k byte)`
+pub fn u8_set(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    numeric_set("bytevector-u8-set!", Numeric::U8, args)
+}
+
+/// `(bytevector-length bytevector)` The number of bytes in `bytevector`.
+pub fn bytevector_length(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !args[0].is_bytevector() {
+        return Err(format!("bytevector-length: {} is not a bytevector", args[0]));
+    }
+    let b = args[0].to_bytevector();
+    let len = b.bytes.len();
+    Box::into_raw(b);
+    Ok(Value::Integer(len as i32))
+}
+
+/// `(bytevector-copy bytevector [start [end]])` A newly allocated bytevector of the bytes of
+/// `bytevector` from `start` up to `end`, by default all of them.
+pub fn bytevector_copy(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "bytevector-copy";
+    if !args[0].is_bytevector() {
+        return Err(format!("{}: {} is not a bytevector", name, args[0]));
+    }
+    let b = args[0].to_bytevector();
+    let bytes = b.bytes.clone();
+    Box::into_raw(b);
+    let bound = |v: Option<&Value>, min: usize, default: usize| match v {
+        None => Ok(default),
+        Some(&v) if v.is_integer() && v.to_integer() >= min as i32 && v.to_integer() as usize <= bytes.len() =>
+            Ok(v.to_integer() as usize),
+        Some(&v) => Err(format!("{}: {} is not a valid index", name, v)),
+    };
+    let start = bound(args.get(1), 0, 0)?;
+    let end = bound(args.get(2), start, bytes.len())?;
+    Ok(Value::Bytevector(bytes[start..end].to_vec()))
+}
+
+/// `(bytevector? obj)`
+pub fn is_bytevector(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(args[0].is_bytevector()))
+}
+
+/// `(native-endianness)` The byte order of the machine, `big` or `little`.
+pub fn native_endianness(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Symbol(VM::intern_symbol(Endianness::NATIVE.name().to_string())))
+}
+
+/// `(integer->bytevector n size [endianness])` Encode the exact integer `n` in `size` bytes, as
+/// two's complement if it is negative.
+pub fn integer_to_bytevector(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "integer->bytevector";
+    let n = exact_integer(name, args[0])?;
+    let size = args[1];
+    if !size.is_integer() || size.to_integer() < 0 {
+        return Err(format!("{}: {} is not a valid size", name, size));
+    }
+    let size = size.to_integer() as usize;
+    vm.check_allocation(name, size)?;
+    let e = endianness(name, args.get(2))?;
+
+    let (mut bytes, pad) = match n.sign() {
+        Sign::Minus => (n.to_signed_bytes_le(), 0xFF),
+        Sign::NoSign => (vec![], 0),
+        Sign::Plus => (n.to_bytes_le().1, 0),
+    };
+    if bytes.len() > size {
+        return Err(format!("{}: {} does not fit in {} bytes", name, args[0], size));
+    }
+    bytes.resize(size, pad);
+    if e == Endianness::Big {
+        bytes.reverse();
+    }
+    Ok(Value::Bytevector(bytes))
+}
+
+/// `(bytevector->integer bytevector [endianness] [signedness])` Decode a bytevector as a `signed`
+/// or `unsigned` (the default) integer.
+pub fn bytevector_to_integer(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "bytevector->integer";
+    if !args[0].is_bytevector() {
+        return Err(format!("{}: {} is not a bytevector", name, args[0]));
+    }
+    let e = endianness(name, args.get(1))?;
+    let signed = match args.get(2) {
+        None => false,
+        Some(&v) => {
+            let s = if v.is_symbol() { get_value(v.to_symbol()) } else { None };
+            match s.as_ref().map(String::as_str) {
+                Some("signed") => true,
+                Some("unsigned") => false,
+                _ => return Err(format!("{}: {} is not a signedness, expected signed or unsigned", name, v)),
+            }
+        }
+    };
+
+    let b = args[0].to_bytevector();
+    let mut bytes = b.bytes.clone();
+    Box::into_raw(b);
+    if e == Endianness::Big {
+        bytes.reverse();
+    }
+    let n = if signed {
+        BigInt::from_signed_bytes_le(&bytes)
+    } else {
+        BigInt::from_bytes_le(Sign::Plus, &bytes)
+    };
+    Ok(Value::integer(n))
+}
@@ -0,0 +1,54 @@
+//! Characters, which are Unicode scalar values.
+
+use {Value, VM};
+
+fn char_arg(name: &str, v: Value) -> Result<char, String> {
+    if v.is_char() {
+        Ok(v.to_char())
+    } else {
+        Err(format!("{}: {} is not a character", name, v))
+    }
+}
+
+/// `(char? obj)`
+pub fn is_char(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(args[0].is_char()))
+}
+
+/// `(char->integer char)` The code point of `char`.
+pub fn char_to_integer(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Integer(char_arg("char->integer", args[0])? as i32))
+}
+
+/// `(integer->char n)` The character whose code point is `n`. Surrogates are not characters.
+pub fn integer_to_char(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let n = args[0];
+    if n.is_integer() && n.to_integer() >= 0 {
+        if let Some(c) = char::from_u32(n.to_integer() as u32) {
+            return Ok(Value::Char(c));
+        }
+    }
+    Err(format!("integer->char: {} is not a code point", n))
+}
+
+/// `(char-upcase char)` The upper case form of `char`, or `char` if it has none which is a single
+/// character.
+pub fn char_upcase(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let c = char_arg("char-upcase", args[0])?;
+    Ok(Value::Char(single(c.to_uppercase()).unwrap_or(c)))
+}
+
+/// `(char-downcase char)` The lower case form of `char`, or `char` if it has none which is a
+/// single character.
+pub fn char_downcase(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let c = char_arg("char-downcase", args[0])?;
+    Ok(Value::Char(single(c.to_lowercase()).unwrap_or(c)))
+}
+
+// The only character of `chars`. Some case mappings, like that of `ß` to `SS`, take more than one.
+fn single<I: Iterator<Item = char>>(mut chars: I) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
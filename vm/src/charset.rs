@@ -0,0 +1,256 @@
+//! Sets of characters, for classifying characters in lexers and other text processing.
+//!
+//! A char-set keeps its characters as sorted ranges of code points, so that large sets such as
+//! `char-set:letter`, or the complement of a small set, take little space and membership is a
+//! binary search. Like a vector slice, a char-set value is an index into the char-set table of the
+//! machine which made it. Every table starts with the standard sets, which `init_env` binds to
+//! `char-set:letter` and the like; each is only computed when it is first used.
+//!
+//! `for-each` and `fold` give the characters of a set in order of their code points.
+
+use {Value, VM};
+
+use std::cmp::Ordering;
+use std::sync::LazyLock;
+
+// The largest code point, and the surrogates, which are code points but not characters
+const MAX: u32 = 0x10FFFF;
+const SURROGATES: (u32, u32) = (0xD800, 0xDFFF);
+
+/// A set of characters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CharSet {
+    // Disjoint inclusive ranges of code points in order, none adjacent to the next
+    ranges: Vec<(u32, u32)>,
+}
+
+impl CharSet {
+    fn from_chars<I: IntoIterator<Item = char>>(chars: I) -> CharSet {
+        let mut points: Vec<u32> = chars.into_iter().map(|c| c as u32).collect();
+        points.sort_unstable();
+        points.dedup();
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for c in points {
+            match ranges.last_mut() {
+                Some(r) if r.1 + 1 == c => r.1 = c,
+                _ => ranges.push((c, c)),
+            }
+        }
+        CharSet { ranges }
+    }
+
+    // Every character for which `f` is true.
+    fn matching(f: fn(char) -> bool) -> CharSet {
+        CharSet::from_chars((0..=MAX).filter_map(char::from_u32).filter(|&c| f(c)))
+    }
+
+    fn full() -> CharSet {
+        CharSet { ranges: vec![(0, SURROGATES.0 - 1), (SURROGATES.1 + 1, MAX)] }
+    }
+
+    fn contains(&self, c: u32) -> bool {
+        self.ranges.binary_search_by(|&(lo, hi)| {
+            if hi < c {
+                Ordering::Less
+            } else if lo > c {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        }).is_ok()
+    }
+
+    /// The code points `c` for which `f(self.contains(c), other.contains(c))` is true.
+    fn combine(&self, other: &CharSet, f: fn(bool, bool) -> bool) -> CharSet {
+        // Membership only changes where a range of either set starts or ends
+        let mut bounds: Vec<u32> = self.ranges.iter().chain(&other.ranges).flat_map(|&(lo, hi)| [lo, hi + 1]).collect();
+        bounds.sort_unstable();
+        bounds.dedup();
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for w in bounds.windows(2) {
+            if f(self.contains(w[0]), other.contains(w[0])) {
+                match ranges.last_mut() {
+                    Some(r) if r.1 + 1 == w[0] => r.1 = w[1] - 1,
+                    _ => ranges.push((w[0], w[1] - 1)),
+                }
+            }
+        }
+        CharSet { ranges }
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.ranges.iter().map(|&(lo, hi)| (hi - lo + 1) as usize).sum()
+    }
+
+    /// The characters of the set, in order.
+    pub(crate) fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.ranges.iter().flat_map(|&(lo, hi)| lo..=hi).filter_map(char::from_u32)
+    }
+}
+
+/// The names of the standard sets, which are the first entries of every char-set table.
+pub(crate) const STANDARD_NAMES: [&str; 10] = [
+    "char-set:empty",
+    "char-set:full",
+    "char-set:letter",
+    "char-set:digit",
+    "char-set:letter+digit",
+    "char-set:whitespace",
+    "char-set:upper-case",
+    "char-set:lower-case",
+    "char-set:ascii",
+    "char-set:hex-digit",
+];
+
+static STANDARD: [LazyLock<CharSet>; 10] = [
+    LazyLock::new(CharSet::default),
+    LazyLock::new(CharSet::full),
+    LazyLock::new(|| CharSet::matching(char::is_alphabetic)),
+    LazyLock::new(|| CharSet::matching(char::is_numeric)),
+    LazyLock::new(|| CharSet::matching(char::is_alphanumeric)),
+    LazyLock::new(|| CharSet::matching(char::is_whitespace)),
+    LazyLock::new(|| CharSet::matching(char::is_uppercase)),
+    LazyLock::new(|| CharSet::matching(char::is_lowercase)),
+    LazyLock::new(|| CharSet { ranges: vec![(0, 0x7F)] }),
+    LazyLock::new(|| CharSet::from_chars("0123456789abcdefABCDEF".chars())),
+];
+
+/// The char-set `v` is, if it is one.
+pub(crate) fn get(vm: &VM, v: Value) -> Option<&CharSet> {
+    if !v.is_char_set() {
+        return None;
+    }
+    match v.to_char_set().checked_sub(STANDARD.len()) {
+        None => Some(&*STANDARD[v.to_char_set()]),
+        Some(i) => vm.char_sets.get(i),
+    }
+}
+
+fn char_set_arg<'a>(vm: &'a VM, name: &str, v: Value) -> Result<&'a CharSet, String> {
+    get(vm, v).ok_or_else(|| format!("{}: {} is not a char-set", name, v))
+}
+
+fn char_arg(name: &str, v: Value) -> Result<char, String> {
+    if v.is_char() {
+        Ok(v.to_char())
+    } else {
+        Err(format!("{}: {} is not a character", name, v))
+    }
+}
+
+fn new_char_set(vm: &mut VM, set: CharSet) -> Value {
+    vm.char_sets.push(set);
+    Value::CharSet((STANDARD.len() + vm.char_sets.len() - 1) as u32)
+}
+
+/// `(char-set char ...)` A char-set of the arguments.
+pub fn char_set(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let chars = args.iter().map(|&c| char_arg("char-set", c)).collect::<Result<Vec<_>, _>>()?;
+    Ok(new_char_set(vm, CharSet::from_chars(chars)))
+}
+
+/// `(string->char-set string)` A char-set of the characters of `string`.
+pub fn string_to_char_set(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !args[0].is_string() {
+        return Err(format!("string->char-set: {} is not a string", args[0]));
+    }
+    let s = args[0].to_string();
+    let set = CharSet::from_chars(s.str.chars());
+    Box::into_raw(s);
+    Ok(new_char_set(vm, set))
+}
+
+/// `(list->char-set list)` A char-set of the characters in `list`.
+pub fn list_to_char_set(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let mut chars = Vec::new();
+    let mut l = args[0];
+    while l.is_pair() {
+        chars.push(char_arg("list->char-set", l.car())?);
+        l = l.cdr();
+    }
+    if !l.is_nil() {
+        return Err(format!("list->char-set: {} is not a proper list", args[0]));
+    }
+    Ok(new_char_set(vm, CharSet::from_chars(chars)))
+}
+
+/// `(ucs-range->char-set start end)` A char-set of the characters whose code points are from
+/// `start` up to, but not including, `end`.
+pub fn ucs_range_to_char_set(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let bound = |v: Value| {
+        if v.is_integer() && 0 <= v.to_integer() && v.to_integer() as u32 <= MAX + 1 {
+            Ok(v.to_integer() as u32)
+        } else {
+            Err(format!("ucs-range->char-set: {} is not a code point", v))
+        }
+    };
+    let (start, end) = (bound(args[0])?, bound(args[1])?);
+    let range = if start < end {
+        CharSet { ranges: vec![(start, end - 1)] }
+    } else {
+        CharSet::default()
+    };
+    let set = range.combine(&CharSet::full(), |a, b| a && b);
+    Ok(new_char_set(vm, set))
+}
+
+/// `(char-set? obj)`
+pub fn is_char_set(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(get(vm, args[0]).is_some()))
+}
+
+/// `(char-set-contains? char-set char)`
+pub fn char_set_contains(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let set = char_set_arg(vm, "char-set-contains?", args[0])?;
+    let c = char_arg("char-set-contains?", args[1])?;
+    Ok(Value::Bool(set.contains(c as u32)))
+}
+
+/// `(char-set-size char-set)` The number of characters in `char-set`.
+pub fn char_set_size(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let set = char_set_arg(vm, "char-set-size", args[0])?;
+    Ok(Value::Integer(set.size() as i32))
+}
+
+/// `(char-set->list char-set)` The characters of `char-set`, in order.
+pub fn char_set_to_list(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let set = char_set_arg(vm, "char-set->list", args[0])?;
+    let chars: Vec<char> = set.chars().collect();
+    Ok(chars.into_iter().rev().fold(Value::Nil, |l, c| Value::Pair(Value::Char(c), l)))
+}
+
+// Combine `first` with each of `rest` in turn.
+fn combine_all(vm: &mut VM, name: &str, first: CharSet, rest: &[Value], f: fn(bool, bool) -> bool)
+    -> Result<Value, String>
+{
+    let mut set = first;
+    for &v in rest {
+        set = set.combine(char_set_arg(vm, name, v)?, f);
+    }
+    Ok(new_char_set(vm, set))
+}
+
+/// `(char-set-union char-set ...)` The characters in any of the char-sets.
+pub fn char_set_union(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    combine_all(vm, "char-set-union", CharSet::default(), args, |a, b| a || b)
+}
+
+/// `(char-set-intersection char-set1 char-set ...)` The characters in every char-set.
+pub fn char_set_intersection(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let first = char_set_arg(vm, "char-set-intersection", args[0])?.clone();
+    combine_all(vm, "char-set-intersection", first, &args[1..], |a, b| a && b)
+}
+
+/// `(char-set-difference char-set1 char-set ...)` The characters in `char-set1` and in none of
+/// the rest.
+pub fn char_set_difference(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let first = char_set_arg(vm, "char-set-difference", args[0])?.clone();
+    combine_all(vm, "char-set-difference", first, &args[1..], |a, b| a && !b)
+}
+
+/// `(char-set-complement char-set)` Every character not in `char-set`.
+pub fn char_set_complement(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let set = char_set_arg(vm, "char-set-complement", args[0])?;
+    let complement = CharSet::full().combine(set, |a, b| a && !b);
+    Ok(new_char_set(vm, complement))
+}
@@ -0,0 +1,52 @@
+//! Compression primitives, enabled by the `compress` feature.
+
+use {Value, VM};
+use bytevector::bytes_arg;
+
+use flate2::Compression;
+use flate2::read::{DeflateDecoder, DeflateEncoder, GzDecoder, GzEncoder};
+
+use std::io::Read;
+
+/// The optional compression level, 0 (none) through 9 (best).
+fn level(name: &str, v: Option<&Value>) -> Result<Compression, String> {
+    match v {
+        None => Ok(Compression::default()),
+        Some(v) if v.is_integer() && (0..=9).contains(&v.to_integer()) => {
+            Ok(Compression::new(v.to_integer() as u32))
+        }
+        Some(v) => Err(format!("{}: {} is not a compression level between 0 and 9", name, v)),
+    }
+}
+
+fn read_all<R: Read>(name: &str, mut r: R) -> Result<Value, String> {
+    let mut out = Vec::new();
+    r.read_to_end(&mut out).map_err(|e| format!("{}: {}", name, e))?;
+    Ok(Value::Bytevector(out))
+}
+
+/// `(gzip-compress data [level])` Compress a bytevector or string into the gzip format.
+pub fn gzip_compress(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let data = bytes_arg("gzip-compress", args[0])?;
+    let level = level("gzip-compress", args.get(1))?;
+    read_all("gzip-compress", GzEncoder::new(&data[..], level))
+}
+
+/// `(gzip-decompress bytevector)`
+pub fn gzip_decompress(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let data = bytes_arg("gzip-decompress", args[0])?;
+    read_all("gzip-decompress", GzDecoder::new(&data[..]))
+}
+
+/// `(deflate-compress data [level])` Compress a bytevector or string into a raw deflate stream.
+pub fn deflate_compress(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let data = bytes_arg("deflate-compress", args[0])?;
+    let level = level("deflate-compress", args.get(1))?;
+    read_all("deflate-compress", DeflateEncoder::new(&data[..], level))
+}
+
+/// `(deflate-decompress bytevector)`
+pub fn deflate_decompress(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let data = bytes_arg("deflate-decompress", args[0])?;
+    read_all("deflate-decompress", DeflateDecoder::new(&data[..]))
+}
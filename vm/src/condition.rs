@@ -0,0 +1,219 @@
+//! Conditions signalled by the machine, and the restarts which go on from them.
+//!
+//! The embedder handles conditions with `VM::set_condition_handler`, and Scheme code with
+//! `(with-restart-handler handler thunk)`: a condition signalled while `thunk` runs calls
+//! `(handler kind message)` where it was signalled, where `kind` is a symbol such as
+//! `unbound-variable`. The handler goes on with `(invoke-restart 'retry)` or
+//! `(invoke-restart 'use-value value)` if the condition allows it. If it returns instead, `thunk`
+//! is abandoned and the result of `with-restart-handler` is what the handler returned. A handler
+//! which fails leaves the condition to the handlers outside it.
+
+use {Environment, Operation, Register, Value, VmError, VM};
+
+use string_interner::get_value;
+
+use std::{fmt, mem};
+use std::sync::Arc;
+
+/// A restart which can be invoked to resume from a `Condition`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Restart {
+    /// Discard the suspended computation and return to the top level.
+    Abort,
+    /// Execute the failing instruction again.
+    Retry,
+    /// Continue as if the failing instruction had produced the given value.
+    UseValue(Value),
+}
+
+impl fmt::Display for Restart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Restart::Abort => write!(f, "[ABORT] Return to top level"),
+            Restart::Retry => write!(f, "[RETRY] Retry the failing operation"),
+            Restart::UseValue(_) => write!(f, "[USE-VALUE] Supply a value to use instead"),
+        }
+    }
+}
+
+/// An error signalled by the machine. If no handler chooses a restart the state of the machine at
+/// the point of the error is kept with the condition so that it can be resumed later.
+#[derive(Debug)]
+pub struct Condition {
+    error: VmError,
+    op: Operation,
+    pub(crate) state: Option<MachineState>,
+}
+
+impl Condition {
+    pub(crate) fn new(error: VmError, op: Operation) -> Self {
+        Condition {
+            error: error,
+            op: op,
+            state: None,
+        }
+    }
+
+    /// The restarts applicable to this condition. `UseValue` is listed with a placeholder of
+    /// `Void`, the real value is given when the restart is invoked.
+    pub fn restarts(&self) -> Vec<Restart> {
+        let mut restarts = vec![Restart::Abort];
+        if self.can_retry() {
+            restarts.push(Restart::Retry);
+        }
+        if self.can_use_value() {
+            restarts.push(Restart::UseValue(Value::Void));
+        }
+        restarts
+    }
+
+    /// Whether retrying the failing instruction could succeed, e.g. after the missing variable has
+    /// been defined or enough of the heap has been let go of. An interrupted computation, or one
+    /// which ran out of fuel, is continued by retrying.
+    pub fn can_retry(&self) -> bool {
+        matches!(self.error,
+                 VmError::Undefined(_) | VmError::Interrupt(_) | VmError::OutOfFuel | VmError::OutOfMemory(..))
+    }
+
+    /// Whether the computation was interrupted because the fuel given to `VM::set_fuel` ran out.
+    pub fn is_out_of_fuel(&self) -> bool {
+        matches!(self.error, VmError::OutOfFuel)
+    }
+
+    /// The kind of error, which a handler installed by `with-restart-handler` is given as a symbol.
+    pub fn kind(&self) -> &'static str {
+        match self.error {
+            VmError::Undefined(_) => "unbound-variable",
+            VmError::NonProcedure(_) => "non-procedure",
+            VmError::User(_) | VmError::Throw => "error",
+            VmError::Interrupt(_) => "interrupt",
+            VmError::OutOfFuel => "out-of-fuel",
+            VmError::OutOfMemory(..) => "out-of-memory",
+        }
+    }
+
+    /// Whether the heap was over the limit given to `VM::set_process_heap_limit`.
+    pub fn is_out_of_memory(&self) -> bool {
+        matches!(self.error, VmError::OutOfMemory(..))
+    }
+
+    /// Whether the failing instruction produces a value which can be substituted.
+    pub fn can_use_value(&self) -> bool {
+        matches!(self.error, VmError::Undefined(_) | VmError::NonProcedure(_))
+    }
+
+    /// The register the failing instruction would have written its result to.
+    pub(crate) fn target(&self) -> Register {
+        match self.error {
+            VmError::Undefined(_) => self.op.lookup_register(),
+            _ => Register(0),
+        }
+    }
+
+    /// The message of the error, as a native procedure would report it.
+    pub(crate) fn message(&self) -> String {
+        self.error.message()
+    }
+
+    pub(crate) fn op(&self) -> Operation {
+        self.op
+    }
+
+    pub(crate) fn mark(&self) {
+        if let Some(ref state) = self.state {
+            state.mark();
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+/// A snapshot of everything needed to resume a suspended computation.
+#[derive(Clone, Debug)]
+pub(crate) struct MachineState {
+    pub pc: usize,
+    pub operations: Arc<[Operation]>,
+    pub constants: Arc<[Value]>,
+    pub environment: Environment,
+    pub stack: Vec<Value>,
+    pub kontinue: usize,
+    pub kontinue_stack: Vec<usize>,
+    pub registers: [Value; 32],
+    pub saved_state: Vec<::SaveState>,
+}
+
+impl MachineState {
+    pub(crate) fn mark(&self) {
+        for v in &self.registers {
+            v.mark();
+        }
+        for v in &self.stack {
+            v.mark();
+        }
+        for v in self.constants.iter() {
+            v.mark();
+        }
+        self.environment.mark();
+        for s in &self.saved_state {
+            s.mark();
+        }
+    }
+}
+
+/// A handler which may choose a restart for a condition instead of suspending the machine.
+pub(crate) struct ConditionHandler(pub Box<dyn FnMut(&Condition) -> Option<Restart>>);
+
+impl fmt::Debug for ConditionHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<ConditionHandler>")
+    }
+}
+
+/// `(with-restart-handler handler thunk)` Call `thunk`, calling `(handler kind message)` for the
+/// conditions signalled while it runs.
+pub fn with_restart_handler(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    for &v in args {
+        if !v.is_procedure() {
+            return Err(format!("with-restart-handler: {} is not a procedure", v));
+        }
+    }
+    let index = vm.restart_handlers.len();
+    vm.restart_handlers.push(args[0]);
+    let result = vm.apply(args[1], &[]);
+    vm.restart_handlers.truncate(index);
+    let caught = match vm.caught {
+        Some((i, _)) if i >= index => vm.caught.take(),
+        _ => None,
+    };
+    match (result, caught) {
+        (Err(_), Some((i, v))) if i == index => Ok(v),
+        (result, _) => result,
+    }
+}
+
+/// `(invoke-restart 'retry)` or `(invoke-restart 'use-value value)` Leave the handler and go on
+/// from the condition it was called for, by running the failing instruction again or by
+/// continuing as if it had produced `value`.
+pub fn invoke_restart(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let restarts = match vm.handling.last() {
+        Some(restarts) => restarts,
+        None => return Err("invoke-restart: no condition is being handled".to_string()),
+    };
+    let name = if args[0].is_symbol() { get_value(args[0].to_symbol()) } else { None };
+    let restart = match (name.as_deref(), args.get(1)) {
+        (Some("retry"), None) => Restart::Retry,
+        (Some("use-value"), Some(&v)) => Restart::UseValue(v),
+        _ => return Err(format!("invoke-restart: {} is not a restart, expected retry or use-value and a value",
+                               args[0])),
+    };
+    if !restarts.iter().any(|r| mem::discriminant(r) == mem::discriminant(&restart)) {
+        return Err(format!("invoke-restart: {} is not applicable to the condition", args[0]));
+    }
+    vm.invoked_restart = Some(restart);
+    // Failing abandons the handler, and the machine goes on with the restart once it has left it
+    Err("invoke-restart: left the handler".to_string())
+}
@@ -0,0 +1,68 @@
+//! Parsers for configuration formats, enabled by the `config` feature.
+//!
+//! Tables become hash tables keyed by symbols and arrays become vectors, so a script can walk a
+//! document with `hash-table-ref` and `vector-ref`.
+
+use {Value, VM};
+use port::read_to_string;
+
+use toml;
+use yaml_rust::{Yaml, YamlLoader};
+
+use std::collections::HashMap;
+
+fn key(k: &str) -> Value {
+    Value::Symbol(VM::intern_symbol(k.to_string()))
+}
+
+fn from_toml(v: toml::Value) -> Value {
+    match v {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) if i >= i32::MIN as i64 && i <= i32::MAX as i64 => Value::Integer(i as i32),
+        toml::Value::Integer(i) => Value::Float(i as f64),
+        toml::Value::Float(f) => Value::Float(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Vec(a.into_iter().map(from_toml).collect()),
+        toml::Value::Table(t) => {
+            Value::HashMap(t.into_iter().map(|(k, v)| (key(&k), from_toml(v))).collect())
+        }
+    }
+}
+
+fn from_yaml(v: Yaml) -> Value {
+    match v {
+        Yaml::String(s) => Value::String(s),
+        Yaml::Integer(i) if i >= i32::MIN as i64 && i <= i32::MAX as i64 => Value::Integer(i as i32),
+        Yaml::Integer(i) => Value::Float(i as f64),
+        Yaml::Real(f) => Value::Float(f.parse().unwrap_or(std::f64::NAN)),
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Array(a) => Value::Vec(a.into_iter().map(from_yaml).collect()),
+        Yaml::Hash(h) => {
+            let mut m = HashMap::new();
+            for (k, v) in h {
+                let k = match k {
+                    Yaml::String(s) => key(&s),
+                    k => from_yaml(k),
+                };
+                m.insert(k, from_yaml(v));
+            }
+            Value::HashMap(m)
+        }
+        Yaml::Null | Yaml::Alias(_) | Yaml::BadValue => Value::Nil,
+    }
+}
+
+/// `(toml-read [port])` Parse the rest of `port` as a TOML document.
+pub fn toml_read(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let s = read_to_string(vm, "toml-read", args.first())?;
+    let table: toml::Table = s.parse().map_err(|e| format!("toml-read: {}", e))?;
+    Ok(from_toml(toml::Value::Table(table)))
+}
+
+/// `(yaml-read [port])` Parse the rest of `port` as a YAML document. Null is `()`.
+pub fn yaml_read(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let s = read_to_string(vm, "yaml-read", args.first())?;
+    let docs = YamlLoader::load_from_str(&s).map_err(|e| format!("yaml-read: {}", e))?;
+    Ok(docs.into_iter().next().map(from_yaml).unwrap_or(Value::Nil))
+}
@@ -0,0 +1,45 @@
+//! First-class continuations, made by `call-with-current-continuation`.
+//!
+//! A continuation is a copy of the state of the machine when it was captured: the registers, the
+//! stack, and the saved frames of the procedures waiting for a value. Invoking it with a value
+//! replaces the running computation with a copy of that state, with the value as the result of
+//! the call which captured it. It can be invoked any number of times, including after the
+//! procedure which captured it has returned. Environments are shared rather than copied, so a
+//! resumed continuation sees later changes to variables.
+//!
+//! Native procedures which call procedures, like `for-each`, run them in a nested computation. A
+//! continuation captured outside one can be invoked inside it, which abandons the nested
+//! computation and the native procedure. One captured inside can only be invoked while that native
+//! procedure is still running, since there is no way back into a native which has returned.
+//!
+//! A continuation value is an index into the continuation table of the machine. The machine keeps
+//! a continuation for as long as it can be reached, a collection frees the rest so that capturing
+//! one on every iteration of a loop doesn't grow the table.
+
+use condition::MachineState;
+use native;
+use {NativeFn, Value, VM};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Continuation {
+    pub(crate) state: MachineState,
+    // The nested computations running when it was captured, outermost first
+    pub(crate) applications: Vec<usize>,
+}
+
+/// `(call-with-current-continuation proc)` Call `proc` with the continuation of the call. Calls
+/// made by the machine are handled by the machine itself, this is only reached when a native
+/// procedure applies it.
+pub fn call_cc(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Err("call-with-current-continuation: can't be applied by a native procedure".to_string())
+}
+
+/// Whether `v` is `call-with-current-continuation`, which the machine calls itself.
+pub(crate) fn is_call_cc(v: Value) -> bool {
+    v.is_native() && native::get_native(v.to_native()).f as usize == call_cc as NativeFn as usize
+}
+
+/// `(continuation? obj)`
+pub fn is_continuation(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(args[0].is_continuation()))
+}
@@ -0,0 +1,211 @@
+//! Conversions between values and Rust types, for hosts and native procedures.
+//!
+//! `IntoValue` makes a value from a Rust value, allocating it on the heap if it needs to be, and
+//! `FromValue` does the reverse, failing with a message such as `"x is not a string"` when the value
+//! has another type:
+//!
+//! ```
+//! use vm::{FromValue, IntoValue};
+//!
+//! let v = vec![1i64, 2, 3].into_value();
+//! assert_eq!("(1 2 3)", format!("{}", v));
+//! assert_eq!(Ok(vec![1i64, 2, 3]), Vec::from_value(v));
+//! assert!(String::from_value(v).is_err());
+//! ```
+//!
+//! Integers are fixnums when they fit and bignums otherwise. A `Vec` is a list, and a `HashMap` is
+//! a hash table, whose keys are compared with `eq?` like those of any other. `None` is `#f`, so a
+//! procedure returning an `Option` returns false when it has nothing to return. Values made by
+//! `into_value` are garbage like any other, so they must be rooted or returned to the machine
+//! before it next collects.
+
+use Value;
+
+use num_bigint::BigInt;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+/// A Rust value which can be made into a value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// A Rust value which can be made from a value.
+pub trait FromValue: Sized {
+    /// The Rust value of `v`, or why it has none.
+    fn from_value(v: Value) -> Result<Self, String>;
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(v: Value) -> Result<Self, String> {
+        Ok(v)
+    }
+}
+
+impl IntoValue for i32 {
+    fn into_value(self) -> Value {
+        Value::Integer(self)
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(v: Value) -> Result<Self, String> {
+        if v.is_integer() {
+            Ok(v.to_integer())
+        } else if v.is_bigint() {
+            Err(format!("{} is out of range", v))
+        } else {
+            Err(format!("{} is not an integer", v))
+        }
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        match i32::try_from(self) {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::BigInt(BigInt::from(self)),
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(v: Value) -> Result<Self, String> {
+        if v.is_bigint() {
+            let b = v.to_bigint();
+            let n = i64::try_from(&b.n);
+            Box::into_raw(b);
+            n.map_err(|_| format!("{} is out of range", v))
+        } else {
+            i32::from_value(v).map(i64::from)
+        }
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl FromValue for f64 {
+    /// Exact integers are converted too, as `exact->inexact` would.
+    fn from_value(v: Value) -> Result<Self, String> {
+        if v.is_float() {
+            Ok(v.to_float())
+        } else if v.is_integer() {
+            Ok(f64::from(v.to_integer()))
+        } else {
+            Err(format!("{} is not a number", v))
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(v: Value) -> Result<Self, String> {
+        if v.is_bool() {
+            Ok(v.is_true())
+        } else {
+            Err(format!("{} is not a boolean", v))
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl<'a> IntoValue for &'a str {
+    fn into_value(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(v: Value) -> Result<Self, String> {
+        if v.is_string() {
+            let s = v.to_string();
+            let r = s.str.clone();
+            Box::into_raw(s);
+            Ok(r)
+        } else {
+            Err(format!("{} is not a string", v))
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        let values: Vec<Value> = self.into_iter().map(IntoValue::into_value).collect();
+        values.into_iter().rev().fold(Value::Nil, |l, v| Value::Pair(v, l))
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(v: Value) -> Result<Self, String> {
+        let mut elements = Vec::new();
+        let mut l = v;
+        while l.is_pair() {
+            elements.push(T::from_value(l.car())?);
+            l = l.cdr();
+        }
+        if l.is_nil() {
+            Ok(elements)
+        } else {
+            Err(format!("{} is not a list", v))
+        }
+    }
+}
+
+impl<K: IntoValue, V: IntoValue> IntoValue for HashMap<K, V> {
+    fn into_value(self) -> Value {
+        Value::HashMap(self.into_iter().map(|(k, v)| (k.into_value(), v.into_value())).collect())
+    }
+}
+
+impl<K: FromValue + Eq + Hash, V: FromValue> FromValue for HashMap<K, V> {
+    fn from_value(v: Value) -> Result<Self, String> {
+        if !v.is_hashmap() {
+            return Err(format!("{} is not a hash table", v));
+        }
+        let m = v.to_hashmap();
+        let entries: Vec<(Value, Value)> = m.map.iter().map(|(&k, &v)| (k, v)).collect();
+        Box::into_raw(m);
+        entries.into_iter().map(|(k, v)| Ok((K::from_value(k)?, V::from_value(v)?))).collect()
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(v) => v.into_value(),
+            None => Value::False,
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(v: Value) -> Result<Self, String> {
+        if v.is_false() {
+            Ok(None)
+        } else {
+            T::from_value(v).map(Some)
+        }
+    }
+}
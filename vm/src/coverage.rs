@@ -0,0 +1,66 @@
+//! Coverage of Scheme code.
+//!
+//! Code compiled for coverage calls the procedure returned by `coverage_probe` with the index of a
+//! probe whenever it reaches one, such as the body of a procedure or a branch of an `if`. The
+//! machine counts the hits of each probe, and `coverage-report` lists them like an annotated
+//! source file from gcov, with the probes which were never reached marked `#####`.
+
+use {register_native, Arity, Value, VM};
+
+use std::fmt::Write;
+
+/// A point in instrumented code, and the number of times it was reached.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Probe {
+    /// The code the probe is in, e.g. `fact: (if (= n 0) ...) then`.
+    pub description: String,
+    pub hits: usize,
+}
+
+/// The procedure instrumented code calls with the index of each probe it reaches.
+pub fn coverage_probe() -> Value {
+    register_native("coverage-probe", Arity::Exactly(1), hit)
+}
+
+fn hit(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let probe = if args[0].is_integer() && args[0].to_integer() >= 0 {
+        vm.probes.get_mut(args[0].to_integer() as usize)
+    } else {
+        None
+    };
+    match probe {
+        Some(p) => {
+            p.hits += 1;
+            Ok(Value::Void)
+        }
+        None => Err(format!("coverage-probe: {} is not a probe", args[0])),
+    }
+}
+
+impl VM {
+    /// Add a probe to be reached by instrumented code, returning its index.
+    pub fn add_probe(&mut self, description: String) -> usize {
+        self.probes.push(Probe { description, hits: 0 });
+        self.probes.len() - 1
+    }
+
+    /// Every probe added, in order.
+    pub fn probes(&self) -> &[Probe] {
+        &self.probes
+    }
+}
+
+/// `(coverage-report)` A string listing every probe with the number of times it was reached,
+/// after a summary of how many were reached at all.
+pub fn coverage_report(vm: &mut VM, _: &[Value]) -> Result<Value, String> {
+    let reached = vm.probes.iter().filter(|p| p.hits > 0).count();
+    let mut report = match vm.probes.len() {
+        0 => "; no code was compiled for coverage\n".to_string(),
+        n => format!("; {} of {} probes reached ({:.1}%)\n", reached, n, 100.0 * reached as f64 / n as f64),
+    };
+    for p in &vm.probes {
+        let hits = if p.hits == 0 { "#####".to_string() } else { p.hits.to_string() };
+        writeln!(report, "{:>9}: {}", hits, p.description).unwrap();
+    }
+    Ok(Value::String(report))
+}
@@ -0,0 +1,60 @@
+//! Cryptographic hash primitives, enabled by the `crypto` feature. Digests are returned as
+//! lowercase hexadecimal strings.
+
+use {Value, VM};
+use bytevector::bytes_arg;
+use value_hash::encode;
+
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use std::fmt::Write;
+
+fn hex(digest: &[u8]) -> Value {
+    let mut s = String::with_capacity(digest.len() * 2);
+    for b in digest {
+        write!(s, "{:02x}", b).unwrap();
+    }
+    Value::String(s)
+}
+
+/// `(md5 data)` The MD5 digest of a bytevector or string.
+pub fn md5(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(hex(&Md5::digest(bytes_arg("md5", args[0])?)))
+}
+
+/// `(sha1 data)` The SHA-1 digest of a bytevector or string.
+pub fn sha1(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(hex(&Sha1::digest(bytes_arg("sha1", args[0])?)))
+}
+
+/// `(sha256 data)` The SHA-256 digest of a bytevector or string.
+pub fn sha256(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(hex(&Sha256::digest(bytes_arg("sha256", args[0])?)))
+}
+
+/// `(hmac-sha256 key data)` The HMAC of `data` using SHA-256.
+pub fn hmac_sha256(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let key = bytes_arg("hmac-sha256", args[0])?;
+    let data = bytes_arg("hmac-sha256", args[1])?;
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).unwrap();
+    mac.update(&data);
+    Ok(hex(&mac.finalize().into_bytes()))
+}
+
+/// The SHA-256 digest of the contents of `v`, or the first value reachable from it which can't be
+/// hashed. See `value_hash` for what is hashed.
+pub fn value_digest(v: Value) -> Result<[u8; 32], Value> {
+    Ok(Sha256::digest(encode(v)?).into())
+}
+
+/// `(value-digest obj)` The SHA-256 digest of the contents of `obj`.
+pub fn value_digest_native(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    match value_digest(args[0]) {
+        Ok(digest) => Ok(hex(&digest)),
+        Err(v) => Err(format!("value-digest: {} can't be hashed", v)),
+    }
+}
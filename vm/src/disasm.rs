@@ -0,0 +1,174 @@
+//! A readable listing of assembled code, for seeing what the compiler produced.
+//!
+//! Each instruction is printed with its index. Instructions which are jumped to are preceded by a
+//! label, `L0`, `L1`..., numbered in order, and jumps, `LOADCONTINUE` and switch tables refer to
+//! them by label. The constants used by an instruction are shown in a comment beside it. The
+//! constant pool follows, then the listing of each procedure made by a `MAKECLOSURE`.
+
+use {Instruction, Operation, Value, VM};
+use inspect::short;
+use port::write_bytes;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// Comments start in this column of an instruction line
+const COMMENT: usize = 32;
+
+/// A listing of `code` and its constant pool `consts`.
+pub fn listing(code: &[Operation], consts: &[Value]) -> String {
+    let mut out = String::new();
+    write_listing(&mut out, "", code, consts);
+    out
+}
+
+fn write_listing(o: &mut String, indent: &str, code: &[Operation], consts: &[Value]) {
+    let labels: BTreeMap<usize, String> = targets(code, consts).into_iter()
+        .enumerate()
+        .map(|(i, p)| (p, format!("L{}", i)))
+        .collect();
+    let label = |p: usize| labels.get(&p).cloned().unwrap_or_else(|| p.to_string());
+
+    for (i, &op) in code.iter().enumerate() {
+        if let Some(l) = labels.get(&i) {
+            writeln!(o, "{}{}:", indent, l).unwrap();
+        }
+        let (text, comment) = instruction(op, consts, &label);
+        match comment {
+            Some(c) => writeln!(o, "{}{:5}  {:<width$}; {}", indent, i, text, c, width = COMMENT).unwrap(),
+            None => writeln!(o, "{}{:5}  {}", indent, i, text).unwrap(),
+        }
+    }
+    // A jump past the last instruction returns
+    if let Some(l) = labels.get(&code.len()) {
+        writeln!(o, "{}{}:", indent, l).unwrap();
+    }
+
+    if !consts.is_empty() {
+        writeln!(o, "{}constants:", indent).unwrap();
+        for (i, &c) in consts.iter().enumerate() {
+            writeln!(o, "{}  #{}: {}", indent, i, short(c)).unwrap();
+        }
+    }
+
+    for (i, &c) in consts.iter().enumerate() {
+        if c.is_lambda() {
+            writeln!(o, "{}procedure #{}:", indent, i).unwrap();
+            let l = c.to_lambda();
+            write_listing(o, &format!("{}  ", indent), &l.code, &l.consts);
+            Box::into_raw(l);
+        }
+    }
+}
+
+/// The text of `op` with its jump targets named by `label`, and a comment on its constants.
+fn instruction(op: Operation, consts: &[Value], label: &dyn Fn(usize) -> String) -> (String, Option<String>) {
+    let goto = |p: Option<usize>| p.map_or("CONTINUE".to_string(), label);
+    let constant = |i: usize| consts.get(i).map(|&c| short(c));
+    match op.instruction() {
+        Instruction::LoadContinue => (format!("LOADCONTINUE {}", label(op.loadcontinue_label())), None),
+        Instruction::Goto => (format!("GOTO {}", goto(op.goto_value())), None),
+        Instruction::GotoIf => (format!("GOTOIF {}, {}", goto(op.gotoif_value()), op.gotoif_register()), None),
+        Instruction::GotoIfNot => {
+            (format!("GOTOIFNOT {}, {}", goto(op.gotoifnot_value()), op.gotoifnot_register()), None)
+        }
+        Instruction::LoadConst => {
+            let c = op.loadconst_constant();
+            (format!("LOADCONST {}, #{}", op.loadconst_register(), c), constant(c))
+        }
+        Instruction::MakeClosure => {
+            let c = op.makeclosure_constant();
+            (format!("MAKECLOSURE {}, #{}", op.makeclosure_register(), c), Some(format!("procedure #{}", c)))
+        }
+        Instruction::JumpTable | Instruction::BinarySearch | Instruction::PerfectHash => {
+            let cases = table(op, consts).map(|(cases, default)| {
+                let mut s = String::new();
+                for (key, p) in cases {
+                    write!(s, "{} => {}, ", key, label(p)).unwrap();
+                }
+                write!(s, "else => {}", label(default)).unwrap();
+                s
+            });
+            (format!("{}", op), cases)
+        }
+        _ => (format!("{}", op), None),
+    }
+}
+
+/// The cases of the switch table used by `op`, as keys and targets, and its default target.
+fn table(op: Operation, consts: &[Value]) -> Option<(Vec<(String, usize)>, usize)> {
+    let c = match op.instruction() {
+        Instruction::JumpTable => op.jumptable_table(),
+        Instruction::BinarySearch => op.bsearch_table(),
+        Instruction::PerfectHash => op.phash_table(),
+        _ => return None,
+    };
+    let table = match consts.get(c) {
+        Some(t) if t.is_vec() => t.to_vec(),
+        _ => return None,
+    };
+    let target = |v: Value| if v.is_integer() { Some(v.to_integer() as usize) } else { None };
+
+    let t = &table.vec;
+    let cases = match op.instruction() {
+        // `[min, default, targets...]`
+        Instruction::JumpTable if t.len() >= 2 && t[0].is_integer() => {
+            let min = t[0].to_integer();
+            let cases = t[2..].iter().enumerate()
+                .map(|(i, &p)| Some(((min + i as i32).to_string(), target(p)?)))
+                .collect::<Option<_>>();
+            cases.zip(target(t[1]))
+        }
+        // `[default, key, target, key, target...]`
+        Instruction::BinarySearch if !t.is_empty() => {
+            let cases = t[1..].chunks(2)
+                .map(|e| Some((short(e[0]), target(*e.get(1)?)?)))
+                .collect::<Option<_>>();
+            cases.zip(target(t[0]))
+        }
+        // `[default, multiplier, key, target, key, target...]` with `Void` in empty slots
+        Instruction::PerfectHash if t.len() >= 2 => {
+            let cases = t[2..].chunks(2)
+                .filter(|e| e[0].is_symbol())
+                .map(|e| Some((short(e[0]), target(*e.get(1)?)?)))
+                .collect::<Option<_>>();
+            cases.zip(target(t[0]))
+        }
+        _ => None,
+    };
+    Box::into_raw(table);
+    cases
+}
+
+/// Every index of `code` which is jumped to, in order.
+fn targets(code: &[Operation], consts: &[Value]) -> Vec<usize> {
+    let mut targets = Vec::new();
+    for &op in code {
+        let p = match op.instruction() {
+            Instruction::LoadContinue => Some(op.loadcontinue_label()),
+            Instruction::Goto => op.goto_value(),
+            Instruction::GotoIf => op.gotoif_value(),
+            Instruction::GotoIfNot => op.gotoifnot_value(),
+            _ => None,
+        };
+        targets.extend(p);
+        if let Some((cases, default)) = table(op, consts) {
+            targets.extend(cases.into_iter().map(|(_, p)| p));
+            targets.push(default);
+        }
+    }
+    targets.sort_unstable();
+    targets.dedup();
+    targets
+}
+
+/// `(disassemble procedure [port])` Print a listing of the code compiled for `procedure`.
+pub fn disassemble(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !args[0].is_lambda() {
+        return Err(format!("disassemble: {} is not a compiled procedure", args[0]));
+    }
+    let l = args[0].to_lambda();
+    let s = listing(&l.code, &l.consts);
+    Box::into_raw(l);
+    write_bytes(vm, "disassemble", args.get(1), s.as_bytes())
+}
@@ -4,7 +4,58 @@ use string_interner::Symbol;
 
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
+
+thread_local! {
+    // The environments machines on this thread have been given. Every machine allocates on the same
+    // heap, so a collection by one must keep the bindings of the others alive.
+    static ROOTS: RefCell<Vec<Weak<RefCell<_Environment>>>> = const { RefCell::new(Vec::new()) };
+    // How many times each symbol has been bound or assigned in any environment, indexed by symbol
+    static VERSIONS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A number which changes whenever `name` is bound or assigned in any environment, so that what
+/// it was found to refer to can be cached until then.
+pub(crate) fn binding_version(name: Symbol) -> u64 {
+    VERSIONS.with(|versions| versions.borrow().get(*name).copied().unwrap_or(0))
+}
+
+fn rebind(name: Symbol) {
+    VERSIONS.with(|versions| {
+        let mut versions = versions.borrow_mut();
+        if versions.len() <= *name {
+            versions.resize(*name + 1, 0);
+        }
+        versions[*name] += 1;
+    })
+}
+
+/// Mark the bindings of every environment given to a machine which is still alive.
+pub(crate) fn mark_roots() {
+    ROOTS.with(|roots| {
+        roots.borrow_mut().retain(|env| match env.upgrade() {
+            Some(env) => {
+                env.borrow().mark();
+                true
+            }
+            None => false,
+        })
+    })
+}
+
+/// How a global environment treats definitions which replace an existing binding and assignments
+/// to unbound variables. Each global environment has its own setting, which its procedure frames
+/// share.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Strictness {
+    /// Redefinition is allowed and assigning an unbound variable defines it.
+    #[default]
+    Permissive,
+    /// Redefinition gives a warning and assigning an unbound variable is an error.
+    Warn,
+    /// Redefinition and assigning an unbound variable are both errors.
+    Strict,
+}
 
 #[derive(Default, PartialEq)]
 pub struct Environment {
@@ -36,6 +87,7 @@ impl Environment {
         let env = _Environment {
             bindings: map,
             parent: None,
+            strictness: Strictness::Permissive,
         };
 
         Environment {
@@ -63,30 +115,112 @@ impl Environment {
         self.env.borrow_mut().set_variable_value(name, value)
     }
 
+    /// Assign `value` to the nearest binding of `name`. Returns `false` without defining anything if
+    /// `name` is not bound.
+    pub fn assign_variable(&self, name: Symbol, value: Value) -> bool {
+        self.env.borrow_mut().assign_variable(name, value)
+    }
+
+    /// Whether `name` is bound in this frame, not including its parents.
+    pub fn is_bound_locally(&self, name: Symbol) -> bool {
+        self.env.borrow().bindings.contains_key(&name)
+    }
+
+    /// The strictness of the global environment this frame belongs to.
+    pub fn strictness(&self) -> Strictness {
+        match self.parent() {
+            Some(parent) => parent.strictness(),
+            None => self.env.borrow().strictness,
+        }
+    }
+
+    /// Set the strictness of the global environment this frame belongs to.
+    pub fn set_strictness(&self, strictness: Strictness) {
+        match self.parent() {
+            Some(parent) => parent.set_strictness(strictness),
+            None => self.env.borrow_mut().strictness = strictness,
+        }
+    }
+
     pub fn procedure_local(&self) -> Self {
         let env = self.env.borrow();
         let local = _Environment {
             bindings: env.bindings.clone(),
             parent: env.parent.clone(),
+            strictness: env.strictness,
         };
         Environment {
             env: Rc::new(RefCell::new(local)),
         }
     }
 
+    /// Like `procedure_local`, but reusing the storage of `frame`, which came from `recycle`.
+    pub(crate) fn procedure_local_in(&self, frame: Environment) -> Self {
+        {
+            let env = self.env.borrow();
+            let mut local = frame.env.borrow_mut();
+            local.bindings.clone_from(&env.bindings);
+            local.parent = env.parent.clone();
+            local.strictness = env.strictness;
+        }
+        frame
+    }
+
+    /// Empty this frame so that it can be reused for another call, if nothing else refers to it.
+    /// A frame which has been captured by a closure, made first-class, or kept by a condition is
+    /// returned as `None`.
+    pub(crate) fn recycle(self) -> Option<Environment> {
+        if Rc::strong_count(&self.env) != 1 || Rc::weak_count(&self.env) != 0 {
+            return None;
+        }
+        {
+            let mut env = self.env.borrow_mut();
+            env.bindings.clear();
+            env.parent = None;
+        }
+        Some(self)
+    }
+
     pub fn get_definitions(&self) -> Vec<Symbol> {
         self.env.borrow().get_definitions()
     }
 
+    /// The bindings made in this frame, not including those of its parents.
+    pub fn local_bindings(&self) -> Vec<(Symbol, Value)> {
+        self.env.borrow().bindings.iter().map(|(&s, &v)| (s, v)).collect()
+    }
+
+    /// The enclosing frame, if this is not the global environment.
+    pub fn parent(&self) -> Option<Environment> {
+        self.env.borrow().parent.clone()
+    }
+
+    /// Whether `self` and `other` are the same frame.
+    pub fn ptr_eq(&self, other: &Environment) -> bool {
+        Rc::ptr_eq(&self.env, &other.env)
+    }
+
     pub(crate) fn mark(&self) {
         self.env.borrow().mark()
     }
+
+    /// Keep the bindings of this environment alive for as long as it exists.
+    pub fn add_root(&self) {
+        ROOTS.with(|roots| {
+            let mut roots = roots.borrow_mut();
+            if !roots.iter().any(|env| env.as_ptr() == Rc::as_ptr(&self.env)) {
+                roots.push(Rc::downgrade(&self.env));
+            }
+        })
+    }
 }
 
 #[derive(Default)]
 pub struct _Environment {
     bindings: HashMap<Symbol, Value>,
     parent: Option<Environment>,
+    // Only meaningful for the global environment
+    strictness: Strictness,
 }
 
 impl PartialEq for _Environment {
@@ -111,11 +245,13 @@ impl _Environment {
     }
 
     pub fn define_variable(&mut self, name: Symbol, value: Value) {
+        rebind(name);
         self.bindings.insert(name, value);
     }
 
     pub fn set_variable_value(&mut self, name: Symbol, value: Value) -> Value {
         if let std::collections::hash_map::Entry::Occupied(mut e) = self.bindings.entry(name) {
+            rebind(name);
             e.insert(value);
             Value::Void
         } else if let Some(ref env) = self.parent {
@@ -126,6 +262,18 @@ impl _Environment {
         }
     }
 
+    pub fn assign_variable(&mut self, name: Symbol, value: Value) -> bool {
+        if let Some(v) = self.bindings.get_mut(&name) {
+            rebind(name);
+            *v = value;
+            true
+        } else if let Some(ref env) = self.parent {
+            env.assign_variable(name, value)
+        } else {
+            false
+        }
+    }
+
     pub fn get_definitions(&self) -> Vec<Symbol> {
         let mut definitions: Vec<_> = self.bindings.keys().copied().collect();
         if let Some(ref env) = self.parent {
@@ -0,0 +1,260 @@
+//! Finding files: `glob` and `walk-directory`.
+//!
+//! A glob pattern is a path whose components may contain wildcards: `*` matches any characters,
+//! `?` any one character, and `[abc]`, `[a-z]` or `[!abc]` one character of, or not of, a set. A
+//! component `**` matches any number of directories, so `src/**/*.ss` finds the `.ss` files
+//! anywhere under `src`.
+//!
+//! Both procedures take an optional list of flags:
+//!
+//! - `hidden` includes files whose names start with `.`, which are otherwise left out unless a
+//!   glob component starts with `.` itself.
+//! - `follow-links` enters links to directories when walking or matching `**`. A link back to a
+//!   directory being walked is never entered, so following links always finishes.
+//!
+//! Files whose names aren't valid UTF-8, and directories which can't be read, are skipped.
+
+use {Value, VM};
+
+use string_interner::get_value;
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Default)]
+struct Options {
+    hidden: bool,
+    follow_links: bool,
+}
+
+fn options(name: &str, v: Option<&Value>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut l = v.copied().unwrap_or(Value::Nil);
+    while l.is_pair() {
+        let flag = l.car();
+        let flag_name = if flag.is_symbol() { get_value(flag.to_symbol()) } else { None };
+        match flag_name.as_deref() {
+            Some("hidden") => options.hidden = true,
+            Some("follow-links") => options.follow_links = true,
+            _ => return Err(format!("{}: {} is not a flag", name, flag)),
+        }
+        l = l.cdr();
+    }
+    if l.is_nil() {
+        Ok(options)
+    } else {
+        Err(format!("{}: {} is not a list of flags", name, v.unwrap()))
+    }
+}
+
+fn string_arg(name: &str, v: Value) -> Result<String, String> {
+    if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.clone();
+        Box::into_raw(s);
+        Ok(r)
+    } else {
+        Err(format!("{}: {} is not a string", name, v))
+    }
+}
+
+fn path_value(path: &Path) -> Value {
+    // Paths are only made from strings and names which are valid UTF-8
+    Value::String(path.to_str().unwrap().to_string())
+}
+
+/// Whether the name `name` matches the glob component `pattern`.
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where the pattern continues after the last `*`, and where the name did when it was reached
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match class(&pattern[p..], name[n]) {
+                Some((true, len)) => Some(len),
+                Some((false, _)) => None,
+                // A `[` without a `]` is just a `[`
+                None if name[n] == '[' => Some(1),
+                None => None,
+            },
+            Some(&c) if c == name[n] => Some(1),
+            _ => None,
+        };
+        match (step, star) {
+            (Some(len), _) => {
+                p += len;
+                n += 1;
+            }
+            // Let the last `*` match one more character and try again
+            (None, Some((after, at))) => {
+                star = Some((after, at + 1));
+                p = after;
+                n = at + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether `c` is in the set `[...]` which `pattern` starts with, and the length of the set, or
+/// `None` if the set isn't closed.
+fn class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let negated = pattern.get(1) == Some(&'!');
+    let start = if negated { 2 } else { 1 };
+    let mut i = start;
+    let mut matched = false;
+    while i < pattern.len() {
+        // A `]` first in the set is a member of it
+        if pattern[i] == ']' && i > start {
+            return Some((matched != negated, i + 1));
+        }
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&end| end != ']') {
+            matched |= pattern[i] <= c && c <= pattern[i + 2];
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+    }
+    None
+}
+
+/// A walk over a directory tree.
+struct Walker {
+    options: Options,
+    // The directories being walked, when links are followed
+    ancestors: Vec<PathBuf>,
+}
+
+impl Walker {
+    fn new(options: Options, root: &Path) -> Self {
+        let mut walker = Walker { options, ancestors: Vec::new() };
+        walker.enter(root);
+        walker
+    }
+
+    /// The names and paths of the entries of `dir` in order, without hidden ones unless `hidden`
+    /// is set or they were asked for.
+    fn entries(&self, dir: &Path, hidden: bool) -> Vec<(String, PathBuf)> {
+        let read = if dir.as_os_str().is_empty() { fs::read_dir(".") } else { fs::read_dir(dir) };
+        let mut entries: Vec<(String, PathBuf)> = read.into_iter().flatten().flatten()
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| hidden || self.options.hidden || !name.starts_with('.'))
+            .map(|name| {
+                let path = dir.join(&name);
+                (name, path)
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    /// Enter `path` if it is a directory to walk into. If it returns `true`, `leave` must be
+    /// called when done with it.
+    fn enter(&mut self, path: &Path) -> bool {
+        let path = if path.as_os_str().is_empty() { Path::new(".") } else { path };
+        match fs::symlink_metadata(path) {
+            Ok(m) if m.is_dir() => (),
+            Ok(m) if m.file_type().is_symlink() && self.options.follow_links && path.is_dir() => (),
+            _ => return false,
+        }
+        if self.options.follow_links {
+            match fs::canonicalize(path) {
+                Ok(canonical) if !self.ancestors.contains(&canonical) => self.ancestors.push(canonical),
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    fn leave(&mut self) {
+        if self.options.follow_links {
+            self.ancestors.pop();
+        }
+    }
+
+    /// Add the paths in `base` which match `components` to `out`.
+    fn glob(&mut self, base: PathBuf, components: &[&str], out: &mut Vec<PathBuf>) {
+        let (&first, rest) = match components.split_first() {
+            Some(split) => split,
+            None if base.as_os_str().is_empty() => return,
+            None => return out.push(base),
+        };
+        if first == "**" {
+            self.glob(base.clone(), rest, out);
+            for (_, path) in self.entries(&base, false) {
+                if self.enter(&path) {
+                    self.glob(path, components, out);
+                    self.leave();
+                }
+            }
+        } else if !first.contains(['*', '?', '[']) {
+            let path = base.join(first);
+            if fs::symlink_metadata(&path).is_ok() {
+                self.glob(path, rest, out);
+            }
+        } else {
+            let pattern: Vec<char> = first.chars().collect();
+            for (name, path) in self.entries(&base, first.starts_with('.')) {
+                if matches(&pattern, &name.chars().collect::<Vec<_>>()) {
+                    self.glob(path, rest, out);
+                }
+            }
+        }
+    }
+
+    /// Call `proc` with the path of everything under `dir`, each directory before its contents.
+    fn walk(&mut self, vm: &mut VM, dir: &Path, proc: Value) -> Result<(), String> {
+        for (_, path) in self.entries(dir, false) {
+            vm.apply(proc, &[path_value(&path)])?;
+            if self.enter(&path) {
+                let walked = self.walk(vm, &path, proc);
+                self.leave();
+                walked?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `(glob pattern [flags])` The paths which match `pattern`, in order.
+pub fn glob(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let pattern = string_arg("glob", args[0])?;
+    let options = options("glob", args.get(1))?;
+    let mut base = PathBuf::new();
+    let mut components = Vec::new();
+    for component in Path::new(&pattern).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => base.push(component),
+            Component::CurDir => (),
+            Component::ParentDir => components.push(".."),
+            Component::Normal(name) => components.push(name.to_str().unwrap()),
+        }
+    }
+
+    let mut paths = Vec::new();
+    Walker::new(options, &base).glob(base, &components, &mut paths);
+    // `**` may match the same path more than one way
+    paths.sort();
+    paths.dedup();
+    Ok(paths.iter().rev().fold(Value::Nil, |l, path| Value::Pair(path_value(path), l)))
+}
+
+/// `(walk-directory path proc [flags])` Call `proc` with the path of each file and directory
+/// under the directory `path`, in order, each directory before its contents.
+pub fn walk_directory(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let path = PathBuf::from(string_arg("walk-directory", args[0])?);
+    let options = options("walk-directory", args.get(2))?;
+    if let Err(e) = fs::read_dir(&path) {
+        return Err(format!("walk-directory: could not read {}: {}", args[0], e));
+    }
+    Walker::new(options, &path).walk(vm, &path, args[1])?;
+    Ok(Value::Void)
+}
@@ -1,5 +1,7 @@
 use value::VType;
+use Value;
 
+use std::mem;
 use std::num::NonZeroU64;
 use std::sync::{LazyLock, Mutex};
 
@@ -13,14 +15,53 @@ pub fn set_head(p: u64, ty: VType) {
     VMGC.lock().unwrap().set_head(p, ty)
 }
 
+/// Make a newly allocated object the head of the heap list.
+pub fn allocate(p: u64, ty: VType) {
+    let mut gc = VMGC.lock().unwrap();
+    gc.allocations += 1;
+    gc.set_head(p, ty)
+}
+
+/// The number of heap objects allocated so far.
+pub fn allocations() -> usize {
+    VMGC.lock().unwrap().allocations
+}
+
+/// Approximately the bytes used by the objects on the heap which survived the last collection.
+pub fn heap_bytes() -> usize {
+    VMGC.lock().unwrap().live_bytes
+}
+
+pub(crate) fn set_heap_bytes(bytes: usize) {
+    VMGC.lock().unwrap().live_bytes = bytes;
+}
+
+/// Record that the table entry `v` refers to, such as a continuation, was reached while marking,
+/// so that the machine which owns it marks what it holds and keeps it.
+pub(crate) fn reach(v: Value) {
+    VMGC.lock().unwrap().reached.push(v);
+}
+
+/// Take the table entries reached since this was last called.
+pub(crate) fn take_reached() -> Vec<Value> {
+    mem::take(&mut VMGC.lock().unwrap().reached)
+}
+
 pub struct Gc {
     head: Option<NonZeroU64>,
+    allocations: usize,
+    live_bytes: usize,
+    // Values of table entries reached while marking, see `reach`
+    reached: Vec<Value>,
 }
 
 impl Gc {
     pub fn new() -> Self {
         Gc {
-            head: None
+            head: None,
+            allocations: 0,
+            live_bytes: 0,
+            reached: vec![],
         }
     }
 
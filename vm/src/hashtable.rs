@@ -0,0 +1,205 @@
+//! Hash tables. Keys are compared with `eq?`, so symbols and fixnums make good keys while strings
+//! and pairs are only found by identity.
+//!
+//! A table grows as entries are added, rehashing every entry each time it does. When the number
+//! of entries is known ahead, giving `make-hash-table` a capacity or calling `hash-table-reserve!`
+//! makes room for them at once. `alist->hash-table`, `hash-table-copy`, and `hash-table-merge!`
+//! size the table they fill once, before adding any entries.
+
+use {vector, Value, VM};
+
+use std::collections::HashMap;
+use std::mem;
+
+fn check_table(name: &str, v: Value) -> Result<(), String> {
+    if v.is_hashmap() {
+        Ok(())
+    } else {
+        Err(format!("{}: {} is not a hash table", name, v))
+    }
+}
+
+/// A capacity of `v` entries, if there is room on the heap for them.
+fn capacity_arg(vm: &mut VM, name: &str, v: Value) -> Result<usize, String> {
+    if !v.is_integer() || v.to_integer() < 0 {
+        return Err(format!("{}: {} is not a valid capacity", name, v));
+    }
+    let n = v.to_integer() as usize;
+    vm.check_allocation(name, n.saturating_mul(mem::size_of::<(Value, Value)>()))?;
+    Ok(n)
+}
+
+/// `(make-hash-table [capacity])` A new, empty hash table with room for at least `capacity`
+/// entries before it grows.
+pub fn make_hash_table(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "make-hash-table";
+    let mut map = HashMap::new();
+    if let Some(&n) = args.first() {
+        let n = capacity_arg(vm, name, n)?;
+        map.try_reserve(n).map_err(|_| format!("{}: can't make room for {} entries", name, n))?;
+    }
+    Ok(Value::HashMap(map))
+}
+
+/// `(hash-table? obj)`
+pub fn is_hash_table(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(args[0].is_hashmap()))
+}
+
+/// `(hash-table-ref table key [default])` Return the value of `key`, or `default` if it is absent.
+/// It is an error for `key` to be absent without a default.
+pub fn hash_table_ref(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_table("hash-table-ref", args[0])?;
+    let m = args[0].to_hashmap();
+    let v = m.map.get(&args[1]).cloned();
+    Box::into_raw(m);
+    v.or_else(|| args.get(2).cloned())
+        .ok_or_else(|| format!("hash-table-ref: {} is not in the table", args[1]))
+}
+
+/// `(hash-table-set! table key value)` Associate `key` with `value` in `table`.
+pub fn hash_table_set(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_table("hash-table-set!", args[0])?;
+    let mut m = args[0].to_hashmap();
+    m.map.insert(args[1], args[2]);
+    Box::into_raw(m);
+    Ok(Value::Void)
+}
+
+/// `(hash-table-count table)` The number of entries in `table`.
+pub fn hash_table_count(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_table("hash-table-count", args[0])?;
+    let m = args[0].to_hashmap();
+    let n = m.map.len();
+    Box::into_raw(m);
+    Ok(Value::Integer(n as i32))
+}
+
+/// `(hash-table-reserve! table n)` Make room for at least `n` more entries in `table`, so that
+/// adding them doesn't grow it.
+pub fn hash_table_reserve(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "hash-table-reserve!";
+    check_table(name, args[0])?;
+    let n = capacity_arg(vm, name, args[1])?;
+    let mut m = args[0].to_hashmap();
+    let reserved = m.map.try_reserve(n);
+    Box::into_raw(m);
+    reserved.map_err(|_| format!("{}: can't make room for {} entries", name, n))?;
+    Ok(Value::Void)
+}
+
+/// `(hash-table-capacity table)` The number of entries `table` can hold before it grows.
+pub fn hash_table_capacity(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_table("hash-table-capacity", args[0])?;
+    let m = args[0].to_hashmap();
+    let n = m.map.capacity();
+    Box::into_raw(m);
+    Ok(Value::Integer(n.min(i32::MAX as usize) as i32))
+}
+
+/// `(hash-table-load-factor table)` The fraction of the capacity of `table` in use, from 0 to 1.
+pub fn hash_table_load_factor(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_table("hash-table-load-factor", args[0])?;
+    let m = args[0].to_hashmap();
+    let (len, capacity) = (m.map.len(), m.map.capacity());
+    Box::into_raw(m);
+    Ok(Value::Float(if capacity == 0 { 0.0 } else { len as f64 / capacity as f64 }))
+}
+
+/// The elements of the list or vector `v`.
+fn elements(vm: &VM, name: &str, v: Value) -> Result<Vec<Value>, String> {
+    if v.is_vec() || v.is_slice() {
+        let (vector, range) = vector::range(vm, name, v)?;
+        return Ok(range.filter_map(|i| vector::element(vector, i)).collect());
+    }
+    let mut elements = Vec::new();
+    let mut l = v;
+    while l.is_pair() {
+        elements.push(l.car());
+        l = l.cdr();
+    }
+    if l.is_nil() {
+        Ok(elements)
+    } else {
+        Err(format!("{}: {} is not a list or vector", name, v))
+    }
+}
+
+/// `(alist->hash-table associations)` A new hash table with the entries of `associations`, a list
+/// or vector of pairs of a key and its value. When a key appears more than once the first of its
+/// pairs wins, as with `assq`.
+pub fn alist_to_hash_table(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "alist->hash-table";
+    let associations = elements(vm, name, args[0])?;
+    let mut map = HashMap::with_capacity(associations.len());
+    for a in associations {
+        if !a.is_pair() {
+            return Err(format!("{}: {} is not a pair", name, a));
+        }
+        map.entry(a.car()).or_insert(a.cdr());
+    }
+    Ok(Value::HashMap(map))
+}
+
+/// `(hash-table-copy table)` A new hash table with the same entries as `table`.
+pub fn hash_table_copy(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_table("hash-table-copy", args[0])?;
+    let m = args[0].to_hashmap();
+    let map = m.map.clone();
+    Box::into_raw(m);
+    Ok(Value::HashMap(map))
+}
+
+/// `(hash-table-merge! table other [resolve])` Add the entries of `other` to `table`, and return
+/// `table`. When both have a key its value becomes `(resolve key value other-value)`, or the value
+/// in `other` without `resolve`.
+pub fn hash_table_merge(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "hash-table-merge!";
+    check_table(name, args[0])?;
+    check_table(name, args[1])?;
+    // The keys and values are kept alive by `other`, as long as `resolve` doesn't remove them
+    let other = args[1].to_hashmap();
+    let entries: Vec<(Value, Value)> = other.map.iter().map(|(&k, &v)| (k, v)).collect();
+    Box::into_raw(other);
+
+    let mut m = args[0].to_hashmap();
+    m.map.reserve(entries.len());
+    let conflicts = match args.get(2) {
+        Some(_) => entries.into_iter().filter_map(|(k, v)| match m.map.get(&k) {
+            Some(&old) => Some((k, old, v)),
+            None => {
+                m.map.insert(k, v);
+                None
+            }
+        }).collect(),
+        None => {
+            m.map.extend(entries);
+            Vec::new()
+        }
+    };
+    Box::into_raw(m);
+
+    // `resolve` may change the table, so it is taken afresh for each entry
+    for (k, old, v) in conflicts {
+        let resolved = vm.apply(args[2], &[k, old, v])?;
+        let mut m = args[0].to_hashmap();
+        m.map.insert(k, resolved);
+        Box::into_raw(m);
+    }
+    Ok(args[0])
+}
+
+/// `(hash-table-keys table)` A list of the keys of `table`, in no particular order.
+pub fn hash_table_keys(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_table("hash-table-keys", args[0])?;
+    let m = args[0].to_hashmap();
+    let keys = m.map.keys().fold(Value::Nil, |l, &k| Value::Pair(k, l));
+    Box::into_raw(m);
+    Ok(keys)
+}
+
+/// `(eq-hash obj)` A non-negative fixnum hash of the identity of `obj`. Objects which are `eq?`
+/// have the same hash, and an object's hash never changes.
+pub fn eq_hash(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Integer((args[0].identity_hash() & i32::MAX as u64) as i32))
+}
@@ -0,0 +1,56 @@
+//! Callbacks into the running machine, for profilers, debuggers and coverage tools built outside
+//! the crate.
+//!
+//! A `VmHook` installed with `VM::set_hook` is told about every instruction before it runs, every
+//! procedure called and every procedure which returns. Each method is given the machine, so the
+//! hook can look at its registers and step count. The methods do nothing by default, so a hook
+//! only implements the events it cares about.
+//!
+//! Hooks exist only with the `hooks` feature. Without it the machine makes no checks for them.
+
+use {Operation, Value, VM};
+
+use std::fmt::{self, Formatter};
+
+/// Events in the running machine.
+pub trait VmHook {
+    /// Called before `op`, the instruction at `pc` in the code being run, is run.
+    fn instruction(&mut self, _vm: &VM, _pc: usize, _op: Operation) {}
+
+    /// Called when `procedure` is called with `argc` arguments, including tail calls. Primitives
+    /// compiled inline are not called.
+    fn call(&mut self, _vm: &VM, _procedure: Value, _argc: usize) {}
+
+    /// Called when the compound procedure `procedure` returns `result`. A procedure replaced by a
+    /// tail call doesn't return, the procedure called in its place does.
+    fn ret(&mut self, _vm: &VM, _procedure: Value, _result: Value) {}
+}
+
+// The machine's hook, wrapped so that the machine can still derive `Debug`
+pub(crate) struct Hook(pub(crate) Box<dyn VmHook>);
+
+impl fmt::Debug for Hook {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "VmHook")
+    }
+}
+
+impl VM {
+    /// Tell `hook` about the instructions, calls and returns of this machine from now on.
+    pub fn set_hook(&mut self, hook: impl VmHook + 'static) {
+        self.hook = Some(Hook(Box::new(hook)));
+    }
+
+    /// Remove the hook installed by `set_hook`, e.g. to install it in another machine.
+    pub fn take_hook(&mut self) -> Option<Box<dyn VmHook>> {
+        self.hook.take().map(|h| h.0)
+    }
+
+    // Give the hook, if there is one, the machine without it.
+    pub(crate) fn with_hook(&mut self, f: impl FnOnce(&mut dyn VmHook, &VM)) {
+        if let Some(mut hook) = self.hook.take() {
+            f(&mut *hook.0, self);
+            self.hook = Some(hook);
+        }
+    }
+}
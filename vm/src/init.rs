@@ -1,4 +1,7 @@
-use {assemble, ASM, Environment, Register, Value, VM};
+use {assemble, bytevector, character, charset, condition, continuation, coverage, disasm, files, hashtable, inspect,
+     iterate, list, locale, logging, memo, number, path, port, program, property, random, reflect, register_native,
+     signal, string, terminal, timer, value_hash, vector, xml, Arity, ASM, Environment, NativeFn, Register, Value,
+     VM};
 
 pub fn init_env() -> Environment {
     let env = Environment::new();
@@ -12,6 +15,20 @@ pub fn init_env() -> Environment {
     let mul = vec![ASM::Mul(Register(0), Register(1), Register(2))];
     add_primitive(&env, "*".to_string(), mul);
 
+    add_native(&env, "/", Arity::AtLeast(1), number::div);
+    add_native(&env, "quotient", Arity::Exactly(2), number::quotient);
+    add_native(&env, "remainder", Arity::Exactly(2), number::remainder);
+    add_native(&env, "modulo", Arity::Exactly(2), number::modulo);
+    add_native(&env, "wrapping-integer", Arity::Exactly(2), number::wrapping_integer);
+    add_native(&env, "checked-integer", Arity::Exactly(2), number::checked_integer);
+    add_native(&env, "i32->u32", Arity::Exactly(1), number::i32_to_u32);
+    add_native(&env, "u32->i32", Arity::Exactly(1), number::u32_to_i32);
+    add_native(&env, "number->string", Arity::Range(1, 2), number::number_to_string);
+    add_native(&env, "exact?", Arity::Exactly(1), number::is_exact);
+    add_native(&env, "inexact?", Arity::Exactly(1), number::is_inexact);
+    add_native(&env, "exact", Arity::Exactly(1), number::exact);
+    add_native(&env, "inexact", Arity::Exactly(1), number::inexact);
+
     let eq = vec![ASM::Eq(Register(0), Register(1), Register(2))];
     add_primitive(&env, "=".to_string(), eq);
 
@@ -24,6 +41,277 @@ pub fn init_env() -> Environment {
     add_primitive(&env, "car".to_string(), car);
     let cdr = vec![ASM::Cdr(Register(0), Register(1))];
     add_primitive(&env, "cdr".to_string(), cdr);
+    let vector_ref = vec![ASM::VectorRef(Register(0), Register(1), Register(2))];
+    add_primitive(&env, "vector-ref".to_string(), vector_ref);
+    let set_car = vec![ASM::SetCar(Register(1), Register(2)), ASM::LoadConst(Register(0), Value::Void)];
+    add_primitive(&env, "set-car!".to_string(), set_car);
+    let set_cdr = vec![ASM::SetCdr(Register(1), Register(2)), ASM::LoadConst(Register(0), Value::Void)];
+    add_primitive(&env, "set-cdr!".to_string(), set_cdr);
+
+    let trace = vec![ASM::Trace(Register(1)), ASM::LoadConst(Register(0), Value::Void)];
+    add_primitive(&env, "trace".to_string(), trace);
+    let untrace = vec![ASM::Untrace(Register(1)), ASM::LoadConst(Register(0), Value::Void)];
+    add_primitive(&env, "untrace".to_string(), untrace);
+
+    add_native(&env, "list", Arity::AtLeast(0), list::list);
+    add_native(&env, "list?", Arity::Exactly(1), list::is_list);
+    add_native(&env, "length", Arity::Exactly(1), list::length);
+    add_native(&env, "reverse", Arity::Exactly(1), list::reverse);
+    add_native(&env, "append", Arity::AtLeast(0), list::append);
+    add_native(&env, "reverse!", Arity::Exactly(1), list::reverse_bang);
+    add_native(&env, "append-reverse!", Arity::Exactly(2), list::append_reverse_bang);
+    add_native(&env, "append!", Arity::AtLeast(0), list::append_bang);
+    // Chooses the procedure for calls the compiler found could use a linear update version
+    add_native(&env, " linear-update", Arity::Exactly(2), list::linear_update);
+
+    add_native(&env, "char?", Arity::Exactly(1), character::is_char);
+    add_native(&env, "char->integer", Arity::Exactly(1), character::char_to_integer);
+    add_native(&env, "integer->char", Arity::Exactly(1), character::integer_to_char);
+    add_native(&env, "char-upcase", Arity::Exactly(1), character::char_upcase);
+    add_native(&env, "char-downcase", Arity::Exactly(1), character::char_downcase);
+
+    add_native(&env, "char-set", Arity::AtLeast(0), charset::char_set);
+    add_native(&env, "string->char-set", Arity::Exactly(1), charset::string_to_char_set);
+    add_native(&env, "list->char-set", Arity::Exactly(1), charset::list_to_char_set);
+    add_native(&env, "ucs-range->char-set", Arity::Exactly(2), charset::ucs_range_to_char_set);
+    add_native(&env, "char-set?", Arity::Exactly(1), charset::is_char_set);
+    add_native(&env, "char-set-contains?", Arity::Exactly(2), charset::char_set_contains);
+    add_native(&env, "char-set-size", Arity::Exactly(1), charset::char_set_size);
+    add_native(&env, "char-set->list", Arity::Exactly(1), charset::char_set_to_list);
+    add_native(&env, "char-set-union", Arity::AtLeast(0), charset::char_set_union);
+    add_native(&env, "char-set-intersection", Arity::AtLeast(1), charset::char_set_intersection);
+    add_native(&env, "char-set-difference", Arity::AtLeast(1), charset::char_set_difference);
+    add_native(&env, "char-set-complement", Arity::Exactly(1), charset::char_set_complement);
+    for (i, name) in charset::STANDARD_NAMES.iter().enumerate() {
+        env.define_variable(VM::intern_symbol(name.to_string()), Value::CharSet(i as u32));
+    }
+
+    add_native(&env, "string-search", Arity::Range(2, 3), string::string_search);
+    add_native(&env, "string-replace-all", Arity::Exactly(3), string::string_replace_all);
+
+    add_native(&env, "string-locale<?", Arity::Range(2, 3), locale::string_locale_lt);
+    add_native(&env, "string-locale>?", Arity::Range(2, 3), locale::string_locale_gt);
+    add_native(&env, "string-locale=?", Arity::Range(2, 3), locale::string_locale_eq);
+    add_native(&env, "string-locale-upcase", Arity::Range(1, 2), locale::string_locale_upcase);
+    add_native(&env, "string-locale-downcase", Arity::Range(1, 2), locale::string_locale_downcase);
+
+    add_native(&env, "for-each", Arity::Exactly(2), iterate::for_each);
+    add_native(&env, "fold", Arity::Exactly(3), iterate::fold);
+
+    add_native(&env, "call-with-current-continuation", Arity::Exactly(1), continuation::call_cc);
+    add_native(&env, "call/cc", Arity::Exactly(1), continuation::call_cc);
+    add_native(&env, "continuation?", Arity::Exactly(1), continuation::is_continuation);
+
+    add_native(&env, "current-input-port", Arity::Exactly(0), port::current_input_port);
+    add_native(&env, "current-output-port", Arity::Exactly(0), port::current_output_port);
+    add_native(&env, "open-input-string", Arity::Exactly(1), port::open_input_string);
+    add_native(&env, "open-output-string", Arity::Exactly(0), port::open_output_string);
+    add_native(&env, "get-output-string", Arity::Exactly(1), port::get_output_string);
+    add_native(&env, "open-input-file", Arity::Exactly(1), port::open_input_file);
+    add_native(&env, "open-output-file", Arity::Exactly(1), port::open_output_file);
+    add_native(&env, "open-tcp-connection", Arity::Exactly(2), port::open_tcp_connection);
+    add_native(&env, "close-port", Arity::Exactly(1), port::close_port);
+    add_native(&env, "port?", Arity::Exactly(1), port::is_port);
+    add_native(&env, "input-port?", Arity::Exactly(1), port::is_input_port);
+    add_native(&env, "output-port?", Arity::Exactly(1), port::is_output_port);
+    add_native(&env, "eof-object", Arity::Exactly(0), port::eof_object);
+    add_native(&env, "eof-object?", Arity::Exactly(1), port::is_eof_object);
+    add_native(&env, "read-char", Arity::Range(0, 1), port::read_char);
+    add_native(&env, "peek-char", Arity::Range(0, 1), port::peek_char);
+    add_native(&env, "read-line", Arity::Range(0, 1), port::read_line);
+    add_native(&env, "char-ready?", Arity::Range(0, 1), port::char_ready);
+    add_native(&env, "read-char-no-hang", Arity::Range(0, 1), port::read_char_no_hang);
+    add_native(&env, "read-line-no-hang", Arity::Range(0, 1), port::read_line_no_hang);
+    add_native(&env, "set-port-read-timeout!", Arity::Exactly(2), port::set_port_read_timeout);
+    add_native(&env, "write-char", Arity::Range(1, 2), port::write_char);
+    add_native(&env, "write-string", Arity::Range(1, 2), port::write_string);
+    add_native(&env, "display", Arity::Range(1, 2), port::display);
+    add_native(&env, "write", Arity::Range(1, 2), port::write);
+    add_native(&env, "newline", Arity::Range(0, 1), port::newline);
+    add_native(&env, "flush-output-port", Arity::Range(0, 1), port::flush_output_port);
+
+    add_native(&env, "open-input-bytevector", Arity::Exactly(1), port::open_input_bytevector);
+    add_native(&env, "open-output-bytevector", Arity::Exactly(0), port::open_output_bytevector);
+    add_native(&env, "get-output-bytevector", Arity::Exactly(1), port::get_output_bytevector);
+    add_native(&env, "open-binary-input-file", Arity::Exactly(1), port::open_binary_input_file);
+    add_native(&env, "open-binary-output-file", Arity::Exactly(1), port::open_binary_output_file);
+    add_native(&env, "read-u8", Arity::Range(0, 1), port::read_u8);
+    add_native(&env, "peek-u8", Arity::Range(0, 1), port::peek_u8);
+    add_native(&env, "u8-ready?", Arity::Range(0, 1), port::u8_ready);
+    add_native(&env, "read-bytevector", Arity::Range(1, 2), port::read_bytevector);
+    add_native(&env, "write-u8", Arity::Range(1, 2), port::write_u8);
+    add_native(&env, "write-bytevector", Arity::Range(1, 2), port::write_bytevector);
+    add_native(&env, "read-s8", Arity::Range(1, 2), port::read_s8);
+    add_native(&env, "read-u16", Arity::Range(1, 2), port::read_u16);
+    add_native(&env, "read-s16", Arity::Range(1, 2), port::read_s16);
+    add_native(&env, "read-u32", Arity::Range(1, 2), port::read_u32);
+    add_native(&env, "read-s32", Arity::Range(1, 2), port::read_s32);
+    add_native(&env, "read-f32", Arity::Range(1, 2), port::read_f32);
+    add_native(&env, "read-f64", Arity::Range(1, 2), port::read_f64);
+    add_native(&env, "write-s8", Arity::Range(2, 3), port::write_s8);
+    add_native(&env, "write-u16", Arity::Range(2, 3), port::write_u16);
+    add_native(&env, "write-s16", Arity::Range(2, 3), port::write_s16);
+    add_native(&env, "write-u32", Arity::Range(2, 3), port::write_u32);
+    add_native(&env, "write-s32", Arity::Range(2, 3), port::write_s32);
+    add_native(&env, "write-f32", Arity::Range(2, 3), port::write_f32);
+    add_native(&env, "write-f64", Arity::Range(2, 3), port::write_f64);
+
+    add_native(&env, "vector", Arity::AtLeast(0), vector::vector);
+    add_native(&env, "vector?", Arity::Exactly(1), vector::is_vector);
+    add_native(&env, "vector-length", Arity::Exactly(1), vector::vector_length);
+    add_native(&env, "vector-push!", Arity::Exactly(2), vector::vector_push);
+    add_native(&env, "vector-pop!", Arity::Exactly(1), vector::vector_pop);
+    add_native(&env, "vector-extend!", Arity::Exactly(2), vector::vector_extend);
+    add_native(&env, "vector-index", Arity::Exactly(2), vector::vector_index);
+    add_native(&env, "vector-binary-search", Arity::Exactly(3), vector::vector_binary_search);
+    add_native(&env, "vector-slice", Arity::Range(1, 3), vector::vector_slice);
+    add_native(&env, "vector-slice?", Arity::Exactly(1), vector::is_vector_slice);
+    add_native(&env, "vector-slice->vector", Arity::Exactly(1), vector::vector_slice_to_vector);
+
+    add_native(&env, "bytevector", Arity::AtLeast(0), bytevector::bytevector);
+    add_native(&env, "bytevector?", Arity::Exactly(1), bytevector::is_bytevector);
+    add_native(&env, "make-bytevector", Arity::Range(1, 2), bytevector::make_bytevector);
+    add_native(&env, "bytevector-length", Arity::Exactly(1), bytevector::bytevector_length);
+    add_native(&env, "bytevector-copy", Arity::Range(1, 3), bytevector::bytevector_copy);
+    add_native(&env, "native-endianness", Arity::Exactly(0), bytevector::native_endianness);
+    add_native(&env, "bytevector-u8-ref", Arity::Exactly(2), bytevector::u8_ref);
+    add_native(&env, "bytevector-u8-set!", Arity::Exactly(3), bytevector::u8_set);
+    add_native(&env, "bytevector-u16-ref", Arity::Range(2, 3), bytevector::u16_ref);
+    add_native(&env, "bytevector-u16-set!", Arity::Range(3, 4), bytevector::u16_set);
+    add_native(&env, "bytevector-s16-ref", Arity::Range(2, 3), bytevector::s16_ref);
+    add_native(&env, "bytevector-s16-set!", Arity::Range(3, 4), bytevector::s16_set);
+    add_native(&env, "bytevector-u32-ref", Arity::Range(2, 3), bytevector::u32_ref);
+    add_native(&env, "bytevector-u32-set!", Arity::Range(3, 4), bytevector::u32_set);
+    add_native(&env, "bytevector-s32-ref", Arity::Range(2, 3), bytevector::s32_ref);
+    add_native(&env, "bytevector-s32-set!", Arity::Range(3, 4), bytevector::s32_set);
+    add_native(&env, "bytevector-ieee-single-ref", Arity::Range(2, 3), bytevector::ieee_single_ref);
+    add_native(&env, "bytevector-ieee-single-set!", Arity::Range(3, 4), bytevector::ieee_single_set);
+    add_native(&env, "bytevector-ieee-double-ref", Arity::Range(2, 3), bytevector::ieee_double_ref);
+    add_native(&env, "bytevector-ieee-double-set!", Arity::Range(3, 4), bytevector::ieee_double_set);
+    add_native(&env, "integer->bytevector", Arity::Range(2, 3), bytevector::integer_to_bytevector);
+    add_native(&env, "bytevector->integer", Arity::Range(1, 3), bytevector::bytevector_to_integer);
+
+    add_native(&env, "random-bytes", Arity::Exactly(1), random::random_bytes);
+    add_native(&env, "uuid", Arity::Exactly(0), random::uuid);
+    add_native(&env, "command-line", Arity::Exactly(0), program::command_line);
+    add_native(&env, "parse-args", Arity::Range(1, 2), program::parse_args);
+    add_native(&env, "expand-user", Arity::Exactly(1), path::expand_user);
+    add_native(&env, "expand-variables", Arity::Exactly(1), path::expand_variables);
+    add_native(&env, "path-join", Arity::AtLeast(1), path::path_join);
+    add_native(&env, "path-extension", Arity::Exactly(1), path::path_extension);
+    add_native(&env, "path-file-name", Arity::Exactly(1), path::path_file_name);
+    add_native(&env, "path-parent", Arity::Exactly(1), path::path_parent);
+    add_native(&env, "path-absolute?", Arity::Exactly(1), path::is_path_absolute);
+    add_native(&env, "path-normalize", Arity::Exactly(1), path::path_normalize);
+    add_native(&env, "path-separator", Arity::Exactly(0), path::path_separator);
+    add_native(&env, "glob", Arity::Range(1, 2), files::glob);
+    add_native(&env, "walk-directory", Arity::Range(2, 3), files::walk_directory);
+    add_native(&env, "with-signal-handler", Arity::Exactly(3), signal::with_signal_handler);
+    add_native(&env, "with-restart-handler", Arity::Exactly(2), condition::with_restart_handler);
+    add_native(&env, "invoke-restart", Arity::Range(1, 2), condition::invoke_restart);
+    add_native(&env, "sleep", Arity::Exactly(1), timer::sleep);
+    add_native(&env, "monotonic-time", Arity::Exactly(0), timer::monotonic_time);
+    add_native(&env, "after", Arity::Exactly(2), timer::after);
+
+    add_native(&env, "make-hash-table", Arity::Range(0, 1), hashtable::make_hash_table);
+    add_native(&env, "hash-table?", Arity::Exactly(1), hashtable::is_hash_table);
+    add_native(&env, "hash-table-ref", Arity::Range(2, 3), hashtable::hash_table_ref);
+    add_native(&env, "hash-table-set!", Arity::Exactly(3), hashtable::hash_table_set);
+    add_native(&env, "hash-table-count", Arity::Exactly(1), hashtable::hash_table_count);
+    add_native(&env, "hash-table-keys", Arity::Exactly(1), hashtable::hash_table_keys);
+    add_native(&env, "hash-table-reserve!", Arity::Exactly(2), hashtable::hash_table_reserve);
+    add_native(&env, "hash-table-capacity", Arity::Exactly(1), hashtable::hash_table_capacity);
+    add_native(&env, "hash-table-load-factor", Arity::Exactly(1), hashtable::hash_table_load_factor);
+    add_native(&env, "alist->hash-table", Arity::Exactly(1), hashtable::alist_to_hash_table);
+    add_native(&env, "hash-table-copy", Arity::Exactly(1), hashtable::hash_table_copy);
+    add_native(&env, "hash-table-merge!", Arity::Range(2, 3), hashtable::hash_table_merge);
+    add_native(&env, "eq-hash", Arity::Exactly(1), hashtable::eq_hash);
+    add_native(&env, "object-hash", Arity::Exactly(1), hashtable::eq_hash);
+    add_native(&env, "value-hash", Arity::Exactly(1), value_hash::value_hash_native);
+
+    add_native(&env, "colorize", Arity::Exactly(2), terminal::colorize);
+    add_native(&env, "with-color", Arity::Exactly(2), terminal::with_color);
+    add_native(&env, "terminal-colors?", Arity::Exactly(0), terminal::is_terminal_colors);
+    add_native(&env, "set-terminal-colors!", Arity::Exactly(1), terminal::set_terminal_colors);
+
+    add_native(&env, "log-debug", Arity::AtLeast(1), logging::log_debug);
+    add_native(&env, "log-info", Arity::AtLeast(1), logging::log_info);
+    add_native(&env, "log-warn", Arity::AtLeast(1), logging::log_warn);
+    add_native(&env, "log-error", Arity::AtLeast(1), logging::log_error);
+    add_native(&env, "log-level", Arity::Exactly(0), logging::log_level);
+    add_native(&env, "set-log-level!", Arity::Exactly(1), logging::set_log_level);
+
+    add_native(&env, "put!", Arity::Exactly(3), property::put);
+    add_native(&env, "get", Arity::Range(2, 3), property::get);
+    add_native(&env, "remprop!", Arity::Exactly(2), property::remprop);
+    add_native(&env, "symbol-plist", Arity::Exactly(1), property::symbol_plist);
+
+    add_native(&env, "xml->sxml", Arity::Exactly(1), xml::xml_to_sxml);
+    add_native(&env, "sxml->xml", Arity::Exactly(1), xml::sxml_to_xml);
+
+    add_native(&env, "describe", Arity::Range(1, 2), inspect::describe);
+    add_native(&env, "disassemble", Arity::Range(1, 2), disasm::disassemble);
+
+    add_native(&env, "current-environment", Arity::Exactly(0), reflect::current_environment);
+    add_native(&env, "interaction-environment", Arity::Exactly(0), reflect::interaction_environment);
+    add_native(&env, "environment?", Arity::Exactly(1), reflect::is_environment);
+    add_native(&env, "environment-bound-names", Arity::Exactly(1), reflect::bound_names);
+    add_native(&env, "environment-bindings", Arity::Exactly(1), reflect::bindings);
+    add_native(&env, "environment-parent", Arity::Exactly(1), reflect::parent);
+    add_native(&env, "environment-bound?", Arity::Exactly(2), reflect::is_bound);
+    add_native(&env, "environment-lookup", Arity::Exactly(2), reflect::lookup);
+    add_native(&env, "environment-strictness", Arity::Exactly(1), reflect::strictness);
+    add_native(&env, "set-environment-strictness!", Arity::Exactly(2), reflect::set_strictness);
+    add_native(&env, "current-stack", Arity::Range(0, 1), reflect::current_stack);
+
+    add_native(&env, "coverage-report", Arity::Exactly(0), coverage::coverage_report);
+
+    add_native(&env, "memoize", Arity::Range(1, 2), memo::memoize);
+    add_native(&env, "memoize-clear!", Arity::Exactly(1), memo::clear);
+    add_native(&env, "memoize-count", Arity::Exactly(1), memo::count);
+
+    #[cfg(feature = "compress")]
+    {
+        use compress;
+        add_native(&env, "gzip-compress", Arity::Range(1, 2), compress::gzip_compress);
+        add_native(&env, "gzip-decompress", Arity::Exactly(1), compress::gzip_decompress);
+        add_native(&env, "deflate-compress", Arity::Range(1, 2), compress::deflate_compress);
+        add_native(&env, "deflate-decompress", Arity::Exactly(1), compress::deflate_decompress);
+    }
+
+    #[cfg(feature = "crypto")]
+    {
+        use crypto;
+        add_native(&env, "md5", Arity::Exactly(1), crypto::md5);
+        add_native(&env, "sha1", Arity::Exactly(1), crypto::sha1);
+        add_native(&env, "sha256", Arity::Exactly(1), crypto::sha256);
+        add_native(&env, "hmac-sha256", Arity::Exactly(2), crypto::hmac_sha256);
+        add_native(&env, "value-digest", Arity::Exactly(1), crypto::value_digest_native);
+    }
+
+    #[cfg(feature = "sqlite")]
+    {
+        use sqlite;
+        add_native(&env, "sqlite-open", Arity::Exactly(1), sqlite::open);
+        add_native(&env, "sqlite-close", Arity::Exactly(1), sqlite::close);
+        add_native(&env, "sqlite-exec", Arity::AtLeast(2), sqlite::exec);
+        add_native(&env, "sqlite-query", Arity::AtLeast(2), sqlite::query);
+        add_native(&env, "sqlite-query-alist", Arity::AtLeast(2), sqlite::query_alist);
+    }
+
+    #[cfg(feature = "config")]
+    {
+        use config;
+        add_native(&env, "toml-read", Arity::Range(0, 1), config::toml_read);
+        add_native(&env, "yaml-read", Arity::Range(0, 1), config::yaml_read);
+    }
+
+    #[cfg(feature = "watch")]
+    {
+        use watch;
+        add_native(&env, "watch-path", Arity::Range(2, 3), watch::watch_path);
+        add_native(&env, "unwatch-path", Arity::Exactly(1), watch::unwatch_path);
+    }
 
     env.define_variable(VM::intern_symbol("pi".to_string()), Value::Float(std::f64::consts::PI));
     env.define_variable(VM::intern_symbol("e".to_string()), Value::Float(std::f64::consts::E));
@@ -35,3 +323,7 @@ fn add_primitive(env: &Environment, name: String, code: Vec<ASM>) {
     let (code, consts) = assemble(code);
     env.define_variable(VM::intern_symbol(name), Value::Lambda(env.clone(), code, consts));
 }
+
+fn add_native(env: &Environment, name: &'static str, arity: Arity, f: NativeFn) {
+    env.define_variable(VM::intern_symbol(name.to_string()), register_native(name, arity, f));
+}
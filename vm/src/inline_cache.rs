@@ -0,0 +1,52 @@
+//! Inline caches for the procedures called at call sites.
+//!
+//! Looking up a name hashes it in each frame of the environment until it is found. The lookup of
+//! the procedure a call site calls is compiled to `LookupCached`, which keeps the procedure it
+//! found in a slot of the code it is part of, so later calls skip the search. A procedure's own
+//! frame is new for every call, so entries are keyed by the frame the search continues from, and
+//! a slot holds entries for a few of them since every closure made from a `lambda` shares its
+//! code. An entry is out of date as soon as its name is bound or assigned in any environment, so
+//! redefining a procedure is seen by every call site at once.
+
+use environment::binding_version;
+use {Environment, Value};
+
+use string_interner::Symbol;
+
+/// The most environments a slot keeps an entry for.
+const ENTRIES: usize = 4;
+
+/// The slot of one call site.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct InlineCache {
+    entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    env: Environment,
+    name: Symbol,
+    version: u64,
+    value: Value,
+}
+
+impl InlineCache {
+    /// The value `name` was found to have in `env`, if it hasn't been bound or assigned since.
+    pub(crate) fn get(&self, env: &Environment, name: Symbol) -> Option<Value> {
+        let version = binding_version(name);
+        self.entries.iter()
+            .find(|e| e.name == name && e.version == version && e.env.ptr_eq(env))
+            .map(|e| e.value)
+    }
+
+    /// Remember that `name` has `value` in `env`. Entries which are out of date are dropped, then
+    /// the oldest if the slot is full.
+    pub(crate) fn insert(&mut self, env: Environment, name: Symbol, value: Value) {
+        self.entries.retain(|e| e.version == binding_version(e.name));
+        if self.entries.len() == ENTRIES {
+            self.entries.remove(0);
+        }
+        let version = binding_version(name);
+        self.entries.push(Entry { env, name, version, value });
+    }
+}
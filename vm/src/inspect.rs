@@ -0,0 +1,188 @@
+//! Descriptions of values for `describe` and the debugger.
+
+use {Value, VM};
+use charset;
+use native::get_native;
+use port::{write_bytes, Port};
+use value::heap_repr::*;
+use value::VType;
+
+use std::fmt::Write;
+use std::mem::size_of;
+
+// The number of elements of a collection which are listed
+const SHOWN: usize = 8;
+
+// Printed fields are cut off at this many characters
+const WIDTH: usize = 60;
+
+pub(crate) fn short(v: Value) -> String {
+    let s = format!("{}", v);
+    if s.chars().count() > WIDTH {
+        let mut s: String = s.chars().take(WIDTH - 3).collect();
+        s.push_str("...");
+        s
+    } else {
+        s
+    }
+}
+
+/// Describe `v`: its type, identity, heap size, and contents.
+pub(crate) fn description(vm: &VM, v: Value) -> String {
+    let mut out = String::new();
+    let o = &mut out;
+    let ty = match v.to_type() {
+        VType::Void => "the unspecified value",
+        VType::Nil => "the empty list",
+        VType::Bool => "a boolean",
+        VType::Integer => "a fixnum",
+        VType::Float => "a flonum",
+        VType::Symbol => "a symbol",
+        VType::Native => "a native procedure",
+        VType::Char => "a character",
+        VType::Port => "a port",
+        VType::Eof => "the end of file object",
+        VType::Database => "a database connection",
+        VType::Environment => "an environment",
+        VType::Slice => "a vector slice",
+        VType::Continuation => "a continuation",
+        VType::CharSet => "a char-set",
+        VType::Lambda => "a procedure",
+        VType::Pair => "a pair",
+        VType::Vec => "a vector",
+        VType::String => "a string",
+        VType::HashMap => "a hash table",
+        VType::Bytevector => "a bytevector",
+        VType::BigInt => "a bignum",
+    };
+    writeln!(o, "{} is {}", short(v), ty).unwrap();
+
+    if v.is_lambda() || v.is_pair() || v.is_vec() || v.is_string() || v.is_hashmap() || v.is_bytevector()
+        || v.is_bigint() {
+        writeln!(o, "  address: {:#x}", v.to_pointer()).unwrap();
+        writeln!(o, "  size: {} bytes", heap_size(v)).unwrap();
+    } else {
+        writeln!(o, "  immediate: {:#018x}", v.0).unwrap();
+    }
+
+    if v.is_symbol() {
+        writeln!(o, "  id: {}", *v.to_symbol()).unwrap();
+    } else if v.is_char() {
+        writeln!(o, "  code point: U+{:04X}", v.to_char() as u32).unwrap();
+    } else if v.is_native() {
+        let native = get_native(v.to_native());
+        writeln!(o, "  name: {}", native.name).unwrap();
+        writeln!(o, "  arguments: {}", native.arity).unwrap();
+    } else if v.is_port() {
+        let state = match vm.ports.get(v.to_port()) {
+            Some(Port::Input(_)) => "open input",
+            Some(Port::Output(_)) => "open output",
+            _ => "closed",
+        };
+        writeln!(o, "  state: {}", state).unwrap();
+    } else if v.is_environment() {
+        if let Some(env) = vm.environments.get(v.to_environment()) {
+            writeln!(o, "  bindings: {}", env.local_bindings().len()).unwrap();
+            writeln!(o, "  parent: {}", if env.parent().is_some() { "yes" } else { "none" }).unwrap();
+        }
+    } else if let Some(set) = charset::get(vm, v) {
+        writeln!(o, "  size: {} characters", set.size()).unwrap();
+    } else if v.is_lambda() {
+        let l = v.to_lambda();
+        writeln!(o, "  code: {} instructions", l.code.len()).unwrap();
+        writeln!(o, "  constants: {}", l.consts.len()).unwrap();
+        writeln!(o, "  environment: {} bindings", l.env.get_definitions().len()).unwrap();
+        if let Some(ref memo) = l.memo {
+            match memo.limit() {
+                Some(limit) => writeln!(o, "  memoized: {} of {} results", memo.len(), limit).unwrap(),
+                None => writeln!(o, "  memoized: {} results", memo.len()).unwrap(),
+            }
+        }
+        Box::into_raw(l);
+    } else if v.is_pair() {
+        writeln!(o, "  car: {}", short(v.car())).unwrap();
+        writeln!(o, "  cdr: {}", short(v.cdr())).unwrap();
+    } else if v.is_vec() {
+        let vec = v.to_vec();
+        writeln!(o, "  length: {}", vec.vec.len()).unwrap();
+        for (i, &e) in vec.vec.iter().enumerate().take(SHOWN) {
+            writeln!(o, "  {}: {}", i, short(e)).unwrap();
+        }
+        if vec.vec.len() > SHOWN {
+            writeln!(o, "  ...").unwrap();
+        }
+        Box::into_raw(vec);
+    } else if v.is_string() {
+        let s = v.to_string();
+        writeln!(o, "  length: {} characters, {} bytes", s.str.chars().count(), s.str.len()).unwrap();
+        Box::into_raw(s);
+    } else if v.is_bytevector() {
+        let b = v.to_bytevector();
+        writeln!(o, "  length: {}", b.bytes.len()).unwrap();
+        Box::into_raw(b);
+    } else if v.is_bigint() {
+        let b = v.to_bigint();
+        writeln!(o, "  bits: {}", b.n.bits()).unwrap();
+        Box::into_raw(b);
+    } else if v.is_hashmap() {
+        let m = v.to_hashmap();
+        writeln!(o, "  entries: {}", m.map.len()).unwrap();
+        writeln!(o, "  capacity: {}", m.map.capacity()).unwrap();
+        for (&k, &e) in m.map.iter().take(SHOWN) {
+            writeln!(o, "  {}: {}", short(k), short(e)).unwrap();
+        }
+        if m.map.len() > SHOWN {
+            writeln!(o, "  ...").unwrap();
+        }
+        Box::into_raw(m);
+    }
+    out
+}
+
+/// The bytes used by a heap object, including the buffers it owns but not the objects it refers
+/// to.
+fn heap_size(v: Value) -> usize {
+    if v.is_lambda() {
+        let l = v.to_lambda();
+        let size = size_of::<Lambda>() + l.code.len() * size_of::<::Operation>()
+            + l.consts.len() * size_of::<Value>();
+        Box::into_raw(l);
+        size
+    } else if v.is_pair() {
+        size_of::<Pair>()
+    } else if v.is_vec() {
+        let vec = v.to_vec();
+        let size = size_of::<SVec>() + vec.vec.capacity() * size_of::<Value>();
+        Box::into_raw(vec);
+        size
+    } else if v.is_string() {
+        let s = v.to_string();
+        let size = size_of::<SString>() + s.str.capacity();
+        Box::into_raw(s);
+        size
+    } else if v.is_bytevector() {
+        let b = v.to_bytevector();
+        let size = size_of::<SBytevector>() + b.bytes.capacity();
+        Box::into_raw(b);
+        size
+    } else if v.is_bigint() {
+        let b = v.to_bigint();
+        let size = size_of::<SBigInt>() + b.n.iter_u64_digits().len() * size_of::<u64>();
+        Box::into_raw(b);
+        size
+    } else if v.is_hashmap() {
+        let m = v.to_hashmap();
+        // Each bucket holds a key, a value, and a control byte
+        let size = size_of::<SHashMap>() + m.map.capacity() * (2 * size_of::<Value>() + 1);
+        Box::into_raw(m);
+        size
+    } else {
+        0
+    }
+}
+
+/// `(describe obj [port])` Print a description of `obj`.
+pub fn describe(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let d = description(vm, args[0]);
+    write_bytes(vm, "describe", args.get(1), d.as_bytes())
+}
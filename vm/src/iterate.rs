@@ -0,0 +1,91 @@
+//! Iteration over any collection.
+//!
+//! `for-each` and `fold` accept lists, vectors and their slices, strings, char-sets, hash tables,
+//! and input ports, so that collections don't need to be converted to lists first. Strings and
+//! char-sets give their characters, ports their lines, and hash tables a key and value for each
+//! entry, in no particular order.
+
+use {Value, VM};
+
+use {charset, port, vector};
+
+/// Call `f` with each element of `collection` in order.
+fn each<F>(vm: &mut VM, name: &str, collection: Value, mut f: F) -> Result<(), String>
+    where F: FnMut(&mut VM, &[Value]) -> Result<(), String>
+{
+    if collection.is_pair() || collection.is_nil() {
+        let mut l = collection;
+        while l.is_pair() {
+            f(vm, &[l.car()])?;
+            l = l.cdr();
+        }
+        if !l.is_nil() {
+            return Err(format!("{}: {} is not a proper list", name, collection));
+        }
+    } else if collection.is_vec() || collection.is_slice() {
+        // The procedure may change the length of the vector
+        let (vector, range) = vector::range(vm, name, collection)?;
+        let mut i = range.start;
+        while collection.is_vec() || i < range.end {
+            match vector::element(vector, i) {
+                Some(item) => f(vm, &[item])?,
+                None => break,
+            }
+            i += 1;
+        }
+    } else if collection.is_string() {
+        let s = collection.to_string();
+        let chars: Vec<char> = s.str.chars().collect();
+        Box::into_raw(s);
+        for c in chars {
+            f(vm, &[Value::Char(c)])?;
+        }
+    } else if let Some(set) = charset::get(vm, collection) {
+        // Char-sets never change, but the procedure may make more of them
+        let chars: Vec<char> = set.chars().collect();
+        for c in chars {
+            f(vm, &[Value::Char(c)])?;
+        }
+    } else if collection.is_hashmap() {
+        // The keys and values are kept alive by the table, as long as the procedure doesn't
+        // remove them
+        let m = collection.to_hashmap();
+        let entries: Vec<(Value, Value)> = m.map.iter().map(|(&k, &v)| (k, v)).collect();
+        Box::into_raw(m);
+        for (k, v) in entries {
+            f(vm, &[k, v])?;
+        }
+    } else if collection.is_port() {
+        loop {
+            let line = port::read_line(vm, &[collection]).map_err(|e| e.replacen("read-line", name, 1))?;
+            if !line.is_string() {
+                break;
+            }
+            f(vm, &[line])?;
+        }
+    } else {
+        return Err(format!("{}: {} is not a collection", name, collection));
+    }
+    Ok(())
+}
+
+/// `(for-each proc collection)` Call `proc` on each element of `collection`.
+pub fn for_each(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let proc = args[0];
+    each(vm, "for-each", args[1], |vm, item| vm.apply(proc, item).map(|_| ()))?;
+    Ok(Value::Void)
+}
+
+/// `(fold kons knil collection)` Combine the elements of `collection` with `(kons elem acc)`,
+/// starting from `knil`. For hash tables `kons` is called with `(kons key value acc)`.
+pub fn fold(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let kons = args[0];
+    let mut acc = args[1];
+    each(vm, "fold", args[2], |vm, item| {
+        let mut args = item.to_vec();
+        args.push(acc);
+        acc = vm.apply(kons, &args)?;
+        Ok(())
+    })?;
+    Ok(acc)
+}
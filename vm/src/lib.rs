@@ -1,36 +1,145 @@
 #![feature(lazy_cell)]
 
+extern crate getrandom;
+extern crate num_bigint;
 extern crate string_interner;
+#[cfg(feature = "compress")]
+extern crate flate2;
+#[cfg(feature = "crypto")]
+extern crate hmac;
+#[cfg(feature = "crypto")]
+extern crate md5;
+#[cfg(feature = "icu")]
+extern crate icu_casemap;
+#[cfg(feature = "icu")]
+extern crate icu_collator;
+#[cfg(feature = "icu")]
+extern crate icu_locid;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+#[cfg(feature = "crypto")]
+extern crate sha1;
+#[cfg(feature = "crypto")]
+extern crate sha2;
+#[cfg(feature = "config")]
+extern crate toml;
+#[cfg(feature = "config")]
+extern crate yaml_rust;
 
 mod asm;
 mod bytecode;
+mod bytevector;
+mod character;
+mod charset;
+#[cfg(feature = "compress")]
+mod compress;
+mod condition;
+mod continuation;
+mod convert;
+mod coverage;
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "crypto")]
+mod crypto;
+pub mod disasm;
 mod environment;
+mod files;
 mod gc;
+mod hashtable;
+#[cfg(feature = "hooks")]
+mod hook;
 mod init;
+mod inline_cache;
+mod inspect;
+mod iterate;
+mod list;
+mod locale;
+mod logging;
+mod memo;
+mod native;
+mod number;
+mod path;
+mod port;
+mod prelude;
+mod program;
+mod property;
+mod random;
+mod reflect;
+mod serialize;
+mod signal;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod string;
+mod table;
+mod terminal;
+mod timer;
+mod transfer;
 mod value;
-
-pub use asm::{assemble, GotoValue, ASM, Register};
-pub use environment::Environment;
+mod value_hash;
+mod vector;
+#[cfg(feature = "watch")]
+mod watch;
+mod xml;
+
+pub use asm::{assemble, assemble_pooled, ConstantPool, GotoValue, ASM, Register};
+pub use condition::{Condition, Restart};
+pub use convert::{FromValue, IntoValue};
+pub use coverage::{coverage_probe, Probe};
+pub use environment::{Environment, Strictness};
 pub use gc::*;
+#[cfg(feature = "hooks")]
+pub use hook::VmHook;
 pub use init::init_env;
+#[cfg(feature = "log")]
+pub use logging::forward_to_log;
+pub use logging::{Level, LogSink};
+pub use native::{define_closure, define_module_native, define_native, integer_arg, list_arg, make_list, register_native,
+                 string_arg, Arity, Native, NativeClosure, NativeError, NativeFn};
 pub use bytecode::{Instruction, Operation};
+pub use prelude::Prelude;
+pub use signal::{catch_signal, deliver_signal, Signal};
+pub use terminal::terminal_colors;
+pub use serialize::{write_code, write_definition, write_header, CodeReader, Features, LoadError, FORMAT_VERSION, MAGIC};
+pub use transfer::deep_copy;
 pub use value::Value;
 pub use value::heap_repr;
-
+pub use value_hash::value_hash;
+#[cfg(feature = "crypto")]
+pub use crypto::value_digest;
+
+use condition::{ConditionHandler, MachineState};
+use continuation::{is_call_cc, Continuation};
+use inline_cache::InlineCache;
+use port::Port;
+use table::Table;
+use timer::Timer;
 use value::VType;
 
 use string_interner::Symbol;
 
-use std::{fmt, io, mem};
+use std::{fmt, io, mem, ops};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::io::Write;
 
+// The most returned frames kept for reuse, bounding the memory held after deep recursion
+const FRAME_POOL_SIZE: usize = 64;
+
 /// A Virtual Machine for Scheme.
 #[derive(Debug)]
 pub struct VM {
     debug: bool,
     step: usize,
-    operations: Vec<Operation>,
-    constants: Vec<Value>,
+    // The instructions left to run before the computation is interrupted, see `set_fuel`
+    fuel: Option<usize>,
+    // The most bytes the heap shared by every machine may use, see `set_process_heap_limit`
+    heap_limit: Option<usize>,
+    operations: Arc<[Operation]>,
+    constants: Arc<[Value]>,
     environment: Environment,
     stack: Vec<Value>,
     kontinue_stack: Vec<usize>,
@@ -39,6 +148,88 @@ pub struct VM {
     kontinue: usize,
     registers: [Value; 32],
     saved_state: Vec<SaveState>,
+    conditions: Vec<Condition>,
+    condition_handler: Option<ConditionHandler>,
+    // The identity hashes of the procedures being traced and the names they are printed with. Their
+    // bits would be reused by whatever is allocated in their place once they are collected.
+    traced: HashMap<u64, String>,
+    trace_depth: usize,
+    collections: usize,
+    // Whether `gc_time` is measured, see `set_gc_timing`
+    time_gc: bool,
+    gc_time: Duration,
+    // Open ports, indexed by `Value::Port`. The slot of a closed port is reused.
+    ports: Table<Port>,
+    // Open database connections, indexed by `Value::Database`
+    #[cfg(feature = "sqlite")]
+    databases: Vec<Option<rusqlite::Connection>>,
+    // Environments which have been made first-class, indexed by `Value::Environment`
+    environments: Vec<Environment>,
+    // Views of vectors, indexed by `Value::Slice`. Those which can no longer be reached are freed
+    // by `gc`.
+    slices: Table<vector::Slice>,
+    // Sets of characters, indexed by `Value::CharSet` after the standard sets
+    char_sets: Vec<charset::CharSet>,
+    // Warnings which have not yet been reported, e.g. for redefinitions
+    warnings: Vec<String>,
+    // The largest the stack has been since the last call to `reset_peak_stack`
+    peak_stack: usize,
+    // Frames of returned procedures which nothing refers to, reused by later calls
+    frame_pool: Vec<Environment>,
+    // Computations set aside while a native procedure applies a procedure
+    applying: Vec<MachineState>,
+    // Identifies the computation running for each entry of `applying`
+    applications: Vec<usize>,
+    next_application: usize,
+    // Continuations captured by `call-with-current-continuation`, indexed by `Value::Continuation`.
+    // Those which can no longer be reached are freed by `gc`.
+    continuations: Table<Continuation>,
+    // A continuation being invoked and the value passed to it, while the nested computations it
+    // was not captured in are abandoned
+    throwing: Option<(Value, Value)>,
+    frames_reused: usize,
+    // The inline caches of the call sites in top level code, indexed by slot
+    inline_caches: Vec<InlineCache>,
+    cache_hits: usize,
+    // The probes of code compiled for coverage, indexed by the argument to `coverage-probe`
+    probes: Vec<Probe>,
+    // The path of the running program and its arguments, returned by `command-line`
+    command_line: Vec<String>,
+    // The signals handled by the `with-signal-handler`s running, innermost last
+    signal_handlers: Vec<Signal>,
+    // A signal raised where one of them handles it, until the handler is called
+    caught_signal: Option<Signal>,
+    // The handlers installed by the `with-restart-handler`s running, innermost last
+    restart_handlers: Vec<Value>,
+    // The restarts of the conditions they are handling, innermost last
+    handling: Vec<Vec<Restart>>,
+    // The restart a handler invoked, while the handler is abandoned
+    invoked_restart: Option<Restart>,
+    // The index of a handler which returned and its result, while the computation it was
+    // installed around is abandoned
+    caught: Option<(usize, Value)>,
+    // Procedures scheduled by `after`, in the order they are due
+    timers: Vec<Timer>,
+    // The time while the clock is virtual, see `set_virtual_clock`
+    clock: Option<Instant>,
+    // Values kept alive for whoever is running the machine, see `retain`
+    retained: Vec<Value>,
+    // Whether escape codes for colors are written
+    colors: bool,
+    // The colors of the `with-color`s running, innermost last
+    active_colors: Vec<u8>,
+    // Where logged messages go, or standard error if `None`
+    log_sink: Option<logging::Sink>,
+    // The least important messages which are logged
+    log_level: Level,
+    // The property lists of symbols, see `put!`
+    properties: HashMap<Symbol, Vec<(Value, Value)>>,
+    // Told about instructions, calls and returns, see `set_hook`
+    #[cfg(feature = "hooks")]
+    hook: Option<hook::Hook>,
+    // Paths watched by `watch-path`, or `None` once unwatched
+    #[cfg(feature = "watch")]
+    watches: Vec<Option<watch::Watch>>,
 }
 
 impl Default for VM {
@@ -56,8 +247,10 @@ impl VM {
         VM {
             debug: false,
             step: 0,
-            operations: vec![],
-            constants: vec![],
+            fuel: None,
+            heap_limit: None,
+            operations: Arc::default(),
+            constants: Arc::default(),
             environment: Environment::new(),
             stack: vec![],
             kontinue_stack: vec![],
@@ -65,11 +258,59 @@ impl VM {
             kontinue: 0,
             registers: registers,
             saved_state: vec![],
+            conditions: vec![],
+            condition_handler: None,
+            traced: HashMap::new(),
+            trace_depth: 0,
+            collections: 0,
+            time_gc: false,
+            gc_time: Duration::new(0, 0),
+            ports: port::standard_ports(),
+            #[cfg(feature = "sqlite")]
+            databases: vec![],
+            environments: vec![],
+            slices: Table::default(),
+            char_sets: vec![],
+            warnings: vec![],
+            peak_stack: 0,
+            frame_pool: vec![],
+            applying: vec![],
+            applications: vec![],
+            next_application: 0,
+            continuations: Table::default(),
+            throwing: None,
+            frames_reused: 0,
+            inline_caches: vec![],
+            cache_hits: 0,
+            probes: vec![],
+            command_line: vec![],
+            signal_handlers: vec![],
+            caught_signal: None,
+            restart_handlers: vec![],
+            handling: vec![],
+            invoked_restart: None,
+            caught: None,
+            timers: vec![],
+            clock: None,
+            retained: vec![],
+            colors: terminal::terminal_colors(),
+            active_colors: vec![],
+            log_sink: None,
+            log_level: Level::Info,
+            properties: HashMap::new(),
+            #[cfg(feature = "hooks")]
+            hook: None,
+            #[cfg(feature = "watch")]
+            watches: vec![],
         }
     }
 
     /// Run the currently loaded code.
     pub fn run(&mut self) {
+        // A continuation invoked by a procedure applied from outside the machine had nowhere to go
+        self.throwing = None;
+        self.invoked_restart = None;
+        self.caught = None;
         if self.debug {
             loop {
                 print!("> ");
@@ -111,7 +352,16 @@ impl VM {
                         };
 
                         for _ in 0..i {
-                            self.step();
+                            match self.step() {
+                                Ok(()) => (),
+                                Err(VmError::Throw) => {
+                                    self.throw();
+                                }
+                                Err(e) => {
+                                    self.signal(e);
+                                    break;
+                                }
+                            }
                         }
                     }
                     // TODO
@@ -124,6 +374,14 @@ impl VM {
                             println!("Invalid register name: {}", input);
                         }
                     }
+                    "i" => {
+                        let input = &input[1..].trim();
+                        if let Some(r) = Register::from_str(input) {
+                            print!("{}", inspect::description(self, self.load_register(r)));
+                        } else {
+                            println!("Invalid register name: {}", input);
+                        }
+                    }
                     _ => {
                         println!("Unknown command");
                         continue;
@@ -137,29 +395,53 @@ impl VM {
 
     fn _run(&mut self) {
         while self.pc < self.operations.len() || !self.saved_state.is_empty() {
-            self.step();
+            match self.step() {
+                Ok(()) => (),
+                // Abandon nested computations until reaching the one the continuation belongs to
+                Err(VmError::Throw) => if !self.throw() {
+                    return;
+                },
+                Err(e) => if self.signal(e) {
+                    return;
+                },
+            }
             if self.pc > self.operations.len() {
                 panic!("Bad jump");
             }
         }
     }
 
-    fn step(&mut self) {
+    fn step(&mut self) -> Result<(), VmError> {
         if self.pc > self.operations.len() {
             panic!("Bad jump");
         } else if self.pc == self.operations.len() {
             if self.saved_state.is_empty() {
-                return;
+                return Ok(());
             } else {
                 if self.debug {
                     println!("ending call");
                 }
                 // Restore the saved program counter, code, and environment
-                let SaveState { pc, code, consts, env, sp, fp } = self.saved_state.pop().unwrap();
+                #[cfg(feature = "hooks")]
+                self.with_hook(|hook, vm| {
+                    let procedure = vm.saved_state.last().unwrap().procedure;
+                    hook.ret(vm, procedure, vm.load_register(Register(0)))
+                });
+                let SaveState { pc, code, consts, env, sp, fp, traced, memo, .. } = self.saved_state.pop().unwrap();
+                if traced {
+                    self.trace_return();
+                }
+                for (f, args) in memo {
+                    let result = self.load_register(Register(0));
+                    let mut lambda = f.to_lambda();
+                    lambda.memo.as_mut().unwrap().insert(args, result);
+                    Box::into_raw(lambda);
+                }
                 self.pc = pc;
                 self.operations = code;
                 self.constants = consts;
-                self.environment = env;
+                let frame = mem::replace(&mut self.environment, env);
+                self.recycle_frame(frame);
                 self.assign_sp(sp);
                 self.stack.resize(sp.to_integer() as usize, Value::Void);
                 self.assign_fp(fp);
@@ -172,8 +454,31 @@ impl VM {
         }
 
         let op = self.operations[self.pc];
+        #[cfg(feature = "hooks")]
+        self.with_hook(|hook, vm| hook.instruction(vm, vm.pc, op));
         self.step += 1;
         self.pc += 1;
+        // Retrying the instruction continues the computation after the signal
+        if let Some(signal) = signal::take_pending() {
+            if self.signal_handlers.contains(&signal) {
+                self.caught_signal = Some(signal);
+            }
+            return Err(VmError::Interrupt(signal));
+        }
+        match self.fuel {
+            Some(0) => return Err(VmError::OutOfFuel),
+            Some(ref mut fuel) => *fuel -= 1,
+            None => (),
+        }
+        if let Some(limit) = self.heap_limit {
+            if heap_bytes() > limit {
+                // What was allocated may have become garbage since the last collection
+                self.gc();
+                if heap_bytes() > limit {
+                    return Err(VmError::OutOfMemory(heap_bytes(), limit));
+                }
+            }
+        }
         match op.instruction() {
             Instruction::LoadContinue => self.load_kontinue(op),
             Instruction::SaveContinue => self.save_kontinue(),
@@ -187,40 +492,231 @@ impl VM {
             Instruction::Goto => self.goto(op),
             Instruction::GotoIf => self.goto_if(op),
             Instruction::GotoIfNot => self.goto_if_not(op),
-            Instruction::Add => self.add(op),
-            Instruction::Sub => self.sub(op),
-            Instruction::Mul => self.mul(op),
-            Instruction::Eq => self.eq(op),
-            Instruction::LT => self.lt(op),
+            Instruction::Add => self.add(op)?,
+            Instruction::Sub => self.sub(op)?,
+            Instruction::Mul => self.mul(op)?,
+            Instruction::Eq => self.eq(op)?,
+            Instruction::LT => self.lt(op)?,
             Instruction::StringToSymbol => self.string_to_symbol(op),
             Instruction::Cons => self.cons(op),
-            Instruction::Car => self.car(op),
-            Instruction::Cdr => self.cdr(op),
-            Instruction::Set => self.set(op),
+            Instruction::Car => self.car(op)?,
+            Instruction::Cdr => self.cdr(op)?,
+            Instruction::VectorRef => self.vector_ref(op)?,
+            Instruction::Set => self.set(op)?,
             Instruction::SetCar => self.set_car(op),
             Instruction::SetCdr => self.set_cdr(op),
-            Instruction::Define => self.define(op),
-            Instruction::Lookup => if let Err(e) = self.lookup(op) {
-                self.handle_error(e);
-            },
-            Instruction::Call => if let Err(e) = self.call(op) {
-                self.handle_error(e);
-            },
-            Instruction::TailCall => if let Err(e) = self.tail_call(op) {
-                self.handle_error(e);
-            },
+            Instruction::Define => self.define(op)?,
+            Instruction::Lookup => self.lookup(op)?,
+            Instruction::LookupCached => self.lookup_cached(op)?,
+            Instruction::Call => self.call(op)?,
+            Instruction::TailCall => self.tail_call(op)?,
+            Instruction::CallPrimitive => self.call_primitive(op)?,
+            Instruction::Trace => self.trace(op),
+            Instruction::Untrace => self.untrace(op),
+            Instruction::JumpTable => self.jump_table(op),
+            Instruction::BinarySearch => self.binary_search(op),
+            Instruction::PerfectHash => self.perfect_hash(op),
             Instruction::Return => self.pc = self.operations.len(),
         }
         self.gc();
+        Ok(())
     }
 
-    fn handle_error(&mut self, e: VmError) {
-        // TODO
-        println!("{}", e);
+    /// Signal a condition for `e`. If a handler chooses a restart it is invoked immediately,
+    /// otherwise the current computation is suspended and `true` is returned. A handler installed
+    /// by `with-restart-handler` is consulted before the one given to `set_condition_handler`.
+    fn signal(&mut self, e: VmError) -> bool {
+        let op = self.operations[self.pc - 1];
+        let condition = Condition::new(e, op);
+        // Leaving a handler, or what a handler returned from, is not handled again. There is no
+        // fuel for a handler to run with, and a signal `with-signal-handler` handles goes to it.
+        let unwinding = self.invoked_restart.is_some() || self.caught.is_some();
+        let restart = if unwinding {
+            None
+        } else if !self.restart_handlers.is_empty() && !condition.is_out_of_fuel() && self.caught_signal.is_none() {
+            self.handle(&condition)
+        } else {
+            match self.condition_handler {
+                Some(ConditionHandler(ref mut handler)) => handler(&condition),
+                None => None,
+            }
+        };
+
+        if let Some(restart) = restart {
+            self.restart(&condition, restart)
+        } else {
+            let mut condition = condition;
+            condition.state = Some(self.suspend());
+            self.conditions.push(condition);
+            true
+        }
+    }
+
+    /// Call the innermost handler installed by `with-restart-handler` for `condition`, with only
+    /// the handlers outside it installed. Returns the restart it invoked. If it returned instead,
+    /// the computation it was installed around is abandoned with its result.
+    fn handle(&mut self, condition: &Condition) -> Option<Restart> {
+        let handler = self.restart_handlers.pop().unwrap();
+        let index = self.restart_handlers.len();
+        self.handling.push(condition.restarts());
+        // Over the limit of the heap, the handler would have no room to run
+        let heap_limit = self.heap_limit;
+        if condition.is_out_of_memory() {
+            self.heap_limit = None;
+        }
+        let kind = Value::Symbol(string_interner::get_symbol(condition.kind().to_string()));
+        let result = self.apply(handler, &[kind, Value::String(condition.message())]);
+        self.heap_limit = heap_limit;
+        self.handling.pop();
+        self.restart_handlers.push(handler);
+        match (self.invoked_restart.take(), result) {
+            (Some(restart), _) => Some(restart),
+            (None, Ok(v)) => {
+                self.caught = Some((index, v));
+                None
+            }
+            // A handler which fails leaves the condition unhandled
+            (None, Err(_)) => None,
+        }
+    }
+
+    /// Apply `restart` for `condition` to the running machine. Returns `true` if the machine
+    /// should stop running.
+    fn restart(&mut self, condition: &Condition, restart: Restart) -> bool {
+        match restart {
+            Restart::Abort => {
+                self.abort();
+                true
+            }
+            Restart::Retry if condition.can_retry() => {
+                self.pc -= 1;
+                false
+            }
+            Restart::UseValue(v) if condition.can_use_value() => {
+                self.assign_register(condition.target(), v);
+                if condition.op().instruction() == Instruction::TailCall {
+                    self.pc = self.operations.len();
+                }
+                false
+            }
+            _ => {
+                self.abort();
+                true
+            }
+        }
+    }
+
+    /// Move the current computation out of the machine, leaving it ready to evaluate code in the
+    /// top level environment.
+    fn suspend(&mut self) -> MachineState {
+        let top = self.global_environment();
+        let mut registers = [Value::Nil; 32];
+        registers[29] = Value::Integer(0);
+        registers[30] = Value::Integer(0);
+
+        let state = MachineState {
+            pc: self.pc,
+            operations: mem::take(&mut self.operations),
+            constants: mem::take(&mut self.constants),
+            environment: mem::replace(&mut self.environment, top),
+            stack: mem::take(&mut self.stack),
+            kontinue: self.kontinue,
+            kontinue_stack: mem::take(&mut self.kontinue_stack),
+            registers: mem::replace(&mut self.registers, registers),
+            saved_state: mem::take(&mut self.saved_state),
+        };
+        self.pc = 0;
+        self.kontinue = 0;
+        state
+    }
+
+    fn resume(&mut self, state: MachineState) {
+        self.pc = state.pc;
+        self.operations = state.operations;
+        self.constants = state.constants;
+        self.environment = state.environment;
+        self.stack = state.stack;
+        self.kontinue = state.kontinue;
+        self.kontinue_stack = state.kontinue_stack;
+        self.registers = state.registers;
+        self.saved_state = state.saved_state;
+    }
+
+    /// Discard every suspended computation and return to the top level.
+    fn abort(&mut self) {
+        if let Some(c) = self.conditions.first_mut() {
+            let state = c.state.take().unwrap();
+            self.environment = match state.saved_state.first() {
+                Some(s) => s.env.clone(),
+                None => state.environment,
+            };
+        } else if let Some(s) = self.saved_state.first() {
+            self.environment = s.env.clone();
+        }
+        self.conditions.clear();
         self.saved_state.clear();
         self.pc = 0;
-        self.operations.clear();
+        self.operations = Arc::default();
         self.stack.clear();
+        self.kontinue_stack.clear();
+        // The traced calls and the handlers installed by the computations never return
+        self.trace_depth = 0;
+        self.restart_handlers.clear();
+        self.handling.clear();
+    }
+
+    /// Set the path of the running program and its arguments, given to it by `command-line` and
+    /// `parse-args`.
+    pub fn set_command_line(&mut self, args: Vec<String>) {
+        self.command_line = args;
+    }
+
+    /// Take the warnings produced since the last call, oldest first.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        mem::take(&mut self.warnings)
+    }
+
+    /// The most recent unhandled condition, if any.
+    pub fn condition(&self) -> Option<&Condition> {
+        self.conditions.last()
+    }
+
+    /// The number of suspended computations waiting on a restart.
+    pub fn condition_depth(&self) -> usize {
+        self.conditions.len()
+    }
+
+    /// Resume the computation suspended by the most recent condition using `restart`. Call `run`
+    /// afterwards to continue execution. Returns `false` if the restart is not applicable, in
+    /// which case nothing is changed.
+    pub fn invoke_restart(&mut self, restart: Restart) -> bool {
+        let applicable = match (self.conditions.last(), restart) {
+            (None, _) => false,
+            (Some(_), Restart::Abort) => true,
+            (Some(c), Restart::Retry) => c.can_retry(),
+            (Some(c), Restart::UseValue(_)) => c.can_use_value(),
+        };
+        if !applicable {
+            return false;
+        }
+
+        if restart == Restart::Abort {
+            self.abort();
+            return true;
+        }
+
+        let mut condition = self.conditions.pop().unwrap();
+        self.resume(condition.state.take().unwrap());
+        self.restart(&condition, restart);
+        true
+    }
+
+    /// Install a handler which is consulted whenever a condition is signalled. Returning a
+    /// restart resumes execution immediately, returning `None` suspends the computation as usual.
+    pub fn set_condition_handler<F>(&mut self, handler: F)
+        where F: FnMut(&Condition) -> Option<Restart> + 'static
+    {
+        self.condition_handler = Some(ConditionHandler(Box::new(handler)));
     }
 
     /// Reset the machine. Keeps the current code and constants.
@@ -231,6 +727,12 @@ impl VM {
         mem::swap(&mut new, self);
     }
 
+    /// Keep `values` alive until this is next called, e.g. the constants of code which has been
+    /// compiled but not yet loaded.
+    pub fn retain(&mut self, values: Vec<Value>) {
+        self.retained = values;
+    }
+
     /// Sets the vm to print debug information.
     pub fn set_debug(&mut self) {
         self.debug = true;
@@ -250,15 +752,91 @@ impl VM {
         println!("{}: {}", r, self.load_register(r));
     }
 
+    /// Returns the number of instructions executed so far.
+    pub fn steps(&self) -> usize {
+        self.step
+    }
+
+    /// Limit the instructions run from now on to `fuel`, or remove the limit if `None`. When the
+    /// fuel runs out the computation is interrupted by a condition, which `Retry` continues once
+    /// there is more. Fuel running out in a procedure applied by a native procedure, such as one
+    /// given to `for-each`, abandons it like any other condition, so the computation fails instead.
+    pub fn set_fuel(&mut self, fuel: Option<usize>) {
+        self.fuel = fuel;
+    }
+
+    /// The instructions left to run before the computation is interrupted, if they are limited.
+    pub fn fuel(&self) -> Option<usize> {
+        self.fuel
+    }
+
+    /// Limit the bytes the heap of the process may use while this machine runs to `limit`,
+    /// approximately, or remove the limit if `None`. The heap is shared by every machine, so the
+    /// limit is on everything allocated in the process, not only what this machine allocated.
+    ///
+    /// When the heap is over the limit after a collection the computation signals an
+    /// `out-of-memory` condition rather than letting the process run out of memory. A handler
+    /// installed by `with-restart-handler` runs without the limit, so that it has room to run, and
+    /// may retry once it has let go of enough, or return to abandon the computation.
+    pub fn set_process_heap_limit(&mut self, limit: Option<usize>) {
+        self.heap_limit = limit;
+    }
+
+    /// The most bytes the heap of the process may use while this machine runs, if they are limited.
+    pub fn process_heap_limit(&self) -> Option<usize> {
+        self.heap_limit
+    }
+
+    /// Check that `bytes` more can be allocated without going over the limit of the heap, for the
+    /// native procedure `name` to call before allocating a large object.
+    pub fn check_allocation(&mut self, name: &str, bytes: usize) -> Result<(), String> {
+        match self.heap_limit {
+            Some(limit) if heap_bytes().saturating_add(bytes) > limit => {
+                Err(format!("{}: allocating {} bytes would put the heap over its limit of {}", name, bytes, limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Measure the time spent collecting garbage, reported in `Stats::gc_time`, or stop measuring
+    /// it. It is off by default, since the machine collects after every instruction and reading
+    /// the clock each time would slow down everything it runs.
+    pub fn set_gc_timing(&mut self, on: bool) {
+        self.time_gc = on;
+    }
+
+    /// Returns the counters kept by the machine. Subtract two snapshots to get the cost of the
+    /// code run in between.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            steps: self.step,
+            allocations: allocations(),
+            collections: self.collections,
+            gc_time: self.gc_time,
+            frames_reused: self.frames_reused,
+            cache_hits: self.cache_hits,
+        }
+    }
+
     /// Returns the current stack size.
     pub fn stack_size(&self) -> usize {
         self.stack.len()
     }
 
+    /// The largest stack size since the machine was created or `reset_peak_stack` was called.
+    pub fn peak_stack(&self) -> usize {
+        self.peak_stack
+    }
+
+    /// Start measuring the peak stack size from the current stack size.
+    pub fn reset_peak_stack(&mut self) {
+        self.peak_stack = self.stack.len();
+    }
+
     /// Load code into the machine.
     pub fn load_code(&mut self, code: Vec<Operation>, consts: Vec<Value>) {
-        self.operations = code;
-        self.constants = consts;
+        self.operations = code.into();
+        self.constants = consts.into();
         self.pc = 0;
     }
 
@@ -293,6 +871,7 @@ impl VM {
     }
 
     pub fn assign_environment(&mut self, env: Environment) {
+        env.add_root();
         self.environment = env;
     }
 
@@ -300,6 +879,14 @@ impl VM {
         self.environment.get_definitions()
     }
 
+    /// The top level environment, even while a procedure is running.
+    pub fn global_environment(&self) -> Environment {
+        match self.saved_state.first() {
+            Some(s) => s.env.clone(),
+            None => self.environment.clone(),
+        }
+    }
+
     /// Convert `symbol` to a Symbol.
     pub fn intern_symbol(symbol: String) -> Symbol {
         string_interner::get_symbol(symbol)
@@ -330,6 +917,7 @@ impl VM {
 
     fn save(&mut self, op: Operation) {
         self.stack.push(self.load_register(op.save_register()));
+        self.peak_stack = self.peak_stack.max(self.stack.len());
 
         let mut sp = self.load_sp().to_integer();
         sp += 1;
@@ -361,13 +949,11 @@ impl VM {
     }
 
     fn make_closure(&mut self, op: Operation) {
-        let pointer = self.constants[op.loadconst_constant()];
-        let mut lambda = pointer.to_lambda();
-        // TODO extend env?
-        (*lambda).env = self.environment.extend();
-        self.assign_register(op.makeclosure_register(), pointer);
+        let template = self.constants[op.makeclosure_constant()].to_lambda();
+        let closure = Value::Closure(self.environment.extend(), template.code.clone(), template.consts.clone());
         // Make sure this value isn't freed.
-        Box::into_raw(lambda);
+        Box::into_raw(template);
+        self.assign_register(op.makeclosure_register(), closure);
     }
 
     fn mov(&mut self, op: Operation) {
@@ -407,34 +993,116 @@ impl VM {
         }
     }
 
-    fn add(&mut self, op: Operation) {
-        let left = self.load_register(op.add_left()).to_integer();
-        let right = self.load_register(op.add_right()).to_integer();
-        self.assign_register(op.add_register(), Value::Integer(left + right));
+    fn jump_table(&mut self, op: Operation) {
+        let key = self.load_register(op.jumptable_register());
+        let table = self.constants[op.jumptable_table()].to_vec();
+        let min = table.vec[0].to_integer() as i64;
+        let mut target = table.vec[1];
+        if key.is_integer() {
+            let i = key.to_integer() as i64 - min;
+            if i >= 0 && (i as usize) < table.vec.len() - 2 {
+                target = table.vec[i as usize + 2];
+            }
+        }
+        if self.debug {
+            println!("jump to {}", target);
+        }
+        self.pc = target.to_integer() as usize;
+        // Make sure this value isn't freed.
+        Box::into_raw(table);
+    }
+
+    fn binary_search(&mut self, op: Operation) {
+        let key = *self.load_register(op.bsearch_register());
+        let table = self.constants[op.bsearch_table()].to_vec();
+        let mut target = table.vec[0];
+        let (mut low, mut high) = (0, table.vec.len() / 2);
+        while low < high {
+            let mid = (low + high) / 2;
+            let k = *table.vec[2 * mid + 1];
+            if k == key {
+                target = table.vec[2 * mid + 2];
+                break;
+            } else if k < key {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if self.debug {
+            println!("jump to {}", target);
+        }
+        self.pc = target.to_integer() as usize;
+        // Make sure this value isn't freed.
+        Box::into_raw(table);
     }
 
-    fn sub(&mut self, op: Operation) {
-        let left = self.load_register(op.sub_left()).to_integer();
-        let right = self.load_register(op.sub_right()).to_integer();
-        self.assign_register(op.sub_register(), Value::Integer(left - right));
+    fn perfect_hash(&mut self, op: Operation) {
+        let key = self.load_register(op.phash_register());
+        let table = self.constants[op.phash_table()].to_vec();
+        let mut target = table.vec[0];
+        if key.is_symbol() {
+            let multiplier = table.vec[1].to_integer() as u32;
+            let bits = ((table.vec.len() - 2) / 2).trailing_zeros();
+            let i = asm::symbol_hash(*key.to_symbol(), multiplier, bits);
+            if table.vec[2 + 2 * i] == key {
+                target = table.vec[3 + 2 * i];
+            }
+        }
+        if self.debug {
+            println!("jump to {}", target);
+        }
+        self.pc = target.to_integer() as usize;
+        // Make sure this value isn't freed.
+        Box::into_raw(table);
+    }
+
+    fn add(&mut self, op: Operation) -> Result<(), VmError> {
+        let left = self.load_register(op.add_left());
+        let right = self.load_register(op.add_right());
+        let sum = number::arithmetic("+", left, right, i32::checked_add, |a, b| a + b, |a, b| a + b)
+            .map_err(VmError::User)?;
+        self.assign_register(op.add_register(), sum);
+        Ok(())
     }
 
-    fn mul(&mut self, op: Operation) {
-        let left = self.load_register(op.mul_left()).to_integer();
-        let right = self.load_register(op.mul_right()).to_integer();
-        self.assign_register(op.mul_register(), Value::Integer(left * right));
+    fn sub(&mut self, op: Operation) -> Result<(), VmError> {
+        let left = self.load_register(op.sub_left());
+        let right = self.load_register(op.sub_right());
+        let difference = number::arithmetic("-", left, right, i32::checked_sub, |a, b| a - b, |a, b| a - b)
+            .map_err(VmError::User)?;
+        self.assign_register(op.sub_register(), difference);
+        Ok(())
+    }
+
+    fn mul(&mut self, op: Operation) -> Result<(), VmError> {
+        let left = self.load_register(op.mul_left());
+        let right = self.load_register(op.mul_right());
+        let product = number::arithmetic("*", left, right, i32::checked_mul, |a, b| a * b, |a, b| a * b)
+            .map_err(VmError::User)?;
+        self.assign_register(op.mul_register(), product);
+        Ok(())
     }
 
-    fn eq(&mut self, op: Operation) {
+    fn eq(&mut self, op: Operation) -> Result<(), VmError> {
         let left = self.load_register(op.eq_left());
         let right = self.load_register(op.eq_right());
-        self.assign_register(op.eq_register(), Value::Bool(left == right));
+        // Numbers are compared by value, whatever their exactness
+        let eq = if number::is_number(left) && number::is_number(right) {
+            number::compare("=", left, right).map_err(VmError::User)? == Some(Ordering::Equal)
+        } else {
+            left == right
+        };
+        self.assign_register(op.eq_register(), Value::Bool(eq));
+        Ok(())
     }
 
-    fn lt(&mut self, op: Operation) {
-        let left = self.load_register(op.lt_left()).to_integer();
-        let right = self.load_register(op.lt_right()).to_integer();
-        self.assign_register(op.lt_register(), Value::Bool(left < right));
+    fn lt(&mut self, op: Operation) -> Result<(), VmError> {
+        let left = self.load_register(op.lt_left());
+        let right = self.load_register(op.lt_right());
+        let lt = number::compare("<", left, right).map_err(VmError::User)? == Some(Ordering::Less);
+        self.assign_register(op.lt_register(), Value::Bool(lt));
+        Ok(())
     }
 
     fn string_to_symbol(&mut self, op: Operation) {
@@ -455,22 +1123,53 @@ impl VM {
         self.assign_register(op.cons_register(), pointer);
     }
 
-    fn car(&mut self, op: Operation) {
-        let car = self.load_register(op.car_from()).car();
-        self.assign_register(op.car_to(), car);
+    fn car(&mut self, op: Operation) -> Result<(), VmError> {
+        let pair = self.load_register(op.car_from());
+        if !pair.is_pair() {
+            return Err(VmError::User(format!("car: {} is not a pair", pair)));
+        }
+        self.assign_register(op.car_to(), pair.car());
+        Ok(())
     }
 
-    fn cdr(&mut self, op: Operation) {
-        let cdr = self.load_register(op.cdr_from()).cdr();
-        self.assign_register(op.cdr_to(), cdr);
+    fn cdr(&mut self, op: Operation) -> Result<(), VmError> {
+        let pair = self.load_register(op.cdr_from());
+        if !pair.is_pair() {
+            return Err(VmError::User(format!("cdr: {} is not a pair", pair)));
+        }
+        self.assign_register(op.cdr_to(), pair.cdr());
+        Ok(())
     }
 
-    fn set(&mut self, op: Operation) {
+    fn vector_ref(&mut self, op: Operation) -> Result<(), VmError> {
+        let v = self.load_register(op.vectorref_vector());
+        let k = self.load_register(op.vectorref_index());
+        let (vector, range) = vector::range(self, "vector-ref", v).map_err(VmError::User)?;
+        let element = if k.is_integer() && k.to_integer() >= 0 && (k.to_integer() as usize) < range.len() {
+            vector::element(vector, range.start + k.to_integer() as usize)
+        } else {
+            None
+        };
+        match element {
+            Some(e) => {
+                self.assign_register(op.vectorref_register(), e);
+                Ok(())
+            }
+            None => Err(VmError::User(format!("vector-ref: {} is not a valid index", k))),
+        }
+    }
+
+    fn set(&mut self, op: Operation) -> Result<(), VmError> {
         let n = self.load_register(op.set_name());
         assert!(n.is_symbol());
         let name = n.to_symbol();
         let value = self.load_register(op.set_value());
-        self.environment.set_variable_value(name, value);
+        if self.environment.strictness() == Strictness::Permissive {
+            self.environment.set_variable_value(name, value);
+        } else if !self.environment.assign_variable(name, value) {
+            return Err(VmError::User(format!("set!: variable {} is not bound", n)));
+        }
+        Ok(())
     }
 
     fn set_car(&mut self, op: Operation) {
@@ -483,12 +1182,21 @@ impl VM {
         self.load_register(op.setcdr_register()).set_cdr(value);
     }
 
-    fn define(&mut self, op: Operation) {
+    fn define(&mut self, op: Operation) -> Result<(), VmError> {
         let n = self.load_register(op.define_name());
         assert!(n.is_symbol());
         let name = n.to_symbol();
         let value = self.load_register(op.define_value());
+        // Only top level definitions can shadow something by accident
+        if self.environment.parent().is_none() && self.environment.is_bound_locally(name) {
+            match self.environment.strictness() {
+                Strictness::Permissive => {}
+                Strictness::Warn => self.warnings.push(format!("redefining {}", n)),
+                Strictness::Strict => return Err(VmError::User(format!("define: {} is already defined", n))),
+            }
+        }
         self.environment.define_variable(name, value);
+        Ok(())
     }
 
     fn lookup(&mut self, op: Operation) -> Result<(), VmError> {
@@ -505,6 +1213,55 @@ impl VM {
         Ok(())
     }
 
+    fn lookup_cached(&mut self, op: Operation) -> Result<(), VmError> {
+        let lookup = Operation::Lookup(op.lookupcached_register(), op.lookupcached_name());
+        let name = self.load_register(op.lookupcached_name()).to_symbol();
+        // The frame of a procedure is new for every call, so the search from its parent is cached,
+        // unless the name is bound in the frame itself
+        let procedure = self.saved_state.last().map(|s| s.procedure);
+        let env = match procedure {
+            None => Some(self.environment.clone()),
+            Some(_) if self.environment.is_bound_locally(name) => None,
+            Some(_) => self.environment.parent(),
+        };
+        let env = match env {
+            Some(env) => env,
+            None => return self.lookup(lookup),
+        };
+
+        if let Some(value) = self.with_inline_cache(procedure, op.lookupcached_slot(), |c| c.get(&env, name)) {
+            self.cache_hits += 1;
+            self.assign_register(op.lookupcached_register(), value);
+            return Ok(());
+        }
+        self.lookup(lookup)?;
+        let value = self.load_register(op.lookupcached_register());
+        self.with_inline_cache(procedure, op.lookupcached_slot(), |c| c.insert(env, name, value));
+        Ok(())
+    }
+
+    /// Apply `f` to inline cache `slot` of `procedure`, or of the top level code if there is none.
+    fn with_inline_cache<T, F>(&mut self, procedure: Option<Value>, slot: usize, f: F) -> T
+        where F: FnOnce(&mut InlineCache) -> T
+    {
+        fn cache(caches: &mut Vec<InlineCache>, slot: usize) -> &mut InlineCache {
+            if caches.len() <= slot {
+                caches.resize(slot + 1, InlineCache::default());
+            }
+            &mut caches[slot]
+        }
+
+        match procedure {
+            Some(p) => {
+                let mut lambda = p.to_lambda();
+                let result = f(cache(&mut lambda.caches, slot));
+                Box::into_raw(lambda);
+                result
+            }
+            None => f(cache(&mut self.inline_caches, slot)),
+        }
+    }
+
     fn call(&mut self, op: Operation) -> Result<(), VmError> {
         if self.debug {
             println!("beginning call");
@@ -512,36 +1269,135 @@ impl VM {
 
         // TODO
         let v = self.load_register(op.call_register());
+        #[cfg(feature = "hooks")]
+        self.with_hook(|hook, vm| hook.call(vm, v, op.call_argc()));
         if v.is_lambda() {
-            let lambda = v.to_lambda();
-            // Save the current code and env
-            let mut code = lambda.code.clone();
-            let mut consts = lambda.consts.clone();
-            let mut env = lambda.env.procedure_local();
-            mem::swap(&mut code, &mut self.operations);
-            mem::swap(&mut consts, &mut self.constants);
-            mem::swap(&mut env, &mut self.environment);
-            // Make sure we don't free this
-            Box::into_raw(lambda);
-
-            // Save the vm state
-            let s = SaveState {
-                pc: self.pc,
-                sp: self.load_sp(),
-                fp: self.load_fp(),
-                code: code,
-                consts: consts,
-                env: env,
-            };
-            //self.assign_fp(s.sp);
-            self.saved_state.push(s);
-            self.pc = 0;
-            Ok(())
+            self.call_lambda(v, op.call_argc())
+        } else if is_call_cc(v) {
+            self.call_cc(op, false)
+        } else if v.is_native() {
+            self.call_native(v, op.call_argc())
+        } else if v.is_continuation() {
+            let args: Vec<Value> = (1..=op.call_argc()).map(|i| self.load_register(Register(i as u8))).collect();
+            self.invoke_continuation(v, &args)
         } else {
             Err(VmError::NonProcedure(v))
         }
     }
 
+    /// Run the primitive instruction of `op` on the arguments in X1.. if the procedure being called
+    /// is the primitive made of that instruction alone, which is true unless its name has been
+    /// redefined. Any other procedure is called as usual.
+    fn call_primitive(&mut self, op: Operation) -> Result<(), VmError> {
+        let r = op.callprimitive_register();
+        let (primitive, argc) = match op.callprimitive_instruction() {
+            Instruction::Car => (Operation::Car(Register(0), Register(1)), 1),
+            Instruction::Cdr => (Operation::Cdr(Register(0), Register(1)), 1),
+            Instruction::Cons => (Operation::Cons(Register(0), Register(1), Register(2)), 2),
+            Instruction::VectorRef => (Operation::VectorRef(Register(0), Register(1), Register(2)), 2),
+            i => return Err(VmError::User(format!("callprimitive: {:?} is not a primitive", i))),
+        };
+
+        let v = self.load_register(r);
+        // Traced and memoized procedures have to go through a call to be logged or cached
+        if !v.is_lambda() || self.is_traced(v) {
+            return self.call(Operation::Call(r, argc));
+        }
+        let lambda = v.to_lambda();
+        let inline = *lambda.code == [primitive] && lambda.memo.is_none();
+        Box::into_raw(lambda);
+        if !inline {
+            return self.call(Operation::Call(r, argc));
+        }
+
+        match primitive.instruction() {
+            Instruction::Car => self.car(primitive),
+            Instruction::Cdr => self.cdr(primitive),
+            Instruction::Cons => {
+                self.cons(primitive);
+                Ok(())
+            }
+            _ => self.vector_ref(primitive),
+        }
+    }
+
+    fn call_lambda(&mut self, v: Value, argc: usize) -> Result<(), VmError> {
+        let traced = self.trace_call(v, argc);
+        let mut memo = Vec::new();
+        match self.memo_lookup(v, argc) {
+            Some((_, Some(result))) => {
+                self.assign_register(Register(0), result);
+                if traced {
+                    self.trace_return();
+                }
+                return Ok(());
+            }
+            Some((args, None)) => memo.push((v, args)),
+            None => (),
+        }
+
+        let lambda = v.to_lambda();
+        // Save the current code and env
+        let mut code = lambda.code.clone();
+        let mut consts = lambda.consts.clone();
+        let mut env = self.procedure_frame(&lambda.env);
+        mem::swap(&mut code, &mut self.operations);
+        mem::swap(&mut consts, &mut self.constants);
+        mem::swap(&mut env, &mut self.environment);
+        // Make sure we don't free this
+        Box::into_raw(lambda);
+
+        // Save the vm state
+        let s = SaveState {
+            procedure: v,
+            pc: self.pc,
+            sp: self.load_sp(),
+            fp: self.load_fp(),
+            code: code,
+            consts: consts,
+            env: env,
+            traced: traced,
+            memo: memo,
+        };
+        //self.assign_fp(s.sp);
+        self.saved_state.push(s);
+        self.pc = 0;
+        Ok(())
+    }
+
+    /// A new frame for a call to a procedure closed over `env`, reusing a pooled frame if there
+    /// is one.
+    fn procedure_frame(&mut self, env: &Environment) -> Environment {
+        match self.frame_pool.pop() {
+            Some(frame) => {
+                self.frames_reused += 1;
+                env.procedure_local_in(frame)
+            }
+            None => env.procedure_local(),
+        }
+    }
+
+    /// Keep the frame of a procedure which has returned for reuse, unless it escaped.
+    fn recycle_frame(&mut self, frame: Environment) {
+        if self.frame_pool.len() < FRAME_POOL_SIZE {
+            if let Some(frame) = frame.recycle() {
+                self.frame_pool.push(frame);
+            }
+        }
+    }
+
+    /// If `v` is memoized, return the arguments of the call and the cached result, if any.
+    fn memo_lookup(&self, v: Value, argc: usize) -> Option<(Vec<Value>, Option<Value>)> {
+        let mut lambda = v.to_lambda();
+        let r = lambda.memo.as_mut().map(|memo| {
+            let args: Vec<Value> = (1..=argc).map(|i| self.load_register(Register(i as u8))).collect();
+            let result = memo.get(&args);
+            (args, result)
+        });
+        Box::into_raw(lambda);
+        r
+    }
+
     fn tail_call(&mut self, op: Operation) -> Result<(), VmError> {
         if self.debug {
             println!("beginning tail call");
@@ -549,31 +1405,325 @@ impl VM {
 
         // TODO
         let v = self.load_register(op.tail_call_register());
+        #[cfg(feature = "hooks")]
+        self.with_hook(|hook, vm| hook.call(vm, v, op.tail_call_argc()));
         if v.is_lambda() {
+            match self.memo_lookup(v, op.tail_call_argc()) {
+                // A cached result returns at once, like a native procedure
+                Some((_, Some(result))) => {
+                    let traced = self.trace_call(v, op.tail_call_argc());
+                    self.assign_register(Register(0), result);
+                    if traced {
+                        self.trace_return();
+                    }
+                    self.pc = self.operations.len();
+                    return Ok(());
+                }
+                // The result is the value returned by the frame being replaced
+                Some((args, None)) => match self.saved_state.last_mut() {
+                    Some(s) => s.memo.push((v, args)),
+                    None => {
+                        // There is no frame to record the result, so make one
+                        self.pc = self.operations.len();
+                        return self.call_lambda(v, op.tail_call_argc());
+                    }
+                },
+                None => (),
+            }
+            // The traced return is printed when the frame being replaced returns.
+            if self.trace_call(v, op.tail_call_argc()) {
+                match self.saved_state.last_mut() {
+                    Some(s) if s.traced => self.trace_depth -= 1,
+                    Some(s) => s.traced = true,
+                    None => self.trace_depth -= 1,
+                }
+            }
+            if let Some(s) = self.saved_state.last_mut() {
+                s.procedure = v;
+            }
             let lambda = v.to_lambda();
             self.operations = lambda.code.clone();
             self.constants = lambda.consts.clone();
-            self.environment = lambda.env.procedure_local();
+            let env = self.procedure_frame(&lambda.env);
+            let frame = mem::replace(&mut self.environment, env);
+            self.recycle_frame(frame);
             // Make sure we don't free this
             Box::into_raw(lambda);
+            // Nothing saved by the frame being replaced is needed again
+            if let Some(s) = self.saved_state.last() {
+                let sp = s.sp;
+                self.assign_sp(sp);
+                self.stack.truncate(sp.to_integer() as usize);
+            }
 
             self.pc = 0;
             Ok(())
+        } else if is_call_cc(v) {
+            self.call_cc(op, true)
+        } else if v.is_native() {
+            self.call_native(v, op.tail_call_argc())?;
+            self.pc = self.operations.len();
+            Ok(())
+        } else if v.is_continuation() {
+            let args: Vec<Value> = (1..=op.tail_call_argc()).map(|i| self.load_register(Register(i as u8))).collect();
+            self.invoke_continuation(v, &args)
         } else {
             Err(VmError::NonProcedure(v))
         }
     }
 
+    /// Call the procedure in X1 with the continuation of the call `op`, which is a tail call if
+    /// `tail` is set.
+    fn call_cc(&mut self, op: Operation, tail: bool) -> Result<(), VmError> {
+        let (r, argc) = if tail {
+            (op.tail_call_register(), op.tail_call_argc())
+        } else {
+            (op.call_register(), op.call_argc())
+        };
+        if argc != 1 {
+            return Err(VmError::User(format!("call-with-current-continuation: expected 1 arguments, got {}", argc)));
+        }
+
+        // Copy the running computation
+        let mut state = self.suspend();
+        self.resume(state.clone());
+        // A tail call returns the value to the caller of the current procedure
+        if tail {
+            state.pc = state.operations.len();
+        }
+        let k = self.continuations.insert(Continuation { state, applications: self.applications.clone() });
+        let k = Value::Continuation(k);
+
+        let f = self.load_register(Register(1));
+        self.assign_register(r, f);
+        self.assign_register(Register(1), k);
+        if tail {
+            self.tail_call(Operation::TailCall(r, 1))
+        } else {
+            self.call(Operation::Call(r, 1))
+        }
+    }
+
+    /// Pass `args` to the continuation `k`. The machine switches to it in `throw`.
+    fn invoke_continuation(&mut self, k: Value, args: &[Value]) -> Result<(), VmError> {
+        let v = match args {
+            [] => Value::Void,
+            &[v] => v,
+            _ => {
+                return Err(VmError::User(format!("continuation: expected 0 to 1 arguments, got {}", args.len())));
+            }
+        };
+        let applications = match self.continuations.get(k.to_continuation()) {
+            Some(continuation) => &continuation.applications,
+            None => return Err(VmError::User(format!("continuation: {} has been freed", k))),
+        };
+        if !self.applications.starts_with(applications) {
+            return Err(VmError::User(format!(
+                "continuation: {} was captured in a procedure applied by a native procedure which has returned", k)));
+        }
+        self.throwing = Some((k, v));
+        Err(VmError::Throw)
+    }
+
+    /// Switch to the continuation being invoked if it was captured in the running computation.
+    /// Otherwise returns `false`, and the computation is to be abandoned.
+    fn throw(&mut self) -> bool {
+        let (k, v) = self.throwing.unwrap();
+        // `invoke_continuation` checked that it exists, and it is kept alive while being thrown to
+        let continuation = self.continuations.get(k.to_continuation()).unwrap();
+        if continuation.applications.len() < self.applications.len() {
+            return false;
+        }
+        let state = continuation.state.clone();
+        self.throwing = None;
+        self.resume(state);
+        self.assign_register(Register(0), v);
+        true
+    }
+
+    /// Call the procedure `f` with `args` and return its result, for native procedures which take
+    /// procedures as arguments. The running computation is set aside until `f` returns. If `f`
+    /// signals a condition which isn't handled, its computation is abandoned and the message of
+    /// the condition is returned.
+    /// If `f` invokes a continuation captured outside it, an error is returned, and the native is
+    /// abandoned as soon as it returns.
+    ///
+    /// Only the arguments of `f` and the set aside computation are kept alive while `f` runs, so
+    /// a native must not hold any other newly allocated value across calls.
+    pub fn apply(&mut self, f: Value, args: &[Value]) -> Result<Value, String> {
+        if f.is_native() {
+            let native = native::get_native(f.to_native());
+            if !native.arity.accepts(args.len()) {
+                return Err(format!("{}: expected {} arguments, got {}", native.name, native.arity, args.len()));
+            }
+            return native.call(self, args);
+        } else if f.is_continuation() {
+            return match self.invoke_continuation(f, args) {
+                Err(e) => Err(e.message()),
+                Ok(()) => unreachable!(),
+            };
+        } else if !f.is_lambda() {
+            return Err(format!("apply: attempt to apply non-procedure {}", f));
+        }
+
+        let mut registers = vec![f];
+        registers.extend_from_slice(args);
+        self.nested(None, vec![Operation::Call(Register(0), args.len())], vec![], &registers)
+    }
+
+    /// Run top level code, as `load_code` takes, from a native procedure, as `apply` calls a
+    /// procedure: in the global environment, with the running computation set aside until the
+    /// code finishes. Returns the value of the code, or the message of a condition it signals.
+    pub fn run_nested(&mut self, code: Vec<Operation>, consts: Vec<Value>) -> Result<Value, String> {
+        self.nested(None, code, consts, &[])
+    }
+
+    /// Like `run_nested`, but running the code in `env`, so that its definitions are made there.
+    pub fn run_nested_in(&mut self, env: &Environment, code: Vec<Operation>, consts: Vec<Value>)
+        -> Result<Value, String>
+    {
+        self.nested(Some(env), code, consts, &[])
+    }
+
+    // Run `code` with `registers` in X0.. in a new computation, in `env` or the global environment
+    fn nested(&mut self, env: Option<&Environment>, code: Vec<Operation>, consts: Vec<Value>, registers: &[Value])
+        -> Result<Value, String>
+    {
+        let state = self.suspend();
+        if let Some(env) = env {
+            self.environment = env.clone();
+        }
+        self.applying.push(state);
+        self.applications.push(self.next_application);
+        self.next_application += 1;
+        let conditions = self.conditions.len();
+        for (i, &v) in registers.iter().enumerate() {
+            self.assign_register(Register(i as u8), v);
+        }
+        self.operations = code.into();
+        self.constants = consts.into();
+        self._run();
+        let result = if self.throwing.is_some() {
+            Err(VmError::Throw.message())
+        } else if self.conditions.len() > conditions {
+            Err(self.conditions.pop().unwrap().message())
+        } else {
+            Ok(self.load_register(Register(0)))
+        };
+        let state = self.applying.pop().unwrap();
+        self.applications.pop();
+        self.resume(state);
+        result
+    }
+
+    /// Call the native procedure `v` with the arguments in X1.., placing the result in X0.
+    fn call_native(&mut self, v: Value, argc: usize) -> Result<(), VmError> {
+        let native = native::get_native(v.to_native());
+        if !native.arity.accepts(argc) {
+            return Err(VmError::User(format!("{}: expected {} arguments, got {}",
+                                             native.name, native.arity, argc)));
+        }
+
+        let args: Vec<Value> = (1..=argc).map(|i| self.load_register(Register(i as u8))).collect();
+        let traced = self.trace_call(v, argc);
+        let result = native.call(self, &args);
+        // A continuation invoked by a procedure the native applied leaves the native too
+        if self.throwing.is_some() {
+            if traced {
+                self.trace_depth -= 1;
+            }
+            return Err(VmError::Throw);
+        }
+        match result {
+            Ok(result) => {
+                self.assign_register(Register(0), result);
+                if traced {
+                    self.trace_return();
+                }
+                Ok(())
+            }
+            Err(e) => {
+                if traced {
+                    self.trace_depth -= 1;
+                }
+                Err(VmError::User(e))
+            }
+        }
+    }
+
+    fn trace(&mut self, op: Operation) {
+        let v = self.load_register(op.trace_register());
+        // Use the name the procedure is bound to, if it has one
+        let name = self.procedure_name(v)
+            .map(Self::get_symbol_value)
+            .unwrap_or_else(|| format!("{}", v));
+        self.traced.insert(v.identity_hash(), name);
+    }
+
+    /// A variable visible from the current environment which `v` is bound to.
+    pub(crate) fn procedure_name(&self, v: Value) -> Option<Symbol> {
+        self.environment.get_definitions().into_iter()
+            .find(|&s| self.environment.lookup_variable_value(s) == Some(v))
+    }
+
+    /// The procedures running in each frame of the call stack, innermost first.
+    pub(crate) fn stack_procedures(&self) -> impl Iterator<Item = Value> + '_ {
+        self.saved_state.iter().rev().map(|s| s.procedure)
+    }
+
+    fn untrace(&mut self, op: Operation) {
+        let v = self.load_register(op.untrace_register());
+        self.traced.remove(&v.identity_hash());
+    }
+
+    fn is_traced(&self, procedure: Value) -> bool {
+        !self.traced.is_empty() && self.traced.contains_key(&procedure.identity_hash())
+    }
+
+    /// Print the call if `procedure` is traced. Returns whether it was.
+    fn trace_call(&mut self, procedure: Value, argc: usize) -> bool {
+        // Hashing numbers the procedure, which is not worth doing when nothing is traced
+        if self.traced.is_empty() {
+            return false;
+        }
+        if let Some(name) = self.traced.get(&procedure.identity_hash()) {
+            let mut line = "| ".repeat(self.trace_depth + 1);
+            line.push('(');
+            line.push_str(name);
+            for i in 1..=argc {
+                line.push_str(&format!(" {}", self.load_register(Register(i as u8))));
+            }
+            line.push(')');
+            println!("{}", line);
+            self.trace_depth += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn trace_return(&mut self) {
+        self.trace_depth -= 1;
+        println!("{}{}", "| ".repeat(self.trace_depth + 1), self.load_register(Register(0)));
+    }
+
     pub fn gc(&mut self) {
+        let start = if self.time_gc { Some(Instant::now()) } else { None };
         if self.debug { println!("Beginning garbage collection") }
         if self.debug { println!("marking") }
         self.mark();
         if self.debug { println!("sweeping") }
         self.sweep();
         if self.debug { println!("Done with garbage collection") }
+        self.collections += 1;
+        if let Some(start) = start {
+            self.gc_time += start.elapsed();
+        }
     }
 
     fn mark(&mut self) {
+        // Entries reached by marking done outside a collection, which may not belong to this machine
+        gc::take_reached();
         for i in 0..32 {
             self.load_register(Register(i)).mark();
         }
@@ -582,23 +1732,104 @@ impl VM {
             v.mark();
         }
 
-        for v in &self.constants {
+        for v in self.constants.iter() {
             v.mark();
         }
         self.environment.mark();
+        environment::mark_roots();
+
+        for e in &self.environments {
+            e.mark();
+        }
 
         for s in &self.saved_state {
-            for v in &s.consts {
-                v.mark();
+            s.mark();
+        }
+
+        for c in &self.conditions {
+            c.mark();
+        }
+
+        for s in &self.applying {
+            s.mark();
+        }
+
+        if let Some((k, v)) = self.throwing {
+            k.mark();
+            v.mark();
+        }
+
+        for v in &self.restart_handlers {
+            v.mark();
+        }
+        if let Some(Restart::UseValue(v)) = self.invoked_restart {
+            v.mark();
+        }
+        if let Some((_, v)) = self.caught {
+            v.mark();
+        }
+
+        for t in &self.timers {
+            t.thunk.mark();
+        }
+
+        #[cfg(feature = "watch")]
+        for w in self.watches.iter().flatten() {
+            w.proc.mark();
+        }
+
+        for v in &self.retained {
+            v.mark();
+        }
+
+        for &(k, v) in self.properties.values().flatten() {
+            k.mark();
+            v.mark();
+        }
+
+        self.mark_tables();
+    }
+
+    /// Mark what is held by the table entries reached from the roots, and by the entries reached
+    /// from those in turn, then free the entries which were not reached.
+    fn mark_tables(&mut self) {
+        let mut slices = vec![false; self.slices.capacity()];
+        let mut continuations = vec![false; self.continuations.capacity()];
+        loop {
+            let reached = gc::take_reached();
+            if reached.is_empty() {
+                break;
+            }
+            for v in reached {
+                if v.is_slice() {
+                    let i = v.to_slice();
+                    if let Some(s) = self.slices.get(i) {
+                        if !slices[i] {
+                            slices[i] = true;
+                            s.vector.mark();
+                        }
+                    }
+                } else if v.is_continuation() {
+                    let i = v.to_continuation();
+                    if let Some(k) = self.continuations.get(i) {
+                        if !continuations[i] {
+                            continuations[i] = true;
+                            k.state.mark();
+                        }
+                    }
+                }
             }
-            s.env.mark();
         }
+        self.slices.retain(|i, _| slices[i]);
+        self.continuations.retain(|i, _| continuations[i]);
     }
 
     fn sweep(&mut self) {
         let mut current = get_head();
         let mut previous = None;
         let mut new_root = 0;
+        // The sizes of the objects which survive
+        let mut bytes = 0;
         while current != 0 {
             let ty = VType::from(current >> 56);
             // Perform sign extension, we make sure that the lowest bit is set to 0
@@ -622,6 +1853,7 @@ impl VM {
                                 $new_root = $current;
                             }
                             p.gc = p.gc - 1;
+                            bytes += p.size();
                             $previous = Some($current);
                             $current = p.gc;
                             Box::into_raw(p);
@@ -636,22 +1868,85 @@ impl VM {
                 VType::String => ty_match!(heap_repr::SString, ptr, current, previous, new_root),
                 VType::Vec => ty_match!(heap_repr::SVec, ptr, current, previous, new_root),
                 VType::HashMap => ty_match!(heap_repr::SHashMap, ptr, current, previous, new_root),
+                VType::Bytevector => ty_match!(heap_repr::SBytevector, ptr, current, previous, new_root),
+                VType::BigInt => ty_match!(heap_repr::SBigInt, ptr, current, previous, new_root),
                 _ => unreachable!(),
             }
         }
 
         set_head(new_root, VType::from(new_root >> 56));
+        gc::set_heap_bytes(bytes);
+    }
+}
+
+/// Counters describing the work done by a `VM`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Instructions executed.
+    pub steps: usize,
+    /// Heap objects allocated.
+    pub allocations: usize,
+    /// Garbage collections performed.
+    pub collections: usize,
+    /// Time spent collecting garbage while `VM::set_gc_timing` was on.
+    pub gc_time: Duration,
+    /// Procedure calls which reused the frame of an earlier call instead of allocating one.
+    pub frames_reused: usize,
+    /// Lookups of the procedure to call answered by the inline cache of the call site.
+    pub cache_hits: usize,
+}
+
+impl ops::Sub for Stats {
+    type Output = Stats;
+
+    fn sub(self, other: Stats) -> Stats {
+        Stats {
+            steps: self.steps - other.steps,
+            allocations: self.allocations - other.allocations,
+            collections: self.collections - other.collections,
+            gc_time: self.gc_time - other.gc_time,
+            frames_reused: self.frames_reused - other.frames_reused,
+            cache_hits: self.cache_hits - other.cache_hits,
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instructions, {} allocations, {} collections ({:?}), {} frames reused, {} cache hits",
+               self.steps, self.allocations, self.collections, self.gc_time, self.frames_reused, self.cache_hits)
     }
 }
 
 #[derive(Debug, Clone)]
 struct SaveState {
+    // The procedure running in this frame
+    procedure: Value,
     pc: usize,
-    code: Vec<Operation>,
-    consts: Vec<Value>,
+    code: Arc<[Operation]>,
+    consts: Arc<[Value]>,
     env: Environment,
     sp: Value,
     fp: Value,
+    traced: bool,
+    // Memoized procedures and arguments whose result is the value returned by this frame
+    memo: Vec<(Value, Vec<Value>)>,
+}
+
+impl SaveState {
+    fn mark(&self) {
+        self.procedure.mark();
+        for v in self.consts.iter() {
+            v.mark();
+        }
+        self.env.mark();
+        for &(f, ref args) in &self.memo {
+            f.mark();
+            for v in args {
+                v.mark();
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -659,6 +1954,30 @@ enum VmError {
     Undefined(Symbol),
     NonProcedure(Value),
     User(String),
+    // A continuation is being invoked, see `VM::throwing`
+    Throw,
+    Interrupt(Signal),
+    // The instructions allowed by `VM::set_fuel` have run
+    OutOfFuel,
+    // The heap uses the first number of bytes, over the limit of the second
+    OutOfMemory(usize, usize),
+}
+
+impl VmError {
+    /// The message in the form native procedures report errors, `name: message`.
+    fn message(&self) -> String {
+        match self {
+            VmError::Undefined(s) =>
+                format!("apply: variable {} is not bound", string_interner::get_value(*s).unwrap()),
+            VmError::NonProcedure(v) => format!("apply: attempt to apply non-procedure {}", v),
+            VmError::User(s) => s.clone(),
+            VmError::Throw => "continuation: invoked".to_string(),
+            VmError::Interrupt(signal) => format!("interrupt: received {}", signal),
+            VmError::OutOfFuel => "fuel: ran out".to_string(),
+            VmError::OutOfMemory(used, limit) =>
+                format!("out-of-memory: the heap uses {} bytes, over its limit of {}", used, limit),
+        }
+    }
 }
 
 impl fmt::Display for VmError {
@@ -669,6 +1988,11 @@ impl fmt::Display for VmError {
             VmError::NonProcedure(v) =>
                 write!(f, "Exception: attempt to apply non-procedure {}", v),
             VmError::User(s) => write!(f, "Exception in {}", s),
+            VmError::Throw => write!(f, "Exception in continuation: invoked"),
+            VmError::Interrupt(signal) => write!(f, "Exception in interrupt: received {}", signal),
+            VmError::OutOfFuel => write!(f, "Exception in fuel: ran out"),
+            VmError::OutOfMemory(used, limit) =>
+                write!(f, "Exception in out-of-memory: the heap uses {} bytes, over its limit of {}", used, limit),
         }
     }
 }
@@ -0,0 +1,177 @@
+//! List procedures implemented natively.
+//!
+//! The procedures ending in `!` are linear update versions of their counterparts: they reuse the
+//! pairs of their arguments rather than allocating new ones. They are only safe when no other
+//! reference to those pairs exists, since anything sharing structure with an argument will see it
+//! change.
+
+use {register_native, Arity, NativeFn, Value, VM};
+use native::get_native;
+
+/// The length of `v` if it is a proper list. Cycles created with `set-cdr!` are detected with
+/// Floyd's algorithm: a pointer moving one pair at a time is caught up to by one moving two.
+fn proper_length(v: Value) -> Result<usize, ListError> {
+    let mut slow = v;
+    let mut fast = v;
+    let mut length = 0;
+    loop {
+        for _ in 0..2 {
+            if fast.is_nil() {
+                return Ok(length);
+            } else if !fast.is_pair() {
+                return Err(ListError::Improper);
+            }
+            fast = fast.cdr();
+            length += 1;
+        }
+        slow = slow.cdr();
+        if fast == slow {
+            return Err(ListError::Circular);
+        }
+    }
+}
+
+enum ListError {
+    Improper,
+    Circular,
+}
+
+/// Check that `v` is a proper list, naming `name` in the error if it is not.
+fn check_list(name: &str, v: Value) -> Result<usize, String> {
+    match proper_length(v) {
+        Ok(n) => Ok(n),
+        Err(ListError::Improper) => Err(format!("{}: {} is not a proper list", name, v)),
+        // A circular list can't be printed
+        Err(ListError::Circular) => Err(format!("{}: argument is a circular list", name)),
+    }
+}
+
+/// `(list? obj)` Return `#t` if `obj` is a proper list, `#f` if it is improper or circular.
+pub fn is_list(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(proper_length(args[0]).is_ok()))
+}
+
+/// `(length list)` Return the number of elements in `list`, signalling an error if it is improper
+/// or circular.
+pub fn length(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_list("length", args[0]).map(|n| Value::Integer(n as i32))
+}
+
+fn last_pair(mut l: Value) -> Value {
+    loop {
+        let next = l.cdr();
+        if !next.is_pair() {
+            return l;
+        }
+        l = next;
+    }
+}
+
+/// `(list obj ...)` Return a newly allocated list of the arguments.
+pub fn list(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(args.iter().rev().fold(Value::Nil, |l, &v| Value::Pair(v, l)))
+}
+
+/// `(reverse list)` Return a newly allocated list of the elements of `list` in reverse order.
+pub fn reverse(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_list("reverse", args[0])?;
+    let mut l = args[0];
+    let mut reversed = Value::Nil;
+    while l.is_pair() {
+        reversed = Value::Pair(l.car(), reversed);
+        l = l.cdr();
+    }
+    Ok(reversed)
+}
+
+/// `(append list ...)` Return a list of the elements of each list followed by the last argument.
+/// Every list but the last is copied, the result shares structure with the last argument.
+pub fn append(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let (last, lists) = match args.split_last() {
+        Some(s) => s,
+        None => return Ok(Value::Nil),
+    };
+
+    let mut elements = Vec::new();
+    for &l in lists {
+        check_list("append", l)?;
+        let mut l = l;
+        while l.is_pair() {
+            elements.push(l.car());
+            l = l.cdr();
+        }
+    }
+    Ok(elements.into_iter().rev().fold(*last, |l, v| Value::Pair(v, l)))
+}
+
+/// `(reverse! list)` Reverse `list` in place and return it.
+///
+/// Unsafe if `list` is shared: the pairs of `list` are reused for the result.
+pub fn reverse_bang(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_list("reverse!", args[0])?;
+    Ok(reverse_onto(args[0], Value::Nil))
+}
+
+/// `(append-reverse! rev-head tail)` Reverse `rev-head` in place onto `tail`, the same as
+/// `(append! (reverse! rev-head) tail)`.
+///
+/// Unsafe if `rev-head` is shared: the pairs of `rev-head` are reused for the result.
+pub fn append_reverse_bang(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    check_list("append-reverse!", args[0])?;
+    Ok(reverse_onto(args[0], args[1]))
+}
+
+fn reverse_onto(mut l: Value, mut reversed: Value) -> Value {
+    while l.is_pair() {
+        let next = l.cdr();
+        l.set_cdr(reversed);
+        reversed = l;
+        l = next;
+    }
+    reversed
+}
+
+/// `( linear-update procedure producer)` The linear update version of `procedure` if it is
+/// `reverse` or `append` and `producer`, which made the list passed first to it, is `list` or
+/// `reverse`, otherwise `procedure` itself. The compiler chooses the procedure to call on a list
+/// nothing else refers to with this, so that a redefinition of either name is still respected.
+pub fn linear_update(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let is = |v: Value, f: NativeFn| v.is_native() && get_native(v.to_native()).f as usize == f as usize;
+    if !is(args[1], list) && !is(args[1], reverse) {
+        return Ok(args[0]);
+    }
+    Ok(if is(args[0], reverse) {
+        register_native("reverse!", Arity::Exactly(1), reverse_bang)
+    } else if is(args[0], append) {
+        register_native("append!", Arity::AtLeast(0), append_bang)
+    } else {
+        args[0]
+    })
+}
+
+/// `(append! list ...)` Append the lists by setting the cdr of the last pair of each to the next.
+///
+/// Unsafe if any list but the last is shared: their last pairs are modified.
+pub fn append_bang(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let (&last, lists) = match args.split_last() {
+        Some(s) => s,
+        None => return Ok(Value::Nil),
+    };
+    for &l in lists {
+        check_list("append!", l)?;
+    }
+
+    let mut result = last;
+    let mut tail: Option<Value> = None;
+    for &l in lists.iter().filter(|l| l.is_pair()) {
+        match tail {
+            Some(t) => t.set_cdr(l),
+            None => result = l,
+        }
+        tail = Some(last_pair(l));
+    }
+    if let Some(t) = tail {
+        t.set_cdr(last);
+    }
+    Ok(result)
+}
@@ -0,0 +1,123 @@
+//! Comparing strings and mapping their case by the conventions of a locale.
+//!
+//! Each procedure takes an optional locale, a BCP 47 language tag such as `"sv"` or `"tr-TR"`.
+//! With the `icu` feature strings are collated and case mapped by the Unicode algorithms tailored
+//! for the locale, so that e.g. `"a"` sorts before `"B"`, and `ö` after `z` in Swedish but before
+//! it in German. Without it the locale is ignored: strings are ordered by code point and case
+//! mapped by the default Unicode rules.
+
+use {Value, VM};
+
+use std::cmp::Ordering;
+
+#[cfg(feature = "icu")]
+use icu_casemap::CaseMapper;
+#[cfg(feature = "icu")]
+use icu_collator::{Collator, CollatorOptions};
+#[cfg(feature = "icu")]
+use icu_locid::Locale;
+
+#[cfg(feature = "icu")]
+use std::cell::RefCell;
+
+#[cfg(feature = "icu")]
+thread_local! {
+    // The collator made for the last locale used, since making one loads its tailoring
+    static COLLATOR: RefCell<Option<(Locale, Collator)>> = const { RefCell::new(None) };
+}
+
+fn string_arg(name: &str, v: Value) -> Result<String, String> {
+    if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.clone();
+        Box::into_raw(s);
+        Ok(r)
+    } else {
+        Err(format!("{}: {} is not a string", name, v))
+    }
+}
+
+#[cfg(feature = "icu")]
+fn locale_arg(name: &str, v: Option<&Value>) -> Result<Locale, String> {
+    match v {
+        None => Ok(Locale::UND),
+        Some(&v) => {
+            let tag = string_arg(name, v)?;
+            tag.parse().map_err(|_| format!("{}: {} is not a locale", name, v))
+        }
+    }
+}
+
+#[cfg(not(feature = "icu"))]
+fn locale_arg(name: &str, v: Option<&Value>) -> Result<(), String> {
+    match v {
+        None => Ok(()),
+        Some(&v) => string_arg(name, v).map(|_| ()),
+    }
+}
+
+#[cfg(feature = "icu")]
+fn compare(name: &str, args: &[Value]) -> Result<Ordering, String> {
+    let a = string_arg(name, args[0])?;
+    let b = string_arg(name, args[1])?;
+    let locale = locale_arg(name, args.get(2))?;
+    COLLATOR.with(|c| {
+        let mut c = c.borrow_mut();
+        if c.as_ref().map_or(true, |(l, _)| *l != locale) {
+            let collator = Collator::try_new(&(&locale).into(), CollatorOptions::new())
+                .map_err(|e| format!("{}: no collation for {}: {}", name, locale, e))?;
+            *c = Some((locale, collator));
+        }
+        Ok(c.as_ref().unwrap().1.compare(&a, &b))
+    })
+}
+
+#[cfg(not(feature = "icu"))]
+fn compare(name: &str, args: &[Value]) -> Result<Ordering, String> {
+    let a = string_arg(name, args[0])?;
+    let b = string_arg(name, args[1])?;
+    locale_arg(name, args.get(2))?;
+    Ok(a.cmp(&b))
+}
+
+// `args[0]` in upper case, or lower case if not `upper`
+#[cfg(feature = "icu")]
+fn map_case(name: &str, args: &[Value], upper: bool) -> Result<String, String> {
+    let s = string_arg(name, args[0])?;
+    let locale = locale_arg(name, args.get(1))?;
+    let mapper = CaseMapper::new();
+    Ok(if upper { mapper.uppercase_to_string(&s, &locale.id) } else { mapper.lowercase_to_string(&s, &locale.id) })
+}
+
+#[cfg(not(feature = "icu"))]
+fn map_case(name: &str, args: &[Value], upper: bool) -> Result<String, String> {
+    let s = string_arg(name, args[0])?;
+    locale_arg(name, args.get(1))?;
+    Ok(if upper { s.to_uppercase() } else { s.to_lowercase() })
+}
+
+/// `(string-locale<? a b [locale])` Whether `a` sorts before `b`.
+pub fn string_locale_lt(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    compare("string-locale<?", args).map(|o| Value::Bool(o == Ordering::Less))
+}
+
+/// `(string-locale>? a b [locale])` Whether `a` sorts after `b`.
+pub fn string_locale_gt(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    compare("string-locale>?", args).map(|o| Value::Bool(o == Ordering::Greater))
+}
+
+/// `(string-locale=? a b [locale])` Whether `a` and `b` sort the same, which with the `icu`
+/// feature they may without being equal, e.g. in different normalization forms.
+pub fn string_locale_eq(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    compare("string-locale=?", args).map(|o| Value::Bool(o == Ordering::Equal))
+}
+
+/// `(string-locale-upcase string [locale])`
+pub fn string_locale_upcase(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    map_case("string-locale-upcase", args, true).map(Value::String)
+}
+
+/// `(string-locale-downcase string [locale])`
+pub fn string_locale_downcase(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    map_case("string-locale-downcase", args, false).map(Value::String)
+}
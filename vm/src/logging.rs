@@ -0,0 +1,191 @@
+//! Logging with levels, through a sink installed by the host.
+//!
+//! `(log-info "loaded ~a rules" n)` formats its message as `format` strings are usually formatted:
+//! `~a` is replaced by the next argument as `display` writes it, `~s` as `write` writes it, `~%`
+//! by a newline and `~~` by a `~`. Messages below the level set by `set-log-level!`, `info` by
+//! default, are dropped without being formatted.
+//!
+//! Messages go to the sink installed with `VM::set_log_sink`, so that a host embedding the
+//! interpreter can send them wherever its own logs go. Without one they are written to standard
+//! error. With the `log` feature, `forward_to_log` is a sink for the `log` crate, and through it
+//! for `tracing`.
+
+use {Value, VM};
+use port::display_text;
+
+use string_interner::get_value;
+
+use std::fmt::{self, Display, Formatter};
+
+/// How important a logged message is, least first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn named(name: &str) -> Option<Level> {
+        match name {
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Level::Debug => write!(f, "debug"),
+            Level::Info => write!(f, "info"),
+            Level::Warn => write!(f, "warn"),
+            Level::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Where logged messages go, with their level.
+pub type LogSink = Box<dyn FnMut(Level, &str)>;
+
+// The machine's sink, wrapped so that the machine can still derive `Debug`
+pub(crate) struct Sink(LogSink);
+
+impl fmt::Debug for Sink {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "LogSink")
+    }
+}
+
+/// A sink which passes messages to the `log` crate with the target `minerva`.
+#[cfg(feature = "log")]
+pub fn forward_to_log(level: Level, message: &str) {
+    let level = match level {
+        Level::Debug => log::Level::Debug,
+        Level::Info => log::Level::Info,
+        Level::Warn => log::Level::Warn,
+        Level::Error => log::Level::Error,
+    };
+    log::log!(target: "minerva", level, "{}", message);
+}
+
+impl VM {
+    /// Send logged messages to `sink` instead of standard error.
+    pub fn set_log_sink(&mut self, sink: impl FnMut(Level, &str) + 'static) {
+        self.log_sink = Some(Sink(Box::new(sink)));
+    }
+
+    /// Remove the sink installed by `set_log_sink`, e.g. to install it in another machine.
+    pub fn take_log_sink(&mut self) -> Option<LogSink> {
+        self.log_sink.take().map(|s| s.0)
+    }
+
+    /// Drop messages less important than `level`.
+    pub fn set_log_level(&mut self, level: Level) {
+        self.log_level = level;
+    }
+
+    /// The least important level of message which is logged.
+    pub fn log_level(&self) -> Level {
+        self.log_level
+    }
+
+    /// Log `message` at `level`, if it isn't below the log level.
+    pub fn log(&mut self, level: Level, message: &str) {
+        if level < self.log_level {
+            return;
+        }
+        match self.log_sink {
+            Some(Sink(ref mut sink)) => sink(level, message),
+            None => eprintln!("{}: {}", level.to_string().to_uppercase(), message),
+        }
+    }
+}
+
+/// `fmt` with its directives replaced by `args`.
+fn format(name: &str, fmt: Value, args: &[Value]) -> Result<String, String> {
+    if !fmt.is_string() {
+        return Err(format!("{}: {} is not a string", name, fmt));
+    }
+    let s = fmt.to_string();
+    let fmt = s.str.clone();
+    Box::into_raw(s);
+
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(d @ ('a' | 's')) => match args.next() {
+                Some(&v) if d == 'a' => out.push_str(&display_text(v)),
+                Some(v) => out.push_str(&format!("{}", v)),
+                None => return Err(format!("{}: too few arguments for {:?}", name, fmt)),
+            },
+            Some('%') => out.push('\n'),
+            Some('~') => out.push('~'),
+            Some(d) => return Err(format!("{}: ~{} is not a format directive", name, d)),
+            None => return Err(format!("{}: {:?} ends with ~", name, fmt)),
+        }
+    }
+    if args.next().is_some() {
+        return Err(format!("{}: too many arguments for {:?}", name, fmt));
+    }
+    Ok(out)
+}
+
+fn log(vm: &mut VM, name: &str, level: Level, args: &[Value]) -> Result<Value, String> {
+    if level >= vm.log_level {
+        let message = format(name, args[0], &args[1..])?;
+        vm.log(level, &message);
+    }
+    Ok(Value::Void)
+}
+
+/// `(log-debug fmt arg ...)` Log a message for debugging.
+pub fn log_debug(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    log(vm, "log-debug", Level::Debug, args)
+}
+
+/// `(log-info fmt arg ...)` Log a message about what the program is doing.
+pub fn log_info(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    log(vm, "log-info", Level::Info, args)
+}
+
+/// `(log-warn fmt arg ...)` Log a message about something which may be a problem.
+pub fn log_warn(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    log(vm, "log-warn", Level::Warn, args)
+}
+
+/// `(log-error fmt arg ...)` Log a message about a failure.
+pub fn log_error(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    log(vm, "log-error", Level::Error, args)
+}
+
+/// `(log-level)` The least important level logged, `debug`, `info`, `warn` or `error`.
+pub fn log_level(vm: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Symbol(VM::intern_symbol(vm.log_level.to_string())))
+}
+
+/// `(set-log-level! level)` Drop messages less important than `level`.
+pub fn set_log_level(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let level = if args[0].is_symbol() {
+        get_value(args[0].to_symbol()).and_then(|l| Level::named(&l))
+    } else {
+        None
+    };
+    match level {
+        Some(level) => {
+            vm.log_level = level;
+            Ok(Value::Void)
+        }
+        None => Err(format!("set-log-level!: {} is not a log level, expected debug, info, warn or error", args[0])),
+    }
+}
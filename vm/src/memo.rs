@@ -0,0 +1,231 @@
+//! Memoized procedures, created by `memoize` and `define-memoized`.
+//!
+//! A memoized procedure is a lambda which carries a cache from argument lists to results. The
+//! machine consults the cache when the procedure is called and fills it when the call returns.
+//! Arguments are compared with `equal?`, so lists, vectors, strings, and bytevectors with the same
+//! contents, and bignums with the same value, share an entry. A cache may be bounded, in which
+//! case the least recently used entry is evicted to make room.
+
+use {Value, VM};
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+
+// Nested structure deeper than this is not hashed, which keeps hashing cyclic lists finite
+const HASH_DEPTH: usize = 16;
+
+/// Structural equality, as `equal?`.
+pub(crate) fn equal(a: Value, b: Value) -> bool {
+    let (mut a, mut b) = (a, b);
+    loop {
+        if a.0 == b.0 {
+            return true;
+        } else if a.is_pair() && b.is_pair() {
+            if !equal(a.car(), b.car()) {
+                return false;
+            }
+            a = a.cdr();
+            b = b.cdr();
+        } else if a.is_vec() && b.is_vec() {
+            let (x, y) = (a.to_vec(), b.to_vec());
+            let r = x.vec.len() == y.vec.len() && x.vec.iter().zip(&y.vec).all(|(&x, &y)| equal(x, y));
+            Box::into_raw(x);
+            Box::into_raw(y);
+            return r;
+        } else if a.is_string() && b.is_string() {
+            let (x, y) = (a.to_string(), b.to_string());
+            let r = x.str == y.str;
+            Box::into_raw(x);
+            Box::into_raw(y);
+            return r;
+        } else if a.is_bytevector() && b.is_bytevector() {
+            let (x, y) = (a.to_bytevector(), b.to_bytevector());
+            let r = x.bytes == y.bytes;
+            Box::into_raw(x);
+            Box::into_raw(y);
+            return r;
+        } else if a.is_bigint() && b.is_bigint() {
+            let (x, y) = (a.to_bigint(), b.to_bigint());
+            let r = x.n == y.n;
+            Box::into_raw(x);
+            Box::into_raw(y);
+            return r;
+        } else {
+            return false;
+        }
+    }
+}
+
+/// A hash consistent with `equal`.
+fn hash_value<H: Hasher>(v: Value, depth: usize, state: &mut H) {
+    if depth == HASH_DEPTH {
+        return;
+    }
+    if v.is_pair() {
+        0u8.hash(state);
+        hash_value(v.car(), depth + 1, state);
+        hash_value(v.cdr(), depth + 1, state);
+    } else if v.is_vec() {
+        let vec = v.to_vec();
+        1u8.hash(state);
+        vec.vec.len().hash(state);
+        for &e in &vec.vec {
+            hash_value(e, depth + 1, state);
+        }
+        Box::into_raw(vec);
+    } else if v.is_string() {
+        let s = v.to_string();
+        s.str.hash(state);
+        Box::into_raw(s);
+    } else if v.is_bytevector() {
+        let b = v.to_bytevector();
+        b.bytes.hash(state);
+        Box::into_raw(b);
+    } else if v.is_bigint() {
+        let b = v.to_bigint();
+        b.n.hash(state);
+        Box::into_raw(b);
+    } else {
+        v.0.hash(state);
+    }
+}
+
+/// The arguments of a call.
+#[derive(Clone, Debug)]
+struct Key(Vec<Value>);
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.len().hash(state);
+        for &v in &self.0 {
+            hash_value(v, 0, state);
+        }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        self.0.len() == other.0.len() && self.0.iter().zip(&other.0).all(|(&a, &b)| equal(a, b))
+    }
+}
+
+impl Eq for Key {}
+
+/// The results of a memoized procedure.
+#[derive(Debug)]
+pub(crate) struct Cache {
+    // Results and the tick at which they were last used
+    entries: HashMap<Key, (Value, u64)>,
+    // Keys by the tick at which they were last used, only kept for a bounded cache
+    order: BTreeMap<u64, Key>,
+    limit: Option<usize>,
+    tick: u64,
+}
+
+impl Cache {
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Cache {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            limit: limit,
+            tick: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub(crate) fn get(&mut self, args: &[Value]) -> Option<Value> {
+        let key = Key(args.to_vec());
+        self.tick += 1;
+        let tick = self.tick;
+        let bounded = self.limit.is_some();
+        let entry = self.entries.get_mut(&key)?;
+        if bounded {
+            self.order.remove(&entry.1);
+            self.order.insert(tick, key);
+        }
+        entry.1 = tick;
+        Some(entry.0)
+    }
+
+    pub(crate) fn insert(&mut self, args: Vec<Value>, result: Value) {
+        let key = Key(args);
+        self.tick += 1;
+        if let Some(limit) = self.limit {
+            if let Some((old, _)) = self.entries.remove(&key) {
+                self.order.remove(&old);
+            }
+            while self.entries.len() >= limit {
+                let oldest = *self.order.keys().next().unwrap();
+                let evicted = self.order.remove(&oldest).unwrap();
+                self.entries.remove(&evicted);
+            }
+            self.order.insert(self.tick, key.clone());
+        }
+        self.entries.insert(key, (result, self.tick));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    /// The keys and results held by the cache, for the collector.
+    pub(crate) fn values(&self) -> impl Iterator<Item = Value> + '_ {
+        self.entries.iter().flat_map(|(k, &(v, _))| k.0.iter().cloned().chain(Some(v)))
+    }
+}
+
+/// `(memoize procedure [limit])` Return a copy of `procedure` which caches its results, keeping at
+/// most `limit` of them.
+pub fn memoize(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let f = args[0];
+    if !f.is_lambda() {
+        return Err(format!("memoize: {} is not a compound procedure", f));
+    }
+    let limit = match args.get(1) {
+        None => None,
+        Some(v) if v.is_integer() && v.to_integer() > 0 => Some(v.to_integer() as usize),
+        Some(v) => return Err(format!("memoize: {} is not a positive integer", v)),
+    };
+
+    let lambda = f.to_lambda();
+    let memoized = Value::Closure(lambda.env.clone(), lambda.code.clone(), lambda.consts.clone());
+    Box::into_raw(lambda);
+    let mut lambda = memoized.to_lambda();
+    lambda.memo = Some(Box::new(Cache::new(limit)));
+    Box::into_raw(lambda);
+    Ok(memoized)
+}
+
+fn cache_arg<F, T>(name: &str, f: Value, action: F) -> Result<T, String>
+    where F: FnOnce(&mut Cache) -> T
+{
+    if f.is_lambda() {
+        let mut lambda = f.to_lambda();
+        let r = lambda.memo.as_mut().map(|memo| action(memo));
+        Box::into_raw(lambda);
+        if let Some(r) = r {
+            return Ok(r);
+        }
+    }
+    Err(format!("{}: {} is not a memoized procedure", name, f))
+}
+
+/// `(memoize-clear! procedure)` Forget the results cached by a memoized procedure.
+pub fn clear(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    cache_arg("memoize-clear!", args[0], Cache::clear)?;
+    Ok(Value::Void)
+}
+
+/// `(memoize-count procedure)` The number of results cached by a memoized procedure.
+pub fn count(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let n = cache_arg("memoize-count", args[0], |memo| memo.len())?;
+    Ok(Value::Integer(n as i32))
+}
@@ -0,0 +1,191 @@
+use {Environment, FromValue, IntoValue, Value, VM};
+
+use string_interner::get_symbol;
+
+use std::fmt;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// A procedure implemented in Rust. It is given the machine and its arguments and returns its
+/// result, or a message describing the error.
+pub type NativeFn = fn(&mut VM, &[Value]) -> Result<Value, String>;
+
+/// A procedure implemented by a Rust closure, which can use the state of the host it captures.
+pub type NativeClosure = dyn Fn(&mut VM, &[Value]) -> Result<Value, String> + Send + Sync;
+
+/// The number of arguments a native procedure accepts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    /// Between the first and second number, inclusive.
+    Range(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(self, argc: usize) -> bool {
+        match self {
+            Arity::Exactly(n) => argc == n,
+            Arity::AtLeast(n) => argc >= n,
+            Arity::Range(min, max) => min <= argc && argc <= max,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arity::Exactly(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Native {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub f: NativeFn,
+    // The index in `CLOSURES` of the closure called instead of `f`, if the procedure is one
+    closure: Option<usize>,
+}
+
+impl Native {
+    /// Call the procedure with `args`.
+    pub fn call(&self, vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+        match self.closure {
+            Some(i) => {
+                // The closure may define others, so the table isn't locked while it runs
+                let f = Arc::clone(&CLOSURES.read().unwrap()[i]);
+                f(vm, args)
+            }
+            None => (self.f)(vm, args),
+        }
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native {}>", self.name)
+    }
+}
+
+// Natives are referred to by their index in this table, so it is shared by every machine.
+static NATIVES: LazyLock<RwLock<Vec<Native>>> = LazyLock::new(|| RwLock::new(Vec::new()));
+// The closures of natives defined by `define_closure`
+static CLOSURES: LazyLock<RwLock<Vec<Arc<NativeClosure>>>> = LazyLock::new(|| RwLock::new(Vec::new()));
+
+/// Why a native procedure could not be defined.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NativeError {
+    /// The name is already bound in the environment.
+    AlreadyBound(String),
+    /// A different procedure has already been registered under the name.
+    AlreadyRegistered(String),
+}
+
+impl fmt::Display for NativeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NativeError::AlreadyBound(name) => write!(f, "{} is already bound", name),
+            NativeError::AlreadyRegistered(name) =>
+                write!(f, "a different native procedure is already registered as {}", name),
+        }
+    }
+}
+
+/// Register a native procedure and return the value representing it. Natives are identified by
+/// name, registering a name again returns the existing procedure. Use `define_native` to detect
+/// collisions instead.
+pub fn register_native(name: &'static str, arity: Arity, f: NativeFn) -> Value {
+    let mut natives = NATIVES.write().unwrap();
+    if let Some(i) = natives.iter().position(|n| n.name == name && n.closure.is_none()) {
+        return Value::Native(i as u32);
+    }
+    natives.push(Native { name, arity, f, closure: None });
+    Value::Native(natives.len() as u32 - 1)
+}
+
+/// Register a native procedure and bind it to `name` in `env`. Fails if `name` is already bound in
+/// `env` or one of its parents, or if a different procedure is registered under `name`.
+pub fn define_native(env: &Environment, name: &str, arity: Arity, f: NativeFn) -> Result<Value, NativeError> {
+    let symbol = get_symbol(name.to_string());
+    if env.lookup_variable_value(symbol).is_some() {
+        return Err(NativeError::AlreadyBound(name.to_string()));
+    }
+
+    let mut natives = NATIVES.write().unwrap();
+    let native = match natives.iter().position(|n| n.name == name && n.closure.is_none()) {
+        // Registering the same procedure again is harmless
+        Some(i) if natives[i].arity == arity && natives[i].f as usize == f as usize => Value::Native(i as u32),
+        Some(_) => return Err(NativeError::AlreadyRegistered(name.to_string())),
+        None => {
+            // Natives live as long as the program, so their names can too
+            let name = Box::leak(name.to_string().into_boxed_str());
+            natives.push(Native { name, arity, f, closure: None });
+            Value::Native(natives.len() as u32 - 1)
+        }
+    };
+    env.define_variable(symbol, native);
+    Ok(native)
+}
+
+/// Define a native procedure named `module:name`, e.g. `host:read-sensor`, so that host APIs can't
+/// clobber the standard library.
+pub fn define_module_native(env: &Environment, module: &str, name: &str, arity: Arity, f: NativeFn)
+    -> Result<Value, NativeError>
+{
+    define_native(env, &format!("{}:{}", module, name), arity, f)
+}
+
+/// Register the closure `f` as a native procedure and bind it to `name` in `env`. Unlike
+/// `define_native`, each call registers a new procedure, since closures can't be compared, so a
+/// host should define a closure once for each environment rather than before each evaluation.
+/// Fails if `name` is already bound in `env` or one of its parents.
+pub fn define_closure<F>(env: &Environment, name: &str, arity: Arity, f: F) -> Result<Value, NativeError>
+    where F: Fn(&mut VM, &[Value]) -> Result<Value, String> + Send + Sync + 'static
+{
+    let symbol = get_symbol(name.to_string());
+    if env.lookup_variable_value(symbol).is_some() {
+        return Err(NativeError::AlreadyBound(name.to_string()));
+    }
+
+    let mut closures = CLOSURES.write().unwrap();
+    closures.push(Arc::new(f));
+    let closure = Some(closures.len() - 1);
+    let mut natives = NATIVES.write().unwrap();
+    let name = Box::leak(name.to_string().into_boxed_str());
+    natives.push(Native { name, arity, f: closure_fn, closure });
+    let native = Value::Native(natives.len() as u32 - 1);
+    env.define_variable(symbol, native);
+    Ok(native)
+}
+
+// The `f` of a closure, which `Native::call` never calls
+fn closure_fn(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    unreachable!()
+}
+
+/// The string `v`, as an argument to a native procedure.
+pub fn string_arg(v: Value) -> Result<String, String> {
+    String::from_value(v)
+}
+
+/// The integer `v`, as an argument to a native procedure.
+pub fn integer_arg(v: Value) -> Result<i32, String> {
+    i32::from_value(v)
+}
+
+/// The elements of the list `v`, as an argument to a native procedure.
+pub fn list_arg(v: Value) -> Result<Vec<Value>, String> {
+    Vec::from_value(v)
+}
+
+/// A list of `values`, to return from a native procedure.
+pub fn make_list(values: Vec<Value>) -> Value {
+    values.into_value()
+}
+
+pub(crate) fn get_native(i: usize) -> Native {
+    NATIVES.read().unwrap()[i]
+}
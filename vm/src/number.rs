@@ -0,0 +1,333 @@
+//! Arithmetic, division, the integer division operators, conversions between integer widths, and
+//! writing numbers as text.
+//!
+//! Integers, fixnums and bignums, are exact and floats are inexact. Arithmetic on exact numbers
+//! gives an exact result, which becomes a bignum rather than overflowing and a fixnum again
+//! whenever it fits in one, while an inexact operand makes the result inexact. Numbers compare by value, so `(= 1 1.0)` is true. The reader makes a
+//! literal exact or inexact with a `#e` or `#i` prefix.
+//!
+//! There are no rationals, so dividing exact integers which don't divide evenly gives a float, and
+//! the reader only accepts a ratio such as `6/3` which is an integer.
+//! Dividing an exact integer by exact zero is an error, while float division follows IEEE 754 and
+//! gives an infinity or NaN.
+//!
+//! Width conversions take a type of `u8`, `s8`, `u16`, `s16`, `u32`, `s32`, `u64`, or `s64`.
+//! Results outside the fixnum range are bignums.
+
+use {Value, VM};
+
+use num_bigint::BigInt;
+use string_interner::get_value;
+
+use std::cmp::Ordering;
+
+enum Number {
+    Exact(BigInt),
+    Inexact(f64),
+}
+
+fn number(name: &str, v: Value) -> Result<Number, String> {
+    if v.is_float() {
+        Ok(Number::Inexact(v.to_float()))
+    } else if is_number(v) {
+        Ok(Number::Exact(exact_integer(name, v)?))
+    } else {
+        Err(format!("{}: {} is not a number", name, v))
+    }
+}
+
+fn float(n: &Number) -> f64 {
+    match n {
+        Number::Exact(i) => i.to_string().parse().unwrap(),
+        Number::Inexact(f) => *f,
+    }
+}
+
+fn is_zero(n: &BigInt) -> bool {
+    *n == BigInt::from(0)
+}
+
+fn divide(a: Number, b: Number) -> Result<Value, String> {
+    match (a, b) {
+        (Number::Exact(_), Number::Exact(ref b)) if is_zero(b) => Err("/: division by zero".to_string()),
+        (Number::Exact(a), Number::Exact(b)) => Ok(if is_zero(&(&a % &b)) {
+            Value::integer(a / b)
+        } else {
+            Value::Float(float(&Number::Exact(a)) / float(&Number::Exact(b)))
+        }),
+        (a, b) => Ok(Value::Float(float(&a) / float(&b))),
+    }
+}
+
+/// `(/ z)` `(/ z1 z2 ...)` The reciprocal of `z`, or `z1` divided by the rest.
+pub fn div(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if args.len() == 1 {
+        return divide(Number::Exact(BigInt::from(1)), number("/", args[0])?);
+    }
+    let mut result = args[0];
+    for &v in &args[1..] {
+        result = divide(number("/", result)?, number("/", v)?)?;
+    }
+    Ok(result)
+}
+
+/// The operands of an integer division, as integers or integral floats.
+fn integers(name: &str, args: &[Value]) -> Result<(Number, Number), String> {
+    let integer = |v: Value| match number(name, v)? {
+        Number::Inexact(f) if f.fract() != 0.0 || !f.is_finite() =>
+            Err(format!("{}: {} is not an integer", name, v)),
+        n => Ok(n),
+    };
+    let (n1, n2) = (integer(args[0])?, integer(args[1])?);
+    match n2 {
+        Number::Exact(ref i) if is_zero(i) => Err(format!("{}: division by zero", name)),
+        Number::Inexact(f) if f == 0.0 => Err(format!("{}: division by zero", name)),
+        n2 => Ok((n1, n2)),
+    }
+}
+
+/// Apply an integer division operator, using floats if either operand is inexact.
+fn integer_division<I, F>(name: &str, args: &[Value], exact: I, inexact: F) -> Result<Value, String>
+    where I: Fn(BigInt, BigInt) -> BigInt, F: Fn(f64, f64) -> f64
+{
+    match integers(name, args)? {
+        (Number::Exact(a), Number::Exact(b)) => Ok(Value::integer(exact(a, b))),
+        (a, b) => Ok(Value::Float(inexact(float(&a), float(&b)))),
+    }
+}
+
+/// `(quotient n1 n2)` `n1` divided by `n2`, truncated towards zero.
+pub fn quotient(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    integer_division("quotient", args, |a, b| a / b, |a, b| (a / b).trunc())
+}
+
+/// `(remainder n1 n2)` The remainder of `quotient`, with the sign of `n1`.
+pub fn remainder(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    integer_division("remainder", args, |a, b| a % b, |a, b| a % b)
+}
+
+/// `(modulo n1 n2)` The remainder of floored division, with the sign of `n2`.
+pub fn modulo(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    integer_division("modulo", args, |a, b| {
+        let r = a % &b;
+        if !is_zero(&r) && (r < BigInt::from(0)) != (b < BigInt::from(0)) { r + b } else { r }
+    }, |a, b| {
+        let r = a % b;
+        if r != 0.0 && (r < 0.0) != (b < 0.0) { r + b } else { r }
+    })
+}
+
+/// Whether `v` is a number, exact or inexact.
+pub(crate) fn is_number(v: Value) -> bool {
+    v.is_integer() || v.is_bigint() || v.is_float()
+}
+
+/// The value of a number argument as a float.
+fn inexact_value(name: &str, v: Value) -> Result<f64, String> {
+    if v.is_float() {
+        Ok(v.to_float())
+    } else if v.is_integer() {
+        Ok(v.to_integer() as f64)
+    } else if v.is_bigint() {
+        Ok(exact_integer(name, v)?.to_string().parse().unwrap())
+    } else {
+        Err(format!("{}: {} is not a number", name, v))
+    }
+}
+
+/// Apply `f` to the exact integer `v`, borrowing a bignum from the heap rather than copying it.
+fn with_exact<T>(v: Value, f: impl FnOnce(&BigInt) -> T) -> T {
+    if v.is_bigint() {
+        let b = v.to_bigint();
+        let r = f(&b.n);
+        Box::into_raw(b);
+        r
+    } else {
+        f(&BigInt::from(v.to_integer()))
+    }
+}
+
+/// Apply an arithmetic operator to two numbers. The result is inexact if either operand is, and
+/// otherwise a fixnum if it fits in one. Fixnums are tried first, so that only a result which
+/// overflows, or a bignum operand, reaches `bignum`.
+pub(crate) fn arithmetic<I, B, F>(name: &str, a: Value, b: Value, fixnum: I, bignum: B, inexact: F) -> Result<Value, String>
+    where I: Fn(i32, i32) -> Option<i32>, B: Fn(&BigInt, &BigInt) -> BigInt, F: Fn(f64, f64) -> f64
+{
+    if a.is_integer() && b.is_integer() {
+        if let Some(r) = fixnum(a.to_integer(), b.to_integer()) {
+            return Ok(Value::Integer(r));
+        }
+    }
+    if a.is_float() || b.is_float() {
+        return Ok(Value::Float(inexact(inexact_value(name, a)?, inexact_value(name, b)?)));
+    }
+    for &v in &[a, b] {
+        if !is_number(v) {
+            return Err(format!("{}: {} is not a number", name, v));
+        }
+    }
+    Ok(Value::integer(with_exact(a, |a| with_exact(b, |b| bignum(a, b)))))
+}
+
+/// Compare the values of two numbers. Returns `None` if either is NaN.
+pub(crate) fn compare(name: &str, a: Value, b: Value) -> Result<Option<Ordering>, String> {
+    if a.is_integer() && b.is_integer() {
+        Ok(Some(a.to_integer().cmp(&b.to_integer())))
+    } else if a.is_float() || b.is_float() {
+        Ok(inexact_value(name, a)?.partial_cmp(&inexact_value(name, b)?))
+    } else {
+        for &v in &[a, b] {
+            if !is_number(v) {
+                return Err(format!("{}: {} is not a number", name, v));
+            }
+        }
+        Ok(Some(with_exact(a, |a| with_exact(b, |b| a.cmp(b)))))
+    }
+}
+
+/// `(exact? z)` Whether `z` is exact, an integer rather than a float.
+pub fn is_exact(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !is_number(args[0]) {
+        return Err(format!("exact?: {} is not a number", args[0]));
+    }
+    Ok(Value::Bool(!args[0].is_float()))
+}
+
+/// `(inexact? z)` Whether `z` is inexact, a float.
+pub fn is_inexact(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !is_number(args[0]) {
+        return Err(format!("inexact?: {} is not a number", args[0]));
+    }
+    Ok(Value::Bool(args[0].is_float()))
+}
+
+/// `(exact z)` The exact number equal to `z`. Without rationals, only integral floats have one.
+pub fn exact(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let z = args[0];
+    if !z.is_float() {
+        return if is_number(z) { Ok(z) } else { Err(format!("exact: {} is not a number", z)) };
+    }
+    let f = z.to_float();
+    if f.fract() != 0.0 || !f.is_finite() {
+        return Err(format!("exact: {} has no exact representation", z));
+    }
+    Ok(Value::integer(format!("{:.0}", f).parse().unwrap()))
+}
+
+/// `(inexact z)` The float nearest to `z`.
+pub fn inexact(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Float(inexact_value("inexact", args[0])?))
+}
+
+/// An exact integer argument, a fixnum or a bignum.
+pub(crate) fn exact_integer(name: &str, v: Value) -> Result<BigInt, String> {
+    if v.is_integer() {
+        Ok(BigInt::from(v.to_integer()))
+    } else if v.is_bigint() {
+        let b = v.to_bigint();
+        let n = b.n.clone();
+        Box::into_raw(b);
+        Ok(n)
+    } else {
+        Err(format!("{}: {} is not an exact integer", name, v))
+    }
+}
+
+/// Parse an integer type, returning its width in bits and whether it is signed.
+fn integer_type(name: &str, v: Value) -> Result<(u32, bool), String> {
+    if v.is_symbol() {
+        let t = get_value(v.to_symbol()).unwrap();
+        let signed = match t.chars().next() {
+            Some('u') => Some(false),
+            Some('s') => Some(true),
+            _ => None,
+        };
+        let bits = t.get(1..).and_then(|b| b.parse::<u32>().ok());
+        if let (Some(signed), Some(bits @ (8 | 16 | 32 | 64))) = (signed, bits) {
+            return Ok((bits, signed));
+        }
+    }
+    Err(format!("{}: {} is not an integer type", name, v))
+}
+
+/// The range of an integer type.
+fn type_range(bits: u32, signed: bool) -> (BigInt, BigInt) {
+    if signed {
+        (-(BigInt::from(1) << (bits - 1)), (BigInt::from(1) << (bits - 1)) - 1)
+    } else {
+        (BigInt::from(0), (BigInt::from(1) << bits) - 1)
+    }
+}
+
+/// Reduce `n` to the range of an integer type, as two's complement arithmetic would.
+fn wrap(n: BigInt, bits: u32, signed: bool) -> BigInt {
+    let modulus = BigInt::from(1) << bits;
+    let mut n = ((n % &modulus) + &modulus) % &modulus;
+    if signed && n >= (BigInt::from(1) << (bits - 1)) {
+        n -= modulus;
+    }
+    n
+}
+
+/// `(wrapping-integer n type)` `n` reduced to the range of `type` by keeping its low bits.
+pub fn wrapping_integer(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let n = exact_integer("wrapping-integer", args[0])?;
+    let (bits, signed) = integer_type("wrapping-integer", args[1])?;
+    Ok(Value::integer(wrap(n, bits, signed)))
+}
+
+/// `(checked-integer n type)` `n` if it is in the range of `type`, otherwise `#f`.
+pub fn checked_integer(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let n = exact_integer("checked-integer", args[0])?;
+    let (bits, signed) = integer_type("checked-integer", args[1])?;
+    let (min, max) = type_range(bits, signed);
+    if n >= min && n <= max {
+        Ok(args[0])
+    } else {
+        Ok(Value::False)
+    }
+}
+
+/// Reinterpret the bits of `n`, which must be in the range of the type with the opposite
+/// signedness.
+fn reinterpret(name: &str, v: Value, signed: bool) -> Result<Value, String> {
+    let n = exact_integer(name, v)?;
+    let (min, max) = type_range(32, !signed);
+    if n < min || n > max {
+        return Err(format!("{}: {} is out of range", name, v));
+    }
+    Ok(Value::integer(wrap(n, 32, signed)))
+}
+
+/// `(i32->u32 n)` The unsigned 32 bit integer with the same bits as `n`.
+pub fn i32_to_u32(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    reinterpret("i32->u32", args[0], false)
+}
+
+/// `(u32->i32 n)` The signed 32 bit integer with the same bits as `n`.
+pub fn u32_to_i32(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    reinterpret("u32->i32", args[0], true)
+}
+
+/// `(number->string z [precision])` The text of `z`, as `write` would write it, or with exactly
+/// `precision` digits after the decimal point.
+pub fn number_to_string(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let z = args[0];
+    if !z.is_integer() && !z.is_float() && !z.is_bigint() {
+        return Err(format!("number->string: {} is not a number", z));
+    }
+    let precision = match args.get(1) {
+        None => return Ok(Value::String(format!("{}", z))),
+        Some(p) if p.is_integer() && p.to_integer() >= 0 => p.to_integer() as usize,
+        Some(p) => return Err(format!("number->string: {} is not a valid precision", p)),
+    };
+    vm.check_allocation("number->string", precision)?;
+    let s = if z.is_float() && z.to_float().is_finite() {
+        format!("{:.*}", precision, z.to_float())
+    } else if z.is_float() || precision == 0 {
+        format!("{}", z)
+    } else {
+        format!("{}.{}", exact_integer("number->string", z)?, "0".repeat(precision))
+    };
+    Ok(Value::String(s))
+}
@@ -0,0 +1,161 @@
+//! Manipulating file system paths.
+//!
+//! Paths are strings, taken apart and put together by `std::path`, so they use the separators of
+//! the platform: `path-join` puts `/` between components on Unix and `\` on Windows. None of
+//! these procedures look at the file system; `path-normalize` in particular removes `..` by
+//! dropping the component before it, which differs from the file system's answer when that
+//! component is a symbolic link.
+//!
+//! `expand-user` and `expand-variables` fill in a path from the environment, as a shell would, for
+//! scripts which are given paths like `~/notes` or `$XDG_CONFIG_HOME/app`.
+
+use {Value, VM};
+
+use std::env;
+use std::path::{is_separator, Component, Path, PathBuf, MAIN_SEPARATOR};
+
+// The variable holding the home directory of the user
+#[cfg(not(windows))]
+const HOME: &str = "HOME";
+#[cfg(windows)]
+const HOME: &str = "USERPROFILE";
+
+fn string_arg(name: &str, v: Value) -> Result<String, String> {
+    if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.clone();
+        Box::into_raw(s);
+        Ok(r)
+    } else {
+        Err(format!("{}: {} is not a string", name, v))
+    }
+}
+
+fn path_value(name: &str, path: &Path) -> Result<Value, String> {
+    match path.to_str() {
+        Some(s) => Ok(Value::String(s.to_string())),
+        None => Err(format!("{}: {} is not valid UTF-8", name, path.display())),
+    }
+}
+
+/// `(expand-user path)` `path` with a leading `~` replaced by the home directory of the user.
+/// Other paths, including those starting `~name`, are returned unchanged.
+pub fn expand_user(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let path = string_arg("expand-user", args[0])?;
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(is_separator) => rest,
+        _ => return Ok(Value::String(path)),
+    };
+    match env::var(HOME) {
+        Ok(home) => Ok(Value::String(home + rest)),
+        Err(_) => Err(format!("expand-user: the home directory is unknown, {} is not set", HOME)),
+    }
+}
+
+/// `(expand-variables string)` `string` with each `$NAME` or `${NAME}` replaced by the value of
+/// the environment variable `NAME`. References to variables which are not set are left as they
+/// are.
+pub fn expand_variables(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let s = string_arg("expand-variables", args[0])?;
+    let mut out = String::with_capacity(s.len());
+    let mut rest = &s[..];
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        let (name, len) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            (&rest[..end], end)
+        };
+        match if name.is_empty() { None } else { env::var(name).ok() } {
+            Some(value) => out.push_str(&value),
+            None => {
+                out.push('$');
+                out.push_str(&rest[..len]);
+            }
+        }
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+    Ok(Value::String(out))
+}
+
+/// `(path-join path ...)` The paths joined by the separator of the platform. A path which is
+/// absolute replaces the ones before it.
+pub fn path_join(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let mut path = PathBuf::new();
+    for &v in args {
+        path.push(string_arg("path-join", v)?);
+    }
+    path_value("path-join", &path)
+}
+
+/// `(path-extension path)` The extension of the last component of `path`, without the `.`, or
+/// `#f` if it has none.
+pub fn path_extension(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let path = string_arg("path-extension", args[0])?;
+    match Path::new(&path).extension() {
+        Some(ext) => path_value("path-extension", Path::new(ext)),
+        None => Ok(Value::False),
+    }
+}
+
+/// `(path-file-name path)` The last component of `path`, or `#f` if it ends in `..` or is a root.
+pub fn path_file_name(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let path = string_arg("path-file-name", args[0])?;
+    match Path::new(&path).file_name() {
+        Some(name) => path_value("path-file-name", Path::new(name)),
+        None => Ok(Value::False),
+    }
+}
+
+/// `(path-parent path)` `path` without its last component, or `#f` if it is a root or empty.
+pub fn path_parent(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let path = string_arg("path-parent", args[0])?;
+    match Path::new(&path).parent() {
+        Some(parent) => path_value("path-parent", parent),
+        None => Ok(Value::False),
+    }
+}
+
+/// `(path-absolute? path)`
+pub fn is_path_absolute(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let path = string_arg("path-absolute?", args[0])?;
+    Ok(Value::Bool(Path::new(&path).is_absolute()))
+}
+
+/// `(path-normalize path)` `path` without `.` components, repeated separators, or `..`
+/// components which follow a name. A path which normalizes to nothing is `"."`.
+pub fn path_normalize(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let path = string_arg("path-normalize", args[0])?;
+    let mut normal: Vec<Component> = Vec::new();
+    for component in Path::new(&path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normal.last() {
+                Some(Component::Normal(_)) => {
+                    normal.pop();
+                }
+                // `..` at the root is the root
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normal.push(component),
+            },
+            _ => normal.push(component),
+        }
+    }
+    let normal: PathBuf = normal.into_iter().collect();
+    if normal.as_os_str().is_empty() {
+        Ok(Value::String(".".to_string()))
+    } else {
+        path_value("path-normalize", &normal)
+    }
+}
+
+/// `(path-separator)` The character which separates the components of a path on this platform.
+pub fn path_separator(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Char(MAIN_SEPARATOR))
+}
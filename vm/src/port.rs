@@ -0,0 +1,699 @@
+//! Ports for reading and writing characters and bytes.
+//!
+//! Streams which may block, such as the terminal or a socket, are polled for input before they are
+//! read. This lets `char-ready?`, the `-no-hang` reads, and read timeouts be answered without
+//! blocking the machine. Input is only read when a read asks for it, so standard input is left to
+//! the REPL's line editor otherwise.
+//!
+//! A port value is an index into the port table of the machine. Closing a port frees its slot for
+//! the next port opened, except for the standard ports, so a closed port must not be used again.
+
+use {Value, VM};
+use bytevector::{endianness, Endianness, Numeric};
+use table::Table;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+/// The index of the port reading from stdin.
+pub(crate) const STDIN: usize = 0;
+/// The index of the port writing to stdout.
+pub(crate) const STDOUT: usize = 1;
+
+pub(crate) enum Port {
+    Input(InputPort),
+    Output(OutputPort),
+}
+
+impl fmt::Debug for Port {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Port::Input(_) => write!(f, "<input port>"),
+            Port::Output(_) => write!(f, "<output port>"),
+        }
+    }
+}
+
+/// The initial port table of a machine.
+pub(crate) fn standard_ports() -> Table<Port> {
+    Table::with(vec![
+        Port::Input(InputPort::new(Source::Stream(Box::new(Stdin), 0))),
+        Port::Output(OutputPort::Stdout),
+    ])
+}
+
+enum Source {
+    /// Nothing more will be read.
+    Done,
+    /// A source which is always ready, such as a file.
+    Reader(Box<dyn Read>),
+    /// A stream which may block, and the file descriptor polled for input before reading it.
+    Stream(Box<dyn Read>, i32),
+}
+
+/// Standard input, read directly rather than through the buffer of `io::Stdin`, so that nothing
+/// is taken from it which isn't in the port's own buffer.
+struct Stdin;
+
+impl Read for Stdin {
+    #[cfg(unix)]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::mem::ManuallyDrop;
+        use std::os::unix::io::FromRawFd;
+
+        // Standard input stays open after the read
+        let mut stdin = ManuallyDrop::new(unsafe { File::from_raw_fd(0) });
+        stdin.read(buf)
+    }
+
+    #[cfg(not(unix))]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::stdin().read(buf)
+    }
+}
+
+#[cfg(unix)]
+mod os {
+    use std::io;
+    use std::os::raw::{c_int, c_short};
+    use std::time::Duration;
+
+    #[repr(C)]
+    struct PollFd {
+        fd: c_int,
+        events: c_short,
+        revents: c_short,
+    }
+
+    const POLLIN: c_short = 1;
+
+    #[cfg(target_os = "linux")]
+    type NFds = std::os::raw::c_ulong;
+    #[cfg(not(target_os = "linux"))]
+    type NFds = std::os::raw::c_uint;
+
+    extern "C" {
+        fn poll(fds: *mut PollFd, nfds: NFds, timeout: c_int) -> c_int;
+    }
+
+    /// Wait for `fd` to have input, at most `timeout` or forever if `None`. Returns whether a read
+    /// won't block, which is also the case at its end or when reading it would fail.
+    pub(super) fn readable(fd: i32, timeout: Option<Duration>) -> bool {
+        let timeout = match timeout {
+            // Rounded up, so that a read doesn't give up before its deadline
+            Some(t) => t.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int,
+            None => -1,
+        };
+        let mut fds = PollFd { fd: fd, events: POLLIN, revents: 0 };
+        loop {
+            match unsafe { poll(&mut fds, 1, timeout) } {
+                n if n >= 0 => return n > 0,
+                _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+                // Let the read report the error
+                _ => return true,
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod os {
+    use std::time::Duration;
+
+    /// Without a way to poll, a read always blocks until there is input.
+    pub(super) fn readable(_: i32, _: Option<Duration>) -> bool {
+        true
+    }
+}
+
+/// How long a read may wait for input.
+#[derive(Copy, Clone)]
+enum Wait {
+    Block,
+    Until(Instant),
+    NoHang,
+}
+
+pub(crate) struct InputPort {
+    buffer: VecDeque<u8>,
+    source: Source,
+    timeout: Option<Duration>,
+}
+
+impl InputPort {
+    fn new(source: Source) -> Self {
+        InputPort {
+            buffer: VecDeque::new(),
+            source: source,
+            timeout: None,
+        }
+    }
+
+    fn bytes(b: &[u8]) -> Self {
+        let mut port = InputPort::new(Source::Done);
+        port.buffer.extend(b);
+        port
+    }
+
+
+    fn wait(&self) -> Wait {
+        match self.timeout {
+            Some(t) => Wait::Until(Instant::now() + t),
+            None => Wait::Block,
+        }
+    }
+
+    /// Try to buffer at least `n` bytes. Returns `false` if the port is not ready, `true` if there
+    /// are `n` bytes or the end of the port has been reached.
+    fn fill(&mut self, n: usize, wait: Wait) -> bool {
+        while self.buffer.len() < n {
+            let r = match self.source {
+                Source::Done => return true,
+                Source::Reader(ref mut r) => r,
+                Source::Stream(ref mut r, fd) => {
+                    let timeout = match wait {
+                        Wait::Block => None,
+                        Wait::Until(deadline) => Some(deadline.saturating_duration_since(Instant::now())),
+                        Wait::NoHang => Some(Duration::new(0, 0)),
+                    };
+                    if !os::readable(fd, timeout) {
+                        return false;
+                    }
+                    r
+                }
+            };
+            let mut buf = [0; 4096];
+            match r.read(&mut buf) {
+                Ok(0) | Err(_) => self.source = Source::Done,
+                Ok(n) => self.buffer.extend(&buf[..n]),
+            }
+        }
+        true
+    }
+
+    /// Read or peek at the next character. `None` if the port was not ready in time.
+    fn next_char(&mut self, wait: Wait, consume: bool) -> Option<Value> {
+        if !self.fill(1, wait) {
+            return None;
+        } else if self.buffer.is_empty() {
+            return Some(Value::Eof);
+        }
+
+        let width = match self.buffer[0] {
+            b if b < 0x80 => 1,
+            b if b >= 0xF0 => 4,
+            b if b >= 0xE0 => 3,
+            b if b >= 0xC0 => 2,
+            _ => 1,
+        };
+        if !self.fill(width, wait) {
+            return None;
+        }
+        let width = width.min(self.buffer.len());
+        let bytes: Vec<u8> = self.buffer.iter().take(width).cloned().collect();
+        if consume {
+            self.buffer.drain(..width);
+        }
+        let c = String::from_utf8_lossy(&bytes).chars().next().unwrap();
+        Some(Value::Char(c))
+    }
+
+    /// Read up to `n` bytes, fewer only at the end of the port. `None` if the port was not ready
+    /// in time.
+    fn next_bytes(&mut self, n: usize, wait: Wait, consume: bool) -> Option<Vec<u8>> {
+        if !self.fill(n, wait) {
+            return None;
+        }
+        let n = n.min(self.buffer.len());
+        if consume {
+            Some(self.buffer.drain(..n).collect())
+        } else {
+            Some(self.buffer.iter().take(n).cloned().collect())
+        }
+    }
+
+    /// Read the next line, without its newline. `None` if the port was not ready in time.
+    fn read_line(&mut self, wait: Wait) -> Option<Value> {
+        loop {
+            if let Some(i) = self.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=i).take(i).collect();
+                return Some(Value::String(String::from_utf8_lossy(&line).into_owned()));
+            }
+            let n = self.buffer.len() + 1;
+            if !self.fill(n, wait) {
+                return None;
+            } else if self.buffer.len() < n {
+                // The end of the port
+                if self.buffer.is_empty() {
+                    return Some(Value::Eof);
+                }
+                let line: Vec<u8> = self.buffer.drain(..).collect();
+                return Some(Value::String(String::from_utf8_lossy(&line).into_owned()));
+            }
+        }
+    }
+}
+
+pub(crate) enum OutputPort {
+    Stdout,
+    /// A string or bytevector port.
+    Buffer(Vec<u8>),
+    Writer(Box<dyn Write>),
+}
+
+impl OutputPort {
+    fn write(&mut self, b: &[u8]) -> io::Result<()> {
+        match self {
+            OutputPort::Stdout => io::stdout().write_all(b),
+            OutputPort::Buffer(buf) => {
+                buf.extend(b);
+                Ok(())
+            }
+            OutputPort::Writer(w) => w.write_all(b),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputPort::Stdout => io::stdout().flush(),
+            OutputPort::Buffer(_) => Ok(()),
+            OutputPort::Writer(w) => w.flush(),
+        }
+    }
+}
+
+fn open(vm: &mut VM, port: Port) -> Value {
+    Value::Port(vm.ports.insert(port))
+}
+
+fn input_port<'a>(vm: &'a mut VM, name: &str, p: Option<&Value>) -> Result<&'a mut InputPort, String> {
+    let p = p.cloned().unwrap_or(Value::Port(STDIN as u32));
+    if p.is_port() {
+        if let Some(Port::Input(port)) = vm.ports.get_mut(p.to_port()) {
+            return Ok(port);
+        }
+    }
+    Err(format!("{}: {} is not an open input port", name, p))
+}
+
+fn output_port<'a>(vm: &'a mut VM, name: &str, p: Option<&Value>) -> Result<&'a mut OutputPort, String> {
+    let p = p.cloned().unwrap_or(Value::Port(STDOUT as u32));
+    if p.is_port() {
+        if let Some(Port::Output(port)) = vm.ports.get_mut(p.to_port()) {
+            return Ok(port);
+        }
+    }
+    Err(format!("{}: {} is not an open output port", name, p))
+}
+
+/// Read the rest of an input port, defaulting to the current input port.
+pub(crate) fn read_to_string(vm: &mut VM, name: &str, p: Option<&Value>) -> Result<String, String> {
+    let port = input_port(vm, name, p)?;
+    let wait = port.wait();
+    loop {
+        let n = port.buffer.len() + 1;
+        if !port.fill(n, wait) {
+            return Err(format!("{}: timed out", name));
+        } else if port.buffer.len() < n {
+            let bytes: Vec<u8> = port.buffer.drain(..).collect();
+            return Ok(String::from_utf8_lossy(&bytes).into_owned());
+        }
+    }
+}
+
+fn string_arg(name: &str, v: Value) -> Result<String, String> {
+    if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.clone();
+        Box::into_raw(s);
+        Ok(r)
+    } else {
+        Err(format!("{}: {} is not a string", name, v))
+    }
+}
+
+/// The text `display` writes for `v`: strings and characters without quoting.
+pub(crate) fn display_text(v: Value) -> String {
+    if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.clone();
+        Box::into_raw(s);
+        r
+    } else if v.is_char() {
+        v.to_char().to_string()
+    } else {
+        format!("{}", v)
+    }
+}
+
+pub(crate) fn write_bytes(vm: &mut VM, name: &str, port: Option<&Value>, b: &[u8]) -> Result<Value, String> {
+    output_port(vm, name, port)?.write(b).map_err(|e| format!("{}: {}", name, e))?;
+    Ok(Value::Void)
+}
+
+fn bytevector_arg(name: &str, v: Value) -> Result<Vec<u8>, String> {
+    if v.is_bytevector() {
+        let b = v.to_bytevector();
+        let r = b.bytes.clone();
+        Box::into_raw(b);
+        Ok(r)
+    } else {
+        Err(format!("{}: {} is not a bytevector", name, v))
+    }
+}
+
+/// `(current-input-port)`
+pub fn current_input_port(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Port(STDIN as u32))
+}
+
+/// `(current-output-port)`
+pub fn current_output_port(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Port(STDOUT as u32))
+}
+
+/// `(open-input-string string)`
+pub fn open_input_string(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let s = string_arg("open-input-string", args[0])?;
+    Ok(open(vm, Port::Input(InputPort::bytes(s.as_bytes()))))
+}
+
+/// `(open-output-string)`
+pub fn open_output_string(vm: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(open(vm, Port::Output(OutputPort::Buffer(Vec::new()))))
+}
+
+/// `(open-output-bytevector)`
+pub fn open_output_bytevector(vm: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(open(vm, Port::Output(OutputPort::Buffer(Vec::new()))))
+}
+
+/// `(open-input-bytevector bytevector)`
+pub fn open_input_bytevector(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let b = bytevector_arg("open-input-bytevector", args[0])?;
+    Ok(open(vm, Port::Input(InputPort::bytes(&b))))
+}
+
+/// `(get-output-string port)` Return everything written to a port opened by `open-output-string`.
+pub fn get_output_string(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    match output_port(vm, "get-output-string", args.first())? {
+        OutputPort::Buffer(b) => Ok(Value::String(String::from_utf8_lossy(b).into_owned())),
+        _ => Err(format!("get-output-string: {} is not a string port", args[0])),
+    }
+}
+
+/// `(get-output-bytevector port)` Return everything written to a port opened by
+/// `open-output-bytevector`.
+pub fn get_output_bytevector(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    match output_port(vm, "get-output-bytevector", args.first())? {
+        OutputPort::Buffer(b) => Ok(Value::Bytevector(b.clone())),
+        _ => Err(format!("get-output-bytevector: {} is not a bytevector port", args[0])),
+    }
+}
+
+fn open_file(vm: &mut VM, name: &str, path: Value, output: bool) -> Result<Value, String> {
+    let path = string_arg(name, path)?;
+    let error = |e: io::Error| format!("{}: {}: {}", name, path, e);
+    let port = if output {
+        Port::Output(OutputPort::Writer(Box::new(File::create(&path).map_err(error)?)))
+    } else {
+        Port::Input(InputPort::new(Source::Reader(Box::new(File::open(&path).map_err(error)?))))
+    };
+    Ok(open(vm, port))
+}
+
+/// `(open-input-file path)`
+pub fn open_input_file(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    open_file(vm, "open-input-file", args[0], false)
+}
+
+/// `(open-output-file path)`
+pub fn open_output_file(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    open_file(vm, "open-output-file", args[0], true)
+}
+
+/// `(open-binary-input-file path)`
+pub fn open_binary_input_file(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    open_file(vm, "open-binary-input-file", args[0], false)
+}
+
+/// `(open-binary-output-file path)`
+pub fn open_binary_output_file(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    open_file(vm, "open-binary-output-file", args[0], true)
+}
+
+/// `(open-tcp-connection host port)` Connect to `host` and return a pair of an input and an output
+/// port for the connection.
+pub fn open_tcp_connection(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let host = string_arg("open-tcp-connection", args[0])?;
+    if !args[1].is_integer() {
+        return Err(format!("open-tcp-connection: {} is not a port number", args[1]));
+    }
+    let address = format!("{}:{}", host, args[1].to_integer());
+    let error = |e: io::Error| format!("open-tcp-connection: {}: {}", address, e);
+    let stream = TcpStream::connect(&address).map_err(error)?;
+    let reader = stream.try_clone().map_err(error)?;
+    #[cfg(unix)]
+    let fd = reader.as_raw_fd();
+    #[cfg(not(unix))]
+    let fd = -1;
+    let input = open(vm, Port::Input(InputPort::new(Source::Stream(Box::new(reader), fd))));
+    let output = open(vm, Port::Output(OutputPort::Writer(Box::new(stream))));
+    Ok(Value::Pair(input, output))
+}
+
+/// `(close-port port)`
+pub fn close_port(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let p = args[0];
+    if !p.is_port() {
+        return Err(format!("close-port: {} is not a port", p));
+    }
+    // The standard ports stay closed rather than another port taking their place
+    let i = p.to_port();
+    let port = if i <= STDOUT { vm.ports.take(i) } else { vm.ports.remove(i) };
+    if let Some(Port::Output(mut o)) = port {
+        let _ = o.flush();
+    }
+    Ok(Value::Void)
+}
+
+/// `(port? obj)`
+pub fn is_port(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(args[0].is_port()))
+}
+
+/// `(input-port? obj)`
+pub fn is_input_port(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(input_port(vm, "input-port?", args.first()).is_ok()))
+}
+
+/// `(output-port? obj)`
+pub fn is_output_port(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(output_port(vm, "output-port?", args.first()).is_ok()))
+}
+
+/// `(eof-object)`
+pub fn eof_object(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Eof)
+}
+
+/// `(eof-object? obj)`
+pub fn is_eof_object(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(args[0].is_eof()))
+}
+
+/// `(read-char [port])` Read a character, waiting at most the port's read timeout. Returns `#f` if
+/// the timeout expires.
+pub fn read_char(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let port = input_port(vm, "read-char", args.first())?;
+    let wait = port.wait();
+    Ok(port.next_char(wait, true).unwrap_or(Value::False))
+}
+
+/// `(peek-char [port])` Like `read-char`, but the character is not consumed.
+pub fn peek_char(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let port = input_port(vm, "peek-char", args.first())?;
+    let wait = port.wait();
+    Ok(port.next_char(wait, false).unwrap_or(Value::False))
+}
+
+/// `(read-line [port])` Read a line, waiting at most the port's read timeout. Returns `#f` if the
+/// timeout expires.
+pub fn read_line(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let port = input_port(vm, "read-line", args.first())?;
+    let wait = port.wait();
+    Ok(port.read_line(wait).unwrap_or(Value::False))
+}
+
+/// `(char-ready? [port])` Whether a character can be read from the port without blocking.
+pub fn char_ready(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let port = input_port(vm, "char-ready?", args.first())?;
+    Ok(Value::Bool(port.next_char(Wait::NoHang, false).is_some()))
+}
+
+/// `(read-char-no-hang [port])` Read a character if one is available, otherwise return `#f`.
+pub fn read_char_no_hang(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let port = input_port(vm, "read-char-no-hang", args.first())?;
+    Ok(port.next_char(Wait::NoHang, true).unwrap_or(Value::False))
+}
+
+/// `(read-line-no-hang [port])` Read a line if a complete one is available, otherwise return `#f`.
+pub fn read_line_no_hang(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let port = input_port(vm, "read-line-no-hang", args.first())?;
+    Ok(port.read_line(Wait::NoHang).unwrap_or(Value::False))
+}
+
+/// `(set-port-read-timeout! port seconds)` Limit how long reads from `port` wait for input, `#f`
+/// waits forever.
+pub fn set_port_read_timeout(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let timeout = if args[1].is_false() {
+        None
+    } else if args[1].is_integer() && args[1].to_integer() >= 0 {
+        Some(Duration::from_secs(args[1].to_integer() as u64))
+    } else if args[1].is_float() && args[1].to_float() >= 0.0 {
+        Some(Duration::from_secs_f64(args[1].to_float()))
+    } else {
+        return Err(format!("set-port-read-timeout!: {} is not a number of seconds", args[1]));
+    };
+    input_port(vm, "set-port-read-timeout!", args.first())?.timeout = timeout;
+    Ok(Value::Void)
+}
+
+/// `(write-char char [port])`
+pub fn write_char(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !args[0].is_char() {
+        return Err(format!("write-char: {} is not a character", args[0]));
+    }
+    write_bytes(vm, "write-char", args.get(1), args[0].to_char().to_string().as_bytes())
+}
+
+/// `(write-string string [port])`
+pub fn write_string(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let s = string_arg("write-string", args[0])?;
+    write_bytes(vm, "write-string", args.get(1), s.as_bytes())
+}
+
+/// `(display obj [port])`
+pub fn display(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    write_bytes(vm, "display", args.get(1), display_text(args[0]).as_bytes())
+}
+
+/// `(write obj [port])` Write `obj` as the reader would read it, with strings and characters
+/// quoted.
+pub fn write(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    write_bytes(vm, "write", args.get(1), format!("{}", args[0]).as_bytes())
+}
+
+/// `(newline [port])`
+pub fn newline(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    write_bytes(vm, "newline", args.first(), b"\n")
+}
+
+/// `(flush-output-port [port])`
+pub fn flush_output_port(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    output_port(vm, "flush-output-port", args.first())?.flush()
+        .map_err(|e| format!("flush-output-port: {}", e))?;
+    Ok(Value::Void)
+}
+
+/// `(read-u8 [port])` Read a byte, waiting at most the port's read timeout. Returns `#f` if the
+/// timeout expires.
+pub fn read_u8(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let port = input_port(vm, "read-u8", args.first())?;
+    let wait = port.wait();
+    Ok(port.next_bytes(1, wait, true).map(byte).unwrap_or(Value::False))
+}
+
+/// `(peek-u8 [port])` Like `read-u8`, but the byte is not consumed.
+pub fn peek_u8(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let port = input_port(vm, "peek-u8", args.first())?;
+    let wait = port.wait();
+    Ok(port.next_bytes(1, wait, false).map(byte).unwrap_or(Value::False))
+}
+
+/// `(u8-ready? [port])` Whether a byte can be read from the port without blocking.
+pub fn u8_ready(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let port = input_port(vm, "u8-ready?", args.first())?;
+    Ok(Value::Bool(port.next_bytes(1, Wait::NoHang, false).is_some()))
+}
+
+fn byte(b: Vec<u8>) -> Value {
+    b.first().map(|&b| Value::Integer(b as i32)).unwrap_or(Value::Eof)
+}
+
+/// `(read-bytevector k [port])` Read up to `k` bytes, fewer only at the end of the port.
+pub fn read_bytevector(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !args[0].is_integer() || args[0].to_integer() < 0 {
+        return Err(format!("read-bytevector: {} is not a length", args[0]));
+    }
+    let port = input_port(vm, "read-bytevector", args.get(1))?;
+    let wait = port.wait();
+    Ok(match port.next_bytes(args[0].to_integer() as usize, wait, true) {
+        Some(ref b) if b.is_empty() && args[0].to_integer() > 0 => Value::Eof,
+        Some(b) => Value::Bytevector(b),
+        None => Value::False,
+    })
+}
+
+/// `(write-u8 byte [port])`
+pub fn write_u8(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let b = Numeric::U8.encode("write-u8", args[0], Endianness::Big)?;
+    write_bytes(vm, "write-u8", args.get(1), &b)
+}
+
+/// `(write-bytevector bytevector [port])`
+pub fn write_bytevector(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let b = bytevector_arg("write-bytevector", args[0])?;
+    write_bytes(vm, "write-bytevector", args.get(1), &b)
+}
+
+/// `(read-* port [endianness])`
+fn read_numeric(vm: &mut VM, name: &str, n: Numeric, args: &[Value]) -> Result<Value, String> {
+    let e = endianness(name, args.get(1))?;
+    let port = input_port(vm, name, args.first())?;
+    let wait = port.wait();
+    match port.next_bytes(n.size(), wait, true) {
+        Some(ref b) if b.is_empty() => Ok(Value::Eof),
+        Some(ref b) if b.len() < n.size() => Err(format!("{}: unexpected end of port", name)),
+        Some(b) => Ok(n.decode(&b, e)),
+        None => Ok(Value::False),
+    }
+}
+
+/// `(write-* n port [endianness])`
+fn write_numeric(vm: &mut VM, name: &str, n: Numeric, args: &[Value]) -> Result<Value, String> {
+    let b = n.encode(name, args[0], endianness(name, args.get(2))?)?;
+    write_bytes(vm, name, args.get(1), &b)
+}
+
+macro_rules! binary_io {
+    ($($numeric:ident: $read:ident $read_name:expr, $write:ident $write_name:expr;)*) => {
+        $(
+            #[doc = concat!("`(", $read_name, " port [endianness])` Returns `#f` if the read times out.")]
+            pub fn $read(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+                read_numeric(vm, $read_name, Numeric::$numeric, args)
+            }
+
+            #[doc = concat!("`(", $write_name, " n port [endianness])`")]
+            pub fn $write(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+                write_numeric(vm, $write_name, Numeric::$numeric, args)
+            }
+        )*
+    };
+}
+
+binary_io! {
+    S8: read_s8 "read-s8", write_s8 "write-s8";
+    U16: read_u16 "read-u16", write_u16 "write-u16";
+    S16: read_s16 "read-s16", write_s16 "write-s16";
+    U32: read_u32 "read-u32", write_u32 "write-u32";
+    S32: read_s32 "read-s32", write_s32 "write-s32";
+    F32: read_f32 "read-f32", write_f32 "write-f32";
+    F64: read_f64 "read-f64", write_f64 "write-f64";
+}
@@ -0,0 +1,80 @@
+//! A global environment built once and shared by many machines.
+//!
+//! Building the initial environment assembles every primitive, which is wasteful when a host runs
+//! many short scripts. A `Prelude` takes a finished environment and makes everything reachable
+//! from it permanent: those objects are removed from the heap list, so they are never swept and
+//! marking stops at them. Each instance then only needs its own copy of the bindings, so
+//! definitions stay private to the instance while the procedures and data they refer to are
+//! shared.
+//!
+//! Permanent objects are never freed, and must be treated as read-only. Mutating one, e.g. with
+//! `set-car!`, is visible to every instance, and anything it is made to refer to will not be kept
+//! alive by it.
+//!
+//! Machines still share a single heap, so instances must not run concurrently.
+
+use gc::{get_head, set_head};
+use value::VType;
+use {Environment, Strictness, Value, VM};
+
+use string_interner::Symbol;
+
+use std::collections::HashMap;
+
+#[derive(Clone)]
+pub struct Prelude {
+    bindings: HashMap<Symbol, Value>,
+    strictness: Strictness,
+}
+
+impl Prelude {
+    /// Freeze the bindings of the global environment `env`. Later definitions in `env` are not
+    /// part of the prelude.
+    pub fn new(env: Environment) -> Self {
+        make_permanent(&env);
+        Prelude {
+            bindings: env.local_bindings().into_iter().collect(),
+            strictness: env.strictness(),
+        }
+    }
+
+    /// A new global environment with the bindings of the prelude.
+    pub fn instance(&self) -> Environment {
+        let env = Environment::from_hashmap(self.bindings.clone());
+        env.set_strictness(self.strictness);
+        env
+    }
+
+    /// A new machine running in a new instance of the prelude.
+    pub fn vm(&self) -> VM {
+        let mut vm = VM::new();
+        vm.assign_environment(self.instance());
+        vm
+    }
+}
+
+/// Remove every object reachable from `env` from the heap list. They are left marked so that
+/// marking doesn't traverse them again.
+fn make_permanent(env: &Environment) {
+    env.mark();
+    let mut current = get_head();
+    let mut previous = None;
+    let mut new_root = 0;
+    while current != 0 {
+        let gc = Value::get_gc(current);
+        if gc & 1 == 1 {
+            Value::set_gc(current, 1);
+        } else {
+            match previous {
+                Some(previous) => Value::set_gc(previous, current),
+                None => new_root = current,
+            }
+            previous = Some(current);
+        }
+        current = gc & !1;
+    }
+    if let Some(previous) = previous {
+        Value::set_gc(previous, 0);
+    }
+    set_head(new_root, VType::from(new_root >> 56));
+}
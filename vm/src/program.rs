@@ -0,0 +1,121 @@
+//! The arguments of a program run from the command line.
+//!
+//! A script run by `minerva script.ss arg ...` is evaluated, and then its `main` procedure, if it
+//! defines one, is called with the command line as a list of strings: the path of the script
+//! followed by its arguments. `parse-args` reads options from them in the usual style:
+//!
+//! - `--name` sets a flag, and `--name value` or `--name=value` gives an option a value. An option
+//!   whose name is one character is written `-n` instead.
+//! - `--` ends the options, so that the arguments after it are never taken for options.
+
+use {Value, VM};
+
+use string_interner::get_value;
+
+use std::collections::HashMap;
+
+/// An option of a `parse-args` spec.
+struct Spec {
+    name: Value,
+    // The value of the option if it isn't given, for options which take a value
+    default: Option<Value>,
+}
+
+fn list(values: Vec<Value>) -> Value {
+    values.into_iter().rev().fold(Value::Nil, |l, v| Value::Pair(v, l))
+}
+
+/// `(command-line)` The path of the running program followed by its arguments, as strings.
+pub fn command_line(vm: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(list(vm.command_line.iter().map(|arg| Value::String(arg.clone())).collect()))
+}
+
+fn specs(name: &str, spec: Value) -> Result<Vec<Spec>, String> {
+    let mut specs = Vec::new();
+    let mut l = spec;
+    while l.is_pair() {
+        let option = l.car();
+        if option.is_symbol() {
+            specs.push(Spec { name: option, default: None });
+        } else if option.is_pair() && option.car().is_symbol() && option.cdr().is_pair() &&
+                  option.cdr().cdr().is_nil() {
+            specs.push(Spec { name: option.car(), default: Some(option.cdr().car()) });
+        } else {
+            return Err(format!("{}: {} is not an option", name, option));
+        }
+        l = l.cdr();
+    }
+    if l.is_nil() {
+        Ok(specs)
+    } else {
+        Err(format!("{}: {} is not a list of options", name, spec))
+    }
+}
+
+fn strings(name: &str, args: Value) -> Result<Vec<String>, String> {
+    let mut strings = Vec::new();
+    let mut l = args;
+    while l.is_pair() && l.car().is_string() {
+        let s = l.car().to_string();
+        strings.push(s.str.clone());
+        Box::into_raw(s);
+        l = l.cdr();
+    }
+    if l.is_nil() {
+        Ok(strings)
+    } else {
+        Err(format!("{}: {} is not a list of strings", name, args))
+    }
+}
+
+/// `(parse-args spec [args])` Read the options in `args`, by default the arguments of the program.
+/// `spec` lists the options: a symbol is a flag, and `(name default)` is an option which takes a
+/// value. Returns a list of a hash table and the arguments which are not options. The table maps
+/// the name of each option to its value, `#t` or `#f` for a flag and a string or `default` for
+/// the others.
+pub fn parse_args(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "parse-args";
+    let specs = specs(name, args[0])?;
+    let mut input = match args.get(1) {
+        Some(&args) => strings(name, args)?,
+        None => vm.command_line.iter().skip(1).cloned().collect(),
+    }.into_iter();
+
+    let mut options: HashMap<Value, Value> = specs.iter()
+        .map(|spec| (spec.name, spec.default.unwrap_or(Value::Bool(false))))
+        .collect();
+    let mut rest = Vec::new();
+    while let Some(arg) = input.next() {
+        let (option, value) = if arg == "--" {
+            rest.extend(input.by_ref().map(Value::String));
+            break;
+        } else if let Some(option) = arg.strip_prefix("--") {
+            match option.split_once('=') {
+                Some((option, value)) => (option.to_string(), Some(value.to_string())),
+                None => (option.to_string(), None),
+            }
+        } else if arg.starts_with('-') && arg.chars().count() == 2 {
+            (arg[1..].to_string(), None)
+        } else {
+            rest.push(Value::String(arg));
+            continue;
+        };
+
+        // Long names are written with `--`, and one character names with `-`
+        let spec = specs.iter()
+            .find(|spec| get_value(spec.name.to_symbol()).as_deref() == Some(option.as_str()) &&
+                         (option.chars().count() == 1) == !arg.starts_with("--"))
+            .ok_or_else(|| format!("{}: unknown option {}", name, arg))?;
+        let value = match (spec.default, value) {
+            (None, None) => Value::Bool(true),
+            (None, Some(_)) => return Err(format!("{}: option --{} doesn't take a value", name, option)),
+            (Some(_), Some(value)) => Value::String(value),
+            (Some(_), None) => match input.next() {
+                Some(value) => Value::String(value),
+                None => return Err(format!("{}: option {} needs a value", name, arg)),
+            },
+        };
+        options.insert(spec.name, value);
+    }
+    Ok(list(vec![Value::HashMap(options), list(rest)]))
+}
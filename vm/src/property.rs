@@ -0,0 +1,73 @@
+//! Property lists, which annotate symbols with values under keys.
+//!
+//! `(put! 'swap 'doc "Exchange two values")` stores a property which `(get 'swap 'doc)` returns,
+//! so that macros, documentation tools and programs can attach metadata to an identifier without a
+//! table of their own. Keys are compared with `eq?`, so they are usually symbols. Properties
+//! belong to the machine and last as long as it does.
+
+use {Value, VM};
+
+use string_interner::Symbol;
+
+fn symbol_arg(name: &str, v: Value) -> Result<Symbol, String> {
+    if v.is_symbol() {
+        Ok(v.to_symbol())
+    } else {
+        Err(format!("{}: {} is not a symbol", name, v))
+    }
+}
+
+impl VM {
+    /// The property of `symbol` stored under `key`, if it has one.
+    pub fn property(&self, symbol: Symbol, key: Value) -> Option<Value> {
+        self.properties.get(&symbol)?.iter().find(|&&(k, _)| k == key).map(|&(_, v)| v)
+    }
+
+    /// Store `value` as the property of `symbol` under `key`, replacing any it had.
+    pub fn put_property(&mut self, symbol: Symbol, key: Value, value: Value) {
+        let plist = self.properties.entry(symbol).or_default();
+        match plist.iter_mut().find(|(k, _)| *k == key) {
+            Some(property) => property.1 = value,
+            None => plist.push((key, value)),
+        }
+    }
+
+    /// Remove the property of `symbol` under `key`, returning it.
+    pub fn remove_property(&mut self, symbol: Symbol, key: Value) -> Option<Value> {
+        let plist = self.properties.get_mut(&symbol)?;
+        let i = plist.iter().position(|&(k, _)| k == key)?;
+        let (_, v) = plist.remove(i);
+        if plist.is_empty() {
+            self.properties.remove(&symbol);
+        }
+        Some(v)
+    }
+}
+
+/// `(put! symbol key value)`
+pub fn put(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let symbol = symbol_arg("put!", args[0])?;
+    vm.put_property(symbol, args[1], args[2]);
+    Ok(Value::Void)
+}
+
+/// `(get symbol key [default])` The property of `symbol` under `key`, or `default`, `#f` if not
+/// given, if it has none.
+pub fn get(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let symbol = symbol_arg("get", args[0])?;
+    Ok(vm.property(symbol, args[1]).unwrap_or(args.get(2).copied().unwrap_or(Value::False)))
+}
+
+/// `(remprop! symbol key)` Remove the property of `symbol` under `key`. Returns whether it had one.
+pub fn remprop(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let symbol = symbol_arg("remprop!", args[0])?;
+    Ok(Value::Bool(vm.remove_property(symbol, args[1]).is_some()))
+}
+
+/// `(symbol-plist symbol)` The properties of `symbol` as a list of alternating keys and values, in
+/// the order they were first stored.
+pub fn symbol_plist(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let symbol = symbol_arg("symbol-plist", args[0])?;
+    let plist = vm.properties.get(&symbol).map_or(&[][..], |p| &p[..]);
+    Ok(plist.iter().rev().fold(Value::Nil, |l, &(k, v)| Value::Pair(k, Value::Pair(v, l))))
+}
@@ -0,0 +1,31 @@
+//! Randomness from the operating system's generator.
+
+use {Value, VM};
+
+fn fill(name: &str, bytes: &mut [u8]) -> Result<(), String> {
+    getrandom::getrandom(bytes).map_err(|e| format!("{}: {}", name, e))
+}
+
+/// `(random-bytes n)` Return a bytevector of `n` random bytes.
+pub fn random_bytes(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !args[0].is_integer() || args[0].to_integer() < 0 {
+        return Err(format!("random-bytes: {} is not a length", args[0]));
+    }
+    vm.check_allocation("random-bytes", args[0].to_integer() as usize)?;
+    let mut bytes = vec![0; args[0].to_integer() as usize];
+    fill("random-bytes", &mut bytes)?;
+    Ok(Value::Bytevector(bytes))
+}
+
+/// `(uuid)` Return a random (version 4) UUID as a string.
+pub fn uuid(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    let mut b = [0; 16];
+    fill("uuid", &mut b)?;
+    // The version, 4, and the RFC 4122 variant
+    b[6] = (b[6] & 0x0F) | 0x40;
+    b[8] = (b[8] & 0x3F) | 0x80;
+
+    let hex: Vec<String> = b.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(Value::String(format!("{}-{}-{}-{}-{}", hex[..4].concat(), hex[4..6].concat(),
+                             hex[6..8].concat(), hex[8..10].concat(), hex[10..].concat())))
+}
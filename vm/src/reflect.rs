@@ -0,0 +1,160 @@
+//! First-class environments, and the call stack as a list.
+//!
+//! An environment value is an index into the environment table of the machine, which keeps every
+//! environment it has handed out alive. Procedure calls create a new frame each time, so
+//! `current-environment` should not be called in a loop.
+//!
+//! The strictness of a global environment controls whether redefining one of its bindings is
+//! allowed, gives a warning, or is an error, and whether `set!` of an unbound variable is an error.
+//!
+//! A frame of the call stack is a list of the name of its procedure and the procedure. Source
+//! spans are not kept by the compiler, so there is nothing to say where in the procedure a frame
+//! is. A tail call replaces the frame of its caller, which does not appear.
+
+use {Environment, Strictness, Value, VM};
+
+use string_interner::{get_symbol, get_value, Symbol};
+
+fn list(values: Vec<Value>) -> Value {
+    values.into_iter().rev().fold(Value::Nil, |l, v| Value::Pair(v, l))
+}
+
+/// The value for `env`, adding it to the environment table if it isn't already there.
+fn environment_value(vm: &mut VM, env: Environment) -> Value {
+    let i = match vm.environments.iter().position(|e| e.ptr_eq(&env)) {
+        Some(i) => i,
+        None => {
+            vm.environments.push(env);
+            vm.environments.len() - 1
+        }
+    };
+    Value::Environment(i as u32)
+}
+
+fn environment_arg(vm: &VM, name: &str, v: Value) -> Result<Environment, String> {
+    if v.is_environment() {
+        if let Some(env) = vm.environments.get(v.to_environment()) {
+            return Ok(env.clone());
+        }
+    }
+    Err(format!("{}: {} is not an environment", name, v))
+}
+
+fn symbol_arg(name: &str, v: Value) -> Result<Symbol, String> {
+    if v.is_symbol() {
+        Ok(v.to_symbol())
+    } else {
+        Err(format!("{}: {} is not a symbol", name, v))
+    }
+}
+
+/// `(current-environment)` The environment of the caller.
+pub fn current_environment(vm: &mut VM, _: &[Value]) -> Result<Value, String> {
+    let env = vm.environment.clone();
+    Ok(environment_value(vm, env))
+}
+
+/// `(interaction-environment)` The global environment.
+pub fn interaction_environment(vm: &mut VM, _: &[Value]) -> Result<Value, String> {
+    let mut env = vm.environment.clone();
+    while let Some(parent) = env.parent() {
+        env = parent;
+    }
+    Ok(environment_value(vm, env))
+}
+
+/// `(environment? obj)`
+pub fn is_environment(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(args[0].is_environment()))
+}
+
+/// `(environment-bound-names env)` The names bound in the frame of `env`, not including its
+/// parents.
+pub fn bound_names(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let env = environment_arg(vm, "environment-bound-names", args[0])?;
+    let mut bindings = env.local_bindings();
+    bindings.sort_by_key(|&(s, _)| get_value(s));
+    Ok(list(bindings.into_iter().map(|(s, _)| Value::Symbol(s)).collect()))
+}
+
+/// `(environment-bindings env)` The bindings of the frame of `env` as a list of `(name value)`
+/// lists.
+pub fn bindings(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let env = environment_arg(vm, "environment-bindings", args[0])?;
+    let mut bindings = env.local_bindings();
+    bindings.sort_by_key(|&(s, _)| get_value(s));
+    Ok(list(bindings.into_iter().map(|(s, v)| list(vec![Value::Symbol(s), v])).collect()))
+}
+
+/// `(environment-parent env)` The enclosing environment of `env`, or `#f` for the global
+/// environment.
+pub fn parent(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let env = environment_arg(vm, "environment-parent", args[0])?;
+    match env.parent() {
+        Some(parent) => Ok(environment_value(vm, parent)),
+        None => Ok(Value::False),
+    }
+}
+
+/// `(environment-bound? env symbol)` Whether `symbol` is bound in `env` or its parents.
+pub fn is_bound(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let env = environment_arg(vm, "environment-bound?", args[0])?;
+    let s = symbol_arg("environment-bound?", args[1])?;
+    Ok(Value::Bool(env.lookup_variable_value(s).is_some()))
+}
+
+/// `(environment-lookup env symbol)` The value of `symbol` in `env`.
+pub fn lookup(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let env = environment_arg(vm, "environment-lookup", args[0])?;
+    let s = symbol_arg("environment-lookup", args[1])?;
+    env.lookup_variable_value(s)
+        .ok_or_else(|| format!("environment-lookup: {} is not bound", args[1]))
+}
+
+/// `(environment-strictness env)` The strictness of the global environment of `env`, one of
+/// `permissive`, `warn`, or `strict`.
+pub fn strictness(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let env = environment_arg(vm, "environment-strictness", args[0])?;
+    let name = match env.strictness() {
+        Strictness::Permissive => "permissive",
+        Strictness::Warn => "warn",
+        Strictness::Strict => "strict",
+    };
+    Ok(Value::Symbol(get_symbol(name.to_string())))
+}
+
+/// `(set-environment-strictness! env strictness)` Set the strictness of the global environment of
+/// `env`.
+pub fn set_strictness(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let env = environment_arg(vm, "set-environment-strictness!", args[0])?;
+    let s = symbol_arg("set-environment-strictness!", args[1])?;
+    let strictness = match get_value(s).as_deref() {
+        Some("permissive") => Strictness::Permissive,
+        Some("warn") => Strictness::Warn,
+        Some("strict") => Strictness::Strict,
+        _ => return Err(format!("set-environment-strictness!: {} is not a strictness, expected permissive, warn, or strict",
+                                args[1])),
+    };
+    env.set_strictness(strictness);
+    Ok(Value::Void)
+}
+
+/// The most frames `current-stack` returns when no limit is given.
+const STACK_LIMIT: usize = 64;
+
+/// `(current-stack [limit])` The frames of the call stack, innermost first, at most `limit` of
+/// them. Each frame is a list of the name the procedure is bound to, or #f, and the procedure.
+pub fn current_stack(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let limit = match args.first() {
+        None => STACK_LIMIT,
+        Some(v) if v.is_integer() && v.to_integer() >= 0 => v.to_integer() as usize,
+        Some(v) => return Err(format!("current-stack: {} is not a valid limit", v)),
+    };
+    let frames = vm.stack_procedures().take(limit).collect::<Vec<_>>().into_iter()
+        .map(|f| {
+            let name = vm.procedure_name(f).map_or(Value::False, Value::Symbol);
+            list(vec![name, f])
+        })
+        .collect();
+    Ok(list(frames))
+}
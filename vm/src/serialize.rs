@@ -0,0 +1,458 @@
+//! Compiled code written to and read back from bytes, the `.mvc` files made by `minerva compile`.
+//!
+//! A file is the magic bytes `MVC\0`, a little endian `u16` format version and a `u32` bitmap of
+//! the `Features` it uses, followed by the code of each top level form in order. The code of a
+//! form is its operations, each a `u32`, and its constants. A constant starts with a byte of its
+//! `VType`, followed by its contents: the name of a symbol, the bytes of a string, or the
+//! operations and constants of a procedure. A list is written as the cars of its pairs followed by
+//! its final cdr.
+//!
+//! Nothing is written as the machine represents it: numbers are fixed width and little endian, and
+//! values are written by type and contents rather than as their NaN-boxed words, so a file doesn't
+//! depend on the pointer width or byte order of the machine which compiled it, and one compiled on
+//! a 64-bit machine runs on a 32-bit or wasm target.
+//!
+//! Operations are stored by number, so a change to the numbering needs a new format version. An
+//! operation added later gets a feature bit instead, so that only the files which use it are
+//! rejected by readers which don't know it, and older files still load in newer readers.
+//!
+//! Symbols are interned again by name when read, so tables of jump targets keyed by symbols, which
+//! depend on the numbers given to them, are rebuilt. Code is checked before it is returned: every
+//! operation must be valid, and refer to registers, constants, and targets which exist.
+//! Environments, ports, native procedures, and other values which belong to a running machine
+//! can't be written.
+
+use asm::{perfect_hash, symbol_hash};
+use value::VType;
+use {Environment, Instruction, Operation, Register, Value};
+
+use num_bigint::BigInt;
+use string_interner::{get_symbol, get_value, Symbol};
+
+use std::fmt;
+use std::ops::BitOr;
+
+/// The bytes every compiled file starts with.
+pub const MAGIC: [u8; 4] = *b"MVC\0";
+
+/// The version of the format written. Files of version 1, whose header has no features, are also
+/// read, and files of any other version are rejected.
+pub const FORMAT_VERSION: u16 = 2;
+
+// The oldest version which can be read
+const OLDEST_VERSION: u16 = 1;
+
+/// The operations a compiled file uses beyond the original set, as a bitmap.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Features(pub u32);
+
+impl Features {
+    pub const NONE: Features = Features(0);
+    /// `JumpTable`, `BinarySearch` and `PerfectHash`.
+    pub const SWITCHES: Features = Features(1);
+    /// `VectorRef`.
+    pub const VECTOR_REF: Features = Features(1 << 1);
+    /// `CallPrimitive`.
+    pub const PRIMITIVE_CALLS: Features = Features(1 << 2);
+    /// `LookupCached`.
+    pub const INLINE_CACHES: Features = Features(1 << 3);
+    /// Every feature this version can read.
+    pub const ALL: Features = Features(0b1111);
+
+    /// The features used by `code` and the procedures among its constants `consts`.
+    pub fn of(code: &[Operation], consts: &[Value]) -> Features {
+        let mut features = code.iter().fold(Features::NONE, |f, &op| f | Features::of_operation(op));
+        for &c in consts.iter().filter(|c| c.is_lambda()) {
+            let lambda = c.to_lambda();
+            features = features | Features::of(&lambda.code, &lambda.consts);
+            Box::into_raw(lambda);
+        }
+        features
+    }
+
+    fn of_operation(op: Operation) -> Features {
+        // An operation which isn't one needs no feature, it is rejected when it is read
+        if op.0 & 255 > Instruction::LookupCached as u32 {
+            return Features::NONE;
+        }
+        match op.instruction() {
+            Instruction::JumpTable | Instruction::BinarySearch | Instruction::PerfectHash => Features::SWITCHES,
+            Instruction::VectorRef => Features::VECTOR_REF,
+            Instruction::CallPrimitive => Features::PRIMITIVE_CALLS,
+            Instruction::LookupCached => Features::INLINE_CACHES,
+            _ => Features::NONE,
+        }
+    }
+
+    /// Whether every feature of `other` is in `self`.
+    pub fn contains(self, other: Features) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Features {
+    type Output = Features;
+
+    fn bitor(self, other: Features) -> Features {
+        Features(self.0 | other.0)
+    }
+}
+
+/// Why compiled code couldn't be read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+    /// The bytes don't start with `MAGIC`.
+    NotCompiled,
+    /// The file was written in another version of the format.
+    Version(u16),
+    /// The file uses features, given as a bitmap, which this version can't read.
+    Features(u32),
+    /// The bytes end in the middle of a form.
+    Truncated,
+    /// A constant of an unknown type, or whose contents are invalid.
+    Constant(u8),
+    /// An operation which is invalid, or refers to something which doesn't exist.
+    Operation(u32),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::NotCompiled => write!(f, "not a compiled file"),
+            LoadError::Version(v) =>
+                write!(f, "compiled file has format version {}, expected {} to {}", v, OLDEST_VERSION, FORMAT_VERSION),
+            LoadError::Features(bits) => write!(f, "compiled file uses unsupported features {:#x}", bits),
+            LoadError::Truncated => write!(f, "compiled file is truncated"),
+            LoadError::Constant(t) => write!(f, "compiled file has an invalid constant of type {}", t),
+            LoadError::Operation(op) => write!(f, "compiled file has an invalid operation {:#010x}", op),
+        }
+    }
+}
+
+/// Append the header of a compiled file to `out`, for code which uses `features`, as `Features::of`
+/// finds them.
+pub fn write_header(out: &mut Vec<u8>, features: Features) {
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&features.0.to_le_bytes());
+}
+
+/// Append the code of a top level form to `out`, or return the first constant which can't be
+/// written.
+pub fn write_code(out: &mut Vec<u8>, code: &[Operation], consts: &[Value]) -> Result<(), Value> {
+    write_u32(out, code.len());
+    for op in code {
+        out.extend_from_slice(&op.0.to_le_bytes());
+    }
+    write_u32(out, consts.len());
+    for &c in consts {
+        write_value(out, c)?;
+    }
+    Ok(())
+}
+
+/// Append a top level form to `out` which defines `name` as `value`, or return the first value in
+/// it which can't be written and leave `out` as it was. A procedure is closed over the environment
+/// the form runs in, not the one it was made in.
+pub fn write_definition(out: &mut Vec<u8>, name: Symbol, value: Value) -> Result<(), Value> {
+    let load = if value.is_lambda() { Operation::MakeClosure(Register(0), 1) } else { Operation::LoadConst(Register(0), 1) };
+    let code = [Operation::LoadConst(Register(1), 0), load, Operation::Define(Register(1), Register(0))];
+    let len = out.len();
+    write_code(out, &code, &[Value::Symbol(name), value]).map_err(|v| {
+        out.truncate(len);
+        v
+    })
+}
+
+fn write_u32(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&(n as u32).to_le_bytes());
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn write_value(out: &mut Vec<u8>, mut v: Value) -> Result<(), Value> {
+    // Lists are written iteratively, they may be longer than the stack is deep
+    while v.is_pair() {
+        out.push(VType::Pair as u8);
+        write_value(out, v.car())?;
+        v = v.cdr();
+    }
+    out.push(v.to_type() as u8);
+    match v.to_type() {
+        VType::Void | VType::Nil | VType::Eof => (),
+        VType::Bool => out.push(v.is_true() as u8),
+        VType::Integer => out.extend_from_slice(&v.to_integer().to_le_bytes()),
+        VType::Float => out.extend_from_slice(&v.to_float().to_bits().to_le_bytes()),
+        VType::Char => out.extend_from_slice(&(v.to_char() as u32).to_le_bytes()),
+        VType::Symbol => write_bytes(out, get_value(v.to_symbol()).unwrap().as_bytes()),
+        VType::String => {
+            let s = v.to_string();
+            write_bytes(out, s.str.as_bytes());
+            Box::into_raw(s);
+        }
+        VType::Bytevector => {
+            let b = v.to_bytevector();
+            write_bytes(out, &b.bytes);
+            Box::into_raw(b);
+        }
+        VType::BigInt => {
+            let b = v.to_bigint();
+            write_bytes(out, &b.n.to_signed_bytes_le());
+            Box::into_raw(b);
+        }
+        VType::Vec => {
+            let vec = v.to_vec();
+            let items = vec.vec.clone();
+            Box::into_raw(vec);
+            write_u32(out, items.len());
+            for i in items {
+                write_value(out, i)?;
+            }
+        }
+        VType::Lambda => {
+            let lambda = v.to_lambda();
+            let (code, consts) = (lambda.code.clone(), lambda.consts.clone());
+            Box::into_raw(lambda);
+            write_code(out, &code, &consts)?;
+        }
+        _ => return Err(v),
+    }
+    Ok(())
+}
+
+/// Reads the code of each top level form from a compiled file, in order.
+///
+/// Each form is read only when it is reached. Its constants aren't rooted until the code is loaded
+/// into a machine, so a form should be run before the next is read.
+pub struct CodeReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    features: Features,
+}
+
+impl<'a> CodeReader<'a> {
+    /// Check the header of `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, LoadError> {
+        if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+            return Err(LoadError::NotCompiled);
+        }
+        let mut reader = CodeReader { bytes, pos: MAGIC.len(), features: Features::ALL };
+        let version = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+        if !(OLDEST_VERSION..=FORMAT_VERSION).contains(&version) {
+            return Err(LoadError::Version(version));
+        }
+        if version > 1 {
+            reader.features = Features(reader.u32()?);
+            if !Features::ALL.contains(reader.features) {
+                return Err(LoadError::Features(reader.features.0 & !Features::ALL.0));
+            }
+        }
+        Ok(reader)
+    }
+
+    /// The features the header says the file uses.
+    pub fn features(&self) -> Features {
+        self.features
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], LoadError> {
+        if self.bytes.len() - self.pos < n {
+            return Err(LoadError::Truncated);
+        }
+        self.pos += n;
+        Ok(&self.bytes[self.pos - n..self.pos])
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, LoadError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, LoadError> {
+        let b = self.take(8)?;
+        Ok(u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], LoadError> {
+        let n = self.u32()? as usize;
+        self.take(n)
+    }
+
+    fn code(&mut self) -> Result<(Vec<Operation>, Vec<Value>), LoadError> {
+        let n = self.u32()? as usize;
+        // Not reserved up front, the length may be garbage
+        let mut code = Vec::new();
+        for _ in 0..n {
+            code.push(Operation(self.u32()?));
+        }
+        let n = self.u32()? as usize;
+        let mut consts = Vec::new();
+        for _ in 0..n {
+            consts.push(self.value()?);
+        }
+        for &op in &code {
+            check_operation(op, &code, &consts)?;
+            // An operation the header doesn't mention may mean something else to the writer
+            if !self.features.contains(Features::of_operation(op)) {
+                return Err(LoadError::Operation(op.0));
+            }
+        }
+        for &op in &code {
+            rebuild_table(op, &consts);
+        }
+        Ok((code, consts))
+    }
+
+    fn value(&mut self) -> Result<Value, LoadError> {
+        let mut cars = vec![];
+        let mut t = self.u8()?;
+        while t == VType::Pair as u8 {
+            cars.push(self.value()?);
+            t = self.u8()?;
+        }
+        let mut v = match t {
+            t if t == VType::Void as u8 => Value::Void,
+            t if t == VType::Nil as u8 => Value::Nil,
+            t if t == VType::Eof as u8 => Value::Eof,
+            t if t == VType::Bool as u8 => Value::Bool(self.u8()? != 0),
+            t if t == VType::Integer as u8 => Value::Integer(self.u32()? as i32),
+            t if t == VType::Float as u8 => Value::Float(f64::from_bits(self.u64()?)),
+            t if t == VType::Char as u8 => match char::from_u32(self.u32()?) {
+                Some(c) => Value::Char(c),
+                None => return Err(LoadError::Constant(t)),
+            },
+            t if t == VType::Symbol as u8 => Value::Symbol(get_symbol(self.string(t)?)),
+            t if t == VType::String as u8 => Value::String(self.string(t)?),
+            t if t == VType::Bytevector as u8 => Value::Bytevector(self.bytes()?.to_vec()),
+            t if t == VType::BigInt as u8 => Value::BigInt(BigInt::from_signed_bytes_le(self.bytes()?)),
+            t if t == VType::Vec as u8 => {
+                let n = self.u32()? as usize;
+                let mut items = Vec::new();
+                for _ in 0..n {
+                    items.push(self.value()?);
+                }
+                Value::Vec(items)
+            }
+            t if t == VType::Lambda as u8 => {
+                let (code, consts) = self.code()?;
+                Value::Lambda(Environment::new(), code, consts)
+            }
+            t => return Err(LoadError::Constant(t)),
+        };
+        for car in cars.into_iter().rev() {
+            v = Value::Pair(car, v);
+        }
+        Ok(v)
+    }
+
+    fn string(&mut self, t: u8) -> Result<String, LoadError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| LoadError::Constant(t))
+    }
+}
+
+impl<'a> Iterator for CodeReader<'a> {
+    type Item = Result<(Vec<Operation>, Vec<Value>), LoadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos == self.bytes.len() {
+            return None;
+        }
+        let code = self.code();
+        if code.is_err() {
+            // Nothing after an error can be trusted
+            self.pos = self.bytes.len();
+        }
+        Some(code)
+    }
+}
+
+/// Check that `op`, an operation of `code`, can be run.
+fn check_operation(op: Operation, code: &[Operation], consts: &[Value]) -> Result<(), LoadError> {
+    use Instruction::*;
+
+    let invalid = Err(LoadError::Operation(op.0));
+    if op.0 & 255 > LookupCached as u32 {
+        return invalid;
+    }
+    let register = |shift: u32| (op.0 >> shift) & 255 <= Register::XZR.0 as u32;
+    let constant = |i: usize, is: fn(Value) -> bool| consts.get(i).is_some_and(|&c| is(c));
+    let target = |p: Option<usize>| p.is_none_or(|p| p <= code.len());
+    let valid = match op.instruction() {
+        SaveContinue | RestoreContinue | Return => op.0 >> 8 == 0,
+        LoadContinue => op.loadcontinue_label() <= code.len(),
+        Goto => target(op.goto_value()),
+        GotoIf => target(op.gotoif_value()),
+        GotoIfNot => target(op.gotoifnot_value()),
+        Save | Restore | Trace | Untrace => op.0 >> 16 == 0 && register(8),
+        ReadStack | Call | TailCall => register(8),
+        LoadConst => register(8) && op.loadconst_constant() < consts.len(),
+        MakeClosure => register(8) && constant(op.makeclosure_constant(), Value::is_lambda),
+        JumpTable => register(8) && constant(op.jumptable_table(), Value::is_vec),
+        BinarySearch => register(8) && constant(op.bsearch_table(), Value::is_vec),
+        PerfectHash => register(8) && constant(op.phash_table(), Value::is_vec),
+        CallPrimitive => {
+            register(8) && op.0 >> 16 <= LookupCached as u32
+                && matches!(op.callprimitive_instruction(), Car | Cdr | Cons | VectorRef)
+        }
+        Move | Car | Cdr | StringToSymbol | Set | SetCar | SetCdr | Define | Lookup => {
+            op.0 >> 24 == 0 && register(8) && register(16)
+        }
+        LookupCached => register(8) && register(16),
+        Add | Sub | Mul | Eq | LT | Cons | VectorRef => register(8) && register(16) && register(24),
+    };
+    if valid { Ok(()) } else { invalid }
+}
+
+/// Rebuild the table of `op` if it is keyed by symbols, which may have been numbered differently
+/// when it was written.
+fn rebuild_table(op: Operation, consts: &[Value]) {
+    match op.instruction() {
+        Instruction::BinarySearch => {
+            // `[default, key, target, key, target...]` sorted by key
+            let mut table = consts[op.bsearch_table()].to_vec();
+            if !table.vec.len().is_multiple_of(2) {
+                let mut entries: Vec<_> = table.vec[1..].chunks(2).map(|e| (e[0], e[1])).collect();
+                entries.sort_by_key(|(k, _)| **k);
+                table.vec.truncate(1);
+                for (k, target) in entries {
+                    table.vec.push(k);
+                    table.vec.push(target);
+                }
+            }
+            Box::into_raw(table);
+        }
+        Instruction::PerfectHash => {
+            // `[default, multiplier, key, target, key, target...]` with `Void` in empty slots
+            let mut table = consts[op.phash_table()].to_vec();
+            if table.vec.len() >= 2 && table.vec.len().is_multiple_of(2) {
+                let default = table.vec[0];
+                let entries: Vec<(Symbol, Value)> = table.vec[2..].chunks(2)
+                    .filter(|e| e[0].is_symbol())
+                    .map(|e| (e[0].to_symbol(), e[1]))
+                    .collect();
+                let (multiplier, bits) = perfect_hash(&entries);
+                let mut slots = vec![Value::Void; 2 + (2 << bits)];
+                slots[0] = default;
+                slots[1] = Value::Integer(multiplier as i32);
+                for i in 0..1 << bits {
+                    slots[3 + 2 * i] = default;
+                }
+                for (s, target) in entries {
+                    let i = symbol_hash(*s, multiplier, bits);
+                    slots[2 + 2 * i] = Value::Symbol(s);
+                    slots[3 + 2 * i] = target;
+                }
+                table.vec = slots;
+            }
+            Box::into_raw(table);
+        }
+        _ => (),
+    }
+}
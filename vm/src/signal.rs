@@ -0,0 +1,137 @@
+//! Signals sent to the process, raised in the machine as conditions.
+//!
+//! The handler installed for a signal only records that it arrived. The machine checks for one
+//! before each instruction, a point at which its state is consistent, and signals an `interrupt`
+//! condition there. Like any other condition it suspends the computation, which its `Retry`
+//! restart continues from where it was interrupted.
+//!
+//! `(with-signal-handler 'SIGINT thunk handler)` lets a program handle a signal itself, e.g. to
+//! clean up before exiting: if the signal arrives while `thunk` runs, `thunk` is abandoned and the
+//! result is that of `(handler 'SIGINT)`. Handlers are only installed for `SIGINT` and `SIGTERM`,
+//! by `catch_signal` or a `with-signal-handler` for them, any other signal keeps its default
+//! action.
+
+use {Value, VM};
+
+use string_interner::get_symbol;
+
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// A signal which can be raised in the machine.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Signal {
+    /// `SIGINT`, sent by Ctrl-C.
+    Interrupt = 2,
+    /// `SIGTERM`, a request to terminate.
+    Terminate = 15,
+}
+
+impl Signal {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "SIGINT" => Some(Signal::Interrupt),
+            "SIGTERM" => Some(Signal::Terminate),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Signal::Interrupt => write!(f, "SIGINT"),
+            Signal::Terminate => write!(f, "SIGTERM"),
+        }
+    }
+}
+
+// The signals which have arrived and not yet been raised, a bit for each signal number
+static PENDING: AtomicU32 = AtomicU32::new(0);
+// The signals a handler has been installed for
+static INSTALLED: AtomicU32 = AtomicU32::new(0);
+
+#[cfg(unix)]
+mod os {
+    use std::os::raw::c_int;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+
+    extern "C" fn record(signum: c_int) {
+        super::PENDING.fetch_or(1 << signum, super::Ordering::SeqCst);
+    }
+
+    pub(super) fn install(signum: u32) {
+        unsafe {
+            signal(signum as c_int, record);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod os {
+    pub(super) fn install(_: u32) {}
+}
+
+/// Raise `signal` in the machine instead of taking its default action, from now on.
+pub fn catch_signal(signal: Signal) {
+    let bit = 1 << signal as u32;
+    if INSTALLED.fetch_or(bit, Ordering::SeqCst) & bit == 0 {
+        os::install(signal as u32);
+    }
+}
+
+/// Record that `signal` has arrived, as its handler does. The next machine to run raises it.
+pub fn deliver_signal(signal: Signal) {
+    PENDING.fetch_or(1 << signal as u32, Ordering::SeqCst);
+}
+
+/// Whether a signal has arrived which has not yet been raised.
+pub(crate) fn is_pending() -> bool {
+    PENDING.load(Ordering::Relaxed) != 0
+}
+
+/// Take a signal which has arrived, if any.
+pub(crate) fn take_pending() -> Option<Signal> {
+    if !is_pending() {
+        return None;
+    }
+    for signal in [Signal::Interrupt, Signal::Terminate] {
+        let bit = 1 << signal as u32;
+        if PENDING.fetch_and(!bit, Ordering::SeqCst) & bit != 0 {
+            return Some(signal);
+        }
+    }
+    None
+}
+
+/// `(with-signal-handler signal thunk handler)` Call `thunk`, or if `signal` arrives while it
+/// runs, abandon it and call `(handler signal)` instead.
+pub fn with_signal_handler(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let signal = if args[0].is_symbol() {
+        Signal::from_name(&format!("{}", args[0]))
+    } else {
+        None
+    };
+    let signal = signal.ok_or_else(|| format!("with-signal-handler: {} is not a signal, expected SIGINT or SIGTERM",
+                                              args[0]))?;
+    if !args[1].is_procedure() {
+        return Err(format!("with-signal-handler: {} is not a procedure", args[1]));
+    } else if !args[2].is_procedure() {
+        return Err(format!("with-signal-handler: {} is not a procedure", args[2]));
+    }
+
+    catch_signal(signal);
+    vm.signal_handlers.push(signal);
+    let result = vm.apply(args[1], &[]);
+    vm.signal_handlers.pop();
+    match result {
+        Err(_) if vm.caught_signal == Some(signal) => {
+            vm.caught_signal = None;
+            vm.apply(args[2], &[Value::Symbol(get_symbol(signal.to_string()))])
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,149 @@
+//! SQLite bindings, enabled by the `sqlite` feature.
+//!
+//! SQL values map to Scheme values as follows: `NULL` is `()`, integers are fixnums (or floats
+//! when out of range), reals are floats, text is a string, and blobs are bytevectors. Booleans are
+//! stored as 1 and 0.
+
+use {Value, VM};
+use string_interner::get_value;
+
+use rusqlite::types::{ToSqlOutput, Value as SqlValue, ValueRef};
+use rusqlite::{params_from_iter, Connection, ToSql};
+
+struct Param(SqlValue);
+
+impl ToSql for Param {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::Borrowed(ValueRef::from(&self.0)))
+    }
+}
+
+fn to_sql(name: &str, v: Value) -> Result<Param, String> {
+    let v = if v.is_nil() {
+        SqlValue::Null
+    } else if v.is_integer() {
+        SqlValue::Integer(v.to_integer() as i64)
+    } else if v.is_float() {
+        SqlValue::Real(v.to_float())
+    } else if v.is_true() {
+        SqlValue::Integer(1)
+    } else if v.is_false() {
+        SqlValue::Integer(0)
+    } else if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.clone();
+        Box::into_raw(s);
+        SqlValue::Text(r)
+    } else if v.is_symbol() {
+        SqlValue::Text(get_value(v.to_symbol()).unwrap())
+    } else if v.is_bytevector() {
+        let b = v.to_bytevector();
+        let r = b.bytes.clone();
+        Box::into_raw(b);
+        SqlValue::Blob(r)
+    } else {
+        return Err(format!("{}: {} can't be stored in a database", name, v));
+    };
+    Ok(Param(v))
+}
+
+fn from_sql(v: ValueRef) -> Value {
+    match v {
+        ValueRef::Null => Value::Nil,
+        ValueRef::Integer(i) if i >= i32::MIN as i64 && i <= i32::MAX as i64 => Value::Integer(i as i32),
+        ValueRef::Integer(i) => Value::Float(i as f64),
+        ValueRef::Real(f) => Value::Float(f),
+        ValueRef::Text(s) => Value::String(String::from_utf8_lossy(s).into_owned()),
+        ValueRef::Blob(b) => Value::Bytevector(b.to_vec()),
+    }
+}
+
+fn connection<'a>(vm: &'a mut VM, name: &str, db: Value) -> Result<&'a mut Connection, String> {
+    if db.is_database() {
+        if let Some(Some(c)) = vm.databases.get_mut(db.to_database()) {
+            return Ok(c);
+        }
+    }
+    Err(format!("{}: {} is not an open database", name, db))
+}
+
+fn params(name: &str, args: &[Value]) -> Result<Vec<Param>, String> {
+    args.iter().map(|&v| to_sql(name, v)).collect()
+}
+
+fn sql_arg(name: &str, v: Value) -> Result<String, String> {
+    if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.clone();
+        Box::into_raw(s);
+        Ok(r)
+    } else {
+        Err(format!("{}: {} is not a string", name, v))
+    }
+}
+
+/// `(sqlite-open path)` Open or create the database at `path`, `":memory:"` opens a new in-memory
+/// database.
+pub fn open(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let path = sql_arg("sqlite-open", args[0])?;
+    let c = Connection::open(&path).map_err(|e| format!("sqlite-open: {}: {}", path, e))?;
+    vm.databases.push(Some(c));
+    Ok(Value::Database(vm.databases.len() as u32 - 1))
+}
+
+/// `(sqlite-close db)`
+pub fn close(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    connection(vm, "sqlite-close", args[0])?;
+    vm.databases[args[0].to_database()] = None;
+    Ok(Value::Void)
+}
+
+/// `(sqlite-exec db sql param ...)` Execute statements which return no rows. Returns the number of
+/// rows changed. Without parameters `sql` may contain several statements.
+pub fn exec(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let sql = sql_arg("sqlite-exec", args[1])?;
+    let params = params("sqlite-exec", &args[2..])?;
+    let c = connection(vm, "sqlite-exec", args[0])?;
+    let result = if params.is_empty() {
+        c.execute_batch(&sql).map(|_| c.changes() as usize)
+    } else {
+        c.execute(&sql, params_from_iter(params.iter()))
+    };
+    result.map(|n| Value::Integer(n as i32)).map_err(|e| format!("sqlite-exec: {}", e))
+}
+
+/// Run a query, building each row from its column names and values.
+fn rows<F>(vm: &mut VM, name: &str, args: &[Value], row: F) -> Result<Value, String>
+    where F: Fn(&[String], Vec<Value>) -> Value
+{
+    let error = |e: rusqlite::Error| format!("{}: {}", name, e);
+    let sql = sql_arg(name, args[1])?;
+    let params = params(name, &args[2..])?;
+    let c = connection(vm, name, args[0])?;
+    let mut statement = c.prepare(&sql).map_err(error)?;
+    let columns: Vec<String> = statement.column_names().into_iter().map(String::from).collect();
+    let mut results = statement.query(params_from_iter(params.iter())).map_err(error)?;
+
+    let mut rows = Vec::new();
+    while let Some(r) = results.next().map_err(error)? {
+        let values = (0..columns.len()).map(|i| r.get_ref(i).map(from_sql)).collect::<Result<_, _>>()
+            .map_err(error)?;
+        rows.push(row(&columns, values));
+    }
+    Ok(rows.into_iter().rev().fold(Value::Nil, |l, r| Value::Pair(r, l)))
+}
+
+/// `(sqlite-query db sql param ...)` Return the rows of a query as a list of vectors.
+pub fn query(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    rows(vm, "sqlite-query", args, |_, values| Value::Vec(values))
+}
+
+/// `(sqlite-query-alist db sql param ...)` Return the rows of a query as a list of association
+/// lists from column names to values.
+pub fn query_alist(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    rows(vm, "sqlite-query-alist", args, |columns, values| {
+        columns.iter().zip(values).rev().fold(Value::Nil, |l, (c, v)| {
+            Value::Pair(Value::Pair(Value::Symbol(VM::intern_symbol(c.clone())), v), l)
+        })
+    })
+}
@@ -0,0 +1,94 @@
+//! Searching strings.
+//!
+//! `string-search` and `string-replace-all` find substrings with the Boyer-Moore-Horspool
+//! algorithm, which compares the last byte of the window first and, on a mismatch, skips ahead by
+//! as much as the pattern allows, so most of the text is never looked at for long patterns. The
+//! search is over UTF-8 bytes, which finds the same matches as a search over characters since no
+//! character's encoding starts inside another's. Indices are counted in characters.
+
+use {Value, VM};
+
+/// A pattern prepared for searching with Boyer-Moore-Horspool.
+struct Searcher<'a> {
+    pattern: &'a [u8],
+    // How far the window can move when its last byte is each byte value
+    shift: [usize; 256],
+}
+
+impl<'a> Searcher<'a> {
+    fn new(pattern: &'a [u8]) -> Self {
+        let mut shift = [pattern.len(); 256];
+        for (i, &b) in pattern.iter().enumerate().take(pattern.len().saturating_sub(1)) {
+            shift[b as usize] = pattern.len() - 1 - i;
+        }
+        Searcher { pattern, shift }
+    }
+
+    /// The byte index of the first match in `text` at or after `from`.
+    fn find(&self, text: &[u8], from: usize) -> Option<usize> {
+        let len = self.pattern.len();
+        if len == 0 {
+            return if from <= text.len() { Some(from) } else { None };
+        }
+        let mut pos = from;
+        while pos + len <= text.len() {
+            let last = text[pos + len - 1];
+            if last == self.pattern[len - 1] && text[pos..pos + len - 1] == self.pattern[..len - 1] {
+                return Some(pos);
+            }
+            pos += self.shift[last as usize];
+        }
+        None
+    }
+}
+
+fn string_arg(name: &str, v: Value) -> Result<String, String> {
+    if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.clone();
+        Box::into_raw(s);
+        Ok(r)
+    } else {
+        Err(format!("{}: {} is not a string", name, v))
+    }
+}
+
+/// `(string-search pattern string [start])` The index of the first occurrence of `pattern` in
+/// `string` at or after the index `start`, or `#f` if there is none.
+pub fn string_search(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let pattern = string_arg("string-search", args[0])?;
+    let text = string_arg("string-search", args[1])?;
+    let start = match args.get(2) {
+        None => 0,
+        Some(v) if v.is_integer() && v.to_integer() >= 0 && v.to_integer() as usize <= text.chars().count() =>
+            v.to_integer() as usize,
+        Some(v) => return Err(format!("string-search: {} is not a valid index", v)),
+    };
+    let from = text.char_indices().nth(start).map_or(text.len(), |(i, _)| i);
+    match Searcher::new(pattern.as_bytes()).find(text.as_bytes(), from) {
+        Some(i) => Ok(Value::Integer(text[..i].chars().count() as i32)),
+        None => Ok(Value::False),
+    }
+}
+
+/// `(string-replace-all string pattern replacement)` A newly allocated copy of `string` with each
+/// occurrence of `pattern` replaced by `replacement`, from left to right without overlapping.
+pub fn string_replace_all(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = string_arg("string-replace-all", args[0])?;
+    let pattern = string_arg("string-replace-all", args[1])?;
+    let replacement = string_arg("string-replace-all", args[2])?;
+    if pattern.is_empty() {
+        return Err("string-replace-all: the pattern is empty".to_string());
+    }
+
+    let searcher = Searcher::new(pattern.as_bytes());
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    while let Some(i) = searcher.find(text.as_bytes(), copied) {
+        out.push_str(&text[copied..i]);
+        out.push_str(&replacement);
+        copied = i + pattern.len();
+    }
+    out.push_str(&text[copied..]);
+    Ok(Value::String(out))
+}
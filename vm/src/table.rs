@@ -0,0 +1,81 @@
+//! Tables of objects which values refer to by index, such as ports and continuations. The slot of
+//! an object which has been removed is reused by the next one inserted, so a table only grows to
+//! the most objects it has held at once.
+
+#[derive(Debug)]
+pub(crate) struct Table<T> {
+    slots: Vec<Option<T>>,
+    // The empty slots, the most recently emptied last
+    free: Vec<usize>,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Table {
+            slots: vec![],
+            free: vec![],
+        }
+    }
+}
+
+impl<T> Table<T> {
+    /// A table holding `objects`, in order from index 0.
+    pub(crate) fn with(objects: Vec<T>) -> Self {
+        Table {
+            slots: objects.into_iter().map(Some).collect(),
+            free: vec![],
+        }
+    }
+
+    /// Add `object` to the table and return its index.
+    pub(crate) fn insert(&mut self, object: T) -> u32 {
+        match self.free.pop() {
+            Some(i) => {
+                self.slots[i] = Some(object);
+                i as u32
+            }
+            None => {
+                self.slots.push(Some(object));
+                self.slots.len() as u32 - 1
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, i: usize) -> Option<&T> {
+        self.slots.get(i).and_then(Option::as_ref)
+    }
+
+    pub(crate) fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        self.slots.get_mut(i).and_then(Option::as_mut)
+    }
+
+    /// Remove the object at `i`, freeing its slot.
+    pub(crate) fn remove(&mut self, i: usize) -> Option<T> {
+        let object = self.slots.get_mut(i).and_then(Option::take);
+        if object.is_some() {
+            self.free.push(i);
+        }
+        object
+    }
+
+    /// Remove the object at `i` without freeing its slot, so that the index is never reused.
+    pub(crate) fn take(&mut self, i: usize) -> Option<T> {
+        self.slots.get_mut(i).and_then(Option::take)
+    }
+
+    /// Remove every object for which `keep` returns `false`.
+    pub(crate) fn retain<F>(&mut self, mut keep: F)
+        where F: FnMut(usize, &T) -> bool
+    {
+        for i in 0..self.slots.len() {
+            if matches!(self.slots[i], Some(ref object) if !keep(i, object)) {
+                self.remove(i);
+            }
+        }
+    }
+
+    /// The number of slots, full or empty.
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+}
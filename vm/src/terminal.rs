@@ -0,0 +1,128 @@
+//! ANSI colors and styles for terminal output.
+//!
+//! A color is one of the symbols `black`, `red`, `green`, `yellow`, `blue`, `magenta`, `cyan` and
+//! `white`, one of them prefixed with `bright-`, or one of the styles `bold`, `dim`, `italic` and
+//! `underline`.
+//!
+//! Escape codes are only written when standard output is a terminal which supports them: one
+//! whose `TERM` isn't `dumb`, with no `NO_COLOR` variable set. Otherwise `colorize` and
+//! `with-color` leave their output as it is. `(set-terminal-colors! #t)` overrides this, e.g. for
+//! a `--color=always` option.
+
+use {Value, VM};
+use port::write_bytes;
+
+use string_interner::get_value;
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+const RESET: &str = "\x1b[0m";
+
+/// Whether standard output is a terminal which supports colors.
+pub fn terminal_colors() -> bool {
+    io::stdout().is_terminal()
+        && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && env::var("TERM").map_or(true, |t| t != "dumb")
+}
+
+/// The SGR parameter which selects `color`.
+fn color_arg(name: &str, color: Value) -> Result<u8, String> {
+    let invalid = || format!("{}: {} is not a color", name, color);
+    if !color.is_symbol() {
+        return Err(invalid());
+    }
+    let color = get_value(color.to_symbol()).unwrap();
+    let (base, color) = match color.strip_prefix("bright-") {
+        Some(color) => (90, color),
+        None => (30, color.as_str()),
+    };
+    let code = match color {
+        "black" => 0,
+        "red" => 1,
+        "green" => 2,
+        "yellow" => 3,
+        "blue" => 4,
+        "magenta" => 5,
+        "cyan" => 6,
+        "white" => 7,
+        "bold" if base == 30 => return Ok(1),
+        "dim" if base == 30 => return Ok(2),
+        "italic" if base == 30 => return Ok(3),
+        "underline" if base == 30 => return Ok(4),
+        _ => return Err(invalid()),
+    };
+    Ok(base + code)
+}
+
+fn escape(codes: &[u8]) -> String {
+    let codes: Vec<_> = codes.iter().map(u8::to_string).collect();
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+// Reset the colors to those of the surrounding `with-color`s rather than to none
+fn reset(active: &[u8]) -> String {
+    if active.is_empty() {
+        RESET.to_string()
+    } else {
+        format!("{}{}", RESET, escape(active))
+    }
+}
+
+impl VM {
+    /// Whether escape codes for colors are written.
+    pub fn terminal_colors(&self) -> bool {
+        self.colors
+    }
+
+    /// Write escape codes for colors or not, instead of deciding by what standard output is.
+    pub fn set_terminal_colors(&mut self, colors: bool) {
+        self.colors = colors;
+    }
+}
+
+/// `(colorize string color)` `string` shown in `color`.
+pub fn colorize(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !args[0].is_string() {
+        return Err(format!("colorize: {} is not a string", args[0]));
+    }
+    let code = color_arg("colorize", args[1])?;
+    let s = args[0].to_string();
+    let colored = if vm.colors {
+        format!("{}{}{}", escape(&[code]), s.str, reset(&vm.active_colors))
+    } else {
+        s.str.clone()
+    };
+    Box::into_raw(s);
+    Ok(Value::String(colored))
+}
+
+/// `(with-color color thunk)` Call `thunk`, with what it writes to the current output port shown
+/// in `color`.
+pub fn with_color(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let code = color_arg("with-color", args[0])?;
+    if !args[1].is_procedure() {
+        return Err(format!("with-color: {} is not a procedure", args[1]));
+    } else if !vm.colors {
+        return vm.apply(args[1], &[]);
+    }
+
+    write_bytes(vm, "with-color", None, escape(&[code]).as_bytes())?;
+    vm.active_colors.push(code);
+    let result = vm.apply(args[1], &[]);
+    vm.active_colors.pop();
+    let restore = reset(&vm.active_colors);
+    write_bytes(vm, "with-color", None, restore.as_bytes())?;
+    result
+}
+
+/// `(terminal-colors?)` Whether colors are written.
+pub fn is_terminal_colors(vm: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(vm.colors))
+}
+
+/// `(set-terminal-colors! colors?)` Write colors or not, whatever standard output is.
+pub fn set_terminal_colors(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    vm.colors = !args[0].is_false();
+    Ok(Value::Void)
+}
@@ -0,0 +1,141 @@
+//! Sleeping, a monotonic clock, and timers.
+//!
+//! There is no preemption: a procedure scheduled with `after` runs while the program waits, in
+//! `sleep` or when whoever is running the machine calls `run_timers`, e.g. the REPL once a script
+//! has finished. Timers run in the order they are due, those due at the same time in the order
+//! they were scheduled. `sleep` stops early when a signal arrives, so that it is raised by the
+//! next instruction. Paths watched with `watch-path` are checked for changes in the same loop.
+//!
+//! A host can run the machine on a virtual clock instead of the real one, which stands still while
+//! the program runs and jumps ahead rather than sleeping, so timers run the same way every time.
+
+use {Value, VmError, VM};
+use signal;
+
+use std::sync::LazyLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The longest the machine sleeps without checking for a signal
+const SLICE: Duration = Duration::from_millis(10);
+
+// The time `monotonic-time` counts from
+static EPOCH: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// A procedure to be called once its time comes.
+#[derive(Debug)]
+pub(crate) struct Timer {
+    pub(crate) due: Instant,
+    pub(crate) thunk: Value,
+}
+
+pub(crate) fn seconds_arg(name: &str, v: Value) -> Result<Duration, String> {
+    if v.is_integer() && v.to_integer() >= 0 {
+        Ok(Duration::from_secs(v.to_integer() as u64))
+    } else if v.is_float() && v.to_float() >= 0.0 && v.to_float().is_finite() {
+        Ok(Duration::from_secs_f64(v.to_float()))
+    } else {
+        Err(format!("{}: {} is not a number of seconds", name, v))
+    }
+}
+
+impl VM {
+    /// Wait for the timers scheduled with `after` and run them, as they come due. Returns the
+    /// error of a timer which signals a condition, or an `interrupt` error if a signal arrives.
+    pub fn run_timers(&mut self) -> Result<(), String> {
+        self.wait(None)?;
+        match signal::take_pending() {
+            Some(signal) => Err(VmError::Interrupt(signal).message()),
+            None => Ok(()),
+        }
+    }
+
+    /// Use a virtual clock, which only moves when the program waits, and then straight to the time
+    /// it waits for, or go back to the real clock.
+    pub fn set_virtual_clock(&mut self, on: bool) {
+        LazyLock::force(&EPOCH);
+        self.clock = if on { Some(self.now()) } else { None };
+    }
+
+    /// The time by the clock timers are run by.
+    pub(crate) fn now(&self) -> Instant {
+        self.clock.unwrap_or_else(Instant::now)
+    }
+
+    /// The number of timers waiting to run.
+    pub fn pending_timers(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// Run the timers which are due, waiting for those due before `until`, or for every timer if
+    /// `until` is `None`. Stops early if a signal arrives.
+    fn wait(&mut self, until: Option<Instant>) -> Result<(), String> {
+        loop {
+            if signal::is_pending() {
+                return Ok(());
+            }
+            let now = self.now();
+            if self.timers.first().is_some_and(|t| t.due <= now) {
+                let t = self.timers.remove(0);
+                self.apply(t.thunk, &[])?;
+                continue;
+            }
+            #[cfg(feature = "watch")]
+            if self.poll_watches(now)? {
+                continue;
+            }
+            let next = match (self.next_event(), until) {
+                (Some(due), Some(until)) => due.min(until),
+                (Some(due), None) => due,
+                (None, Some(until)) => until,
+                (None, None) => return Ok(()),
+            };
+            if next <= now {
+                return Ok(());
+            }
+            match self.clock {
+                Some(ref mut clock) => *clock = next,
+                None => thread::sleep((next - now).min(SLICE)),
+            }
+        }
+    }
+
+    /// When the next timer is due, or the next watched path is to be checked.
+    fn next_event(&self) -> Option<Instant> {
+        let timers = self.timers.first().map(|t| t.due);
+        #[cfg(feature = "watch")]
+        let watches = self.next_watch();
+        #[cfg(not(feature = "watch"))]
+        let watches = None;
+        timers.into_iter().chain(watches).min()
+    }
+}
+
+/// `(sleep seconds)` Wait for `seconds`, which may be fractional, running any timers which come
+/// due meanwhile.
+pub fn sleep(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let duration = seconds_arg("sleep", args[0])?;
+    let until = vm.now() + duration;
+    vm.wait(Some(until))?;
+    Ok(Value::Void)
+}
+
+/// `(monotonic-time)` The seconds elapsed since a fixed point in the past, as a float with
+/// sub-microsecond resolution. It never goes backwards, so the difference of two calls measures
+/// the time between them.
+pub fn monotonic_time(vm: &mut VM, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Float(vm.now().saturating_duration_since(*EPOCH).as_secs_f64()))
+}
+
+/// `(after seconds thunk)` Call `thunk` once `seconds` have passed, the next time the program
+/// waits.
+pub fn after(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let delay = seconds_arg("after", args[0])?;
+    if !args[1].is_procedure() {
+        return Err(format!("after: {} is not a procedure", args[1]));
+    }
+    let due = vm.now() + delay;
+    let i = vm.timers.partition_point(|t| t.due <= due);
+    vm.timers.insert(i, Timer { due, thunk: args[1] });
+    Ok(Value::Void)
+}
@@ -0,0 +1,105 @@
+//! Copying values between interpreters.
+//!
+//! Every machine allocates on the same heap, but a value handed from one interpreter to another
+//! must not share mutable structure with the original. `deep_copy` copies everything reachable
+//! from a value, preserving sharing and cycles. Symbols are interned once per process, so they
+//! refer to the same name in every interpreter and are not copied.
+//!
+//! Compound procedures, continuations, ports, databases, environments, vector slices, and char-sets
+//! belong to the machine that made them and can't be copied. Native procedures are shared by every
+//! machine.
+
+use value::VType;
+use Value;
+
+use std::collections::HashMap;
+
+/// A deep copy of `v`, or the first value reachable from it which can't be copied.
+///
+/// Nothing refers to the copy, so it must be rooted, e.g. by binding it in an environment, before
+/// a machine runs again.
+pub fn deep_copy(v: Value) -> Result<Value, Value> {
+    let mut copier = Copier {
+        copies: HashMap::new(),
+        pending: vec![],
+    };
+    let copy = copier.copy(v)?;
+    // Containers are allocated empty and filled afterwards, so that cycles refer to the copy
+    while let Some((from, to)) = copier.pending.pop() {
+        match from.to_type() {
+            VType::Pair => {
+                to.set_car(copier.copy(from.car())?);
+                to.set_cdr(copier.copy(from.cdr())?);
+            }
+            VType::Vec => {
+                let v = from.to_vec();
+                let items = v.vec.clone();
+                Box::into_raw(v);
+                let items = items.into_iter().map(|i| copier.copy(i)).collect::<Result<_, _>>()?;
+                let mut v = to.to_vec();
+                v.vec = items;
+                Box::into_raw(v);
+            }
+            VType::HashMap => {
+                let m = from.to_hashmap();
+                let entries: Vec<_> = m.map.iter().map(|(&k, &v)| (k, v)).collect();
+                Box::into_raw(m);
+                let mut map = HashMap::new();
+                for (k, v) in entries {
+                    map.insert(copier.copy(k)?, copier.copy(v)?);
+                }
+                let mut m = to.to_hashmap();
+                m.map = map;
+                Box::into_raw(m);
+            }
+            _ => unreachable!(),
+        }
+    }
+    Ok(copy)
+}
+
+struct Copier {
+    // Originals and their copies
+    copies: HashMap<Value, Value>,
+    // Containers whose contents haven't been copied yet
+    pending: Vec<(Value, Value)>,
+}
+
+impl Copier {
+    fn copy(&mut self, v: Value) -> Result<Value, Value> {
+        if let Some(&copy) = self.copies.get(&v) {
+            return Ok(copy);
+        }
+        let copy = match v.to_type() {
+            VType::Pair => Value::Pair(Value::Void, Value::Void),
+            VType::Vec => Value::Vec(vec![]),
+            VType::HashMap => Value::HashMap(HashMap::new()),
+            VType::String => {
+                let s = v.to_string();
+                let copy = Value::String(s.str.clone());
+                Box::into_raw(s);
+                copy
+            }
+            VType::Bytevector => {
+                let b = v.to_bytevector();
+                let copy = Value::Bytevector(b.bytes.clone());
+                Box::into_raw(b);
+                copy
+            }
+            VType::BigInt => {
+                let b = v.to_bigint();
+                let copy = Value::BigInt(b.n.clone());
+                Box::into_raw(b);
+                copy
+            }
+            VType::Lambda | VType::Port | VType::Database | VType::Environment | VType::Slice |
+            VType::Continuation | VType::CharSet => return Err(v),
+            _ => return Ok(v),
+        };
+        if matches!(v.to_type(), VType::Pair | VType::Vec | VType::HashMap) {
+            self.pending.push((v, copy));
+        }
+        self.copies.insert(v, copy);
+        Ok(copy)
+    }
+}
@@ -1,12 +1,18 @@
 #![allow(non_upper_case_globals, non_snake_case)]
 
-use {get_head, set_head, Environment, Operation};
+use {allocate, get_head, Environment, Operation};
+use gc::reach;
+use native::get_native;
 use self::heap_repr::*;
 
+use num_bigint::BigInt;
 use string_interner::{get_value, Symbol};
 
-use std::{fmt, ops};
+use std::{fmt, hash, ops};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 pub enum VType {
     Void = 0,
@@ -21,6 +27,16 @@ pub enum VType {
     String = 9,
     HashMap = 10,
     BigInt = 11,
+    Native = 12,
+    Char = 13,
+    Port = 14,
+    Eof = 15,
+    Bytevector = 16,
+    Database = 17,
+    Environment = 18,
+    Slice = 19,
+    Continuation = 20,
+    CharSet = 21,
 }
 
 impl From<u64> for VType {
@@ -35,6 +51,8 @@ impl From<u64> for VType {
             VType::Pair
         } else if p == VType::HashMap as u64 {
             VType::HashMap
+        } else if p == VType::Bytevector as u64 {
+            VType::Bytevector
         } else if p == VType::BigInt as u64 {
             VType::BigInt
         } else if p == VType::Void as u64 {
@@ -66,6 +84,15 @@ const NIL_TAG: u64 =    0b0010 << 44;
 const BOOL_TAG: u64 =   0b0011 << 44;
 const INT_TAG: u64 =    0b0100 << 44;
 const SYMBOL_TAG: u64 = 0b0101 << 44;
+const NATIVE_TAG: u64 = 0b0110 << 44;
+const CHAR_TAG: u64 =   0b0111 << 44;
+const PORT_TAG: u64 =   0b1000 << 44;
+const EOF_TAG: u64 =    0b1001 << 44;
+const DATABASE_TAG: u64 = 0b1010 << 44;
+const ENVIRONMENT_TAG: u64 = 0b1011 << 44;
+const SLICE_TAG: u64 =  0b1100 << 44;
+const CONTINUATION_TAG: u64 = 0b1101 << 44;
+const CHARSET_TAG: u64 = 0b1110 << 44;
 const TRUE: u64 = 1;
 const FALSE: u64 = 0;
 
@@ -76,7 +103,11 @@ const STRING_TAG: u64 = 0b100 << 48;
 
 
 const HASHMAP_TAG: u64 = 0b101 << 48;
-//const BIGINT_TAG: u64 = 0b110 << 48;
+const BIGINT_TAG: u64 =  0b110 << 48;
+const BYTEVECTOR_TAG: u64 = 0b111 << 48;
+
+// The next identity hash given to a heap object, 0 marks an object without one
+static NEXT_HASH: AtomicU64 = AtomicU64::new(1);
 
 macro_rules! is_imm {
     ($name:ident, $tag:ident) => {
@@ -131,6 +162,24 @@ impl Value {
             VType::Float
         } else if self.is_symbol() {
             VType::Symbol
+        } else if self.is_native() {
+            VType::Native
+        } else if self.is_char() {
+            VType::Char
+        } else if self.is_port() {
+            VType::Port
+        } else if self.is_eof() {
+            VType::Eof
+        } else if self.is_database() {
+            VType::Database
+        } else if self.is_environment() {
+            VType::Environment
+        } else if self.is_slice() {
+            VType::Slice
+        } else if self.is_continuation() {
+            VType::Continuation
+        } else if self.is_char_set() {
+            VType::CharSet
         } else if self.is_lambda() {
             VType::Lambda
         } else if self.is_pair() {
@@ -139,6 +188,12 @@ impl Value {
             VType::Vec
         } else if self.is_string() {
             VType::String
+        } else if self.is_bytevector() {
+            VType::Bytevector
+        } else if self.is_bigint() {
+            VType::BigInt
+        } else if self.is_hashmap() {
+            VType::HashMap
         } else {
             unreachable!();
         }
@@ -182,11 +237,17 @@ impl Value {
 
     // TODO: make this const when const mem::transmute is stable
     pub fn Float(f: f64) -> Self {
-        Value::new(f.to_bits())
+        // A NAN's payload could be mistaken for a tag, so every NAN is stored as the quiet NAN.
+        // Both it and the infinities have a tag of 0, which no other value uses.
+        if f.is_nan() {
+            Value::new(f64::NAN.to_bits())
+        } else {
+            Value::new(f.to_bits())
+        }
     }
 
     pub const fn is_float(self) -> bool {
-        (self.0 & NAN) != NAN
+        (self.0 & NAN) != NAN || (self.0 & (TAG_MASK | IMMEDIATE_MASK)) == 0
     }
 
     // TODO: make this const when const mem::transmute is stable
@@ -208,11 +269,107 @@ impl Value {
         Symbol::new(self.0 as u32 as usize)
     }
 
+    /// A procedure implemented in Rust, `i` is its index in the table of natives.
+    pub const fn Native(i: u32) -> Self {
+        Value::new(NAN | NATIVE_TAG | (i as u64))
+    }
+    is_imm!(is_native, NATIVE_TAG);
+
+    pub const fn to_native(self) -> usize {
+        self.0 as u32 as usize
+    }
+
+    pub const fn Char(c: char) -> Self {
+        Value::new(NAN | CHAR_TAG | (c as u64))
+    }
+    is_imm!(is_char, CHAR_TAG);
+
+    pub fn to_char(self) -> char {
+        char::from_u32(self.0 as u32).unwrap()
+    }
+
+    /// A port, `i` is its index in the port table of the machine which opened it.
+    pub const fn Port(i: u32) -> Self {
+        Value::new(NAN | PORT_TAG | (i as u64))
+    }
+    is_imm!(is_port, PORT_TAG);
+
+    pub const fn to_port(self) -> usize {
+        self.0 as u32 as usize
+    }
+
+    /// The object returned by reads at the end of a port.
+    pub const Eof: Self = Value::new(NAN | EOF_TAG);
+    is_imm!(is_eof, EOF_TAG);
+
+    /// A database connection, `i` is its index in the connection table of the machine which opened
+    /// it.
+    pub const fn Database(i: u32) -> Self {
+        Value::new(NAN | DATABASE_TAG | (i as u64))
+    }
+    is_imm!(is_database, DATABASE_TAG);
+
+    pub const fn to_database(self) -> usize {
+        self.0 as u32 as usize
+    }
+
+    /// A first-class environment, `i` is its index in the environment table of the machine which
+    /// created it.
+    pub const fn Environment(i: u32) -> Self {
+        Value::new(NAN | ENVIRONMENT_TAG | (i as u64))
+    }
+    is_imm!(is_environment, ENVIRONMENT_TAG);
+
+    pub const fn to_environment(self) -> usize {
+        self.0 as u32 as usize
+    }
+
+    /// A view of part of a vector, `i` is its index in the slice table of the machine which
+    /// created it.
+    pub const fn Slice(i: u32) -> Self {
+        Value::new(NAN | SLICE_TAG | (i as u64))
+    }
+    is_imm!(is_slice, SLICE_TAG);
+
+    pub const fn to_slice(self) -> usize {
+        self.0 as u32 as usize
+    }
+
+    /// A continuation, `i` is its index in the continuation table of the machine which captured it.
+    pub const fn Continuation(i: u32) -> Self {
+        Value::new(NAN | CONTINUATION_TAG | (i as u64))
+    }
+    is_imm!(is_continuation, CONTINUATION_TAG);
+
+    pub const fn to_continuation(self) -> usize {
+        self.0 as u32 as usize
+    }
+
+    /// A set of characters, `i` is its index in the char-set table of the machine which made it.
+    pub const fn CharSet(i: u32) -> Self {
+        Value::new(NAN | CHARSET_TAG | (i as u64))
+    }
+    is_imm!(is_char_set, CHARSET_TAG);
+
+    pub const fn to_char_set(self) -> usize {
+        self.0 as u32 as usize
+    }
+
+    /// Whether this value can be called.
+    pub fn is_procedure(self) -> bool {
+        self.is_lambda() || self.is_native() || self.is_continuation()
+    }
+
     pub fn Lambda(env: Environment, code: Vec<Operation>, consts: Vec<Self>) -> Self {
+        Value::Closure(env, code.into(), consts.into())
+    }
+
+    /// A procedure closing over `env` which shares its code and constants with other closures.
+    pub fn Closure(env: Environment, code: Arc<[Operation]>, consts: Arc<[Self]>) -> Self {
         let next = get_head();
         let lambda = Box::into_raw(Box::new(Lambda::new(next, env, code, consts)));
         let p = lambda as u64;
-        set_head(p, VType::Lambda);
+        allocate(p, VType::Lambda);
         Value::new(NAN | LAMBDA_TAG | (p & ((1 << 48) - 1)))
     }
     is_pointer!(is_lambda, LAMBDA_TAG);
@@ -222,7 +379,7 @@ impl Value {
         let next = get_head();
         let pair = Box::into_raw(Box::new(Pair::new(next, car, cdr)));
         let p = pair as u64;
-        set_head(p, VType::Pair);
+        allocate(p, VType::Pair);
         Value::new(NAN | PAIR_TAG | (p & ((1 << 48) - 1)))
     }
     is_pointer!(is_pair, PAIR_TAG);
@@ -258,7 +415,7 @@ impl Value {
         let next = get_head();
         let vec = Box::into_raw(Box::new(SVec::new(next, v)));
         let p = vec as u64;
-        set_head(p, VType::Vec);
+        allocate(p, VType::Vec);
         Value::new(NAN | VEC_TAG | (p & ((1 << 48) - 1)))
     }
     is_pointer!(is_vec, VEC_TAG);
@@ -268,7 +425,7 @@ impl Value {
         let next = get_head();
         let str = Box::into_raw(Box::new(SString::new(next, s)));
         let p = str as u64;
-        set_head(p, VType::String);
+        allocate(p, VType::String);
         Value::new(NAN | STRING_TAG | (p & ((1 << 48) - 1)))
     }
     is_pointer!(is_string, STRING_TAG);
@@ -278,12 +435,42 @@ impl Value {
         let next = get_head();
         let str = Box::into_raw(Box::new(SHashMap::new(next, m)));
         let p = str as u64;
-        set_head(p, VType::HashMap);
+        allocate(p, VType::HashMap);
         Value::new(NAN | HASHMAP_TAG | (p & ((1 << 48) - 1)))
     }
     is_pointer!(is_hashmap, HASHMAP_TAG);
     to_pointer!(to_hashmap, SHashMap);
 
+    pub fn Bytevector(b: Vec<u8>) -> Self {
+        let next = get_head();
+        let bytes = Box::into_raw(Box::new(SBytevector::new(next, b)));
+        let p = bytes as u64;
+        allocate(p, VType::Bytevector);
+        Value::new(NAN | BYTEVECTOR_TAG | (p & ((1 << 48) - 1)))
+    }
+    is_pointer!(is_bytevector, BYTEVECTOR_TAG);
+    to_pointer!(to_bytevector, SBytevector);
+
+    /// An integer outside the fixnum range. Use `Value::integer` for a value which may fit in a
+    /// fixnum.
+    pub fn BigInt(n: BigInt) -> Self {
+        let next = get_head();
+        let big = Box::into_raw(Box::new(SBigInt::new(next, n)));
+        let p = big as u64;
+        allocate(p, VType::BigInt);
+        Value::new(NAN | BIGINT_TAG | (p & ((1 << 48) - 1)))
+    }
+    is_pointer!(is_bigint, BIGINT_TAG);
+    to_pointer!(to_bigint, SBigInt);
+
+    /// An exact integer, a fixnum if `n` is in range and a bignum otherwise.
+    pub fn integer(n: BigInt) -> Self {
+        match i32::try_from(&n) {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::BigInt(n),
+        }
+    }
+
     // TODO: make const when Option::unwrap is allowed
     pub fn to_pointer(self) -> u64 {
         // Amd64 currently only uses the lower 48 bits for pointers, which is what makes NANboxing
@@ -305,9 +492,12 @@ impl Value {
                     let mut p = cur.to_lambda();
                     if p.gc & 1 != 1 {
                         p.gc = p.gc | 1;
-                        for &v in &p.consts {
+                        for &v in p.consts.iter() {
                             list.push(v);
                         }
+                        if let Some(ref memo) = p.memo {
+                            list.extend(memo.values());
+                        }
                         p.env.mark();
                     }
                     Box::into_raw(p);
@@ -336,20 +526,85 @@ impl Value {
                     p.gc = p.gc | 1;
                     Box::into_raw(p);
                 }
+                VType::Bytevector => {
+                    let mut p = cur.to_bytevector();
+                    p.gc = p.gc | 1;
+                    Box::into_raw(p);
+                }
+                VType::BigInt => {
+                    let mut p = cur.to_bigint();
+                    p.gc = p.gc | 1;
+                    Box::into_raw(p);
+                }
                 VType::HashMap => {
                     let mut p = cur.to_hashmap();
-                    p.gc = p.gc | 1;
-                    for (&k, &v) in &p.map {
-                        list.push(k);
-                        list.push(v);
+                    if p.gc & 1 != 1 {
+                        p.gc = p.gc | 1;
+                        for (&k, &v) in &p.map {
+                            list.push(k);
+                            list.push(v);
+                        }
                     }
                     Box::into_raw(p);
                 }
+                // What these hold is in a table of the machine, which marks it
+                VType::Slice | VType::Continuation => reach(cur),
                 _ => (),
             }
         }
     }
 
+    /// A hash of the identity of `self`, which stays the same for as long as the object lives.
+    /// Heap objects are numbered in their header the first time they are hashed, rather than
+    /// hashing their address, so that the hash survives the object being moved.
+    pub(crate) fn identity_hash(self) -> u64 {
+        macro_rules! header {
+            ($p:expr) => {
+                {
+                    let mut p = $p;
+                    if p.hash == 0 {
+                        p.hash = NEXT_HASH.fetch_add(1, Ordering::Relaxed);
+                    }
+                    let hash = p.hash;
+                    Box::into_raw(p);
+                    hash
+                }
+            };
+        }
+
+        match self.to_type() {
+            VType::Lambda => header!(self.to_lambda()),
+            VType::Pair => header!(self.to_pair()),
+            VType::Vec => header!(self.to_vec()),
+            VType::String => header!(self.to_string()),
+            VType::HashMap => header!(self.to_hashmap()),
+            VType::Bytevector => header!(self.to_bytevector()),
+            VType::BigInt => header!(self.to_bigint()),
+            // Immediates are their own identity, mix their bits so nearby values spread out
+            _ => self.0.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 16,
+        }
+    }
+
+    /// The `gc` field of the heap object at `p`, an entry of the heap list.
+    pub(crate) fn get_gc(p: u64) -> u64 {
+        let ty = VType::from(p >> 56);
+        let ptr = Self::sign_extend(p, 55) & !7;
+        macro_rules! gc {
+            ($T:ty) => { unsafe { (*(ptr as *const $T)).gc } };
+        }
+
+        match ty {
+            VType::Lambda => gc!(Lambda),
+            VType::Pair => gc!(Pair),
+            VType::String => gc!(SString),
+            VType::Vec => gc!(SVec),
+            VType::HashMap => gc!(SHashMap),
+            VType::Bytevector => gc!(SBytevector),
+            VType::BigInt => gc!(SBigInt),
+            _ => unreachable!(),
+        }
+    }
+
     pub(crate) fn set_gc(p: u64, gc: u64) {
         let ty = VType::from(p >> 56);
         // TODO
@@ -388,6 +643,16 @@ impl Value {
                 p.gc = gc;
                 Box::into_raw(p);
             }
+            VType::Bytevector => {
+                let mut p = unsafe { Box::from_raw(ptr as *mut SBytevector) };
+                p.gc = gc;
+                Box::into_raw(p);
+            }
+            VType::BigInt => {
+                let mut p = unsafe { Box::from_raw(ptr as *mut SBigInt) };
+                p.gc = gc;
+                Box::into_raw(p);
+            }
             _ => unreachable!(),
         }
     }
@@ -402,7 +667,16 @@ impl fmt::Debug for Value {
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.is_float() {
-            write!(f, "{}", self.to_float())
+            let n = self.to_float();
+            if n.is_nan() {
+                write!(f, "+nan.0")
+            } else if n.is_infinite() {
+                write!(f, "{}inf.0", if n > 0.0 { "+" } else { "-" })
+            } else {
+                // The shortest text which reads back as `n`, always with a decimal point or
+                // exponent so that it reads back as a float
+                write!(f, "{:?}", n)
+            }
         } else if self.is_integer() {
             write!(f, "{}", self.to_integer())
         } else if self.is_symbol() {
@@ -418,6 +692,36 @@ impl fmt::Display for Value {
             Ok(())
         } else if self.is_lambda() {
             write!(f, "#<procedure>")
+        } else if self.is_native() {
+            write!(f, "#<procedure {}>", get_native(self.to_native()).name)
+        } else if self.is_char() {
+            match self.to_char() {
+                ' ' => write!(f, "#\\space"),
+                '\n' => write!(f, "#\\newline"),
+                '\t' => write!(f, "#\\tab"),
+                '\r' => write!(f, "#\\return"),
+                '\0' => write!(f, "#\\null"),
+                '\x07' => write!(f, "#\\alarm"),
+                '\x08' => write!(f, "#\\backspace"),
+                '\x1b' => write!(f, "#\\escape"),
+                '\x7f' => write!(f, "#\\delete"),
+                c if c.is_control() || c.is_whitespace() => write!(f, "#\\x{:x}", c as u32),
+                c => write!(f, "#\\{}", c),
+            }
+        } else if self.is_port() {
+            write!(f, "#<port {}>", self.to_port())
+        } else if self.is_eof() {
+            write!(f, "#<eof>")
+        } else if self.is_database() {
+            write!(f, "#<database {}>", self.to_database())
+        } else if self.is_environment() {
+            write!(f, "#<environment {}>", self.to_environment())
+        } else if self.is_slice() {
+            write!(f, "#<vector-slice {}>", self.to_slice())
+        } else if self.is_continuation() {
+            write!(f, "#<continuation {}>", self.to_continuation())
+        } else if self.is_char_set() {
+            write!(f, "#<char-set {}>", self.to_char_set())
         } else if self.is_pair() {
             let p = Value::to_pair(*self);
 
@@ -439,21 +743,47 @@ impl fmt::Display for Value {
             r
         } else if self.is_string() {
             let s = Value::to_string(*self);
-            let r = write!(f, "\"{}\"", s.str);
+            let r = write_string(f, &s.str);
             Box::into_raw(s);
             r
         } else if self.is_vec() {
             let vec = Value::to_vec(*self);
             write!(f, "#(")?;
             for (i, v) in vec.vec.iter().enumerate() {
-                if i+1 != vec.vec.len() {
-                    write!(f, "{}, ", v)?;
-                } else {
-                    write!(f, "{}", v)?;
+                if i != 0 {
+                    write!(f, " ")?;
                 }
+                write!(f, "{}", v)?;
             }
             Box::into_raw(vec);
             write!(f, ")")
+        } else if self.is_bigint() {
+            let b = Value::to_bigint(*self);
+            write!(f, "{}", b.n)?;
+            Box::into_raw(b);
+            Ok(())
+        } else if self.is_bytevector() {
+            let b = Value::to_bytevector(*self);
+            write!(f, "#u8(")?;
+            for (i, byte) in b.bytes.iter().enumerate() {
+                if i != 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", byte)?;
+            }
+            Box::into_raw(b);
+            write!(f, ")")
+        } else if self.is_hashmap() {
+            let m = Value::to_hashmap(*self);
+            write!(f, "#hash(")?;
+            for (i, (k, v)) in m.map.iter().enumerate() {
+                if i != 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "({} . {})", k, v)?;
+            }
+            Box::into_raw(m);
+            write!(f, ")")
         } else {
             write!(f, "debug: ")
             //write!(f, "debug: {:?}", self)
@@ -461,6 +791,25 @@ impl fmt::Display for Value {
     }
 }
 
+/// Write `s` as a string literal the reader reads back as `s`.
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            '\x07' => write!(f, "\\a")?,
+            '\x08' => write!(f, "\\b")?,
+            c if c.is_control() => write!(f, "\\x{:x};", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
 impl ops::Deref for Value {
     type Target = u64;
     fn deref(&self) -> &u64 {
@@ -468,6 +817,13 @@ impl ops::Deref for Value {
     }
 }
 
+// Keys are compared by their bits, like `eq?`
+impl hash::Hash for Value {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
 impl From<u64> for Value {
     fn from(v: u64) -> Self {
         Value::new(v)
@@ -477,29 +833,53 @@ impl From<u64> for Value {
 pub mod heap_repr {
     use super::Value;
     use {Environment, Operation};
+    use inline_cache::InlineCache;
+    use memo::Cache;
+
+    use num_bigint::BigInt;
 
     use std::collections::HashMap;
+    use std::mem;
+    use std::sync::Arc;
 
     pub struct Lambda {
         pub(crate) gc: u64,
+        pub(crate) hash: u64,
         pub env: Environment,
-        pub code: Vec<Operation>,
-        pub consts: Vec<Value>,
+        // The code and constants are shared by every closure made from the same lambda expression
+        pub code: Arc<[Operation]>,
+        pub consts: Arc<[Value]>,
+        // The results of a memoized procedure
+        pub(crate) memo: Option<Box<Cache>>,
+        // The inline caches of the call sites in `code`, indexed by slot
+        pub(crate) caches: Vec<InlineCache>,
     }
 
     impl Lambda {
-        pub fn new(gc: u64, env: Environment, code: Vec<Operation>, consts: Vec<Value>) -> Self {
+        pub fn new(gc: u64, env: Environment, code: Arc<[Operation]>, consts: Arc<[Value]>) -> Self {
             Lambda {
                 gc: gc,
+                hash: 0,
                 env: env,
                 code: code,
                 consts: consts,
+                memo: None,
+                caches: vec![],
             }
         }
+
+        /// Approximately the bytes it uses, not counting its environment or memoized results. Code
+        /// shared with other closures is counted in full by each.
+        pub(crate) fn size(&self) -> usize {
+            mem::size_of::<Self>() + self.code.len() * mem::size_of::<Operation>()
+                + self.consts.len() * mem::size_of::<Value>()
+                + self.caches.capacity() * mem::size_of::<InlineCache>()
+        }
     }
 
     pub struct Pair {
         pub(crate) gc: u64,
+        pub(crate) hash: u64,
         pub car: Value,
         pub cdr: Value,
     }
@@ -508,14 +888,20 @@ pub mod heap_repr {
         pub fn new(gc: u64, car: Value, cdr: Value) -> Self {
             Pair {
                 gc: gc,
+                hash: 0,
                 car,
                 cdr,
             }
         }
+
+        pub(crate) fn size(&self) -> usize {
+            mem::size_of::<Self>()
+        }
     }
 
     pub struct SString {
         pub(crate) gc: u64,
+        pub(crate) hash: u64,
         pub str: String,
     }
 
@@ -523,13 +909,19 @@ pub mod heap_repr {
         pub fn new(gc: u64, s: String) -> Self {
             SString {
                 gc: gc,
+                hash: 0,
                 str: s,
             }
         }
+
+        pub(crate) fn size(&self) -> usize {
+            mem::size_of::<Self>() + self.str.capacity()
+        }
     }
 
     pub struct SVec {
         pub(crate) gc: u64,
+        pub(crate) hash: u64,
         pub vec: Vec<Value>,
     }
 
@@ -537,13 +929,19 @@ pub mod heap_repr {
         pub fn new(gc: u64, v: Vec<Value>) -> Self {
             SVec {
                 gc: gc,
+                hash: 0,
                 vec: v,
             }
         }
+
+        pub(crate) fn size(&self) -> usize {
+            mem::size_of::<Self>() + self.vec.capacity() * mem::size_of::<Value>()
+        }
     }
 
     pub struct SHashMap {
         pub(crate) gc: u64,
+        pub(crate) hash: u64,
         pub map: HashMap<Value, Value>,
     }
 
@@ -551,8 +949,54 @@ pub mod heap_repr {
         pub fn new(gc: u64, m: HashMap<Value, Value>) -> Self {
             SHashMap {
                 gc: gc,
+                hash: 0,
                 map: m,
             }
         }
+
+        /// Approximately the bytes it uses, an entry and a control byte for each it has room for.
+        pub(crate) fn size(&self) -> usize {
+            mem::size_of::<Self>() + self.map.capacity() * (2 * mem::size_of::<Value>() + 1)
+        }
+    }
+
+    pub struct SBytevector {
+        pub(crate) gc: u64,
+        pub(crate) hash: u64,
+        pub bytes: Vec<u8>,
+    }
+
+    impl SBytevector {
+        pub fn new(gc: u64, b: Vec<u8>) -> Self {
+            SBytevector {
+                gc: gc,
+                hash: 0,
+                bytes: b,
+            }
+        }
+
+        pub(crate) fn size(&self) -> usize {
+            mem::size_of::<Self>() + self.bytes.capacity()
+        }
+    }
+
+    pub struct SBigInt {
+        pub(crate) gc: u64,
+        pub(crate) hash: u64,
+        pub n: BigInt,
+    }
+
+    impl SBigInt {
+        pub fn new(gc: u64, n: BigInt) -> Self {
+            SBigInt {
+                gc: gc,
+                hash: 0,
+                n: n,
+            }
+        }
+
+        pub(crate) fn size(&self) -> usize {
+            mem::size_of::<Self>() + (self.n.bits() as usize).div_ceil(8)
+        }
     }
 }
@@ -0,0 +1,187 @@
+//! Hashing values by their contents, for caching and deduplicating data across runs.
+//!
+//! A value is hashed by writing it in a canonical encoding, which depends only on its structure:
+//! not on heap addresses, the order entries were added to a hash table, or how the symbol table
+//! numbers symbols. Structures which are `equal` but share differently, e.g. a list containing
+//! the same pair twice and one containing two copies of it, have the same hash. The encoding of
+//! each value is the number of its type, as in `VType`, followed by
+//!
+//! - nothing for `()`, the void object, and the eof object,
+//! - one byte, 0 or 1, for a boolean,
+//! - the length and big-endian two's complement bytes of an integer, with fixnums and bignums
+//!   both encoded as the integer type,
+//! - the IEEE 754 bits of a float, little-endian,
+//! - the code point of a character, little-endian,
+//! - the length and UTF-8 bytes of a symbol or string, the name of a native procedure, or the
+//!   length and bytes of a bytevector,
+//! - the car and then the cdr of a pair,
+//! - the length and elements of a vector,
+//! - the number of entries of a hash table and each key followed by its value, sorted by the
+//!   encoding of the key.
+//!
+//! Lengths are 8 bytes, little-endian. A pair, vector, or hash table which contains itself is
+//! encoded, where it appears inside itself, as the byte 255 followed by how many containers out
+//! the enclosing one is, so cycles have a hash too.
+//!
+//! `value-hash` is the 64 bit FNV-1a hash of the encoding. With the `crypto` feature,
+//! `value-digest` is its SHA-256 digest. Compound procedures, continuations, ports, databases,
+//! environments, vector slices, and char-sets have no contents to hash.
+
+use value::VType;
+use {native, Value, VM};
+
+use num_bigint::BigInt;
+use string_interner::get_value;
+
+use std::collections::HashMap;
+
+// Marks a reference to a container enclosing the one being encoded
+const BACK_REFERENCE: u8 = 255;
+
+/// The canonical encoding of `v`, or the first value reachable from it which can't be hashed.
+pub(crate) fn encode(v: Value) -> Result<Vec<u8>, Value> {
+    let mut encoder = Encoder {
+        out: vec![],
+        path: vec![],
+        enclosing: HashMap::new(),
+    };
+    encoder.encode(v)?;
+    Ok(encoder.out)
+}
+
+/// The 64 bit hash of the contents of `v`, or the first value reachable from it which can't be
+/// hashed. Equal structures have the same hash in every run of every program.
+pub fn value_hash(v: Value) -> Result<u64, Value> {
+    // FNV-1a
+    let mut hash = 0xcbf29ce484222325u64;
+    for b in encode(v)? {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    Ok(hash)
+}
+
+/// `(value-hash obj)` The 64 bit hash of the contents of `obj`, as an exact integer.
+pub fn value_hash_native(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    match value_hash(args[0]) {
+        Ok(hash) => Ok(Value::integer(BigInt::from(hash))),
+        Err(v) => Err(format!("value-hash: {} can't be hashed", v)),
+    }
+}
+
+struct Encoder {
+    out: Vec<u8>,
+    // The containers being encoded, outermost first
+    path: Vec<Value>,
+    // The position of each container in `path`
+    enclosing: HashMap<Value, usize>,
+}
+
+impl Encoder {
+    fn encode(&mut self, mut v: Value) -> Result<(), Value> {
+        let depth = self.path.len();
+        // The spine of a list is encoded in a loop, so long lists don't exhaust the stack
+        loop {
+            if let Some(&i) = self.enclosing.get(&v) {
+                self.out.push(BACK_REFERENCE);
+                self.length(self.path.len() - i);
+                break;
+            }
+            // Fixnums and bignums are the same type of number
+            self.out.push(match v.to_type() {
+                VType::BigInt => VType::Integer as u8,
+                t => t as u8,
+            });
+            match v.to_type() {
+                VType::Void | VType::Nil | VType::Eof => (),
+                VType::Bool => self.out.push(v.is_true() as u8),
+                VType::Integer => self.integer(BigInt::from(v.to_integer())),
+                VType::BigInt => {
+                    let b = v.to_bigint();
+                    let n = b.n.clone();
+                    Box::into_raw(b);
+                    self.integer(n);
+                }
+                VType::Float => self.out.extend_from_slice(&v.to_float().to_bits().to_le_bytes()),
+                VType::Char => self.out.extend_from_slice(&(v.to_char() as u32).to_le_bytes()),
+                VType::Symbol => self.bytes(get_value(v.to_symbol()).unwrap().as_bytes()),
+                VType::Native => self.bytes(native::get_native(v.to_native()).name.as_bytes()),
+                VType::String => {
+                    let s = v.to_string();
+                    self.bytes(s.str.as_bytes());
+                    Box::into_raw(s);
+                }
+                VType::Bytevector => {
+                    let b = v.to_bytevector();
+                    self.bytes(&b.bytes);
+                    Box::into_raw(b);
+                }
+                VType::Pair => {
+                    self.enter(v);
+                    self.encode(v.car())?;
+                    v = v.cdr();
+                    continue;
+                }
+                VType::Vec => {
+                    self.enter(v);
+                    let items = v.to_vec();
+                    let elements = items.vec.clone();
+                    Box::into_raw(items);
+                    self.length(elements.len());
+                    for e in elements {
+                        self.encode(e)?;
+                    }
+                }
+                VType::HashMap => {
+                    self.enter(v);
+                    let m = v.to_hashmap();
+                    let entries: Vec<_> = m.map.iter().map(|(&k, &v)| (k, v)).collect();
+                    Box::into_raw(m);
+                    let mut encoded = Vec::with_capacity(entries.len());
+                    for (k, v) in entries {
+                        encoded.push((self.encode_apart(k)?, self.encode_apart(v)?));
+                    }
+                    encoded.sort();
+                    self.length(encoded.len());
+                    for (k, v) in encoded {
+                        self.out.extend(k);
+                        self.out.extend(v);
+                    }
+                }
+                VType::Lambda | VType::Port | VType::Database | VType::Environment | VType::Slice |
+                VType::Continuation | VType::CharSet => return Err(v),
+            }
+            break;
+        }
+        for v in self.path.drain(depth..) {
+            self.enclosing.remove(&v);
+        }
+        Ok(())
+    }
+
+    /// The encoding of `v` on its own, inside the current container.
+    fn encode_apart(&mut self, v: Value) -> Result<Vec<u8>, Value> {
+        let out = std::mem::take(&mut self.out);
+        let result = self.encode(v);
+        let encoded = std::mem::replace(&mut self.out, out);
+        result.map(|_| encoded)
+    }
+
+    fn enter(&mut self, container: Value) {
+        self.enclosing.insert(container, self.path.len());
+        self.path.push(container);
+    }
+
+    fn length(&mut self, n: usize) {
+        self.out.extend_from_slice(&(n as u64).to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.length(bytes.len());
+        self.out.extend_from_slice(bytes);
+    }
+
+    fn integer(&mut self, n: BigInt) {
+        self.bytes(&n.to_signed_bytes_be());
+    }
+}
@@ -0,0 +1,217 @@
+//! Vectors, sequences of values indexed from 0. `vector-push!`, `vector-pop!`, and
+//! `vector-extend!` change the length of a vector in place, taking amortized constant time per
+//! element, so a vector can be used as a stack or a growable array.
+//!
+//! `vector-ref` is a primitive made of the `VectorRef` instruction, like `car`, so calls to it
+//! can be compiled to the instruction itself.
+//!
+//! A slice is a view of a range of the elements of a vector, which shares them rather than copying
+//! them, so algorithms can be passed part of a vector. A slice value is an index into the slice
+//! table of the machine, which keeps the slice and the vector it views alive for as long as the
+//! slice can be reached. A collection frees the rest, so slicing on every call of a recursion
+//! doesn't grow the table. The procedures which read vectors also accept slices, with indices
+//! counted from the start of the slice. A slice of a vector which has since shrunk ends where the
+//! vector does.
+
+use {Value, VM};
+
+use std::mem;
+use std::ops::Range;
+
+/// A view of the elements of `vector` from `start` up to `end`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Slice {
+    pub(crate) vector: Value,
+    start: usize,
+    end: usize,
+}
+
+/// The vector `v` views and the range of its elements, whether `v` is a vector or a slice.
+pub(crate) fn range(vm: &VM, name: &str, v: Value) -> Result<(Value, Range<usize>), String> {
+    if v.is_vec() {
+        return Ok((v, 0..length(v)));
+    } else if v.is_slice() {
+        if let Some(s) = vm.slices.get(v.to_slice()) {
+            // Elements past the end of the vector are not part of the slice
+            let end = s.end.min(length(s.vector));
+            return Ok((s.vector, s.start.min(end)..end));
+        }
+    }
+    Err(format!("{}: {} is not a vector", name, v))
+}
+
+fn length(vector: Value) -> usize {
+    let v = vector.to_vec();
+    let len = v.vec.len();
+    Box::into_raw(v);
+    len
+}
+
+/// The `i`th element of `vector`, read afresh in case a procedure has changed the vector.
+pub(crate) fn element(vector: Value, i: usize) -> Option<Value> {
+    let v = vector.to_vec();
+    let e = v.vec.get(i).copied();
+    Box::into_raw(v);
+    e
+}
+
+fn vector_arg(name: &str, v: Value) -> Result<Value, String> {
+    if v.is_vec() {
+        Ok(v)
+    } else {
+        Err(format!("{}: {} is not a vector", name, v))
+    }
+}
+
+/// The index `k` of a sequence of `len` elements, which may be `len` itself if `end` is set.
+fn index_arg(name: &str, k: Value, len: usize, end: bool) -> Result<usize, String> {
+    if k.is_integer() && k.to_integer() >= 0 {
+        let k = k.to_integer() as usize;
+        if k < len || (end && k == len) {
+            return Ok(k);
+        }
+    }
+    Err(format!("{}: {} is not a valid index", name, k))
+}
+
+/// `(vector obj ...)` A newly allocated vector of the arguments.
+pub fn vector(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Vec(args.to_vec()))
+}
+
+/// `(vector? obj)`
+pub fn is_vector(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(args[0].is_vec()))
+}
+
+/// `(vector-length vector)` The number of elements in `vector`.
+pub fn vector_length(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let (_, range) = range(vm, "vector-length", args[0])?;
+    Ok(Value::Integer(range.len() as i32))
+}
+
+/// `(vector-push! vector obj)` Add `obj` to the end of `vector`.
+pub fn vector_push(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let mut v = vector_arg("vector-push!", args[0])?.to_vec();
+    v.vec.push(args[1]);
+    Box::into_raw(v);
+    Ok(Value::Void)
+}
+
+/// `(vector-pop! vector)` Remove the last element of `vector` and return it.
+pub fn vector_pop(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let mut v = vector_arg("vector-pop!", args[0])?.to_vec();
+    let last = v.vec.pop();
+    Box::into_raw(v);
+    last.ok_or_else(|| format!("vector-pop!: {} is empty", args[0]))
+}
+
+/// `(vector-extend! vector elements)` Add the elements of the vector, slice, or list `elements` to
+/// the end of `vector`, in order.
+pub fn vector_extend(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "vector-extend!";
+    let vector = vector_arg(name, args[0])?;
+    let mut elements = vec![];
+    if args[1].is_pair() || args[1].is_nil() {
+        let mut l = args[1];
+        while l.is_pair() {
+            elements.push(l.car());
+            l = l.cdr();
+        }
+        if !l.is_nil() {
+            return Err(format!("{}: {} is not a proper list", name, args[1]));
+        }
+    } else {
+        let (from, range) = range(vm, name, args[1])?;
+        let v = from.to_vec();
+        elements.extend_from_slice(&v.vec[range]);
+        Box::into_raw(v);
+    }
+    vm.check_allocation(name, elements.len() * mem::size_of::<Value>())?;
+    let mut v = vector.to_vec();
+    v.vec.extend(elements);
+    Box::into_raw(v);
+    Ok(Value::Void)
+}
+
+/// `(vector-slice vector [start [end]])` A slice of the elements of `vector` from `start` up to
+/// `end`, by default all of them. Slicing a slice gives a slice of the same vector.
+pub fn vector_slice(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "vector-slice";
+    let (vector, range) = range(vm, name, args[0])?;
+    let start = match args.get(1) {
+        Some(&k) => index_arg(name, k, range.len(), true)?,
+        None => 0,
+    };
+    let end = match args.get(2) {
+        Some(&k) => index_arg(name, k, range.len(), true)?,
+        None => range.len(),
+    };
+    if end < start {
+        return Err(format!("{}: {} is before the start {}", name, end, start));
+    }
+    let slice = vm.slices.insert(Slice { vector, start: range.start + start, end: range.start + end });
+    Ok(Value::Slice(slice))
+}
+
+/// `(vector-slice? obj)`
+pub fn is_vector_slice(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(args[0].is_slice()))
+}
+
+/// `(vector-slice->vector slice)` A newly allocated vector of the elements of `slice`.
+pub fn vector_slice_to_vector(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let (vector, range) = range(vm, "vector-slice->vector", args[0])?;
+    let v = vector.to_vec();
+    let elements = v.vec.get(range).map(<[Value]>::to_vec).unwrap_or_default();
+    Box::into_raw(v);
+    Ok(Value::Vec(elements))
+}
+
+/// `(vector-index pred vector)` The index of the first element of `vector` which satisfies
+/// `pred`, or `#f` if none does.
+pub fn vector_index(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let pred = args[0];
+    let (vector, range) = range(vm, "vector-index", args[1])?;
+    for (k, i) in range.enumerate() {
+        let e = match element(vector, i) {
+            Some(e) => e,
+            None => break,
+        };
+        if !vm.apply(pred, &[e])?.is_false() {
+            return Ok(Value::Integer(k as i32));
+        }
+    }
+    Ok(Value::False)
+}
+
+/// `(vector-binary-search vector value cmp)` The index of an element of the sorted `vector` for
+/// which `(cmp element value)` is zero, or `#f` if there is none. `cmp` returns a negative number
+/// if `element` comes before `value` and a positive number if it comes after.
+pub fn vector_binary_search(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = "vector-binary-search";
+    let (vector, range) = range(vm, name, args[0])?;
+    let (value, cmp) = (args[1], args[2]);
+    let (mut low, mut high) = (range.start, range.end);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let e = match element(vector, mid) {
+            Some(e) => e,
+            None => return Err(format!("{}: {} changed while it was searched", name, args[0])),
+        };
+        let order = vm.apply(cmp, &[e, value])?;
+        let sign = if order.is_integer() {
+            order.to_integer().signum()
+        } else if order.is_float() && !order.to_float().is_nan() {
+            order.to_float().partial_cmp(&0.0).unwrap() as i32
+        } else {
+            return Err(format!("{}: {} is not a number", name, order));
+        };
+        match sign {
+            0 => return Ok(Value::Integer((mid - range.start) as i32)),
+            s if s < 0 => low = mid + 1,
+            _ => high = mid,
+        }
+    }
+    Ok(Value::False)
+}
@@ -0,0 +1,147 @@
+//! Watching files for changes, enabled by the `watch` feature.
+//!
+//! `(watch-path path proc)` calls `(proc event file)` whenever a file at or under `path` is
+//! created, modified or removed, with `event` the symbol `created`, `modified` or `removed`. Like
+//! timers, watches are checked while the program waits, in `sleep` or `run_timers`, so a script
+//! which watches a path and then returns keeps running until the watch is removed:
+//!
+//! ```scheme
+//! (watch-path "src" (lambda (event file) (display file) (newline)))
+//! ```
+//!
+//! Changes are found by comparing the modification time and size of each file with those seen
+//! the last time the path was checked, by default every half second, so a file changed twice in
+//! that time gives one event.
+
+use {Value, VM};
+use timer::seconds_arg;
+
+use string_interner::get_symbol;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const INTERVAL: Duration = Duration::from_millis(500);
+
+// What is compared to tell whether a file changed
+type Stamp = (Option<SystemTime>, u64);
+
+/// A path being watched.
+#[derive(Debug)]
+pub(crate) struct Watch {
+    path: PathBuf,
+    pub(crate) proc: Value,
+    interval: Duration,
+    // When the path is next checked
+    pub(crate) due: Instant,
+    files: HashMap<PathBuf, Stamp>,
+}
+
+/// The files at or under `path`, without following links.
+fn scan(path: &Path, files: &mut HashMap<PathBuf, Stamp>) {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(_) => return,
+    };
+    if meta.is_dir() {
+        for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+            scan(&entry.path(), files);
+        }
+    } else {
+        files.insert(path.to_path_buf(), (meta.modified().ok(), meta.len()));
+    }
+}
+
+impl Watch {
+    /// The changes since the path was last checked, in order of the files' paths.
+    fn changes(&mut self) -> Vec<(&'static str, PathBuf)> {
+        let mut files = HashMap::new();
+        scan(&self.path, &mut files);
+        let removed = self.files.keys().filter(|path| !files.contains_key(*path));
+        let mut changes: Vec<(&'static str, PathBuf)> = files.iter()
+            .filter_map(|(path, stamp)| match self.files.get(path) {
+                None => Some(("created", path.clone())),
+                Some(old) if old != stamp => Some(("modified", path.clone())),
+                Some(_) => None,
+            })
+            .chain(removed.map(|path| ("removed", path.clone())))
+            .collect();
+        changes.sort_by(|a, b| a.1.cmp(&b.1));
+        self.files = files;
+        changes
+    }
+}
+
+impl VM {
+    /// Check the watched paths which are due, and call their procedures with the changes. Returns
+    /// whether there were any.
+    pub(crate) fn poll_watches(&mut self, now: Instant) -> Result<bool, String> {
+        let mut changed = false;
+        for i in 0..self.watches.len() {
+            let changes = match &mut self.watches[i] {
+                Some(w) if w.due <= now => {
+                    w.due = now + w.interval;
+                    w.changes()
+                }
+                _ => continue,
+            };
+            for (event, path) in changes {
+                changed = true;
+                // An earlier call may have removed the watch
+                let proc = match &self.watches[i] {
+                    Some(w) => w.proc,
+                    None => break,
+                };
+                let path = Value::String(path.to_string_lossy().into_owned());
+                self.apply(proc, &[Value::Symbol(get_symbol(event.to_string())), path])?;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// When the next watched path is to be checked.
+    pub(crate) fn next_watch(&self) -> Option<Instant> {
+        self.watches.iter().flatten().map(|w| w.due).min()
+    }
+}
+
+/// `(watch-path path proc [seconds])` Call `(proc event file)` for each change to the files at or
+/// under `path`, checking for them every `seconds`. Returns a watch to give to `unwatch-path`.
+pub fn watch_path(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if !args[0].is_string() {
+        return Err(format!("watch-path: {} is not a string", args[0]));
+    }
+    let s = args[0].to_string();
+    let path = PathBuf::from(&s.str);
+    Box::into_raw(s);
+    if !args[1].is_procedure() {
+        return Err(format!("watch-path: {} is not a procedure", args[1]));
+    }
+    let interval = match args.get(2) {
+        Some(&v) => seconds_arg("watch-path", v)?,
+        None => INTERVAL,
+    };
+    if let Err(e) = fs::symlink_metadata(&path) {
+        return Err(format!("watch-path: could not watch {}: {}", args[0], e));
+    }
+
+    let mut files = HashMap::new();
+    scan(&path, &mut files);
+    let watch = Watch { path, proc: args[1], interval, due: vm.now() + interval, files };
+    vm.watches.push(Some(watch));
+    Ok(Value::Integer(vm.watches.len() as i32 - 1))
+}
+
+/// `(unwatch-path watch)` Stop watching the path of `watch`.
+pub fn unwatch_path(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let watch = args[0];
+    if watch.is_integer() && watch.to_integer() >= 0 {
+        if let Some(w @ Some(_)) = vm.watches.get_mut(watch.to_integer() as usize) {
+            *w = None;
+            return Ok(Value::Void);
+        }
+    }
+    Err(format!("unwatch-path: {} is not a watch", watch))
+}
@@ -0,0 +1,361 @@
+//! Conversion between XML and SXML.
+//!
+//! An element is a list of its name, an optional attribute list `(@ (name "value") ...)`, and its
+//! children. Text is a string, comments are `(*COMMENT* "text")`, processing instructions are
+//! `(*PI* target "text")`, and the document is `(*TOP* node ...)`.
+//!
+//! The parser is lenient enough for HTML: tags are matched case-insensitively, void elements such as `br`
+//! need no closing tag, unclosed elements are closed by their parent's closing tag, and stray
+//! closing tags are ignored.
+
+use {Value, VM};
+use port::read_to_string;
+
+use string_interner::get_value;
+
+const VOID_ELEMENTS: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link",
+                                 "meta", "param", "source", "track", "wbr"];
+
+fn is_void(name: &str) -> bool {
+    VOID_ELEMENTS.iter().any(|e| e.eq_ignore_ascii_case(name))
+}
+
+fn symbol(s: &str) -> Value {
+    Value::Symbol(VM::intern_symbol(s.to_string()))
+}
+
+fn list(values: Vec<Value>) -> Value {
+    values.into_iter().rev().fold(Value::Nil, |l, v| Value::Pair(v, l))
+}
+
+struct Element {
+    name: String,
+    children: Vec<Value>,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consume up to and including `end`, returning the text before it.
+    fn until(&mut self, end: &str) -> &'a str {
+        let rest = self.rest();
+        match rest.find(end) {
+            Some(i) => {
+                self.pos += i + end.len();
+                &rest[..i]
+            }
+            None => {
+                self.pos = self.input.len();
+                rest
+            }
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let rest = self.rest();
+        let len = rest.find(|c: char| c.is_whitespace() || "/>=\"'<".contains(c)).unwrap_or(rest.len());
+        self.pos += len;
+        rest[..len].to_string()
+    }
+
+    fn attributes(&mut self) -> Vec<Value> {
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().is_empty() || self.rest().starts_with('>') || self.rest().starts_with("/>") {
+                return attributes;
+            }
+            let name = self.name();
+            if name.is_empty() {
+                // Skip a character we can't make sense of
+                self.pos += self.rest().chars().next().unwrap().len_utf8();
+                continue;
+            }
+            self.skip_whitespace();
+            let value = if self.eat("=") {
+                self.skip_whitespace();
+                if self.eat("\"") {
+                    decode(self.until("\""))
+                } else if self.eat("'") {
+                    decode(self.until("'"))
+                } else {
+                    let rest = self.rest();
+                    let len = rest.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(rest.len());
+                    self.pos += len;
+                    decode(&rest[..len])
+                }
+            } else {
+                // A boolean attribute, as in HTML
+                name.clone()
+            };
+            attributes.push(list(vec![symbol(&name), Value::String(value)]));
+        }
+    }
+
+    fn parse(&mut self) -> Value {
+        let mut stack = vec![Element { name: "*TOP*".to_string(), children: Vec::new() }];
+        while !self.rest().is_empty() {
+            if self.eat("<!--") {
+                let text = self.until("-->");
+                let comment = list(vec![symbol("*COMMENT*"), Value::String(text.to_string())]);
+                stack.last_mut().unwrap().children.push(comment);
+            } else if self.eat("<![CDATA[") {
+                let text = self.until("]]>");
+                stack.last_mut().unwrap().children.push(Value::String(text.to_string()));
+            } else if self.eat("<!") {
+                // A doctype or other declaration
+                self.until(">");
+            } else if self.eat("<?") {
+                let target = self.name();
+                self.skip_whitespace();
+                let text = self.until("?>");
+                let pi = list(vec![symbol("*PI*"), symbol(&target), Value::String(text.trim_end().to_string())]);
+                stack.last_mut().unwrap().children.push(pi);
+            } else if self.eat("</") {
+                let name = self.name();
+                self.until(">");
+                // Close everything up to the matching element, ignoring a stray closing tag
+                if let Some(i) = stack.iter().rposition(|e| e.name.eq_ignore_ascii_case(&name)) {
+                    if i > 0 {
+                        while stack.len() > i {
+                            close(&mut stack);
+                        }
+                    }
+                }
+            } else if self.rest().starts_with('<')
+                && self.rest()[1..].starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                self.pos += 1;
+                let name = self.name();
+                let attributes = self.attributes();
+                let self_closing = self.eat("/>");
+                if !self_closing {
+                    self.eat(">");
+                }
+                let empty = self_closing || is_void(&name);
+
+                let mut children = Vec::new();
+                if !attributes.is_empty() {
+                    let mut a = vec![symbol("@")];
+                    a.extend(attributes);
+                    children.push(list(a));
+                }
+                stack.push(Element { name: name, children: children });
+                if empty {
+                    close(&mut stack);
+                }
+            } else {
+                let rest = self.rest();
+                // A lone '<' is text
+                let first = rest.chars().next().unwrap().len_utf8();
+                let len = rest[first..].find('<').map(|i| i + first).unwrap_or(rest.len());
+                self.pos += len;
+                let text = decode(&rest[..len]);
+                // Whitespace between elements is not content
+                if !text.trim().is_empty() {
+                    stack.last_mut().unwrap().children.push(Value::String(text));
+                }
+            }
+        }
+        while stack.len() > 1 {
+            close(&mut stack);
+        }
+        let top = stack.pop().unwrap();
+        let mut top_list = vec![symbol("*TOP*")];
+        top_list.extend(top.children);
+        list(top_list)
+    }
+}
+
+fn close(stack: &mut Vec<Element>) {
+    let e = stack.pop().unwrap();
+    let mut element = vec![symbol(&e.name)];
+    element.extend(e.children);
+    stack.last_mut().unwrap().children.push(list(element));
+}
+
+/// Replace character and entity references.
+fn decode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let end = match rest.find(';') {
+            Some(end) if end < 12 => end,
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{A0}'),
+            _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(std::char::from_u32)
+            }
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(std::char::from_u32),
+            _ => None,
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn string_value(v: Value) -> Option<String> {
+    if v.is_string() {
+        let s = v.to_string();
+        let r = s.str.clone();
+        Box::into_raw(s);
+        Some(r)
+    } else if v.is_symbol() {
+        get_value(v.to_symbol())
+    } else if v.is_char() {
+        Some(v.to_char().to_string())
+    } else if v.is_integer() || v.is_float() {
+        Some(format!("{}", v))
+    } else {
+        None
+    }
+}
+
+fn write_node(v: Value, out: &mut String) -> Result<(), String> {
+    if let Some(s) = string_value(v) {
+        escape(&s, out);
+        return Ok(());
+    } else if v.is_nil() {
+        return Ok(());
+    } else if !v.is_pair() || !v.car().is_symbol() {
+        return Err(format!("sxml->xml: {} is not an SXML node", v));
+    }
+
+    let name = get_value(v.car().to_symbol()).unwrap();
+    let mut children = v.cdr();
+    match &*name {
+        "*TOP*" => (),
+        "*COMMENT*" => {
+            out.push_str("<!--");
+            while children.is_pair() {
+                out.push_str(&string_value(children.car()).unwrap_or_default());
+                children = children.cdr();
+            }
+            out.push_str("-->");
+            return Ok(());
+        }
+        "*PI*" => {
+            out.push_str("<?");
+            while children.is_pair() {
+                out.push_str(&string_value(children.car()).unwrap_or_default());
+                children = children.cdr();
+                if children.is_pair() {
+                    out.push(' ');
+                }
+            }
+            out.push_str("?>");
+            return Ok(());
+        }
+        _ => {
+            out.push('<');
+            out.push_str(&name);
+            if children.is_pair() && children.car().is_pair()
+                && string_value(children.car().car()).as_ref().map(String::as_str) == Some("@") {
+                let mut attributes = children.car().cdr();
+                while attributes.is_pair() {
+                    let a = attributes.car();
+                    let key = if a.is_pair() { string_value(a.car()) } else { None };
+                    let key = key.ok_or_else(|| format!("sxml->xml: {} is not an attribute", a))?;
+                    out.push(' ');
+                    out.push_str(&key);
+                    if a.cdr().is_pair() {
+                        out.push_str("=\"");
+                        escape(&string_value(a.cdr().car()).unwrap_or_default(), out);
+                        out.push('"');
+                    }
+                    attributes = attributes.cdr();
+                }
+                children = children.cdr();
+            }
+            if children.is_nil() {
+                out.push_str(if is_void(&name) { ">" } else { "/>" });
+                return Ok(());
+            }
+            out.push('>');
+        }
+    }
+
+    while children.is_pair() {
+        write_node(children.car(), out)?;
+        children = children.cdr();
+    }
+    if name != "*TOP*" {
+        out.push_str("</");
+        out.push_str(&name);
+        out.push('>');
+    }
+    Ok(())
+}
+
+/// `(xml->sxml string-or-port)` Parse an XML or HTML document into SXML.
+pub fn xml_to_sxml(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = if args[0].is_string() {
+        string_value(args[0]).unwrap()
+    } else {
+        read_to_string(vm, "xml->sxml", args.first())?
+    };
+    Ok(Parser { input: &text, pos: 0 }.parse())
+}
+
+/// `(sxml->xml node)` Write an SXML node as an XML string.
+pub fn sxml_to_xml(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let mut out = String::new();
+    write_node(args[0], &mut out)?;
+    Ok(Value::String(out))
+}
@@ -1,5 +1,8 @@
 extern crate vm;
 
+mod common;
+
+use common::HEAP;
 use vm::*;
 
 /*
@@ -46,3 +49,37 @@ fn assemble_iterative_factorial() {
     assert_eq!(expected_code, code);
 }
 */
+
+#[test]
+fn shared_constants() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let list = |s: &str| Value::Pair(Value::String(s.to_string()), Value::Nil);
+    let (code, consts) = assemble(vec![
+        ASM::LoadConst(Register(1), list("a")),
+        ASM::LoadConst(Register(2), list("a")),
+        ASM::LoadConst(Register(3), Value::Integer(1)),
+        ASM::LoadConst(Register(4), Value::Integer(1)),
+        ASM::LoadConst(Register(5), list("b")),
+        ASM::MakeClosure(Register(0), Box::new(vec![ASM::LoadConst(Register(0), list("a")), ASM::Return])),
+    ]);
+    // A constant loaded twice is stored once, and equal ones are the same object
+    assert_eq!(vec![
+        Operation::LoadConst(Register(1), 0),
+        Operation::LoadConst(Register(2), 0),
+        Operation::LoadConst(Register(3), 1),
+        Operation::LoadConst(Register(4), 1),
+        Operation::LoadConst(Register(5), 2),
+    ], &code[..5]);
+    assert_eq!(4, consts.len());
+    let lambda = consts[3].to_lambda();
+    assert_eq!(consts[0], lambda.consts[0]);
+    Box::into_raw(lambda);
+
+    // Code assembled with the same pool shares its constants too
+    let mut pool = ConstantPool::new();
+    let (_, a) = assemble_pooled(vec![ASM::LoadConst(Register(0), list("a"))], &mut pool);
+    let (_, b) = assemble_pooled(vec![ASM::LoadConst(Register(0), list("a"))], &mut pool);
+    let (_, c) = assemble(vec![ASM::LoadConst(Register(0), list("a"))]);
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+}
@@ -0,0 +1,35 @@
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use vm::*;
+
+#[test]
+fn characters() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    assert_eq!(Value::True, call(&mut vm, "char?", &[Value::Char('a')]));
+    assert_eq!(Value::False, call(&mut vm, "char?", &[Value::Integer(97)]));
+    assert_eq!(Value::Integer(97), call(&mut vm, "char->integer", &[Value::Char('a')]));
+    assert_eq!(Value::Char('a'), call(&mut vm, "integer->char", &[Value::Integer(97)]));
+    assert_eq!(Value::Char('Ä'), call(&mut vm, "char-upcase", &[Value::Char('ä')]));
+    assert_eq!(Value::Char('z'), call(&mut vm, "char-downcase", &[Value::Char('Z')]));
+    assert_eq!(Value::Char('1'), call(&mut vm, "char-upcase", &[Value::Char('1')]));
+    // The upper case of ß is two characters
+    assert_eq!(Value::Char('ß'), call(&mut vm, "char-upcase", &[Value::Char('ß')]));
+
+    call(&mut vm, "integer->char", &[Value::Integer(0xD800)]);
+    assert_eq!("Exception in integer->char: 55296 is not a code point", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "char->integer", &[Value::Integer(97)]);
+    assert_eq!("Exception in char->integer: 97 is not a character", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn written_characters() {
+    assert_eq!("#\\a", format!("{}", Value::Char('a')));
+    assert_eq!("#\\space", format!("{}", Value::Char(' ')));
+    assert_eq!("#\\delete", format!("{}", Value::Char('\x7f')));
+    assert_eq!("#\\x85", format!("{}", Value::Char('\u{85}')));
+}
@@ -0,0 +1,26 @@
+//! Helpers shared by the tests. Each test file uses only some of them.
+#![allow(dead_code)]
+
+use vm::*;
+
+use std::sync::Mutex;
+
+/// The heap is shared by every machine, so tests which allocate must not run concurrently.
+/// A test which panics while holding it poisons it, so take it with
+/// `HEAP.lock().unwrap_or_else(|e| e.into_inner())` to keep one failure from failing the rest.
+pub static HEAP: Mutex<()> = Mutex::new(());
+
+/// Call the procedure bound to `name` in the environment of `vm` with `args`.
+pub fn call(vm: &mut VM, name: &str, args: &[Value]) -> Value {
+    let mut code = Vec::new();
+    for (i, &arg) in args.iter().enumerate() {
+        code.push(ASM::LoadConst(Register(i as u8 + 1), arg));
+    }
+    code.push(ASM::LoadConst(Register(0), Value::Symbol(VM::intern_symbol(name.to_string()))));
+    code.push(ASM::Lookup(Register(0), Register(0)));
+    code.push(ASM::Call(Register(0), args.len()));
+    let (code, consts) = assemble(code);
+    vm.load_code(code, consts);
+    vm.run();
+    vm.load_register(Register(0))
+}
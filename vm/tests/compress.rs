@@ -0,0 +1,32 @@
+#![cfg(feature = "compress")]
+
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+#[test]
+fn round_trip() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let text = "abcabcabcabcabcabcabcabcabcabc";
+    for &(compress, decompress) in &[("gzip-compress", "gzip-decompress"),
+                                     ("deflate-compress", "deflate-decompress")] {
+        let compressed = call(&mut vm, compress, &[Value::String(text.to_string()), Value::Integer(9)]);
+        assert!(compressed.is_bytevector());
+        // Keep the result alive between calls
+        env.define_variable(get_symbol("compressed".to_string()), compressed);
+        let expected: Vec<String> = text.bytes().map(|b| b.to_string()).collect();
+        assert_eq!(format!("#u8({})", expected.join(" ")),
+                   format!("{}", call(&mut vm, decompress, &[compressed])));
+    }
+
+    call(&mut vm, "gzip-decompress", &[Value::Bytevector(vec![1, 2, 3])]);
+    assert!(format!("{}", vm.condition().unwrap()).starts_with("Exception in gzip-decompress: "));
+}
@@ -0,0 +1,83 @@
+extern crate string_interner;
+extern crate vm;
+
+use string_interner::get_symbol;
+use vm::*;
+
+fn lookup_code(name: &str) -> (Vec<Operation>, Vec<Value>) {
+    assemble(vec![
+        ASM::LoadConst(Register(1), Value::Symbol(get_symbol(name.to_string()))),
+        ASM::Lookup(Register(0), Register(1)),
+        ASM::LoadConst(Register(2), Value::Integer(1)),
+        ASM::Add(Register(0), Register(0), Register(2)),
+    ])
+}
+
+#[test]
+fn unbound_variable_suspends() {
+    let mut vm = VM::new();
+    let (code, consts) = lookup_code("condition-unbound");
+    vm.load_code(code, consts);
+    vm.run();
+
+    assert_eq!(1, vm.condition_depth());
+    let restarts = vm.condition().unwrap().restarts();
+    assert_eq!(vec![Restart::Abort, Restart::Retry, Restart::UseValue(Value::Void)], restarts);
+
+    assert!(vm.invoke_restart(Restart::Abort));
+    assert_eq!(0, vm.condition_depth());
+    assert_eq!(0, vm.stack_size());
+}
+
+#[test]
+fn retry_after_define() {
+    let mut vm = VM::new();
+    let env = Environment::new();
+    vm.assign_environment(env.clone());
+    let (code, consts) = lookup_code("condition-retry");
+    vm.load_code(code, consts);
+    vm.run();
+    assert_eq!(1, vm.condition_depth());
+
+    env.define_variable(get_symbol("condition-retry".to_string()), Value::Integer(41));
+    assert!(vm.invoke_restart(Restart::Retry));
+    vm.run();
+
+    assert_eq!(0, vm.condition_depth());
+    assert_eq!(Value::Integer(42), vm.load_register(Register(0)));
+}
+
+#[test]
+fn use_value() {
+    let mut vm = VM::new();
+    let (code, consts) = lookup_code("condition-use-value");
+    vm.load_code(code, consts);
+    vm.run();
+
+    assert!(vm.invoke_restart(Restart::UseValue(Value::Integer(9))));
+    vm.run();
+    assert_eq!(Value::Integer(10), vm.load_register(Register(0)));
+}
+
+#[test]
+fn handler_chooses_restart() {
+    let mut vm = VM::new();
+    vm.set_condition_handler(|_| Some(Restart::UseValue(Value::Integer(1))));
+    let (code, consts) = lookup_code("condition-handler");
+    vm.load_code(code, consts);
+    vm.run();
+
+    assert_eq!(0, vm.condition_depth());
+    assert_eq!(Value::Integer(2), vm.load_register(Register(0)));
+}
+
+#[test]
+fn not_a_primitive() {
+    let mut vm = VM::new();
+    let (code, consts) = assemble(vec![ASM::CallPrimitive(Register(0), Instruction::Add)]);
+    vm.load_code(code, consts);
+    vm.run();
+
+    assert_eq!(1, vm.condition_depth());
+    assert_eq!("Exception in callprimitive: Add is not a primitive", format!("{}", vm.condition().unwrap()));
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "config")]
+
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+fn symbol(s: &str) -> Value {
+    Value::Symbol(get_symbol(s.to_string()))
+}
+
+// Parse `text` with `reader` and return the value at `path` through nested tables.
+fn read(reader: &str, text: &str, path: &[&str]) -> String {
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let port = call(&mut vm, "open-input-string", &[Value::String(text.to_string())]);
+    let mut v = call(&mut vm, reader, &[port]);
+    // Keep the document alive between calls
+    env.define_variable(get_symbol("document".to_string()), v);
+    for key in path {
+        v = call(&mut vm, "hash-table-ref", &[v, symbol(key)]);
+    }
+    format!("{}", v)
+}
+
+#[test]
+fn toml() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let text = "title = \"example\"\n[server]\nports = [80, 443]\nratio = 0.5\nenabled = true\n";
+    assert_eq!("\"example\"", read("toml-read", text, &["title"]));
+    assert_eq!("#(80 443)", read("toml-read", text, &["server", "ports"]));
+    assert_eq!("0.5", read("toml-read", text, &["server", "ratio"]));
+    assert_eq!("#t", read("toml-read", text, &["server", "enabled"]));
+}
+
+#[test]
+fn yaml() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let text = "name: example\nserver:\n  ports: [80, 443]\n  ratio: 0.5\n  proxy: ~\n";
+    assert_eq!("\"example\"", read("yaml-read", text, &["name"]));
+    assert_eq!("#(80 443)", read("yaml-read", text, &["server", "ports"]));
+    assert_eq!("0.5", read("yaml-read", text, &["server", "ratio"]));
+    assert_eq!("()", read("yaml-read", text, &["server", "proxy"]));
+}
+
+#[test]
+fn parse_error() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let port = call(&mut vm, "open-input-string", &[Value::String("a = ".to_string())]);
+    call(&mut vm, "toml-read", &[port]);
+    assert!(format!("{}", vm.condition().unwrap()).starts_with("Exception in toml-read: "));
+}
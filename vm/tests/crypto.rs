@@ -0,0 +1,42 @@
+#![cfg(feature = "crypto")]
+
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use vm::*;
+
+#[test]
+fn digests() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    assert_eq!("\"900150983cd24fb0d6963f7d28e17f72\"", format!("{}", call(&mut vm, "md5", &[Value::String("abc".to_string())])));
+    assert_eq!("\"a9993e364706816aba3e25717850c26c9cd0d89d\"",
+               format!("{}", call(&mut vm, "sha1", &[Value::Bytevector(b"abc".to_vec())])));
+    assert_eq!("\"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\"",
+               format!("{}", call(&mut vm, "sha256", &[Value::String("abc".to_string())])));
+    // RFC 4231 test case 2
+    let key = Value::String("Jefe".to_string());
+    let data = Value::String("what do ya want for nothing?".to_string());
+    assert_eq!("\"5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843\"",
+               format!("{}", call(&mut vm, "hmac-sha256", &[key, data])));
+
+    call(&mut vm, "sha256", &[Value::Integer(1)]);
+    assert_eq!("Exception in sha256: 1 is not a bytevector or string", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn value_digests() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    // The SHA-256 digest of the string type, the length 3 in 8 bytes, and "abc"
+    assert_eq!("\"cc7dbd14f935eec2f5841fc0b15b7c5444fd7bc65df290e029b422eba85fa289\"",
+               format!("{}", call(&mut vm, "value-digest", &[Value::String("abc".to_string())])));
+    let a = Value::Pair(Value::Integer(1), Value::Nil);
+    let b = Value::Pair(Value::Integer(1), Value::Nil);
+    assert_eq!(value_digest(a), value_digest(b));
+    assert_ne!(value_digest(a), value_digest(Value::Integer(1)));
+}
@@ -0,0 +1,82 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+use vm::disasm::listing;
+
+fn label(name: &str) -> string_interner::Symbol {
+    get_symbol(name.to_string())
+}
+
+#[test]
+fn listings() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let inner = vec![
+        ASM::LoadConst(Register(1), Value::Integer(1)),
+        ASM::Add(Register(0), Register(1), Register(1)),
+        ASM::Return,
+    ];
+    let (code, consts) = assemble(vec![
+        ASM::LoadConst(Register(1), Value::Bool(true)),
+        ASM::GotoIfNot(GotoValue::Label(label("else")), Register(1)),
+        ASM::MakeClosure(Register(0), Box::new(inner)),
+        ASM::Goto(GotoValue::Label(label("end"))),
+        ASM::Label(label("else")),
+        ASM::JumpTable(Register(1), 3, vec![label("end"), label("else")], label("end")),
+        ASM::PerfectHash(Register(1), vec![(label("a"), label("end"))], label("else")),
+        ASM::Label(label("end")),
+    ]);
+    let listing = listing(&code, &consts);
+    let lines: Vec<_> = listing.lines().collect();
+    assert_eq!(vec![
+        "    0  LOADCONST X1, #0                ; #t",
+        "    1  GOTOIFNOT L0, X1",
+        "    2  MAKECLOSURE X0, #1              ; procedure #1",
+        "    3  GOTO L1",
+        "L0:",
+        "    4  JUMPTABLE X1, #2                ; 3 => L1, 4 => L0, else => L1",
+        "    5  PHASH X1, #3                    ; a => L1, else => L0",
+        "L1:",
+        "constants:",
+        "  #0: #t",
+        "  #1: #<procedure>",
+    ], &lines[..11]);
+    assert_eq!(vec![
+        "procedure #1:",
+        "      0  LOADCONST X1, #0                ; 1",
+        "      1  ADD X0, X1, X1",
+        "      2  RETURN",
+        "  constants:",
+        "    #0: 1",
+    ], &lines[13..]);
+}
+
+#[test]
+fn disassemble() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let (code, consts) = assemble(vec![
+        ASM::MakeClosure(Register(0), Box::new(vec![ASM::Move(Register(0), Register(1)), ASM::Return])),
+    ]);
+    vm.load_code(code, consts);
+    vm.run();
+    let identity = vm.load_register(Register(0));
+    // Keep the values alive between calls
+    env.define_variable(label("identity"), identity);
+    let port = call(&mut vm, "open-output-string", &[]);
+    env.define_variable(label("port"), port);
+
+    call(&mut vm, "disassemble", &[identity, port]);
+    let s = call(&mut vm, "get-output-string", &[port]).to_string();
+    assert_eq!("    0  MOVE X0, X1\n    1  RETURN\n", s.str);
+    Box::into_raw(s);
+
+    call(&mut vm, "disassemble", &[Value::Integer(1)]);
+    assert_eq!("Exception in disassemble: 1 is not a compiled procedure", format!("{}", vm.condition().unwrap()));
+}
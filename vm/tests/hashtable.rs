@@ -0,0 +1,29 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+#[test]
+fn eq_hash() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let a = Value::Pair(Value::Integer(1), Value::Nil);
+    let b = Value::Pair(Value::Integer(1), Value::Nil);
+    // Keep the pairs alive between calls
+    env.define_variable(get_symbol("a".to_string()), a);
+    env.define_variable(get_symbol("b".to_string()), b);
+
+    let hash = call(&mut vm, "eq-hash", &[a]);
+    assert!(hash.is_integer() && hash.to_integer() >= 0);
+    assert_eq!(hash, call(&mut vm, "eq-hash", &[a]));
+    // Collections don't change the hash
+    assert_eq!(hash, call(&mut vm, "object-hash", &[a]));
+    assert_ne!(hash, call(&mut vm, "eq-hash", &[b]));
+    assert_eq!(call(&mut vm, "eq-hash", &[Value::Integer(7)]), call(&mut vm, "eq-hash", &[Value::Integer(7)]));
+}
@@ -0,0 +1,45 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+fn describe(v: Value) -> Vec<String> {
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    // Keep the value alive between calls
+    env.define_variable(get_symbol("value".to_string()), v);
+    let port = call(&mut vm, "open-output-string", &[]);
+    call(&mut vm, "describe", &[v, port]);
+    let s = call(&mut vm, "get-output-string", &[port]).to_string();
+    let lines = s.str.lines().map(String::from).collect();
+    Box::into_raw(s);
+    lines
+}
+
+#[test]
+fn immediates() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    assert_eq!(vec!["42 is a fixnum", "  immediate: 0x7ff040000000002a"], describe(Value::Integer(42)));
+    assert_eq!(vec!["#\\a is a character", "  immediate: 0x7ff0700000000061", "  code point: U+0061"],
+               describe(Value::Char('a')));
+}
+
+#[test]
+fn heap_objects() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let d = describe(Value::Pair(Value::Integer(1), Value::Nil));
+    assert_eq!("(1) is a pair", d[0]);
+    assert!(d[1].starts_with("  address: 0x"));
+    assert_eq!("  size: 32 bytes", d[2]);
+    assert_eq!(vec!["  car: 1", "  cdr: ()"], &d[3..]);
+
+    let d = describe(Value::Vec((0..10).map(Value::Integer).collect()));
+    assert_eq!("  length: 10", d[3]);
+    assert_eq!("  7: 7", d[11]);
+    assert_eq!("  ...", d[12]);
+}
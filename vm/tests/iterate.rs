@@ -0,0 +1,91 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+use std::collections::HashMap;
+
+fn int(i: i32) -> Value {
+    Value::Integer(i)
+}
+
+fn lookup(env: &Environment, name: &str) -> Value {
+    env.lookup_variable_value(get_symbol(name.to_string())).unwrap()
+}
+
+// Bind `v` so that it survives collection.
+fn keep(env: &Environment, name: &str, v: Value) -> Value {
+    env.define_variable(get_symbol(name.to_string()), v);
+    v
+}
+
+#[test]
+fn fold() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let (plus, list) = (lookup(&env, "+"), lookup(&env, "list"));
+
+    let l = keep(&env, "l", call(&mut vm, "list", &[int(1), int(2), int(3)]));
+    assert_eq!(int(6), call(&mut vm, "fold", &[plus, int(0), l]));
+    assert_eq!(int(0), call(&mut vm, "fold", &[plus, int(0), Value::Nil]));
+    let v = keep(&env, "v", Value::Vec(vec![int(4), int(5)]));
+    assert_eq!(int(10), call(&mut vm, "fold", &[plus, int(1), v]));
+    let s = keep(&env, "s", Value::String("ab".to_string()));
+    assert_eq!("(#\\b (#\\a ()))", format!("{}", call(&mut vm, "fold", &[list, Value::Nil, s])));
+
+    let mut m = HashMap::new();
+    m.insert(int(1), int(2));
+    let table = keep(&env, "table", Value::HashMap(m));
+    assert_eq!("(1 2 ())", format!("{}", call(&mut vm, "fold", &[list, Value::Nil, table])));
+
+    let text = keep(&env, "text", Value::String("first\nsecond\n".to_string()));
+    let port = keep(&env, "port", call(&mut vm, "open-input-string", &[text]));
+    assert_eq!("(\"second\" (\"first\" ()))", format!("{}", call(&mut vm, "fold", &[list, Value::Nil, port])));
+    assert_eq!(0, vm.condition_depth());
+}
+
+#[test]
+fn for_each() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let read_char = lookup(&env, "read-char");
+    let text = keep(&env, "text", Value::String("abc".to_string()));
+    let port = keep(&env, "port", call(&mut vm, "open-input-string", &[text]));
+
+    // Read twice from the same port
+    let ports = keep(&env, "ports", Value::Vec(vec![port, port]));
+    assert_eq!(Value::Void, call(&mut vm, "for-each", &[read_char, ports]));
+    assert_eq!(Value::Char('c'), call(&mut vm, "read-char", &[port]));
+    assert_eq!(Value::Void, call(&mut vm, "for-each", &[read_char, Value::Nil]));
+    assert_eq!(0, vm.condition_depth());
+}
+
+#[test]
+fn errors() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let (plus, length) = (lookup(&env, "+"), lookup(&env, "length"));
+
+    call(&mut vm, "fold", &[plus, int(0), int(1)]);
+    assert_eq!("Exception in fold: 1 is not a collection", format!("{}", vm.condition().unwrap()));
+    let improper = keep(&env, "improper", Value::Pair(int(1), int(2)));
+    call(&mut vm, "for-each", &[lookup(&env, "list"), improper]);
+    assert_eq!("Exception in for-each: (1 . 2) is not a proper list", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "for-each", &[length, improper]);
+    assert_eq!("Exception in length: 1 is not a proper list", format!("{}", vm.condition().unwrap()));
+    let l = keep(&env, "l", call(&mut vm, "list", &[int(1)]));
+    call(&mut vm, "fold", &[length, int(0), l]);
+    assert_eq!("Exception in length: expected 1 arguments, got 2", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "for-each", &[int(1), l]);
+    assert_eq!("Exception in apply: attempt to apply non-procedure 1", format!("{}", vm.condition().unwrap()));
+}
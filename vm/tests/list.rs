@@ -0,0 +1,73 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+fn list(values: &[i32]) -> Value {
+    values.iter().rev().fold(Value::Nil, |l, &i| Value::Pair(Value::Integer(i), l))
+}
+
+#[test]
+fn reverse() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let l = list(&[1, 2, 3]);
+    assert_eq!("(3 2 1)", format!("{}", call(&mut vm, "reverse", &[l])));
+    assert_eq!("(1 2 3)", format!("{}", l));
+
+    assert_eq!("(3 2 1)", format!("{}", call(&mut vm, "reverse!", &[l])));
+    // The first pair is now the last
+    assert_eq!("(1)", format!("{}", l));
+}
+
+#[test]
+fn append() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let a = list(&[1, 2]);
+    let b = list(&[3]);
+    assert_eq!("(1 2 3 . 4)", format!("{}", call(&mut vm, "append", &[a, Value::Nil, b, Value::Integer(4)])));
+    assert_eq!("(1 2)", format!("{}", a));
+
+    assert_eq!("(1 2 3)", format!("{}", call(&mut vm, "append!", &[a, Value::Nil, b])));
+    assert_eq!("(1 2 3)", format!("{}", a));
+    assert_eq!(Value::Nil, call(&mut vm, "append!", &[]));
+}
+
+#[test]
+fn improper_list() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    call(&mut vm, "reverse!", &[Value::Pair(Value::Integer(1), Value::Integer(2))]);
+    assert_eq!(1, vm.condition_depth());
+    assert_eq!("Exception in reverse!: (1 . 2) is not a proper list",
+               format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn circular_list() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let l = list(&[1, 2, 3]);
+    // Keep the list alive between calls
+    env.define_variable(get_symbol("circular".to_string()), l);
+    assert_eq!(Value::True, call(&mut vm, "list?", &[l]));
+    assert_eq!(Value::Integer(3), call(&mut vm, "length", &[l]));
+    assert_eq!(Value::False, call(&mut vm, "list?", &[Value::Pair(Value::Nil, Value::Integer(1))]));
+
+    l.cdr().cdr().set_cdr(l);
+    assert_eq!(Value::False, call(&mut vm, "list?", &[l]));
+    call(&mut vm, "length", &[l]);
+    assert_eq!(1, vm.condition_depth());
+    assert_eq!("Exception in length: argument is a circular list",
+               format!("{}", vm.condition().unwrap()));
+}
@@ -0,0 +1,109 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+// Define `name` as the memoized identity procedure, which counts its calls in the car of `calls`.
+fn define_counter(vm: &mut VM, name: &str, limit: Option<i32>) {
+    let body = vec![
+        ASM::LoadConst(Register(2), Value::Symbol(get_symbol("calls".to_string()))),
+        ASM::Lookup(Register(2), Register(2)),
+        ASM::Car(Register(3), Register(2)),
+        ASM::LoadConst(Register(4), Value::Integer(1)),
+        ASM::Add(Register(3), Register(3), Register(4)),
+        ASM::SetCar(Register(2), Register(3)),
+        ASM::Move(Register(0), Register(1)),
+    ];
+    let mut code = vec![ASM::MakeClosure(Register(1), Box::new(body))];
+    let argc = match limit {
+        Some(n) => {
+            code.push(ASM::LoadConst(Register(2), Value::Integer(n)));
+            2
+        }
+        None => 1,
+    };
+    code.extend(vec![
+        ASM::LoadConst(Register(0), Value::Symbol(get_symbol("memoize".to_string()))),
+        ASM::Lookup(Register(0), Register(0)),
+        ASM::Call(Register(0), argc),
+        ASM::LoadConst(Register(1), Value::Symbol(get_symbol(name.to_string()))),
+        ASM::Define(Register(1), Register(0)),
+    ]);
+    let (code, consts) = assemble(code);
+    vm.load_code(code, consts);
+    vm.run();
+}
+
+fn calls(env: &Environment) -> Value {
+    env.lookup_variable_value(get_symbol("calls".to_string())).unwrap().car()
+}
+
+#[test]
+fn memoize() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    env.define_variable(get_symbol("calls".to_string()), Value::Pair(Value::Integer(0), Value::Nil));
+    define_counter(&mut vm, "f", None);
+
+    assert_eq!(Value::Integer(5), call(&mut vm, "f", &[Value::Integer(5)]));
+    assert_eq!(Value::Integer(5), call(&mut vm, "f", &[Value::Integer(5)]));
+    assert_eq!(Value::Integer(1), calls(&env));
+
+    // Arguments are compared with equal?
+    let a = Value::Pair(Value::Integer(1), Value::Pair(Value::String("a".to_string()), Value::Nil));
+    let b = Value::Pair(Value::Integer(1), Value::Pair(Value::String("a".to_string()), Value::Nil));
+    env.define_variable(get_symbol("a".to_string()), a);
+    env.define_variable(get_symbol("b".to_string()), b);
+    assert_eq!(a, call(&mut vm, "f", &[a]));
+    // The cached result is the first list
+    assert_eq!(a, call(&mut vm, "f", &[b]));
+    assert_eq!(Value::Integer(2), calls(&env));
+    let f = env.lookup_variable_value(get_symbol("f".to_string())).unwrap();
+    assert_eq!(Value::Integer(2), call(&mut vm, "memoize-count", &[f]));
+
+    call(&mut vm, "memoize-clear!", &[f]);
+    call(&mut vm, "f", &[Value::Integer(5)]);
+    assert_eq!(Value::Integer(3), calls(&env));
+}
+
+#[test]
+fn memoize_lru() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    env.define_variable(get_symbol("calls".to_string()), Value::Pair(Value::Integer(0), Value::Nil));
+    define_counter(&mut vm, "f", Some(2));
+
+    call(&mut vm, "f", &[Value::Integer(1)]);
+    call(&mut vm, "f", &[Value::Integer(2)]);
+    // 1 is now the most recently used, so 2 is evicted
+    call(&mut vm, "f", &[Value::Integer(1)]);
+    call(&mut vm, "f", &[Value::Integer(3)]);
+    assert_eq!(Value::Integer(3), calls(&env));
+    call(&mut vm, "f", &[Value::Integer(1)]);
+    assert_eq!(Value::Integer(3), calls(&env));
+    call(&mut vm, "f", &[Value::Integer(2)]);
+    assert_eq!(Value::Integer(4), calls(&env));
+
+    let f = env.lookup_variable_value(get_symbol("f".to_string())).unwrap();
+    assert_eq!(Value::Integer(2), call(&mut vm, "memoize-count", &[f]));
+}
+
+#[test]
+fn memoize_errors() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let native = env.lookup_variable_value(get_symbol("length".to_string())).unwrap();
+    call(&mut vm, "memoize", &[native]);
+    assert_eq!(format!("Exception in memoize: {} is not a compound procedure", native),
+               format!("{}", vm.condition().unwrap()));
+}
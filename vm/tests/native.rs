@@ -0,0 +1,89 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+fn read_sensor(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Integer(args[0].to_integer() * 10))
+}
+
+fn first(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(args[0])
+}
+
+#[test]
+fn define_natives() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+
+    define_module_native(&env, "host", "read-sensor", Arity::Exactly(1), read_sensor).unwrap();
+    assert_eq!(Value::Integer(30), call(&mut vm, "host:read-sensor", &[Value::Integer(3)]));
+    define_native(&env, "native-test-first", Arity::Exactly(1), first).unwrap();
+    assert_eq!(Value::Integer(1), call(&mut vm, "native-test-first", &[Value::Integer(1)]));
+
+    // A name which is a procedure in the standard library, whether native or not
+    assert_eq!(Err(NativeError::AlreadyBound("car".to_string())),
+               define_native(&env, "car", Arity::Exactly(1), first));
+    assert_eq!(Err(NativeError::AlreadyBound("length".to_string())),
+               define_native(&env, "length", Arity::Exactly(1), first));
+    assert_eq!(Err(NativeError::AlreadyBound("host:read-sensor".to_string())),
+               define_module_native(&env, "host", "read-sensor", Arity::Exactly(1), read_sensor));
+    assert_eq!(Value::Integer(30), call(&mut vm, "host:read-sensor", &[Value::Integer(3)]));
+}
+
+#[test]
+fn registration_collision() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let first_env = init_env();
+    let a = define_module_native(&first_env, "sensor", "read", Arity::Exactly(1), read_sensor).unwrap();
+    // The same procedure can be defined in another environment
+    let second_env = init_env();
+    assert_eq!(Ok(a), define_module_native(&second_env, "sensor", "read", Arity::Exactly(1), read_sensor));
+    // But the name can't be reused for something else
+    let e = define_module_native(&init_env(), "sensor", "read", Arity::Exactly(1), first).unwrap_err();
+    assert_eq!("a different native procedure is already registered as sensor:read", format!("{}", e));
+    let e = define_module_native(&init_env(), "sensor", "read", Arity::Exactly(2), read_sensor).unwrap_err();
+    assert_eq!(NativeError::AlreadyRegistered("sensor:read".to_string()), e);
+}
+
+#[test]
+fn closures() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+
+    let offset = 100;
+    define_closure(&env, "native-test-offset", Arity::Exactly(1), move |_, args| {
+        Ok(Value::Integer(integer_arg(args[0])? + offset))
+    }).unwrap();
+    assert_eq!(Value::Integer(101), call(&mut vm, "native-test-offset", &[Value::Integer(1)]));
+    // Closures are never looked up by name, so others and native procedures may share it
+    define_native(&init_env(), "native-test-offset", Arity::Exactly(1), first).unwrap();
+    let other = init_env();
+    let a = define_closure(&other, "native-test-offset", Arity::Exactly(1), |_, _| Ok(Value::Integer(0))).unwrap();
+    assert_ne!(Some(a), env.lookup_variable_value(get_symbol("native-test-offset".to_string())));
+    assert_eq!(Err(NativeError::AlreadyBound("native-test-offset".to_string())),
+               define_closure(&env, "native-test-offset", Arity::Exactly(1), |_, args| Ok(args[0])));
+}
+
+#[test]
+fn argument_conversions() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    assert_eq!(Ok("text".to_string()), string_arg(Value::String("text".to_string())));
+    assert_eq!(Err("1 is not a string".to_string()), string_arg(Value::Integer(1)));
+    assert_eq!(Ok(-4), integer_arg(Value::Integer(-4)));
+    assert_eq!(Err("1.5 is not an integer".to_string()), integer_arg(Value::Float(1.5)));
+
+    let list = make_list(vec![Value::Integer(1), Value::Integer(2)]);
+    assert_eq!("(1 2)", format!("{}", list));
+    assert_eq!(Ok(vec![Value::Integer(1), Value::Integer(2)]), list_arg(list));
+    assert_eq!(Ok(vec![]), list_arg(make_list(vec![])));
+    assert_eq!(Err("(1 . 2) is not a list".to_string()), list_arg(Value::Pair(Value::Integer(1), Value::Integer(2))));
+}
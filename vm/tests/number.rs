@@ -0,0 +1,301 @@
+extern crate num_bigint;
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use num_bigint::BigInt;
+use string_interner::get_symbol;
+use vm::*;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn int(i: i32) -> Value {
+    Value::Integer(i)
+}
+
+fn float(f: f64) -> Value {
+    Value::Float(f)
+}
+
+fn symbol(s: &str) -> Value {
+    Value::Symbol(get_symbol(s.to_string()))
+}
+
+#[test]
+fn exact_division() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    assert_eq!(int(3), call(&mut vm, "/", &[int(6), int(2)]));
+    assert_eq!(int(-3), call(&mut vm, "/", &[int(6), int(-2)]));
+    assert_eq!(int(1), call(&mut vm, "/", &[int(24), int(2), int(3), int(4)]));
+    assert_eq!(int(0), call(&mut vm, "/", &[int(0), int(7)]));
+    assert_eq!(int(-1), call(&mut vm, "/", &[int(-1)]));
+    // Without rationals, uneven division is inexact
+    assert_eq!(float(3.5), call(&mut vm, "/", &[int(7), int(2)]));
+    assert_eq!(float(0.25), call(&mut vm, "/", &[int(4)]));
+    // The quotient doesn't fit in a fixnum
+    assert_eq!("2147483648", format!("{}", call(&mut vm, "/", &[int(i32::MIN), int(-1)])));
+}
+
+#[test]
+fn float_division() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    assert_eq!(float(2.5), call(&mut vm, "/", &[float(5.0), int(2)]));
+    assert_eq!(float(0.5), call(&mut vm, "/", &[int(1), float(2.0)]));
+    assert_eq!(float(f64::INFINITY), call(&mut vm, "/", &[float(1.0), int(0)]));
+    assert_eq!(float(f64::NEG_INFINITY), call(&mut vm, "/", &[int(-1), float(0.0)]));
+    assert_eq!(float(f64::INFINITY), call(&mut vm, "/", &[float(0.0)]));
+    assert!(call(&mut vm, "/", &[float(0.0), float(0.0)]).to_float().is_nan());
+    assert_eq!(0, vm.condition_depth());
+}
+
+#[test]
+fn division_by_zero() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    call(&mut vm, "/", &[int(1), int(0)]);
+    assert_eq!("Exception in /: division by zero", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "/", &[int(0)]);
+    assert_eq!("Exception in /: division by zero", format!("{}", vm.condition().unwrap()));
+
+    for name in &["quotient", "remainder", "modulo"] {
+        call(&mut vm, name, &[int(1), int(0)]);
+        assert_eq!(format!("Exception in {}: division by zero", name), format!("{}", vm.condition().unwrap()));
+        call(&mut vm, name, &[float(1.0), float(0.0)]);
+        assert_eq!(format!("Exception in {}: division by zero", name), format!("{}", vm.condition().unwrap()));
+    }
+
+    call(&mut vm, "/", &[int(1), Value::Nil]);
+    assert_eq!("Exception in /: () is not a number", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn division_by_zero_is_catchable() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let caught = Rc::new(RefCell::new(None));
+    let c = caught.clone();
+    vm.set_condition_handler(move |condition| {
+        *c.borrow_mut() = Some(format!("{}", condition));
+        Some(Restart::Abort)
+    });
+    call(&mut vm, "/", &[int(1), int(0)]);
+    assert_eq!(Some("Exception in /: division by zero".to_string()), *caught.borrow());
+    assert_eq!(0, vm.condition_depth());
+}
+
+#[test]
+fn integer_division() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let cases = [(7, 2, 3, 1, 1), (-7, 2, -3, -1, 1), (7, -2, -3, 1, -1), (-7, -2, 3, -1, -1), (6, 3, 2, 0, 0)];
+    for &(n1, n2, q, r, m) in &cases {
+        assert_eq!(int(q), call(&mut vm, "quotient", &[int(n1), int(n2)]));
+        assert_eq!(int(r), call(&mut vm, "remainder", &[int(n1), int(n2)]));
+        assert_eq!(int(m), call(&mut vm, "modulo", &[int(n1), int(n2)]));
+    }
+
+    assert_eq!("2147483648", format!("{}", call(&mut vm, "quotient", &[int(i32::MIN), int(-1)])));
+    assert_eq!(int(0), call(&mut vm, "remainder", &[int(i32::MIN), int(-1)]));
+    assert_eq!(int(0), call(&mut vm, "modulo", &[int(i32::MIN), int(-1)]));
+
+    assert_eq!(float(-3.0), call(&mut vm, "quotient", &[float(-7.0), int(2)]));
+    assert_eq!(float(1.0), call(&mut vm, "modulo", &[float(-7.0), int(2)]));
+    call(&mut vm, "quotient", &[float(7.5), int(2)]);
+    assert_eq!("Exception in quotient: 7.5 is not an integer", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn bignum_division() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let big = Value::BigInt(BigInt::from(-(1i64 << 40)));
+    env.define_variable(get_symbol("big".to_string()), big);
+
+    // Results which fit are fixnums again
+    assert_eq!(int(-(1 << 20)), call(&mut vm, "/", &[big, int(1 << 20)]));
+    assert_eq!(int(-(1 << 20)), call(&mut vm, "quotient", &[big, int(1 << 20)]));
+    assert_eq!("-4294967296", format!("{}", call(&mut vm, "quotient", &[big, int(256)])));
+    assert_eq!(int(-2), call(&mut vm, "remainder", &[big, int(7)]));
+    assert_eq!(int(5), call(&mut vm, "modulo", &[big, int(7)]));
+    assert_eq!(float(-(1i64 << 40) as f64 / 3.0), call(&mut vm, "/", &[big, int(3)]));
+    assert_eq!(float(-4.0), call(&mut vm, "quotient", &[big, float((1i64 << 38) as f64)]));
+    call(&mut vm, "modulo", &[big, int(0)]);
+    assert_eq!("Exception in modulo: division by zero", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn width_conversion() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    assert_eq!(int(44), call(&mut vm, "wrapping-integer", &[int(300), symbol("u8")]));
+    assert_eq!(int(-1), call(&mut vm, "wrapping-integer", &[int(255), symbol("s8")]));
+    assert_eq!(int(255), call(&mut vm, "wrapping-integer", &[int(-1), symbol("u8")]));
+    assert_eq!(int(-32768), call(&mut vm, "wrapping-integer", &[int(32768), symbol("s16")]));
+    assert_eq!("4294967295", format!("{}", call(&mut vm, "wrapping-integer", &[int(-1), symbol("u32")])));
+    assert_eq!("18446744073709551615", format!("{}", call(&mut vm, "wrapping-integer", &[int(-1), symbol("u64")])));
+
+    assert_eq!(int(255), call(&mut vm, "checked-integer", &[int(255), symbol("u8")]));
+    assert_eq!(Value::False, call(&mut vm, "checked-integer", &[int(256), symbol("u8")]));
+    assert_eq!(Value::False, call(&mut vm, "checked-integer", &[int(-1), symbol("u32")]));
+    assert_eq!(int(-128), call(&mut vm, "checked-integer", &[int(-128), symbol("s8")]));
+    assert_eq!(Value::False, call(&mut vm, "checked-integer", &[int(-129), symbol("s8")]));
+
+    call(&mut vm, "wrapping-integer", &[int(1), symbol("u7")]);
+    assert_eq!("Exception in wrapping-integer: u7 is not an integer type", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "checked-integer", &[float(1.0), symbol("u8")]);
+    assert_eq!("Exception in checked-integer: 1.0 is not an exact integer", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn reinterpret() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let max = call(&mut vm, "i32->u32", &[int(-1)]);
+    // Keep the bignum alive between calls
+    env.define_variable(get_symbol("max".to_string()), max);
+    assert_eq!("4294967295", format!("{}", max));
+    assert_eq!(int(-1), call(&mut vm, "u32->i32", &[max]));
+    assert_eq!(int(i32::MAX), call(&mut vm, "i32->u32", &[int(i32::MAX)]));
+    assert_eq!("2147483648", format!("{}", call(&mut vm, "i32->u32", &[int(i32::MIN)])));
+    assert_eq!(int(5), call(&mut vm, "u32->i32", &[int(5)]));
+
+    call(&mut vm, "u32->i32", &[int(-1)]);
+    assert_eq!("Exception in u32->i32: -1 is out of range", format!("{}", vm.condition().unwrap()));
+    let big = Value::BigInt(BigInt::from(1u64 << 32));
+    env.define_variable(get_symbol("big".to_string()), big);
+    call(&mut vm, "i32->u32", &[big]);
+    assert_eq!("Exception in i32->u32: 4294967296 is out of range", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn integer_bytevector() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let (big, little) = (symbol("big"), symbol("little"));
+    assert_eq!("#u8(0 0 1 2)", format!("{}", call(&mut vm, "integer->bytevector", &[int(258), int(4)])));
+    assert_eq!("#u8(2 1 0 0)", format!("{}", call(&mut vm, "integer->bytevector", &[int(258), int(4), little])));
+    assert_eq!("#u8(255 254)", format!("{}", call(&mut vm, "integer->bytevector", &[int(-2), int(2), big])));
+    assert_eq!("#u8(255)", format!("{}", call(&mut vm, "integer->bytevector", &[int(255), int(1)])));
+    assert_eq!("#u8()", format!("{}", call(&mut vm, "integer->bytevector", &[int(0), int(0)])));
+    call(&mut vm, "integer->bytevector", &[int(256), int(1)]);
+    assert_eq!("Exception in integer->bytevector: 256 does not fit in 1 bytes", format!("{}", vm.condition().unwrap()));
+
+    let n = Value::BigInt("1208925819614629174706175".parse().unwrap());
+    env.define_variable(get_symbol("n".to_string()), n);
+    let b = call(&mut vm, "integer->bytevector", &[n, int(10), little]);
+    env.define_variable(get_symbol("b".to_string()), b);
+    assert_eq!("#u8(255 255 255 255 255 255 255 255 255 255)", format!("{}", b));
+    assert_eq!(format!("{}", n), format!("{}", call(&mut vm, "bytevector->integer", &[b, little])));
+    assert_eq!(int(-1), call(&mut vm, "bytevector->integer", &[b, big, symbol("signed")]));
+
+    let b = Value::Bytevector(vec![1, 0]);
+    env.define_variable(get_symbol("b".to_string()), b);
+    assert_eq!(int(256), call(&mut vm, "bytevector->integer", &[b]));
+    assert_eq!(int(1), call(&mut vm, "bytevector->integer", &[b, little, symbol("unsigned")]));
+    call(&mut vm, "bytevector->integer", &[b, big, int(1)]);
+    assert_eq!("Exception in bytevector->integer: 1 is not a signedness, expected signed or unsigned",
+               format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn number_to_string() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let text = |vm: &mut VM, args: &[Value]| format!("{}", call(vm, "number->string", args));
+    // Integral floats keep their decimal point, and every float is as short as it can be
+    assert_eq!("\"2.0\"", text(&mut vm, &[float(2.0)]));
+    assert_eq!("\"0.1\"", text(&mut vm, &[float(0.1)]));
+    assert_eq!("\"-0.0\"", text(&mut vm, &[float(-0.0)]));
+    assert_eq!("\"1e21\"", text(&mut vm, &[float(1e21)]));
+    assert_eq!("\"1.5e-7\"", text(&mut vm, &[float(1.5e-7)]));
+    assert_eq!("\"42\"", text(&mut vm, &[int(42)]));
+
+    assert_eq!("\"3.14\"", text(&mut vm, &[float(std::f64::consts::PI), int(2)]));
+    assert_eq!("\"3\"", text(&mut vm, &[float(2.6), int(0)]));
+    assert_eq!("\"7.000\"", text(&mut vm, &[int(7), int(3)]));
+    assert_eq!("\"+inf.0\"", text(&mut vm, &[float(std::f64::INFINITY), int(3)]));
+
+    call(&mut vm, "number->string", &[int(1), int(-1)]);
+    assert_eq!("Exception in number->string: -1 is not a valid precision", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "number->string", &[symbol("a")]);
+    assert_eq!("Exception in number->string: a is not a number", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn exactness() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    // Exact arithmetic stays exact, becoming a bignum instead of overflowing
+    assert_eq!(int(5), call(&mut vm, "+", &[int(2), int(3)]));
+    assert_eq!("2147483648", format!("{}", call(&mut vm, "+", &[int(i32::MAX), int(1)])));
+    assert_eq!("4611686014132420609", format!("{}", call(&mut vm, "*", &[int(i32::MAX), int(i32::MAX)])));
+    assert_eq!("-2147483649", format!("{}", call(&mut vm, "-", &[int(i32::MIN), int(1)])));
+    // An inexact operand makes the result inexact
+    assert_eq!(float(3.0), call(&mut vm, "*", &[int(2), float(1.5)]));
+    assert_eq!(float(0.5), call(&mut vm, "-", &[float(1.5), int(1)]));
+
+    // Numbers compare by value
+    assert_eq!(Value::True, call(&mut vm, "=", &[int(1), float(1.0)]));
+    assert_eq!(Value::False, call(&mut vm, "=", &[float(std::f64::NAN), float(std::f64::NAN)]));
+    assert_eq!(Value::True, call(&mut vm, "<", &[int(1), float(1.5)]));
+    assert_eq!(Value::False, call(&mut vm, "<", &[float(2.0), int(2)]));
+
+    assert_eq!(Value::True, call(&mut vm, "exact?", &[int(1)]));
+    assert_eq!(Value::False, call(&mut vm, "exact?", &[float(1.0)]));
+    assert_eq!(Value::True, call(&mut vm, "inexact?", &[float(1.0)]));
+    assert_eq!(int(4), call(&mut vm, "exact", &[float(4.0)]));
+    assert_eq!("10000000000", format!("{}", call(&mut vm, "exact", &[float(1e10)])));
+    assert_eq!(float(4.0), call(&mut vm, "inexact", &[int(4)]));
+    assert_eq!(float(2.5), call(&mut vm, "inexact", &[float(2.5)]));
+
+    call(&mut vm, "exact", &[float(2.5)]);
+    assert_eq!("Exception in exact: 2.5 has no exact representation", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "+", &[int(1), symbol("a")]);
+    assert_eq!("Exception in +: a is not a number", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "exact?", &[symbol("a")]);
+    assert_eq!("Exception in exact?: a is not a number", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn bignum_arithmetic() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let big = Value::BigInt("100000000000000000000".parse().unwrap());
+    // Keep the bignum alive between calls
+    env.define_variable(get_symbol("big".to_string()), big);
+    assert_eq!("200000000000000000000", format!("{}", call(&mut vm, "+", &[big, big])));
+    assert_eq!("99999999999999999999", format!("{}", call(&mut vm, "-", &[big, int(1)])));
+    assert_eq!("-300000000000000000000", format!("{}", call(&mut vm, "*", &[int(-3), big])));
+    // A result which fits is a fixnum again
+    let sum = call(&mut vm, "+", &[big, int(7)]);
+    env.define_variable(get_symbol("sum".to_string()), sum);
+    assert_eq!(int(7), call(&mut vm, "-", &[sum, big]));
+    // The operands are left as they were
+    assert_eq!("100000000000000000000", format!("{}", big));
+
+    assert_eq!(Value::True, call(&mut vm, "<", &[int(i32::MAX), big]));
+    let product = call(&mut vm, "*", &[big, int(1)]);
+    env.define_variable(get_symbol("product".to_string()), product);
+    assert_eq!(Value::True, call(&mut vm, "=", &[big, product]));
+}
@@ -0,0 +1,149 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+use std::io::Write;
+use std::net::TcpListener;
+
+#[test]
+fn string_ports() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let input = call(&mut vm, "open-input-string", &[Value::String("λx\nrest".to_string())]);
+    assert_eq!(Value::True, call(&mut vm, "input-port?", &[input]));
+    assert_eq!(Value::True, call(&mut vm, "char-ready?", &[input]));
+    assert_eq!(Value::Char('λ'), call(&mut vm, "peek-char", &[input]));
+    assert_eq!(Value::Char('λ'), call(&mut vm, "read-char", &[input]));
+    assert_eq!("\"x\"", format!("{}", call(&mut vm, "read-line", &[input])));
+    assert_eq!("\"rest\"", format!("{}", call(&mut vm, "read-line-no-hang", &[input])));
+    // A port at its end is always ready
+    assert_eq!(Value::True, call(&mut vm, "char-ready?", &[input]));
+    assert_eq!(Value::Eof, call(&mut vm, "read-char-no-hang", &[input]));
+
+    let output = call(&mut vm, "open-output-string", &[]);
+    call(&mut vm, "write-char", &[Value::Char('a'), output]);
+    call(&mut vm, "display", &[Value::String("bc".to_string()), output]);
+    call(&mut vm, "display", &[Value::Integer(1), output]);
+    call(&mut vm, "newline", &[output]);
+    call(&mut vm, "write", &[Value::String("d\"e".to_string()), output]);
+    call(&mut vm, "write", &[Value::Char('f'), output]);
+    assert_eq!(r##""abc1\n\"d\\\"e\"#\\f""##, format!("{}", call(&mut vm, "get-output-string", &[output])));
+
+    call(&mut vm, "read-char", &[output]);
+    assert_eq!(format!("Exception in read-char: {} is not an open input port", output),
+               format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn closing() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    // The slot of a closed port is given to the next port opened
+    let first = call(&mut vm, "open-input-string", &[Value::String("a".to_string())]);
+    call(&mut vm, "close-port", &[first]);
+    assert_eq!(Value::False, call(&mut vm, "input-port?", &[first]));
+    let second = call(&mut vm, "open-output-string", &[]);
+    assert_eq!(first, second);
+    assert_eq!(Value::True, call(&mut vm, "output-port?", &[second]));
+
+    // But not that of a standard port
+    let stdout = call(&mut vm, "current-output-port", &[]);
+    call(&mut vm, "close-port", &[stdout]);
+    assert_ne!(stdout, call(&mut vm, "open-output-string", &[]));
+    assert_eq!(Value::False, call(&mut vm, "output-port?", &[stdout]));
+}
+
+#[test]
+fn non_blocking_reads() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let ports = call(&mut vm, "open-tcp-connection",
+                     &[Value::String("127.0.0.1".to_string()), Value::Integer(address.port() as i32)]);
+    let input = ports.car();
+    let (mut stream, _) = listener.accept().unwrap();
+
+    // Nothing has been sent yet
+    assert_eq!(Value::False, call(&mut vm, "char-ready?", &[input]));
+    assert_eq!(Value::False, call(&mut vm, "read-char-no-hang", &[input]));
+    call(&mut vm, "set-port-read-timeout!", &[input, Value::Float(0.05)]);
+    assert_eq!(Value::False, call(&mut vm, "read-char", &[input]));
+
+    stream.write_all(b"partial").unwrap();
+    call(&mut vm, "set-port-read-timeout!", &[input, Value::False]);
+    assert_eq!(Value::Char('p'), call(&mut vm, "read-char", &[input]));
+    // The line is incomplete
+    assert_eq!(Value::False, call(&mut vm, "read-line-no-hang", &[input]));
+
+    stream.write_all(b" line\n").unwrap();
+    assert_eq!("\"artial line\"", format!("{}", call(&mut vm, "read-line", &[input])));
+    drop(stream);
+    assert_eq!(Value::Eof, call(&mut vm, "read-char", &[input]));
+}
+
+#[test]
+fn binary_ports() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let big = Value::Symbol(get_symbol("big".to_string()));
+    let little = Value::Symbol(get_symbol("little".to_string()));
+
+    let output = call(&mut vm, "open-output-bytevector", &[]);
+    call(&mut vm, "write-u32", &[Value::Integer(0x01020304), output]);
+    call(&mut vm, "write-u16", &[Value::Integer(0xABCD), output, little]);
+    call(&mut vm, "write-f64", &[Value::Float(1.5), output, little]);
+    call(&mut vm, "write-u8", &[Value::Integer(255), output]);
+    let bytes = call(&mut vm, "get-output-bytevector", &[output]);
+    // Keep the bytevector alive between calls
+    env.define_variable(get_symbol("bytes".to_string()), bytes);
+    assert_eq!("#u8(1 2 3 4 205 171 0 0 0 0 0 0 248 63 255)", format!("{}", bytes));
+
+    let input = call(&mut vm, "open-input-bytevector", &[bytes]);
+    assert_eq!(Value::Integer(0x04030201), call(&mut vm, "read-u32", &[input, little]));
+    assert_eq!(Value::Integer(0xABCD), call(&mut vm, "read-u16", &[input, little]));
+    assert_eq!(Value::Float(1.5), call(&mut vm, "read-f64", &[input, little]));
+    assert_eq!(Value::Integer(-1), call(&mut vm, "read-s8", &[input]));
+    assert_eq!(Value::Eof, call(&mut vm, "read-u8", &[input]));
+
+    let input = call(&mut vm, "open-input-bytevector", &[bytes]);
+    call(&mut vm, "read-bytevector", &[Value::Integer(14), input]);
+    call(&mut vm, "read-u16", &[input, big]);
+    assert_eq!("Exception in read-u16: unexpected end of port", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn bytevector_accessors() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let little = Value::Symbol(get_symbol("little".to_string()));
+    let b = Value::Bytevector(vec![0; 8]);
+    // Keep the bytevector alive between calls
+    env.define_variable(get_symbol("bytes".to_string()), b);
+
+    call(&mut vm, "bytevector-u32-set!", &[b, Value::Integer(0), Value::Float(4294967295.0)]);
+    assert_eq!(Value::Float(4294967295.0), call(&mut vm, "bytevector-u32-ref", &[b, Value::Integer(0)]));
+    assert_eq!(Value::Integer(-1), call(&mut vm, "bytevector-s32-ref", &[b, Value::Integer(0)]));
+    call(&mut vm, "bytevector-s16-set!", &[b, Value::Integer(2), Value::Integer(-2), little]);
+    assert_eq!("#u8(255 255 254 255 0 0 0 0)", format!("{}", b));
+
+    call(&mut vm, "bytevector-ieee-double-set!", &[b, Value::Integer(0), Value::Float(-0.25)]);
+    assert_eq!(Value::Float(-0.25), call(&mut vm, "bytevector-ieee-double-ref", &[b, Value::Integer(0)]));
+    call(&mut vm, "bytevector-ieee-single-set!", &[b, Value::Integer(4), Value::Integer(3), little]);
+    assert_eq!(Value::Float(3.0), call(&mut vm, "bytevector-ieee-single-ref", &[b, Value::Integer(4), little]));
+
+    call(&mut vm, "bytevector-u16-ref", &[b, Value::Integer(7)]);
+    assert_eq!("Exception in bytevector-u16-ref: 7 is not a valid index", format!("{}", vm.condition().unwrap()));
+}
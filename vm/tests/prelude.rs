@@ -0,0 +1,57 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+#[test]
+fn shared_prelude() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let env = init_env();
+    let l = get_symbol("l".to_string());
+    env.define_variable(l, Value::Pair(Value::Integer(1), Value::Pair(Value::Integer(2), Value::Nil)));
+    env.set_strictness(Strictness::Warn);
+    let prelude = Prelude::new(env);
+
+    // Instances don't allocate
+    let allocated = allocations();
+    let (a, b) = (prelude.instance(), prelude.instance());
+    let (mut vm_a, mut vm_b) = (VM::new(), VM::new());
+    vm_a.assign_environment(a.clone());
+    vm_b.assign_environment(b.clone());
+    assert_eq!(allocated, allocations());
+    assert_eq!(Strictness::Warn, a.strictness());
+
+    // Collecting in one instance doesn't free anything the prelude refers to
+    vm_a.gc();
+    vm_b.gc();
+    assert_eq!(Value::Integer(3), call(&mut vm_a, "+", &[Value::Integer(1), Value::Integer(2)]));
+    assert_eq!("(1 2)", format!("{}", a.lookup_variable_value(l).unwrap()));
+    assert_eq!(Value::Integer(2), call(&mut vm_b, "length", &[b.lookup_variable_value(l).unwrap()]));
+
+    // Definitions are private to an instance
+    let x = get_symbol("x".to_string());
+    a.define_variable(x, Value::Integer(1));
+    a.define_variable(l, Value::Nil);
+    assert_eq!(None, b.lookup_variable_value(x));
+    assert_eq!("(1 2)", format!("{}", b.lookup_variable_value(l).unwrap()));
+    assert_eq!(None, prelude.instance().lookup_variable_value(x));
+}
+
+#[test]
+fn prelude_survives_collection() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let prelude = Prelude::new(init_env());
+    let mut vm = prelude.vm();
+    let before = allocations();
+    let l = call(&mut vm, "list", &[Value::Integer(1), Value::Integer(2)]);
+    assert_eq!("(1 2)", format!("{}", l));
+    assert!(allocations() > before);
+    // Collect the list, leaving only permanent objects reachable
+    call(&mut vm, "+", &[Value::Integer(1), Value::Integer(2)]);
+    vm.gc();
+    assert_eq!(Value::Integer(5), call(&mut vm, "+", &[Value::Integer(2), Value::Integer(3)]));
+}
@@ -0,0 +1,33 @@
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use vm::*;
+
+#[test]
+fn random_bytes() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let b = format!("{}", call(&mut vm, "random-bytes", &[Value::Integer(16)]));
+    assert!(b.starts_with("#u8("));
+    assert_eq!(16, b.split(' ').count());
+    assert_eq!("#u8()", format!("{}", call(&mut vm, "random-bytes", &[Value::Integer(0)])));
+}
+
+#[test]
+fn uuid() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let a = format!("{}", call(&mut vm, "uuid", &[]));
+    let b = format!("{}", call(&mut vm, "uuid", &[]));
+    assert_ne!(a, b);
+
+    let a = a.trim_matches('"');
+    let groups: Vec<usize> = a.split('-').map(str::len).collect();
+    assert_eq!(vec![8, 4, 4, 4, 12], groups);
+    assert_eq!(Some('4'), a.chars().nth(14));
+    assert!("89ab".contains(a.chars().nth(19).unwrap()));
+}
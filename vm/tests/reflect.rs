@@ -0,0 +1,132 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+fn symbol(s: &str) -> Value {
+    Value::Symbol(get_symbol(s.to_string()))
+}
+
+#[test]
+fn environments() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let global = init_env();
+    let local = global.extend();
+    local.define_variable(get_symbol("b".to_string()), Value::Integer(2));
+    local.define_variable(get_symbol("a".to_string()), Value::Integer(1));
+    vm.assign_environment(local);
+
+    let env = call(&mut vm, "current-environment", &[]);
+    assert!(env.is_environment());
+    assert_eq!(Value::True, call(&mut vm, "environment?", &[env]));
+    assert_eq!(Value::False, call(&mut vm, "environment?", &[Value::Integer(1)]));
+    // The same frame is always the same value
+    assert_eq!(env, call(&mut vm, "current-environment", &[]));
+
+    assert_eq!("(a b)", format!("{}", call(&mut vm, "environment-bound-names", &[env])));
+    assert_eq!("((a 1) (b 2))", format!("{}", call(&mut vm, "environment-bindings", &[env])));
+
+    let parent = call(&mut vm, "environment-parent", &[env]);
+    assert_eq!(parent, call(&mut vm, "interaction-environment", &[]));
+    assert_eq!(Value::False, call(&mut vm, "environment-parent", &[parent]));
+
+    assert_eq!(Value::True, call(&mut vm, "environment-bound?", &[env, symbol("a")]));
+    assert_eq!(Value::True, call(&mut vm, "environment-bound?", &[env, symbol("car")]));
+    assert_eq!(Value::False, call(&mut vm, "environment-bound?", &[parent, symbol("a")]));
+    assert_eq!(Value::Integer(2), call(&mut vm, "environment-lookup", &[env, symbol("b")]));
+
+    call(&mut vm, "environment-lookup", &[parent, symbol("b")]);
+    assert_eq!("Exception in environment-lookup: b is not bound", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "environment-bindings", &[Value::Environment(100)]);
+    assert_eq!("Exception in environment-bindings: #<environment 100> is not an environment",
+               format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn environment_bindings_survive_collection() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let local = init_env().extend();
+    vm.assign_environment(local.clone());
+    local.define_variable(get_symbol("l".to_string()), Value::Pair(Value::Integer(1), Value::Nil));
+    let env = call(&mut vm, "current-environment", &[]);
+
+    // Only the environment table refers to the frame now
+    vm.assign_environment(init_env());
+    drop(local);
+    vm.gc();
+    assert_eq!("((l (1)))", format!("{}", call(&mut vm, "environment-bindings", &[env])));
+}
+
+// Run `code` in the machine's current environment.
+fn run(vm: &mut VM, code: Vec<ASM>) {
+    let (code, consts) = assemble(code);
+    vm.load_code(code, consts);
+    vm.run();
+}
+
+fn define(name: &str, v: Value) -> Vec<ASM> {
+    vec![ASM::LoadConst(Register(1), symbol(name)), ASM::LoadConst(Register(2), v),
+         ASM::Define(Register(1), Register(2))]
+}
+
+fn set(name: &str, v: Value) -> Vec<ASM> {
+    vec![ASM::LoadConst(Register(1), symbol(name)), ASM::LoadConst(Register(2), v),
+         ASM::Set(Register(1), Register(2))]
+}
+
+#[test]
+fn strictness() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let global = init_env();
+    vm.assign_environment(global.clone());
+    let env = call(&mut vm, "interaction-environment", &[]);
+    assert_eq!(symbol("permissive"), call(&mut vm, "environment-strictness", &[env]));
+
+    // Permissive: redefinition is silent and `set!` defines unbound variables
+    run(&mut vm, define("x", Value::Integer(1)));
+    run(&mut vm, define("x", Value::Integer(2)));
+    run(&mut vm, set("y", Value::Integer(3)));
+    assert_eq!(Some(Value::Integer(3)), global.lookup_variable_value(get_symbol("y".to_string())));
+    assert!(vm.take_warnings().is_empty());
+
+    call(&mut vm, "set-environment-strictness!", &[env, symbol("warn")]);
+    run(&mut vm, define("x", Value::Integer(4)));
+    run(&mut vm, define("z", Value::Integer(5)));
+    assert_eq!(vec!["redefining x".to_string()], vm.take_warnings());
+    assert_eq!(Some(Value::Integer(4)), global.lookup_variable_value(get_symbol("x".to_string())));
+    run(&mut vm, set("w", Value::Integer(6)));
+    assert_eq!("Exception in set!: variable w is not bound", format!("{}", vm.condition().unwrap()));
+    assert_eq!(None, global.lookup_variable_value(get_symbol("w".to_string())));
+
+    call(&mut vm, "set-environment-strictness!", &[env, symbol("strict")]);
+    run(&mut vm, define("x", Value::Integer(7)));
+    assert_eq!("Exception in define: x is already defined", format!("{}", vm.condition().unwrap()));
+    assert_eq!(Some(Value::Integer(4)), global.lookup_variable_value(get_symbol("x".to_string())));
+    run(&mut vm, set("x", Value::Integer(8)));
+    assert_eq!(Some(Value::Integer(8)), global.lookup_variable_value(get_symbol("x".to_string())));
+
+    // Procedure frames share the setting of their global environment, but may shadow freely
+    let local = global.extend();
+    local.define_variable(get_symbol("x".to_string()), Value::Integer(9));
+    vm.assign_environment(local.clone());
+    let local_env = call(&mut vm, "current-environment", &[]);
+    assert_eq!(symbol("strict"), call(&mut vm, "environment-strictness", &[local_env]));
+    let depth = vm.condition_depth();
+    run(&mut vm, define("x", Value::Integer(10)));
+    assert_eq!(depth, vm.condition_depth());
+
+    // Another global environment is unaffected
+    vm.assign_environment(init_env());
+    let other = call(&mut vm, "interaction-environment", &[]);
+    assert_eq!(symbol("permissive"), call(&mut vm, "environment-strictness", &[other]));
+    call(&mut vm, "set-environment-strictness!", &[other, symbol("loose")]);
+    assert_eq!("Exception in set-environment-strictness!: loose is not a strictness, expected permissive, warn, or strict",
+               format!("{}", vm.condition().unwrap()));
+}
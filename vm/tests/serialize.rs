@@ -0,0 +1,236 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use string_interner::get_symbol;
+use vm::*;
+
+fn label(name: &str) -> string_interner::Symbol {
+    get_symbol(name.to_string())
+}
+
+fn write(code: &[Operation], consts: &[Value]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_header(&mut out, Features::of(code, consts));
+    write_code(&mut out, code, consts).unwrap();
+    out
+}
+
+type Code = (Vec<Operation>, Vec<Value>);
+
+fn read(bytes: &[u8]) -> Result<Vec<Code>, LoadError> {
+    CodeReader::new(bytes)?.collect()
+}
+
+fn run(code: Vec<Operation>, consts: Vec<Value>) -> Value {
+    let mut vm = VM::new();
+    vm.load_code(code, consts);
+    vm.run();
+    vm.load_register(Register(0))
+}
+
+#[test]
+fn round_trip() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let list = (0..10_000).rev().fold(Value::Nil, |l, i| Value::Pair(Value::Integer(i), l));
+    let consts = vec![
+        Value::Void, Value::Nil, Value::Bool(true), Value::Integer(-5), Value::Float(2.5), Value::Char('λ'),
+        Value::Symbol(label("name")), Value::String("a string".to_string()), Value::Bytevector(vec![0, 255]),
+        Value::Pair(Value::Integer(1), Value::Integer(2)), Value::Vec(vec![Value::Integer(1), Value::Nil]), list,
+    ];
+    let code: Vec<_> = (0..consts.len()).map(|i| Operation::LoadConst(Register(1), i)).collect();
+    let mut loaded = read(&write(&code, &consts)).unwrap();
+    assert_eq!(1, loaded.len());
+    let (loaded_code, loaded_consts) = loaded.pop().unwrap();
+    assert_eq!(code, loaded_code);
+    for (c, l) in consts.iter().zip(&loaded_consts) {
+        assert_eq!(format!("{}", c), format!("{}", l));
+    }
+
+    let add = vec![
+        ASM::LoadConst(Register(1), Value::Integer(40)),
+        ASM::LoadConst(Register(2), Value::Integer(2)),
+        ASM::Add(Register(0), Register(1), Register(2)),
+        ASM::Return,
+    ];
+    let (code, consts) = assemble(vec![
+        ASM::MakeClosure(Register(0), Box::new(add)),
+        ASM::Call(Register(0), 0),
+    ]);
+    let (code, consts) = read(&write(&code, &consts)).unwrap().pop().unwrap();
+    assert_eq!(Value::Integer(42), run(code, consts));
+
+    // Values which belong to a machine can't be written
+    let port = Value::Port(0);
+    assert_eq!(Err(port), write_code(&mut Vec::new(), &[], &[Value::Pair(Value::Integer(1), port)]));
+}
+
+#[test]
+fn definitions() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    // A procedure which returns the value of `x` where it is defined
+    let (code, consts) = assemble(vec![
+        ASM::LoadConst(Register(1), Value::Symbol(label("x"))),
+        ASM::Lookup(Register(0), Register(1)),
+        ASM::Return,
+    ]);
+    let get_x = Value::Lambda(Environment::new(), code, consts);
+    let list = Value::Pair(Value::Integer(1), Value::Pair(Value::String("s".to_string()), Value::Nil));
+
+    let mut out = Vec::new();
+    write_header(&mut out, Features::NONE);
+    write_definition(&mut out, label("x"), Value::Integer(42)).unwrap();
+    write_definition(&mut out, label("list"), list).unwrap();
+    write_definition(&mut out, label("get-x"), get_x).unwrap();
+    // A value which can't be written leaves nothing behind
+    let written = out.clone();
+    let port = Value::Port(0);
+    assert_eq!(Err(port), write_definition(&mut out, label("port"), Value::Pair(Value::Nil, port)));
+    assert_eq!(written, out);
+
+    let env = Environment::new();
+    let mut vm = VM::new();
+    vm.assign_environment(env.clone());
+    for code in CodeReader::new(&out).unwrap() {
+        let (code, consts) = code.unwrap();
+        vm.load_code(code, consts);
+        vm.run();
+        assert!(vm.condition().is_none());
+    }
+    assert_eq!(Some(Value::Integer(42)), env.lookup_variable_value(label("x")));
+    assert_eq!("(1 \"s\")", format!("{:?}", env.lookup_variable_value(label("list")).unwrap()));
+
+    // The procedure is closed over the environment it was defined in
+    let (code, consts) = assemble(vec![
+        ASM::LoadConst(Register(1), Value::Symbol(label("get-x"))),
+        ASM::Lookup(Register(0), Register(1)),
+        ASM::Call(Register(0), 0),
+    ]);
+    vm.load_code(code, consts);
+    vm.run();
+    assert_eq!(Value::Integer(42), vm.load_register(Register(0)));
+}
+
+#[test]
+fn tables_rebuilt() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let dispatch = |switch: ASM, key: &str| {
+        let (code, consts) = assemble(vec![
+            ASM::LoadConst(Register(1), Value::Symbol(label(key))),
+            switch,
+            ASM::Label(label("switch-a")),
+            ASM::LoadConst(Register(0), Value::Integer(10)),
+            ASM::Return,
+            ASM::Label(label("switch-else")),
+            ASM::LoadConst(Register(0), Value::Integer(30)),
+            ASM::Return,
+        ]);
+        // Scramble the table, as if the symbols had been numbered differently when it was made
+        let mut table = consts[1].to_vec();
+        match code[1].instruction() {
+            Instruction::BinarySearch => {
+                let entries: Vec<_> = table.vec[1..].chunks(2).rev().flatten().copied().collect();
+                table.vec.truncate(1);
+                table.vec.extend(entries);
+            }
+            _ => table.vec[1] = Value::Integer(12345),
+        }
+        Box::into_raw(table);
+        let (code, consts) = read(&write(&code, &consts)).unwrap().pop().unwrap();
+        run(code, consts)
+    };
+
+    let names = ["if", "define", "lambda", "begin", "quote"];
+    let entries = || names.iter().map(|n| (label(n), label("switch-a"))).collect::<Vec<_>>();
+    for n in &names {
+        assert_eq!(Value::Integer(10), dispatch(ASM::PerfectHash(Register(1), entries(), label("switch-else")), n));
+        let keys = entries().into_iter().map(|(s, l)| (Value::Symbol(s), l)).collect();
+        assert_eq!(Value::Integer(10), dispatch(ASM::BinarySearch(Register(1), keys, label("switch-else")), n));
+    }
+    assert_eq!(Value::Integer(30), dispatch(ASM::PerfectHash(Register(1), entries(), label("switch-else")), "or"));
+}
+
+#[test]
+fn rejected() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    assert_eq!(Err(LoadError::NotCompiled), read(b"(define x 1)"));
+    let mut bytes = write(&[Operation::Return], &[]);
+    bytes[4] = 0xFF;
+    assert_eq!(Err(LoadError::Version(FORMAT_VERSION & 0xFF00 | 0xFF)), read(&bytes));
+
+    let bytes = write(&[Operation::LoadConst(Register(0), 0), Operation::Return], &[Value::Integer(1)]);
+    assert_eq!(Err(LoadError::Truncated), read(&bytes[..bytes.len() - 1]));
+    // The first form is read before the second is found to be truncated
+    let mut two = bytes.clone();
+    two.extend_from_slice(&bytes[10..bytes.len() - 2]);
+    let mut reader = CodeReader::new(&two).unwrap();
+    assert!(reader.next().unwrap().is_ok());
+    assert_eq!(Some(Err(LoadError::Truncated)), reader.next());
+    assert_eq!(None, reader.next());
+
+    let invalid = |op: Operation| {
+        assert_eq!(Err(LoadError::Operation(op.0)), read(&write(&[op], &[Value::Integer(1)])));
+    };
+    invalid(Operation(200));
+    invalid(Operation::LoadConst(Register(0), 1));
+    invalid(Operation::MakeClosure(Register(0), 0));
+    invalid(Operation::Goto(Some(5)));
+    invalid(Operation(Operation::Move(Register(0), Register(1)).0 | 40 << 16));
+    invalid(Operation::CallPrimitive(Register(0), Instruction::Add));
+
+    let mut bytes = write(&[], &[Value::Integer(1)]);
+    let tag = bytes.len() - 5;
+    bytes[tag] = 99;
+    assert_eq!(Err(LoadError::Constant(99)), read(&bytes));
+}
+
+#[test]
+fn features() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let vector_ref = Operation::VectorRef(Register(0), Register(0), Register(0));
+    let inner = Value::Lambda(Environment::new(), vec![vector_ref, Operation::Return], vec![]);
+    let code = [Operation::MakeClosure(Register(0), 0), Operation::Return];
+    assert_eq!(Features::NONE, Features::of(&[Operation::Return], &[]));
+    assert_eq!(Features::VECTOR_REF, Features::of(&code, &[inner]));
+
+    let bytes = write(&code, &[inner]);
+    assert_eq!(Features::VECTOR_REF, CodeReader::new(&bytes).unwrap().features());
+    assert!(read(&bytes).is_ok());
+
+    // Features this version doesn't know
+    let mut unknown = bytes.clone();
+    unknown[9] = 0x80;
+    assert_eq!(Err(LoadError::Features(0x8000_0000)), read(&unknown));
+    // A feature the header doesn't declare
+    let mut undeclared = bytes.clone();
+    undeclared[6] = 0;
+    assert_eq!(Err(LoadError::Operation(vector_ref.0)), read(&undeclared));
+
+    // Version 1 has no features in its header, and may use any of them
+    let mut old = bytes[..4].to_vec();
+    old.extend_from_slice(&1u16.to_le_bytes());
+    old.extend_from_slice(&bytes[10..]);
+    assert_eq!(Features::ALL, CodeReader::new(&old).unwrap().features());
+    assert!(read(&old).is_ok());
+}
+
+#[test]
+fn portable() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    // The exact bytes, which mustn't depend on how the writing machine represents values
+    let consts = [Value::Integer(-2), Value::Float(1.5), Value::Symbol(label("a")), Value::Nil];
+    let mut expected = b"MVC\0".to_vec();
+    expected.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    expected.extend_from_slice(&[0, 0, 0, 0]);
+    expected.extend_from_slice(&[1, 0, 0, 0]);
+    expected.extend_from_slice(&Operation::Return.0.to_le_bytes());
+    expected.extend_from_slice(&[4, 0, 0, 0]);
+    expected.extend_from_slice(&[3, 0xFE, 0xFF, 0xFF, 0xFF]);
+    expected.push(4);
+    expected.extend_from_slice(&1.5f64.to_bits().to_le_bytes());
+    expected.extend_from_slice(&[5, 1, 0, 0, 0, b'a']);
+    expected.push(1);
+    assert_eq!(expected, write(&[Operation::Return], &consts));
+}
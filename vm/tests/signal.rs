@@ -0,0 +1,27 @@
+extern crate vm;
+
+use vm::*;
+
+#[test]
+fn interrupt_continues() {
+    let mut vm = VM::new();
+    let (code, consts) = assemble(vec![
+        ASM::LoadConst(Register(1), Value::Integer(40)),
+        ASM::LoadConst(Register(2), Value::Integer(2)),
+        ASM::Add(Register(0), Register(1), Register(2)),
+    ]);
+    vm.load_code(code, consts);
+    deliver_signal(Signal::Interrupt);
+    vm.run();
+
+    assert_eq!(1, vm.condition_depth());
+    let condition = vm.condition().unwrap();
+    assert_eq!("Exception in interrupt: received SIGINT", format!("{}", condition));
+    assert_eq!(vec![Restart::Abort, Restart::Retry], condition.restarts());
+
+    // Retrying runs the instruction which was interrupted
+    assert!(vm.invoke_restart(Restart::Retry));
+    vm.run();
+    assert_eq!(0, vm.condition_depth());
+    assert_eq!(Value::Integer(42), vm.load_register(Register(0)));
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "sqlite")]
+
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use vm::*;
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+#[test]
+fn query() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let db = call(&mut vm, "sqlite-open", &[string(":memory:")]);
+    call(&mut vm, "sqlite-exec", &[db, string("CREATE TABLE t (id INTEGER, name TEXT, score REAL, data BLOB);")]);
+    assert_eq!(Value::Integer(1), call(&mut vm, "sqlite-exec", &[
+        db, string("INSERT INTO t VALUES (?, ?, ?, ?)"),
+        Value::Integer(1), string("a"), Value::Float(1.5), Value::Bytevector(vec![1, 2]),
+    ]));
+    call(&mut vm, "sqlite-exec", &[db, string("INSERT INTO t VALUES (?, ?, NULL, NULL)"), Value::Integer(2), string("b")]);
+
+    assert_eq!("(#(1 \"a\" 1.5 #u8(1 2)) #(2 \"b\" () ()))",
+               format!("{}", call(&mut vm, "sqlite-query", &[db, string("SELECT * FROM t ORDER BY id")])));
+    assert_eq!("(((id . 2) (name . \"b\")))",
+               format!("{}", call(&mut vm, "sqlite-query-alist", &[
+                   db, string("SELECT id, name FROM t WHERE id > ?"), Value::Integer(1),
+               ])));
+
+    call(&mut vm, "sqlite-query", &[db, string("SELECT * FROM missing")]);
+    assert_eq!("Exception in sqlite-query: no such table: missing", format!("{}", vm.condition().unwrap()));
+
+    call(&mut vm, "sqlite-close", &[db]);
+    call(&mut vm, "sqlite-query", &[db, string("SELECT 1")]);
+    assert_eq!(format!("Exception in sqlite-query: {} is not an open database", db),
+               format!("{}", vm.condition().unwrap()));
+}
@@ -0,0 +1,46 @@
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use vm::*;
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+#[test]
+fn search() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    let text = string("GET /a 200\nGET /b 404\nPOST /c 404\n");
+    assert_eq!(Value::Integer(18), call(&mut vm, "string-search", &[string("404"), text]));
+    assert_eq!(Value::Integer(30), call(&mut vm, "string-search", &[string("404"), text, Value::Integer(19)]));
+    assert_eq!(Value::False, call(&mut vm, "string-search", &[string("500"), text]));
+    assert_eq!(Value::Integer(0), call(&mut vm, "string-search", &[string("GET"), text]));
+    assert_eq!(Value::Integer(5), call(&mut vm, "string-search", &[string(""), text, Value::Integer(5)]));
+    // Indices count characters, not bytes
+    assert_eq!(Value::Integer(3), call(&mut vm, "string-search", &[string("é!"), string("λλλé!")]));
+    assert_eq!(Value::Integer(3), call(&mut vm, "string-search", &[string("aab"), string("aaaaab")]));
+
+    call(&mut vm, "string-search", &[string("a"), string("abc"), Value::Integer(4)]);
+    assert_eq!("Exception in string-search: 4 is not a valid index", format!("{}", vm.condition().unwrap()));
+    call(&mut vm, "string-search", &[Value::Integer(1), string("abc")]);
+    assert_eq!("Exception in string-search: 1 is not a string", format!("{}", vm.condition().unwrap()));
+}
+
+#[test]
+fn replace_all() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    vm.assign_environment(init_env());
+    assert_eq!("\"a-b-c\"", format!("{}", call(&mut vm, "string-replace-all", &[string("a, b, c"), string(", "), string("-")])));
+    // Matches don't overlap
+    assert_eq!("\"xa\"", format!("{}", call(&mut vm, "string-replace-all", &[string("aaa"), string("aa"), string("x")])));
+    assert_eq!("\"λ\"", format!("{}", call(&mut vm, "string-replace-all", &[string("λ"), string("x"), string("y")])));
+    assert_eq!("\"\"", format!("{}", call(&mut vm, "string-replace-all", &[string("abab"), string("ab"), string("")])));
+
+    call(&mut vm, "string-replace-all", &[string("abc"), string(""), string("x")]);
+    assert_eq!("Exception in string-replace-all: the pattern is empty", format!("{}", vm.condition().unwrap()));
+}
@@ -0,0 +1,80 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::HEAP;
+use string_interner::get_symbol;
+use vm::*;
+
+fn label(name: &str) -> string_interner::Symbol {
+    get_symbol(name.to_string())
+}
+
+// Load `key`, dispatch with `switch` and return the constant stored by the branch taken.
+fn dispatch(key: Value, switch: ASM) -> Value {
+    let code = vec![
+        ASM::LoadConst(Register(1), key),
+        switch,
+        ASM::Label(label("switch-a")),
+        ASM::LoadConst(Register(0), Value::Integer(10)),
+        ASM::Return,
+        ASM::Label(label("switch-b")),
+        ASM::LoadConst(Register(0), Value::Integer(20)),
+        ASM::Return,
+        ASM::Label(label("switch-else")),
+        ASM::LoadConst(Register(0), Value::Integer(30)),
+        ASM::Return,
+    ];
+    let mut vm = VM::new();
+    let (code, consts) = assemble(code);
+    vm.load_code(code, consts);
+    vm.run();
+    vm.load_register(Register(0))
+}
+
+#[test]
+fn jump_table() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let table = || ASM::JumpTable(Register(1), 3,
+                                  vec![label("switch-a"), label("switch-else"), label("switch-b")],
+                                  label("switch-else"));
+    assert_eq!(Value::Integer(10), dispatch(Value::Integer(3), table()));
+    assert_eq!(Value::Integer(30), dispatch(Value::Integer(4), table()));
+    assert_eq!(Value::Integer(20), dispatch(Value::Integer(5), table()));
+    assert_eq!(Value::Integer(30), dispatch(Value::Integer(2), table()));
+    assert_eq!(Value::Integer(30), dispatch(Value::Integer(6), table()));
+    assert_eq!(Value::Integer(30), dispatch(Value::Bool(true), table()));
+}
+
+#[test]
+fn binary_search() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let table = || ASM::BinarySearch(Register(1), vec![
+        (Value::Integer(1000), label("switch-b")),
+        (Value::Symbol(label("apple")), label("switch-a")),
+        (Value::Integer(-7), label("switch-a")),
+        (Value::Integer(1), label("switch-b")),
+    ], label("switch-else"));
+    assert_eq!(Value::Integer(10), dispatch(Value::Symbol(label("apple")), table()));
+    assert_eq!(Value::Integer(10), dispatch(Value::Integer(-7), table()));
+    assert_eq!(Value::Integer(20), dispatch(Value::Integer(1), table()));
+    assert_eq!(Value::Integer(20), dispatch(Value::Integer(1000), table()));
+    assert_eq!(Value::Integer(30), dispatch(Value::Integer(2), table()));
+    assert_eq!(Value::Integer(30), dispatch(Value::Symbol(label("pear")), table()));
+}
+
+#[test]
+fn perfect_hash() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let names = ["if", "define", "lambda", "begin", "quote", "set!", "let", "cond", "case", "and"];
+    let table = || ASM::PerfectHash(Register(1), names.iter().enumerate()
+        .map(|(i, n)| (label(n), if i % 2 == 0 { label("switch-a") } else { label("switch-b") }))
+        .collect(), label("switch-else"));
+    for (i, n) in names.iter().enumerate() {
+        let expected = if i % 2 == 0 { 10 } else { 20 };
+        assert_eq!(Value::Integer(expected), dispatch(Value::Symbol(label(n)), table()));
+    }
+    assert_eq!(Value::Integer(30), dispatch(Value::Symbol(label("or")), table()));
+    assert_eq!(Value::Integer(30), dispatch(Value::Integer(3), table()));
+}
@@ -1,7 +1,11 @@
+extern crate num_bigint;
 extern crate vm;
 
+use num_bigint::BigInt;
 use vm::*;
 
+use std::collections::HashMap;
+
 #[test]
 fn string() {
     let s = String::from("abc");
@@ -9,3 +13,59 @@ fn string() {
     let s = v.to_string();
     assert_eq!(&s.str, "abc");
 }
+
+#[test]
+fn non_finite_float() {
+    for &f in &[std::f64::INFINITY, std::f64::NEG_INFINITY, std::f64::NAN, -std::f64::NAN] {
+        let v = Value::Float(f);
+        assert!(v.is_float());
+        assert!(!v.is_integer() && !v.is_void() && !v.is_nil() && !v.is_lambda());
+    }
+    assert_eq!(std::f64::INFINITY, Value::Float(std::f64::INFINITY).to_float());
+    assert_eq!("+inf.0", format!("{}", Value::Float(std::f64::INFINITY)));
+    assert_eq!("-inf.0", format!("{}", Value::Float(std::f64::NEG_INFINITY)));
+    assert_eq!("+nan.0", format!("{}", Value::Float(-std::f64::NAN)));
+}
+
+#[test]
+fn float_text() {
+    assert_eq!("1.0", format!("{}", Value::Float(1.0)));
+    assert_eq!("-2.5", format!("{}", Value::Float(-2.5)));
+    assert_eq!("0.30000000000000004", format!("{}", Value::Float(0.1 + 0.2)));
+    assert_eq!("1e100", format!("{}", Value::Float(1e100)));
+}
+
+#[test]
+fn integer() {
+    assert_eq!(Value::Integer(i32::MAX), Value::integer(BigInt::from(i32::MAX)));
+    assert_eq!(Value::Integer(i32::MIN), Value::integer(BigInt::from(i32::MIN)));
+    let big = Value::integer(BigInt::from(i32::MAX as i64 + 1));
+    assert!(big.is_bigint() && !big.is_integer() && !big.is_float());
+    assert_eq!("2147483648", format!("{}", big));
+    assert_eq!("-2147483649", format!("{}", Value::integer(BigInt::from(i32::MIN as i64 - 1))));
+}
+
+#[test]
+fn conversions() {
+    assert_eq!(Ok(7), i64::from_value(7i64.into_value()));
+    let big = (1i64 << 40).into_value();
+    assert!(big.is_bigint());
+    assert_eq!(Ok(1 << 40), i64::from_value(big));
+    assert_eq!(Err("1099511627776 is out of range".to_string()), i32::from_value(big));
+    assert_eq!(Ok(2.0), f64::from_value(Value::Integer(2)));
+    assert_eq!(Ok(true), bool::from_value(true.into_value()));
+    assert_eq!(Err("1 is not a boolean".to_string()), bool::from_value(Value::Integer(1)));
+    assert_eq!(Ok("abc".to_string()), String::from_value("abc".into_value()));
+
+    let v = vec![vec![1i32, 2], vec![]].into_value();
+    assert_eq!("((1 2) ())", format!("{}", v));
+    assert_eq!(Ok(vec![vec![1, 2], vec![]]), Vec::<Vec<i32>>::from_value(v));
+    assert_eq!(Err("() is not an integer".to_string()), Vec::<i32>::from_value(vec![Value::Nil].into_value()));
+    assert_eq!(Err("1 is not a list".to_string()), Vec::<i32>::from_value(Value::Integer(1)));
+
+    let mut map: HashMap<i32, Option<f64>> = HashMap::new();
+    map.insert(1, Some(2.5));
+    map.insert(2, None);
+    assert_eq!(Ok(map.clone()), HashMap::from_value(map.into_value()));
+    assert_eq!(Ok(None), Option::<String>::from_value(Value::False));
+}
@@ -0,0 +1,85 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+use std::collections::HashMap;
+
+fn symbol(s: &str) -> Value {
+    Value::Symbol(get_symbol(s.to_string()))
+}
+
+fn list(values: &[Value]) -> Value {
+    values.iter().rev().fold(Value::Nil, |l, &v| Value::Pair(v, l))
+}
+
+#[test]
+fn structural_hash() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    // The hash of the encoding of `()`, a single byte, is the same in every run
+    assert_eq!(Ok(0xaf63bc4c8601b62c), value_hash(Value::Nil));
+
+    let a = list(&[Value::Integer(1), Value::String("a".to_string()), symbol("b"), Value::Float(1.5)]);
+    let b = list(&[Value::Integer(1), Value::String("a".to_string()), symbol("b"), Value::Float(1.5)]);
+    assert_eq!(value_hash(a), value_hash(b));
+    assert_ne!(value_hash(a), value_hash(list(&[Value::Integer(1), Value::String("a".to_string())])));
+    assert_ne!(value_hash(Value::Integer(1)), value_hash(Value::Float(1.0)));
+    assert_ne!(value_hash(Value::String("b".to_string())), value_hash(symbol("b")));
+
+    // Sharing doesn't matter, only structure
+    let shared = Value::Pair(Value::Integer(2), Value::Nil);
+    let copy = Value::Pair(Value::Integer(2), Value::Nil);
+    assert_eq!(value_hash(list(&[shared, shared])), value_hash(list(&[shared, copy])));
+
+    // Nor does the order entries were added to a hash table
+    let mut m1 = HashMap::new();
+    let mut m2 = HashMap::new();
+    for i in 0..20 {
+        m1.insert(Value::Integer(i), symbol(&format!("v{}", i)));
+        m2.insert(Value::Integer(19 - i), symbol(&format!("v{}", 19 - i)));
+    }
+    assert_eq!(value_hash(Value::HashMap(m1)), value_hash(Value::HashMap(m2)));
+
+    // Vectors and lists with the same elements differ
+    let v = Value::Vec(vec![Value::Integer(1), Value::Integer(2)]);
+    assert_ne!(value_hash(v), value_hash(list(&[Value::Integer(1), Value::Integer(2)])));
+}
+
+#[test]
+fn cycles() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let cycle = list(&[Value::Integer(1), Value::Integer(2)]);
+    cycle.cdr().set_cdr(cycle);
+    let other = list(&[Value::Integer(1), Value::Integer(2)]);
+    other.cdr().set_cdr(other);
+    assert_eq!(value_hash(cycle), value_hash(other));
+
+    // A cycle through the second pair rather than the first
+    let later = list(&[Value::Integer(1), Value::Integer(2)]);
+    later.cdr().set_cdr(later.cdr());
+    assert!(value_hash(later).is_ok());
+    assert_ne!(value_hash(cycle), value_hash(later));
+
+    let v = Value::Vec(vec![Value::Nil]);
+    let mut items = v.to_vec();
+    items.vec[0] = v;
+    Box::into_raw(items);
+    assert!(value_hash(v).is_ok());
+}
+
+#[test]
+fn value_hash_procedure() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    assert_eq!("12638152016183539244", format!("{}", call(&mut vm, "value-hash", &[Value::Nil])));
+    let car = env.lookup_variable_value(get_symbol("car".to_string())).unwrap();
+    assert_eq!(Err(car), value_hash(car));
+    call(&mut vm, "value-hash", &[car]);
+    assert!(format!("{}", vm.condition().unwrap()).ends_with("can't be hashed"));
+}
@@ -0,0 +1,41 @@
+extern crate string_interner;
+extern crate vm;
+
+mod common;
+
+use common::{call, HEAP};
+use string_interner::get_symbol;
+use vm::*;
+
+// Parse `xml`, returning the SXML and the result of writing it back out.
+fn round_trip(xml: &str) -> (String, String) {
+    let mut vm = VM::new();
+    let env = init_env();
+    vm.assign_environment(env.clone());
+    let sxml = call(&mut vm, "xml->sxml", &[Value::String(xml.to_string())]);
+    // Keep the document alive between calls
+    env.define_variable(get_symbol("document".to_string()), sxml);
+    let xml = call(&mut vm, "sxml->xml", &[sxml]).to_string();
+    let text = xml.str.clone();
+    Box::into_raw(xml);
+    (format!("{}", sxml), text)
+}
+
+#[test]
+fn xml() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let (sxml, xml) = round_trip("<?xml version=\"1.0\"?>\n<!-- note -->\n\
+                                  <doc lang='en'><item id=\"1\">a &amp; b</item><empty/><![CDATA[<raw>]]></doc>");
+    assert_eq!("(*TOP* (*PI* xml \"version=\\\"1.0\\\"\") (*COMMENT* \" note \") \
+                (doc (@ (lang \"en\")) (item (@ (id \"1\")) \"a & b\") (empty) \"<raw>\"))", sxml);
+    assert_eq!("<?xml version=\"1.0\"?><!-- note -->\
+                <doc lang=\"en\"><item id=\"1\">a &amp; b</item><empty/>&lt;raw&gt;</doc>", xml);
+}
+
+#[test]
+fn html() {
+    let _heap = HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    let (sxml, xml) = round_trip("<!DOCTYPE html><HTML><body><p>one<br>two<p>three &#x263A;</i></body></html>");
+    assert_eq!("(*TOP* (HTML (body (p \"one\" (br) \"two\" (p \"three ☺\")))))", sxml);
+    assert_eq!("<HTML><body><p>one<br>two<p>three ☺</p></p></body></HTML>", xml);
+}